@@ -0,0 +1,251 @@
+//! Checks of edited AI patterns against the layouts of their commands and the
+//! tables of a client
+//!
+//! The server reads the operands of a condition or action with a fixed layout
+//! for its opcode, so an unknown opcode or operands of another size make it
+//! read garbage or crash. These layouts are built into roselib, see
+//! [`AiCondition::operand_size`] and [`AiAction::operand_size`], and commands
+//! that don't match them are read as `Raw`.
+//!
+//! The NPCs, skills and items referenced by conditions and actions are also
+//! checked against the rows of their STB, and the quest triggers run by
+//! actions against the triggers of the QSD files of a client.
+use std::collections::{HashMap, HashSet};
+
+use roselib::files::aip::{AiAction, AiCondition};
+use roselib::files::{AIP, STB};
+
+use crate::client::Client;
+use crate::qsd_check::{
+    check_item, check_row, quest_triggers, ITEM_TABLES, LIST_NPC_PATH, LIST_SKILL_PATH,
+};
+
+/// Conditions and actions of an AI pattern with an unknown opcode or operands
+/// of another size than their layout
+pub fn check_layouts(aip: &AIP) -> Vec<String> {
+    check_commands(
+        aip,
+        |condition| match condition {
+            AiCondition::Raw(command) => AiCondition::decode(command)
+                .err()
+                .map(|e| e.to_string())
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        },
+        |action| match action {
+            AiAction::Raw(command) => AiAction::decode(command)
+                .err()
+                .map(|e| e.to_string())
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        },
+    )
+}
+
+/// Number of rows of the STBs referenced by AI patterns and names of the quest
+/// triggers, for the ones in the client
+#[derive(Debug, Default, PartialEq)]
+pub struct AiTables {
+    pub npcs: Option<usize>,
+    pub skills: Option<usize>,
+    /// Rows of the STB of each item type
+    pub items: HashMap<u32, usize>,
+    /// Triggers of every QSD of the client, if it has any
+    pub triggers: Option<HashSet<String>>,
+}
+
+impl AiTables {
+    pub fn from_client(client: &Client) -> AiTables {
+        let rows = |path: &str| client.read_file::<STB>(path).ok().map(|stb| stb.rows());
+        AiTables {
+            npcs: rows(LIST_NPC_PATH),
+            skills: rows(LIST_SKILL_PATH),
+            items: ITEM_TABLES
+                .iter()
+                .filter_map(|&(item_type, path)| Some((item_type, rows(path)?)))
+                .collect(),
+            triggers: quest_triggers(client),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.npcs.is_none()
+            && self.skills.is_none()
+            && self.items.is_empty()
+            && self.triggers.is_none()
+    }
+
+    /// NPCs, skills and items referenced by an AI pattern that aren't rows of
+    /// their STB and quest triggers missing from the client, skipping
+    /// references to STBs missing from the client
+    pub fn check(&self, aip: &AIP) -> Vec<String> {
+        let npc = |npc: i64| check_row("NPC", npc, self.npcs, LIST_NPC_PATH);
+        check_commands(
+            aip,
+            |condition| match *condition {
+                AiCondition::SelectNpc { npc: n } => npc(i64::from(n)).into_iter().collect(),
+                _ => Vec::new(),
+            },
+            |action| match action {
+                AiAction::Transform { npc: n }
+                | AiAction::Summon { npc: n }
+                | AiAction::CallMonsters { npc: n, .. }
+                | AiAction::SpawnMonster { npc: n, .. }
+                | AiAction::SummonWithOwner { npc: n, .. }
+                | AiAction::SpawnMonsterWithOwner { npc: n, .. } => {
+                    npc(i64::from(*n)).into_iter().collect()
+                }
+                AiAction::Skill { skill, .. } => {
+                    check_row("skill", i64::from(*skill), self.skills, LIST_SKILL_PATH)
+                        .into_iter()
+                        .collect()
+                }
+                AiAction::DropItem { items, .. } => items
+                    .iter()
+                    .filter(|&&item| item != 0)
+                    .filter_map(|&item| check_item(i64::from(item), &self.items))
+                    .collect(),
+                AiAction::GiveItem { item, .. } => check_item(i64::from(*item), &self.items)
+                    .into_iter()
+                    .collect(),
+                AiAction::QuestTrigger { trigger } => match &self.triggers {
+                    Some(triggers) if !triggers.contains(trigger) => {
+                        vec![format!("quest trigger {} isn't in the QSD files", trigger)]
+                    }
+                    _ => Vec::new(),
+                },
+                _ => Vec::new(),
+            },
+        )
+    }
+}
+
+/// Problems of every condition and action of an AI pattern, prefixed with
+/// their trigger, event and position
+fn check_commands<C, A>(aip: &AIP, check_condition: C, check_action: A) -> Vec<String>
+where
+    C: Fn(&AiCondition) -> Vec<String>,
+    A: Fn(&AiAction) -> Vec<String>,
+{
+    let mut problems = Vec::new();
+    for trigger in &aip.triggers {
+        for event in &trigger.events {
+            let commands = event
+                .conditions
+                .iter()
+                .map(|c| ("condition", check_condition(c)))
+                .enumerate()
+                .chain(
+                    event
+                        .actions
+                        .iter()
+                        .map(|a| ("action", check_action(a)))
+                        .enumerate(),
+                );
+            for (idx, (kind, command_problems)) in commands {
+                for problem in command_problems {
+                    problems.push(format!(
+                        "{} / {} / {} {}: {}",
+                        trigger.name, event.name, kind, idx, problem
+                    ));
+                }
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::aip::{AiCommand, AiEvent, AiTrigger};
+    use roselib::io::RoseFile;
+
+    fn aip(conditions: Vec<AiCondition>, actions: Vec<AiAction>) -> AIP {
+        let mut aip = AIP::new();
+        aip.triggers.push(AiTrigger {
+            name: String::from("On Idle"),
+            events: vec![AiEvent {
+                name: String::from("Wander"),
+                conditions,
+                actions,
+            }],
+        });
+        aip
+    }
+
+    fn raw(opcode: u32, size: usize) -> AiCommand {
+        AiCommand {
+            opcode,
+            data: vec![0; size],
+        }
+    }
+
+    #[test]
+    fn test_check_layouts() {
+        assert!(check_layouts(&aip(
+            vec![AiCondition::Random { percent: 50 }],
+            vec![AiAction::Stop]
+        ))
+        .is_empty());
+        // Known opcodes with padding that isn't zero are kept raw but valid
+        let padded = AiCommand {
+            opcode: 0x0400_0008,
+            data: vec![50, 0xCC, 0xCC, 0xCC],
+        };
+        assert_eq!(
+            check_layouts(&aip(
+                vec![
+                    AiCondition::from_command(&padded),
+                    AiCondition::from_command(&raw(0x0400_0008, 8))
+                ],
+                vec![
+                    AiAction::Stop,
+                    AiAction::from_command(&raw(0x0400_0008, 4))
+                ]
+            )),
+            [
+                "On Idle / Wander / condition 1: condition 0x04000008 has 8 bytes of operands instead of 4",
+                "On Idle / Wander / action 1: unknown action opcode 0x04000008"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_tables() {
+        let mut tables = AiTables::default();
+        assert!(tables.is_empty());
+        tables.npcs = Some(100);
+        tables.items = vec![(10, 50)].into_iter().collect();
+        tables.triggers = Some(vec![String::from("wolf-01")].into_iter().collect());
+
+        let actions = vec![
+            AiAction::Summon { npc: 20 },
+            AiAction::Transform { npc: 150 },
+            AiAction::Skill {
+                target: 0,
+                skill: 5000,
+                motion: 0,
+            },
+            AiAction::DropItem {
+                items: [10001, 0, 10060, 0, 0],
+                to_owner: 0,
+            },
+            AiAction::QuestTrigger {
+                trigger: String::from("wolf-02"),
+            },
+        ];
+        assert_eq!(
+            tables.check(&aip(vec![AiCondition::SelectNpc { npc: 0 }], actions)),
+            [
+                "On Idle / Wander / condition 0: NPC 0 isn't a row of LIST_NPC.STB, which has 100 rows",
+                "On Idle / Wander / action 1: NPC 150 isn't a row of LIST_NPC.STB, which has 100 rows",
+                "On Idle / Wander / action 3: item 60 isn't a row of LIST_USEITEM.STB, which has 50 rows (10060)",
+                "On Idle / Wander / action 4: quest trigger wolf-02 isn't in the QSD files"
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Axes and units of exported models
+//!
+//! ROSE is Z-up and right-handed, with map and motion positions in
+//! centimeters. Exports are written like glTF, Y-up and right-handed in
+//! meters, and can be converted to the conventions of other engines: Y-up and
+//! left-handed for Unity, Z-up and left-handed for Unreal or Z-up and
+//! right-handed for Blender. `scale` multiplies meters, 100 exports
+//! centimeters.
+//!
+//! Left-handed axes mirror the models, which turns their faces inside out
+//! unless the winding of the faces is reversed too.
+use std::fmt::Write;
+
+use failure::{bail, Error};
+
+/// Axis pointing up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpAxis {
+    Y,
+    Z,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handedness {
+    Right,
+    Left,
+}
+
+/// Axes and scale of exported positions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Axes {
+    pub up: UpAxis,
+    pub handedness: Handedness,
+    pub scale: f32,
+}
+
+impl Default for Axes {
+    fn default() -> Axes {
+        Axes {
+            up: UpAxis::Y,
+            handedness: Handedness::Right,
+            scale: 1.0,
+        }
+    }
+}
+
+impl UpAxis {
+    pub fn from_name(name: &str) -> Result<UpAxis, Error> {
+        match name.to_lowercase().as_str() {
+            "y" => Ok(UpAxis::Y),
+            "z" => Ok(UpAxis::Z),
+            _ => bail!("Invalid up axis: {}", name),
+        }
+    }
+}
+
+impl Handedness {
+    pub fn from_name(name: &str) -> Result<Handedness, Error> {
+        match name.to_lowercase().as_str() {
+            "right" => Ok(Handedness::Right),
+            "left" => Ok(Handedness::Left),
+            _ => bail!("Invalid handedness: {}", name),
+        }
+    }
+}
+
+impl Axes {
+    /// Whether these are the glTF axes, which need no conversion
+    pub fn is_default(&self) -> bool {
+        *self == Axes::default()
+    }
+
+    /// Whether the conversion mirrors models, reversing their winding
+    pub fn mirrors(&self) -> bool {
+        self.handedness == Handedness::Left
+    }
+
+    /// Position in these axes of a Y-up, right-handed position in meters
+    pub fn position(&self, p: [f32; 3]) -> [f32; 3] {
+        let [x, y, z] = self.direction(p);
+        [x * self.scale, y * self.scale, z * self.scale]
+    }
+
+    /// Direction in these axes of a Y-up, right-handed direction, such as a
+    /// normal
+    pub fn direction(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        // Adding zero turns -0.0 into 0.0
+        match (self.up, self.handedness) {
+            (UpAxis::Y, Handedness::Right) => [x, y, z],
+            (UpAxis::Y, Handedness::Left) => [x, y, -z + 0.0],
+            (UpAxis::Z, Handedness::Right) => [x, -z + 0.0, y],
+            (UpAxis::Z, Handedness::Left) => [x, z, y],
+        }
+    }
+
+    /// Column-major matrix of the conversion
+    pub fn matrix(&self) -> [f32; 16] {
+        let mut matrix = [0.0; 16];
+        let columns = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        for (i, column) in columns.iter().enumerate() {
+            matrix[i * 4..i * 4 + 3].copy_from_slice(&self.position(*column));
+        }
+        matrix[15] = 1.0;
+        matrix
+    }
+
+    /// Convert an OBJ written in glTF axes, its vertices and normals, and
+    /// reverse the winding of its faces if the axes mirror them
+    pub fn convert_obj(&self, obj: &str) -> String {
+        if self.is_default() {
+            return obj.to_string();
+        }
+        let mut converted = String::with_capacity(obj.len());
+        for line in obj.lines() {
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let values: Vec<&str> = words.collect();
+            let vector = || -> Option<[f32; 3]> {
+                match values.as_slice() {
+                    [x, y, z] => Some([x.parse().ok()?, y.parse().ok()?, z.parse().ok()?]),
+                    _ => None,
+                }
+            };
+            match (keyword, vector()) {
+                ("v", Some(p)) => {
+                    let [x, y, z] = self.position(p);
+                    let _ = writeln!(converted, "v {} {} {}", x, y, z);
+                }
+                ("vn", Some(n)) => {
+                    let [x, y, z] = self.direction(n);
+                    let _ = writeln!(converted, "vn {} {} {}", x, y, z);
+                }
+                ("f", _) if self.mirrors() => {
+                    let corners: Vec<&str> = values.iter().rev().copied().collect();
+                    let _ = writeln!(converted, "f {}", corners.join(" "));
+                }
+                _ => {
+                    converted.push_str(line);
+                    converted.push('\n');
+                }
+            }
+        }
+        converted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_axes() {
+        let axes = Axes::default();
+        assert!(axes.is_default());
+        assert!(!axes.mirrors());
+        assert_eq!(axes.position([1.0, 2.0, 3.0]), [1.0, 2.0, 3.0]);
+
+        // Back to the ROSE axes in centimeters
+        let rose = Axes {
+            up: UpAxis::Z,
+            handedness: Handedness::Right,
+            scale: 100.0,
+        };
+        assert_eq!(rose.position([1.0, 2.0, -3.0]), [100.0, 300.0, 200.0]);
+        assert_eq!(rose.direction([0.0, 1.0, 0.0]), [0.0, 0.0, 1.0]);
+
+        let unity = Axes {
+            handedness: Handedness::Left,
+            ..Axes::default()
+        };
+        assert!(unity.mirrors());
+        assert_eq!(unity.position([1.0, 2.0, 3.0]), [1.0, 2.0, -3.0]);
+
+        let unreal = Axes {
+            up: UpAxis::Z,
+            handedness: Handedness::Left,
+            scale: 100.0,
+        };
+        assert_eq!(
+            unreal.matrix(),
+            [
+                100.0, 0.0, 0.0, 0.0, 0.0, 0.0, 100.0, 0.0, 0.0, 100.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+                1.0
+            ]
+        );
+
+        let obj = "o BOX\nv 1 2 3\nvn 0 1 0\nf 1//1 2//2 3//3\n";
+        assert_eq!(
+            unreal.convert_obj(obj),
+            "o BOX\nv 100 300 200\nvn 0 0 1\nf 3//3 2//2 1//1\n"
+        );
+        assert_eq!(axes.convert_obj(obj), obj);
+
+        assert_eq!(UpAxis::from_name("Z").unwrap(), UpAxis::Z);
+        assert!(Handedness::from_name("up").is_err());
+    }
+}
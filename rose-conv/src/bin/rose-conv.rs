@@ -1,30 +1,114 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::env;
 use std::f32;
 use std::fs;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufWriter, Cursor, Read, Write};
 use std::iter;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::process::exit;
+use std::str;
+use std::sync::mpsc::channel;
+use std::time::{Duration, Instant, SystemTime};
 
-use clap::{crate_authors, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
-use failure::{bail, Error};
-use image::io::Reader as ImageReader;
+use clap::{crate_authors, crate_name, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+use failure::{bail, format_err, Error};
+use log::{debug, error, info, trace, warn};
 use image::{GrayImage, ImageBuffer, RgbaImage};
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+use roselib::files::stl::StringTableLanguage;
 use roselib::files::zon::ZoneTileRotation;
 use roselib::files::*;
-use roselib::io::{RoseFile, RoseReader};
+use roselib::io::{set_string_encoding, set_string_normalization, Normalization};
+use roselib::io::{RoseFile, RoseReader, RoseWriter, StringEncoding};
 
-use rose_conv::{FromCsv, ToCsv};
+use rose_conv::aip_check::{self, AiTables};
+use rose_conv::axes::{Axes, Handedness, UpAxis};
+use rose_conv::bounds;
+use rose_conv::cache::{self, Cache};
+use rose_conv::client::Client;
+use rose_conv::client_diff::ClientDiff;
+use rose_conv::client_manifest::{ClientManifest, CLIENT_MANIFEST_FILE};
+use rose_conv::codegen;
+use rose_conv::collision::{CollisionObj, CollisionShape};
+use rose_conv::config::{Config, CONFIG_FILE};
+use rose_conv::csv_dialect::CsvDialect;
+use rose_conv::doctor;
+use rose_conv::encoding_report::{self, EncodingReport};
+use rose_conv::file_info;
+use rose_conv::gltf;
+use rose_conv::godot::{self, GodotScene};
+use rose_conv::grep::{self, GrepMatch, Pattern};
+use rose_conv::dds::{self, DxtFormat};
+use rose_conv::iconsheet;
+use rose_conv::ifo_instances::{self, InstanceKind};
+use rose_conv::image_format::{read_image, read_image_dimensions, ImageFormat, IMAGE_FORMAT_NAMES};
+use rose_conv::lightmap::{self, LightmapEntry, PartLightmap};
+use rose_conv::tsi_atlas::{self, AtlasFormat, AtlasSheet, ATLAS_FORMAT_NAMES};
+use rose_conv::manifest::{Manifest, MANIFEST_FILE};
+use rose_conv::map_spawns::Spawns;
+use rose_conv::minimap::{self, Minimap};
+use rose_conv::naming::NameFormat;
+use rose_conv::navmesh::{self, Navmesh};
+use rose_conv::normal_map;
+use rose_conv::patch::{Patch, PatchOp};
+use rose_conv::qsd_check::{self, QuestTables};
+use rose_conv::references::{self, DependencyGraph, ReferenceGraph};
+use rose_conv::report::Report;
+use rose_conv::schema::{schema, SCHEMA_TYPES};
+use rose_conv::script;
+use rose_conv::splatmap::{SplatChannel, Splatmap};
+use rose_conv::stats::{self, ClientStats, FileSize};
+use rose_conv::stb_csv;
+use rose_conv::stb_diff::StbDiff;
+use rose_conv::stb_merge::StbMerge;
+use rose_conv::stb_join::JoinSpec;
+use rose_conv::stb_json::TypedStb;
+use rose_conv::stb_schema::StbSchema;
+use rose_conv::stb_sqlite::StbDatabase;
+use rose_conv::stl_csv;
+use rose_conv::stl_diff::StlDiff;
+use rose_conv::stl_duplicates::DuplicateReport;
+use rose_conv::stl_po::{self, PoFile};
+use rose_conv::stl_report::StlReport;
+use rose_conv::terrain::{self, Terrain, TerrainLayer, UnityTerrain, UnrealLandscape, UnrealLayer};
+use rose_conv::tile_pyramid::TilePyramid;
+use rose_conv::validate;
+use rose_conv::vfs;
+use rose_conv::warp_graph::WarpGraph;
+use rose_conv::water;
+use rose_conv::world_atlas::{self, WorldAtlas};
+use rose_conv::xlsx::{Sheet, Workbook};
+use rose_conv::zmd_viz;
+use rose_conv::zmo_events;
+use rose_conv::zmo_resample;
+use rose_conv::zmo_root_motion;
+use rose_conv::zms_merge;
+use rose_conv::zms_normals;
+use rose_conv::zms_obj;
+use rose_conv::zms_optimize;
+use rose_conv::zms_simplify;
+use rose_conv::zms_skin;
+use rose_conv::zms_weld;
+use rose_conv::zone_manifest::{BlockSummary, ZoneManifest, ZoneScenes};
+use rose_conv::zsc_bundle::{self, BundleFile, BundleManifest, BUNDLE_MANIFEST_FILE};
+use rose_conv::zsc_materials::MaterialReport;
+use rose_conv::FromCsv;
 use rose_conv::{FromJson, ToJson};
+use rose_conv::{FromCbor, FromMsgpack, ToCbor, ToMsgpack};
+use rose_conv::{FromYaml, ToYaml};
 
-const SERIALIZE_VALUES: [&'static str; 14] = [
-    "him", "idx", "ifo", "lit", "stb", "stl", "wstb", "til", "tsi", "zmd", "zmo", "zms", "zon",
-    "zsc",
+const SERIALIZE_VALUES: [&'static str; 16] = [
+    "aip", "him", "idx", "ifo", "lit", "qsd", "stb", "stl", "wstb", "til", "tsi", "zmd", "zmo",
+    "zms", "zon", "zsc",
 ];
 
-const DESERIALIZE_VALUES: [&'static str; 5] = ["idx", "lit", "stb", "stl", "zsc"];
+const DESERIALIZE_VALUES: [&'static str; 9] =
+    ["aip", "idx", "lit", "qsd", "stb", "stl", "wstb", "zms", "zsc"];
 
 #[derive(Debug, Deserialize, Serialize)]
 struct TilemapTile {
@@ -33,6 +117,13 @@ struct TilemapTile {
     rotation: ZoneTileRotation,
 }
 
+#[derive(Debug, Serialize)]
+struct SplatmapFile {
+    tile_size: u32,
+    images: Vec<String>,
+    channels: Vec<SplatChannel>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct TilemapFile {
     textures: Vec<String>,
@@ -40,8 +131,310 @@ struct TilemapFile {
     tilemap: Vec<Vec<i32>>,
 }
 
+/// Structured format used for the JSON-capable file types
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Format {
+    #[default]
+    Json,
+    Yaml,
+    Msgpack,
+    Cbor,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "msgpack" => Some(Format::Msgpack),
+            "cbor" => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Msgpack => "msgpack",
+            Format::Cbor => "cbor",
+        }
+    }
+
+    /// Serialize a ROSE file, `pretty` only applies to JSON
+    fn serialize<T>(self, file: &T, pretty: bool) -> Result<Vec<u8>, Error>
+    where
+        T: ToJson + ToYaml + ToMsgpack + ToCbor + Serialize,
+    {
+        match self {
+            Format::Json if pretty => Ok(file.to_json()?.into_bytes()),
+            Format::Json => Ok(file.to_json_compact()?.into_bytes()),
+            Format::Yaml => Ok(file.to_yaml()?.into_bytes()),
+            Format::Msgpack => file.to_msgpack(),
+            Format::Cbor => file.to_cbor(),
+        }
+    }
+
+    fn deserialize<T>(self, data: &[u8]) -> Result<T, Error>
+    where
+        T: FromJson + FromYaml + FromMsgpack + FromCbor + DeserializeOwned,
+    {
+        match self {
+            Format::Json => T::from_json(str::from_utf8(data)?),
+            Format::Yaml => T::from_yaml(str::from_utf8(data)?),
+            Format::Msgpack => T::from_msgpack(data),
+            Format::Cbor => T::from_cbor(data),
+        }
+    }
+}
+
+/// Options shared by all conversions
+#[derive(Debug, Default)]
+struct ConvertOptions {
+    /// Only report what would be read and written
+    dry_run: bool,
+    /// Format of the JSON-capable file types
+    format: Format,
+    /// Pretty-print JSON output
+    pretty: bool,
+    /// Serialize STB files as typed JSON instead of CSV
+    stb_json: bool,
+    /// Serialize STB and STL files as XLSX workbooks instead of CSV
+    xlsx: bool,
+    /// Serialize QSD and AIP files as scripts instead of JSON
+    script: bool,
+    /// Directory of the STB column schemas
+    schema_dir: PathBuf,
+    /// Write the STB headers and root row to CSV files
+    headers: bool,
+    /// Dialect of STB and STL CSV files
+    csv: CsvDialect,
+    /// Languages of STL files to serialize, every language if empty
+    languages: Vec<StringTableLanguage>,
+    /// Single STL language to serialize or patch as compact CSV
+    language: Option<StringTableLanguage>,
+    /// Stop a batch at the first failed conversion
+    fail_fast: bool,
+    /// Template for output file names
+    name_format: Option<NameFormat>,
+    /// Overwrite existing output files
+    force: bool,
+    /// Skip conversions whose output file already exists
+    skip_existing: bool,
+    /// Copy existing output files to `<file>.bak` before overwriting them
+    backup: bool,
+    /// Directory of the incremental conversion cache, only set on
+    /// incremental runs
+    cache_dir: Option<PathBuf>,
+    /// Recompute ZMS normals, smoothing faces within this angle in degrees
+    smoothing_angle: Option<f32>,
+    /// Weld ZMS vertices whose attributes are within this tolerance
+    weld_tolerance: Option<f32>,
+    /// Keep this many of the strongest bone influences of ZMS vertices
+    max_influences: Option<usize>,
+    /// Split ZMS meshes referencing more bones than this
+    bone_limit: Option<usize>,
+    /// Axes and scale of exported models, only set if any of `--up-axis`,
+    /// `--handedness` or `--scale` are
+    axes: Option<Axes>,
+    /// Write ZMS and ZSC files with their bounds as they are instead of
+    /// recomputing them
+    keep_bounds: bool,
+    /// Client directory of `--client`
+    client_dir: Option<PathBuf>,
+}
+
+impl ConvertOptions {
+    fn from_matches(matches: &ArgMatches) -> ConvertOptions {
+        ConvertOptions {
+            dry_run: matches.is_present("dry_run"),
+            format: matches
+                .value_of("format")
+                .and_then(Format::from_name)
+                .unwrap_or_default(),
+            pretty: !matches.is_present("compact"),
+            stb_json: matches.value_of("format") == Some("json"),
+            xlsx: matches.value_of("format") == Some("xlsx"),
+            script: matches.value_of("format") == Some("script"),
+            schema_dir: PathBuf::from(matches.value_of("schema_dir").unwrap_or_default()),
+            headers: matches.is_present("headers"),
+            csv: CsvDialect {
+                // Validated when parsing the args
+                delimiter: matches
+                    .value_of("delimiter")
+                    .and_then(|s| CsvDialect::parse_delimiter(s).ok())
+                    .unwrap_or(b','),
+                quote_all: matches.is_present("quote_all"),
+                crlf: matches.value_of("line_ending") == Some("crlf"),
+            },
+            // Validated when parsing the args
+            languages: matches
+                .values_of("languages")
+                .map(|values| values.filter_map(|s| s.parse().ok()).collect())
+                .unwrap_or_default(),
+            language: matches.value_of("language").and_then(|s| s.parse().ok()),
+            fail_fast: matches.is_present("fail_fast"),
+            // Validated when parsing the args
+            name_format: matches
+                .value_of("name_format")
+                .and_then(|s| NameFormat::new(s).ok()),
+            // Incremental runs reconvert changed files over their old outputs
+            force: matches.is_present("force") || matches.is_present("incremental"),
+            skip_existing: matches.is_present("skip_existing"),
+            backup: matches.is_present("backup"),
+            cache_dir: if matches.is_present("incremental") {
+                matches.value_of("out_dir").map(PathBuf::from)
+            } else {
+                None
+            },
+            // Validated when parsing the args
+            smoothing_angle: if matches.is_present("recompute_normals") {
+                Some(
+                    matches
+                        .value_of("smoothing_angle")
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(60.0),
+                )
+            } else {
+                None
+            },
+            // Validated when parsing the args
+            weld_tolerance: if matches.is_present("weld") {
+                Some(
+                    matches
+                        .value_of("weld_tolerance")
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(0.0001),
+                )
+            } else {
+                None
+            },
+            // Validated when parsing the args
+            max_influences: matches
+                .value_of("max_influences")
+                .and_then(|s| s.parse().ok()),
+            bone_limit: matches.value_of("bone_limit").and_then(|s| s.parse().ok()),
+            axes: axes_from_matches(matches),
+            keep_bounds: matches.is_present("keep_bounds"),
+            client_dir: matches.value_of("client").map(PathBuf::from),
+        }
+    }
+
+    /// Hash of the options that change the outputs of a conversion, files
+    /// converted with other options are converted again on incremental runs
+    fn fingerprint(&self) -> String {
+        let options = format!(
+            "{:?} {} {} {} {} {:?} {} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {} {:?} {:?}",
+            self.format,
+            self.pretty,
+            self.stb_json,
+            self.xlsx,
+            self.script,
+            self.schema_dir,
+            self.headers,
+            self.csv,
+            self.languages,
+            self.language,
+            self.name_format,
+            self.smoothing_angle,
+            self.weld_tolerance,
+            self.max_influences,
+            self.bone_limit,
+            self.axes,
+            self.keep_bounds,
+            self.client_dir,
+            self.cache_dir,
+        );
+        cache::data_hash(options.as_bytes())
+    }
+}
+
+/// Axes of `--up-axis`, `--handedness` and `--scale`, `None` if none are set
+fn axes_from_matches(matches: &ArgMatches) -> Option<Axes> {
+    let up = matches.value_of("up_axis");
+    let handedness = matches.value_of("handedness");
+    let scale = matches.value_of("scale");
+    if up.is_none() && handedness.is_none() && scale.is_none() {
+        return None;
+    }
+    let default = Axes::default();
+    // Validated when parsing the args
+    Some(Axes {
+        up: up
+            .and_then(|s| UpAxis::from_name(s).ok())
+            .unwrap_or(default.up),
+        handedness: handedness
+            .and_then(|s| Handedness::from_name(s).ok())
+            .unwrap_or(default.handedness),
+        scale: scale.and_then(|s| s.parse().ok()).unwrap_or(default.scale),
+    })
+}
+
 fn main() {
-    let matches = App::new("ROSE Converter")
+    let matches = build_cli().get_matches();
+
+    // Global args are propagated down to the subcommand so prefer its matches
+    let global_matches = matches.subcommand().1.unwrap_or(&matches);
+    let verbosity = if global_matches.is_present("quiet") {
+        0
+    } else {
+        2 + global_matches.occurrences_of("verbose") as usize
+    };
+
+    stderrlog::new()
+        .module(module_path!())
+        .verbosity(verbosity)
+        .init()
+        .expect("Failed to initialize logger");
+
+    if let Some(label) = global_matches.value_of("encoding") {
+        set_string_encoding(StringEncoding::from_label(label).expect("Invalid encoding"));
+    }
+    if let Some(name) = global_matches.value_of("normalize") {
+        set_string_normalization(Normalization::from_name(name).expect("Invalid normalization"));
+    }
+
+    let mut report = Report::new();
+    let res = run_command(&matches, &mut report);
+
+    // Summarize failures at the end so they aren't lost in the output of a batch
+    if report.results.len() > 1 && report.has_failures() {
+        error!(
+            "Failed to convert {} of {} files:",
+            report.failures().count(),
+            report.results.len()
+        );
+        for failure in report.failures() {
+            error!(
+                "\t{}: {}",
+                failure.input.display(),
+                failure.error.as_deref().unwrap_or_default()
+            );
+        }
+    } else if let Err(e) = &res {
+        error!("Error occured: {}", e);
+    }
+
+    if global_matches.is_present("manifest") {
+        if let Err(e) = write_manifest(global_matches, &report) {
+            error!("Failed to write manifest: {}", e);
+        }
+    }
+
+    if global_matches.value_of("report") == Some("json") {
+        match report.to_json() {
+            Ok(json) => println!("{}", json),
+            Err(e) => error!("Failed to generate report: {}", e),
+        }
+    }
+
+    if res.is_err() || report.has_failures() {
+        exit(1);
+    }
+}
+
+fn build_cli() -> App<'static, 'static> {
+    App::new("ROSE Converter")
         .version(crate_version!())
         .author(crate_authors!())
         .about("Convert ROSE Online files to/from various formats")
@@ -52,6 +445,127 @@ fn main() {
                 .short("o")
                 .global(true),
         )
+        .arg(
+            Arg::with_name("dry_run")
+                .help("Print the files that would be read and written without modifying anything")
+                .long("dry-run")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .help("Increase logging verbosity (-v for debug, -vv for trace)")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .help("Only log errors")
+                .short("q")
+                .long("quiet")
+                .conflicts_with("verbose")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("pretty")
+                .help("Pretty-print JSON output (default)")
+                .long("pretty")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("compact")
+                .help("Write JSON output without whitespace")
+                .long("compact")
+                .conflicts_with("pretty")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("encoding")
+                .help("Encoding of strings in ROSE files, e.g. `euc-kr` for the original Korean files [default: auto]")
+                .long_help(
+"Encoding of strings in ROSE files, e.g. `euc-kr` (CP949) for the original
+Korean files or `shift_jis`. Strings are decoded to UTF-8 when serializing and
+encoded back when deserializing. `auto` reads UTF-8 strings, falling back to
+EUC-KR, and writes UTF-8. Wide-strings are always UTF-16."
+                )
+                .long("encoding")
+                .takes_value(true)
+                .validator(|s| {
+                    StringEncoding::from_label(&s)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                })
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("normalize")
+                .help("Unicode normalization form of strings read from ROSE files")
+                .long("normalize")
+                .takes_value(true)
+                .possible_values(&["nfc", "nfkc"])
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("report")
+                .help("Print a summary of every conversion to stdout in the given format")
+                .long("report")
+                .takes_value(true)
+                .possible_values(&["json"])
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("manifest")
+                .help("Write a manifest.json mapping each input to its outputs to the output directory")
+                .long("manifest")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("fail_fast")
+                .help("Stop at the first file that fails to convert")
+                .long("fail-fast")
+                .conflicts_with("keep_going")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("force")
+                .help("Overwrite existing output files")
+                .short("f")
+                .long("force")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("skip_existing")
+                .help("Skip files whose output already exists")
+                .long("skip-existing")
+                .conflicts_with_all(&["force", "backup"])
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("backup")
+                .help("Copy existing output files to <file>.bak before overwriting them")
+                .long("backup")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("incremental")
+                .help("Only convert files that changed since the last run")
+                .long_help(
+"Only convert files that changed since the last run. Converted files are
+tracked in a cache file in the output directory and are converted again when
+their contents change or their outputs are missing."
+                )
+                .short("i")
+                .long("incremental")
+                .conflicts_with("skip_existing")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("keep_going")
+                .help("Convert the remaining files when one fails to convert (default)")
+                .long("keep-going")
+                .global(true),
+        )
         .settings(&[
             AppSettings::SubcommandRequiredElseHelp,
             AppSettings::VersionlessSubcommands,
@@ -60,30 +574,496 @@ fn main() {
         .subcommand(
             SubCommand::with_name("map")
                 .about("Convert ROSE map files")
+                .arg(format_arg())
+                .args(&csv_args())
+                .args(&axes_args())
+                .setting(AppSettings::SubcommandsNegateReqs)
                 .arg(
                     Arg::with_name("map_dir")
                         .help("Map directory containing zon, him, til and ifo files")
                         .required(true),
+                )
+                .arg(
+                    Arg::with_name("normal_map")
+                        .help("Also write a tangent-space normal map of the heightmap as `<map>_normal.png`, green up like OpenGL")
+                        .long("normal-map"),
+                )
+                .arg(
+                    Arg::with_name("normal_strength")
+                        .help("Scale of the slopes of the normal map, above 1 to exaggerate the terrain [default: 1]")
+                        .long("normal-strength")
+                        .takes_value(true)
+                        .requires("normal_map")
+                        .validator(|s| match s.parse::<f32>() {
+                            Ok(v) if v >= 0.0 => Ok(()),
+                            Ok(_) => Err(String::from("must not be negative")),
+                            Err(e) => Err(e.to_string()),
+                        }),
+                )
+                .arg(
+                    Arg::with_name("splatmap")
+                        .help("Also write splatmaps with the weight of every tile texture, four textures per `<map>_splat_<n>.png`, described by `<map>_splat.json`")
+                        .long("splatmap"),
+                )
+                .arg(
+                    Arg::with_name("splat_size")
+                        .help("Pixels per tile of the splatmaps of --splatmap and --target, a map block is 16x16 tiles [default: 4]")
+                        .long("splat-size")
+                        .takes_value(true)
+                        .validator(|s| match s.parse::<u32>() {
+                            Ok(0) => Err(String::from("must be at least 1")),
+                            Ok(_) => Ok(()),
+                            Err(e) => Err(e.to_string()),
+                        }),
+                )
+                .arg(
+                    Arg::with_name("client")
+                        .help("Client directory with the tile textures of the ZON, for the alpha of blended tiles [default: the directory with 3DDATA above the map]")
+                        .long("client")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .help("Instead, export the terrain for a game engine to `<out_dir>/<map>_<target>`: a unity RAW heightmap with splatmaps, or an unreal 16-bit PNG heightmap with weight maps and a data table of the placed objects, with textures and settings (`--format yaml` for YAML), or a godot scene with glTFs of the terrain and the placed objects")
+                        .long("target")
+                        .takes_value(true)
+                        .possible_values(&["unity", "unreal", "godot"]),
+                )
+                .arg(
+                    Arg::with_name("lightmaps")
+                        .help("With --target godot, bake the lightmaps of the LIT files of the map blocks into the vertex colors of the placed objects, exported once per lit instance")
+                        .long("lightmaps")
+                        .requires("target"),
+                )
+                .subcommand(
+                    SubCommand::with_name("atlas")
+                        .about("Stitch the minimaps or heightmaps of every zone of a client into a single image")
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory with 3DDATA/STB/LIST_ZONE.STB and the maps")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("heightmap")
+                                .help("Stitch the heightmaps of the zones instead of their minimaps, as grays from the lowest to the highest height of the world")
+                                .long("heightmap"),
+                        )
+                        .arg(
+                            Arg::with_name("hillshade")
+                                .help("Shade the slopes of the terrain of the minimaps from the HIM files")
+                                .long("hillshade")
+                                .conflicts_with("heightmap"),
+                        )
+                        .arg(
+                            Arg::with_name("tile_size")
+                                .help("Pixels per tile, a map block is 16x16 tiles [default: 2]")
+                                .long("tile-size")
+                                .takes_value(true)
+                                .validator(|s| match s.parse::<u32>() {
+                                    Ok(0) => Err(String::from("must be at least 1")),
+                                    Ok(_) => Ok(()),
+                                    Err(e) => Err(e.to_string()),
+                                }),
+                        )
+                        .arg(
+                            Arg::with_name("columns")
+                                .help("Zones per row, every planet starts a new row [default: 8]")
+                                .long("columns")
+                                .takes_value(true)
+                                .validator(validate_index),
+                        )
+                        .arg(
+                            Arg::with_name("labels")
+                                .help("Label the zones with their names from an STL, e.g. `3DDATA/STB/LIST_ZONE_S.STL`, relative to the client")
+                                .long("labels")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("language")
+                                .help("Language of the labels [default: en]")
+                                .long("language")
+                                .takes_value(true)
+                                .requires("labels")
+                                .validator(validate_language),
+                        )
+                        .arg(
+                            Arg::with_name("tiles")
+                                .help("Write a pyramid of web map tiles `world_tiles/{z}/{x}/{y}.png` instead of a single image")
+                                .long("tiles"),
+                        )
+                        .arg(
+                            Arg::with_name("web_tile_size")
+                                .help("Pixels of the web map tiles on each side [default: 256]")
+                                .long("web-tile-size")
+                                .takes_value(true)
+                                .requires("tiles")
+                                .validator(|s| match s.parse::<u32>() {
+                                    Ok(0) => Err(String::from("must be at least 1")),
+                                    Ok(_) => Ok(()),
+                                    Err(e) => Err(e.to_string()),
+                                }),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("collision")
+                        .about("Export the collision geometry of the objects placed by the IFO files of a map as a single OBJ, without render-only meshes")
+                        .args(&axes_args())
+                        .arg(
+                            Arg::with_name("map_dir")
+                                .help("Map directory containing ifo files, and him files with --terrain")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("terrain")
+                                .help("Add the terrain of the HIM files")
+                                .long("terrain"),
+                        )
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory with 3DDATA/STB/LIST_ZONE.STB, the ZSCs and their meshes [default: the directory with 3DDATA above the map]")
+                                .long("client")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("decorations")
+                                .help("Decoration ZSC of the zone [default: from LIST_ZONE.STB]")
+                                .long("decorations")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("buildings")
+                                .help("Construction ZSC of the zone [default: from LIST_ZONE.STB]")
+                                .long("buildings")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("instances")
+                        .about("List the decorations and buildings placed by the IFO files of a map with their models and world transforms as JSON")
+                        .arg(
+                            Arg::with_name("map_dir")
+                                .help("Map directory containing zon and ifo files")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory with 3DDATA/STB/LIST_ZONE.STB and the ZSCs [default: the directory with 3DDATA above the map]")
+                                .long("client")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("decorations")
+                                .help("Decoration ZSC of the zone [default: from LIST_ZONE.STB]")
+                                .long("decorations")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("buildings")
+                                .help("Construction ZSC of the zone [default: from LIST_ZONE.STB]")
+                                .long("buildings")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("manifest")
+                        .about("Write a JSON manifest of a zone with its ZON metadata, map blocks, IFO summaries and referenced ZSCs and textures")
+                        .arg(
+                            Arg::with_name("map_dir")
+                                .help("Map directory containing zon, him, til and ifo files")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory with 3DDATA/STB/LIST_ZONE.STB and the ZSCs [default: the directory with 3DDATA above the map]")
+                                .long("client")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("minimap")
+                        .about("Render a top-down image of a map from the textures of its tiles")
+                        .arg(
+                            Arg::with_name("map_dir")
+                                .help("Map directory containing zon, til and him files")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory with the tile textures of the ZON [default: the directory with 3DDATA above the map]")
+                                .long("client")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("tile_size")
+                                .help("Pixels per tile, a map block is 16x16 tiles")
+                                .long("tile-size")
+                                .takes_value(true)
+                                .default_value("4")
+                                .validator(|s| match s.parse::<u32>() {
+                                    Ok(0) => Err(String::from("must be at least 1")),
+                                    Ok(_) => Ok(()),
+                                    Err(e) => Err(e.to_string()),
+                                }),
+                        )
+                        .arg(
+                            Arg::with_name("hillshade")
+                                .help("Shade the slopes of the terrain from the HIM files")
+                                .long("hillshade"),
+                        )
+                        .arg(
+                            Arg::with_name("tiles")
+                                .help("Write a pyramid of web map tiles `<map>_tiles/{z}/{x}/{y}.png` instead of a single image")
+                                .long("tiles"),
+                        )
+                        .arg(
+                            Arg::with_name("web_tile_size")
+                                .help("Pixels of the web map tiles on each side [default: 256]")
+                                .long("web-tile-size")
+                                .takes_value(true)
+                                .requires("tiles")
+                                .validator(|s| match s.parse::<u32>() {
+                                    Ok(0) => Err(String::from("must be at least 1")),
+                                    Ok(_) => Ok(()),
+                                    Err(e) => Err(e.to_string()),
+                                }),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("navmesh")
+                        .about("Build a navigation mesh of a map from the slopes of its terrain and the objects of its IFO files, as OBJ and JSON")
+                        .args(&axes_args())
+                        .arg(
+                            Arg::with_name("map_dir")
+                                .help("Map directory containing him and ifo files")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("max_slope")
+                                .help("Steepest walkable slope in degrees [default: 45]")
+                                .long("max-slope")
+                                .takes_value(true)
+                                .validator(|s| match s.parse::<f32>() {
+                                    Ok(v) if (0.0..=90.0).contains(&v) => Ok(()),
+                                    Ok(_) => Err(String::from("must be between 0 and 90")),
+                                    Err(e) => Err(e.to_string()),
+                                }),
+                        )
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory with 3DDATA/STB/LIST_ZONE.STB and the ZSCs [default: the directory with 3DDATA above the map]")
+                                .long("client")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("decorations")
+                                .help("Decoration ZSC of the zone [default: from LIST_ZONE.STB]")
+                                .long("decorations")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("buildings")
+                                .help("Construction ZSC of the zone [default: from LIST_ZONE.STB]")
+                                .long("buildings")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("spawns")
+                        .about("Collect the monster spawns and NPCs of the IFO files of maps into a single JSON or CSV file")
+                        .args(&csv_args())
+                        .arg(
+                            Arg::with_name("input")
+                                .help("Map directories, a directory of maps (e.g. 3DDATA/MAPS) or IFO files")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("csv")
+                                .help("Write a CSV file with a row per monster of a spawn point and per NPC instead of JSON")
+                                .long("csv"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("warps")
+                        .about("Export the graph of the zones connected by warp gates as JSON or Graphviz DOT")
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory with 3DDATA/STB/LIST_ZONE.STB, 3DDATA/STB/WARP.STB and the maps")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("dot")
+                                .help("Write a Graphviz DOT file instead of JSON")
+                                .long("dot"),
+                        ),
                 ),
         )
         .subcommand(
             SubCommand::with_name("iconsheet")
                 .about("Convert ROSE iconsheet to icon files")
+                .setting(AppSettings::SubcommandsNegateReqs)
                 .arg(
                     Arg::with_name("iconsheets")
                         .help("Path to iconsheet")
                         .required(true)
                         .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("tsi")
+                        .help("Sprite information (TSI) of the iconsheets, icons are cut from its sprites and named by sprite")
+                        .long("tsi")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("icon_size")
+                        .help("Size of the grid cells, `<size>` or `<width>x<height>` [default: 40]")
+                        .long("icon-size")
+                        .takes_value(true)
+                        .conflicts_with("tsi")
+                        .validator(validate_icon_size),
+                )
+                .arg(
+                    Arg::with_name("cols")
+                        .help("Number of grid columns [default: as many as fit]")
+                        .long("cols")
+                        .takes_value(true)
+                        .conflicts_with("tsi")
+                        .validator(validate_index),
+                )
+                .arg(
+                    Arg::with_name("rows")
+                        .help("Number of grid rows [default: as many as fit]")
+                        .long("rows")
+                        .takes_value(true)
+                        .conflicts_with("tsi")
+                        .validator(validate_index),
+                )
+                .arg(
+                    Arg::with_name("padding")
+                        .help("Pixels between the grid cells [default: 0]")
+                        .long("padding")
+                        .takes_value(true)
+                        .conflicts_with("tsi")
+                        .validator(validate_index),
+                )
+                .arg(
+                    Arg::with_name("image_format")
+                        .help("Format of the icons")
+                        .long("image-format")
+                        .takes_value(true)
+                        .case_insensitive(true)
+                        .possible_values(&IMAGE_FORMAT_NAMES)
+                        .default_value("png"),
+                )
+                .arg(
+                    Arg::with_name("color_key")
+                        .help("Make pixels of the key color transparent, magenta unless given as `--color-key=rrggbb`")
+                        .long("color-key")
+                        .takes_value(true)
+                        .min_values(0)
+                        .require_equals(true)
+                        .validator(validate_color),
+                )
+                .subcommand(
+                    SubCommand::with_name("pack")
+                        .about("Pack a directory of 40x40 icons into iconsheets")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("Directory of icons, packed in natural order of their file names")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("name")
+                                .help("File name of the iconsheets, numbered if there is more than one [default: name of the directory]")
+                                .long("name")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("tsi")
+                                .help("Sprite information (TSI) to update with the sprites of the iconsheets, created if it doesn't exist")
+                                .long("tsi")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("image_format")
+                                .help("Format of the iconsheets")
+                                .long("image-format")
+                                .takes_value(true)
+                                .case_insensitive(true)
+                                .possible_values(&IMAGE_FORMAT_NAMES)
+                                .default_value("png"),
+                        )
+                        .arg(
+                            Arg::with_name("color_key")
+                                .help("Fill transparent pixels with the key color, magenta unless given as `--color-key=rrggbb`")
+                                .long("color-key")
+                                .takes_value(true)
+                                .min_values(0)
+                                .require_equals(true)
+                                .validator(validate_color),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("texture")
+                .about("Convert DDS and TGA textures to PNG or another image format, or images to DDS")
+                .arg(name_format_arg())
+                .arg(
+                    Arg::with_name("input")
+                        .help("DDS and TGA textures, or PNG/TGA/BMP images with --dxt1 or --dxt5, or directories, searched recursively")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("image_format")
+                        .help("Format of the converted textures")
+                        .long("image-format")
+                        .takes_value(true)
+                        .case_insensitive(true)
+                        .possible_values(&IMAGE_FORMAT_NAMES)
+                        .default_value("png"),
+                )
+                .arg(
+                    Arg::with_name("dxt1")
+                        .help("Encode images to DXT1 textures, with 1-bit alpha")
+                        .long("dxt1")
+                        .conflicts_with("dxt5"),
+                )
+                .arg(
+                    Arg::with_name("dxt5")
+                        .help("Encode images to DXT5 textures, with interpolated alpha")
+                        .long("dxt5"),
+                )
+                .arg(
+                    Arg::with_name("no_mipmaps")
+                        .help("Encode textures without mipmaps")
+                        .long("no-mipmaps"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("lit")
+                .about("Crop the lightmap of every part listed by LIT files from its DDS page, with a JSON mapping of the parts to their pages and rectangles")
+                .arg(
+                    Arg::with_name("input")
+                        .help("LIT files, with their DDS pages next to them, or directories, searched recursively")
+                        .required(true)
+                        .multiple(true),
                 ),
         )
         .subcommand(
             SubCommand::with_name("serialize")
                 .visible_alias("se")
-                .about("Serialize a ROSE File into JSON (CSV for STB/STL).")
+                .about("Serialize a ROSE File into JSON, YAML, MessagePack or CBOR (CSV for STB/STL).")
+                .arg(format_arg())
+                .arg(schema_dir_arg())
+                .arg(headers_arg())
+                .args(&csv_args())
+                .arg(name_format_arg())
                 .arg(
                     Arg::with_name("input")
-                        .help("Path to ROSE file")
-                        .required(true),
+                        .help("Path to ROSE files or directories")
+                        .required(true)
+                        .multiple(true),
                 )
                 .arg(
                     Arg::with_name("type")
@@ -100,12 +1080,84 @@ fn main() {
                         .help("Keep the original file extension in addition to the next one, e.g. list_zone.stb.csv")
                         .required(false)
                         .takes_value(false)
+                )
+                .arg(
+                    Arg::with_name("languages")
+                        .help("Languages of STL files to serialize, e.g. `en,jp` [default: all]")
+                        .long("languages")
+                        .takes_value(true)
+                        .use_delimiter(true)
+                        .validator(validate_language),
+                )
+                .arg(
+                    Arg::with_name("language")
+                        .help("Serialize a single language of STL files as compact CSV, e.g. `en`")
+                        .long("language")
+                        .takes_value(true)
+                        .conflicts_with("languages")
+                        .validator(validate_language),
+                )
+                .arg(
+                    Arg::with_name("recompute_normals")
+                        .help("Rebuild smooth normals of ZMS files from their faces")
+                        .long("recompute-normals"),
+                )
+                .arg(
+                    Arg::with_name("smoothing_angle")
+                        .help("Largest angle in degrees between faces smoothed together by --recompute-normals [default: 60]")
+                        .long("smoothing-angle")
+                        .takes_value(true)
+                        .requires("recompute_normals")
+                        .validator(validate_smoothing_angle),
+                )
+                .arg(
+                    Arg::with_name("weld")
+                        .help("Weld duplicate vertices of ZMS files and rebuild their faces")
+                        .long("weld"),
+                )
+                .arg(
+                    Arg::with_name("weld_tolerance")
+                        .help("Largest difference between the attributes of vertices welded by --weld [default: 0.0001]")
+                        .long("weld-tolerance")
+                        .takes_value(true)
+                        .requires("weld")
+                        .validator(|s| match s.parse::<f32>() {
+                            Ok(v) if v >= 0.0 => Ok(()),
+                            Ok(_) => Err(String::from("must not be negative")),
+                            Err(e) => Err(e.to_string()),
+                        }),
+                )
+                .arg(
+                    Arg::with_name("max_influences")
+                        .help("Keep the strongest bone influences of ZMS vertices and normalize their weights (1-4)")
+                        .long("max-influences")
+                        .takes_value(true)
+                        .validator(|s| match s.parse::<usize>() {
+                            Ok(v) if (1..=4).contains(&v) => Ok(()),
+                            Ok(_) => Err(String::from("must be between 1 and 4")),
+                            Err(e) => Err(e.to_string()),
+                        }),
+                )
+                .arg(
+                    Arg::with_name("bone_limit")
+                        .help("Split ZMS files referencing more bones into `<name>_<n>` parts")
+                        .long("bone-limit")
+                        .takes_value(true)
+                        .validator(|s| match s.parse::<usize>() {
+                            Ok(v) if v > 0 => Ok(()),
+                            Ok(_) => Err(String::from("must be at least 1")),
+                            Err(e) => Err(e.to_string()),
+                        }),
                 ),
         )
         .subcommand(
             SubCommand::with_name("deserialize")
                 .visible_alias("de")
-                .about("Deserialize a ROSE file from JSON (CSV for STB/STL).")
+                .about("Deserialize a ROSE file from JSON, YAML, MessagePack or CBOR (CSV for STB/STL).")
+                .arg(format_arg())
+                .arg(schema_dir_arg())
+                .args(&csv_args())
+                .arg(name_format_arg())
                 .arg(
                     Arg::with_name("type")
                         .help("ROSE file type")
@@ -115,7 +1167,7 @@ fn main() {
                 )
                 .arg(
                     Arg::with_name("input")
-                        .help("Path to JSON/CSV file")
+                        .help("Path to serialized file or directory")
                         .required(true),
                 )
                 .arg(
@@ -128,429 +1180,6661 @@ priority over the output directory flag (`-o`)."
                         )
                         .conflicts_with("out_dir")
                 )
-                ,
+                .arg(
+                    Arg::with_name("language")
+                        .help("Patch a single language from compact CSV into the existing output STL, e.g. `en`")
+                        .long("language")
+                        .takes_value(true)
+                        .validator(validate_language),
+                )
+                .arg(
+                    Arg::with_name("recompute_normals")
+                        .help("Rebuild smooth normals of ZMS files from their faces")
+                        .long("recompute-normals"),
+                )
+                .arg(
+                    Arg::with_name("smoothing_angle")
+                        .help("Largest angle in degrees between faces smoothed together by --recompute-normals [default: 60]")
+                        .long("smoothing-angle")
+                        .takes_value(true)
+                        .requires("recompute_normals")
+                        .validator(validate_smoothing_angle),
+                )
+                .arg(
+                    Arg::with_name("weld")
+                        .help("Weld duplicate vertices of ZMS files and rebuild their faces")
+                        .long("weld"),
+                )
+                .arg(
+                    Arg::with_name("weld_tolerance")
+                        .help("Largest difference between the attributes of vertices welded by --weld [default: 0.0001]")
+                        .long("weld-tolerance")
+                        .takes_value(true)
+                        .requires("weld")
+                        .validator(|s| match s.parse::<f32>() {
+                            Ok(v) if v >= 0.0 => Ok(()),
+                            Ok(_) => Err(String::from("must not be negative")),
+                            Err(e) => Err(e.to_string()),
+                        }),
+                )
+                .arg(
+                    Arg::with_name("max_influences")
+                        .help("Keep the strongest bone influences of ZMS vertices and normalize their weights (1-4)")
+                        .long("max-influences")
+                        .takes_value(true)
+                        .validator(|s| match s.parse::<usize>() {
+                            Ok(v) if (1..=4).contains(&v) => Ok(()),
+                            Ok(_) => Err(String::from("must be between 1 and 4")),
+                            Err(e) => Err(e.to_string()),
+                        }),
+                )
+                .arg(
+                    Arg::with_name("bone_limit")
+                        .help("Split ZMS files referencing more bones into `<name>_<n>` parts")
+                        .long("bone-limit")
+                        .takes_value(true)
+                        .validator(|s| match s.parse::<usize>() {
+                            Ok(v) if v > 0 => Ok(()),
+                            Ok(_) => Err(String::from("must be at least 1")),
+                            Err(e) => Err(e.to_string()),
+                        }),
+                )
+                .arg(
+                    Arg::with_name("keep_bounds")
+                        .help("Write the bounds of ZMS and ZSC files as they are instead of recomputing them from the vertices")
+                        .long("keep-bounds"),
+                )
+                .arg(
+                    Arg::with_name("client")
+                        .help("Client directory of the meshes of ZSC files, for their bounds, of the AIP and QSD files to check the opcodes of AIP and QSD files against, and of the STBs to check the quests, items and NPCs of QSD files against [default: the closest directory above the input with a 3DDATA directory]")
+                        .long("client")
+                        .takes_value(true),
+                ),
         )
-        .get_matches();
-
-    // Run subcommands
-    let res = match matches.subcommand() {
-        ("map", Some(matches)) => convert_map(matches),
-        ("serialize", Some(matches)) => serialize(matches),
-        ("deserialize", Some(matches)) => deserialize(matches),
-        ("iconsheet", Some(matches)) => convert_iconsheets(matches),
-        _ => {
-            eprintln!("ROSE Online Converter. Run with `--help` for more info.");
-            exit(1);
-        }
-    };
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("Watch a directory and convert ROSE files as they change")
+                .arg(format_arg())
+                .arg(schema_dir_arg())
+                .arg(headers_arg())
+                .args(&csv_args())
+                .arg(
+                    Arg::with_name("watch_dir")
+                        .help("Directory to watch for changes")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("rule")
+                        .help("Conversion rule for an extension, e.g. `csv:stb`")
+                        .long_help(
+"Conversion rule of the form <ext>:<type>. Files with a `csv`, `json` or `yaml`
+extension are deserialized as <type>, any other extension is serialized as
+<type>. Without a matching rule ROSE files are serialized and files with a
+double extension (e.g. list_zone.stb.csv) are deserialized."
+                        )
+                        .short("r")
+                        .long("rule")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Print a summary of ROSE files")
+                .arg(
+                    Arg::with_name("input")
+                        .help("Files to summarize")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .help("Type of file")
+                        .short("t")
+                        .long("type")
+                        .takes_value(true)
+                        .possible_values(&SERIALIZE_VALUES),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Count the files of client directories or VFS indexes by type and zone")
+                .arg(
+                    Arg::with_name("input")
+                        .help("Client directories or VFS indexes (.idx)")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("largest")
+                        .help("Number of largest files listed")
+                        .long("largest")
+                        .takes_value(true)
+                        .default_value("10")
+                        .validator(|s| s.parse::<usize>().map(|_| ()).map_err(|e| e.to_string())),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the statistics as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("Check the references between the files of a client directory or VFS index")
+                .arg(
+                    Arg::with_name("input")
+                        .help("Client directory or VFS index (.idx)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the issues as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unused")
+                .about("List the files of a client directory or VFS index that no file references")
+                .arg(
+                    Arg::with_name("input")
+                        .help("Client directory or VFS index (.idx)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the unused files as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("missing")
+                .about("List the referenced paths missing from a client directory or VFS index")
+                .arg(
+                    Arg::with_name("input")
+                        .help("Client directory or VFS index (.idx)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the missing paths as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("deps")
+                .about("Print the graph of the files a file of a client depends on, as JSON or DOT")
+                .arg(
+                    Arg::with_name("root")
+                        .help("File to start from, a client path or zone name with --client, a file in a client directory otherwise")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("client")
+                        .help("Client directory or VFS index (.idx) [default: the directory with 3DDATA above the file]")
+                        .long("client")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("dot")
+                        .help("Print the graph as Graphviz DOT instead of JSON")
+                        .long("dot"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("checksums")
+                .about("Create a manifest of the files of a client with their checksums, or verify a client against one")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("create")
+                        .about("Write the path, size and SHA-256 of every file of a client directory or VFS index to a JSON manifest")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("Client directory or VFS index (.idx)")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .help("Path of the manifest [default: <out_dir>/client_manifest.json]")
+                                .long("output")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("verify")
+                        .about("Check a client directory or VFS index against a manifest")
+                        .arg(
+                            Arg::with_name("manifest_file")
+                                .value_name("manifest")
+                                .help("JSON manifest created by `checksums create`")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("input")
+                                .help("Client directory or VFS index (.idx)")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("json")
+                                .help("Print the differing files as JSON")
+                                .long("json"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("Check the health of a client directory or VFS index: its VFS volumes, STB, STL and ZSC files and references")
+                .arg(
+                    Arg::with_name("input")
+                        .help("Client directory or VFS index (.idx)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the report as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("Compare two client directories or VFS indexes: added zones and files, changed files and their STB rows and STL strings")
+                .arg(
+                    Arg::with_name("old")
+                        .help("Old client directory or VFS index (.idx)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("new")
+                        .help("New client directory or VFS index (.idx)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the differences as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("schema")
+                .about("Print the JSON Schema of a serialized ROSE file type")
+                .arg(
+                    Arg::with_name("type")
+                        .help("ROSE file type")
+                        .case_insensitive(true)
+                        .possible_values(&SCHEMA_TYPES)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Compare two STB files cell by cell or two STL files key by key")
+                .arg(
+                    Arg::with_name("old")
+                        .help("Original STB or STL file")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("new")
+                        .help("Changed STB or STL file")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the differences as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("encoding-report")
+                .about("Report the string encoding of STB, STL and ZMD files and flag mojibake")
+                .arg(
+                    Arg::with_name("input")
+                        .help("Files or directories to check")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("samples")
+                        .help("Number of non-ASCII strings checked per file, 0 checks every string")
+                        .long("samples")
+                        .takes_value(true)
+                        .default_value("1000")
+                        .validator(|s| s.parse::<usize>().map(|_| ()).map_err(|e| e.to_string())),
+                )
+                .arg(
+                    Arg::with_name("issues")
+                        .help("Only list files with mixed encodings or mojibake")
+                        .long("issues"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the report as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("grep")
+                .about("Search the strings of STL files and the cells of STB files")
+                .arg(
+                    Arg::with_name("pattern")
+                        .help("Regular expression to search for, e.g. `^Iron (Sword|Axe)$`")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("input")
+                        .help("Files, directories or VFS indexes (.idx) to search")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("ignore_case")
+                        .help("Search case-insensitively")
+                        .long("ignore-case"),
+                )
+                .arg(
+                    Arg::with_name("fixed_strings")
+                        .help("Search for the pattern as a plain string")
+                        .short("F")
+                        .long("fixed-strings"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the matches as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("Three-way merge of STB files")
+                .arg(
+                    Arg::with_name("base")
+                        .help("Common ancestor of both STB files")
+                        .long("base")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("ours")
+                        .help("Our changed STB file, kept on conflicts")
+                        .long("ours")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("theirs")
+                        .help("Their changed STB file")
+                        .long("theirs")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("join")
+                .about("Join STB and STL files into a single table")
+                .arg(format_arg())
+                .arg(schema_dir_arg())
+                .args(&csv_args())
+                .arg(
+                    Arg::with_name("spec")
+                        .help("TOML join spec, table paths are relative to it")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("codegen")
+                .about("Generate Rust structs and loaders from STB schemas")
+                .arg(schema_dir_arg())
+                .arg(
+                    Arg::with_name("schemas")
+                        .help("STB schema files [default: every schema in --schema-dir]")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .help("Path of the Rust module [default: <out_dir>/stb_tables.rs]")
+                        .long("output")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stl")
+                .about("Localization tools for STL files")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("to-po")
+                        .about("Export the translations of STL files to gettext PO files, one per language")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("STL files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("source")
+                                .help("Language translated from")
+                                .long("source")
+                                .takes_value(true)
+                                .default_value("en")
+                                .validator(validate_language),
+                        )
+                        .arg(
+                            Arg::with_name("languages")
+                                .help("Languages translated to, e.g. `jp,tw` [default: every other language]")
+                                .long("languages")
+                                .takes_value(true)
+                                .use_delimiter(true)
+                                .validator(validate_language),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("from-po")
+                        .about("Merge the translations of gettext PO files into an STL file in place")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("STL file to edit")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("po")
+                                .help("PO files, the language is read from their Language header")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("language")
+                                .help("Language of the PO files, overriding their Language header")
+                                .long("language")
+                                .takes_value(true)
+                                .validator(validate_language),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("merge")
+                        .about("Merge the strings of a CSV file into an STL file in place")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("STL file to edit")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("csv")
+                                .help("CSV file of strings, serialized with or without `--language`")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("language")
+                                .help("Language of a CSV file serialized with `--language`")
+                                .long("language")
+                                .takes_value(true)
+                                .validator(validate_language),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("report")
+                        .about("List strings that are missing or identical to the source language")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("STL files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("source")
+                                .help("Language translated from")
+                                .long("source")
+                                .takes_value(true)
+                                .default_value("en")
+                                .validator(validate_language),
+                        )
+                        .arg(
+                            Arg::with_name("languages")
+                                .help("Languages to check, e.g. `jp,tw` [default: every other language]")
+                                .long("languages")
+                                .takes_value(true)
+                                .use_delimiter(true)
+                                .validator(validate_language),
+                        )
+                        .arg(
+                            Arg::with_name("summary")
+                                .help("Only print the counts of every file")
+                                .long("summary"),
+                        )
+                        .arg(
+                            Arg::with_name("json")
+                                .help("Print the report as JSON")
+                                .long("json"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("duplicates")
+                        .about("Group identical and near-identical strings across STL files")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("STL files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("languages")
+                                .help("Languages to check, e.g. `en,jp` [default: every language]")
+                                .long("languages")
+                                .takes_value(true)
+                                .use_delimiter(true)
+                                .validator(validate_language),
+                        )
+                        .arg(
+                            Arg::with_name("exact")
+                                .help("Only group identical strings, not strings differing in case, punctuation or whitespace")
+                                .long("exact"),
+                        )
+                        .arg(
+                            Arg::with_name("summary")
+                                .help("Only print the string and word counts of every language")
+                                .long("summary"),
+                        )
+                        .arg(
+                            Arg::with_name("json")
+                                .help("Print the report as JSON")
+                                .long("json"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("patch")
+                .about("Apply semantic patches to STB and STL files")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("apply")
+                        .about("Apply a patch to an STB or STL file")
+                        .arg(
+                            Arg::with_name("patch")
+                                .help("JSON patch file")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("input")
+                                .help("STB or STL file to patch")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stb")
+                .about("Edit STB files in place")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("Set the value of cells in an STB file")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("STB file to edit")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("row")
+                                .help("Row index")
+                                .validator(validate_index)
+                                .required_unless("from_file"),
+                        )
+                        .arg(
+                            Arg::with_name("column")
+                                .help("Column index")
+                                .validator(validate_index)
+                                .required_unless("from_file"),
+                        )
+                        .arg(
+                            Arg::with_name("value")
+                                .help("New value of the cell")
+                                .required_unless("from_file"),
+                        )
+                        .arg(
+                            Arg::with_name("from_file")
+                                .help("CSV file of edits with the columns row, column and value")
+                                .long("from-file")
+                                .takes_value(true)
+                                .conflicts_with_all(&["row", "column", "value"]),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("insert-row")
+                        .about("Insert a row into an STB file, missing values are left empty")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("STB file to edit")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("row")
+                                .help("Index of the new row")
+                                .validator(validate_index)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("values")
+                                .help("Values of the new row")
+                                .multiple(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("append-row")
+                        .about("Append a row to an STB file, missing values are left empty")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("STB file to edit")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("values")
+                                .help("Values of the new row")
+                                .multiple(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("delete-row")
+                        .about("Delete a row from an STB file")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("STB file to edit")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("row")
+                                .help("Index of the row to delete")
+                                .validator(validate_index)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("to-sqlite")
+                        .about("Export every STB in a directory or VFS to an SQLite database")
+                        .arg(schema_dir_arg())
+                        .arg(
+                            Arg::with_name("input")
+                                .help("Directory of STB files or a VFS index (.idx)")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("database")
+                                .help("Path of the database [default: <out_dir>/stb.sqlite]")
+                                .long("database")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("from-sqlite")
+                        .about("Write every STB in an SQLite database back to STB files")
+                        .arg(
+                            Arg::with_name("database")
+                                .help("Database exported by `stb to-sqlite`")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("copy-row")
+                        .about("Copy a row of an STB file")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("STB file to edit")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("row")
+                                .help("Index of the row to copy")
+                                .validator(validate_index)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("to")
+                                .help("Index of the copy, appended to the end by default")
+                                .validator(validate_index),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tsi")
+                .about("Tools for the sprite information (TSI) of UI sheets")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("atlas")
+                        .about("Export TSI files as sprite atlas descriptors for web and engine UIs")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("TSI files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("atlas_format")
+                                .help("TexturePacker JSON (hash) per sheet or a CSS sprite map per TSI")
+                                .long("atlas-format")
+                                .takes_value(true)
+                                .case_insensitive(true)
+                                .possible_values(&ATLAS_FORMAT_NAMES)
+                                .default_value("texturepacker"),
+                        )
+                        .arg(
+                            Arg::with_name("image_format")
+                                .help("Format of the exported sheets, used for the sheet file names")
+                                .long("image-format")
+                                .takes_value(true)
+                                .case_insensitive(true)
+                                .possible_values(&IMAGE_FORMAT_NAMES)
+                                .default_value("png"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("build")
+                        .about("Pack a directory of images into sheets and write a new TSI describing them")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("Directory of images, the sprites are named by file name")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("name")
+                                .help("File name of the TSI and the sheets, numbered if there is more than one [default: name of the directory]")
+                                .long("name")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("size")
+                                .help("Width and height of the sheets")
+                                .long("size")
+                                .takes_value(true)
+                                .default_value("1024")
+                                .validator(validate_index),
+                        )
+                        .arg(
+                            Arg::with_name("padding")
+                                .help("Pixels between the sprites")
+                                .long("padding")
+                                .takes_value(true)
+                                .default_value("0")
+                                .validator(validate_index),
+                        )
+                        .arg(
+                            Arg::with_name("sheet_dir")
+                                .help("Client directory of the sheets, referenced in the TSI as DDS textures")
+                                .long("sheet-dir")
+                                .takes_value(true)
+                                .default_value("3DDATA/CONTROL/RES"),
+                        )
+                        .arg(
+                            Arg::with_name("image_format")
+                                .help("Format of the sheets")
+                                .long("image-format")
+                                .takes_value(true)
+                                .case_insensitive(true)
+                                .possible_values(&IMAGE_FORMAT_NAMES)
+                                .default_value("png"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("zsc")
+                .about("Tools for the scenes (ZSC) of objects and characters")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("bundle")
+                        .about("Copy a ZSC with every mesh, texture, effect and animation it references into a self-contained directory")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("ZSC files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory the referenced paths are relative to [default: the directory with 3DDATA above the ZSC]")
+                                .long("client")
+                                .takes_value(true)
+                                .conflicts_with("vfs"),
+                        )
+                        .arg(
+                            Arg::with_name("vfs")
+                                .help("VFS index (.idx) to extract the referenced files from")
+                                .long("vfs")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("gltf")
+                        .about("Export every object of a ZSC as a glTF model with its parts, materials and textures")
+                        .args(&axes_args())
+                        .arg(
+                            Arg::with_name("input")
+                                .help("ZSC files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory the meshes and textures are relative to [default: the directory with 3DDATA above the ZSC]")
+                                .long("client")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("object")
+                                .help("Index of an object to export, all objects by default")
+                                .long("object")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1)
+                                .validator(validate_index),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("materials")
+                        .about("Write the material of every part of every object of a ZSC as JSON, with its texture, blending, alpha and glow settings")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("ZSC files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory the textures are relative to [default: the directory with 3DDATA above the ZSC]")
+                                .long("client")
+                                .takes_value(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("zmd")
+                .about("Tools for the skeletons (ZMD) of characters and objects")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("viz")
+                        .about("Export the bones and dummy bones of ZMD files as a glTF of nodes with a mesh of lines between the joints, to see them in a viewer")
+                        .args(&axes_args())
+                        .arg(
+                            Arg::with_name("input")
+                                .help("ZMD files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("obj")
+                                .help("Write an OBJ with a vertex per joint and a line per bone instead")
+                                .long("obj"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("zmo")
+                .about("Tools for the motions (ZMO) of characters and objects")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("resample")
+                        .about("Resample the channels of ZMO files to another frame rate, interpolating the frames in between")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("ZMO files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("fps")
+                                .help("Frame rate of the resampled motions")
+                                .long("fps")
+                                .takes_value(true)
+                                .required(true)
+                                .validator(|s| match s.parse::<u32>() {
+                                    Ok(0) => Err(String::from("must be at least 1")),
+                                    Ok(_) => Ok(()),
+                                    Err(e) => Err(e.to_string()),
+                                }),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("root-motion")
+                        .about("Export the translation of the root bone of ZMO files as a root motion curve, relative to the first frame")
+                        .args(&csv_args())
+                        .args(&axes_args())
+                        .arg(
+                            Arg::with_name("input")
+                                .help("ZMO files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("bone")
+                                .help("Index of the root bone")
+                                .long("bone")
+                                .takes_value(true)
+                                .default_value("0")
+                                .validator(validate_index),
+                        )
+                        .arg(
+                            Arg::with_name("csv")
+                                .help("Write a CSV file with a row per frame instead of JSON")
+                                .long("csv"),
+                        )
+                        .arg(
+                            Arg::with_name("in_place")
+                                .help("Also write the ZMO with the root bone pinned at its first position, for engines applying the root motion themselves")
+                                .long("in-place"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("events")
+                        .about("List the frame events of a ZMO file, such as sounds and attack hits, or edit them in place")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("ZMO file")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("json")
+                                .help("Print the events as JSON")
+                                .long("json"),
+                        )
+                        .arg(
+                            Arg::with_name("move")
+                                .help("Move the event of a frame to another frame, as `<from>=<to>`")
+                                .long("move")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1)
+                                .validator(validate_frame_edit),
+                        )
+                        .arg(
+                            Arg::with_name("set")
+                                .help("Set the event of a frame, as `<frame>=<event>`, 0 to remove it")
+                                .long("set")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1)
+                                .validator(validate_frame_edit),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("zms")
+                .about("Tools for the meshes (ZMS) of characters and objects")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("merge")
+                        .about("Merge ZMS files into a single mesh, e.g. the parts of a ZSC object for static batching")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("ZMS files, merged in order")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .help("Path of the merged mesh [default: <out_dir>/<first input>_merged.zms]")
+                                .long("output")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("material_groups")
+                                .help("Give the faces of every input their own material group")
+                                .long("material-groups"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("simplify")
+                        .about("Write lower detail LODs of ZMS files by quadric decimation, keeping their UV seams and bone weights")
+                        .args(&axes_args())
+                        .arg(
+                            Arg::with_name("input")
+                                .help("ZMS files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("ratio")
+                                .help("Share of the faces to keep")
+                                .long("ratio")
+                                .takes_value(true)
+                                .default_value("0.5")
+                                .validator(|s| match s.parse::<f32>() {
+                                    Ok(v) if v > 0.0 && v <= 1.0 => Ok(()),
+                                    Ok(_) => Err(String::from("must be above 0 and at most 1")),
+                                    Err(e) => Err(e.to_string()),
+                                }),
+                        )
+                        .arg(
+                            Arg::with_name("obj")
+                                .help("Write an OBJ instead, Y-up in meters like glTF exports")
+                                .long("obj"),
+                        )
+                        .arg(
+                            Arg::with_name("uv2")
+                                .help("Also write the second UV channel as `<name>_lod_uv2.obj`")
+                                .long("uv2")
+                                .requires("obj"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("optimize")
+                        .about("Reorder the faces and vertices of ZMS files for the vertex cache of GPUs")
+                        .arg(
+                            Arg::with_name("input")
+                                .help("ZMS files or directories")
+                                .required(true)
+                                .multiple(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("gltf")
+                        .about("Export ZMS files as glTF models with their material and texture")
+                        .args(&axes_args())
+                        .arg(
+                            Arg::with_name("input")
+                                .help("ZMS files or directories")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("client")
+                                .help("Client directory the meshes and textures are relative to [default: the directory with 3DDATA above the ZMS]")
+                                .long("client")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("texture")
+                                .help("Client path of the texture of the meshes [default: the material of the first ZSC part using the mesh]")
+                                .long("texture")
+                                .takes_value(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Run a conversion profile from a config file")
+                .arg(
+                    Arg::with_name("profile")
+                        .help("Name of the profile to run")
+                        .short("p")
+                        .long("profile")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .help("Path to the config file")
+                        .short("c")
+                        .long("config")
+                        .takes_value(true)
+                        .default_value(CONFIG_FILE),
+                ),
+        )
+}
+
+/// `--format` of the subcommands serializing files
+fn format_arg() -> Arg<'static, 'static> {
+    Arg::with_name("format")
+        .help("Format used when serializing file types that aren't serialized as CSV")
+        .long("format")
+        .takes_value(true)
+        .possible_values(&["json", "yaml", "msgpack", "cbor", "xlsx", "script"])
+}
+
+/// `--schema-dir` of the subcommands reading STB schemas
+fn schema_dir_arg() -> Arg<'static, 'static> {
+    Arg::with_name("schema_dir")
+        .help("Directory of STB column schemas, e.g. schemas/list_zone.toml")
+        .long("schema-dir")
+        .takes_value(true)
+        .default_value("schemas")
+}
+
+/// `--headers` of the subcommands writing STB CSV files
+fn headers_arg() -> Arg<'static, 'static> {
+    Arg::with_name("headers")
+        .help("Write the STB's own column headers and root row to CSV files")
+        .long("headers")
+}
+
+/// `--delimiter`, `--quote-all` and `--line-ending` of the subcommands reading
+/// or writing CSV files
+fn csv_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("delimiter")
+            .help("Delimiter of STB and STL CSV files, e.g. `\\t` for tab-separated files")
+            .long("delimiter")
+            .takes_value(true)
+            .validator(|s| {
+                CsvDialect::parse_delimiter(&s)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }),
+        Arg::with_name("quote_all")
+            .help("Quote every field of CSV files")
+            .long("quote-all"),
+        Arg::with_name("line_ending")
+            .help("Line ending of CSV files")
+            .long("line-ending")
+            .takes_value(true)
+            .possible_values(&["lf", "crlf"]),
+    ]
+}
+
+/// `--name-format` of the subcommands converting directories
+fn name_format_arg() -> Arg<'static, 'static> {
+    Arg::with_name("name_format")
+        .help("Template for output file names, e.g. `{dir}/{stem:lower}.{type}.{ext}`")
+        .long_help(
+"Template for output file names, relative to the output directory.
+Placeholders:
+    {name}     Input file name
+    {stem}     Input file name without its extension
+    {src_ext}  Input file extension
+    {ext}      Output file extension
+    {type}     ROSE file type
+    {dir}      Input directory relative to the directory being converted
+    {parent}   Name of the input's parent directory
+Append `:lower` or `:upper` to change the case, e.g. `{stem:lower}`."
+        )
+        .long("name-format")
+        .takes_value(true)
+        .validator(|s| NameFormat::new(&s).map(|_| ()).map_err(|e| e.to_string()))
+}
+
+/// `--up-axis`, `--handedness` and `--scale` of the subcommands exporting
+/// models and root motion
+fn axes_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("up_axis")
+            .help("Up axis of exported models and root motion [default: y]")
+            .long("up-axis")
+            .takes_value(true)
+            .possible_values(&["y", "z"]),
+        Arg::with_name("handedness")
+            .help("Handedness of the axes of exported models and root motion [default: right]")
+            .long("handedness")
+            .takes_value(true)
+            .possible_values(&["right", "left"]),
+        Arg::with_name("scale")
+            .help("Units per meter of exported models and root motion, e.g. 100 for centimeters [default: 1]")
+            .long("scale")
+            .takes_value(true)
+            .validator(|s| match s.parse::<f32>() {
+                Ok(v) if v > 0.0 => Ok(()),
+                Ok(_) => Err(String::from("must be positive")),
+                Err(e) => Err(e.to_string()),
+            }),
+    ]
+}
+
+/// Write a manifest of the successful conversions to the output directory
+fn write_manifest(matches: &ArgMatches, report: &Report) -> Result<(), Error> {
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let path = out_dir.join(MANIFEST_FILE);
+
+    if matches.is_present("dry_run") {
+        dry_run_write(&path);
+        return Ok(());
+    }
+
+    create_output_dir(out_dir)?;
+    Manifest::from_report(report)?.write_to_path(&path)?;
+    info!("Saved manifest to: {}", path.display());
+    Ok(())
+}
+
+/// Run the subcommand selected in `matches`
+fn run_command(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    match matches.subcommand() {
+        ("map", Some(matches)) => match matches.subcommand() {
+            ("atlas", Some(matches)) => map_atlas(matches, report),
+            ("collision", Some(matches)) => map_collision(matches, report),
+            ("instances", Some(matches)) => map_instances(matches, report),
+            ("manifest", Some(matches)) => map_manifest(matches, report),
+            ("minimap", Some(matches)) => map_minimap(matches, report),
+            ("navmesh", Some(matches)) => map_navmesh(matches, report),
+            ("spawns", Some(matches)) => map_spawns(matches, report),
+            ("warps", Some(matches)) => map_warps(matches, report),
+            _ => convert_map(matches, report),
+        },
+        ("serialize", Some(matches)) => serialize(matches, report),
+        ("texture", Some(matches)) => convert_textures(matches, report),
+        ("lit", Some(matches)) => export_lightmaps(matches, report),
+        ("deserialize", Some(matches)) => deserialize(matches, report),
+        ("iconsheet", Some(matches)) => match matches.subcommand() {
+            ("pack", Some(matches)) => pack_iconsheets(matches, report),
+            _ => convert_iconsheets(matches, report),
+        },
+        ("watch", Some(matches)) => watch(matches),
+        ("run", Some(matches)) => run_profile(matches, report),
+        ("info", Some(matches)) => print_info(matches),
+        ("stats", Some(matches)) => print_stats(matches),
+        ("validate", Some(matches)) => validate_client(matches),
+        ("unused", Some(matches)) => print_unused(matches),
+        ("missing", Some(matches)) => print_missing(matches),
+        ("deps", Some(matches)) => print_deps(matches),
+        ("checksums", Some(matches)) => match matches.subcommand() {
+            ("create", Some(matches)) => create_checksums(matches),
+            ("verify", Some(matches)) => verify_checksums(matches),
+            _ => bail!("Missing checksums subcommand"),
+        },
+        ("doctor", Some(matches)) => print_doctor(matches),
+        ("compare", Some(matches)) => compare_clients(matches),
+        ("schema", Some(matches)) => print_schema(matches),
+        ("diff", Some(matches)) => diff_stb(matches),
+        ("encoding-report", Some(matches)) => encoding_report(matches),
+        ("grep", Some(matches)) => grep_files(matches),
+        ("merge", Some(matches)) => merge_stb(matches, report),
+        ("join", Some(matches)) => join_tables(matches, report),
+        ("codegen", Some(matches)) => generate_code(matches, report),
+        ("stb", Some(matches)) => match matches.subcommand() {
+            ("set", Some(matches)) => stb_set(matches, report),
+            ("insert-row", Some(matches)) => stb_insert_row(matches, report),
+            ("append-row", Some(matches)) => stb_insert_row(matches, report),
+            ("delete-row", Some(matches)) => stb_delete_row(matches, report),
+            ("copy-row", Some(matches)) => stb_copy_row(matches, report),
+            ("to-sqlite", Some(matches)) => stb_to_sqlite(matches, report),
+            ("from-sqlite", Some(matches)) => stb_from_sqlite(matches, report),
+            _ => bail!("Missing stb subcommand"),
+        },
+        ("stl", Some(matches)) => match matches.subcommand() {
+            ("to-po", Some(matches)) => stl_to_po(matches, report),
+            ("from-po", Some(matches)) => stl_from_po(matches, report),
+            ("report", Some(matches)) => stl_report(matches),
+            ("duplicates", Some(matches)) => stl_duplicates(matches),
+            ("merge", Some(matches)) => stl_merge(matches, report),
+            _ => bail!("Missing stl subcommand"),
+        },
+        ("tsi", Some(matches)) => match matches.subcommand() {
+            ("atlas", Some(matches)) => tsi_atlas(matches, report),
+            ("build", Some(matches)) => tsi_build(matches, report),
+            _ => bail!("Missing tsi subcommand"),
+        },
+        ("zsc", Some(matches)) => match matches.subcommand() {
+            ("bundle", Some(matches)) => zsc_bundle(matches, report),
+            ("gltf", Some(matches)) => zsc_gltf(matches, report),
+            ("materials", Some(matches)) => zsc_materials(matches, report),
+            _ => bail!("Missing zsc subcommand"),
+        },
+        ("zmd", Some(matches)) => match matches.subcommand() {
+            ("viz", Some(matches)) => zmd_viz(matches, report),
+            _ => bail!("Missing zmd subcommand"),
+        },
+        ("zmo", Some(matches)) => match matches.subcommand() {
+            ("resample", Some(matches)) => zmo_resample(matches, report),
+            ("events", Some(matches)) => zmo_frame_events(matches, report),
+            ("root-motion", Some(matches)) => zmo_root_motion(matches, report),
+            _ => bail!("Missing zmo subcommand"),
+        },
+        ("zms", Some(matches)) => match matches.subcommand() {
+            ("merge", Some(matches)) => zms_merge(matches, report),
+            ("simplify", Some(matches)) => zms_simplify(matches, report),
+            ("optimize", Some(matches)) => zms_optimize(matches, report),
+            ("gltf", Some(matches)) => zms_gltf(matches, report),
+            _ => bail!("Missing zms subcommand"),
+        },
+        ("patch", Some(matches)) => match matches.subcommand() {
+            ("apply", Some(matches)) => apply_patch(matches, report),
+            _ => bail!("Missing patch subcommand"),
+        },
+        _ => {
+            error!("ROSE Online Converter. Run with `--help` for more info.");
+            exit(1);
+        }
+    }
+}
+
+/// Run the subcommands defined by a profile in the config file. Flags given
+/// on the command line are applied on top of the profile.
+fn run_profile(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let config_path = Path::new(matches.value_of("config").unwrap_or(CONFIG_FILE));
+    let profile_name = matches.value_of("profile").unwrap_or_default();
+    let options = ConvertOptions::from_matches(matches);
+
+    let config = Config::from_path(config_path)?;
+    let profile = config.profile(profile_name)?;
+    let base_dir = config_path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut failed = false;
+    for line in profile.command_lines(base_dir)? {
+        let mut args = vec![String::from(crate_name!())];
+        args.extend(line);
+
+        for (name, flag) in &[
+            ("dry_run", "--dry-run"),
+            ("fail_fast", "--fail-fast"),
+            ("keep_going", "--keep-going"),
+            ("force", "--force"),
+            ("skip_existing", "--skip-existing"),
+            ("backup", "--backup"),
+            ("incremental", "--incremental"),
+            ("pretty", "--pretty"),
+            ("compact", "--compact"),
+        ] {
+            if matches.is_present(name) && !args.iter().any(|a| a == flag) {
+                args.push(String::from(*flag));
+            }
+        }
+
+        info!("Running profile {}: {}", profile_name, args[1..].join(" "));
+        let profile_matches = build_cli().get_matches_from_safe(&args)?;
+        if let Err(e) = run_command(&profile_matches, report) {
+            error!("Error occured: {}", e);
+            failed = true;
+
+            if options.fail_fast || profile.fail_fast {
+                break;
+            }
+        }
+    }
+
+    if failed {
+        bail!("Failed to run profile: {}", profile_name);
+    }
+    Ok(())
+}
+
+fn create_output_dir(out_dir: &Path) -> Result<(), Error> {
+    if let Err(e) = fs::create_dir_all(&out_dir) {
+        bail!(
+            "Error creating output directory {}: {}",
+            out_dir.to_str().unwrap_or(""),
+            e
+        );
+    }
+    Ok(())
+}
+
+/// Print a file that would be read in a dry run to stderr, keeping stdout for
+/// the report
+fn dry_run_read(path: &Path) {
+    eprintln!("[dry-run] read:  {}", path.display());
+}
+
+/// Print a file that would be written in a dry run to stderr
+fn dry_run_write(path: &Path) {
+    eprintln!("[dry-run] write: {}", path.display());
+}
+
+/// Check whether the output file `out` can be written, backing it up first if
+/// `--backup` is set. Returns false if the file exists and should be skipped.
+fn prepare_output(out: &Path, options: &ConvertOptions) -> Result<bool, Error> {
+    if !out.exists() {
+        return Ok(true);
+    }
+
+    if options.skip_existing {
+        info!("Skipping existing file: {}", out.display());
+        return Ok(false);
+    }
+
+    if options.backup {
+        backup_file(out, options)?;
+        return Ok(true);
+    }
+
+    if !options.force {
+        bail!(
+            "Output file already exists, use --force to overwrite: {}",
+            out.display()
+        );
+    }
+    Ok(true)
+}
+
+/// Copy `path` to `<path>.bak`
+fn backup_file(path: &Path, options: &ConvertOptions) -> Result<(), Error> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+
+    if options.dry_run {
+        eprintln!("[dry-run] backup: {}", backup.display());
+    } else {
+        fs::copy(path, &backup)?;
+        debug!("Backed up {} to {}", path.display(), backup.display());
+    }
+    Ok(())
+}
+
+/// Lowercase extension of a path, empty if it has none
+fn lowercase_extension(path: &Path) -> String {
+    path.extension()
+        .unwrap_or_default()
+        .to_str()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// A file to convert
+struct InputFile {
+    path: PathBuf,
+    /// Directory of the file relative to the input directory it was found
+    /// in, empty for files passed explicitly
+    dir: PathBuf,
+}
+
+impl AsRef<Path> for InputFile {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Expand the input paths into a list of files. Directories are walked
+/// recursively and only files accepted by `filter` are included, files passed
+/// explicitly are always included.
+fn collect_inputs<'a, I, F>(paths: I, filter: F) -> Result<Vec<InputFile>, Error>
+where
+    I: IntoIterator<Item = &'a str>,
+    F: Fn(&Path) -> bool,
+{
+    fn walk_dir<F: Fn(&Path) -> bool>(
+        root: &Path,
+        dir: &Path,
+        filter: &F,
+        files: &mut Vec<InputFile>,
+    ) -> Result<(), Error> {
+        let mut entries = fs::read_dir(dir)?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?;
+        entries.sort();
+
+        for path in entries {
+            if path.is_dir() {
+                walk_dir(root, &path, filter, files)?;
+            } else if filter(&path) {
+                files.push(InputFile {
+                    dir: dir.strip_prefix(root)?.to_path_buf(),
+                    path,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    for path in paths {
+        let path = PathBuf::from(path);
+        if path.is_dir() {
+            walk_dir(&path, &path, &filter, &mut files)?;
+        } else {
+            files.push(InputFile {
+                path,
+                dir: PathBuf::new(),
+            });
+        }
+    }
+    Ok(files)
+}
+
+/// Convert every input, recording the results in the report. `convert`
+/// returns the file type of the input and the output paths. Failures are
+/// logged as they happen and the batch stops at the first one if
+/// `--fail-fast` is set.
+fn convert_batch<T, F>(
+    inputs: &[T],
+    options: &ConvertOptions,
+    report: &mut Report,
+    mut convert: F,
+) -> Result<(), Error>
+where
+    T: AsRef<Path>,
+    F: FnMut(&T) -> Result<(String, Vec<PathBuf>), Error>,
+{
+    let mut cache = match &options.cache_dir {
+        Some(dir) => Some(Cache::load(dir)?),
+        None => None,
+    };
+    let fingerprint = options.fingerprint();
+
+    let mut failed = 0;
+    for input in inputs {
+        if let Some(cache) = &mut cache {
+            let path = input.as_ref();
+            if cache.is_fresh(path, &fingerprint)? {
+                debug!("Skipping unchanged file: {}", path.display());
+                if let Some(entry) = cache.get(path) {
+                    report.success(path, &entry.file_type, entry.outputs.clone());
+                }
+                continue;
+            }
+        }
+
+        let res = convert(input);
+        let input = input.as_ref();
+        match res {
+            Ok((file_type, outputs)) => {
+                if let Some(cache) = &mut cache {
+                    if !options.dry_run {
+                        cache.update(input, &file_type, &fingerprint, &outputs)?;
+                    }
+                }
+                report.success(input, &file_type, outputs);
+            }
+            Err(e) => {
+                error!("Error occured: {}", e);
+                error!("\t{}", input.display());
+                report.failure(input, &e);
+                failed += 1;
+
+                if let Some(cache) = &mut cache {
+                    cache.remove(input);
+                }
+
+                if options.fail_fast {
+                    break;
+                }
+            }
+        }
+    }
+
+    if let (Some(cache), Some(dir)) = (&cache, &options.cache_dir) {
+        if !options.dry_run {
+            cache.save(dir)?;
+        }
+    }
+
+    if failed > 0 && options.fail_fast {
+        bail!("Stopping after first failure");
+    }
+
+    if failed > 0 {
+        bail!("Failed to convert {} of {} files", failed, inputs.len());
+    }
+    Ok(())
+}
+
+fn serialize(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let input_type = matches.value_of("type").unwrap_or_default();
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        let extension = lowercase_extension(p);
+        if input_type.is_empty() {
+            SERIALIZE_VALUES.contains(&extension.as_str())
+        } else if input_type == "wstb" {
+            extension == "stb"
+        } else {
+            extension == input_type
+        }
+    })?;
+
+    convert_batch(
+        &inputs,
+        &ConvertOptions::from_matches(matches),
+        report,
+        |input| {
+            serialize_input(matches, input)
+        },
+    )
+}
+
+/// Serialize a single input, returning its type and the output paths
+fn serialize_input(
+    matches: &ArgMatches,
+    input_file: &InputFile,
+) -> Result<(String, Vec<PathBuf>), Error> {
+    let input = input_file.path.as_path();
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let input_type = matches.value_of("type").unwrap_or_default();
+
+    if !input.exists() {
+        bail!("File does not exist: {}", input.display());
+    }
+
+    let extension = lowercase_extension(input);
+
+    let rose_type = if input_type.is_empty() {
+        if !SERIALIZE_VALUES.contains(&extension.as_str()) {
+            bail!("No type provided and unrecognized extension");
+        }
+        String::from(&extension)
+    } else {
+        String::from(input_type)
+    };
+
+    // If the keep-extension flag is present we prepend the original extension
+    // e.g. list_zone.stb.json
+    let name_format = match &options.name_format {
+        Some(name_format) => name_format.clone(),
+        None if matches.is_present("keep-extension") => {
+            NameFormat::new("{stem}.{src_ext:lower}.{ext}")?
+        }
+        None => NameFormat::default(),
+    };
+
+    let out = out_dir.join(name_format.format(
+        input,
+        &input_file.dir,
+        &rose_type,
+        serialized_extension(&rose_type, &options),
+    ));
+
+    let outputs = serialize_file(input, &rose_type, &out, &options)?;
+    Ok((rose_type, outputs))
+}
+
+/// Extension of the serialized representation of a ROSE file type
+fn serialized_extension(rose_type: &str, options: &ConvertOptions) -> &'static str {
+    match rose_type {
+        "stb" | "wstb" if options.stb_json => "json",
+        "stb" | "wstb" | "stl" if options.xlsx => "xlsx",
+        "stb" | "wstb" | "stl" => "csv",
+        "aip" | "qsd" if options.script => "script",
+        _ => options.format.extension(),
+    }
+}
+
+/// Extension of a deserialized ROSE file type, wide-string STB files are
+/// still `.stb` files
+fn deserialized_extension(rose_type: &str) -> &str {
+    match rose_type {
+        "wstb" => "stb",
+        _ => rose_type,
+    }
+}
+
+/// Validate an STL language argument
+fn validate_language(s: String) -> Result<(), String> {
+    s.parse::<StringTableLanguage>()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Whether the extension is of a serialized file (CSV, JSON, YAML, ...)
+fn is_serialized_extension(extension: &str) -> bool {
+    extension == "csv"
+        || extension == "xlsx"
+        || extension == "script"
+        || Format::from_name(extension).is_some()
+}
+
+/// Serialize an STB to CSV, typed JSON with `--format json` or a workbook with
+/// `--format xlsx`, naming the columns if it has a schema
+fn serialize_stb(stb: &STB, input: &Path, options: &ConvertOptions) -> Result<Vec<u8>, Error> {
+    let schema = StbSchema::find(&options.schema_dir, input)?;
+
+    if options.stb_json {
+        let typed = TypedStb::new(stb, &schema.unwrap_or_default())?;
+        let json = if options.pretty {
+            typed.to_json()?
+        } else {
+            typed.to_json_compact()?
+        };
+        return Ok(json.into_bytes());
+    }
+
+    let csv = match schema {
+        Some(schema) => schema.to_csv(stb, options.headers, &options.csv)?,
+        None => stb_csv::write(stb, &stb.headers, options.headers, &options.csv)?,
+    };
+
+    if options.xlsx {
+        let name = input.file_stem().unwrap_or_default().to_string_lossy();
+        let workbook = Workbook {
+            sheets: vec![Sheet::from_csv(&name, &csv, &options.csv)?],
+        };
+        return workbook.to_bytes();
+    }
+    Ok(csv.into_bytes())
+}
+
+/// Deserialize an STB from the first sheet of a workbook, which has the same
+/// rows as the CSV
+fn stb_from_xlsx(data: &[u8], input: &Path, options: &ConvertOptions) -> Result<STB, Error> {
+    let workbook = Workbook::from_bytes(data)?;
+    match workbook.sheets.first() {
+        Some(sheet) => stb_from_csv(&sheet.to_csv(&options.csv)?, input, options),
+        None => bail!("Workbook has no sheets"),
+    }
+}
+
+/// Deserialize an STB from CSV, mapping the columns by name if it has a schema
+fn stb_from_csv(data: &str, input: &Path, options: &ConvertOptions) -> Result<STB, Error> {
+    match StbSchema::find(&options.schema_dir, input)? {
+        Some(schema) => schema.from_csv(data, &options.csv),
+        None => STB::from_csv_with(data, &options.csv),
+    }
+}
+
+/// Serialize the ROSE file at `input` as `rose_type` and write it to `out`,
+/// returning the output paths. ZMS files split by `--bone-limit` are written
+/// next to `out` as parts.
+fn serialize_file(
+    input: &Path,
+    rose_type: &str,
+    out: &Path,
+    options: &ConvertOptions,
+) -> Result<Vec<PathBuf>, Error> {
+    if !prepare_output(out, options)? {
+        return Ok(vec![out.to_path_buf()]);
+    }
+
+    if options.dry_run {
+        dry_run_read(input);
+        dry_run_write(out);
+        return Ok(vec![out.to_path_buf()]);
+    }
+
+    let start = Instant::now();
+    let format = options.format;
+
+    let data = match rose_type {
+        // CSV
+        "stb" => serialize_stb(&STB::from_path(input)?, input, options)?,
+        "stl" if options.xlsx && (!options.languages.is_empty() || options.language.is_some()) => {
+            bail!("STL languages can only be selected when serializing to CSV")
+        }
+        "stl" if options.xlsx => Workbook::from_stl(&STL::from_path(input)?)?.to_bytes()?,
+        "stl" => {
+            let stl = STL::from_path(input)?;
+            match options.language {
+                Some(language) => stl_csv::write_language(&stl, language, &options.csv)?,
+                None => stl_csv::write(&stl, &options.languages, &options.csv)?,
+            }
+            .into_bytes()
+        }
+        // JSON/YAML/MessagePack/CBOR
+        "aip" if options.script => script::aip_to_script(&AIP::from_path(input)?).into_bytes(),
+        "aip" => format.serialize(&AIP::from_path(input)?, options.pretty)?,
+        "him" => format.serialize(&HIM::from_path(input)?, options.pretty)?,
+        "idx" => format.serialize(&IDX::from_path(input)?, options.pretty)?,
+        "ifo" => format.serialize(&IFO::from_path(input)?, options.pretty)?,
+        "lit" => format.serialize(&LIT::from_path(input)?, options.pretty)?,
+        "qsd" if options.script => script::qsd_to_script(&QSD::from_path(input)?).into_bytes(),
+        "qsd" => format.serialize(&QSD::from_path(input)?, options.pretty)?,
+        "til" => format.serialize(&TIL::from_path(input)?, options.pretty)?,
+        "tsi" => format.serialize(&TSI::from_path(input)?, options.pretty)?,
+        "zmd" => format.serialize(&ZMD::from_path(input)?, options.pretty)?,
+        "zmo" => format.serialize(&ZMO::from_path(input)?, options.pretty)?,
+        "zms" => {
+            let mut zms = ZMS::from_path(input)?;
+            process_zms(&mut zms, input, options)?;
+            let mut parts = split_zms(zms, input, options)?;
+            if parts.len() > 1 {
+                return write_zms_parts(&mut parts, out, options, |part, path| {
+                    let mut f = File::create(path)?;
+                    f.write_all(&format.serialize(part, options.pretty)?)?;
+                    Ok(())
+                });
+            }
+            format.serialize(&parts[0], options.pretty)?
+        }
+        "zon" => format.serialize(&ZON::from_path(input)?, options.pretty)?,
+        "zsc" => format.serialize(&ZSC::from_path(input)?, options.pretty)?,
+        "wstb" => {
+            let f = File::open(input)?;
+            let mut reader = RoseReader::new(f);
+            reader.set_wide_strings(true);
+            let mut stb: STB = RoseFile::new();
+            let res = stb.read(&mut reader);
+            // The option is shared by every reader on this thread
+            reader.set_wide_strings(false);
+            res?;
+            serialize_stb(&stb, input, options)?
+        }
+        _ => bail!("Unsupported file type: {}", rose_type),
+    };
+
+    if let Some(p) = out.parent() {
+        create_output_dir(p)?;
+    }
+
+    let mut f = File::create(out)?;
+    f.write_all(&data)?;
+
+    debug!(
+        "Serialized {} to {} in {:.2?}",
+        input.display(),
+        out.display(),
+        start.elapsed()
+    );
+
+    Ok(vec![out.to_path_buf()])
+}
+
+fn deserialize(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let input = matches.value_of("input").unwrap_or_default();
+    if Path::new(input).is_dir() && matches.is_present("output") {
+        bail!("An output path can't be used when deserializing a directory");
+    }
+
+    let inputs = collect_inputs(iter::once(input), |p| {
+        is_serialized_extension(&lowercase_extension(p))
+    })?;
+
+    convert_batch(
+        &inputs,
+        &ConvertOptions::from_matches(matches),
+        report,
+        |input| {
+            let outputs = deserialize_input(matches, input)?;
+            Ok((matches.value_of("type").unwrap_or_default().to_string(), outputs))
+        },
+    )
+}
+
+/// Deserialize a single input, returning the output paths
+fn deserialize_input(matches: &ArgMatches, input_file: &InputFile) -> Result<Vec<PathBuf>, Error> {
+    let input = input_file.path.as_path();
+    let options = ConvertOptions::from_matches(matches);
+    let filetype = matches.value_of("type").unwrap_or_default();
+
+    if !input.exists() {
+        bail!("File does not exist: {}", input.display());
+    }
+
+    // Use the output arg if it's set, otherwise use the output directory option
+    let out = if let Some(s) = matches.value_of("output") {
+        PathBuf::from(s)
+    } else {
+        let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+        let name_format = options.name_format.clone().unwrap_or_default();
+        let extension = deserialized_extension(filetype);
+        out_dir.join(name_format.format(input, &input_file.dir, filetype, extension))
+    };
+
+    deserialize_file(input, filetype, &out, &options)
+}
+
+/// Deserialize the serialized file at `input` as `filetype` and write the
+/// ROSE file to `out`, returning the output paths. The format is detected
+/// from the file extension. ZMS files split by `--bone-limit` are written
+/// next to `out` as parts.
+fn deserialize_file(
+    input: &Path,
+    filetype: &str,
+    out: &Path,
+    options: &ConvertOptions,
+) -> Result<Vec<PathBuf>, Error> {
+    if let Some(language) = options.language {
+        if filetype != "stl" {
+            bail!("Only STL files can be patched with a single language");
+        }
+        patch_stl_language(input, language, out, options)?;
+        return Ok(vec![out.to_path_buf()]);
+    }
+
+    if !prepare_output(out, options)? {
+        return Ok(vec![out.to_path_buf()]);
+    }
+
+    if options.dry_run {
+        dry_run_read(input);
+        dry_run_write(out);
+        return Ok(vec![out.to_path_buf()]);
+    }
+
+    if let Some(p) = out.parent() {
+        create_output_dir(p)?;
+    }
+
+    let start = Instant::now();
+
+    let mut data = Vec::new();
+
+    let mut file = File::open(input)?;
+    file.read_to_end(&mut data)?;
+
+    let format = Format::from_name(&lowercase_extension(input)).unwrap_or(options.format);
+
+    match filetype {
+        "stb" | "wstb" => {
+            let mut stb = match lowercase_extension(input).as_str() {
+                "csv" => stb_from_csv(str::from_utf8(&data)?, input, options)?,
+                "xlsx" => stb_from_xlsx(&data, input, options)?,
+                _ => bail!("STB files can only be deserialized from CSV or XLSX"),
+            };
+            let mut writer = RoseWriter::new(File::create(out)?);
+            stb.write_with(&mut writer, filetype == "wstb")?;
+        }
+        "stl" if lowercase_extension(input) == "xlsx" => {
+            Workbook::from_bytes(&data)?.to_stl()?.write_to_path(out)?
+        }
+        "stl" => STL::from_csv_with(str::from_utf8(&data)?, &options.csv)?.write_to_path(out)?,
+        "aip" => {
+            let mut aip = match lowercase_extension(input).as_str() {
+                "script" => script::aip_from_script(str::from_utf8(&data)?)?,
+                _ => format.deserialize::<AIP>(&data)?,
+            };
+            check_aip(&aip, input, options)?;
+            aip.write_to_path(out)?
+        }
+        "idx" => format.deserialize::<IDX>(&data)?.write_to_path(out)?,
+        "lit" => format.deserialize::<IDX>(&data)?.write_to_path(out)?,
+        "qsd" => {
+            let mut qsd = match lowercase_extension(input).as_str() {
+                "script" => script::qsd_from_script(str::from_utf8(&data)?)?,
+                _ => format.deserialize::<QSD>(&data)?,
+            };
+            check_qsd(&qsd, input, options)?;
+            qsd.write_to_path(out)?
+        }
+        "zms" => {
+            let mut zms = format.deserialize::<ZMS>(&data)?;
+            process_zms(&mut zms, input, options)?;
+            if !options.keep_bounds && bounds::recompute_mesh(&mut zms) {
+                debug!("Recomputed the bounding box of {}", input.display());
+            }
+            let mut parts = split_zms(zms, input, options)?;
+            if parts.len() > 1 {
+                return write_zms_parts(&mut parts, out, options, |part, path| {
+                    part.write_to_path(path)
+                });
+            }
+            parts[0].write_to_path(out)?
+        }
+        "zsc" => {
+            let mut zsc = format.deserialize::<ZSC>(&data)?;
+            if !options.keep_bounds {
+                recompute_zsc_bounds(&mut zsc, input, options);
+            }
+            zsc.write_to_path(out)?
+        }
+        _ => bail!("Unsupported file type: {}", filetype),
+    }
+
+    debug!(
+        "Deserialized {} to {} in {:.2?}",
+        input.display(),
+        out.display(),
+        start.elapsed()
+    );
+
+    Ok(vec![out.to_path_buf()])
+}
+
+/// Clean up the skin weights of a ZMS if `--max-influences` is set, weld its
+/// vertices if `--weld` is set, then recompute its normals if
+/// `--recompute-normals` is set
+fn process_zms(zms: &mut ZMS, input: &Path, options: &ConvertOptions) -> Result<(), Error> {
+    if let Some(max) = options.max_influences {
+        let changed = zms_skin::limit_influences(zms, max);
+        debug!(
+            "Cleaned up the skin weights of {} vertices of {}",
+            changed,
+            input.display()
+        );
+    }
+    if let Some(tolerance) = options.weld_tolerance {
+        let removed = zms_weld::weld(zms, tolerance);
+        debug!("Welded {} vertices of {}", removed, input.display());
+    }
+    if let Some(angle) = options.smoothing_angle {
+        let added = zms_normals::recompute_normals(zms, angle)?;
+        if added > 0 {
+            debug!(
+                "Split {} vertices along sharp edges of {}",
+                added,
+                input.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Check the opcodes and operands of an AI pattern against their layouts, and
+/// its references against the STB and QSD files of the client directory
+fn check_aip(aip: &AIP, input: &Path, options: &ConvertOptions) -> Result<(), Error> {
+    let mut problems = aip_check::check_layouts(aip);
+
+    let client_dir = options
+        .client_dir
+        .clone()
+        .or_else(|| vfs::find_client_dir(input));
+    match &client_dir {
+        Some(client_dir) => {
+            let tables = AiTables::from_client(&Client::open(client_dir)?);
+            if tables.is_empty() {
+                warn!(
+                    "No STB or QSD files in {} to check the references of {} against",
+                    client_dir.display(),
+                    input.display()
+                );
+            }
+            problems.extend(tables.check(aip));
+        }
+        None => warn!(
+            "No client directory to check the references of {} against, use --client",
+            input.display()
+        ),
+    }
+
+    for problem in &problems {
+        error!("{}: {}", input.display(), problem);
+    }
+    if !problems.is_empty() {
+        bail!(
+            "{} conditions and actions of {} have unknown opcodes, operands or references",
+            problems.len(),
+            input.display()
+        );
+    }
+    Ok(())
+}
+
+/// Check the opcodes and operands of a quest against their layouts, and its
+/// references against the STB and QSD files of the client directory
+fn check_qsd(qsd: &QSD, input: &Path, options: &ConvertOptions) -> Result<(), Error> {
+    let mut problems = qsd_check::check_layouts(qsd);
+
+    let client_dir = options
+        .client_dir
+        .clone()
+        .or_else(|| vfs::find_client_dir(input));
+    match &client_dir {
+        Some(client_dir) => {
+            let tables = QuestTables::from_client(&Client::open(client_dir)?);
+            if tables.is_empty() {
+                warn!(
+                    "No STB or QSD files in {} to check the references of {} against",
+                    client_dir.display(),
+                    input.display()
+                );
+            }
+            problems.extend(tables.check(qsd));
+        }
+        None => warn!(
+            "No client directory to check the references of {} against, use --client",
+            input.display()
+        ),
+    }
+
+    for problem in &problems {
+        error!("{}: {}", input.display(), problem);
+    }
+    if !problems.is_empty() {
+        bail!(
+            "{} conditions and rewards of {} have unknown opcodes, operands or references",
+            problems.len(),
+            input.display()
+        );
+    }
+    Ok(())
+}
+
+/// Recompute the bounds of the objects of a ZSC from its meshes in the
+/// client directory, keeping the bounds of objects with missing meshes
+fn recompute_zsc_bounds(zsc: &mut ZSC, input: &Path, options: &ConvertOptions) {
+    let client_dir = match &options.client_dir {
+        Some(dir) => dir.clone(),
+        None => match vfs::find_client_dir(input) {
+            Some(dir) => dir,
+            None => {
+                warn!(
+                    "No client directory for the meshes of {}, keeping its bounds, use --client",
+                    input.display()
+                );
+                return;
+            }
+        },
+    };
+    let meshes: Vec<Option<ZMS>> = zsc
+        .meshes
+        .iter()
+        .map(|path| {
+            vfs::resolve_client_path(&client_dir, &path.to_string_lossy())
+                .and_then(|path| ZMS::from_path(&path).ok())
+        })
+        .collect();
+    let missing = meshes.iter().filter(|zms| zms.is_none()).count();
+    if missing > 0 {
+        warn!(
+            "{} meshes of {} not found in {}, their objects keep their bounds",
+            missing,
+            input.display(),
+            client_dir.display()
+        );
+    }
+    let changed = bounds::recompute_scene(zsc, |id| {
+        meshes.get(id as usize).and_then(Option::as_ref)
+    });
+    debug!(
+        "Recomputed the bounds of {} objects of {}",
+        changed,
+        input.display()
+    );
+}
+
+/// Split a ZMS into parts within `--bone-limit` if it's set
+fn split_zms(zms: ZMS, input: &Path, options: &ConvertOptions) -> Result<Vec<ZMS>, Error> {
+    let limit = match options.bone_limit {
+        Some(limit) => limit,
+        None => return Ok(vec![zms]),
+    };
+    let bones = zms.bones.len();
+    let parts = zms_skin::split_bones(zms, limit)?;
+    if parts.len() > 1 {
+        info!(
+            "Split {} with {} bones into {} parts",
+            input.display(),
+            bones,
+            parts.len()
+        );
+    }
+    Ok(parts)
+}
+
+/// Write the parts of a split ZMS as `<name>_<n>` next to `out`, returning
+/// their paths
+fn write_zms_parts<F>(
+    parts: &mut [ZMS],
+    out: &Path,
+    options: &ConvertOptions,
+    mut write: F,
+) -> Result<Vec<PathBuf>, Error>
+where
+    F: FnMut(&mut ZMS, &Path) -> Result<(), Error>,
+{
+    if let Some(p) = out.parent() {
+        create_output_dir(p)?;
+    }
+    let file_name = out.file_name().unwrap_or_default().to_string_lossy();
+    // Number the parts before every extension, e.g. `MALE_1.zms.json`
+    let (name, extension) = match file_name.find('.') {
+        Some(i) => file_name.split_at(i),
+        None => (file_name.as_ref(), ""),
+    };
+    let mut outputs = Vec::with_capacity(parts.len());
+    for (i, part) in parts.iter_mut().enumerate() {
+        let path = out.with_file_name(format!("{}_{}{}", name, i, extension));
+        if prepare_output(&path, options)? {
+            write(part, &path)?;
+        }
+        outputs.push(path);
+    }
+    Ok(outputs)
+}
+
+/// Patch a single language from the compact CSV at `input` into the existing
+/// STL at `out`
+fn patch_stl_language(
+    input: &Path,
+    language: StringTableLanguage,
+    out: &Path,
+    options: &ConvertOptions,
+) -> Result<(), Error> {
+    if lowercase_extension(input) != "csv" {
+        bail!("STL languages can only be patched from CSV");
+    }
+    if !out.exists() {
+        bail!("STL to patch does not exist: {}", out.display());
+    }
+
+    if options.backup {
+        backup_file(out, options)?;
+    }
+
+    if options.dry_run {
+        dry_run_read(input);
+        dry_run_read(out);
+        dry_run_write(out);
+        return Ok(());
+    }
+
+    let start = Instant::now();
+
+    let mut stl = STL::from_path(out)?;
+    let csv = fs::read_to_string(input)?;
+    let count = stl_csv::patch_language(&mut stl, language, &csv, &options.csv)?;
+    stl.write_to_path(out)?;
+
+    debug!(
+        "Patched {} {} rows of {} from {} in {:.2?}",
+        count,
+        language,
+        out.display(),
+        input.display(),
+        start.elapsed()
+    );
+
+    Ok(())
+}
+
+/// Conversion performed on a file picked up by the watcher
+enum WatchAction {
+    Serialize(String),
+    Deserialize(String),
+}
+
+/// Watch a directory and convert files as they change:
+/// - ROSE files are serialized, keeping their original extension (e.g. list_zone.stb.csv)
+/// - Serialized files with a double extension are deserialized (e.g. list_zone.stb.csv)
+/// - Rules (`<ext>:<type>`) override the above for files with the given extension
+fn watch(matches: &ArgMatches) -> Result<(), Error> {
+    let watch_dir = Path::new(matches.value_of("watch_dir").unwrap_or_default());
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let mut options = ConvertOptions::from_matches(matches);
+
+    // Changed files are converted again so their outputs are expected to be
+    // overwritten
+    if !options.skip_existing {
+        options.force = true;
+    }
+
+    if !watch_dir.is_dir() {
+        bail!("Watch path is not a directory: {}", watch_dir.display());
+    }
+
+    let mut rules: HashMap<String, String> = HashMap::new();
+    for rule in matches.values_of("rule").unwrap_or_default() {
+        let parts: Vec<&str> = rule.split(':').collect();
+        if parts.len() != 2 {
+            bail!("Invalid rule, expected <ext>:<type>: {}", rule);
+        }
+
+        let ext = parts[0].trim_start_matches('.').to_lowercase();
+        let rose_type = parts[1].to_lowercase();
+        if !SERIALIZE_VALUES.contains(&rose_type.as_str())
+            && !DESERIALIZE_VALUES.contains(&rose_type.as_str())
+        {
+            bail!("Unsupported file type in rule: {}", rule);
+        }
+
+        rules.insert(ext, rose_type);
+    }
+
+    if !options.dry_run {
+        create_output_dir(out_dir)?;
+    }
+
+    // Canonicalize both paths so the outputs are under the same root as the
+    // paths of the events
+    let watch_dir = watch_dir.canonicalize()?;
+    let out_dir = if out_dir.exists() {
+        out_dir.canonicalize()?
+    } else {
+        env::current_dir()?.join(out_dir)
+    };
+
+    let (tx, rx) = channel();
+    let mut watcher = notify::watcher(tx, Duration::from_millis(500))?;
+    watcher.watch(&watch_dir, RecursiveMode::Recursive)?;
+
+    info!("Watching for changes in: {}", watch_dir.display());
+
+    // Modification times of the files written by the watcher, so their events
+    // are ignored instead of converting them again. The output directory can
+    // be the watched directory, e.g. the client folder.
+    let mut written: HashMap<PathBuf, SystemTime> = HashMap::new();
+
+    loop {
+        let path = match rx.recv()? {
+            DebouncedEvent::Create(p) | DebouncedEvent::Write(p) | DebouncedEvent::Rename(_, p) => p,
+            DebouncedEvent::Error(e, p) => {
+                error!("Watch error: {}", e);
+                if let Some(p) = p {
+                    error!("\t{}", p.display());
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        if !path.is_file() {
+            continue;
+        }
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        if modified.is_some() && written.get(&path) == modified.as_ref() {
+            continue;
+        }
+
+        let action = match watch_action(&path, &rules) {
+            Some(action) => action,
+            None => continue,
+        };
+
+        // Keep the directories of the file below the watched directory so
+        // files with the same name don't overwrite each other
+        let relative = path.strip_prefix(&watch_dir).unwrap_or(&path);
+        let out_base = out_dir.join(relative.parent().unwrap_or_else(|| Path::new("")));
+        let file_name = path.file_name().unwrap_or_default();
+        let res = match action {
+            WatchAction::Serialize(rose_type) => {
+                let mut new_extension = path
+                    .extension()
+                    .unwrap_or_default()
+                    .to_str()
+                    .unwrap_or_default()
+                    .to_lowercase();
+                new_extension.push('.');
+                new_extension.push_str(serialized_extension(&rose_type, &options));
+
+                let out = out_base.join(file_name).with_extension(new_extension);
+                info!("Serializing {} to {}", path.display(), out.display());
+                serialize_file(&path, &rose_type, &out, &options)
+            }
+            WatchAction::Deserialize(rose_type) => {
+                let stem = path.file_stem().unwrap_or_default();
+                let out = out_base
+                    .join(stem)
+                    .with_extension(deserialized_extension(&rose_type));
+                info!("Deserializing {} to {}", path.display(), out.display());
+                deserialize_file(&path, &rose_type, &out, &options)
+            }
+        };
+
+        match res {
+            Ok(outputs) => {
+                for out in outputs {
+                    if let Ok(modified) = fs::metadata(&out).and_then(|m| m.modified()) {
+                        written.insert(out, modified);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Error occured: {}", e);
+                error!("\t{}", path.display());
+            }
+        }
+    }
+}
+
+/// Determine what to do with a changed file based on its extension(s)
+fn watch_action(path: &Path, rules: &HashMap<String, String>) -> Option<WatchAction> {
+    let extension = lowercase_extension(path);
+    let is_serialized = is_serialized_extension(&extension);
+
+    if let Some(rose_type) = rules.get(&extension) {
+        return if is_serialized {
+            Some(WatchAction::Deserialize(rose_type.clone()))
+        } else {
+            Some(WatchAction::Serialize(rose_type.clone()))
+        };
+    }
+
+    if is_serialized {
+        let inner_extension = lowercase_extension(Path::new(path.file_stem()?));
+        if DESERIALIZE_VALUES.contains(&inner_extension.as_str()) {
+            return Some(WatchAction::Deserialize(inner_extension));
+        }
+        return None;
+    }
+
+    if SERIALIZE_VALUES.contains(&extension.as_str()) {
+        return Some(WatchAction::Serialize(extension));
+    }
+
+    None
+}
+
+/// Print a summary of every input
+fn print_info(matches: &ArgMatches) -> Result<(), Error> {
+    let input_type = matches.value_of("type").unwrap_or_default();
+    let inputs: Vec<&str> = matches.values_of("input").unwrap_or_default().collect();
+    for (i, input) in inputs.iter().enumerate() {
+        let path = Path::new(input);
+        let rose_type = if input_type.is_empty() {
+            let extension = lowercase_extension(path);
+            if !SERIALIZE_VALUES.contains(&extension.as_str()) {
+                bail!("No type provided and unrecognized extension: {}", input);
+            }
+            extension
+        } else {
+            String::from(input_type)
+        };
+
+        let info = file_info::read(path, &rose_type)
+            .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+        if inputs.len() > 1 {
+            if i > 0 {
+                println!();
+            }
+            println!("{}", path.display());
+        }
+        print!("{}", info);
+    }
+    Ok(())
+}
+
+/// Print the statistics of client directories and VFS indexes
+fn print_stats(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    // Validated when parsing the args
+    let largest: usize = matches.value_of("largest").unwrap_or_default().parse()?;
+
+    let mut stats = ClientStats::new(largest);
+    for input_arg in matches.values_of("input").unwrap_or_default() {
+        let input = Path::new(input_arg);
+        if lowercase_extension(input) == "idx" {
+            let idx = IDX::from_path(input)?;
+            for file in idx.file_systems.iter().flat_map(|vfs| vfs.files.iter()) {
+                if !file.is_deleted {
+                    stats.add(&file.filepath.to_string_lossy(), file.size.max(0) as u64);
+                }
+            }
+            let contents = vfs::read_files(input, |p| {
+                ClientStats::reads_contents(&p.to_string_lossy())
+            })?;
+            for (path, data) in contents {
+                let path = path.to_string_lossy();
+                if let Err(e) = stats.add_contents(&path, &data) {
+                    warn!("Skipping {}: {}", path, e);
+                }
+            }
+            continue;
+        }
+
+        for input_file in collect_inputs(iter::once(input_arg), |_| true)? {
+            let name = input_file.path.file_name().unwrap_or_default();
+            let client_path = input_file.dir.join(name).to_string_lossy().into_owned();
+            stats.add(&client_path, fs::metadata(&input_file.path)?.len());
+            if ClientStats::reads_contents(&client_path) {
+                let data = fs::read(&input_file.path)?;
+                if let Err(e) = stats.add_contents(&client_path, &data) {
+                    warn!("Skipping {}: {}", input_file.path.display(), e);
+                }
+            }
+        }
+    }
+
+    if matches.is_present("json") {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&stats)?
+        } else {
+            serde_json::to_string(&stats)?
+        };
+        println!("{}", json);
+    } else {
+        print!("{}", stats);
+    }
+    Ok(())
+}
+
+/// Print the broken references of a client, failing if there are any
+fn validate_client(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let client = Client::open(Path::new(matches.value_of("input").unwrap_or_default()))?;
+    let issues = validate::validate(&client);
+
+    if matches.is_present("json") {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&issues)?
+        } else {
+            serde_json::to_string(&issues)?
+        };
+        println!("{}", json);
+    } else {
+        for issue in &issues {
+            println!("{}", issue);
+        }
+    }
+
+    if !issues.is_empty() {
+        let files: HashSet<&str> = issues.iter().map(|i| i.file.as_str()).collect();
+        bail!("{} issues in {} files", issues.len(), files.len());
+    }
+    info!("No issues in {} files", client.files().count());
+    Ok(())
+}
+
+/// Print the files of a client that nothing references with their size
+fn print_unused(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let client = Client::open(Path::new(matches.value_of("input").unwrap_or_default()))?;
+    let graph = ReferenceGraph::new(&client);
+    for (path, e) in &graph.unreadable {
+        warn!("Unable to read the references of {}: {}", path, e);
+    }
+
+    let unused: Vec<FileSize> = graph
+        .unused(&client)
+        .iter()
+        .map(|f| FileSize {
+            path: f.path.clone(),
+            size: f.size,
+        })
+        .collect();
+    let size: u64 = unused.iter().map(|f| f.size).sum();
+
+    if matches.is_present("json") {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&unused)?
+        } else {
+            serde_json::to_string(&unused)?
+        };
+        println!("{}", json);
+    } else {
+        for file in &unused {
+            println!("{:>12}  {}", stats::format_size(file.size), file.path);
+        }
+    }
+
+    info!(
+        "{} unused files, {} reclaimable",
+        unused.len(),
+        stats::format_size(size)
+    );
+    Ok(())
+}
+
+/// Print the missing paths referenced by the files of a client, by
+/// referencing file
+fn print_missing(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let client = Client::open(Path::new(matches.value_of("input").unwrap_or_default()))?;
+    let graph = ReferenceGraph::new(&client);
+    for (path, e) in &graph.unreadable {
+        warn!("Unable to read the references of {}: {}", path, e);
+    }
+    let missing = graph.missing(&client);
+
+    if matches.is_present("json") {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&missing)?
+        } else {
+            serde_json::to_string(&missing)?
+        };
+        println!("{}", json);
+    } else {
+        for (file, paths) in &missing {
+            println!("{}", file);
+            for path in paths {
+                println!("    {}", path);
+            }
+        }
+    }
+
+    info!(
+        "{} missing paths referenced by {} files",
+        missing.values().map(|paths| paths.len()).sum::<usize>(),
+        missing.len()
+    );
+    Ok(())
+}
+
+/// Print the graph of the files a file of a client depends on
+fn print_deps(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let root = matches.value_of("root").unwrap_or_default();
+    let (client, root) = match matches.value_of("client") {
+        Some(client) => (Client::open(Path::new(client))?, root.to_string()),
+        None => {
+            let path = Path::new(root);
+            let client_dir = match vfs::find_client_dir(path) {
+                Some(dir) => dir,
+                None => bail!(
+                    "No client directory above {}, use --client",
+                    path.display()
+                ),
+            };
+            let root = path
+                .strip_prefix(&client_dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned();
+            (Client::open(&client_dir)?, root)
+        }
+    };
+
+    let root = match references::resolve_root(&client, &root) {
+        Some(file) => file,
+        None => bail!("File or zone not found in the client: {}", root),
+    };
+    let graph = DependencyGraph::new(&client, root);
+    for (path, e) in &graph.unreadable {
+        warn!("Unable to read the references of {}: {}", path, e);
+    }
+
+    if matches.is_present("dot") {
+        print!("{}", graph.to_dot());
+    } else {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&graph)?
+        } else {
+            serde_json::to_string(&graph)?
+        };
+        println!("{}", json);
+    }
+
+    info!(
+        "{} files, {} missing",
+        graph.files.len(),
+        graph.files.iter().filter(|f| f.missing).count()
+    );
+    Ok(())
+}
+
+/// Write the manifest of the files of a client with their checksums
+fn create_checksums(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let input = Path::new(matches.value_of("input").unwrap_or_default());
+    let out = match matches.value_of("output") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(matches.value_of("out_dir").unwrap_or_default())
+            .join(CLIENT_MANIFEST_FILE),
+    };
+
+    let client = Client::open(input)?;
+    if !prepare_output(&out, &options)? {
+        return Ok(());
+    }
+    if options.dry_run {
+        dry_run_read(input);
+        dry_run_write(&out);
+        return Ok(());
+    }
+
+    let manifest = ClientManifest::new(&client)?;
+    if let Some(p) = out.parent() {
+        create_output_dir(p)?;
+    }
+    manifest.write_to_path(&out, options.pretty)?;
+
+    info!(
+        "Wrote the checksums of {} files to {}",
+        manifest.files.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// Print the files of a client that don't match a manifest
+fn verify_checksums(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let manifest =
+        ClientManifest::from_path(Path::new(
+        matches.value_of("manifest_file").unwrap_or_default(),
+    ))?;
+    let client = Client::open(Path::new(matches.value_of("input").unwrap_or_default()))?;
+    let differences = manifest.verify(&client)?;
+
+    if matches.is_present("json") {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&differences)?
+        } else {
+            serde_json::to_string(&differences)?
+        };
+        println!("{}", json);
+    } else {
+        for difference in &differences {
+            println!("{}", difference);
+        }
+    }
+
+    if !differences.is_empty() {
+        bail!(
+            "{} of {} files don't match the manifest",
+            differences.len(),
+            manifest.files.len()
+        );
+    }
+    info!("All {} files match the manifest", manifest.files.len());
+    Ok(())
+}
+
+/// Print the health report of a client
+fn print_doctor(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let report = doctor::diagnose(Path::new(matches.value_of("input").unwrap_or_default()));
+
+    if matches.is_present("json") {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&report)?
+        } else {
+            serde_json::to_string(&report)?
+        };
+        println!("{}", json);
+    } else {
+        print!("{}", report);
+    }
+
+    if report.problems() > 0 {
+        bail!("{} problems found", report.problems());
+    }
+    info!("No problems found");
+    Ok(())
+}
+
+/// Print the differences between two clients
+fn compare_clients(matches: &ArgMatches) -> Result<(), Error> {
+    let old = Client::open(Path::new(matches.value_of("old").unwrap_or_default()))?;
+    let new = Client::open(Path::new(matches.value_of("new").unwrap_or_default()))?;
+    let diff = ClientDiff::new(&old, &new)?;
+    for (path, e) in &diff.unreadable {
+        warn!("Unable to compare the contents of {}: {}", path, e);
+    }
+
+    if matches.is_present("json") {
+        let json = if ConvertOptions::from_matches(matches).pretty {
+            serde_json::to_string_pretty(&diff)?
+        } else {
+            serde_json::to_string(&diff)?
+        };
+        println!("{}", json);
+    } else if diff.is_empty() {
+        info!("No differences");
+    } else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
+
+/// Print the JSON Schema of a file type to stdout
+fn print_schema(matches: &ArgMatches) -> Result<(), Error> {
+    let rose_type = matches.value_of("type").unwrap_or_default().to_lowercase();
+    let schema = schema(&rose_type)?;
+
+    let json = if ConvertOptions::from_matches(matches).pretty {
+        serde_json::to_string_pretty(&schema)?
+    } else {
+        serde_json::to_string(&schema)?
+    };
+    println!("{}", json);
+    Ok(())
+}
+
+fn diff_stb(matches: &ArgMatches) -> Result<(), Error> {
+    let old_path = Path::new(matches.value_of("old").unwrap_or_default());
+    let new_path = Path::new(matches.value_of("new").unwrap_or_default());
+    if lowercase_extension(old_path) == "stl" {
+        return diff_stl(matches, old_path, new_path);
+    }
+
+    let old = STB::from_path(old_path)?;
+    let new = STB::from_path(new_path)?;
+    let diff = StbDiff::new(&old, &new);
+
+    if matches.is_present("json") {
+        let json = if ConvertOptions::from_matches(matches).pretty {
+            serde_json::to_string_pretty(&diff)?
+        } else {
+            serde_json::to_string(&diff)?
+        };
+        println!("{}", json);
+    } else if diff.is_empty() {
+        info!("No differences");
+    } else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
+
+fn diff_stl(matches: &ArgMatches, old_path: &Path, new_path: &Path) -> Result<(), Error> {
+    let old = STL::from_path(old_path)?;
+    let new = STL::from_path(new_path)?;
+    let diff = StlDiff::new(&old, &new);
+
+    if matches.is_present("json") {
+        let json = if ConvertOptions::from_matches(matches).pretty {
+            serde_json::to_string_pretty(&diff)?
+        } else {
+            serde_json::to_string(&diff)?
+        };
+        println!("{}", json);
+    } else if diff.is_empty() {
+        info!("No differences");
+    } else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
+
+/// Merge two STB files into the output directory, printing any conflicts
+fn merge_stb(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let ours_path = Path::new(matches.value_of("ours").unwrap_or_default());
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let out = out_dir.join(ours_path.file_name().unwrap_or_default());
+
+    let base = STB::from_path(Path::new(matches.value_of("base").unwrap_or_default()))?;
+    let ours = STB::from_path(ours_path)?;
+    let theirs = STB::from_path(Path::new(matches.value_of("theirs").unwrap_or_default()))?;
+    let mut merge = StbMerge::new(&base, &ours, &theirs);
+
+    for conflict in &merge.conflicts {
+        print!("{}", conflict);
+    }
+
+    if prepare_output(&out, &options)? {
+        if options.dry_run {
+            dry_run_write(&out);
+        } else {
+            create_output_dir(out_dir)?;
+            merge.stb.write_to_path(&out)?;
+        }
+    }
+
+    if !merge.conflicts.is_empty() {
+        let e = format_err!(
+            "Merged with {} conflicts, kept our changes",
+            merge.conflicts.len()
+        );
+        report.failure(ours_path, &e);
+        return Err(e);
+    }
+
+    report.success(ours_path, "stb", vec![out]);
+    Ok(())
+}
+
+/// Join the tables of a spec and write the result to the output directory as
+/// CSV, typed JSON with `--format json` or a workbook with `--format xlsx`
+fn join_tables(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let spec_path = Path::new(matches.value_of("spec").unwrap_or_default());
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+
+    let extension = if options.stb_json {
+        "json"
+    } else if options.xlsx {
+        "xlsx"
+    } else {
+        "csv"
+    };
+    let stem = spec_path.file_stem().unwrap_or_default();
+    let out = out_dir.join(stem).with_extension(extension);
+
+    let res = join_spec(spec_path, &out, &options);
+    match &res {
+        Ok(()) => report.success(spec_path, "join", vec![out]),
+        Err(e) => report.failure(spec_path, e),
+    }
+    res
+}
+
+fn join_spec(spec_path: &Path, out: &Path, options: &ConvertOptions) -> Result<(), Error> {
+    let spec = JoinSpec::from_path(spec_path)?;
+    let base_dir = spec_path.parent().unwrap_or_else(|| Path::new(""));
+    let view = spec.run(base_dir, &options.schema_dir)?;
+
+    if !prepare_output(out, options)? {
+        return Ok(());
+    }
+    if options.dry_run {
+        dry_run_write(out);
+        return Ok(());
+    }
+
+    let data = if options.stb_json {
+        let typed = view.to_typed()?;
+        if options.pretty {
+            typed.to_json()?.into_bytes()
+        } else {
+            typed.to_json_compact()?.into_bytes()
+        }
+    } else {
+        let csv = stb_csv::write(&view.to_stb(), &view.names, false, &options.csv)?;
+        if options.xlsx {
+            let name = spec_path.file_stem().unwrap_or_default().to_string_lossy();
+            Workbook {
+                sheets: vec![Sheet::from_csv(&name, &csv, &options.csv)?],
+            }
+            .to_bytes()?
+        } else {
+            csv.into_bytes()
+        }
+    };
+
+    if let Some(p) = out.parent() {
+        create_output_dir(p)?;
+    }
+    fs::write(out, data)?;
+
+    info!("Joined {} rows to {}", view.rows.len(), out.display());
+    Ok(())
+}
+
+/// Generate a Rust module with a struct for each STB schema
+fn generate_code(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out = match matches.value_of("output") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(matches.value_of("out_dir").unwrap_or_default()).join("stb_tables.rs"),
+    };
+
+    let mut paths: Vec<PathBuf> = match matches.values_of("schemas") {
+        Some(values) => values.map(PathBuf::from).collect(),
+        None => fs::read_dir(&options.schema_dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?
+            .into_iter()
+            .filter(|p| lowercase_extension(p) == "toml")
+            .collect(),
+    };
+    paths.sort();
+
+    let mut tables = Vec::new();
+    for path in &paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        match StbSchema::from_path(path) {
+            Ok(schema) => tables.push((name.to_lowercase(), schema)),
+            Err(e) => bail!("Invalid STB schema {}: {}", path.display(), e),
+        }
+    }
+    if tables.is_empty() {
+        bail!("No STB schemas found in {}", options.schema_dir.display());
+    }
+
+    if !prepare_output(&out, &options)? {
+        return Ok(());
+    }
+    if options.dry_run {
+        for path in &paths {
+            dry_run_read(path);
+        }
+        dry_run_write(&out);
+        return Ok(());
+    }
+
+    if let Some(p) = out.parent() {
+        create_output_dir(p)?;
+    }
+    fs::write(&out, codegen::generate(&tables)?)?;
+
+    info!("Generated {} tables in {}", tables.len(), out.display());
+    for path in &paths {
+        report.success(path, "schema", vec![out.clone()]);
+    }
+    Ok(())
+}
+
+/// Export the translations of STL files to a PO file per target language
+fn stl_to_po(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    // Validated when parsing the args
+    let source: StringTableLanguage = matches.value_of("source").unwrap_or_default().parse()?;
+
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "stl"
+    })?;
+
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let stl = STL::from_path(input)?;
+
+        let targets: Vec<StringTableLanguage> = if options.languages.is_empty() {
+            stl.language_tables
+                .iter()
+                .map(|table| table.language)
+                .filter(|language| *language != source && *language != StringTableLanguage::Unknown)
+                .collect()
+        } else {
+            options.languages.clone()
+        };
+
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let mut outputs = Vec::new();
+        for target in targets {
+            let out = out_dir
+                .join(&input_file.dir)
+                .join(format!("{}.{}.po", stem, stl_po::po_language(target)));
+            outputs.push(out.clone());
+
+            if !prepare_output(&out, &options)? {
+                continue;
+            }
+            if options.dry_run {
+                dry_run_read(input);
+                dry_run_write(&out);
+                continue;
+            }
+
+            let mut po = stl_po::from_stl(&stl, source, target)?;
+            po.headers.insert(0, ("Project-Id-Version".into(), stem.to_string()));
+            if let Some(p) = out.parent() {
+                create_output_dir(p)?;
+            }
+            fs::write(&out, po.to_string())?;
+            debug!("Exported {} {} messages to {}", po.entries.len(), target, out.display());
+        }
+
+        Ok((String::from("stl"), outputs))
+    })
+}
+
+/// Merge the translations of PO files into an STL file in place
+fn stl_from_po(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let path = Path::new(matches.value_of("file").unwrap_or_default());
+
+    let mut stl = STL::from_path(path)?;
+    for po_path in matches.values_of("po").unwrap_or_default() {
+        let po = PoFile::parse(&fs::read_to_string(po_path)?)
+            .map_err(|e| format_err!("Invalid PO file {}: {}", po_path, e))?;
+
+        let language = match (options.language, po.header("Language")) {
+            (Some(language), _) => language,
+            (None, Some(code)) => stl_po::parse_po_language(code)?,
+            (None, None) => bail!("{} has no Language header, use --language", po_path),
+        };
+
+        let count = stl_po::patch_stl(&mut stl, &po, language)?;
+        info!("Merged {} {} translations from {}", count, language, po_path);
+    }
+
+    write_stl_in_place(&mut stl, path, &options)?;
+    report.success(path, "stl", vec![path.to_path_buf()]);
+    Ok(())
+}
+
+/// Merge the strings of a CSV file into an STL file in place
+fn stl_merge(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let path = Path::new(matches.value_of("file").unwrap_or_default());
+    let csv_path = matches.value_of("csv").unwrap_or_default();
+
+    let mut stl = STL::from_path(path)?;
+    let csv = fs::read_to_string(csv_path)?;
+    let count = stl_csv::merge(&mut stl, &csv, options.language, &options.csv)?;
+
+    write_stl_in_place(&mut stl, path, &options)?;
+    report.success(path, "stl", vec![path.to_path_buf()]);
+    info!("Merged {} strings from {}", count, csv_path);
+    Ok(())
+}
+
+/// Write an STL file in place, backing it up first with `--backup`
+fn write_stl_in_place(stl: &mut STL, path: &Path, options: &ConvertOptions) -> Result<(), Error> {
+    if options.backup {
+        backup_file(path, options)?;
+    }
+
+    if options.dry_run {
+        dry_run_write(path);
+    } else {
+        stl.write_to_path(path)?;
+    }
+    Ok(())
+}
+
+/// Print the missing and untranslated strings of STL files
+fn stl_report(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    // Validated when parsing the args
+    let source: StringTableLanguage = matches.value_of("source").unwrap_or_default().parse()?;
+
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "stl"
+    })?;
+
+    let mut reports = BTreeMap::new();
+    for input in &inputs {
+        let stl = STL::from_path(&input.path)?;
+        let report = StlReport::new(&stl, source, &options.languages)
+            .map_err(|e| format_err!("{}: {}", input.path.display(), e))?;
+        reports.insert(input.path.display().to_string(), report);
+    }
+
+    if matches.is_present("json") {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&reports)?
+        } else {
+            serde_json::to_string(&reports)?
+        };
+        println!("{}", json);
+        return Ok(());
+    }
+
+    for (path, report) in &reports {
+        println!(
+            "{}: {} missing, {} untranslated",
+            path,
+            report.missing(),
+            report.untranslated()
+        );
+        if !matches.is_present("summary") {
+            for line in report.to_string().lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+
+    info!(
+        "{} missing and {} untranslated strings in {} files",
+        reports.values().map(StlReport::missing).sum::<usize>(),
+        reports.values().map(StlReport::untranslated).sum::<usize>(),
+        reports.len()
+    );
+    Ok(())
+}
+
+/// Print the string encoding of every STB, STL and ZMD file
+fn encoding_report(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    // Validated when parsing the args
+    let samples: usize = matches.value_of("samples").unwrap_or_default().parse()?;
+
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        ["stb", "stl", "zmd"].contains(&lowercase_extension(p).as_str())
+    })?;
+
+    let mut reports = BTreeMap::new();
+    for input in &inputs {
+        let strings = match encoding_report::file_strings(&input.path) {
+            Ok(strings) => strings,
+            Err(e) => {
+                warn!("Skipping {}: {}", input.path.display(), e);
+                continue;
+            }
+        };
+        let report = EncodingReport::new(strings, samples);
+        if !matches.is_present("issues") || report.has_issues() {
+            reports.insert(input.path.display().to_string(), report);
+        }
+    }
+
+    if matches.is_present("json") {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&reports)?
+        } else {
+            serde_json::to_string(&reports)?
+        };
+        println!("{}", json);
+        return Ok(());
+    }
+
+    for (path, report) in &reports {
+        let report = report.to_string();
+        let mut lines = report.lines();
+        println!("{}: {}", path, lines.next().unwrap_or_default());
+        for line in lines {
+            println!("    {}", line);
+        }
+    }
+
+    info!(
+        "{} of {} files have mixed encodings or mojibake",
+        reports.values().filter(|r| r.has_issues()).count(),
+        inputs.len()
+    );
+    Ok(())
+}
+
+/// Print the STL strings and STB cells matching a pattern
+fn grep_files(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let pattern = Pattern::new(
+        matches.value_of("pattern").unwrap_or_default(),
+        matches.is_present("fixed_strings"),
+        matches.is_present("ignore_case"),
+    )?;
+
+    let is_searched = |p: &Path| ["stb", "stl"].contains(&lowercase_extension(p).as_str());
+    let mut files = Vec::new();
+    for input_arg in matches.values_of("input").unwrap_or_default() {
+        let input = Path::new(input_arg);
+        if lowercase_extension(input) == "idx" {
+            files.extend(vfs::read_files(input, is_searched)?);
+        } else {
+            for input_file in collect_inputs(iter::once(input_arg), is_searched)? {
+                let data = fs::read(&input_file.path)?;
+                files.push((input_file.path, data));
+            }
+        }
+    }
+
+    let mut found: Vec<GrepMatch> = Vec::new();
+    for (path, data) in &files {
+        let file = path.display().to_string();
+        let res = if lowercase_extension(path) == "stb" {
+            stb_from_bytes(data).map(|stb| grep::grep_stb(&file, &stb, &pattern))
+        } else {
+            let mut stl = STL::new();
+            stl.read(&mut RoseReader::new(Cursor::new(data)))
+                .map(|_| grep::grep_stl(&file, &stl, &pattern))
+        };
+
+        match res {
+            Ok(file_matches) => found.extend(file_matches),
+            Err(e) => warn!("Skipping {}: {}", file, e),
+        }
+    }
+
+    if matches.is_present("json") {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&found)?
+        } else {
+            serde_json::to_string(&found)?
+        };
+        println!("{}", json);
+    } else {
+        for m in &found {
+            println!("{}", m);
+        }
+    }
+
+    info!("{} matches in {} files", found.len(), files.len());
+    Ok(())
+}
+
+/// Print the duplicate strings of STL files
+fn stl_duplicates(matches: &ArgMatches) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "stl"
+    })?;
+
+    let mut files = Vec::new();
+    for input in &inputs {
+        files.push((input.path.display().to_string(), STL::from_path(&input.path)?));
+    }
+    let report = DuplicateReport::new(&files, &options.languages, matches.is_present("exact"));
+
+    if matches.is_present("json") {
+        let json = if options.pretty {
+            serde_json::to_string_pretty(&report)?
+        } else {
+            serde_json::to_string(&report)?
+        };
+        println!("{}", json);
+    } else if matches.is_present("summary") {
+        for summary in &report.summaries {
+            print!("{}", summary);
+        }
+    } else {
+        print!("{}", report);
+    }
+
+    info!(
+        "{} groups of duplicate strings in {} files",
+        report.groups.len(),
+        files.len()
+    );
+    Ok(())
+}
+
+/// Apply a patch to an STB or STL file and write it to the output directory
+fn apply_patch(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let input = Path::new(matches.value_of("input").unwrap_or_default());
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let out = out_dir.join(input.file_name().unwrap_or_default());
+
+    let patch = Patch::from_path(Path::new(matches.value_of("patch").unwrap_or_default()))?;
+    let rose_type = lowercase_extension(input);
+
+    if !prepare_output(&out, &options)? {
+        return Ok(());
+    }
+
+    match rose_type.as_str() {
+        "stb" => {
+            let mut stb = STB::from_path(input)?;
+            patch.apply_stb(&mut stb)?;
+            if !options.dry_run {
+                create_output_dir(out_dir)?;
+                stb.write_to_path(&out)?;
+            }
+        }
+        "stl" => {
+            let mut stl = patch.apply_stl(&STL::from_path(input)?)?;
+            if !options.dry_run {
+                create_output_dir(out_dir)?;
+                stl.write_to_path(&out)?;
+            }
+        }
+        _ => bail!("Patches can only be applied to STB and STL files"),
+    }
+
+    if options.dry_run {
+        dry_run_write(&out);
+    }
+
+    info!("Applied {} patch ops to {}", patch.ops.len(), out.display());
+    report.success(input, &rose_type, vec![out]);
+    Ok(())
+}
+
+fn validate_index(s: String) -> Result<(), String> {
+    s.parse::<usize>().map(|_| ()).map_err(|e| e.to_string())
+}
+
+fn validate_smoothing_angle(s: String) -> Result<(), String> {
+    match s.parse::<f32>() {
+        Ok(v) if (0.0..=180.0).contains(&v) => Ok(()),
+        Ok(_) => Err(String::from("must be between 0 and 180")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn validate_frame_edit(s: String) -> Result<(), String> {
+    zmo_events::parse_edit(&s)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn validate_color(s: String) -> Result<(), String> {
+    iconsheet::parse_color(&s)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Key color of `--color-key`, magenta if the option has no value
+fn color_key(matches: &ArgMatches) -> Result<Option<[u8; 3]>, Error> {
+    if !matches.is_present("color_key") {
+        return Ok(None);
+    }
+    match matches.value_of("color_key") {
+        Some(color) => Ok(Some(iconsheet::parse_color(color)?)),
+        None => Ok(Some(iconsheet::COLOR_KEY)),
+    }
+}
+
+fn validate_icon_size(s: String) -> Result<(), String> {
+    iconsheet::Grid::parse_size(&s)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Write an STB edited in place, backing it up first if `--backup` is set
+fn write_stb_in_place(stb: &mut STB, path: &Path, options: &ConvertOptions) -> Result<(), Error> {
+    if options.backup {
+        backup_file(path, options)?;
+    }
+
+    if options.dry_run {
+        dry_run_write(path);
+    } else {
+        stb.write_to_path(path)?;
+    }
+    Ok(())
+}
+
+/// Apply a patch to an STB file in place
+fn patch_stb_in_place(matches: &ArgMatches, report: &mut Report, patch: &Patch) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let path = Path::new(matches.value_of("file").unwrap_or_default());
+
+    let mut stb = STB::from_path(path)?;
+    patch.apply_stb(&mut stb)?;
+    write_stb_in_place(&mut stb, path, &options)?;
+
+    report.success(path, "stb", vec![path.to_path_buf()]);
+    Ok(())
+}
+
+/// Parse a row or column index, validated when parsing the args
+fn index_arg(matches: &ArgMatches, name: &str) -> Result<usize, Error> {
+    Ok(matches.value_of(name).unwrap_or_default().parse()?)
+}
+
+/// Set the value of one or more cells of an STB file in place
+fn stb_set(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let patch = if let Some(edits) = matches.value_of("from_file") {
+        Patch::from_csv_edits(&fs::read_to_string(edits)?)?
+    } else {
+        Patch {
+            ops: vec![PatchOp::Set {
+                row: index_arg(matches, "row")?,
+                column: index_arg(matches, "column")?,
+                value: matches.value_of("value").unwrap_or_default().to_string(),
+            }],
+        }
+    };
+
+    patch_stb_in_place(matches, report, &patch)?;
+    info!("Set {} cells", patch.ops.len());
+    Ok(())
+}
+
+/// Insert or append a row to an STB file in place
+fn stb_insert_row(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let path = Path::new(matches.value_of("file").unwrap_or_default());
+    let stb = STB::from_path(path)?;
+
+    let mut values: Vec<String> = matches
+        .values_of("values")
+        .map(|values| values.map(String::from).collect())
+        .unwrap_or_default();
+    if values.len() > stb.headers.len() {
+        bail!(
+            "Too many values, the STB has {} columns but {} values were given",
+            stb.headers.len(),
+            values.len()
+        );
+    }
+    values.resize(stb.headers.len(), String::new());
+
+    let row = if matches.is_present("row") {
+        index_arg(matches, "row")?
+    } else {
+        stb.rows()
+    };
+
+    patch_stb_in_place(matches, report, &Patch {
+        ops: vec![PatchOp::Insert { row, values }],
+    })?;
+    info!("Inserted row {}", row);
+    Ok(())
+}
+
+fn stb_delete_row(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let row = index_arg(matches, "row")?;
+    patch_stb_in_place(matches, report, &Patch {
+        ops: vec![PatchOp::Delete { row }],
+    })?;
+    info!("Deleted row {}", row);
+    Ok(())
+}
+
+/// Copy a row of an STB file in place, appending it by default
+fn stb_copy_row(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let path = Path::new(matches.value_of("file").unwrap_or_default());
+    let stb = STB::from_path(path)?;
+
+    let row = index_arg(matches, "row")?;
+    let values = match stb.data.get(row) {
+        Some(values) => values.clone(),
+        None => bail!("Row {} does not exist", row),
+    };
+    let to = if matches.is_present("to") {
+        index_arg(matches, "to")?
+    } else {
+        stb.rows()
+    };
+
+    patch_stb_in_place(matches, report, &Patch {
+        ops: vec![PatchOp::Insert { row: to, values }],
+    })?;
+    info!("Copied row {} to {}", row, to);
+    Ok(())
+}
+
+/// Path of the STB database, `--database` or `stb.sqlite` in the output directory
+fn stb_database_path(matches: &ArgMatches) -> PathBuf {
+    match matches.value_of("database") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(matches.value_of("out_dir").unwrap_or_default()).join("stb.sqlite"),
+    }
+}
+
+/// Export every STB in a directory or VFS to a new SQLite database
+fn stb_to_sqlite(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let input_arg = matches.value_of("input").unwrap_or_default();
+    let input = Path::new(input_arg);
+    let db_path = stb_database_path(matches);
+
+    let is_stb = |p: &Path| lowercase_extension(p) == "stb";
+    let files = if input.is_dir() {
+        let mut files = Vec::new();
+        for input_file in collect_inputs(iter::once(input_arg), is_stb)? {
+            let data = fs::read(&input_file.path)?;
+            // Stored relative to the input directory for `stb from-sqlite`
+            let file_name = input_file.path.file_name().unwrap_or_default();
+            files.push((input_file.dir.join(file_name), data));
+        }
+        files
+    } else if lowercase_extension(input) == "idx" {
+        vfs::read_files(input, is_stb)?
+    } else {
+        bail!("Expected a directory or VFS index: {}", input.display());
+    };
+
+    if !prepare_output(&db_path, &options)? {
+        return Ok(());
+    }
+
+    if options.dry_run {
+        for (path, _) in &files {
+            dry_run_read(path);
+        }
+        dry_run_write(&db_path);
+        return Ok(());
+    }
+
+    if db_path.exists() {
+        fs::remove_file(&db_path)?;
+    }
+    if let Some(p) = db_path.parent() {
+        create_output_dir(p)?;
+    }
+    let mut db = StbDatabase::create(&db_path)?;
+
+    let mut failed = 0;
+    for (path, data) in &files {
+        let res = stb_from_bytes(data).and_then(|stb| {
+            let schema = StbSchema::find(&options.schema_dir, path)?.unwrap_or_default();
+            db.add(path, &stb, &schema)
+        });
+
+        match res {
+            Ok(table) => {
+                debug!("Exported {} to table {}", path.display(), table);
+                report.success(path, "stb", vec![db_path.clone()]);
+            }
+            Err(e) => {
+                error!("Error exporting {}: {}", path.display(), e);
+                report.failure(path, &e);
+                failed += 1;
+
+                if options.fail_fast {
+                    bail!("Stopping after first failure");
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("Failed to export {} of {} files", failed, files.len());
+    }
+    info!("Exported {} STB files to {}", files.len(), db_path.display());
+    Ok(())
+}
+
+/// Write every STB in an SQLite database to the output directory
+fn stb_from_sqlite(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let db_path = Path::new(matches.value_of("database").unwrap_or_default());
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+
+    let db = StbDatabase::open(db_path)?;
+    let tables = db.tables()?;
+
+    let mut failed = 0;
+    for table in &tables {
+        // Paths are relative to the exported directory, but don't write
+        // outside the output directory if the database was edited
+        let out = if table.path.is_relative()
+            && table.path.components().all(|c| c != Component::ParentDir)
+        {
+            out_dir.join(&table.path)
+        } else {
+            out_dir.join(table.path.file_name().unwrap_or_default())
+        };
+
+        let res = prepare_output(&out, &options).and_then(|write| {
+            let mut stb = db.read(&table.table_name)?;
+            if !write {
+                return Ok(());
+            }
+            if options.dry_run {
+                dry_run_write(&out);
+                return Ok(());
+            }
+            if let Some(p) = out.parent() {
+                create_output_dir(p)?;
+            }
+            stb.write_to_path(&out)
+        });
+
+        match res {
+            Ok(()) => {
+                debug!("Wrote table {} to {}", table.table_name, out.display());
+                report.success(db_path, "stb", vec![out]);
+            }
+            Err(e) => {
+                error!("Error writing table {}: {}", table.table_name, e);
+                report.failure(db_path, &e);
+                failed += 1;
+
+                if options.fail_fast {
+                    bail!("Stopping after first failure");
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("Failed to write {} of {} tables", failed, tables.len());
+    }
+    info!("Wrote {} STB files to {}", tables.len(), out_dir.display());
+    Ok(())
+}
+
+/// Read an STB file from memory
+fn stb_from_bytes(data: &[u8]) -> Result<STB, Error> {
+    let mut reader = RoseReader::new(Cursor::new(data));
+    let mut stb = STB::new();
+    stb.read(&mut reader)?;
+    Ok(stb)
+}
+
+/// Write `value` as JSON, pretty-printed unless `--compact` is set
+fn write_json<T: Serialize>(path: &Path, value: &T, options: &ConvertOptions) -> Result<(), Error> {
+    let f = BufWriter::new(File::create(path)?);
+    if options.pretty {
+        serde_json::to_writer_pretty(f, value)?;
+    } else {
+        serde_json::to_writer(f, value)?;
+    }
+    Ok(())
+}
+
+/// Convert map files:
+/// - ZON: JSON
+/// - TIL: Combined into 1 JSON file
+/// - IFO: Combined into 1 JSON file
+/// - HIM: Combined into 1 greyscale png
+fn convert_map(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let map_dir = Path::new(matches.value_of("map_dir").unwrap());
+
+    let res = convert_map_dir(matches, map_dir);
+    match &res {
+        Ok(outputs) => report.success(map_dir, "map", outputs.clone()),
+        Err(e) => report.failure(map_dir, e),
+    }
+    res.map(|_| ())
+}
+
+/// Convert a single map directory, returning the output paths
+fn convert_map_dir(matches: &ArgMatches, map_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !map_dir.is_dir() {
+        bail!("Map path is not a directory: {:?}", map_dir);
+    }
+    match matches.value_of("target") {
+        Some("unity") => return map_dir_unity(matches, map_dir),
+        Some("unreal") => return map_dir_unreal(matches, map_dir),
+        Some("godot") => return map_dir_godot(matches, map_dir),
+        _ => {}
+    }
+
+    info!("Loading map from: {}", map_dir.to_str().unwrap());
+    let start = Instant::now();
+
+    // Collect coordinates from file names (using HIM as reference)
+    let mut x_coords: Vec<u32> = Vec::new();
+    let mut y_coords: Vec<u32> = Vec::new();
+
+    for f in fs::read_dir(map_dir)? {
+        let f = f?;
+        let fpath = f.path();
+        if !fpath.is_file() {
+            continue;
+        }
+
+        if fpath.extension().unwrap().to_str().unwrap().to_lowercase() == "him" {
+            let fname = fpath.file_stem().unwrap().to_str().unwrap();
+            let parts: Vec<&str> = fname.split('_').collect();
+            x_coords.push(parts[0].parse()?);
+            y_coords.push(parts[1].parse()?);
+        }
+    }
+
+    x_coords.sort();
+    y_coords.sort();
+
+    let x_min = *x_coords.iter().min().unwrap();
+    let x_max = *x_coords.iter().max().unwrap();
+    let y_min = *y_coords.iter().min().unwrap();
+    let y_max = *y_coords.iter().max().unwrap();
+
+    let map_name = map_dir.file_name().unwrap().to_str().unwrap();
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"));
+
+    let height_file = out_dir.join(map_name).with_extension("png");
+    let zon_file = out_dir.join(map_name).with_extension("json");
+    let tile_file = out_dir
+        .join(format!("{}_tilemap", map_name))
+        .with_extension("json");
+    let normal_file = out_dir
+        .join(format!("{}_normal", map_name))
+        .with_extension("png");
+    let splat_file = out_dir
+        .join(format!("{}_splat", map_name))
+        .with_extension("json");
+    let splat_size: Option<u32> = if matches.is_present("splatmap") {
+        Some(matches.value_of("splat_size").unwrap_or("4").parse()?)
+    } else {
+        None
+    };
+    let normal_strength: Option<f32> = if matches.is_present("normal_map") {
+        Some(matches.value_of("normal_strength").unwrap_or("1").parse()?)
+    } else {
+        None
+    };
+
+    // Check all outputs before doing any work
+    let options = ConvertOptions::from_matches(matches);
+    let write_height = prepare_output(&height_file, &options)?;
+    let write_zon = prepare_output(&zon_file, &options)?;
+    let write_tile = prepare_output(&tile_file, &options)?;
+    let write_normal = normal_strength.is_some() && prepare_output(&normal_file, &options)?;
+    // The splatmap images are skipped or overwritten with their description
+    let write_splat = splat_size.is_some() && prepare_output(&splat_file, &options)?;
+
+    let mut outputs = vec![height_file.clone(), zon_file.clone(), tile_file.clone()];
+    if normal_strength.is_some() {
+        outputs.push(normal_file.clone());
+    }
+    if splat_size.is_some() {
+        outputs.push(splat_file.clone());
+    }
+    if !(write_height || write_zon || write_tile || write_normal || write_splat) {
+        return Ok(outputs);
+    }
+
+    if options.dry_run {
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                dry_run_read(&map_dir.join(format!("{}_{}.HIM", x, y)));
+                dry_run_read(&map_dir.join(format!("{}_{}.TIL", x, y)));
+            }
+        }
+        dry_run_read(&map_dir.join(format!("{}.ZON", map_name)));
+
+        for (out, write) in &[
+            (&height_file, write_height),
+            (&zon_file, write_zon),
+            (&tile_file, write_tile),
+            (&normal_file, write_normal),
+            (&splat_file, write_splat),
+        ] {
+            if *write {
+                dry_run_write(out);
+            }
+        }
+        return Ok(outputs);
+    }
+
+    let map_width = (x_max - x_min + 1) * 65;
+    let map_height = (y_max - y_min + 1) * 65;
+
+    let mut max_height = f32::NAN;
+    let mut min_height = f32::NAN;
+
+    // Ensure map dimensions are divisible by 4 for tiling
+    let new_map_width = (map_width as f32 / 4.0).ceil() * 4.0;
+    let new_map_height = (map_height as f32 / 4.0).ceil() * 4.0;
+
+    let new_map_width = new_map_width as u32 + 1;
+    let new_map_height = new_map_height as u32 + 1;
+
+    let mut heights: Vec<Vec<f32>> = Vec::new();
+    heights.resize(
+        new_map_height as usize,
+        iter::repeat(0.0).take(new_map_width as usize).collect(),
+    );
+
+    // Number of tiles in x and y direction
+    let tiles_x = new_map_width / 4;
+    let tiles_y = new_map_height / 4;
+
+    let mut tiles: Vec<Vec<i32>> = Vec::new();
+    tiles.resize(
+        tiles_y as usize,
+        iter::repeat(0).take(tiles_x as usize).collect(),
+    );
+
+    for y in y_min..=y_max {
+        for x in x_min..=x_max {
+            //-- Load HIMs
+            let him_name = format!("{}_{}.HIM", x, y);
+            let him_path = map_dir.join(&him_name);
+
+            let him = HIM::from_path(&him_path).unwrap();
+            if him.length != 65 || him.width != 65 {
+                bail!(
+                    "Unexpected HIM dimensions. Expected 65x65: {} ({}x{})",
+                    &him_path.to_str().unwrap_or(&him_name),
+                    him.width,
+                    him.length
+                );
+            }
+
+            for h in 0..him.length {
+                for w in 0..him.width {
+                    let height = him.height(h as usize, w as usize);
+
+                    if (height > max_height) || (max_height.is_nan()) {
+                        max_height = height;
+                    }
+                    if (height < min_height) || (min_height.is_nan()) {
+                        min_height = height;
+                    }
+
+                    let new_x = ((x - x_min) * 65) + w as u32;
+                    let new_y = ((y - y_min) * 65) + h as u32;
+
+                    heights[new_y as usize][new_x as usize] = height;
+                }
+            }
+
+            // -- Load TILs
+            let til_name = format!("{}_{}.TIL", x, y);
+            let til_path = map_dir.join(&til_name);
+
+            let til = TIL::from_path(&til_path).unwrap();
+            if til.height != 16 || til.width != 16 {
+                bail!(
+                    "Unexpected TIL dimensions. Expected 16x16: {} ({}x{})",
+                    &til_path.to_str().unwrap_or(&til_name),
+                    til.width,
+                    til.height
+                );
+            }
+
+            for h in 0..til.height {
+                for w in 0..til.width {
+                    let tile_id = til.tiles[h as usize][w as usize].tile_id;
+
+                    let new_x = ((x - x_min) * 16) + w as u32;
+                    let new_y = ((y - y_min) * 16) + h as u32;
+
+                    tiles[new_y as usize][new_x as usize] = tile_id;
+                }
+            }
+
+            // TODO:
+            // Load IFO data
+        }
+    }
+
+    create_output_dir(out_dir)?;
+
+    // -- Heightmap image
+    let delta_height = max_height - min_height;
+
+    let mut height_image: GrayImage = ImageBuffer::new(new_map_width, new_map_height);
+
+    for y in 0..new_map_height {
+        for x in 0..new_map_width {
+            let height = heights[y as usize][x as usize];
+
+            let norm_height = |h| (255.0 * ((h - min_height) / delta_height)) as u8;
+
+            let pixel = image::Luma([norm_height(height)]);
+            height_image.put_pixel(x, y, pixel);
+        }
+    }
+
+    // Save heightmap image
+    if write_height {
+        info!("Saving heightmap to: {}", &height_file.to_str().unwrap());
+        height_image.save(&height_file)?;
+    }
+
+    // Dump ZON as JSON
+    let zon = ZON::from_path(&map_dir.join(format!("{}.ZON", map_name)))?;
+
+    // Normal map of the heightmap, with the distance between heights of the ZON
+    if let Some(strength) = normal_strength.filter(|_| write_normal) {
+        let spacing = if zon.grid_size > 0.0 { zon.grid_size } else { 250.0 };
+        info!("Saving normal map to: {}", &normal_file.to_str().unwrap());
+        normal_map::normal_map(&heights, spacing, strength).save(&normal_file)?;
+    }
+
+    // Splatmaps of the tile textures, from the TILs again
+    if let Some(splat_size) = splat_size.filter(|_| write_splat) {
+        let textures = read_tile_textures(&map_client_dir(matches, map_dir), &zon);
+        let splatmap = draw_map_splatmap(
+            map_dir,
+            &zon,
+            textures,
+            splat_size,
+            [x_min, y_min],
+            [x_max, y_max],
+        )?;
+        let images = save_splatmaps(&splatmap, out_dir, map_name, &mut outputs)?;
+        info!("Saving splatmaps to: {}", &splat_file.to_str().unwrap());
+        let splat = SplatmapFile {
+            tile_size: splat_size,
+            images,
+            channels: splatmap.channels(&zon.textures),
+        };
+        write_json(&splat_file, &splat, &options)?;
+    }
+
+    if write_zon {
+        info!("Dumping ZON file to: {}", &zon_file.to_str().unwrap());
+        write_json(&zon_file, &zon, &options)?;
+    }
+
+    // Create tilemap file
+    let mut tilemap_tiles: Vec<TilemapTile> = Vec::new();
+    for zon_tile in zon.tiles {
+        tilemap_tiles.push(TilemapTile {
+            layer1: zon_tile.layer1 + zon_tile.offset1,
+            layer2: zon_tile.layer2 + zon_tile.offset2,
+            rotation: zon_tile.rotation,
+        });
+    }
+
+    let tilemap = TilemapFile {
+        textures: zon.textures,
+        tiles: tilemap_tiles,
+        tilemap: tiles,
+    };
+
+    if write_tile {
+        info!("Saving tilemap file to: {}", &tile_file.to_str().unwrap());
+        write_json(&tile_file, &tilemap, &options)?;
+    }
+
+    // EXPORT IFO data as JSON
+
+    debug!("Converted map {} in {:.2?}", map_name, start.elapsed());
+
+    Ok(outputs)
+}
+
+/// Client directory of a map, from `--client` or above the map
+fn map_client_dir(matches: &ArgMatches, map_dir: &Path) -> PathBuf {
+    match matches.value_of("client") {
+        Some(dir) => PathBuf::from(dir),
+        None => vfs::find_client_dir(map_dir).unwrap_or_else(|| PathBuf::from(".")),
+    }
+}
+
+/// Splatmap of the TILs of a map from its first to its last block
+fn draw_map_splatmap<'a>(
+    map_dir: &Path,
+    zon: &'a ZON,
+    textures: Vec<Option<RgbaImage>>,
+    splat_size: u32,
+    min_block: [u32; 2],
+    max_block: [u32; 2],
+) -> Result<Splatmap<'a>, Error> {
+    let block_size = splat_size * minimap::BLOCK_TILES;
+    let mut splatmap = Splatmap::new(
+        &zon.tiles,
+        textures,
+        splat_size,
+        (max_block[0] - min_block[0] + 1) * block_size,
+        (max_block[1] - min_block[1] + 1) * block_size,
+    );
+    for ((x, y), til_path) in map_block_files(map_dir, "til")? {
+        if x < min_block[0] || y < min_block[1] || x > max_block[0] || y > max_block[1] {
+            continue;
+        }
+        let til = TIL::from_path(&til_path)?;
+        splatmap.draw_block(
+            (x - min_block[0]) * block_size,
+            (y - min_block[1]) * block_size,
+            &til,
+        );
+    }
+    Ok(splatmap)
+}
+
+/// Save the images of a splatmap as `<map>_splat_<n>.png`, returning their
+/// file names
+fn save_splatmaps(
+    splatmap: &Splatmap,
+    out_dir: &Path,
+    map_name: &str,
+    outputs: &mut Vec<PathBuf>,
+) -> Result<Vec<String>, Error> {
+    let mut images = Vec::new();
+    for (i, img) in splatmap.to_images().iter().enumerate() {
+        let name = format!("{}_splat_{}.png", map_name, i);
+        let path = out_dir.join(&name);
+        img.save(&path)?;
+        outputs.push(path);
+        images.push(name);
+    }
+    Ok(images)
+}
+
+/// Export the terrain of a map for Unity: a RAW heightmap, splatmaps, the
+/// tile textures as PNG and the settings of the terrain
+fn map_dir_unity(matches: &ArgMatches, map_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let start = Instant::now();
+    let options = ConvertOptions::from_matches(matches);
+    let map_name = map_dir.file_name().unwrap_or_default().to_string_lossy();
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"))
+        .join(format!("{}_unity", map_name));
+    let extension = if options.format == Format::Yaml { "yaml" } else { "json" };
+    let out = out_dir.join(format!("{}.{}", map_name, extension));
+    if !prepare_output(&out, &options)? {
+        return Ok(vec![out]);
+    }
+
+    let him_files = map_block_files(map_dir, "him")?;
+    if him_files.is_empty() {
+        bail!("No HIM files in map directory: {}", map_dir.display());
+    }
+    let zon_path = map_dir.join(format!("{}.ZON", map_name));
+    let ifo_paths = map_ifo_paths(map_dir)?;
+    if options.dry_run {
+        for path in him_files.values().chain(&ifo_paths) {
+            dry_run_read(path);
+        }
+        dry_run_read(&zon_path);
+        dry_run_write(&out);
+        return Ok(vec![out]);
+    }
+
+    let terrain = read_map_terrain(&him_files)?;
+    let zon = ZON::from_path(&zon_path)?;
+    create_output_dir(&out_dir)?;
+    let mut outputs = Vec::new();
+
+    let resolution = terrain::unity_resolution(terrain.rows().max(terrain.cols()));
+    let heightmap = format!("{}.raw", map_name);
+    info!("Saving {0}x{0} heightmap to: {1}", resolution, heightmap);
+    fs::write(out_dir.join(&heightmap), terrain::unity_raw(&terrain, resolution))?;
+    outputs.push(out_dir.join(&heightmap));
+
+    let splat_size: u32 = matches.value_of("splat_size").unwrap_or("4").parse()?;
+    let textures = read_tile_textures(&map_client_dir(matches, map_dir), &zon);
+    let splatmap = draw_map_splatmap(
+        map_dir,
+        &zon,
+        textures.clone(),
+        splat_size,
+        terrain.min_block,
+        terrain.max_block,
+    )?;
+    let images = save_splatmaps(&splatmap, &out_dir, &map_name, &mut outputs)?;
+
+    let mut layers = Vec::new();
+    for channel in splatmap.channels(&zon.textures) {
+        let texture = match textures.get(channel.texture_id) {
+            Some(Some(img)) => Some(save_terrain_texture(
+                img,
+                &channel.texture,
+                &out_dir,
+                &mut outputs,
+            )?),
+            _ => None,
+        };
+        layers.push(TerrainLayer {
+            source: channel.texture,
+            texture,
+            splatmap: images[channel.image].clone(),
+            channel: channel.channel,
+            tile_size: terrain::BLOCK_SIZE / 100.0 / minimap::BLOCK_TILES as f32,
+        });
+    }
+
+    let mut unity = UnityTerrain::new(&terrain, heightmap, resolution, layers);
+    unity.water = write_map_water(&ifo_paths, &out_dir, &map_name, &options, &mut outputs)?;
+    write_terrain_settings(&out, &unity, &options)?;
+    outputs.push(out);
+
+    debug!("Exported map {} in {:.2?}", map_name, start.elapsed());
+    Ok(outputs)
+}
+
+/// Export the terrain of a map for Unreal Engine: a 16-bit PNG heightmap,
+/// weight maps, the tile textures as PNG, the placed objects as a data table
+/// and the import settings of the landscape
+fn map_dir_unreal(matches: &ArgMatches, map_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let start = Instant::now();
+    let options = ConvertOptions::from_matches(matches);
+    let map_name = map_dir.file_name().unwrap_or_default().to_string_lossy();
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"))
+        .join(format!("{}_unreal", map_name));
+    let extension = if options.format == Format::Yaml { "yaml" } else { "json" };
+    let out = out_dir.join(format!("{}.{}", map_name, extension));
+    if !prepare_output(&out, &options)? {
+        return Ok(vec![out]);
+    }
+
+    let him_files = map_block_files(map_dir, "him")?;
+    if him_files.is_empty() {
+        bail!("No HIM files in map directory: {}", map_dir.display());
+    }
+    let zon_path = map_dir.join(format!("{}.ZON", map_name));
+    let ifo_paths = map_ifo_paths(map_dir)?;
+    if options.dry_run {
+        for path in him_files.values().chain(&ifo_paths) {
+            dry_run_read(path);
+        }
+        dry_run_read(&zon_path);
+        dry_run_write(&out);
+        return Ok(vec![out]);
+    }
+
+    let terrain = read_map_terrain(&him_files)?;
+    let zon = ZON::from_path(&zon_path)?;
+    create_output_dir(&out_dir)?;
+    let mut outputs = Vec::new();
+
+    let size = [
+        terrain::unreal_size(terrain.cols()),
+        terrain::unreal_size(terrain.rows()),
+    ];
+    let heightmap = format!("{}.png", map_name);
+    info!("Saving {}x{} heightmap to: {}", size[0], size[1], heightmap);
+    terrain::unreal_heightmap(&terrain, size[0], size[1]).save(out_dir.join(&heightmap))?;
+    outputs.push(out_dir.join(&heightmap));
+
+    // Weight maps are the size of the heightmap
+    let splat_size: u32 = matches.value_of("splat_size").unwrap_or("4").parse()?;
+    let textures = read_tile_textures(&map_client_dir(matches, map_dir), &zon);
+    let splatmap = draw_map_splatmap(
+        map_dir,
+        &zon,
+        textures.clone(),
+        splat_size,
+        terrain.min_block,
+        terrain.max_block,
+    )?;
+    let mut layers = Vec::new();
+    for channel in splatmap.channels(&zon.textures) {
+        let weights = match splatmap.weights(channel.texture_id) {
+            Some(weights) => weights,
+            None => continue,
+        };
+        let name = Path::new(&channel.texture)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let weightmap = format!("{}_{}.png", map_name, name);
+        image::imageops::resize(
+            weights,
+            size[0] as u32,
+            size[1] as u32,
+            image::imageops::FilterType::Triangle,
+        )
+        .save(out_dir.join(&weightmap))?;
+        outputs.push(out_dir.join(&weightmap));
+
+        let texture = match textures.get(channel.texture_id) {
+            Some(Some(img)) => Some(save_terrain_texture(
+                img,
+                &channel.texture,
+                &out_dir,
+                &mut outputs,
+            )?),
+            _ => None,
+        };
+        layers.push(UnrealLayer {
+            name,
+            source: channel.texture,
+            texture,
+            weightmap,
+        });
+    }
+
+    // Without the zone's ZSCs the landscape is still useful
+    let instances = if ifo_paths.is_empty() {
+        Ok(None)
+    } else {
+        read_map_scenes(matches, map_dir)
+            .and_then(|(decorations, buildings)| {
+                read_map_instances(&ifo_paths, &decorations, &buildings)
+            })
+            .map(Some)
+    };
+    let placements = match instances {
+        Ok(None) => None,
+        Ok(Some(instances)) => {
+            let name = format!("{}_placements.csv", map_name);
+            fs::write(
+                out_dir.join(&name),
+                ifo_instances::to_unreal_csv(&instances, &options.csv)?,
+            )?;
+            outputs.push(out_dir.join(&name));
+            Some(name)
+        }
+        Err(e) => {
+            warn!("Skipping the placements of {}: {}", map_name, e);
+            None
+        }
+    };
+
+    let mut landscape = UnrealLandscape::new(&terrain, heightmap, size, layers, placements);
+    landscape.water = write_map_water(&ifo_paths, &out_dir, &map_name, &options, &mut outputs)?;
+    write_terrain_settings(&out, &landscape, &options)?;
+    outputs.push(out);
+
+    debug!("Exported map {} in {:.2?}", map_name, start.elapsed());
+    Ok(outputs)
+}
+
+/// Export a map for Godot: a scene instancing glTFs of the terrain, textured
+/// like `map minimap`, and of the placed objects, with their textures as PNG
+fn map_dir_godot(matches: &ArgMatches, map_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let start = Instant::now();
+    let options = ConvertOptions::from_matches(matches);
+    let map_name = map_dir.file_name().unwrap_or_default().to_string_lossy();
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"))
+        .join(format!("{}_godot", map_name));
+    let out = out_dir.join(format!("{}.tscn", map_name));
+    if !prepare_output(&out, &options)? {
+        return Ok(vec![out]);
+    }
+
+    let him_files = map_block_files(map_dir, "him")?;
+    if him_files.is_empty() {
+        bail!("No HIM files in map directory: {}", map_dir.display());
+    }
+    let zon_path = map_dir.join(format!("{}.ZON", map_name));
+    let ifo_paths = map_ifo_paths(map_dir)?;
+    if options.dry_run {
+        for path in him_files.values().chain(&ifo_paths) {
+            dry_run_read(path);
+        }
+        dry_run_read(&zon_path);
+        dry_run_write(&out);
+        return Ok(vec![out]);
+    }
+
+    let terrain = read_map_terrain(&him_files)?;
+    let zon = ZON::from_path(&zon_path)?;
+    let client_dir = map_client_dir(matches, map_dir);
+    create_output_dir(&out_dir)?;
+    let mut outputs = Vec::new();
+
+    // Texture of the whole terrain, drawn from the tiles
+    let tile_size: u32 = matches.value_of("splat_size").unwrap_or("4").parse()?;
+    let minimap = Minimap::new(&zon.tiles, read_tile_textures(&client_dir, &zon), tile_size);
+    let block_size = minimap.block_size();
+    let [x_min, y_min] = terrain.min_block;
+    let [x_max, y_max] = terrain.max_block;
+    let mut img = RgbaImage::new(
+        (x_max - x_min + 1) * block_size,
+        (y_max - y_min + 1) * block_size,
+    );
+    for ((x, y), til_path) in map_block_files(map_dir, "til")? {
+        if x < x_min || y < y_min || x > x_max || y > y_max {
+            continue;
+        }
+        let til = TIL::from_path(&til_path)?;
+        minimap.draw_block(&mut img, (x - x_min) * block_size, (y - y_min) * block_size, &til);
+    }
+    let terrain_name = format!("{}_terrain", map_name);
+    let texture_uri = format!("{}/{}.png", gltf::TEXTURE_DIR, terrain_name);
+    create_output_dir(&out_dir.join(gltf::TEXTURE_DIR))?;
+    img.save(out_dir.join(&texture_uri))?;
+    outputs.push(out_dir.join(&texture_uri));
+
+    let doc = gltf::terrain(&terrain_name, &terrain, Some(texture_uri));
+    write_gltf(doc, &out_dir, &terrain_name, &options, &mut outputs)?;
+    let mut scene = GodotScene::new(&map_name);
+    let resource = scene.resource(&format!("{}.gltf", terrain_name));
+    scene.add_instance("Terrain", ".", resource, None);
+    if let Some(water) = write_map_water(&ifo_paths, &out_dir, &map_name, &options, &mut outputs)? {
+        let resource = scene.resource(&water);
+        scene.add_instance("Water", ".", resource, None);
+    }
+
+    // Without the zone's ZSCs the terrain is still useful
+    let scenes = if ifo_paths.is_empty() {
+        Ok(None)
+    } else {
+        read_map_scenes(matches, map_dir).and_then(|(decorations, buildings)| {
+            let instances = read_map_instances(&ifo_paths, &decorations, &buildings)?;
+            Ok(Some((decorations, buildings, instances)))
+        })
+    };
+    match scenes {
+        Ok(None) => {}
+        Ok(Some((decorations, buildings, instances))) => {
+            let mut textures = gltf::Textures::new(&client_dir, &out_dir);
+            scene.add_group("Decorations");
+            scene.add_group("Buildings");
+            let bake = matches.is_present("lightmaps");
+            let mut lits = HashMap::new();
+            let mut pages: HashMap<PathBuf, Option<RgbaImage>> = HashMap::new();
+            let mut counts = HashMap::new();
+            let names = ifo_instances::instance_names(&instances);
+            for (instance, name) in instances.iter().zip(names) {
+                // LIT objects are numbered from 1 by kind in their block
+                let key = (instance.kind == InstanceKind::Building, instance.block);
+                let count = counts.entry(key).or_insert(0);
+                *count += 1;
+                if instance.parts.is_none() {
+                    continue;
+                }
+                let (zsc, prefix, group) = match instance.kind {
+                    InstanceKind::Decoration => (&decorations, "deco", "Decorations"),
+                    InstanceKind::Building => (&buildings, "cnst", "Buildings"),
+                };
+
+                if bake {
+                    let entries = lits
+                        .entry(key)
+                        .or_insert_with(|| read_block_lightmaps(map_dir, instance));
+                    let entries: Vec<_> = entries
+                        .iter()
+                        .filter(|(entry, _)| entry.object == *count)
+                        .collect();
+                    for (_, page_path) in &entries {
+                        pages.entry(page_path.clone()).or_insert_with(|| {
+                            match read_image(page_path) {
+                                Ok(img) => Some(img),
+                                Err(e) => {
+                                    warn!("Skipping lightmap page {}: {}", page_path.display(), e);
+                                    None
+                                }
+                            }
+                        });
+                    }
+                    let lightmaps: HashMap<usize, PartLightmap> = entries
+                        .iter()
+                        .filter_map(|(entry, page_path)| {
+                            let page = pages.get(page_path)?.as_ref()?;
+                            let lightmap = PartLightmap {
+                                page,
+                                uv: entry.uv,
+                            };
+                            Some((entry.part as usize, lightmap))
+                        })
+                        .collect();
+                    // Lit instances get their own object with baked colors
+                    if !lightmaps.is_empty() {
+                        let doc = gltf::zsc_object_lit(
+                            zsc,
+                            instance.object_id as usize,
+                            &name,
+                            &client_dir,
+                            &mut textures,
+                            &lightmaps,
+                        )?;
+                        write_gltf(doc, &out_dir, &name, &options, &mut outputs)?;
+                        let resource = scene.resource(&format!("{}.gltf", name));
+                        let transform = godot::instance_transform(instance);
+                        scene.add_instance(&name, group, resource, Some(transform));
+                        continue;
+                    }
+                }
+
+                // Every object of the ZSCs is exported once
+                let object_name = format!("{}_{}", prefix, instance.object_id);
+                let path = format!("{}.gltf", object_name);
+                if !outputs.contains(&out_dir.join(&path)) {
+                    let doc = gltf::zsc_object(
+                        zsc,
+                        instance.object_id as usize,
+                        &object_name,
+                        &client_dir,
+                        &mut textures,
+                    )?;
+                    write_gltf(doc, &out_dir, &object_name, &options, &mut outputs)?;
+                }
+                let resource = scene.resource(&path);
+                let transform = godot::instance_transform(instance);
+                scene.add_instance(&name, group, resource, Some(transform));
+            }
+            outputs.extend(textures.paths());
+        }
+        Err(e) => warn!("Skipping the placed objects of {}: {}", map_name, e),
+    }
+
+    info!("Saving scene to: {}", out.display());
+    fs::write(&out, scene.to_tscn())?;
+    outputs.push(out);
+
+    debug!("Exported map {} in {:.2?}", map_name, start.elapsed());
+    Ok(outputs)
+}
+
+/// Lightmaps of the LIT of the decorations or buildings of the block of an
+/// instance with the path of their page, empty without a LIT
+fn read_block_lightmaps(
+    map_dir: &Path,
+    instance: &ifo_instances::Instance,
+) -> Vec<(LightmapEntry, PathBuf)> {
+    let lit_name = match instance.kind {
+        InstanceKind::Decoration => "OBJECTLIGHTMAPDATA.LIT",
+        InstanceKind::Building => "BUILDINGLIGHTMAPDATA.LIT",
+    };
+    let [x, y] = instance.block;
+    let lit_path = format!("{}_{}/LIGHTMAP/{}", x, y, lit_name);
+    let lit_path = match vfs::resolve_client_path(map_dir, &lit_path) {
+        Some(path) => path,
+        None => return Vec::new(),
+    };
+    let lit = match LIT::from_path(&lit_path) {
+        Ok(lit) => lit,
+        Err(e) => {
+            warn!("Skipping lightmaps of {}: {}", lit_path.display(), e);
+            return Vec::new();
+        }
+    };
+    let lit_dir = lit_path.parent().unwrap_or_else(|| Path::new(""));
+    lightmap::lightmap_entries(&lit)
+        .into_iter()
+        .filter_map(|entry| {
+            let page = vfs::resolve_client_path(lit_dir, &entry.page);
+            if page.is_none() {
+                warn!("Lightmap page not found in {}: {}", lit_dir.display(), entry.page);
+            }
+            Some((entry, page?))
+        })
+        .collect()
+}
+
+/// Write a glTF document as `<name>.gltf` and `<name>.bin`
+fn write_gltf(
+    mut doc: gltf::Document,
+    out_dir: &Path,
+    name: &str,
+    options: &ConvertOptions,
+    outputs: &mut Vec<PathBuf>,
+) -> Result<(), Error> {
+    doc.convert_axes(name, &options.axes.unwrap_or_default());
+    let (json, buffer) = doc.to_files(name, options.pretty)?;
+    let gltf_path = out_dir.join(format!("{}.gltf", name));
+    let bin_path = out_dir.join(format!("{}.bin", name));
+    fs::write(&gltf_path, json)?;
+    fs::write(&bin_path, buffer)?;
+    outputs.push(gltf_path);
+    outputs.push(bin_path);
+    Ok(())
+}
+
+/// OBJ in the axes of `--up-axis`, `--handedness` and `--scale`
+fn obj_axes(obj: &str, options: &ConvertOptions) -> String {
+    options.axes.unwrap_or_default().convert_obj(obj)
+}
+
+/// Write the water planes of the IFOs of a map as `<map>_water.gltf`,
+/// returning its file name, or `None` without water
+fn write_map_water(
+    ifo_paths: &[PathBuf],
+    out_dir: &Path,
+    map_name: &str,
+    options: &ConvertOptions,
+    outputs: &mut Vec<PathBuf>,
+) -> Result<Option<String>, Error> {
+    let mut planes = Vec::new();
+    for path in ifo_paths {
+        planes.extend(water::water_planes(&IFO::from_path(path)?));
+    }
+    if planes.is_empty() {
+        return Ok(None);
+    }
+    let name = format!("{}_water", map_name);
+    info!("Saving {} water planes to: {}.gltf", planes.len(), name);
+    write_gltf(gltf::water(&name, &planes), out_dir, &name, options, outputs)?;
+    Ok(Some(format!("{}.gltf", name)))
+}
+
+/// Stitch the HIMs of a map by block coordinates
+fn read_map_terrain(him_files: &BTreeMap<(u32, u32), PathBuf>) -> Result<Terrain, Error> {
+    let mut hims = BTreeMap::new();
+    for (&block, path) in him_files {
+        hims.insert(block, HIM::from_path(path)?);
+    }
+    Ok(Terrain::from_blocks(&hims))
+}
+
+/// Save a tile texture as `Textures/<name>.png`, returning its path relative
+/// to `out_dir`
+fn save_terrain_texture(
+    img: &RgbaImage,
+    source: &str,
+    out_dir: &Path,
+    outputs: &mut Vec<PathBuf>,
+) -> Result<String, Error> {
+    let stem = Path::new(source).file_stem().unwrap_or_default().to_string_lossy();
+    let name = format!("Textures/{}.png", stem);
+    create_output_dir(&out_dir.join("Textures"))?;
+    img.save(out_dir.join(&name))?;
+    outputs.push(out_dir.join(&name));
+    Ok(name)
+}
+
+/// Write the settings of a terrain as JSON, or YAML with `--format yaml`
+fn write_terrain_settings<T: Serialize>(
+    out: &Path,
+    settings: &T,
+    options: &ConvertOptions,
+) -> Result<(), Error> {
+    info!("Saving terrain settings to: {}", out.display());
+    if options.format == Format::Yaml {
+        fs::write(out, serde_yaml::to_string(settings)?)?;
+    } else {
+        write_json(out, settings, options)?;
+    }
+    Ok(())
+}
+
+/// Write the collision geometry of a map as `<map>_collision.obj`
+fn map_collision(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let map_dir = Path::new(matches.value_of("map_dir").unwrap());
+
+    let res = map_dir_collision(matches, map_dir);
+    match &res {
+        Ok(outputs) => report.success(map_dir, "map", outputs.clone()),
+        Err(e) => report.failure(map_dir, e),
+    }
+    res.map(|_| ())
+}
+
+fn map_dir_collision(matches: &ArgMatches, map_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !map_dir.is_dir() {
+        bail!("Map path is not a directory: {:?}", map_dir);
+    }
+    let start = Instant::now();
+    let options = ConvertOptions::from_matches(matches);
+    let map_name = map_dir.file_name().unwrap_or_default().to_string_lossy();
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"));
+    let out = out_dir.join(format!("{}_collision.obj", map_name));
+
+    if !prepare_output(&out, &options)? {
+        return Ok(vec![out]);
+    }
+
+    let ifo_paths = map_ifo_paths(map_dir)?;
+    let him_files = if matches.is_present("terrain") {
+        map_block_files(map_dir, "him")?
+    } else {
+        BTreeMap::new()
+    };
+    if options.dry_run {
+        for path in ifo_paths.iter().chain(him_files.values()) {
+            dry_run_read(path);
+        }
+        dry_run_write(&out);
+        return Ok(vec![out]);
+    }
+
+    let client_dir = map_client_dir(matches, map_dir);
+    let (decorations, buildings) = read_map_scenes(matches, map_dir)?;
+    let instances = read_map_instances(&ifo_paths, &decorations, &buildings)?;
+
+    // Meshes of the colliding parts by ZSC, read once each
+    let mut meshes: HashMap<(usize, u16), Option<ZMS>> = HashMap::new();
+    for instance in &instances {
+        let (scene, zsc) = match instance.kind {
+            InstanceKind::Decoration => (0, &decorations),
+            InstanceKind::Building => (1, &buildings),
+        };
+        let object = match usize::try_from(instance.object_id)
+            .ok()
+            .and_then(|id| zsc.objects.get(id))
+        {
+            Some(object) => object,
+            None => continue,
+        };
+        for part in &object.parts {
+            if CollisionShape::from_part(part).is_none()
+                || meshes.contains_key(&(scene, part.mesh_id))
+            {
+                continue;
+            }
+            let mesh = match zsc.meshes.get(part.mesh_id as usize) {
+                Some(path) => {
+                    let path = path.to_string_lossy();
+                    match vfs::resolve_client_path(&client_dir, &path) {
+                        Some(path) => Some(ZMS::from_path(&path)?),
+                        None => {
+                            warn!("Mesh not found in {}: {}", client_dir.display(), path);
+                            None
+                        }
+                    }
+                }
+                None => None,
+            };
+            meshes.insert((scene, part.mesh_id), mesh);
+        }
+    }
+
+    let mut obj = CollisionObj::new(&map_name);
+    let mut parts = 0;
+    let names = ifo_instances::instance_names(&instances);
+    for (instance, name) in instances.iter().zip(names) {
+        let (scene, zsc) = match instance.kind {
+            InstanceKind::Decoration => (0, &decorations),
+            InstanceKind::Building => (1, &buildings),
+        };
+        if let Some(object) = usize::try_from(instance.object_id)
+            .ok()
+            .and_then(|id| zsc.objects.get(id))
+        {
+            parts += obj.add_instance(&name, instance, object, |mesh_id| {
+                meshes.get(&(scene, mesh_id)).and_then(Option::as_ref)
+            });
+        }
+    }
+    if !him_files.is_empty() {
+        obj.add_terrain(&read_map_terrain(&him_files)?);
+    }
+
+    create_output_dir(out_dir)?;
+    fs::write(&out, obj_axes(obj.to_obj(), &options))?;
+
+    debug!(
+        "Exported {} collision parts of {} with {} triangles in {:.2?}",
+        parts,
+        map_name,
+        obj.triangles,
+        start.elapsed()
+    );
+    Ok(vec![out])
+}
+
+/// Write the decorations and buildings of a map with the models of their ZSC
+/// objects as JSON
+fn map_instances(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let map_dir = Path::new(matches.value_of("map_dir").unwrap());
+
+    let res = map_dir_instances(matches, map_dir);
+    match &res {
+        Ok(outputs) => report.success(map_dir, "map", outputs.clone()),
+        Err(e) => report.failure(map_dir, e),
+    }
+    res.map(|_| ())
+}
+
+fn map_dir_instances(matches: &ArgMatches, map_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !map_dir.is_dir() {
+        bail!("Map path is not a directory: {:?}", map_dir);
+    }
+    let start = Instant::now();
+    let options = ConvertOptions::from_matches(matches);
+    let map_name = map_dir.file_name().unwrap_or_default().to_string_lossy();
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"));
+    let out = out_dir.join(format!("{}_instances.json", map_name));
+
+    if !prepare_output(&out, &options)? {
+        return Ok(vec![out]);
+    }
+
+    let ifo_paths = map_ifo_paths(map_dir)?;
+    if options.dry_run {
+        for path in &ifo_paths {
+            dry_run_read(path);
+        }
+        dry_run_write(&out);
+        return Ok(vec![out]);
+    }
+
+    let (decorations, buildings) = read_map_scenes(matches, map_dir)?;
+    let instances = read_map_instances(&ifo_paths, &decorations, &buildings)?;
+    create_output_dir(out_dir)?;
+    write_json(&out, &instances, &options)?;
+
+    debug!(
+        "Listed {} instances of {} in {:.2?}",
+        instances.len(),
+        map_name,
+        start.elapsed()
+    );
+    Ok(vec![out])
+}
+
+/// IFO files of a map directory, sorted
+fn map_ifo_paths(map_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut ifo_paths = Vec::new();
+    for entry in fs::read_dir(map_dir)? {
+        let path = entry?.path();
+        if path.is_file() && lowercase_extension(&path) == "ifo" {
+            ifo_paths.push(path);
+        }
+    }
+    ifo_paths.sort();
+    Ok(ifo_paths)
+}
+
+/// Decoration and construction ZSCs of the zone of a map, or the ZSCs of
+/// `--decorations` and `--buildings`
+fn read_map_scenes(matches: &ArgMatches, map_dir: &Path) -> Result<(ZSC, ZSC), Error> {
+    let client_dir = map_client_dir(matches, map_dir);
+
+    // Client paths of the zone's ZSCs, unless both are given
+    let zone_scenes = match (matches.value_of("decorations"), matches.value_of("buildings")) {
+        (Some(_), Some(_)) => None,
+        _ => Some(
+            zone_scene_paths(&client_dir, map_dir)
+                .map_err(|e| format_err!("{}, use --decorations and --buildings", e))?,
+        ),
+    };
+
+    let read_scene = |arg: &str, scene: Option<&String>| -> Result<ZSC, Error> {
+        let path = match (matches.value_of(arg), scene) {
+            (Some(path), _) => PathBuf::from(path),
+            // Zones without decorations or buildings
+            (None, Some(scene)) if scene.is_empty() => return Ok(ZSC::new()),
+            (None, Some(scene)) => vfs::resolve_client_path(&client_dir, scene).ok_or_else(|| {
+                format_err!("ZSC not found in {}: {}", client_dir.display(), scene)
+            })?,
+            (None, None) => unreachable!(),
+        };
+        debug!("Reading {} from {}", arg, path.display());
+        ZSC::from_path(&path)
+    };
+    let decorations = read_scene("decorations", zone_scenes.as_ref().map(|s| &s.0))?;
+    let buildings = read_scene("buildings", zone_scenes.as_ref().map(|s| &s.1))?;
+    Ok((decorations, buildings))
+}
+
+/// Decorations and buildings of the IFOs of a map with the models of their
+/// objects in the zone's ZSCs
+fn read_map_instances(
+    ifo_paths: &[PathBuf],
+    decorations: &ZSC,
+    buildings: &ZSC,
+) -> Result<Vec<ifo_instances::Instance>, Error> {
+    let mut instances = Vec::new();
+    for path in ifo_paths {
+        let ifo = IFO::from_path(path)?;
+        for instance in ifo_instances::instances(&ifo, decorations, buildings) {
+            if instance.parts.is_none() {
+                warn!(
+                    "{}: {:?} object {} is not in the ZSC",
+                    path.display(),
+                    instance.kind,
+                    instance.object_id
+                );
+            }
+            instances.push(instance);
+        }
+    }
+    Ok(instances)
+}
+
+/// Write the navigation mesh of a map as `<map>_navmesh.obj` with its cells
+/// as `<map>_navmesh.json`
+fn map_navmesh(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let map_dir = Path::new(matches.value_of("map_dir").unwrap());
+
+    let res = map_dir_navmesh(matches, map_dir);
+    match &res {
+        Ok(outputs) => report.success(map_dir, "map", outputs.clone()),
+        Err(e) => report.failure(map_dir, e),
+    }
+    res.map(|_| ())
+}
+
+fn map_dir_navmesh(matches: &ArgMatches, map_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !map_dir.is_dir() {
+        bail!("Map path is not a directory: {:?}", map_dir);
+    }
+    let start = Instant::now();
+    let options = ConvertOptions::from_matches(matches);
+    let map_name = map_dir.file_name().unwrap_or_default().to_string_lossy();
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"));
+    let obj_path = out_dir.join(format!("{}_navmesh.obj", map_name));
+    let json_path = out_dir.join(format!("{}_navmesh.json", map_name));
+    let outputs = vec![obj_path.clone(), json_path.clone()];
+
+    if !prepare_output(&obj_path, &options)? {
+        return Ok(outputs);
+    }
+
+    let him_files = map_block_files(map_dir, "him")?;
+    if him_files.is_empty() {
+        bail!("No HIM files in map directory: {}", map_dir.display());
+    }
+    let ifo_paths = map_ifo_paths(map_dir)?;
+    if options.dry_run {
+        for path in him_files.values().chain(&ifo_paths) {
+            dry_run_read(path);
+        }
+        dry_run_write(&obj_path);
+        dry_run_write(&json_path);
+        return Ok(outputs);
+    }
+
+    let max_slope: f32 = matches.value_of("max_slope").unwrap_or("45").parse()?;
+    let terrain = read_map_terrain(&him_files)?;
+    let mut navmesh = Navmesh::new(&terrain, max_slope);
+
+    // Without the zone's ZSCs only the slopes block the way
+    if !ifo_paths.is_empty() {
+        match read_map_scenes(matches, map_dir).and_then(|(decorations, buildings)| {
+            let instances = read_map_instances(&ifo_paths, &decorations, &buildings)?;
+            Ok((decorations, buildings, instances))
+        }) {
+            Ok((decorations, buildings, instances)) => {
+                for instance in &instances {
+                    let zsc = match instance.kind {
+                        InstanceKind::Decoration => &decorations,
+                        InstanceKind::Building => &buildings,
+                    };
+                    let footprint = usize::try_from(instance.object_id)
+                        .ok()
+                        .and_then(|id| zsc.objects.get(id))
+                        .and_then(|object| navmesh::footprint(instance, object));
+                    if let Some(footprint) = footprint {
+                        navmesh.block(&footprint);
+                    }
+                }
+            }
+            Err(e) => warn!("Skipping objects of {}: {}", map_name, e),
+        }
+    }
+
+    create_output_dir(out_dir)?;
+    fs::write(&obj_path, obj_axes(&navmesh.to_obj(&map_name), &options))?;
+    let grid = navmesh.grid();
+    write_json(&json_path, &grid, &options)?;
+
+    debug!(
+        "Built navigation mesh of {} with {} walkable cells in {:.2?}",
+        map_name,
+        grid.walkable,
+        start.elapsed()
+    );
+    Ok(outputs)
+}
+
+/// Write the manifest of a zone as `<map>_manifest.json`
+fn map_manifest(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let map_dir = Path::new(matches.value_of("map_dir").unwrap());
+
+    let res = map_dir_manifest(matches, map_dir);
+    match &res {
+        Ok(outputs) => report.success(map_dir, "map", outputs.clone()),
+        Err(e) => report.failure(map_dir, e),
+    }
+    res.map(|_| ())
+}
+
+fn map_dir_manifest(matches: &ArgMatches, map_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !map_dir.is_dir() {
+        bail!("Map path is not a directory: {:?}", map_dir);
+    }
+    let start = Instant::now();
+    let options = ConvertOptions::from_matches(matches);
+    let map_name = map_dir.file_name().unwrap_or_default().to_string_lossy();
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"));
+    let out = out_dir.join(format!("{}_manifest.json", map_name));
+    let zon_path = map_dir.join(format!("{}.ZON", map_name));
+
+    if !prepare_output(&out, &options)? {
+        return Ok(vec![out]);
+    }
+
+    // Blocks by coordinates, from the `<x>_<y>` names of their files
+    let mut blocks: BTreeMap<(u32, u32), BlockSummary> = BTreeMap::new();
+    let mut ifo_paths = Vec::new();
+    for entry in fs::read_dir(map_dir)? {
+        let path = entry?.path();
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let coordinates = match stem.split_once('_') {
+            Some((x, y)) => (x.parse::<u32>(), y.parse::<u32>()),
+            None => continue,
+        };
+        let (x, y) = match coordinates {
+            (Ok(x), Ok(y)) => (x, y),
+            _ => continue,
+        };
+        let block = blocks
+            .entry((x, y))
+            .or_insert_with(|| BlockSummary::new(x, y));
+        match lowercase_extension(&path).as_str() {
+            "him" => block.has_heightmap = true,
+            "til" => block.has_tiles = true,
+            "ifo" => ifo_paths.push(((x, y), path)),
+            _ => {}
+        }
+    }
+    blocks.retain(|_, b| b.has_heightmap || b.has_tiles);
+    ifo_paths.sort();
+
+    if options.dry_run {
+        dry_run_read(&zon_path);
+        for (_, path) in &ifo_paths {
+            dry_run_read(path);
+        }
+        dry_run_write(&out);
+        return Ok(vec![out]);
+    }
+
+    let mut manifest = ZoneManifest::new(&map_name, ZON::from_path(&zon_path)?);
+    let mut ifos = Vec::new();
+    for (coordinates, path) in ifo_paths {
+        let ifo = IFO::from_path(&path).map_err(|e| format_err!("{}: {}", path.display(), e))?;
+        blocks
+            .entry(coordinates)
+            .or_insert_with(|| BlockSummary::new(coordinates.0, coordinates.1))
+            .set_objects(&ifo);
+        ifos.push(ifo);
+    }
+    manifest.set_blocks(blocks.into_values().collect());
+
+    let client_dir = match matches.value_of("client") {
+        Some(dir) => PathBuf::from(dir),
+        None => vfs::find_client_dir(map_dir).unwrap_or_else(|| PathBuf::from(".")),
+    };
+    match zone_scene_paths(&client_dir, map_dir) {
+        Ok((decorations_path, buildings_path)) => {
+            let read_scene = |scene: &str| -> Result<ZSC, Error> {
+                if scene.is_empty() {
+                    return Ok(ZSC::new());
+                }
+                match vfs::resolve_client_path(&client_dir, scene) {
+                    Some(path) => ZSC::from_path(&path),
+                    None => bail!("ZSC not found in {}: {}", client_dir.display(), scene),
+                }
+            };
+            let decorations = read_scene(&decorations_path)?;
+            let buildings = read_scene(&buildings_path)?;
+            let scenes = ZoneScenes {
+                decorations: decorations_path,
+                buildings: buildings_path,
+            };
+            manifest.set_scenes(scenes, &ifos, &decorations, &buildings);
+        }
+        Err(e) => warn!("The ZSCs of {} are left out: {}", map_name, e),
+    }
+
+    create_output_dir(out_dir)?;
+    write_json(&out, &manifest, &options)?;
+
+    debug!("Wrote the manifest of {} in {:.2?}", map_name, start.elapsed());
+    Ok(vec![out])
+}
+
+/// Render the tiles of a map as `<map>_minimap.png`, or as a tile pyramid in
+/// `<map>_tiles` with `--tiles`
+fn map_minimap(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let map_dir = Path::new(matches.value_of("map_dir").unwrap());
+
+    let res = map_dir_minimap(matches, map_dir);
+    match &res {
+        Ok(outputs) => report.success(map_dir, "map", outputs.clone()),
+        Err(e) => report.failure(map_dir, e),
+    }
+    res.map(|_| ())
+}
+
+fn map_dir_minimap(matches: &ArgMatches, map_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !map_dir.is_dir() {
+        bail!("Map path is not a directory: {:?}", map_dir);
+    }
+    let start = Instant::now();
+    let options = ConvertOptions::from_matches(matches);
+    let map_name = map_dir.file_name().unwrap_or_default().to_string_lossy();
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"));
+    let tiles_dir = out_dir.join(format!("{}_tiles", map_name));
+    let web_tile_size: Option<u32> = if matches.is_present("tiles") {
+        Some(matches.value_of("web_tile_size").unwrap_or("256").parse()?)
+    } else {
+        None
+    };
+    // The pyramid is skipped or overwritten as a whole, by its metadata
+    let out = match web_tile_size {
+        Some(_) => tiles_dir.join("tiles.json"),
+        None => out_dir.join(format!("{}_minimap.png", map_name)),
+    };
+    let zon_path = map_dir.join(format!("{}.ZON", map_name));
+    let tile_size: u32 = matches.value_of("tile_size").unwrap_or("4").parse()?;
+    let hillshade = matches.is_present("hillshade");
+
+    if !prepare_output(&out, &options)? {
+        return Ok(vec![out]);
+    }
+
+    let tile_paths = map_block_files(map_dir, "til")?;
+    if tile_paths.is_empty() {
+        bail!("No TIL files in map directory: {}", map_dir.display());
+    }
+    let x_min = tile_paths.keys().map(|(x, _)| *x).min().unwrap_or_default();
+    let x_max = tile_paths.keys().map(|(x, _)| *x).max().unwrap_or_default();
+    let y_min = tile_paths.keys().map(|(_, y)| *y).min().unwrap_or_default();
+    let y_max = tile_paths.keys().map(|(_, y)| *y).max().unwrap_or_default();
+
+    if options.dry_run {
+        dry_run_read(&zon_path);
+        for path in tile_paths.values() {
+            dry_run_read(path);
+            if hillshade {
+                dry_run_read(&path.with_extension("HIM"));
+            }
+        }
+        dry_run_write(&out);
+        return Ok(vec![out]);
+    }
+
+    let zon = ZON::from_path(&zon_path)?;
+    let client_dir = match matches.value_of("client") {
+        Some(dir) => PathBuf::from(dir),
+        None => vfs::find_client_dir(map_dir).unwrap_or_else(|| PathBuf::from(".")),
+    };
+    let minimap = Minimap::new(&zon.tiles, read_tile_textures(&client_dir, &zon), tile_size);
+
+    let block_size = minimap.block_size();
+    // Missing blocks are left transparent
+    let mut img = RgbaImage::new(
+        (x_max - x_min + 1) * block_size,
+        (y_max - y_min + 1) * block_size,
+    );
+    for ((x, y), path) in &tile_paths {
+        let til = TIL::from_path(path).map_err(|e| format_err!("{}: {}", path.display(), e))?;
+        let (px, py) = ((x - x_min) * block_size, (y - y_min) * block_size);
+        minimap.draw_block(&mut img, px, py, &til);
+
+        if hillshade {
+            hillshade_map_block(&mut img, px, py, block_size, path, &map_name)?;
+        }
+    }
+
+    let outputs = match web_tile_size {
+        Some(web_tile_size) => write_tile_pyramid(&img, &tiles_dir, web_tile_size, &options)?,
+        None => {
+            create_output_dir(out_dir)?;
+            img.save(&out)?;
+            vec![out]
+        }
+    };
+
+    debug!("Rendered the minimap of {} in {:.2?}", map_name, start.elapsed());
+    Ok(outputs)
+}
+
+/// Map block files with extension `extension` of a map directory by block
+/// coordinates, from their `<x>_<y>` names
+fn map_block_files(map_dir: &Path, extension: &str) -> Result<BTreeMap<(u32, u32), PathBuf>, Error> {
+    let mut files = BTreeMap::new();
+    for entry in fs::read_dir(map_dir)? {
+        let path = entry?.path();
+        if lowercase_extension(&path) != extension {
+            continue;
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        if let Some((x, y)) = stem.split_once('_') {
+            if let (Ok(x), Ok(y)) = (x.parse::<u32>(), y.parse::<u32>()) {
+                files.insert((x, y), path.clone());
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Read the tile textures of a ZON, `None` for the missing ones
+fn read_tile_textures(client_dir: &Path, zon: &ZON) -> Vec<Option<RgbaImage>> {
+    zon.textures
+        .iter()
+        .map(|texture| {
+            if texture.trim().is_empty() {
+                return None;
+            }
+            let img = vfs::resolve_client_path(client_dir, texture)
+                .ok_or_else(|| format_err!("not found in {}", client_dir.display()))
+                .and_then(|path| read_image(&path));
+            match img {
+                Ok(img) => Some(img),
+                Err(e) => {
+                    warn!("Skipping tile texture {}: {}", texture, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Shade a block drawn at `(x, y)` by the HIM next to its TIL file `til_path`
+fn hillshade_map_block(
+    img: &mut RgbaImage,
+    x: u32,
+    y: u32,
+    block_size: u32,
+    til_path: &Path,
+    map_name: &str,
+) -> Result<(), Error> {
+    let him_path = ["HIM", "him"]
+        .iter()
+        .map(|ext| til_path.with_extension(ext))
+        .find(|p| p.is_file());
+    match him_path {
+        Some(him_path) => {
+            let him = HIM::from_path(&him_path)
+                .map_err(|e| format_err!("{}: {}", him_path.display(), e))?;
+            minimap::hillshade_block(img, x, y, block_size, &him);
+        }
+        None => warn!(
+            "Block {} of {} has no HIM to shade",
+            til_path.file_stem().unwrap_or_default().to_string_lossy(),
+            map_name
+        ),
+    }
+    Ok(())
+}
 
-    if let Err(e) = res {
-        eprintln!("Error occured: {}", e);
-        let filename = match matches.subcommand() {
-            ("serialize", Some(matches)) => matches.value_of("input"),
-            ("deserialize", Some(matches)) => matches.value_of("input"),
-            _ => None,
+/// Write `img` as a pyramid of web map tiles `<dir>/{z}/{x}/{y}.png` with its
+/// metadata in `<dir>/tiles.json`, returning the written files
+fn write_tile_pyramid(
+    img: &RgbaImage,
+    dir: &Path,
+    tile_size: u32,
+    options: &ConvertOptions,
+) -> Result<Vec<PathBuf>, Error> {
+    let pyramid = TilePyramid::new(img.width(), img.height(), tile_size);
+    let mut outputs = Vec::new();
+    pyramid.write_tiles(img, |z, x, y, tile| {
+        let path = dir
+            .join(z.to_string())
+            .join(x.to_string())
+            .join(format!("{}.png", y));
+        create_output_dir(path.parent().unwrap())?;
+        tile.save(&path)?;
+        outputs.push(path);
+        Ok(())
+    })?;
+    let metadata = dir.join("tiles.json");
+    write_json(&metadata, &pyramid, options)?;
+    info!(
+        "Wrote {} tiles to {} with zoom levels 0 to {}",
+        outputs.len(),
+        dir.display(),
+        pyramid.max_zoom
+    );
+    outputs.push(metadata);
+    Ok(outputs)
+}
+
+/// Stitch the minimaps or heightmaps of every zone of a client into
+/// `world_atlas.png`, with the layout in `world_atlas.json`
+fn map_atlas(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let client_dir = Path::new(matches.value_of("client").unwrap());
+
+    let res = client_atlas(matches, client_dir);
+    match &res {
+        Ok(outputs) => report.success(client_dir, "map", outputs.clone()),
+        Err(e) => report.failure(client_dir, e),
+    }
+    res.map(|_| ())
+}
+
+fn client_atlas(matches: &ArgMatches, client_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let start = Instant::now();
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"));
+    let layout_out = out_dir.join("world_atlas.json");
+    let tiles_dir = out_dir.join("world_tiles");
+    let web_tile_size: Option<u32> = if matches.is_present("tiles") {
+        Some(matches.value_of("web_tile_size").unwrap_or("256").parse()?)
+    } else {
+        None
+    };
+    let out = match web_tile_size {
+        Some(_) => tiles_dir.join("tiles.json"),
+        None => out_dir.join("world_atlas.png"),
+    };
+    let tile_size: u32 = matches.value_of("tile_size").unwrap_or("2").parse()?;
+    let columns: usize = matches.value_of("columns").unwrap_or("8").parse()?;
+    let heightmap = matches.is_present("heightmap");
+    let hillshade = matches.is_present("hillshade");
+
+    let list_zone_path = vfs::resolve_client_path(client_dir, "3DDATA/STB/LIST_ZONE.STB")
+        .ok_or_else(|| format_err!("LIST_ZONE.STB not found in {}", client_dir.display()))?;
+    let names_path = match matches.value_of("labels") {
+        Some(path) => Some(
+            vfs::resolve_client_path(client_dir, path)
+                .ok_or_else(|| format_err!("{} not found in {}", path, client_dir.display()))?,
+        ),
+        None => None,
+    };
+
+    if !prepare_output(&out, &options)? || !prepare_output(&layout_out, &options)? {
+        return Ok(vec![out, layout_out]);
+    }
+
+    // Zones with their map directories and block files
+    let extension = if heightmap { "him" } else { "til" };
+    let mut zones = Vec::new();
+    let mut zone_blocks = HashMap::new();
+    for mut zone in world_atlas::list_zones(&STB::from_path(&list_zone_path)?) {
+        let zon_path = match vfs::resolve_client_path(client_dir, &zone.zon) {
+            Some(path) => path,
+            None => {
+                warn!("ZON of zone {} not found: {}", zone.id, zone.zon);
+                continue;
+            }
         };
+        let map_dir = zon_path.parent().unwrap_or_else(|| Path::new("."));
+        let blocks = map_block_files(map_dir, extension)?;
+        if blocks.is_empty() {
+            warn!("Zone {} has no {} files: {}", zone.id, extension.to_uppercase(), map_dir.display());
+            continue;
+        }
+        zone.set_blocks(&blocks.keys().copied().collect::<Vec<_>>());
+        zone_blocks.insert(zone.id, (zon_path, blocks));
+        zones.push(zone);
+    }
+
+    if options.dry_run {
+        dry_run_read(&list_zone_path);
+        if let Some(path) = &names_path {
+            dry_run_read(path);
+        }
+        for zone in &zones {
+            let (zon_path, blocks) = &zone_blocks[&zone.id];
+            dry_run_read(zon_path);
+            for path in blocks.values() {
+                dry_run_read(path);
+            }
+        }
+        dry_run_write(&out);
+        dry_run_write(&layout_out);
+        return Ok(vec![out, layout_out]);
+    }
+
+    let block_size = tile_size * minimap::BLOCK_TILES;
+    let mut atlas = WorldAtlas::new(zones, block_size, columns);
+    if let Some(path) = &names_path {
+        let language = matches.value_of("language").unwrap_or("en").parse()?;
+        atlas.set_names(&STL::from_path(path)?, language);
+    }
+
+    // Heights are scaled to the lowest and highest height of the world
+    let (mut min_height, mut max_height) = (f32::MAX, f32::MIN);
+    if heightmap {
+        for (_, blocks) in zone_blocks.values() {
+            for path in blocks.values() {
+                let him = HIM::from_path(path).map_err(|e| format_err!("{}: {}", path.display(), e))?;
+                min_height = min_height.min(him.min_height);
+                max_height = max_height.max(him.max_height);
+            }
+        }
+    }
+
+    let mut img = RgbaImage::new(atlas.width, atlas.height);
+    for zone in &atlas.zones {
+        let (zon_path, blocks) = &zone_blocks[&zone.id];
+        let map_name = zon_path.file_stem().unwrap_or_default().to_string_lossy();
+        if heightmap {
+            for ((x, y), path) in blocks {
+                let him = HIM::from_path(path).map_err(|e| format_err!("{}: {}", path.display(), e))?;
+                let (px, py) = zone.block_origin(*x, *y, block_size);
+                world_atlas::draw_heights(&mut img, px, py, block_size, &him, min_height, max_height);
+            }
+            continue;
+        }
 
-        if let Some(name) = filename {
-            eprintln!("\t{}", name);
+        let zon = match ZON::from_path(zon_path) {
+            Ok(zon) => zon,
+            Err(e) => {
+                warn!("Zone {} is left out, failed to read {}: {}", zone.id, zon_path.display(), e);
+                continue;
+            }
+        };
+        let minimap = Minimap::new(&zon.tiles, read_tile_textures(client_dir, &zon), tile_size);
+        for ((x, y), path) in blocks {
+            let til = TIL::from_path(path).map_err(|e| format_err!("{}: {}", path.display(), e))?;
+            let (px, py) = zone.block_origin(*x, *y, block_size);
+            minimap.draw_block(&mut img, px, py, &til);
+            if hillshade {
+                hillshade_map_block(&mut img, px, py, block_size, path, &map_name)?;
+            }
         }
     }
+    if names_path.is_some() {
+        atlas.draw_labels(&mut img);
+    }
+
+    let mut outputs = match web_tile_size {
+        Some(web_tile_size) => write_tile_pyramid(&img, &tiles_dir, web_tile_size, &options)?,
+        None => {
+            create_output_dir(out_dir)?;
+            img.save(&out)?;
+            vec![out]
+        }
+    };
+    create_output_dir(out_dir)?;
+    write_json(&layout_out, &atlas, &options)?;
+    outputs.push(layout_out);
+
+    debug!(
+        "Stitched {} zones into a {}x{} atlas in {:.2?}",
+        atlas.zones.len(),
+        atlas.width,
+        atlas.height,
+        start.elapsed()
+    );
+    Ok(outputs)
 }
 
-fn create_output_dir(out_dir: &Path) -> Result<(), Error> {
-    if let Err(e) = fs::create_dir_all(&out_dir) {
-        bail!(
-            "Error creating output directory {}: {}",
-            out_dir.to_str().unwrap_or(""),
-            e
-        );
+/// Collect the monster spawns and NPCs of IFO files into
+/// `<name>_spawns.json`, named after the input if there is only one
+fn map_spawns(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"));
+    let input_args: Vec<&str> = matches.values_of("input").unwrap_or_default().collect();
+    let name = match input_args.as_slice() {
+        [input] => Path::new(input)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        _ => String::from("maps"),
+    };
+    let extension = if matches.is_present("csv") { "csv" } else { "json" };
+    let out = out_dir.join(format!("{}_spawns.{}", name, extension));
+
+    let inputs = collect_inputs(input_args.iter().copied(), |p| lowercase_extension(p) == "ifo")?;
+    if inputs.is_empty() {
+        bail!("No IFO files found");
+    }
+    if !prepare_output(&out, &options)? {
+        return Ok(());
+    }
+    if options.dry_run {
+        for input in &inputs {
+            dry_run_read(&input.path);
+        }
+        dry_run_write(&out);
+        return Ok(());
+    }
+
+    let mut spawns = Spawns::new();
+    for input in &inputs {
+        // Maps are named after their directory
+        let zone = input
+            .path
+            .parent()
+            .and_then(Path::file_name)
+            .unwrap_or_default()
+            .to_string_lossy();
+        let ifo = IFO::from_path(&input.path)
+            .map_err(|e| format_err!("{}: {}", input.path.display(), e))?;
+        spawns.add(&zone, &ifo);
+    }
+
+    create_output_dir(out_dir)?;
+    if matches.is_present("csv") {
+        fs::write(&out, spawns.to_csv(&options.csv)?)?;
+    } else {
+        write_json(&out, &spawns, &options)?;
+    }
+
+    info!(
+        "Collected {} spawns and {} NPCs from {} IFO files",
+        spawns.monsters.len(),
+        spawns.npcs.len(),
+        inputs.len()
+    );
+    for input_arg in input_args {
+        report.success(Path::new(input_arg), "map", vec![out.clone()]);
     }
     Ok(())
 }
 
-fn serialize(matches: &ArgMatches) -> Result<(), Error> {
-    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
-    let input = Path::new(matches.value_of("input").unwrap_or_default());
-    let input_type = matches.value_of("type").unwrap_or_default();
+/// Export the zones and the warp gates between them as `warps.json` or
+/// `warps.dot`
+fn map_warps(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let client_dir = Path::new(matches.value_of("client").unwrap());
 
-    if !input.exists() {
-        bail!("File does not exist: {}", input.display());
+    let res = client_warps(matches, client_dir);
+    match &res {
+        Ok(outputs) => report.success(client_dir, "map", outputs.clone()),
+        Err(e) => report.failure(client_dir, e),
     }
+    res.map(|_| ())
+}
 
-    let extension = input
-        .extension()
-        .unwrap_or_default()
-        .to_str()
-        .unwrap_or_default()
-        .to_lowercase();
+fn client_warps(matches: &ArgMatches, client_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"));
+    let dot = matches.is_present("dot");
+    let out = out_dir.join(if dot { "warps.dot" } else { "warps.json" });
 
-    let rose_type = if input_type.is_empty() {
-        if !SERIALIZE_VALUES.contains(&extension.as_str()) {
-            bail!("No type provided and unrecognized extension");
-        }
-        String::from(&extension)
-    } else {
-        String::from(input_type)
+    let stb_path = |name: &str| {
+        vfs::resolve_client_path(client_dir, &format!("3DDATA/STB/{}", name))
+            .ok_or_else(|| format_err!("{} not found in {}", name, client_dir.display()))
     };
+    let list_zone_path = stb_path("LIST_ZONE.STB")?;
+    let warp_path = stb_path("WARP.STB")?;
 
-    let data = match rose_type.as_str() {
-        // CSV
-        "stb" => STB::from_path(&input)?.to_csv()?,
-        "stl" => STL::from_path(&input)?.to_csv()?,
-        // JSON
-        "him" => HIM::from_path(&input)?.to_json()?,
-        "idx" => IDX::from_path(&input)?.to_json()?,
-        "ifo" => IFO::from_path(&input)?.to_json()?,
-        "lit" => LIT::from_path(&input)?.to_json()?,
-        "til" => TIL::from_path(&input)?.to_json()?,
-        "tsi" => TSI::from_path(&input)?.to_json()?,
-        "zmd" => ZMD::from_path(&input)?.to_json()?,
-        "zmo" => ZMO::from_path(&input)?.to_json()?,
-        "zms" => ZMS::from_path(&input)?.to_json()?,
-        "zon" => ZON::from_path(&input)?.to_json()?,
-        "zsc" => ZSC::from_path(&input)?.to_json()?,
-        "wstb" => {
-            let f = File::open(input)?;
-            let mut reader = RoseReader::new(f);
-            reader.set_wide_strings(true);
-            let mut stb: STB = RoseFile::new();
-            stb.read(&mut reader)?;
-            stb.to_csv()?
+    if !prepare_output(&out, &options)? {
+        return Ok(vec![out]);
+    }
+    if options.dry_run {
+        dry_run_read(&list_zone_path);
+        dry_run_read(&warp_path);
+        dry_run_write(&out);
+        return Ok(vec![out]);
+    }
+
+    let warp_list = STB::from_path(&warp_path)?;
+    let mut graph = WarpGraph::new(&STB::from_path(&list_zone_path)?);
+
+    let mut event_points = Vec::new();
+    let zones: Vec<(usize, String)> = graph.zones.iter().map(|z| (z.id, z.zon.clone())).collect();
+    for (zone_id, zon) in zones {
+        let zon_path = match vfs::resolve_client_path(client_dir, &zon) {
+            Some(path) => path,
+            None => {
+                warn!("ZON of zone {} not found: {}", zone_id, zon);
+                continue;
+            }
+        };
+        // Only needed for the arrival positions
+        match ZON::from_path(&zon_path) {
+            Ok(zon) => event_points.push((zone_id, zon.event_points)),
+            Err(e) => warn!("Failed to read {}: {}", zon_path.display(), e),
         }
-        _ => bail!("Unsupported file type: {}", rose_type.as_str()),
-    };
 
-    let new_extension = if rose_type == "stb" || rose_type == "stl" {
-        "csv"
+        let map_dir = zon_path.parent().unwrap_or_else(|| Path::new("."));
+        let mut ifo_paths = Vec::new();
+        for entry in fs::read_dir(map_dir)? {
+            let path = entry?.path();
+            if path.is_file() && lowercase_extension(&path) == "ifo" {
+                ifo_paths.push(path);
+            }
+        }
+        ifo_paths.sort();
+        for path in ifo_paths {
+            let ifo = IFO::from_path(&path)
+                .map_err(|e| format_err!("{}: {}", path.display(), e))?;
+            graph.add_warps(zone_id, &ifo, &warp_list);
+        }
+    }
+    for (zone_id, points) in &event_points {
+        graph.set_event_points(*zone_id, points);
+    }
+    for warp in &graph.warps {
+        if warp.to.is_none() {
+            warn!("Warp {} of zone {} has no destination zone", warp.warp_id, warp.from);
+        } else if warp.destination_position.is_none() {
+            warn!(
+                "Warp {} of zone {} arrives at unknown event point: {}",
+                warp.warp_id, warp.from, warp.destination
+            );
+        }
+    }
+
+    create_output_dir(out_dir)?;
+    if dot {
+        fs::write(&out, graph.to_dot())?;
     } else {
-        "json"
+        write_json(&out, &graph, &options)?;
+    }
+
+    info!(
+        "Exported {} zones connected by {} warp gates",
+        graph.zones.len(),
+        graph.warps.len()
+    );
+    Ok(vec![out])
+}
+
+/// Client paths of the decoration and construction ZSCs of the zone of a map
+/// directory, from the `LIST_ZONE.STB` of the client
+fn zone_scene_paths(client_dir: &Path, map_dir: &Path) -> Result<(String, String), Error> {
+    let map_name = map_dir.file_name().unwrap_or_default().to_string_lossy();
+    let list_zone = vfs::resolve_client_path(client_dir, "3DDATA/STB/LIST_ZONE.STB")
+        .ok_or_else(|| format_err!("LIST_ZONE.STB not found in {}", client_dir.display()))?;
+    let zon_path = map_dir
+        .canonicalize()?
+        .join(format!("{}.ZON", map_name));
+    let zon_path = zon_path
+        .strip_prefix(client_dir.canonicalize()?)
+        .map_err(|_| {
+            format_err!(
+                "Map {} is not in the client directory {}",
+                map_dir.display(),
+                client_dir.display()
+            )
+        })?
+        .to_string_lossy()
+        .into_owned();
+    ifo_instances::zone_scenes(&STB::from_path(&list_zone)?, &zon_path)
+        .ok_or_else(|| format_err!("No zone with the ZON file {} in LIST_ZONE.STB", zon_path))
+}
+
+fn convert_iconsheets(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let options = ConvertOptions::from_matches(matches);
+    let iconsheet_paths: Vec<PathBuf> = matches
+        .values_of("iconsheets")
+        .unwrap_or_default()
+        .map(|p| PathBuf::from(p))
+        .collect();
+
+    let image_format = ImageFormat::from_name(matches.value_of("image_format").unwrap_or("png"))?;
+    let color_key = color_key(matches)?;
+    let tsi = match matches.value_of("tsi") {
+        Some(tsi_path) => Some(TSI::from_path(Path::new(tsi_path))?),
+        None => None,
     };
 
-    // If the keep-extension flag is present we prepend the original extension
-    // e.g. list_zone.stb.json
-    let new_extension = if matches.is_present("keep-extension") {
-        extension + "." + new_extension
-    } else {
-        String::from(new_extension)
+    let mut grid = iconsheet::Grid::default();
+    if let Some(size) = matches.value_of("icon_size") {
+        let (width, height) = iconsheet::Grid::parse_size(size)?;
+        grid.icon_width = width;
+        grid.icon_height = height;
+    }
+    grid.cols = matches.value_of("cols").map(|s| s.parse()).transpose()?;
+    grid.rows = matches.value_of("rows").map(|s| s.parse()).transpose()?;
+    grid.padding = matches.value_of("padding").unwrap_or("0").parse()?;
+
+    let convert_iconsheet = |iconsheet_path: &Path| -> Result<Vec<PathBuf>, Error> {
+        if !iconsheet_path.exists() {
+            bail!("File does not exist: {}", iconsheet_path.display());
+        }
+
+        let sheet_name = iconsheet_path.file_stem().unwrap_or_default().to_string_lossy();
+        let icons = match &tsi {
+            Some(tsi) => match iconsheet::find_sheet(tsi, iconsheet_path) {
+                Some(sheet) => iconsheet::tsi_icons(&sheet_name, sheet),
+                None => bail!("No sprites for {} in the TSI", iconsheet_path.display()),
+            },
+            None => {
+                let (width, height) = read_image_dimensions(iconsheet_path)?;
+                grid.icons(&sheet_name, width, height)
+            }
+        };
+        let icon_paths: Vec<PathBuf> = icons
+            .iter()
+            .map(|icon| out_dir.join(format!("{}.{}", icon.name, image_format)))
+            .collect();
+
+        if options.dry_run {
+            dry_run_read(iconsheet_path);
+            for icon_path in &icon_paths {
+                if prepare_output(icon_path, &options)? {
+                    dry_run_write(icon_path);
+                }
+            }
+            return Ok(icon_paths);
+        }
+
+        let start = Instant::now();
+        let mut img = read_image(iconsheet_path)?;
+        if let Some(key) = color_key {
+            iconsheet::remove_color_key(&mut img, key);
+        }
+
+        for (icon, icon_path) in icons.iter().zip(&icon_paths) {
+            if prepare_output(icon_path, &options)? {
+                trace!("Saving icon to: {}", icon_path.display());
+                image_format.save(&iconsheet::crop(&img, icon)?, icon_path)?;
+            }
+        }
+
+        debug!(
+            "Converted iconsheet {} to {} icons in {:.2?}",
+            iconsheet_path.display(),
+            icons.len(),
+            start.elapsed()
+        );
+
+        Ok(icon_paths)
     };
 
-    let out = out_dir
-        .join(input.file_name().unwrap_or_default())
-        .with_extension(new_extension);
+    if !options.dry_run {
+        create_output_dir(out_dir)?;
+    }
 
-    if let Some(p) = out.parent() {
-        create_output_dir(p)?;
+    convert_batch(&iconsheet_paths, &options, report, |p| {
+        Ok((String::from("iconsheet"), convert_iconsheet(p)?))
+    })?;
+
+    info!("Done.");
+    Ok(())
+}
+
+fn pack_iconsheets(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let options = ConvertOptions::from_matches(matches);
+    let input_dir = PathBuf::from(matches.value_of("input").unwrap_or_default());
+    let tsi_path = matches.value_of("tsi").map(PathBuf::from);
+    let image_format = ImageFormat::from_name(matches.value_of("image_format").unwrap_or("png"))?;
+    let color_key = color_key(matches)?;
+
+    let pack_iconsheet = |input_dir: &Path| -> Result<Vec<PathBuf>, Error> {
+        if !input_dir.is_dir() {
+            bail!("Directory does not exist: {}", input_dir.display());
+        }
+
+        let name = match matches.value_of("name") {
+            Some(name) => name.to_string(),
+            None => input_dir
+                .canonicalize()?
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        };
+
+        let icon_paths = image_files(input_dir)?;
+        if icon_paths.is_empty() {
+            bail!("No icons in {}", input_dir.display());
+        }
+
+        let start = Instant::now();
+        let mut icons = Vec::new();
+        for icon_path in &icon_paths {
+            if options.dry_run {
+                dry_run_read(icon_path);
+                continue;
+            }
+            let icon_name = icon_path.file_stem().unwrap_or_default().to_string_lossy();
+            let icon = read_image(icon_path)?;
+            icons.push((icon_name.into_owned(), icon));
+        }
+
+        if options.dry_run {
+            let per_sheet = (iconsheet::SHEET_SIZE / iconsheet::ICON_SIZE).pow(2) as usize;
+            let sheet_count = icon_paths.len().div_ceil(per_sheet);
+            let mut outputs = iconsheet_paths(out_dir, &name, image_format, sheet_count);
+            if let Some(tsi_path) = &tsi_path {
+                outputs.push(out_dir.join(tsi_path.file_name().unwrap_or_default()));
+            }
+            for output in &outputs {
+                if prepare_output(output, &options)? {
+                    dry_run_write(output);
+                }
+            }
+            return Ok(outputs);
+        }
+
+        let mut sheets = iconsheet::pack(&icons)?;
+        if let Some(key) = color_key {
+            for sheet in &mut sheets {
+                iconsheet::apply_color_key(&mut sheet.image, key);
+            }
+        }
+        let sheet_paths = iconsheet_paths(out_dir, &name, image_format, sheets.len());
+        for (sheet, sheet_path) in sheets.iter().zip(&sheet_paths) {
+            if prepare_output(sheet_path, &options)? {
+                trace!("Saving iconsheet to: {}", sheet_path.display());
+                image_format.save(&sheet.image, sheet_path)?;
+            }
+        }
+        let mut outputs = sheet_paths.clone();
+
+        if let Some(tsi_path) = &tsi_path {
+            let mut tsi = if tsi_path.exists() {
+                TSI::from_path(tsi_path)?
+            } else {
+                TSI::new()
+            };
+            for (sheet, sheet_path) in sheets.iter().zip(&sheet_paths) {
+                iconsheet::update_tsi(&mut tsi, sheet_path, &sheet.icons);
+            }
+
+            let out_tsi = out_dir.join(tsi_path.file_name().unwrap_or_default());
+            if prepare_output(&out_tsi, &options)? {
+                trace!("Saving TSI to: {}", out_tsi.display());
+                tsi.write_to_path(&out_tsi)?;
+            }
+            outputs.push(out_tsi);
+        }
+
+        debug!(
+            "Packed {} icons from {} into {} iconsheets in {:.2?}",
+            icons.len(),
+            input_dir.display(),
+            sheets.len(),
+            start.elapsed()
+        );
+
+        Ok(outputs)
+    };
+
+    if !options.dry_run {
+        create_output_dir(out_dir)?;
     }
 
-    let mut f = File::create(&out)?;
-    f.write_all(data.as_bytes())?;
+    convert_batch(&[input_dir], &options, report, |p| {
+        Ok((String::from("iconsheet"), pack_iconsheet(p)?))
+    })?;
 
+    info!("Done.");
     Ok(())
 }
 
-fn deserialize(matches: &ArgMatches) -> Result<(), Error> {
-    let filetype = matches.value_of("type").unwrap_or_default();
-    let input = Path::new(matches.value_of("input").unwrap_or_default());
+/// Export the sheets of TSI files as TexturePacker JSON or CSS sprite maps
+fn tsi_atlas(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let format = AtlasFormat::from_name(matches.value_of("atlas_format").unwrap_or("texturepacker"))?;
+    let image_format = ImageFormat::from_name(matches.value_of("image_format").unwrap_or("png"))?;
 
-    if !input.exists() {
-        bail!("File does not exist: {}", input.display());
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "tsi"
+    })?;
+
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let tsi = TSI::from_path(input)?;
+        let sheets: Vec<AtlasSheet> = tsi
+            .sprite_sheets
+            .iter()
+            .map(|sheet| AtlasSheet::new(sheet, image_format.extension()))
+            .collect();
+
+        let dir = out_dir.join(&input_file.dir);
+        let files: Vec<(PathBuf, String)> = match format {
+            AtlasFormat::TexturePacker => sheets
+                .iter()
+                .map(|sheet| {
+                    let out = dir.join(format!("{}.{}", sheet.name, format.extension()));
+                    Ok((out, tsi_atlas::texturepacker_json(sheet, options.pretty)?))
+                })
+                .collect::<Result<_, Error>>()?,
+            AtlasFormat::Css => {
+                let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+                let out = dir.join(format!("{}.{}", stem, format.extension()));
+                vec![(out, tsi_atlas::css(&sheets))]
+            }
+        };
+
+        let mut outputs = Vec::new();
+        for (out, contents) in files {
+            outputs.push(out.clone());
+            if !prepare_output(&out, &options)? {
+                continue;
+            }
+            if options.dry_run {
+                dry_run_read(input);
+                dry_run_write(&out);
+                continue;
+            }
+            create_output_dir(&dir)?;
+            fs::write(&out, contents)?;
+        }
+        debug!("Exported {} sprites of {}", tsi.total_sprites(), input.display());
+
+        Ok((String::from("tsi"), outputs))
+    })
+}
+
+/// Pack the images of a directory into sheets and write a TSI describing them
+fn tsi_build(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let options = ConvertOptions::from_matches(matches);
+    let input_dir = PathBuf::from(matches.value_of("input").unwrap_or_default());
+    let image_format = ImageFormat::from_name(matches.value_of("image_format").unwrap_or("png"))?;
+    // Validated when parsing the args
+    let size: u32 = matches.value_of("size").unwrap_or("1024").parse()?;
+    let padding: u32 = matches.value_of("padding").unwrap_or("0").parse()?;
+    let sheet_dir = PathBuf::from(matches.value_of("sheet_dir").unwrap_or_default());
+
+    let build_tsi = |input_dir: &Path| -> Result<Vec<PathBuf>, Error> {
+        if !input_dir.is_dir() {
+            bail!("Directory does not exist: {}", input_dir.display());
+        }
+
+        let name = match matches.value_of("name") {
+            Some(name) => name.to_string(),
+            None => input_dir
+                .canonicalize()?
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        };
+
+        let image_paths = image_files(input_dir)?;
+        if image_paths.is_empty() {
+            bail!("No images in {}", input_dir.display());
+        }
+
+        let start = Instant::now();
+        let mut sprites = Vec::new();
+        for image_path in &image_paths {
+            if options.dry_run {
+                dry_run_read(image_path);
+            }
+            let sprite_name = image_path.file_stem().unwrap_or_default().to_string_lossy();
+            sprites.push((sprite_name.into_owned(), read_image(image_path)?));
+        }
+
+        let sheets = iconsheet::pack_sprites(&sprites, size, padding)?;
+        let sheet_paths = iconsheet_paths(out_dir, &name, image_format, sheets.len());
+
+        let mut tsi = TSI::new();
+        for (sheet, sheet_path) in sheets.iter().zip(&sheet_paths) {
+            // The client loads the sheets as DDS textures
+            let stem = sheet_path.file_stem().unwrap_or_default().to_string_lossy();
+            let client_path = sheet_dir.join(format!("{}.DDS", stem));
+            tsi.sprite_sheets.push(iconsheet::tsi_sheet(client_path, &sheet.icons));
+        }
+        let tsi_path = out_dir.join(format!("{}.TSI", name));
+
+        for (sheet, sheet_path) in sheets.iter().zip(&sheet_paths) {
+            if !prepare_output(sheet_path, &options)? {
+                continue;
+            }
+            if options.dry_run {
+                dry_run_write(sheet_path);
+            } else {
+                trace!("Saving sheet to: {}", sheet_path.display());
+                image_format.save(&sheet.image, sheet_path)?;
+            }
+        }
+        if prepare_output(&tsi_path, &options)? {
+            if options.dry_run {
+                dry_run_write(&tsi_path);
+            } else {
+                trace!("Saving TSI to: {}", tsi_path.display());
+                tsi.write_to_path(&tsi_path)?;
+            }
+        }
+
+        let mut outputs = sheet_paths;
+        outputs.push(tsi_path);
+
+        debug!(
+            "Packed {} sprites from {} into {} sheets in {:.2?}",
+            sprites.len(),
+            input_dir.display(),
+            sheets.len(),
+            start.elapsed()
+        );
+
+        Ok(outputs)
+    };
+
+    if !options.dry_run {
+        create_output_dir(out_dir)?;
     }
 
-    // Use the output arg if it's set, otherwise use the output directory option
-    let out = if let Some(s) = matches.value_of("output") {
-        PathBuf::from(s)
+    convert_batch(&[input_dir], &options, report, |p| {
+        Ok((String::from("tsi"), build_tsi(p)?))
+    })?;
+
+    info!("Done.");
+    Ok(())
+}
+
+/// Images of a directory that can be read by `read_image`, in natural order
+/// of their file names
+fn image_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file() && ["png", "tga", "bmp", "dds"].contains(&lowercase_extension(path).as_str())
+        })
+        .collect();
+    paths.sort_by(|a, b| {
+        iconsheet::natural_cmp(
+            &a.file_name().unwrap_or_default().to_string_lossy(),
+            &b.file_name().unwrap_or_default().to_string_lossy(),
+        )
+    });
+    Ok(paths)
+}
+
+/// Convert DDS and TGA textures to images, or images to DDS textures with --dxt1 or
+/// --dxt5
+fn convert_textures(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let image_format = ImageFormat::from_name(matches.value_of("image_format").unwrap_or("png"))?;
+    let dxt_format = if matches.is_present("dxt1") {
+        Some(DxtFormat::Dxt1)
+    } else if matches.is_present("dxt5") {
+        Some(DxtFormat::Dxt5)
     } else {
-        let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
-        out_dir
-            .join(input.file_name().unwrap_or_default())
-            .with_extension(filetype)
+        None
+    };
+    let mipmaps = !matches.is_present("no_mipmaps");
+    // Textures of different models share file names, keep the directories
+    let name_format = match &options.name_format {
+        Some(name_format) => name_format.clone(),
+        None => NameFormat::new("{dir}/{stem}.{ext}")?,
     };
 
-    if let Some(p) = out.parent() {
-        create_output_dir(p)?;
-    }
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        let extension = lowercase_extension(p);
+        match dxt_format {
+            Some(_) => ["png", "tga", "bmp"].contains(&extension.as_str()),
+            None => extension == "dds" || extension == "tga",
+        }
+    })?;
+
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let input_type = lowercase_extension(input);
+        let out_extension = match dxt_format {
+            Some(_) => "dds",
+            None => image_format.extension(),
+        };
+        let out = out_dir.join(name_format.format(
+            input,
+            &input_file.dir,
+            &input_type,
+            out_extension,
+        ));
+
+        if !prepare_output(&out, &options)? {
+            return Ok((input_type, vec![out]));
+        }
+        if options.dry_run {
+            dry_run_read(input);
+            dry_run_write(&out);
+            return Ok((input_type, vec![out]));
+        }
+
+        let start = Instant::now();
+        let img = read_image(input)?;
+        if let Some(p) = out.parent() {
+            create_output_dir(p)?;
+        }
+        match dxt_format {
+            Some(format) => fs::write(&out, dds::encode(&img, format, mipmaps))?,
+            None => image_format.save(&img, &out)?,
+        }
+        debug!(
+            "Converted {}x{} texture {} in {:.2?}",
+            img.width(),
+            img.height(),
+            input.display(),
+            start.elapsed()
+        );
+
+        Ok((input_type, vec![out]))
+    })
+}
 
-    let mut data = String::new();
+/// Write the lightmaps of the parts of LIT files as `<lit>/<object>_<part>.png`
+/// with their mapping as `<lit>.json`
+fn export_lightmaps(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "lit"
+    })?;
 
-    let mut file = File::open(&input)?;
-    file.read_to_string(&mut data)?;
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let lit_out_dir = out_dir.join(&input_file.dir);
+        let out = lit_out_dir.join(format!("{}.json", stem));
 
-    match filetype {
-        "stb" => STB::from_csv(&data)?.write_to_path(&out)?,
-        "stl" => STL::from_csv(&data)?.write_to_path(&out)?,
-        "idx" => IDX::from_json(&data)?.write_to_path(&out)?,
-        "lit" => IDX::from_json(&data)?.write_to_path(&out)?,
-        "zsc" => IDX::from_json(&data)?.write_to_path(&out)?,
-        _ => bail!("Unsupported file type: {}", filetype),
-    }
+        if !prepare_output(&out, &options)? {
+            return Ok((String::from("lit"), vec![out]));
+        }
+        let lit = LIT::from_path(input)?;
+        let mut entries = lightmap::lightmap_entries(&lit);
+        let lit_dir = input.parent().unwrap_or_else(|| Path::new(""));
+        if options.dry_run {
+            dry_run_read(input);
+            dry_run_write(&out);
+            return Ok((String::from("lit"), vec![out]));
+        }
 
-    Ok(())
+        let start = Instant::now();
+        create_output_dir(&lit_out_dir.join(&*stem))?;
+        let mut outputs = Vec::new();
+        let mut pages: HashMap<String, Option<RgbaImage>> = HashMap::new();
+        for entry in &mut entries {
+            let page = pages.entry(entry.page.clone()).or_insert_with(|| {
+                let img = vfs::resolve_client_path(lit_dir, &entry.page)
+                    .ok_or_else(|| format_err!("not found in {}", lit_dir.display()))
+                    .and_then(|path| read_image(&path));
+                match img {
+                    Ok(img) => Some(img),
+                    Err(e) => {
+                        warn!("Skipping lightmap page {}: {}", entry.page, e);
+                        None
+                    }
+                }
+            });
+            let page = match page {
+                Some(page) => page,
+                None => continue,
+            };
+            let name = format!("{}/{}_{}.png", stem, entry.object, entry.part);
+            lightmap::crop(entry, page).save(lit_out_dir.join(&name))?;
+            outputs.push(lit_out_dir.join(&name));
+            entry.image = Some(name);
+        }
+        write_json(&out, &entries, &options)?;
+        outputs.push(out);
+
+        debug!(
+            "Exported {} lightmaps of {} in {:.2?}",
+            outputs.len() - 1,
+            input.display(),
+            start.elapsed()
+        );
+        Ok((String::from("lit"), outputs))
+    })
 }
 
-/// Convert map files:
-/// - ZON: JSON
-/// - TIL: Combined into 1 JSON file
-/// - IFO: Combined into 1 JSON file
-/// - HIM: Combined into 1 greyscale png
-fn convert_map(matches: &ArgMatches) -> Result<(), Error> {
-    let map_dir = Path::new(matches.value_of("map_dir").unwrap());
-    if !map_dir.is_dir() {
-        bail!("Map path is not a directory: {:?}", map_dir);
-    }
+/// Copy ZSC files and the files they reference into a directory per ZSC,
+/// with a manifest of the bundled files
+fn zsc_bundle(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let vfs_path = matches.value_of("vfs").map(PathBuf::from);
 
-    println!("Loading map from: {}", map_dir.to_str().unwrap());
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "zsc"
+    })?;
 
-    // Collect coordinates from file names (using HIM as reference)
-    let mut x_coords: Vec<u32> = Vec::new();
-    let mut y_coords: Vec<u32> = Vec::new();
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let start = Instant::now();
+        let zsc = ZSC::from_path(input)?;
+        let dependencies = zsc_bundle::dependencies(&zsc);
 
-    for f in fs::read_dir(map_dir)? {
-        let f = f?;
-        let fpath = f.path();
-        if !fpath.is_file() {
-            continue;
+        // Contents of the dependencies found, by client path key
+        let mut found: HashMap<String, Vec<u8>> = HashMap::new();
+        if let Some(idx_path) = &vfs_path {
+            let keys: HashSet<String> = dependencies
+                .iter()
+                .map(|d| vfs::client_path_key(&d.path))
+                .collect();
+            let files = vfs::read_files(idx_path, |p| {
+                keys.contains(&vfs::client_path_key(&p.to_string_lossy()))
+            })?;
+            for (path, data) in files {
+                found.insert(vfs::client_path_key(&path.to_string_lossy()), data);
+            }
+        } else {
+            let client_dir = match matches.value_of("client") {
+                Some(dir) => PathBuf::from(dir),
+                None => vfs::find_client_dir(input).unwrap_or_else(|| PathBuf::from(".")),
+            };
+            for dependency in &dependencies {
+                if let Some(path) = vfs::resolve_client_path(&client_dir, &dependency.path) {
+                    found.insert(vfs::client_path_key(&dependency.path), fs::read(path)?);
+                }
+            }
         }
 
-        if fpath.extension().unwrap().to_str().unwrap().to_lowercase() == "him" {
-            let fname = fpath.file_stem().unwrap().to_str().unwrap();
-            let parts: Vec<&str> = fname.split('_').collect();
-            x_coords.push(parts[0].parse()?);
-            y_coords.push(parts[1].parse()?);
-        }
-    }
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let bundle_dir = out_dir.join(&input_file.dir).join(stem.as_ref());
+        let scene = input.file_name().unwrap_or_default().to_string_lossy().into_owned();
 
-    x_coords.sort();
-    y_coords.sort();
+        let mut files = vec![(bundle_dir.join(&scene), fs::read(input)?)];
+        let mut manifest = BundleManifest {
+            scene,
+            ..Default::default()
+        };
+        for dependency in dependencies {
+            let bundle_file = |checksum| BundleFile {
+                path: dependency.path.clone(),
+                file_type: dependency.file_type,
+                checksum,
+            };
+            match found.remove(&vfs::client_path_key(&dependency.path)) {
+                Some(data) => {
+                    manifest.files.push(bundle_file(Some(cache::data_hash(&data))));
+                    files.push((bundle_dir.join(&dependency.path), data));
+                }
+                None => {
+                    warn!("{} references a missing file: {}", input.display(), dependency.path);
+                    manifest.missing.push(bundle_file(None));
+                }
+            }
+        }
+        let manifest_json = if options.pretty {
+            serde_json::to_string_pretty(&manifest)?
+        } else {
+            serde_json::to_string(&manifest)?
+        };
+        files.push((bundle_dir.join(BUNDLE_MANIFEST_FILE), manifest_json.into_bytes()));
 
-    let x_min = *x_coords.iter().min().unwrap();
-    let x_max = *x_coords.iter().max().unwrap();
-    let y_min = *y_coords.iter().min().unwrap();
-    let y_max = *y_coords.iter().max().unwrap();
+        let mut outputs = Vec::new();
+        for (out, data) in files {
+            outputs.push(out.clone());
+            if !prepare_output(&out, &options)? {
+                continue;
+            }
+            if options.dry_run {
+                dry_run_write(&out);
+                continue;
+            }
+            if let Some(p) = out.parent() {
+                create_output_dir(p)?;
+            }
+            fs::write(&out, data)?;
+        }
 
-    let map_width = (x_max - x_min + 1) * 65;
-    let map_height = (y_max - y_min + 1) * 65;
+        debug!(
+            "Bundled {} with {} files ({} missing) in {:.2?}",
+            input.display(),
+            manifest.files.len(),
+            manifest.missing.len(),
+            start.elapsed()
+        );
 
-    let mut max_height = f32::NAN;
-    let mut min_height = f32::NAN;
+        Ok((String::from("zsc"), outputs))
+    })
+}
 
-    // Ensure map dimensions are divisible by 4 for tiling
-    let new_map_width = (map_width as f32 / 4.0).ceil() * 4.0;
-    let new_map_height = (map_height as f32 / 4.0).ceil() * 4.0;
+/// Export the objects of ZSC files as glTF models, in a directory per ZSC
+/// with their textures
+fn zsc_gltf(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    // Validated when parsing the args
+    let object_ids: Vec<usize> = matches
+        .values_of("object")
+        .unwrap_or_default()
+        .map(|id| id.parse())
+        .collect::<Result<_, _>>()?;
 
-    let new_map_width = new_map_width as u32 + 1;
-    let new_map_height = new_map_height as u32 + 1;
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "zsc"
+    })?;
 
-    let mut heights: Vec<Vec<f32>> = Vec::new();
-    heights.resize(
-        new_map_height as usize,
-        iter::repeat(0.0).take(new_map_width as usize).collect(),
-    );
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let start = Instant::now();
+        let zsc = ZSC::from_path(input)?;
+        let client_dir = match matches.value_of("client") {
+            Some(dir) => PathBuf::from(dir),
+            None => vfs::find_client_dir(input).unwrap_or_else(|| PathBuf::from(".")),
+        };
 
-    // Number of tiles in x and y direction
-    let tiles_x = new_map_width / 4;
-    let tiles_y = new_map_height / 4;
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let model_dir = out_dir.join(&input_file.dir).join(stem.as_ref());
 
-    let mut tiles: Vec<Vec<i32>> = Vec::new();
-    tiles.resize(
-        tiles_y as usize,
-        iter::repeat(0).take(tiles_x as usize).collect(),
-    );
+        // Empty objects are unused slots of the object ids
+        let ids: Vec<usize> = if object_ids.is_empty() {
+            (0..zsc.objects.len())
+                .filter(|&id| !zsc.objects[id].parts.is_empty())
+                .collect()
+        } else {
+            object_ids.clone()
+        };
 
-    for y in y_min..=y_max {
-        for x in x_min..=x_max {
-            //-- Load HIMs
-            let him_name = format!("{}_{}.HIM", x, y);
-            let him_path = map_dir.join(&him_name);
+        let mut outputs = Vec::new();
+        if options.dry_run {
+            dry_run_read(input);
+            for id in &ids {
+                let name = format!("{}_{}", stem, id);
+                for extension in &["gltf", "bin"] {
+                    let out = model_dir.join(format!("{}.{}", name, extension));
+                    dry_run_write(&out);
+                    outputs.push(out);
+                }
+            }
+            return Ok((String::from("zsc"), outputs));
+        }
 
-            let him = HIM::from_path(&him_path).unwrap();
-            if him.length != 65 || him.width != 65 {
-                bail!(
-                    "Unexpected HIM dimensions. Expected 65x65: {} ({}x{})",
-                    &him_path.to_str().unwrap_or(&him_name),
-                    him.width,
-                    him.length
-                );
+        let mut textures = gltf::Textures::new(&client_dir, &model_dir);
+        for id in ids {
+            let name = format!("{}_{}", stem, id);
+            let gltf_path = model_dir.join(format!("{}.gltf", name));
+            let bin_path = model_dir.join(format!("{}.bin", name));
+            if !prepare_output(&gltf_path, &options)? {
+                continue;
             }
 
-            for h in 0..him.length {
-                for w in 0..him.width {
-                    let height = him.height(h as usize, w as usize);
+            let mut doc = gltf::zsc_object(&zsc, id, &name, &client_dir, &mut textures)?;
+            doc.convert_axes(&name, &options.axes.unwrap_or_default());
+            let (json, buffer) = doc.to_files(&name, options.pretty)?;
+            create_output_dir(&model_dir)?;
+            fs::write(&gltf_path, json)?;
+            fs::write(&bin_path, buffer)?;
+            outputs.push(gltf_path);
+            outputs.push(bin_path);
+        }
+        outputs.extend(textures.paths());
 
-                    if (height > max_height) || (max_height.is_nan()) {
-                        max_height = height;
-                    }
-                    if (height < min_height) || (min_height.is_nan()) {
-                        min_height = height;
-                    }
+        debug!(
+            "Exported {} objects of {} in {:.2?}",
+            outputs.iter().filter(|p| lowercase_extension(p) == "gltf").count(),
+            input.display(),
+            start.elapsed()
+        );
 
-                    let new_x = ((x - x_min) * 65) + w as u32;
-                    let new_y = ((y - y_min) * 65) + h as u32;
+        Ok((String::from("zsc"), outputs))
+    })
+}
 
-                    heights[new_y as usize][new_x as usize] = height;
-                }
-            }
+/// Write the resolved materials of the parts of ZSC objects as JSON,
+/// `<name>.materials.json` for each ZSC
+fn zsc_materials(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
 
-            // -- Load TILs
-            let til_name = format!("{}_{}.TIL", x, y);
-            let til_path = map_dir.join(&til_name);
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "zsc"
+    })?;
 
-            let til = TIL::from_path(&til_path).unwrap();
-            if til.height != 16 || til.width != 16 {
-                bail!(
-                    "Unexpected TIL dimensions. Expected 16x16: {} ({}x{})",
-                    &til_path.to_str().unwrap_or(&til_name),
-                    til.width,
-                    til.height
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let out = out_dir
+            .join(&input_file.dir)
+            .join(format!("{}.materials.json", stem));
+        if !prepare_output(&out, &options)? {
+            return Ok((String::from("zsc"), vec![out]));
+        }
+        if options.dry_run {
+            dry_run_read(input);
+            dry_run_write(&out);
+            return Ok((String::from("zsc"), vec![out]));
+        }
+
+        let zsc = ZSC::from_path(input)?;
+        let client_dir = match matches.value_of("client") {
+            Some(dir) => PathBuf::from(dir),
+            None => vfs::find_client_dir(input).unwrap_or_else(|| PathBuf::from(".")),
+        };
+        let materials = MaterialReport::new(&zsc, &client_dir);
+        for object in &materials.objects {
+            for part in object.parts.iter().filter(|p| p.material.is_none()) {
+                warn!(
+                    "{}: part {} of object {} references missing material {}",
+                    input.display(),
+                    part.part,
+                    object.object,
+                    part.material_id
                 );
             }
+        }
 
-            for h in 0..til.height {
-                for w in 0..til.width {
-                    let tile_id = til.tiles[h as usize][w as usize].tile_id;
+        if let Some(p) = out.parent() {
+            create_output_dir(p)?;
+        }
+        write_json(&out, &materials, &options)?;
+        Ok((String::from("zsc"), vec![out]))
+    })
+}
 
-                    let new_x = ((x - x_min) * 16) + w as u32;
-                    let new_y = ((y - y_min) * 16) + h as u32;
+/// Write the skeletons of ZMD files as `<name>.gltf`, or `<name>.obj` with
+/// `--obj`
+fn zmd_viz(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let extension = if matches.is_present("obj") { "obj" } else { "gltf" };
 
-                    tiles[new_y as usize][new_x as usize] = tile_id;
-                }
-            }
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "zmd"
+    })?;
 
-            // TODO:
-            // Load IFO data
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let zmd_out_dir = out_dir.join(&input_file.dir);
+        let out = zmd_out_dir.join(format!("{}.{}", stem, extension));
+        if !prepare_output(&out, &options)? {
+            return Ok((String::from("zmd"), vec![out]));
+        }
+        if options.dry_run {
+            dry_run_read(input);
+            dry_run_write(&out);
+            return Ok((String::from("zmd"), vec![out]));
         }
-    }
 
-    let map_name = map_dir.file_name().unwrap().to_str().unwrap();
-    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or("out"));
-    create_output_dir(out_dir)?;
+        let zmd = ZMD::from_path(input)?;
+        create_output_dir(&zmd_out_dir)?;
+        let mut outputs = Vec::new();
+        if matches.is_present("obj") {
+            let joints = zmd_viz::joints(&zmd);
+            fs::write(&out, obj_axes(&zmd_viz::to_obj(&stem, &joints), &options))?;
+            outputs.push(out);
+        } else {
+            let doc = gltf::skeleton(&stem, &zmd);
+            write_gltf(doc, &zmd_out_dir, &stem, &options, &mut outputs)?;
+        }
+        debug!(
+            "Exported {} bones and {} dummy bones of {}",
+            zmd.bones.len(),
+            zmd.dummy_bones.len(),
+            input.display()
+        );
+        Ok((String::from("zmd"), outputs))
+    })
+}
 
-    // -- Heightmap image
-    let delta_height = max_height - min_height;
+/// Write ZMO files resampled to `--fps` to the output directory, with their
+/// file names
+fn zmo_resample(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let fps: u32 = matches.value_of("fps").unwrap_or_default().parse()?;
 
-    let mut height_image: GrayImage = ImageBuffer::new(new_map_width, new_map_height);
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "zmo"
+    })?;
 
-    for y in 0..new_map_height {
-        for x in 0..new_map_width {
-            let height = heights[y as usize][x as usize];
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let out = out_dir
+            .join(&input_file.dir)
+            .join(input.file_name().unwrap_or_default());
+        if !prepare_output(&out, &options)? {
+            return Ok((String::from("zmo"), vec![out]));
+        }
+        if options.dry_run {
+            dry_run_read(input);
+            dry_run_write(&out);
+            return Ok((String::from("zmo"), vec![out]));
+        }
 
-            let norm_height = |h| (255.0 * ((h - min_height) / delta_height)) as u8;
+        let zmo = ZMO::from_path(input)?;
+        let mut resampled = zmo_resample::resample(&zmo, fps);
+        debug!(
+            "Resampled {} from {} frames at {} fps to {} frames at {} fps",
+            input.display(),
+            zmo.frames,
+            zmo.fps,
+            resampled.frames,
+            resampled.fps
+        );
 
-            let pixel = image::Luma([norm_height(height)]);
-            height_image.put_pixel(x, y, pixel);
+        if let Some(p) = out.parent() {
+            create_output_dir(p)?;
         }
-    }
-
-    // Save heightmap image
-    let mut height_file = PathBuf::from(out_dir);
-    height_file.push(map_name);
-    height_file.set_extension("png");
+        resampled.write_to_path(&out)?;
+        Ok((String::from("zmo"), vec![out]))
+    })
+}
 
-    println!("Saving heightmap to: {}", &height_file.to_str().unwrap());
-    height_image.save(height_file)?;
+/// Write the root motion of ZMO files as `<name>_root_motion.json` or `.csv`,
+/// and with `--in-place` the ZMO without it
+fn zmo_root_motion(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let bone: u32 = matches.value_of("bone").unwrap_or("0").parse()?;
+    let extension = if matches.is_present("csv") { "csv" } else { "json" };
+    let in_place = matches.is_present("in_place");
 
-    // Dump ZON as JSON
-    let zon = ZON::from_path(&map_dir.join(format!("{}.ZON", map_name)))?;
-    let mut zon_file = PathBuf::from(out_dir);
-    zon_file.push(map_name.to_string());
-    zon_file.set_extension("json");
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "zmo"
+    })?;
 
-    println!("Dumping ZON file to: {}", &zon_file.to_str().unwrap());
-    let f = File::create(zon_file)?;
-    serde_json::to_writer_pretty(f, &zon)?;
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let zmo_out_dir = out_dir.join(&input_file.dir);
+        let out = zmo_out_dir.join(format!("{}_root_motion.{}", stem, extension));
+        let mut outputs = vec![out.clone()];
+        if in_place {
+            outputs.push(zmo_out_dir.join(input.file_name().unwrap_or_default()));
+        }
+        if !prepare_output(&out, &options)? {
+            return Ok((String::from("zmo"), outputs));
+        }
+        if options.dry_run {
+            dry_run_read(input);
+            for path in &outputs {
+                dry_run_write(path);
+            }
+            return Ok((String::from("zmo"), outputs));
+        }
 
-    // Create tilemap file
-    let mut tilemap_tiles: Vec<TilemapTile> = Vec::new();
-    for zon_tile in zon.tiles {
-        tilemap_tiles.push(TilemapTile {
-            layer1: zon_tile.layer1 + zon_tile.offset1,
-            layer2: zon_tile.layer2 + zon_tile.offset2,
-            rotation: zon_tile.rotation,
-        });
-    }
+        let mut zmo = ZMO::from_path(input)?;
+        let mut motion = zmo_root_motion::RootMotion::from_zmo(&zmo, bone)
+            .ok_or_else(|| format_err!("No position channel for bone {}", bone))?;
+        if let Some(axes) = &options.axes {
+            motion.convert_axes(axes);
+        }
 
-    let tilemap = TilemapFile {
-        textures: zon.textures,
-        tiles: tilemap_tiles,
-        tilemap: tiles,
-    };
+        create_output_dir(&zmo_out_dir)?;
+        if matches.is_present("csv") {
+            fs::write(&out, motion.to_csv(&options.csv)?)?;
+        } else {
+            write_json(&out, &motion, &options)?;
+        }
+        if in_place {
+            zmo_root_motion::make_in_place(&mut zmo, bone);
+            zmo.write_to_path(&outputs[1])?;
+        }
+        Ok((String::from("zmo"), outputs))
+    })
+}
 
-    let mut tile_file = PathBuf::from(out_dir);
-    tile_file.push(format!("{}_tilemap", map_name));
-    tile_file.set_extension("json");
+/// List the frame events of a ZMO file, or move and set them in place with
+/// `--move` and `--set`
+fn zmo_frame_events(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let path = Path::new(matches.value_of("file").unwrap_or_default());
+    let mut zmo = ZMO::from_path(path)?;
 
-    println!("Saving tilemap file to: {}", &tile_file.to_str().unwrap());
-    let f = File::create(tile_file)?;
-    serde_json::to_writer_pretty(f, &tilemap)?;
+    let moves = matches.values_of("move").unwrap_or_default();
+    let sets = matches.values_of("set").unwrap_or_default();
+    if moves.len() + sets.len() == 0 {
+        let events = zmo_events::frame_events(&zmo);
+        if matches.is_present("json") {
+            let json = if options.pretty {
+                serde_json::to_string_pretty(&events)?
+            } else {
+                serde_json::to_string(&events)?
+            };
+            println!("{}", json);
+        } else {
+            for event in &events {
+                println!("{}", event);
+            }
+        }
+        info!("{} frame events in {} frames", events.len(), zmo.frames);
+        return Ok(());
+    }
 
-    // EXPORT IFO data as JSON
+    let mut edits = 0;
+    for edit in moves {
+        let (from, to) = zmo_events::parse_edit(edit)?;
+        zmo_events::move_event(&mut zmo, from, to)?;
+        edits += 1;
+    }
+    for edit in sets {
+        let (frame, event) = zmo_events::parse_edit(edit)?;
+        let event = u16::try_from(event).map_err(|_| format_err!("Invalid event: {}", event))?;
+        zmo_events::set_event(&mut zmo, frame, event)?;
+        edits += 1;
+    }
 
+    if options.backup {
+        backup_file(path, &options)?;
+    }
+    if options.dry_run {
+        dry_run_write(path);
+    } else {
+        zmo.write_to_path(path)?;
+    }
+    report.success(path, "zmo", vec![path.to_path_buf()]);
+    info!("Edited {} frame events of {}", edits, path.display());
     Ok(())
 }
 
-fn convert_iconsheets(matches: &ArgMatches) -> Result<(), Error> {
+/// Merge ZMS files into one mesh written to `--output`
+fn zms_merge(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
     let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
-    let iconsheet_paths: Vec<PathBuf> = matches
-        .values_of("iconsheets")
+    let inputs: Vec<&Path> = matches
+        .values_of("input")
         .unwrap_or_default()
-        .map(|p| PathBuf::from(p))
+        .map(Path::new)
         .collect();
+    let first = inputs[0];
+    let out = match matches.value_of("output") {
+        Some(output) => PathBuf::from(output),
+        None => {
+            let stem = first.file_stem().unwrap_or_default().to_string_lossy();
+            out_dir.join(format!("{}_merged.zms", stem))
+        }
+    };
 
-    let convert_iconsheet = |iconsheet_path: &Path| -> Result<(), Error> {
-        if !iconsheet_path.exists() {
-            bail!("File does not exist: {}", iconsheet_path.display());
+    let res = merge_meshes(&inputs, &out, matches.is_present("material_groups"), &options);
+    match &res {
+        Ok(()) => report.success(first, "zms", vec![out]),
+        Err(e) => report.failure(first, e),
+    }
+    res
+}
+
+fn merge_meshes(
+    inputs: &[&Path],
+    out: &Path,
+    material_groups: bool,
+    options: &ConvertOptions,
+) -> Result<(), Error> {
+    if !prepare_output(out, options)? {
+        return Ok(());
+    }
+    if options.dry_run {
+        for input in inputs {
+            dry_run_read(input);
         }
+        dry_run_write(out);
+        return Ok(());
+    }
+
+    let meshes = inputs
+        .iter()
+        .map(|input| ZMS::from_path(input))
+        .collect::<Result<Vec<ZMS>, Error>>()?;
+    let mut merged = zms_merge::merge(&meshes, material_groups)?;
+    if let Some(p) = out.parent() {
+        create_output_dir(p)?;
+    }
+    merged.write_to_path(out)?;
+    info!(
+        "Merged {} meshes into {} with {} vertices and {} faces",
+        meshes.len(),
+        out.display(),
+        merged.vertices.len(),
+        merged.indices.len()
+    );
+    Ok(())
+}
 
-        let img = ImageReader::open(iconsheet_path)?.decode()?.into_rgba8();
+/// Write ZMS files simplified to `--ratio` of their faces as
+/// `<name>_lod.zms`, or OBJ with `--obj`
+fn zms_simplify(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let ratio: f32 = matches.value_of("ratio").unwrap_or_default().parse()?;
+    let extension = if matches.is_present("obj") { "obj" } else { "zms" };
 
-        // ROSE Icons are 40 pixels x 40 pixels
-        let icon_x_count = (img.width() as f32 / 40.0).floor() as u32;
-        let icon_y_count = (img.height() as f32 / 40.0).floor() as u32;
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "zms"
+    })?;
 
-        let mut icon_number = 0;
-        for icon_y in 0..icon_y_count {
-            for icon_x in 0..icon_x_count {
-                let mut icon = RgbaImage::new(40, 40);
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let zms_out_dir = out_dir.join(&input_file.dir);
+        let name = format!("{}_lod", stem);
+        let out = zms_out_dir.join(format!("{}.{}", name, extension));
+        let uv2_out = zms_out_dir.join(format!("{}_uv2.obj", name));
+        if !prepare_output(&out, &options)? {
+            return Ok((String::from("zms"), vec![out]));
+        }
+        if options.dry_run {
+            dry_run_read(input);
+            dry_run_write(&out);
+            if matches.is_present("uv2") {
+                dry_run_write(&uv2_out);
+            }
+            return Ok((String::from("zms"), vec![out]));
+        }
 
-                for pixel_y in 0..40 {
-                    for pixel_x in 0..40 {
-                        let x = (icon_x * 40) + pixel_x;
-                        let y = (icon_y * 40) + pixel_y;
-                        let pixel = img.get_pixel(x, y);
-                        icon.put_pixel(pixel_x, pixel_y, *pixel);
+        let mut zms = ZMS::from_path(input)?;
+        let faces = zms.indices.len();
+        zms_simplify::simplify(&mut zms, ratio);
+        bounds::recompute_mesh(&mut zms);
+        create_output_dir(&zms_out_dir)?;
+        let mut outputs = vec![out.clone()];
+        if matches.is_present("obj") {
+            fs::write(&out, obj_axes(&zms_obj::to_obj(&name, &zms), &options))?;
+            if matches.is_present("uv2") {
+                match zms_obj::to_obj_with_uv(&name, &zms, 2) {
+                    Some(obj) => {
+                        fs::write(&uv2_out, obj_axes(&obj, &options))?;
+                        outputs.push(uv2_out);
                     }
+                    None => warn!("{} has no second UV channel", input.display()),
                 }
+            }
+        } else {
+            zms.write_to_path(&out)?;
+        }
+        debug!(
+            "Simplified {} from {} to {} faces",
+            input.display(),
+            faces,
+            zms.indices.len()
+        );
+        Ok((String::from("zms"), outputs))
+    })
+}
 
-                let icon_name = iconsheet_path.file_stem().unwrap();
-                let icon_path = out_dir
-                    .join(format!("{}_{}", icon_name.to_str().unwrap(), icon_number))
-                    .with_extension("png");
-                dbg!(&icon_path);
-                icon.save(&icon_path)?;
+/// Write ZMS files with their faces and vertices reordered for the vertex
+/// cache to the output directory, with their file names
+fn zms_optimize(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
 
-                icon_number += 1;
-            }
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "zms"
+    })?;
+
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let zms_out_dir = out_dir.join(&input_file.dir);
+        let out = zms_out_dir.join(input.file_name().unwrap_or_default());
+        if !prepare_output(&out, &options)? {
+            return Ok((String::from("zms"), vec![out]));
+        }
+        if options.dry_run {
+            dry_run_read(input);
+            dry_run_write(&out);
+            return Ok((String::from("zms"), vec![out]));
         }
 
-        Ok(())
-    };
+        let mut zms = ZMS::from_path(input)?;
+        let before = zms_optimize::acmr(&zms, zms_optimize::CACHE_SIZE);
+        zms_optimize::optimize(&mut zms);
+        create_output_dir(&zms_out_dir)?;
+        zms.write_to_path(&out)?;
+        debug!(
+            "Optimized {}, {:.3} to {:.3} vertices transformed per face",
+            input.display(),
+            before,
+            zms_optimize::acmr(&zms, zms_optimize::CACHE_SIZE)
+        );
+        Ok((String::from("zms"), vec![out]))
+    })
+}
 
-    create_output_dir(out_dir)?;
+/// Export ZMS files as glTF, `<name>.gltf` and `<name>.bin` with the textures
+/// of their material in `textures`
+fn zms_gltf(matches: &ArgMatches, report: &mut Report) -> Result<(), Error> {
+    let options = ConvertOptions::from_matches(matches);
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap_or_default());
+    let texture = matches.value_of("texture").map(|path| zsc::SceneMaterial {
+        path: PathBuf::from(path),
+        alpha: 1.0,
+        z_write_enabled: true,
+        z_test_enabled: true,
+        ..Default::default()
+    });
+
+    let inputs = collect_inputs(matches.values_of("input").unwrap_or_default(), |p| {
+        lowercase_extension(p) == "zms"
+    })?;
+
+    // ZSCs of each client, read once for the materials of the meshes
+    let mut client_zscs: HashMap<PathBuf, Vec<ZSC>> = HashMap::new();
+    let mut textures: HashMap<PathBuf, gltf::Textures> = HashMap::new();
 
-    let mut all_succeeded = true;
-    for iconsheet_path in iconsheet_paths {
-        if let Err(e) = convert_iconsheet(&iconsheet_path) {
-            all_succeeded = false;
-            eprintln!("{}", e);
+    convert_batch(&inputs, &options, report, |input_file| {
+        let input = input_file.path.as_path();
+        let client_dir = match matches.value_of("client") {
+            Some(dir) => PathBuf::from(dir),
+            None => vfs::find_client_dir(input).unwrap_or_else(|| PathBuf::from(".")),
+        };
+
+        let name = input.file_stem().unwrap_or_default().to_string_lossy();
+        let model_dir = out_dir.join(&input_file.dir);
+        let gltf_path = model_dir.join(format!("{}.gltf", name));
+        let bin_path = model_dir.join(format!("{}.bin", name));
+        let mut outputs = vec![gltf_path.clone(), bin_path.clone()];
+        if !prepare_output(&gltf_path, &options)? {
+            return Ok((String::from("zms"), outputs));
+        }
+        if options.dry_run {
+            dry_run_read(input);
+            dry_run_write(&gltf_path);
+            dry_run_write(&bin_path);
+            return Ok((String::from("zms"), outputs));
         }
-    }
 
-    if !all_succeeded {
-        bail!("Failed to convert all tilesheets");
-    }
+        let zms = ZMS::from_path(input)?;
+        let material = match &texture {
+            Some(material) => Some(material),
+            None => {
+                let zscs = client_zscs.entry(client_dir.clone()).or_insert_with(|| {
+                    let client = Client::open(&client_dir).unwrap_or_default();
+                    client
+                        .files_with_extension("zsc")
+                        .filter_map(|file| client.read_file::<ZSC>(&file.path).ok())
+                        .collect()
+                });
+                let mesh = input.strip_prefix(&client_dir).unwrap_or(input);
+                gltf::mesh_material(zscs, &mesh.to_string_lossy())
+            }
+        };
 
-    eprintln!("Done.");
-    Ok(())
+        let textures = textures
+            .entry(model_dir.clone())
+            .or_insert_with(|| gltf::Textures::new(&client_dir, &model_dir));
+        let mut doc = gltf::zms_model(&name, &zms, material, textures)?;
+        doc.convert_axes(&name, &options.axes.unwrap_or_default());
+        let (json, buffer) = doc.to_files(&name, options.pretty)?;
+        create_output_dir(&model_dir)?;
+        fs::write(&gltf_path, json)?;
+        fs::write(&bin_path, buffer)?;
+        outputs.extend(textures.paths());
+        debug!("Exported {} as glTF", input.display());
+
+        Ok((String::from("zms"), outputs))
+    })
+}
+
+/// Paths of packed iconsheets, numbered from 1 if there is more than one
+fn iconsheet_paths(
+    out_dir: &Path,
+    name: &str,
+    image_format: ImageFormat,
+    count: usize,
+) -> Vec<PathBuf> {
+    if count == 1 {
+        return vec![out_dir.join(format!("{}.{}", name, image_format))];
+    }
+    (1..=count)
+        .map(|n| out_dir.join(format!("{}_{}.{}", name, n, image_format)))
+        .collect()
 }
 
 /*
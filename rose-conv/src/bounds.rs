@@ -0,0 +1,201 @@
+//! Bounding volumes of meshes and objects
+//!
+//! The client culls ZMS meshes and the objects of ZSC files with the bounds
+//! stored in them, which edited meshes keep as they were. Bounds are
+//! recomputed from the vertices instead: the box of a mesh around its
+//! vertices, and the box and cylinder of an object around the meshes of its
+//! parts moved into the object, in centimeters like part positions.
+use roselib::files::zsc::SceneObject;
+use roselib::files::{ZMS, ZSC};
+use roselib::utils::{BoundingBox, BoundingCylinder, Vector2, Vector3};
+
+use crate::collision;
+
+/// Centimeters per meter, meshes are in meters
+const OBJECT_SCALE: f32 = 100.0;
+
+/// Box around the vertices of a mesh, `None` without vertices
+pub fn mesh_bounds(zms: &ZMS) -> Option<BoundingBox<f32>> {
+    bounding_box(zms.vertices.iter().map(|v| v.position))
+}
+
+/// Recompute the bounding box of a mesh, returning whether it changed
+pub fn recompute_mesh(zms: &mut ZMS) -> bool {
+    match mesh_bounds(zms) {
+        Some(bounds) if bounds != zms.bounding_box => {
+            zms.bounding_box = bounds;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Box and cylinder around the meshes of the parts of an object, with the
+/// meshes of the ZSC by index from `mesh`. `None` if a mesh is missing or
+/// the meshes have no vertices.
+pub fn object_bounds<'a, F>(
+    object: &SceneObject,
+    mesh: F,
+) -> Option<(BoundingBox<f32>, BoundingCylinder)>
+where
+    F: Fn(u16) -> Option<&'a ZMS>,
+{
+    let mut points = Vec::new();
+    for (idx, part) in object.parts.iter().enumerate() {
+        let zms = mesh(part.mesh_id)?;
+        let transform = collision::part_matrix(object, idx);
+        points.extend(zms.vertices.iter().map(|v| {
+            let p = collision::apply(&transform, &v.position);
+            Vector3 {
+                x: p.x * OBJECT_SCALE,
+                y: p.y * OBJECT_SCALE,
+                z: p.z * OBJECT_SCALE,
+            }
+        }));
+    }
+    let bounds = bounding_box(points.iter().copied())?;
+
+    // Upright cylinder around the center of the box, with a whole radius as
+    // ZSC files store it
+    let center = Vector2 {
+        x: ((bounds.min.x + bounds.max.x) / 2.0).round(),
+        y: ((bounds.min.y + bounds.max.y) / 2.0).round(),
+    };
+    let radius = points
+        .iter()
+        .map(|p| (p.x - center.x).hypot(p.y - center.y))
+        .fold(0.0, f32::max)
+        .ceil();
+    let cylinder = BoundingCylinder {
+        center: Vector2 {
+            x: center.x as i32,
+            y: center.y as i32,
+        },
+        radius,
+    };
+    Some((bounds, cylinder))
+}
+
+/// Recompute the bounds of the objects of a ZSC, with its meshes by index
+/// from `mesh`. Objects with missing meshes keep their bounds. Returns the
+/// number of objects whose bounds changed.
+pub fn recompute_scene<'a, F>(zsc: &mut ZSC, mesh: F) -> usize
+where
+    F: Fn(u16) -> Option<&'a ZMS>,
+{
+    let mut changed = 0;
+    for object in zsc.objects.iter_mut() {
+        if let Some((bounds, cylinder)) = object_bounds(object, &mesh) {
+            if bounds != object.bounding_box || cylinder != object.bounding_cylinder {
+                object.bounding_box = bounds;
+                object.bounding_cylinder = cylinder;
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
+
+fn bounding_box<I>(mut points: I) -> Option<BoundingBox<f32>>
+where
+    I: Iterator<Item = Vector3<f32>>,
+{
+    let first = points.next()?;
+    let (mut min, mut max) = (first, first);
+    for p in points {
+        min = Vector3 {
+            x: min.x.min(p.x),
+            y: min.y.min(p.y),
+            z: min.z.min(p.z),
+        };
+        max = Vector3 {
+            x: max.x.max(p.x),
+            y: max.y.max(p.y),
+            z: max.z.max(p.z),
+        };
+    }
+    Some(BoundingBox { min, max })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zms::Vertex;
+    use roselib::files::zsc::SceneObjectPart;
+    use roselib::io::RoseFile;
+    use roselib::utils::Quaternion;
+
+    fn mesh(points: &[[f32; 3]]) -> ZMS {
+        let mut zms = ZMS::new();
+        for &[x, y, z] in points {
+            let mut vertex = Vertex::new();
+            vertex.position = Vector3 { x, y, z };
+            zms.vertices.push(vertex);
+        }
+        zms
+    }
+
+    #[test]
+    fn test_mesh_bounds() {
+        let mut zms = mesh(&[[1.0, -2.0, 0.5], [-1.0, 3.0, 0.0], [0.0, 0.0, 2.0]]);
+        assert!(recompute_mesh(&mut zms));
+        assert_eq!(
+            zms.bounding_box.min,
+            Vector3 {
+                x: -1.0,
+                y: -2.0,
+                z: 0.0
+            }
+        );
+        assert_eq!(
+            zms.bounding_box.max,
+            Vector3 {
+                x: 1.0,
+                y: 3.0,
+                z: 2.0
+            }
+        );
+        assert!(!recompute_mesh(&mut zms));
+        assert_eq!(mesh_bounds(&ZMS::new()), None);
+    }
+
+    #[test]
+    fn test_object_bounds() {
+        let meshes = [mesh(&[[-1.0, -1.0, 0.0], [1.0, 1.0, 2.0]])];
+        let part = |position: [f32; 3], parent: u16| SceneObjectPart {
+            mesh_id: 0,
+            position: Vector3 {
+                x: position[0],
+                y: position[1],
+                z: position[2],
+            },
+            rotation: Quaternion {
+                w: 1.0,
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            parent,
+            ..SceneObjectPart::default()
+        };
+        let mut zsc = ZSC::new();
+        zsc.objects.push(SceneObject {
+            parts: vec![part([0.0, 0.0, 0.0], 0), part([300.0, 0.0, 0.0], 1)],
+            ..Default::default()
+        });
+
+        assert_eq!(recompute_scene(&mut zsc, |id| meshes.get(id as usize)), 1);
+        let object = &zsc.objects[0];
+        assert_eq!(object.bounding_box.min.x, -100.0);
+        assert_eq!(object.bounding_box.max.x, 400.0);
+        assert_eq!(object.bounding_box.max.z, 200.0);
+        assert_eq!(object.bounding_cylinder.center, Vector2 { x: 150, y: 0 });
+        assert_eq!(object.bounding_cylinder.radius, 270.0);
+
+        // Objects with missing meshes keep their bounds
+        zsc.objects[0].parts[1].mesh_id = 1;
+        assert_eq!(recompute_scene(&mut zsc, |id| meshes.get(id as usize)), 0);
+        assert_eq!(zsc.objects[0].bounding_box.max.x, 400.0);
+    }
+}
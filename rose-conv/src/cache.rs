@@ -0,0 +1,169 @@
+//! Cache of converted inputs, used to skip unchanged files on incremental runs
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use failure::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Name of the cache file in the output directory
+pub const CACHE_FILE: &str = ".rose-conv-cache.json";
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct CacheEntry {
+    pub modified: SystemTime,
+    #[serde(default)]
+    pub file_type: String,
+    pub hash: String,
+    /// Fingerprint of the options the input was converted with
+    #[serde(default)]
+    pub options: String,
+    pub outputs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Cache {
+    pub entries: HashMap<String, CacheEntry>,
+}
+
+impl Cache {
+    /// Load the cache from `dir`, an empty cache is returned if it doesn't
+    /// exist yet
+    pub fn load(dir: &Path) -> Result<Cache, Error> {
+        let path = dir.join(CACHE_FILE);
+        if !path.exists() {
+            return Ok(Cache::default());
+        }
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), Error> {
+        fs::create_dir_all(dir)?;
+        let f = File::create(dir.join(CACHE_FILE))?;
+        serde_json::to_writer(f, self)?;
+        Ok(())
+    }
+
+    fn key(input: &Path) -> String {
+        let path = input.canonicalize().unwrap_or_else(|_| input.to_path_buf());
+        path.to_string_lossy().into_owned()
+    }
+
+    pub fn get(&self, input: &Path) -> Option<&CacheEntry> {
+        self.entries.get(&Cache::key(input))
+    }
+
+    /// Whether `input` is unchanged since it was last converted with the
+    /// same `options` fingerprint and all of its outputs still exist
+    pub fn is_fresh(&mut self, input: &Path, options: &str) -> Result<bool, Error> {
+        if !input.exists() {
+            return Ok(false);
+        }
+
+        let entry = match self.entries.get_mut(&Cache::key(input)) {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+
+        if entry.options != options || !entry.outputs.iter().all(|p| p.exists()) {
+            return Ok(false);
+        }
+
+        let modified = fs::metadata(input)?.modified()?;
+        if modified == entry.modified {
+            return Ok(true);
+        }
+
+        // The timestamp can change without the contents changing, e.g. after
+        // a fresh checkout, so fall back to comparing the hash
+        if file_hash(input)? == entry.hash {
+            entry.modified = modified;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    /// Record a successful conversion of `input` with the `options`
+    /// fingerprint
+    pub fn update(
+        &mut self,
+        input: &Path,
+        file_type: &str,
+        options: &str,
+        outputs: &[PathBuf],
+    ) -> Result<(), Error> {
+        let entry = CacheEntry {
+            modified: fs::metadata(input)?.modified()?,
+            file_type: file_type.to_string(),
+            hash: file_hash(input)?,
+            options: options.to_string(),
+            outputs: outputs.to_vec(),
+        };
+        self.entries.insert(Cache::key(input), entry);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, input: &Path) {
+        self.entries.remove(&Cache::key(input));
+    }
+}
+
+/// SHA-256 hash of a file as a hex string
+pub fn file_hash(path: &Path) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// SHA-256 hash of data as a hex string
+pub fn data_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join("rose-conv-test-cache");
+        fs::create_dir_all(&dir).unwrap();
+
+        let input = dir.join("input.stb");
+        let output = dir.join("input.csv");
+        fs::write(&input, "data").unwrap();
+        fs::write(&output, "data").unwrap();
+
+        let mut cache = Cache::default();
+        assert!(!cache.is_fresh(&input, "json").unwrap());
+
+        cache
+            .update(&input, "stb", "json", std::slice::from_ref(&output))
+            .unwrap();
+        assert!(cache.is_fresh(&input, "json").unwrap());
+        assert!(!cache.is_fresh(&input, "yaml").unwrap());
+        assert_eq!(
+            cache.get(&input).unwrap().hash,
+            "3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7"
+        );
+
+        cache.save(&dir).unwrap();
+        let mut cache = Cache::load(&dir).unwrap();
+        assert!(cache.is_fresh(&input, "json").unwrap());
+
+        fs::write(&input, "changed").unwrap();
+        assert!(!cache.is_fresh(&input, "json").unwrap());
+
+        cache
+            .update(&input, "stb", "json", std::slice::from_ref(&output))
+            .unwrap();
+        fs::remove_file(&output).unwrap();
+        assert!(!cache.is_fresh(&input, "json").unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+//! Files of a ROSE client
+//!
+//! A client is read either from an extracted client directory or from the
+//! file systems of a VFS index. Its files are found by their client path,
+//! e.g. `3DDATA\STB\LIST_ZONE.STB`, case-insensitively and with either
+//! separator, like the references in the client's files.
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use failure::{bail, Error};
+
+use roselib::files::IDX;
+use roselib::io::{RoseFile, RoseReader};
+
+use crate::vfs::client_path_key;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Location {
+    Disk(PathBuf),
+    Vfs { vfs: PathBuf, offset: u64 },
+}
+
+/// File of a client
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientFile {
+    /// Client path with forward slashes, in the case of the client
+    pub path: String,
+    pub size: u64,
+    location: Location,
+}
+
+impl ClientFile {
+    /// Lowercase extension, empty without one
+    pub fn extension(&self) -> String {
+        let name = self.path.rsplit('/').next().unwrap_or_default();
+        match name.rfind('.') {
+            Some(dot) if dot > 0 => name[dot + 1..].to_lowercase(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Files of a client by client path
+#[derive(Debug, Default)]
+pub struct Client {
+    files: BTreeMap<String, ClientFile>,
+}
+
+impl Client {
+    /// Open a client directory, or the VFS index at `path` if it's a `.idx`
+    pub fn open(path: &Path) -> Result<Client, Error> {
+        let is_idx = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("idx"));
+        if is_idx {
+            Client::from_vfs(path)
+        } else if path.is_dir() {
+            Client::from_dir(path)
+        } else {
+            bail!("Not a client directory or VFS index: {}", path.display())
+        }
+    }
+
+    /// Every file under a client directory
+    pub fn from_dir(dir: &Path) -> Result<Client, Error> {
+        let mut client = Client::default();
+        let mut dirs = vec![(dir.to_path_buf(), String::new())];
+        while let Some((dir, prefix)) = dirs.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let client_path = format!("{}{}", prefix, name);
+                if path.is_dir() {
+                    dirs.push((path, format!("{}/", client_path)));
+                } else {
+                    let size = entry.metadata()?.len();
+                    client.insert(client_path, size, Location::Disk(path));
+                }
+            }
+        }
+        Ok(client)
+    }
+
+    /// Files of the file systems of a VFS index, expected next to the index,
+    /// without the deleted files
+    pub fn from_vfs(idx_path: &Path) -> Result<Client, Error> {
+        let idx = IDX::from_path(idx_path)?;
+        let idx_dir = idx_path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut client = Client::default();
+        for vfs in &idx.file_systems {
+            for file in vfs.files.iter().filter(|f| !f.is_deleted) {
+                let location = Location::Vfs {
+                    vfs: idx_dir.join(&vfs.filename),
+                    offset: file.offset.max(0) as u64,
+                };
+                let path = file.filepath.to_string_lossy().replace('\\', "/");
+                client.insert(path, file.size.max(0) as u64, location);
+            }
+        }
+        Ok(client)
+    }
+
+    fn insert(&mut self, path: String, size: u64, location: Location) {
+        let file = ClientFile {
+            path,
+            size,
+            location,
+        };
+        self.files.insert(client_path_key(&file.path), file);
+    }
+
+    /// Every file, ordered by client path
+    pub fn files(&self) -> impl Iterator<Item = &ClientFile> {
+        self.files.values()
+    }
+
+    /// Files with a lowercase extension
+    pub fn files_with_extension<'a>(
+        &'a self,
+        extension: &'a str,
+    ) -> impl Iterator<Item = &'a ClientFile> + 'a {
+        self.files().filter(move |f| f.extension() == extension)
+    }
+
+    pub fn get(&self, path: &str) -> Option<&ClientFile> {
+        self.files.get(&client_path_key(path.trim()))
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.get(path).is_some()
+    }
+
+    /// Contents of a file by client path
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let file = match self.get(path) {
+            Some(file) => file,
+            None => bail!("File not found in the client: {}", path),
+        };
+        match &file.location {
+            Location::Disk(path) => Ok(fs::read(path)?),
+            Location::Vfs { vfs, offset } => {
+                let mut f = match File::open(vfs) {
+                    Ok(f) => f,
+                    Err(e) => bail!("Unable to open {}: {}", vfs.display(), e),
+                };
+                let mut data = vec![0u8; file.size as usize];
+                f.seek(SeekFrom::Start(*offset))?;
+                f.read_exact(&mut data)?;
+                Ok(data)
+            }
+        }
+    }
+
+    /// ROSE file by client path
+    pub fn read_file<T: RoseFile>(&self, path: &str) -> Result<T, Error> {
+        let data = self.read(path)?;
+        let mut file = T::new();
+        file.read(&mut RoseReader::new(Cursor::new(data)))?;
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::idx::{VfsFileMetadata, VfsMetadata};
+
+    #[test]
+    fn test_client() {
+        let dir = std::env::temp_dir().join("rose-conv-test-client-files");
+        fs::create_dir_all(dir.join("3DDATA/STB")).unwrap();
+        fs::write(dir.join("3DDATA/STB/LIST.STB"), "stb").unwrap();
+        fs::write(dir.join("data.vfs"), "aaabb").unwrap();
+
+        let client = Client::open(&dir).unwrap();
+        assert!(client.contains("3ddata\\stb\\list.stb"));
+        assert_eq!(client.read("3DDATA/STB/LIST.STB").unwrap(), b"stb");
+        assert_eq!(client.files_with_extension("vfs").count(), 1);
+        assert!(client.read("3DDATA/MISSING.STB").is_err());
+
+        let mut idx = IDX::new();
+        let mut vfs = VfsMetadata::new();
+        vfs.filename = PathBuf::from("data.vfs");
+        for (path, offset, size, is_deleted) in &[("A.TXT", 0, 3, false), ("B.TXT", 3, 2, true)] {
+            let mut file = VfsFileMetadata::new();
+            file.filepath = PathBuf::from(path);
+            file.offset = *offset;
+            file.size = *size;
+            file.is_deleted = *is_deleted;
+            vfs.files.push(file);
+        }
+        idx.file_systems.push(vfs);
+        let idx_path = dir.join("data.idx");
+        idx.write_to_path(&idx_path).unwrap();
+
+        let client = Client::open(&idx_path).unwrap();
+        assert_eq!(client.files().count(), 1);
+        assert_eq!(client.read("a.txt").unwrap(), b"aaa");
+        assert!(!client.contains("B.TXT"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+//! Comparison of two clients
+//!
+//! Files are matched by client path. Files in both clients with different
+//! contents, such as models and textures, are listed with their SHA-256
+//! hashes, and the changed STB and STL files are also compared cell by cell
+//! and key by key. Zones are compared by their ZON files.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use failure::Error;
+use serde::Serialize;
+
+use roselib::files::{STB, STL};
+
+use crate::cache::data_hash;
+use crate::client::{Client, ClientFile};
+use crate::stb_diff::StbDiff;
+use crate::stl_diff::StlDiff;
+
+/// File in both clients with different contents
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FileChange {
+    pub path: String,
+    pub old_sha256: String,
+    pub new_sha256: String,
+}
+
+/// Differences between two clients
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ClientDiff {
+    /// ZON files of the new client only
+    pub added_zones: Vec<String>,
+    /// ZON files of the old client only
+    pub removed_zones: Vec<String>,
+    pub added_files: Vec<String>,
+    pub removed_files: Vec<String>,
+    pub changed_files: Vec<FileChange>,
+    /// Differences of the changed STB files, by client path
+    pub tables: BTreeMap<String, StbDiff>,
+    /// Differences of the changed STL files, by client path
+    pub strings: BTreeMap<String, StlDiff>,
+    /// Changed STB and STL files that couldn't be read, with the error
+    pub unreadable: Vec<(String, String)>,
+}
+
+impl ClientDiff {
+    /// Compare every file of two clients, reading the files in both
+    pub fn new(old: &Client, new: &Client) -> Result<ClientDiff, Error> {
+        let mut diff = ClientDiff::default();
+
+        for file in old.files().filter(|f| !new.contains(&f.path)) {
+            diff.removed_files.push(file.path.clone());
+        }
+        for file in new.files().filter(|f| !old.contains(&f.path)) {
+            diff.added_files.push(file.path.clone());
+        }
+        let is_zone = |path: &&String| path.to_lowercase().ends_with(".zon");
+        diff.removed_zones = diff.removed_files.iter().filter(is_zone).cloned().collect();
+        diff.added_zones = diff.added_files.iter().filter(is_zone).cloned().collect();
+
+        for old_file in old.files() {
+            let new_file = match new.get(&old_file.path) {
+                Some(file) => file,
+                None => continue,
+            };
+            let old_data = old.read(&old_file.path)?;
+            let new_data = new.read(&new_file.path)?;
+            if old_data == new_data {
+                continue;
+            }
+
+            diff.changed_files.push(FileChange {
+                path: old_file.path.clone(),
+                old_sha256: data_hash(&old_data),
+                new_sha256: data_hash(&new_data),
+            });
+            diff.compare_contents(old, new, old_file, new_file);
+        }
+        Ok(diff)
+    }
+
+    /// Compare the cells or keys of a changed STB or STL file
+    fn compare_contents(
+        &mut self,
+        old: &Client,
+        new: &Client,
+        old_file: &ClientFile,
+        new_file: &ClientFile,
+    ) {
+        let path = old_file.path.clone();
+        match old_file.extension().as_str() {
+            "stb" => {
+                match (
+                    old.read_file::<STB>(&old_file.path),
+                    new.read_file::<STB>(&new_file.path),
+                ) {
+                    (Ok(old_stb), Ok(new_stb)) => {
+                        let stb_diff = StbDiff::new(&old_stb, &new_stb);
+                        if !stb_diff.is_empty() {
+                            self.tables.insert(path, stb_diff);
+                        }
+                    }
+                    (Err(e), _) | (_, Err(e)) => self.unreadable.push((path, e.to_string())),
+                }
+            }
+            "stl" => {
+                match (
+                    old.read_file::<STL>(&old_file.path),
+                    new.read_file::<STL>(&new_file.path),
+                ) {
+                    (Ok(old_stl), Ok(new_stl)) => {
+                        let stl_diff = StlDiff::new(&old_stl, &new_stl);
+                        if !stl_diff.is_empty() {
+                            self.strings.insert(path, stl_diff);
+                        }
+                    }
+                    (Err(e), _) | (_, Err(e)) => self.unreadable.push((path, e.to_string())),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_files.is_empty()
+            && self.removed_files.is_empty()
+            && self.changed_files.is_empty()
+    }
+}
+
+impl fmt::Display for ClientDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.added_zones.is_empty() || !self.removed_zones.is_empty() {
+            writeln!(f, "Zones:")?;
+            for path in &self.removed_zones {
+                writeln!(f, "- {}", path)?;
+            }
+            for path in &self.added_zones {
+                writeln!(f, "+ {}", path)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(
+            f,
+            "Files: {} added, {} removed, {} changed",
+            self.added_files.len(),
+            self.removed_files.len(),
+            self.changed_files.len()
+        )?;
+        for path in &self.removed_files {
+            writeln!(f, "- {}", path)?;
+        }
+        for path in &self.added_files {
+            writeln!(f, "+ {}", path)?;
+        }
+        for change in &self.changed_files {
+            writeln!(
+                f,
+                "~ {} ({} -> {})",
+                change.path,
+                &change.old_sha256[..12],
+                &change.new_sha256[..12]
+            )?;
+        }
+
+        for (path, diff) in &self.tables {
+            writeln!(f, "\n{}", path)?;
+            write!(f, "{}", diff)?;
+        }
+        for (path, diff) in &self.strings {
+            writeln!(f, "\n{}", path)?;
+            write!(f, "{}", diff)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::Path;
+
+    use roselib::io::RoseFile;
+
+    fn write_stb(path: &Path, names: &[&str]) {
+        let mut stb = STB::new();
+        stb.headers = vec![String::from("ID"), String::from("NAME")];
+        stb.data = names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| vec![id.to_string(), name.to_string()])
+            .collect();
+        stb.write_to_path(path).unwrap();
+    }
+
+    #[test]
+    fn test_client_diff() {
+        let dir = std::env::temp_dir().join("rose-conv-test-client-diff");
+        for client in &["old", "new"] {
+            fs::create_dir_all(dir.join(client).join("3DDATA/STB")).unwrap();
+            fs::create_dir_all(dir.join(client).join("3DDATA/MAPS/JUNON/JPT01")).unwrap();
+            fs::write(dir.join(client).join("3DDATA/SAME.ZMS"), "mesh").unwrap();
+        }
+        let old = dir.join("old");
+        let new = dir.join("new");
+        write_stb(&old.join("3DDATA/STB/LIST_ZONE.STB"), &["Junon"]);
+        write_stb(&new.join("3DDATA/STB/LIST_ZONE.STB"), &["Junon", "Zant"]);
+        fs::write(old.join("3DDATA/BOX.ZMS"), "box").unwrap();
+        fs::write(new.join("3DDATA/BOX.ZMS"), "BOX").unwrap();
+        fs::write(old.join("3DDATA/OLD.DDS"), "").unwrap();
+        fs::write(new.join("3DDATA/MAPS/JUNON/JPT01/JPT01.ZON"), "").unwrap();
+
+        let diff =
+            ClientDiff::new(&Client::open(&old).unwrap(), &Client::open(&new).unwrap()).unwrap();
+        assert_eq!(diff.added_zones, ["3DDATA/MAPS/JUNON/JPT01/JPT01.ZON"]);
+        assert_eq!(diff.added_files, ["3DDATA/MAPS/JUNON/JPT01/JPT01.ZON"]);
+        assert_eq!(diff.removed_files, ["3DDATA/OLD.DDS"]);
+        let changed: Vec<&str> = diff.changed_files.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(changed, ["3DDATA/BOX.ZMS", "3DDATA/STB/LIST_ZONE.STB"]);
+        assert_eq!(diff.changed_files[0].old_sha256, data_hash(b"box"));
+        assert_eq!(diff.tables["3DDATA/STB/LIST_ZONE.STB"].added_rows.len(), 1);
+        assert!(diff.strings.is_empty());
+        assert!(diff
+            .to_string()
+            .contains("\n3DDATA/STB/LIST_ZONE.STB\n+ row 1: 1, Zant\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+//! Manifest of the files of a client with their checksums
+//!
+//! Launchers and patchers check an install against the manifest of a known
+//! good client to find the files to download again. Files are compared by
+//! size first and only hashed if the size matches.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+use failure::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::data_hash;
+use crate::client::Client;
+
+/// Default name of the manifest file in the output directory
+pub const CLIENT_MANIFEST_FILE: &str = "client_manifest.json";
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ManifestFile {
+    pub size: u64,
+    /// SHA-256 hash of the contents as a hex string
+    pub sha256: String,
+}
+
+/// Files of a client by client path
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ClientManifest {
+    pub files: BTreeMap<String, ManifestFile>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mismatch {
+    /// The client doesn't have the file
+    Missing,
+    /// The file has a different size
+    Size,
+    /// The file has the same size but different contents
+    Checksum,
+}
+
+/// File of a client that doesn't match its manifest
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Difference {
+    pub path: String,
+    pub mismatch: Mismatch,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mismatch = match self.mismatch {
+            Mismatch::Missing => "missing",
+            Mismatch::Size => "size differs",
+            Mismatch::Checksum => "checksum differs",
+        };
+        write!(f, "{}: {}", self.path, mismatch)
+    }
+}
+
+impl ClientManifest {
+    /// Manifest of every file of a client, reading all of them
+    pub fn new(client: &Client) -> Result<ClientManifest, Error> {
+        let mut manifest = ClientManifest::default();
+        for file in client.files() {
+            let data = client.read(&file.path)?;
+            manifest.files.insert(
+                file.path.clone(),
+                ManifestFile {
+                    size: data.len() as u64,
+                    sha256: data_hash(&data),
+                },
+            );
+        }
+        Ok(manifest)
+    }
+
+    pub fn from_path(path: &Path) -> Result<ClientManifest, Error> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    pub fn write_to_path(&self, path: &Path, pretty: bool) -> Result<(), Error> {
+        let f = File::create(path)?;
+        if pretty {
+            serde_json::to_writer_pretty(f, self)?;
+        } else {
+            serde_json::to_writer(f, self)?;
+        }
+        Ok(())
+    }
+
+    /// Files of the manifest that a client is missing or that differ,
+    /// ignoring the files of the client that aren't in the manifest
+    pub fn verify(&self, client: &Client) -> Result<Vec<Difference>, Error> {
+        let mut differences = Vec::new();
+        for (path, expected) in &self.files {
+            let mismatch = match client.get(path) {
+                None => Some(Mismatch::Missing),
+                Some(file) if file.size != expected.size => Some(Mismatch::Size),
+                Some(file) => {
+                    let sha256 = data_hash(&client.read(&file.path)?);
+                    if sha256.eq_ignore_ascii_case(&expected.sha256) {
+                        None
+                    } else {
+                        Some(Mismatch::Checksum)
+                    }
+                }
+            };
+            if let Some(mismatch) = mismatch {
+                differences.push(Difference {
+                    path: path.clone(),
+                    mismatch,
+                });
+            }
+        }
+        Ok(differences)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn test_client_manifest() {
+        let dir = std::env::temp_dir().join("rose-conv-test-client-manifest");
+        fs::create_dir_all(dir.join("3DDATA/STB")).unwrap();
+        fs::write(dir.join("3DDATA/STB/LIST.STB"), "stb").unwrap();
+        fs::write(dir.join("3DDATA/STB/LIST.STL"), "stl").unwrap();
+        fs::write(dir.join("3DDATA/STB/OLD.STB"), "old").unwrap();
+
+        let client = Client::open(&dir).unwrap();
+        let manifest = ClientManifest::new(&client).unwrap();
+        assert_eq!(manifest.files.len(), 3);
+        assert_eq!(
+            manifest.files["3DDATA/STB/LIST.STB"],
+            ManifestFile {
+                size: 3,
+                sha256: data_hash(b"stb")
+            }
+        );
+        assert!(manifest.verify(&client).unwrap().is_empty());
+
+        let path = dir.join(CLIENT_MANIFEST_FILE);
+        manifest.write_to_path(&path, false).unwrap();
+        let manifest = ClientManifest::from_path(&path).unwrap();
+
+        fs::write(dir.join("3DDATA/STB/LIST.STB"), "STB").unwrap();
+        fs::write(dir.join("3DDATA/STB/LIST.STL"), "stl2").unwrap();
+        fs::remove_file(dir.join("3DDATA/STB/OLD.STB")).unwrap();
+        let client = Client::open(&dir).unwrap();
+        let differences: Vec<String> = manifest
+            .verify(&client)
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            differences,
+            [
+                "3DDATA/STB/LIST.STB: checksum differs",
+                "3DDATA/STB/LIST.STL: size differs",
+                "3DDATA/STB/OLD.STB: missing"
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
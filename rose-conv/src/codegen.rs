@@ -0,0 +1,321 @@
+//! Rust code generation from STB schemas
+//!
+//! Every schema becomes a row struct with a field per column and a table
+//! struct that loads an STB with roselib, e.g. `schemas/list_item.toml`
+//! generates:
+//!
+//! ```ignore
+//! pub struct ListItemRow {
+//!     pub name: String,
+//!     pub price: i32,
+//! }
+//!
+//! pub struct ListItem {
+//!     pub rows: Vec<ListItemRow>,
+//! }
+//! ```
+//!
+//! Int columns are `i32`, float columns `f32` and empty numeric cells are
+//! read as 0. Columns past the end of the schema are ignored. A `Tables`
+//! struct loads every table from a directory, matching the file names
+//! case-insensitively.
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use failure::Error;
+
+use crate::stb_schema::{ColumnType, StbSchema};
+
+/// Rust keywords, which can't be used as field names
+const KEYWORDS: [&str; 51] = [
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "union", "unsafe", "unsized", "use", "virtual", "where", "while",
+];
+
+/// Helpers shared by the generated tables
+const HELPERS: &str = r#"/// Value of a cell, empty if the row is too short
+fn cell(row: &[String], column: usize) -> &str {
+    row.get(column).map(String::as_str).unwrap_or_default()
+}
+
+/// Parse a numeric cell, empty cells are 0
+#[allow(dead_code)]
+fn parse<T>(row: &[String], row_idx: usize, column: usize, name: &str) -> Result<T, Error>
+where
+    T: FromStr + Default,
+{
+    let value = cell(row, column).trim();
+    if value.is_empty() {
+        return Ok(T::default());
+    }
+    match value.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => bail!("Invalid value in row {} column {}: {}", row_idx, name, value),
+    }
+}
+
+/// Find a file in `dir` by its case-insensitive name
+fn find_file(dir: &Path, name: &str) -> Result<PathBuf, Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if file_name.eq_ignore_ascii_case(name) {
+            return Ok(path);
+        }
+    }
+    bail!("{} not found in {}", name, dir.display())
+}
+"#;
+
+/// Name of the table struct of a schema, e.g. `ListItem` for `list_item`
+pub fn type_name(table: &str) -> String {
+    let mut name = String::new();
+    for part in table.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_ascii_uppercase());
+            name.extend(chars.map(|c| c.to_ascii_lowercase()));
+        }
+    }
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, 'T');
+    }
+    name
+}
+
+/// Name of the field of a column, e.g. `name_id` for `Name ID`
+pub fn field_name(column: &str) -> String {
+    let mut name: String = column
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    if KEYWORDS.contains(&name.as_str()) || name == "_" {
+        name.push('_');
+    }
+    name
+}
+
+fn rust_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::String => "String",
+        ColumnType::Int => "i32",
+        ColumnType::Float => "f32",
+    }
+}
+
+/// Generate a Rust module for the tables, given as the schema name, e.g.
+/// `list_item`, and its schema
+pub fn generate(tables: &[(String, StbSchema)]) -> Result<String, Error> {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "//! STB tables generated by `rose-conv codegen`, do not edit"
+    )?;
+    writeln!(out, "use std::fs;")?;
+    writeln!(out, "use std::path::{{Path, PathBuf}};")?;
+    writeln!(out, "use std::str::FromStr;")?;
+    writeln!(out)?;
+    writeln!(out, "use failure::{{bail, Error}};")?;
+    writeln!(out, "use roselib::files::STB;")?;
+    writeln!(out, "use roselib::io::RoseFile;")?;
+
+    for (table, schema) in tables {
+        writeln!(out)?;
+        generate_table(&mut out, table, schema)?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "/// Every table, loaded from a directory of STB files")?;
+    writeln!(out, "#[derive(Clone, Debug, Default, PartialEq)]")?;
+    writeln!(out, "pub struct Tables {{")?;
+    for (table, _) in tables {
+        writeln!(out, "    pub {}: {},", field_name(table), type_name(table))?;
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(out, "impl Tables {{")?;
+    writeln!(
+        out,
+        "    pub fn load(dir: &Path) -> Result<Tables, Error> {{"
+    )?;
+    writeln!(out, "        Ok(Tables {{")?;
+    for (table, _) in tables {
+        writeln!(
+            out,
+            "            {}: {}::from_path(&find_file(dir, \"{}.stb\")?)?,",
+            field_name(table),
+            type_name(table),
+            table.escape_default()
+        )?;
+    }
+    writeln!(out, "        }})")?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "}}")?;
+
+    writeln!(out)?;
+    out.push_str(HELPERS);
+    Ok(out)
+}
+
+fn generate_table(out: &mut String, table: &str, schema: &StbSchema) -> Result<(), Error> {
+    let type_name = type_name(table);
+    let row_name = format!("{}Row", type_name);
+
+    // Field names have to be unique
+    let mut used = HashSet::new();
+    let mut fields = Vec::new();
+    for (idx, column) in schema.columns.iter().enumerate() {
+        let mut name = field_name(&column.name);
+        if !used.insert(name.clone()) {
+            name = format!("column_{}", idx);
+            used.insert(name.clone());
+        }
+        fields.push((name, column));
+    }
+
+    writeln!(out, "/// Row of {}.STB", table.to_uppercase())?;
+    writeln!(out, "#[derive(Clone, Debug, Default, PartialEq)]")?;
+    writeln!(out, "pub struct {} {{", row_name)?;
+    for (name, column) in &fields {
+        writeln!(out, "    pub {}: {},", name, rust_type(column.column_type))?;
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    writeln!(out, "#[derive(Clone, Debug, Default, PartialEq)]")?;
+    writeln!(out, "pub struct {} {{", type_name)?;
+    writeln!(out, "    pub rows: Vec<{}>,", row_name)?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    writeln!(out, "impl {} {{", type_name)?;
+    writeln!(
+        out,
+        "    pub fn from_path(path: &Path) -> Result<{}, Error> {{",
+        type_name
+    )?;
+    writeln!(
+        out,
+        "        {}::from_stb(&STB::from_path(path)?)",
+        type_name
+    )?;
+    writeln!(out, "    }}")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "    pub fn from_stb(stb: &STB) -> Result<{}, Error> {{",
+        type_name
+    )?;
+    writeln!(
+        out,
+        "        let mut rows = Vec::with_capacity(stb.data.len());"
+    )?;
+    if fields
+        .iter()
+        .all(|(_, c)| c.column_type == ColumnType::String)
+    {
+        writeln!(out, "        for row in &stb.data {{")?;
+    } else {
+        writeln!(
+            out,
+            "        for (row_idx, row) in stb.data.iter().enumerate() {{"
+        )?;
+    }
+    writeln!(out, "            rows.push({} {{", row_name)?;
+    for (idx, (name, column)) in fields.iter().enumerate() {
+        match column.column_type {
+            ColumnType::String => writeln!(
+                out,
+                "                {}: cell(row, {}).to_string(),",
+                name, idx
+            )?,
+            _ => writeln!(
+                out,
+                "                {}: parse(row, row_idx, {}, \"{}\")?,",
+                name,
+                idx,
+                column.name.escape_default()
+            )?,
+        }
+    }
+    writeln!(out, "            }});")?;
+    writeln!(out, "        }}")?;
+    writeln!(out, "        Ok({} {{ rows }})", type_name)?;
+    writeln!(out, "    }}")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "    pub fn get(&self, idx: usize) -> Option<&{}> {{",
+        row_name
+    )?;
+    writeln!(out, "        self.rows.get(idx)")?;
+    writeln!(out, "    }}")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "    pub fn iter(&self) -> impl Iterator<Item = &{}> {{",
+        row_name
+    )?;
+    writeln!(out, "        self.rows.iter()")?;
+    writeln!(out, "    }}")?;
+    writeln!(out)?;
+    writeln!(out, "    pub fn len(&self) -> usize {{")?;
+    writeln!(out, "        self.rows.len()")?;
+    writeln!(out, "    }}")?;
+    writeln!(out)?;
+    writeln!(out, "    pub fn is_empty(&self) -> bool {{")?;
+    writeln!(out, "        self.rows.is_empty()")?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        assert_eq!(type_name("list_item"), "ListItem");
+        assert_eq!(type_name("3ddata"), "T3ddata");
+        assert_eq!(field_name("Name ID"), "name_id");
+        assert_eq!(field_name("type"), "type_");
+        assert_eq!(field_name("1st"), "_1st");
+
+        let schema: StbSchema = r#"
+            [[columns]]
+            name = "name"
+
+            [[columns]]
+            name = "price"
+            type = "int"
+
+            [[columns]]
+            name = "Price"
+            type = "float"
+        "#
+        .parse()
+        .unwrap();
+
+        let code = generate(&[("list_item".into(), schema)]).unwrap();
+        assert!(code.contains("pub struct ListItemRow {\n    pub name: String,\n    pub price: i32,\n    pub column_2: f32,\n}"));
+        assert!(code.contains("price: parse(row, row_idx, 1, \"price\")?,"));
+        assert!(
+            code.contains("list_item: ListItem::from_path(&find_file(dir, \"list_item.stb\")?)?,")
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Collision geometry of maps
+//!
+//! The parts of a ZSC object collide with a shape given by the low bits of
+//! their `collision` flags: a sphere, an axis aligned or oriented box, or the
+//! polygons of the mesh. Every shape is bounded by the part's mesh, so the
+//! collision geometry of a map is the meshes of the colliding parts of its
+//! placed objects, moved into the world, without their render-only parts.
+//!
+//! The geometry is written as a single OBJ with a group per part, Y-up in
+//! meters at world positions like glTF exports, with the terrain optionally.
+use std::fmt::Write;
+
+use roselib::files::zsc::{SceneObject, SceneObjectPart};
+use roselib::files::ZMS;
+use roselib::utils::{Quaternion, Vector3};
+
+use crate::gltf;
+use crate::ifo_instances::Instance;
+use crate::terrain::{Terrain, BLOCK_CELLS, BLOCK_SIZE};
+
+/// Mask of the collision shape of ZSC parts, the other bits are flags
+const COLLISION_SHAPE: u16 = 0x7;
+
+/// Affine transform as the rows of a 3x4 matrix
+type Matrix = [[f32; 4]; 3];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+];
+
+/// Collision shape of a ZSC part
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CollisionShape {
+    Sphere,
+    AxisAlignedBox,
+    OrientedBox,
+    Polygon,
+}
+
+impl CollisionShape {
+    pub fn from_part(part: &SceneObjectPart) -> Option<CollisionShape> {
+        match part.collision & COLLISION_SHAPE {
+            1 => Some(CollisionShape::Sphere),
+            2 => Some(CollisionShape::AxisAlignedBox),
+            3 => Some(CollisionShape::OrientedBox),
+            4 => Some(CollisionShape::Polygon),
+            _ => None,
+        }
+    }
+}
+
+/// Merged OBJ of collision geometry being built
+pub struct CollisionObj {
+    obj: String,
+    vertices: usize,
+    pub triangles: usize,
+}
+
+impl CollisionObj {
+    pub fn new(name: &str) -> CollisionObj {
+        CollisionObj {
+            obj: format!("# Collision geometry of {}\n", name),
+            vertices: 0,
+            triangles: 0,
+        }
+    }
+
+    /// Add the meshes of the colliding parts of an instance of `object`,
+    /// with the meshes of the ZSC by index from `mesh`. Returns the number of
+    /// parts added.
+    pub fn add_instance<'a, F>(
+        &mut self,
+        name: &str,
+        instance: &Instance,
+        object: &SceneObject,
+        mesh: F,
+    ) -> usize
+    where
+        F: Fn(u16) -> Option<&'a ZMS>,
+    {
+        let placement = matrix(
+            [
+                instance.position.x / 100.0,
+                instance.position.y / 100.0,
+                instance.position.z / 100.0,
+            ],
+            &instance.rotation,
+            &instance.scale,
+        );
+        let mut added = 0;
+        for (idx, part) in object.parts.iter().enumerate() {
+            let shape = match CollisionShape::from_part(part) {
+                Some(shape) => shape,
+                None => continue,
+            };
+            let zms = match mesh(part.mesh_id) {
+                Some(zms) => zms,
+                None => continue,
+            };
+            let transform = multiply(&placement, &part_matrix(object, idx));
+            let _ = writeln!(self.obj, "g {}_{}\n# {:?}", name, idx, shape);
+            self.add_mesh(zms, &transform);
+            added += 1;
+        }
+        added
+    }
+
+    fn add_mesh(&mut self, zms: &ZMS, transform: &Matrix) {
+        for vertex in &zms.vertices {
+            let [x, y, z] = gltf::position(&apply(transform, &vertex.position));
+            let _ = writeln!(self.obj, "v {} {} {}", x, y, z);
+        }
+        for triangle in &zms.indices {
+            // The change of axes is a rotation, keeping the winding
+            let _ = writeln!(
+                self.obj,
+                "f {} {} {}",
+                self.vertices + triangle.x as usize + 1,
+                self.vertices + triangle.y as usize + 1,
+                self.vertices + triangle.z as usize + 1
+            );
+        }
+        self.vertices += zms.vertices.len();
+        self.triangles += zms.indices.len();
+    }
+
+    /// Add the terrain as a group of two triangles per cell
+    pub fn add_terrain(&mut self, terrain: &Terrain) {
+        let cell = BLOCK_SIZE / BLOCK_CELLS as f32;
+        let (x0, y0) = terrain.origin();
+        let (rows, cols) = (terrain.rows(), terrain.cols());
+        let _ = writeln!(self.obj, "g Terrain");
+        for (row, heights) in terrain.heights.iter().enumerate() {
+            for (col, height) in heights.iter().enumerate() {
+                let [x, y, z] = gltf::position(&Vector3 {
+                    x: (x0 + col as f32 * cell) / 100.0,
+                    y: (y0 - row as f32 * cell) / 100.0,
+                    z: height / 100.0,
+                });
+                let _ = writeln!(self.obj, "v {} {} {}", x, y, z);
+            }
+        }
+        for row in 0..rows.saturating_sub(1) {
+            for col in 0..cols.saturating_sub(1) {
+                let i = self.vertices + row * cols + col + 1;
+                let (right, below) = (i + 1, i + cols);
+                let _ = writeln!(
+                    self.obj,
+                    "f {} {} {}\nf {} {} {}",
+                    i,
+                    below,
+                    right,
+                    right,
+                    below,
+                    below + 1
+                );
+                self.triangles += 2;
+            }
+        }
+        self.vertices += rows * cols;
+    }
+
+    pub fn to_obj(&self) -> &str {
+        &self.obj
+    }
+}
+
+/// Transform of a part of an object from its mesh to the object, through its
+/// parents
+pub(crate) fn part_matrix(object: &SceneObject, idx: usize) -> Matrix {
+    let mut transform = IDENTITY;
+    let mut current = Some(idx);
+    // Parents in a cycle stop after every part has been visited once
+    for _ in 0..object.parts.len() {
+        let part = match current.and_then(|i| object.parts.get(i)) {
+            Some(part) => part,
+            None => break,
+        };
+        let local = matrix(
+            [
+                part.position.x / 100.0,
+                part.position.y / 100.0,
+                part.position.z / 100.0,
+            ],
+            &part.rotation,
+            &part.scale,
+        );
+        transform = multiply(&local, &transform);
+        current = match part.parent as usize {
+            0 => None,
+            parent => Some(parent - 1),
+        };
+    }
+    transform
+}
+
+/// Translation, then rotation, then scale, a zero scale being no scale
+fn matrix(translation: [f32; 3], q: &Quaternion, scale: &Vector3<f32>) -> Matrix {
+    let scale = if *scale == Vector3::default() {
+        [1.0; 3]
+    } else {
+        [scale.x, scale.y, scale.z]
+    };
+    let (x, y, z, w) = (q.x, q.y, q.z, q.w);
+    let rotation = [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - z * w),
+            2.0 * (x * z + y * w),
+        ],
+        [
+            2.0 * (x * y + z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - x * w),
+        ],
+        [
+            2.0 * (x * z - y * w),
+            2.0 * (y * z + x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ];
+    let mut m = [[0.0; 4]; 3];
+    for row in 0..3 {
+        for col in 0..3 {
+            m[row][col] = rotation[row][col] * scale[col];
+        }
+        m[row][3] = translation[row];
+    }
+    m
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.0; 4]; 3];
+    for row in 0..3 {
+        for col in 0..4 {
+            m[row][col] = (0..3).map(|k| a[row][k] * b[k][col]).sum::<f32>();
+        }
+        m[row][3] += a[row][3];
+    }
+    m
+}
+
+pub(crate) fn apply(m: &Matrix, v: &Vector3<f32>) -> Vector3<f32> {
+    let row = |r: &[f32; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z + r[3];
+    Vector3 {
+        x: row(&m[0]),
+        y: row(&m[1]),
+        z: row(&m[2]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zms::Vertex;
+    use roselib::io::RoseFile;
+
+    use crate::ifo_instances::InstanceKind;
+
+    #[test]
+    fn test_collision_obj() {
+        let mut zms = ZMS::new();
+        for &(x, y) in &[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)] {
+            let mut vertex = Vertex::new();
+            vertex.position = Vector3 { x, y, z: 0.0 };
+            zms.vertices.push(vertex);
+        }
+        zms.indices.push(Vector3 { x: 0, y: 1, z: 2 });
+
+        let identity = Quaternion {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 1.0,
+        };
+        let mut object = SceneObject::default();
+        // A render-only part, then a colliding one 2 meters above it
+        object.parts.push(SceneObjectPart {
+            mesh_id: 0,
+            rotation: identity,
+            ..Default::default()
+        });
+        object.parts.push(SceneObjectPart {
+            mesh_id: 0,
+            position: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 200.0,
+            },
+            rotation: identity,
+            parent: 1,
+            collision: 4,
+            ..Default::default()
+        });
+
+        // A quarter turn counterclockwise, twice as big
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let instance = Instance {
+            kind: InstanceKind::Building,
+            name: String::new(),
+            object_id: 0,
+            block: [32, 32],
+            position: Vector3 {
+                x: 520_000.0,
+                y: 520_000.0,
+                z: 100.0,
+            },
+            rotation: Quaternion {
+                x: 0.0,
+                y: 0.0,
+                z: half,
+                w: half,
+            },
+            scale: Vector3 {
+                x: 2.0,
+                y: 2.0,
+                z: 2.0,
+            },
+            parts: None,
+        };
+
+        let mut obj = CollisionObj::new("JPT01");
+        assert_eq!(
+            obj.add_instance("Building_32_32_0", &instance, &object, |_| None),
+            0
+        );
+        assert_eq!(
+            obj.add_instance("Building_32_32_0", &instance, &object, |_| Some(&zms)),
+            1
+        );
+        assert_eq!(obj.triangles, 1);
+
+        let lines: Vec<&str> = obj.to_obj().lines().collect();
+        assert_eq!(lines[1], "g Building_32_32_0_1");
+        assert_eq!(lines[2], "# Polygon");
+        // Y-up: east stays X, 5 meters up and 5200 meters north
+        assert_eq!(lines[3], "v 5200 5 -5200");
+        let vertex: Vec<f32> = lines[4][2..]
+            .split(' ')
+            .map(|v| v.parse::<f32>().unwrap().round())
+            .collect();
+        // East turned north, twice as far
+        assert_eq!(vertex, vec![5200.0, 5.0, -5202.0]);
+        assert_eq!(lines[6], "f 1 2 3");
+
+        // A flat block
+        obj.add_terrain(&Terrain::from_blocks(&Default::default()));
+        assert_eq!(obj.triangles, 1 + 64 * 64 * 2);
+        assert!(obj.to_obj().contains("\ng Terrain\nv 0 0 -10400\n"));
+        assert!(obj.to_obj().ends_with("f 4163 4227 4228\n"));
+    }
+}
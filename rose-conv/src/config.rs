@@ -0,0 +1,181 @@
+//! Conversion profiles loaded from a `rose-conv.toml` file
+//!
+//! ```toml
+//! [profiles.export-tables]
+//! command = "serialize"
+//! inputs = ["3DDATA/STB/*.STB"]
+//! type = "stb"
+//! out-dir = "out/stb"
+//! keep-extension = true
+//! ```
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use failure::{bail, Error};
+use serde::Deserialize;
+
+/// Default name of the configuration file
+pub const CONFIG_FILE: &str = "rose-conv.toml";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// A named set of arguments for a single subcommand
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Profile {
+    /// Subcommand to run: `serialize`, `deserialize`, `map` or `iconsheet`
+    pub command: String,
+    /// Input paths or glob patterns, relative to the config file
+    pub inputs: Vec<String>,
+    /// ROSE file type
+    #[serde(rename = "type")]
+    pub rose_type: Option<String>,
+    /// Output directory, relative to the config file
+    pub out_dir: Option<String>,
+    #[serde(default)]
+    pub keep_extension: bool,
+    #[serde(default)]
+    pub fail_fast: bool,
+    /// Extra arguments passed to the subcommand as-is
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl FromStr for Config {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Config, Error> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+impl Config {
+    pub fn from_path(path: &Path) -> Result<Config, Error> {
+        if !path.exists() {
+            bail!("Config file does not exist: {}", path.display());
+        }
+        fs::read_to_string(path)?.parse()
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile, Error> {
+        match self.profiles.get(name) {
+            Some(profile) => Ok(profile),
+            None => {
+                let mut names: Vec<&str> = self.profiles.keys().map(|k| k.as_str()).collect();
+                names.sort_unstable();
+                bail!(
+                    "Profile not found: {} (available: {})",
+                    name,
+                    names.join(", ")
+                );
+            }
+        }
+    }
+}
+
+impl Profile {
+    /// Expand the input globs relative to `base_dir`
+    pub fn input_paths(&self, base_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut paths = Vec::new();
+        for pattern in &self.inputs {
+            let pattern = base_dir.join(pattern);
+            let matches = glob::glob(pattern.to_str().unwrap_or_default())?
+                .collect::<Result<Vec<PathBuf>, _>>()?;
+
+            if matches.is_empty() {
+                bail!("No files match input: {}", pattern.display());
+            }
+            paths.extend(matches);
+        }
+        Ok(paths)
+    }
+
+    /// Command lines (without the program name) that run this profile.
+    /// Subcommands that only accept a single input are run once per input.
+    pub fn command_lines(&self, base_dir: &Path) -> Result<Vec<Vec<String>>, Error> {
+        let inputs: Vec<String> = self
+            .input_paths(base_dir)?
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+
+        let mut options = Vec::new();
+        if let Some(out_dir) = &self.out_dir {
+            options.push(String::from("-o"));
+            options.push(base_dir.join(out_dir).to_string_lossy().into_owned());
+        }
+        if self.keep_extension {
+            options.push(String::from("--keep-extension"));
+        }
+        if self.fail_fast {
+            options.push(String::from("--fail-fast"));
+        }
+        options.extend(self.args.iter().cloned());
+
+        let command_lines = match self.command.as_str() {
+            "serialize" | "iconsheet" => {
+                let mut line = vec![self.command.clone()];
+                line.extend(inputs);
+                if let Some(rose_type) = &self.rose_type {
+                    line.push(String::from("-t"));
+                    line.push(rose_type.clone());
+                }
+                line.extend(options);
+                vec![line]
+            }
+            "deserialize" | "map" => inputs
+                .into_iter()
+                .map(|input| {
+                    let mut line = vec![self.command.clone()];
+                    line.extend(self.rose_type.clone());
+                    line.push(input);
+                    line.extend(options.iter().cloned());
+                    line
+                })
+                .collect(),
+            _ => bail!("Unsupported profile command: {}", self.command),
+        };
+
+        Ok(command_lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let config = Config::from_str(
+            r#"
+            [profiles.export-tables]
+            command = "serialize"
+            inputs = ["Cargo.toml"]
+            type = "stb"
+            out-dir = "out"
+            keep-extension = true
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.profile("missing").is_err());
+
+        let profile = config.profile("export-tables").unwrap();
+        assert_eq!(profile.rose_type, Some(String::from("stb")));
+
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let lines = profile.command_lines(base_dir).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0][0], "serialize");
+        assert_eq!(lines[0][1], base_dir.join("Cargo.toml").to_str().unwrap());
+        assert_eq!(lines[0][2..4], ["-t", "stb"]);
+        assert_eq!(lines[0][4], "-o");
+        assert_eq!(lines[0][6], "--keep-extension");
+    }
+}
@@ -0,0 +1,83 @@
+//! CSV dialect used when reading and writing STB and STL files
+use failure::{bail, Error};
+
+/// Delimiter, quoting and line endings of CSV files
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CsvDialect {
+    pub delimiter: u8,
+    /// Quote every field instead of only the ones that need it
+    pub quote_all: bool,
+    /// End lines with `\r\n` instead of `\n`
+    pub crlf: bool,
+}
+
+impl Default for CsvDialect {
+    fn default() -> CsvDialect {
+        CsvDialect {
+            delimiter: b',',
+            quote_all: false,
+            crlf: false,
+        }
+    }
+}
+
+impl CsvDialect {
+    /// Parse a delimiter, either a single ASCII character or `\t`/`tab` for
+    /// tab-separated files
+    pub fn parse_delimiter(s: &str) -> Result<u8, Error> {
+        match s {
+            "\\t" | "tab" => Ok(b'\t'),
+            _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+            _ => bail!("Invalid CSV delimiter, expected a single character: {}", s),
+        }
+    }
+
+    pub fn writer_builder(&self) -> csv::WriterBuilder {
+        let mut builder = csv::WriterBuilder::new();
+        builder.delimiter(self.delimiter);
+        if self.quote_all {
+            builder.quote_style(csv::QuoteStyle::Always);
+        }
+        if self.crlf {
+            builder.terminator(csv::Terminator::CRLF);
+        }
+        builder
+    }
+
+    /// Readers accept both line endings and quoted or unquoted fields
+    pub fn reader_builder(&self) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder.delimiter(self.delimiter);
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::STB;
+    use roselib::io::RoseFile;
+
+    use crate::{FromCsv, ToCsv};
+
+    #[test]
+    fn test_csv_dialect() {
+        assert_eq!(CsvDialect::parse_delimiter("\\t").unwrap(), b'\t');
+        assert_eq!(CsvDialect::parse_delimiter(";").unwrap(), b';');
+        assert!(CsvDialect::parse_delimiter("ab").is_err());
+
+        let mut stb = STB::new();
+        stb.headers = vec!["NAME".into(), "DESC".into()];
+        stb.data = vec![vec!["a".into(), "b, c".into()]];
+
+        let dialect = CsvDialect {
+            delimiter: b'\t',
+            quote_all: true,
+            crlf: true,
+        };
+        let csv = stb.to_csv_with(&dialect).unwrap();
+        assert_eq!(csv, "\"NAME\"\t\"DESC\"\r\n\"a\"\t\"b, c\"\r\n");
+        assert_eq!(STB::from_csv_with(&csv, &dialect).unwrap(), stb);
+    }
+}
@@ -0,0 +1,568 @@
+//! DirectDraw Surface (DDS) textures
+//!
+//! Decodes the top mipmap of the DDS textures used by the client: DXT1 (with
+//! 1-bit alpha), DXT3 and DXT5 compressed textures and uncompressed RGB,
+//! luminance and alpha textures described by bit masks, e.g. A8R8G8B8,
+//! A4R4G4B4, X1R5G5B5 or R5G6B5.
+//!
+//! Images are encoded as DXT1 or DXT5 textures with a full mipmap chain.
+//! Block endpoints are the extremes of the block's colors along their
+//! principal axis.
+use byteorder::{ByteOrder, LittleEndian};
+use failure::{bail, Error};
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: usize = 128;
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+/// Compression of encoded textures
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DxtFormat {
+    /// 4 bits per pixel, with transparent or opaque pixels
+    Dxt1,
+    /// 8 bits per pixel, with interpolated alpha
+    Dxt5,
+}
+
+impl DxtFormat {
+    fn block_size(&self) -> usize {
+        match self {
+            DxtFormat::Dxt1 => 8,
+            DxtFormat::Dxt5 => 16,
+        }
+    }
+}
+
+/// Whether the data starts with the DDS magic number
+pub fn is_dds(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Width and height of a DDS texture
+pub fn dimensions(data: &[u8]) -> Result<(u32, u32), Error> {
+    if !is_dds(data) || data.len() < HEADER_SIZE {
+        bail!("Not a DDS texture");
+    }
+    let height = LittleEndian::read_u32(&data[12..16]);
+    let width = LittleEndian::read_u32(&data[16..20]);
+    Ok((width, height))
+}
+
+/// Decode the top mipmap of a DDS texture
+pub fn decode(data: &[u8]) -> Result<RgbaImage, Error> {
+    let (width, height) = dimensions(data)?;
+    let flags = LittleEndian::read_u32(&data[80..84]);
+    let pixels = &data[HEADER_SIZE..];
+
+    if flags & DDPF_FOURCC != 0 {
+        let fourcc = &data[84..88];
+        let block_size = match fourcc {
+            b"DXT1" => 8,
+            b"DXT2" | b"DXT3" | b"DXT4" | b"DXT5" => 16,
+            _ => bail!(
+                "Unsupported DDS format: {}",
+                String::from_utf8_lossy(fourcc)
+            ),
+        };
+
+        let blocks_wide = (width as usize).div_ceil(4);
+        let blocks_high = (height as usize).div_ceil(4);
+        if pixels.len() < blocks_wide * blocks_high * block_size {
+            bail!("DDS texture is truncated");
+        }
+
+        let mut img = RgbaImage::new(width, height);
+        for (idx, block) in pixels
+            .chunks_exact(block_size)
+            .take(blocks_wide * blocks_high)
+            .enumerate()
+        {
+            let texels = match fourcc {
+                b"DXT1" => decode_color_block(block, true),
+                b"DXT2" | b"DXT3" => {
+                    let mut texels = decode_color_block(&block[8..], false);
+                    for (i, texel) in texels.iter_mut().enumerate() {
+                        let alpha = (block[i / 2] >> (4 * (i % 2))) & 0xF;
+                        texel[3] = alpha * 17;
+                    }
+                    texels
+                }
+                _ => {
+                    let mut texels = decode_color_block(&block[8..], false);
+                    for (texel, alpha) in texels.iter_mut().zip(decode_alpha_block(block)) {
+                        texel[3] = alpha;
+                    }
+                    texels
+                }
+            };
+
+            let block_x = (idx % blocks_wide) as u32 * 4;
+            let block_y = (idx / blocks_wide) as u32 * 4;
+            for (i, texel) in texels.iter().enumerate() {
+                let x = block_x + i as u32 % 4;
+                let y = block_y + i as u32 / 4;
+                if x < width && y < height {
+                    img.put_pixel(x, y, Rgba(*texel));
+                }
+            }
+        }
+        return Ok(img);
+    }
+
+    let bit_count = LittleEndian::read_u32(&data[88..92]);
+    let masks = [
+        LittleEndian::read_u32(&data[92..96]),
+        LittleEndian::read_u32(&data[96..100]),
+        LittleEndian::read_u32(&data[100..104]),
+        LittleEndian::read_u32(&data[104..108]),
+    ];
+    let pixel_size = match bit_count {
+        8 | 16 | 24 | 32 => bit_count as usize / 8,
+        _ => bail!("Unsupported DDS pixel size: {} bits", bit_count),
+    };
+    if pixels.len() < width as usize * height as usize * pixel_size {
+        bail!("DDS texture is truncated");
+    }
+
+    let has_alpha = flags & (DDPF_ALPHAPIXELS | DDPF_ALPHA) != 0 && masks[3] != 0;
+    let mut img = RgbaImage::new(width, height);
+    for (pixel, bytes) in img.pixels_mut().zip(pixels.chunks_exact(pixel_size)) {
+        let value = bytes
+            .iter()
+            .rev()
+            .fold(0u32, |value, &byte| (value << 8) | byte as u32);
+
+        let alpha = if has_alpha {
+            channel(value, masks[3])
+        } else {
+            255
+        };
+        *pixel = if flags & DDPF_ALPHA != 0 {
+            Rgba([255, 255, 255, alpha])
+        } else if flags & DDPF_LUMINANCE != 0 {
+            let l = channel(value, masks[0]);
+            Rgba([l, l, l, alpha])
+        } else {
+            Rgba([
+                channel(value, masks[0]),
+                channel(value, masks[1]),
+                channel(value, masks[2]),
+                alpha,
+            ])
+        };
+    }
+    Ok(img)
+}
+
+/// Value of the bits of a mask scaled to 8 bits
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let max = mask >> mask.trailing_zeros();
+    let bits = (value & mask) >> mask.trailing_zeros();
+    ((bits as u64 * 255 + max as u64 / 2) / max as u64) as u8
+}
+
+/// RGB of an R5G6B5 color
+fn rgb565(color: u16) -> [u8; 3] {
+    let r = (color >> 11) as u8 & 0x1F;
+    let g = (color >> 5) as u8 & 0x3F;
+    let b = color as u8 & 0x1F;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// Colors of a DXT color block. Only DXT1 blocks have a transparent color,
+/// used when the first color isn't greater than the second.
+fn color_palette(c0: u16, c1: u16, dxt1: bool) -> [[u8; 4]; 4] {
+    let (rgb0, rgb1) = (rgb565(c0), rgb565(c1));
+
+    let mut colors = [[0u8; 4]; 4];
+    for i in 0..3 {
+        let (a, b) = (rgb0[i] as u32, rgb1[i] as u32);
+        colors[0][i] = a as u8;
+        colors[1][i] = b as u8;
+        if c0 > c1 || !dxt1 {
+            colors[2][i] = ((2 * a + b + 1) / 3) as u8;
+            colors[3][i] = ((a + 2 * b + 1) / 3) as u8;
+        } else {
+            colors[2][i] = (a + b).div_ceil(2) as u8;
+        }
+    }
+    colors[0][3] = 255;
+    colors[1][3] = 255;
+    colors[2][3] = 255;
+    colors[3][3] = if c0 > c1 || !dxt1 { 255 } else { 0 };
+    colors
+}
+
+/// Texels of a DXT color block, row by row
+fn decode_color_block(block: &[u8], dxt1: bool) -> [[u8; 4]; 16] {
+    let c0 = LittleEndian::read_u16(&block[0..2]);
+    let c1 = LittleEndian::read_u16(&block[2..4]);
+    let colors = color_palette(c0, c1, dxt1);
+
+    let indices = LittleEndian::read_u32(&block[4..8]);
+    let mut texels = [[0u8; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = colors[(indices >> (2 * i)) as usize & 0x3];
+    }
+    texels
+}
+
+/// Alpha values of a DXT5 alpha block, 8 interpolated values if the first
+/// is greater than the second, else 6 and fully transparent and opaque
+fn alpha_palette(a0: u8, a1: u8) -> [u32; 8] {
+    let (a0, a1) = (a0 as u32, a1 as u32);
+    let mut alphas = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for i in 1..7 {
+            alphas[i + 1] = ((7 - i as u32) * a0 + i as u32 * a1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            alphas[i + 1] = ((5 - i as u32) * a0 + i as u32 * a1) / 5;
+        }
+    }
+    alphas
+}
+
+/// Alpha values of a DXT5 alpha block, row by row
+fn decode_alpha_block(block: &[u8]) -> [u8; 16] {
+    let alphas = alpha_palette(block[0], block[1]);
+    let indices = LittleEndian::read_u48(&block[2..8]);
+    let mut values = [0u8; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = alphas[(indices >> (3 * i)) as usize & 0x7] as u8;
+    }
+    values
+}
+
+/// Encode an image as a DXT1 or DXT5 DDS texture, with mipmaps down to 1x1
+/// if `mipmaps` is set
+pub fn encode(img: &RgbaImage, format: DxtFormat, mipmaps: bool) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let mut levels = vec![img.clone()];
+    let (mut w, mut h) = (width, height);
+    while mipmaps && (w > 1 || h > 1) {
+        w = (w / 2).max(1);
+        h = (h / 2).max(1);
+        levels.push(imageops::resize(img, w, h, FilterType::Triangle));
+    }
+
+    let level_size =
+        |w: u32, h: u32| (w as usize).div_ceil(4) * (h as usize).div_ceil(4) * format.block_size();
+
+    let mut data = vec![0u8; HEADER_SIZE];
+    data[0..4].copy_from_slice(MAGIC);
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_LINEARSIZE;
+    let mut caps = DDSCAPS_TEXTURE;
+    if mipmaps {
+        flags |= DDSD_MIPMAPCOUNT;
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+    LittleEndian::write_u32(&mut data[4..8], 124);
+    LittleEndian::write_u32(&mut data[8..12], flags);
+    LittleEndian::write_u32(&mut data[12..16], height);
+    LittleEndian::write_u32(&mut data[16..20], width);
+    LittleEndian::write_u32(&mut data[20..24], level_size(width, height) as u32);
+    LittleEndian::write_u32(&mut data[28..32], levels.len() as u32);
+    LittleEndian::write_u32(&mut data[76..80], 32);
+    LittleEndian::write_u32(&mut data[80..84], DDPF_FOURCC);
+    data[84..88].copy_from_slice(match format {
+        DxtFormat::Dxt1 => b"DXT1",
+        DxtFormat::Dxt5 => b"DXT5",
+    });
+    LittleEndian::write_u32(&mut data[108..112], caps);
+
+    for level in &levels {
+        let (w, h) = level.dimensions();
+        for block_y in (0..h).step_by(4) {
+            for block_x in (0..w).step_by(4) {
+                // Blocks past the edge repeat the last row and column
+                let mut texels = [[0u8; 4]; 16];
+                for (i, texel) in texels.iter_mut().enumerate() {
+                    let x = (block_x + i as u32 % 4).min(w - 1);
+                    let y = (block_y + i as u32 / 4).min(h - 1);
+                    *texel = level.get_pixel(x, y).0;
+                }
+
+                match format {
+                    DxtFormat::Dxt1 => data.extend_from_slice(&encode_color_block(&texels, true)),
+                    DxtFormat::Dxt5 => {
+                        data.extend_from_slice(&encode_alpha_block(&texels));
+                        data.extend_from_slice(&encode_color_block(&texels, false));
+                    }
+                }
+            }
+        }
+    }
+    data
+}
+
+/// R5G6B5 color closest to an RGB color
+fn to_rgb565(rgb: [f32; 3]) -> u16 {
+    let quantize = |v: f32, max: f32| (v.clamp(0.0, 255.0) * max / 255.0).round() as u16;
+    (quantize(rgb[0], 31.0) << 11) | (quantize(rgb[1], 63.0) << 5) | quantize(rgb[2], 31.0)
+}
+
+fn distance(a: &[u8; 4], b: &[u8; 4]) -> u32 {
+    (0..3)
+        .map(|i| (a[i] as i32 - b[i] as i32).pow(2) as u32)
+        .sum()
+}
+
+/// Encode the colors of 16 texels. DXT1 blocks with texels that are more
+/// than half transparent use the 3-color mode with the transparent color.
+fn encode_color_block(texels: &[[u8; 4]; 16], dxt1: bool) -> [u8; 8] {
+    let transparent = |texel: &[u8; 4]| dxt1 && texel[3] < 128;
+    let opaque: Vec<[f32; 3]> = texels
+        .iter()
+        .filter(|texel| !transparent(texel))
+        .map(|texel| [texel[0] as f32, texel[1] as f32, texel[2] as f32])
+        .collect();
+    let has_transparent = opaque.len() < 16;
+
+    let mut block = [0u8; 8];
+    if opaque.is_empty() {
+        // Both colors black in 3-color mode, every texel transparent
+        LittleEndian::write_u32(&mut block[4..8], u32::MAX);
+        return block;
+    }
+
+    let count = opaque.len() as f32;
+    let mut mean = [0f32; 3];
+    for color in &opaque {
+        for i in 0..3 {
+            mean[i] += color[i] / count;
+        }
+    }
+    let mut covariance = [[0f32; 3]; 3];
+    for color in &opaque {
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] += (color[i] - mean[i]) * (color[j] - mean[j]);
+            }
+        }
+    }
+
+    // Principal axis by power iteration, starting from the luminance axis
+    let mut axis = [0.299f32, 0.587, 0.114];
+    for _ in 0..8 {
+        let mut next = [0f32; 3];
+        for i in 0..3 {
+            next[i] = (0..3).map(|j| covariance[i][j] * axis[j]).sum();
+        }
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            break;
+        }
+        axis = [next[0] / length, next[1] / length, next[2] / length];
+    }
+
+    let project = |color: &[f32; 3]| (0..3).map(|i| (color[i] - mean[i]) * axis[i]).sum::<f32>();
+    let (mut min, mut max) = (opaque[0], opaque[0]);
+    for color in &opaque {
+        if project(color) < project(&min) {
+            min = *color;
+        }
+        if project(color) > project(&max) {
+            max = *color;
+        }
+    }
+
+    let (mut c0, mut c1) = (to_rgb565(max), to_rgb565(min));
+    // The 4-color mode needs the first color to be greater, the 3-color mode
+    // the second
+    if (c0 < c1) != has_transparent {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+    let colors = color_palette(c0, c1, dxt1);
+    let choices = if has_transparent || (dxt1 && c0 == c1) {
+        3
+    } else {
+        4
+    };
+
+    let mut indices = 0u32;
+    for (i, texel) in texels.iter().enumerate() {
+        let index = if transparent(texel) {
+            3
+        } else {
+            (0..choices)
+                .min_by_key(|&c| distance(texel, &colors[c]))
+                .unwrap_or(0)
+        };
+        indices |= (index as u32) << (2 * i);
+    }
+
+    LittleEndian::write_u16(&mut block[0..2], c0);
+    LittleEndian::write_u16(&mut block[2..4], c1);
+    LittleEndian::write_u32(&mut block[4..8], indices);
+    block
+}
+
+/// Encode the alpha of 16 texels, with the 8-value or the 6-value palette,
+/// whichever is closer
+fn encode_alpha_block(texels: &[[u8; 4]; 16]) -> [u8; 8] {
+    let alphas: Vec<u8> = texels.iter().map(|texel| texel[3]).collect();
+    let min = alphas.iter().copied().min().unwrap_or(0);
+    let max = alphas.iter().copied().max().unwrap_or(255);
+
+    // The 6-value palette spans the alphas between fully transparent and
+    // fully opaque, which it has exactly
+    let inner = alphas.iter().filter(|&&a| a != 0 && a != 255);
+    let inner_min = inner.clone().copied().min().unwrap_or(min);
+    let inner_max = inner.copied().max().unwrap_or(max);
+
+    let mut best: Option<(u32, [u8; 8])> = None;
+    for &(a0, a1) in &[(max, min), (inner_min, inner_max)] {
+        let palette = alpha_palette(a0, a1);
+        let mut error = 0;
+        let mut indices = 0u64;
+        for (i, &alpha) in alphas.iter().enumerate() {
+            let (index, value) = palette
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, &value)| (value as i32 - alpha as i32).abs())
+                .unwrap_or((0, &0));
+            error += (*value as i32 - alpha as i32).pow(2) as u32;
+            indices |= (index as u64) << (3 * i);
+        }
+
+        if best.is_none_or(|(best_error, _)| error < best_error) {
+            let mut block = [0u8; 8];
+            block[0] = a0;
+            block[1] = a1;
+            LittleEndian::write_u48(&mut block[2..8], indices);
+            best = Some((error, block));
+        }
+    }
+    best.map(|(_, block)| block).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(width: u32, height: u32, flags: u32, fourcc: &[u8; 4], masks: [u32; 5]) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_SIZE];
+        data[0..4].copy_from_slice(MAGIC);
+        LittleEndian::write_u32(&mut data[4..8], 124);
+        LittleEndian::write_u32(&mut data[12..16], height);
+        LittleEndian::write_u32(&mut data[16..20], width);
+        LittleEndian::write_u32(&mut data[76..80], 32);
+        LittleEndian::write_u32(&mut data[80..84], flags);
+        data[84..88].copy_from_slice(fourcc);
+        for (i, mask) in masks.iter().enumerate() {
+            LittleEndian::write_u32(&mut data[88 + i * 4..92 + i * 4], *mask);
+        }
+        data
+    }
+
+    #[test]
+    fn test_decode_uncompressed() {
+        // A4R4G4B4
+        let mut data = header(2, 1, 0x41, b"\0\0\0\0", [16, 0xF00, 0xF0, 0xF, 0xF000]);
+        data.extend_from_slice(&[0x0F, 0xF0, 0x80, 0x0F]);
+        let img = decode(&data).unwrap();
+        assert_eq!(img.get_pixel(0, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(img.get_pixel(1, 0), &Rgba([255, 136, 0, 0]));
+
+        // X1R5G5B5 has no alpha even though the mask bit is set
+        let mut data = header(1, 1, 0x40, b"\0\0\0\0", [16, 0x7C00, 0x3E0, 0x1F, 0x8000]);
+        data.extend_from_slice(&[0x1F, 0x7C]);
+        assert_eq!(
+            decode(&data).unwrap().get_pixel(0, 0),
+            &Rgba([255, 0, 255, 255])
+        );
+
+        // A8R8G8B8
+        let mut data = header(
+            1,
+            1,
+            0x41,
+            b"\0\0\0\0",
+            [32, 0xFF0000, 0xFF00, 0xFF, 0xFF000000],
+        );
+        data.extend_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(decode(&data).unwrap().get_pixel(0, 0), &Rgba([3, 2, 1, 4]));
+        assert!(decode(&data[..HEADER_SIZE + 2]).is_err());
+    }
+
+    #[test]
+    fn test_decode_dxt() {
+        // Blue and red with the transparent color on the second row
+        let mut data = header(2, 2, DDPF_FOURCC, b"DXT1", [0; 5]);
+        data.extend_from_slice(&[0x1F, 0x00, 0x00, 0xF8, 0x04, 0x0F, 0, 0]);
+        let img = decode(&data).unwrap();
+        assert_eq!(img.dimensions(), (2, 2));
+        assert_eq!(img.get_pixel(0, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(img.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(img.get_pixel(0, 1)[3], 0);
+
+        let mut data = header(4, 4, DDPF_FOURCC, b"DXT5", [0; 5]);
+        data.extend_from_slice(&[255, 0, 0x01, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0xFF, 0xFF, 0, 0, 0, 0, 0, 0]);
+        let img = decode(&data).unwrap();
+        assert_eq!(img.get_pixel(0, 0), &Rgba([255, 255, 255, 0]));
+        assert_eq!(img.get_pixel(1, 0), &Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_encode() {
+        // A gradient with a transparent corner, not a multiple of the block size
+        let img = RgbaImage::from_fn(10, 6, |x, y| {
+            let alpha = if x < 2 && y < 2 { 0 } else { 255 };
+            Rgba([(x + y) as u8 * 16, 255 - (x + y) as u8 * 8, 200, alpha])
+        });
+
+        let data = encode(&img, DxtFormat::Dxt1, true);
+        assert_eq!(&data[84..88], b"DXT1");
+        // 10x6, 5x3, 2x1 and 1x1
+        assert_eq!(LittleEndian::read_u32(&data[28..32]), 4);
+        assert_eq!(data.len(), HEADER_SIZE + (6 + 2 + 1 + 1) * 8);
+
+        let decoded = decode(&data).unwrap();
+        assert_eq!(decoded.dimensions(), (10, 6));
+        for (x, y, pixel) in img.enumerate_pixels() {
+            let texel = decoded.get_pixel(x, y);
+            assert_eq!(texel[3], pixel[3]);
+            if pixel[3] == 255 {
+                assert!((0..3).all(|i| (texel[i] as i32 - pixel[i] as i32).abs() <= 16));
+            }
+        }
+
+        let img = RgbaImage::from_fn(4, 4, |x, y| Rgba([255, 0, 0, (x * 16 + y * 64) as u8]));
+        let data = encode(&img, DxtFormat::Dxt5, false);
+        assert_eq!(data.len(), HEADER_SIZE + 16);
+        let decoded = decode(&data).unwrap();
+        // Within half a step of the 8 interpolated alphas
+        for (x, y, pixel) in img.enumerate_pixels() {
+            let texel = decoded.get_pixel(x, y);
+            assert_eq!(&texel.0[0..3], &[255, 0, 0]);
+            assert!((texel[3] as i32 - pixel[3] as i32).abs() <= 17);
+        }
+    }
+}
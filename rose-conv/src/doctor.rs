@@ -0,0 +1,274 @@
+//! Health check of a client
+//!
+//! One report of the checks a broken install usually needs: the VFS indexes
+//! parse, the volumes they list can be opened and hold every file, every STB,
+//! STL and ZSC parses and the references between the files are valid. The
+//! files are read from the `3DDATA` directory of a client directory if it has
+//! one and from its VFS index otherwise.
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use roselib::files::{IDX, STB, STL, ZSC};
+use roselib::io::RoseFile;
+
+use crate::client::Client;
+use crate::validate::{self, Check};
+use crate::vfs;
+
+/// Result of a check
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Section {
+    pub name: String,
+    /// Number of things checked, such as files or volumes
+    pub checked: usize,
+    pub problems: Vec<String>,
+}
+
+impl Section {
+    fn new(name: &str) -> Section {
+        Section {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub sections: Vec<Section>,
+}
+
+impl HealthReport {
+    /// Number of problems of every section
+    pub fn problems(&self) -> usize {
+        self.sections.iter().map(|s| s.problems.len()).sum()
+    }
+}
+
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for section in &self.sections {
+            let status = if section.problems.is_empty() {
+                "ok"
+            } else {
+                "!!"
+            };
+            write!(
+                f,
+                "[{}] {}: {} checked",
+                status, section.name, section.checked
+            )?;
+            if section.problems.is_empty() {
+                writeln!(f)?;
+            } else {
+                writeln!(f, ", {} problems", section.problems.len())?;
+            }
+            for problem in &section.problems {
+                writeln!(f, "     {}", problem)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run every check on a client directory or VFS index
+pub fn diagnose(path: &Path) -> HealthReport {
+    let mut report = HealthReport::default();
+
+    let (section, indexes) = check_indexes(&index_paths(path));
+    report.sections.push(section);
+    report.sections.push(check_volumes(&indexes));
+
+    let client = if path.is_dir() && vfs::resolve_client_path(path, "3DDATA").is_some() {
+        Client::from_dir(path)
+    } else {
+        match indexes.first() {
+            Some((idx_path, _)) => Client::from_vfs(idx_path),
+            None => {
+                let mut section = Section::new("Client");
+                section.problems.push(format!(
+                    "No 3DDATA directory or VFS index in {}",
+                    path.display()
+                ));
+                report.sections.push(section);
+                return report;
+            }
+        }
+    };
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            let mut section = Section::new("Client");
+            section.problems.push(e.to_string());
+            report.sections.push(section);
+            return report;
+        }
+    };
+
+    let (section, unreadable) = check_files(&client);
+    report.sections.push(section);
+    report.sections.push(check_references(&client, &unreadable));
+    report
+}
+
+/// The path itself if it's a VFS index, the VFS indexes in it if it's a
+/// directory
+fn index_paths(path: &Path) -> Vec<PathBuf> {
+    let is_idx = |p: &Path| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("idx"));
+    if !path.is_dir() {
+        return if is_idx(path) {
+            vec![path.to_path_buf()]
+        } else {
+            Vec::new()
+        };
+    }
+    let mut paths: Vec<PathBuf> = match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && is_idx(p))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    paths
+}
+
+/// Parse VFS indexes, returning the ones that parsed
+pub fn check_indexes(paths: &[PathBuf]) -> (Section, Vec<(PathBuf, IDX)>) {
+    let mut section = Section::new("VFS indexes");
+    let mut indexes = Vec::new();
+    for path in paths {
+        section.checked += 1;
+        match IDX::from_path(path) {
+            Ok(idx) => indexes.push((path.clone(), idx)),
+            Err(e) => section.problems.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    (section, indexes)
+}
+
+/// Check that the volumes of VFS indexes exist next to them and are large
+/// enough for the files they hold
+pub fn check_volumes(indexes: &[(PathBuf, IDX)]) -> Section {
+    let mut section = Section::new("VFS volumes");
+    for (idx_path, idx) in indexes {
+        let idx_dir = idx_path.parent().unwrap_or_else(|| Path::new(""));
+        for volume in &idx.file_systems {
+            section.checked += 1;
+            let path = idx_dir.join(&volume.filename);
+            let size = match fs::metadata(&path) {
+                Ok(metadata) => metadata.len(),
+                Err(e) => {
+                    section.problems.push(format!("{}: {}", path.display(), e));
+                    continue;
+                }
+            };
+            let end = volume
+                .files
+                .iter()
+                .filter(|f| !f.is_deleted)
+                .map(|f| f.offset.max(0) as u64 + f.size.max(0) as u64)
+                .max()
+                .unwrap_or_default();
+            if end > size {
+                section.problems.push(format!(
+                    "{}: {} bytes but its files end at {} bytes",
+                    path.display(),
+                    size,
+                    end
+                ));
+            }
+        }
+    }
+    section
+}
+
+/// Parse every STB, STL and ZSC of a client, returning the client paths of
+/// the files that didn't parse
+pub fn check_files(client: &Client) -> (Section, HashSet<String>) {
+    let mut section = Section::new("STB, STL and ZSC files");
+    let mut unreadable = HashSet::new();
+    for file in client.files() {
+        let res = match file.extension().as_str() {
+            "stb" => client.read_file::<STB>(&file.path).map(|_| ()),
+            "stl" => client.read_file::<STL>(&file.path).map(|_| ()),
+            "zsc" => client.read_file::<ZSC>(&file.path).map(|_| ()),
+            _ => continue,
+        };
+        section.checked += 1;
+        if let Err(e) = res {
+            section.problems.push(format!("{}: {}", file.path, e));
+            unreadable.insert(file.path.clone());
+        }
+    }
+    (section, unreadable)
+}
+
+/// Validate the references between the files of a client, without the
+/// files already reported as unreadable
+pub fn check_references(client: &Client, unreadable: &HashSet<String>) -> Section {
+    let mut section = Section::new("References");
+    section.checked = client.files().count();
+    section.problems = validate::validate(client)
+        .into_iter()
+        .filter(|i| !(i.check == Check::Unreadable && unreadable.contains(&i.file)))
+        .map(|i| i.to_string())
+        .collect();
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::idx::{VfsFileMetadata, VfsMetadata};
+
+    #[test]
+    fn test_diagnose() {
+        let dir = std::env::temp_dir().join("rose-conv-test-doctor");
+        fs::create_dir_all(dir.join("3DDATA/STB")).unwrap();
+        let mut stb = STB::new();
+        stb.headers = vec![String::from("ID"), String::from("NAME")];
+        stb.data = vec![vec![String::from("1"), String::from("Junon")]];
+        stb.write_to_path(&dir.join("3DDATA/STB/LIST.STB")).unwrap();
+        fs::write(dir.join("3DDATA/STB/BROKEN.ZSC"), "zsc").unwrap();
+        fs::write(dir.join("data.vfs"), "aaa").unwrap();
+
+        let mut idx = IDX::new();
+        let mut volume = VfsMetadata::new();
+        volume.filename = PathBuf::from("data.vfs");
+        let mut file = VfsFileMetadata::new();
+        file.filepath = PathBuf::from("A.TXT");
+        file.size = 5;
+        volume.files.push(file);
+        idx.file_systems.push(volume);
+        idx.write_to_path(&dir.join("data.idx")).unwrap();
+
+        let report = diagnose(&dir);
+        let sections: Vec<(&str, usize, usize)> = report
+            .sections
+            .iter()
+            .map(|s| (s.name.as_str(), s.checked, s.problems.len()))
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                ("VFS indexes", 1, 0),
+                ("VFS volumes", 1, 1),
+                ("STB, STL and ZSC files", 2, 1),
+                ("References", 4, 0)
+            ]
+        );
+        assert!(report.sections[2].problems[0].starts_with("3DDATA/STB/BROKEN.ZSC: "));
+        assert_eq!(report.problems(), 2);
+        assert!(report
+            .to_string()
+            .starts_with("[ok] VFS indexes: 1 checked\n[!!] VFS volumes: 1 checked, 1 problems\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,327 @@
+//! Encoding audit of ROSE files
+//!
+//! Files are read with the `x-user-defined` encoding, which maps every byte to
+//! a character and back, so the raw bytes of each string can be classified.
+//! ASCII strings are the same in every encoding and only count towards the
+//! total.
+use std::fmt;
+use std::path::Path;
+use std::str;
+
+use encoding_rs::{EUC_KR, WINDOWS_1252, X_USER_DEFINED};
+use failure::{bail, Error};
+use serde::Serialize;
+
+use roselib::files::{STB, STL, ZMD};
+use roselib::io::{set_string_encoding, string_encoding, RoseFile, StringEncoding};
+
+use crate::stl_csv;
+
+/// Encoding of a string's bytes
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StringKind {
+    Ascii,
+    Utf8,
+    EucKr,
+    /// Neither valid UTF-8 nor EUC-KR
+    Invalid,
+}
+
+impl StringKind {
+    pub fn classify(bytes: &[u8]) -> StringKind {
+        if bytes.is_ascii() {
+            StringKind::Ascii
+        } else if str::from_utf8(bytes).is_ok() {
+            StringKind::Utf8
+        } else if !EUC_KR.decode_without_bom_handling(bytes).1 {
+            StringKind::EucKr
+        } else {
+            StringKind::Invalid
+        }
+    }
+}
+
+/// Encoding a file appears to use
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectedEncoding {
+    Ascii,
+    Utf8,
+    EucKr,
+    /// Both UTF-8 and EUC-KR strings
+    Mixed,
+    /// Only strings that are neither UTF-8 nor EUC-KR
+    Unknown,
+}
+
+impl fmt::Display for DetectedEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DetectedEncoding::Ascii => "ascii",
+            DetectedEncoding::Utf8 => "utf-8",
+            DetectedEncoding::EucKr => "euc-kr",
+            DetectedEncoding::Mixed => "mixed",
+            DetectedEncoding::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MojibakeKind {
+    /// UTF-8 or EUC-KR text that was decoded as Windows-1252 and saved as
+    /// UTF-8, e.g. `ëª¬ìŠ¤í„°`
+    DoubleEncoded,
+    /// UTF-8 text with replacement characters left by a lossy conversion
+    Replacement,
+    /// Neither valid UTF-8 nor EUC-KR
+    Invalid,
+}
+
+/// String that looks corrupted
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Mojibake {
+    /// Where the string is in the file, e.g. `row 3 col 2`
+    pub location: String,
+    pub kind: MojibakeKind,
+    /// String decoded as well as possible
+    pub text: String,
+}
+
+impl fmt::Display for Mojibake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            MojibakeKind::DoubleEncoded => "double-encoded",
+            MojibakeKind::Replacement => "replacement characters",
+            MojibakeKind::Invalid => "invalid",
+        };
+        writeln!(f, "{}: {} {:?}", self.location, kind, self.text)
+    }
+}
+
+/// Encodings of the strings of a file
+#[derive(Debug, PartialEq, Serialize)]
+pub struct EncodingReport {
+    pub encoding: DetectedEncoding,
+    pub strings: usize,
+    pub utf8: usize,
+    pub euc_kr: usize,
+    pub invalid: usize,
+    pub mojibake: Vec<Mojibake>,
+}
+
+impl EncodingReport {
+    /// Classify the raw strings of a file, stopping after `samples` non-ASCII
+    /// strings unless it's 0
+    pub fn new<I>(strings: I, samples: usize) -> EncodingReport
+    where
+        I: IntoIterator<Item = (String, Vec<u8>)>,
+    {
+        let mut report = EncodingReport {
+            encoding: DetectedEncoding::Ascii,
+            strings: 0,
+            utf8: 0,
+            euc_kr: 0,
+            invalid: 0,
+            mojibake: Vec::new(),
+        };
+
+        for (location, bytes) in strings {
+            if samples > 0 && report.utf8 + report.euc_kr + report.invalid >= samples {
+                break;
+            }
+            report.strings += 1;
+
+            let (kind, text) = match StringKind::classify(&bytes) {
+                StringKind::Ascii => continue,
+                StringKind::Utf8 => {
+                    report.utf8 += 1;
+                    let text = String::from_utf8(bytes).unwrap_or_default();
+                    if text.contains('\u{fffd}') {
+                        (MojibakeKind::Replacement, text)
+                    } else if let Some(original) = double_encoded(&text) {
+                        (MojibakeKind::DoubleEncoded, original)
+                    } else {
+                        continue;
+                    }
+                }
+                StringKind::EucKr => {
+                    report.euc_kr += 1;
+                    continue;
+                }
+                StringKind::Invalid => {
+                    report.invalid += 1;
+                    let (text, _malformed) = EUC_KR.decode_without_bom_handling(&bytes);
+                    (MojibakeKind::Invalid, text.into_owned())
+                }
+            };
+            report.mojibake.push(Mojibake {
+                location,
+                kind,
+                text,
+            });
+        }
+
+        report.encoding = match (report.utf8 > 0, report.euc_kr > 0) {
+            (true, true) => DetectedEncoding::Mixed,
+            (true, false) => DetectedEncoding::Utf8,
+            (false, true) => DetectedEncoding::EucKr,
+            (false, false) if report.invalid > 0 => DetectedEncoding::Unknown,
+            (false, false) => DetectedEncoding::Ascii,
+        };
+        report
+    }
+
+    /// Whether the file mixes encodings or has corrupted strings
+    pub fn has_issues(&self) -> bool {
+        self.encoding == DetectedEncoding::Mixed || !self.mojibake.is_empty()
+    }
+}
+
+impl fmt::Display for EncodingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ({} strings, {} utf-8, {} euc-kr, {} invalid)",
+            self.encoding, self.strings, self.utf8, self.euc_kr, self.invalid
+        )?;
+        for mojibake in &self.mojibake {
+            write!(f, "{}", mojibake)?;
+        }
+        Ok(())
+    }
+}
+
+/// Original text of a UTF-8 string that was decoded from UTF-8 or EUC-KR as
+/// Windows-1252
+fn double_encoded(text: &str) -> Option<String> {
+    let (bytes, _encoding, unmappable) = WINDOWS_1252.encode(text);
+    if unmappable || bytes.is_ascii() {
+        return None;
+    }
+
+    if let Ok(original) = str::from_utf8(&bytes) {
+        return Some(original.to_string());
+    }
+
+    // Latin-1 text rarely decodes as EUC-KR, require Hangul to be sure
+    let (original, malformed) = EUC_KR.decode_without_bom_handling(&bytes);
+    if !malformed
+        && original
+            .chars()
+            .any(|c| ('\u{ac00}'..='\u{d7a3}').contains(&c))
+    {
+        return Some(original.into_owned());
+    }
+    None
+}
+
+/// Raw bytes of every string of an STB, STL or ZMD file with their location
+pub fn file_strings(path: &Path) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let encoding = string_encoding();
+    set_string_encoding(StringEncoding::Fixed(X_USER_DEFINED));
+    let res = read_strings(path);
+    set_string_encoding(encoding);
+    res
+}
+
+fn read_strings(path: &Path) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let mut strings = Vec::new();
+    match extension.as_str() {
+        "stb" => {
+            let stb = STB::from_path(path)?;
+            for (col, header) in stb.headers.iter().enumerate() {
+                strings.push((format!("header {}", col), raw_bytes(header)));
+            }
+            strings.push((String::from("root"), raw_bytes(&stb.root_row)));
+            for (row, values) in stb.data.iter().enumerate() {
+                for (col, value) in values.iter().enumerate() {
+                    strings.push((format!("row {} col {}", row, col), raw_bytes(value)));
+                }
+            }
+        }
+        "stl" => {
+            let stl = STL::from_path(path)?;
+            let columns = stl_csv::columns(&stl.format);
+            for key in &stl.keys {
+                strings.push((format!("key {}", key.id), raw_bytes(&key.name)));
+            }
+            for table in &stl.language_tables {
+                for (row, values) in table.rows.iter().enumerate() {
+                    let key_id = stl.keys.get(row).map(|key| key.id).unwrap_or_default();
+                    for (column, value) in columns.iter().zip(stl_csv::row_values(values)) {
+                        let location =
+                            format!("key {} {} {}", key_id, table.language.code(), column);
+                        strings.push((location, raw_bytes(value)));
+                    }
+                }
+            }
+        }
+        "zmd" => {
+            let zmd = ZMD::from_path(path)?;
+            for (idx, bone) in zmd.bones.iter().enumerate() {
+                strings.push((format!("bone {}", idx), raw_bytes(&bone.name)));
+            }
+            for (idx, bone) in zmd.dummy_bones.iter().enumerate() {
+                strings.push((format!("dummy {}", idx), raw_bytes(&bone.name)));
+            }
+        }
+        _ => bail!("Encoding reports are only supported for STB, STL and ZMD files"),
+    }
+    Ok(strings)
+}
+
+/// Bytes of a string read as `x-user-defined`
+fn raw_bytes(s: &str) -> Vec<u8> {
+    X_USER_DEFINED.encode(s).0.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&[u8]]) -> Vec<(String, Vec<u8>)> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(idx, bytes)| (format!("row {}", idx), bytes.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_encoding_report() {
+        let (euc_kr, _, _) = EUC_KR.encode("몬스터");
+        let report = EncodingReport::new(strings(&[b"ITEM", &euc_kr]), 0);
+        assert_eq!(report.encoding, DetectedEncoding::EucKr);
+        assert!(!report.has_issues());
+
+        let double = WINDOWS_1252
+            .decode_without_bom_handling("몬스터".as_bytes())
+            .0;
+        let values = strings(&[
+            "몬스터".as_bytes(),
+            &euc_kr,
+            double.as_bytes(),
+            &[0xff, 0xff],
+        ]);
+        let report = EncodingReport::new(values, 0);
+        assert_eq!(report.encoding, DetectedEncoding::Mixed);
+        assert_eq!((report.utf8, report.euc_kr, report.invalid), (2, 1, 1));
+        assert_eq!(
+            report.mojibake[0].to_string(),
+            "row 2: double-encoded \"몬스터\"\n"
+        );
+        assert_eq!(report.mojibake[1].kind, MojibakeKind::Invalid);
+
+        let values = strings(&["몬스터".as_bytes(), &euc_kr, &euc_kr]);
+        assert_eq!(EncodingReport::new(values, 1).strings, 1);
+    }
+}
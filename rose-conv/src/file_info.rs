@@ -0,0 +1,352 @@
+//! Summaries of ROSE files
+//!
+//! The headline numbers of a file, such as the vertices of a mesh or the
+//! frames of a motion, read from the parsed file without serializing it.
+use std::fmt;
+use std::fs::{self, File};
+use std::path::Path;
+
+use failure::{bail, Error};
+
+use roselib::files::zms::VertexFormat;
+use roselib::files::{AIP, HIM, IDX, IFO, LIT, QSD, STB, STL, TIL, TSI, ZMD, ZMO, ZMS, ZON, ZSC};
+use roselib::io::{RoseFile, RoseReader};
+
+/// Named values describing a file, in display order
+#[derive(Debug, Default, PartialEq)]
+pub struct FileInfo {
+    pub rose_type: String,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl FileInfo {
+    pub fn new(rose_type: &str) -> FileInfo {
+        FileInfo {
+            rose_type: rose_type.to_uppercase(),
+            fields: Vec::new(),
+        }
+    }
+
+    fn field<T: fmt::Display>(mut self, name: &'static str, value: T) -> FileInfo {
+        self.fields.push((name, value.to_string()));
+        self
+    }
+
+    /// Value of a field by name
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl fmt::Display for FileInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.fields.iter().map(|(name, _)| name.len()).max();
+        let width = width.unwrap_or_default().max("type".len());
+        writeln!(f, "{:width$}  {}", "type", self.rose_type, width = width)?;
+        for (name, value) in &self.fields {
+            writeln!(f, "{:width$}  {}", name, value, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read a file of a ROSE type and summarize it
+pub fn read(path: &Path, rose_type: &str) -> Result<FileInfo, Error> {
+    let size = fs::metadata(path)?.len();
+    let info = match rose_type {
+        "aip" => aip(&AIP::from_path(path)?),
+        "him" => him(&HIM::from_path(path)?),
+        "idx" => idx(&IDX::from_path(path)?),
+        "ifo" => ifo(&IFO::from_path(path)?),
+        "lit" => lit(&LIT::from_path(path)?),
+        "qsd" => qsd(&QSD::from_path(path)?),
+        "stb" => stb(&STB::from_path(path)?),
+        "stl" => stl(&STL::from_path(path)?),
+        "til" => til(&TIL::from_path(path)?),
+        "tsi" => tsi(&TSI::from_path(path)?),
+        "zmd" => zmd(&ZMD::from_path(path)?),
+        "zmo" => zmo(&ZMO::from_path(path)?),
+        "zms" => zms(&ZMS::from_path(path)?),
+        "zon" => zon(&ZON::from_path(path)?),
+        "zsc" => zsc(&ZSC::from_path(path)?),
+        "wstb" => {
+            let mut reader = RoseReader::new(File::open(path)?);
+            reader.set_wide_strings(true);
+            let mut table: STB = RoseFile::new();
+            let res = table.read(&mut reader);
+            // The option is shared by every reader on this thread
+            reader.set_wide_strings(false);
+            res?;
+            FileInfo {
+                rose_type: String::from("WSTB"),
+                ..stb(&table)
+            }
+        }
+        _ => bail!("Unsupported file type: {}", rose_type),
+    };
+    Ok(info.field("file size", format!("{} bytes", size)))
+}
+
+pub fn aip(aip: &AIP) -> FileInfo {
+    FileInfo::new("aip")
+        .field("title", &aip.title)
+        .field("idle interval", format!("{}s", aip.idle_interval))
+        .field("damage chance", format!("{}%", aip.damage_chance))
+        .field("triggers", aip.triggers.len())
+        .field(
+            "events",
+            aip.triggers.iter().map(|t| t.events.len()).sum::<usize>(),
+        )
+        .field("conditions", aip.conditions().count())
+        .field("actions", aip.actions().count())
+}
+
+pub fn him(him: &HIM) -> FileInfo {
+    FileInfo::new("him")
+        .field("size", format!("{}x{}", him.width, him.length))
+        .field("grid count", him.grid_count)
+        .field("scale", him.scale)
+        .field("min height", him.min_height)
+        .field("max height", him.max_height)
+}
+
+pub fn idx(idx: &IDX) -> FileInfo {
+    let files = || idx.file_systems.iter().flat_map(|fs| fs.files.iter());
+    FileInfo::new("idx")
+        .field(
+            "version",
+            format!("{} (base {})", idx.current_version, idx.base_version),
+        )
+        .field("file systems", idx.file_systems.len())
+        .field("files", files().filter(|f| !f.is_deleted).count())
+        .field("deleted files", files().filter(|f| f.is_deleted).count())
+        .field(
+            "data size",
+            format!(
+                "{} bytes",
+                files()
+                    .filter(|f| !f.is_deleted)
+                    .map(|f| f.size.max(0) as u64)
+                    .sum::<u64>()
+            ),
+        )
+}
+
+pub fn ifo(ifo: &IFO) -> FileInfo {
+    FileInfo::new("ifo")
+        .field("map", format!("{},{}", ifo.map_pos.x, ifo.map_pos.y))
+        .field("objects", ifo.objects.len())
+        .field("buildings", ifo.buildings.len())
+        .field("npcs", ifo.npcs.len())
+        .field("monster spawns", ifo.monster_spawns.len())
+        .field("warps", ifo.warps.len())
+        .field("events", ifo.events.len())
+        .field("sounds", ifo.sounds.len())
+        .field("effects", ifo.effects.len())
+        .field("animations", ifo.animations.len())
+        .field("waters", ifo.waters.len())
+        .field("oceans", ifo.oceans.len())
+        .field("collision objects", ifo.collision_objects.len())
+}
+
+pub fn lit(lit: &LIT) -> FileInfo {
+    FileInfo::new("lit")
+        .field("objects", lit.objects.len())
+        .field(
+            "parts",
+            lit.objects.iter().map(|o| o.parts.len()).sum::<usize>(),
+        )
+        .field("lightmaps", lit.filenames.len())
+}
+
+pub fn qsd(qsd: &QSD) -> FileInfo {
+    FileInfo::new("qsd")
+        .field("name", &qsd.name)
+        .field("version", qsd.version)
+        .field("groups", qsd.groups.len())
+        .field("triggers", qsd.triggers().count())
+        .field(
+            "conditions",
+            qsd.triggers().map(|t| t.conditions.len()).sum::<usize>(),
+        )
+        .field(
+            "rewards",
+            qsd.triggers().map(|t| t.rewards.len()).sum::<usize>(),
+        )
+}
+
+pub fn stb(stb: &STB) -> FileInfo {
+    FileInfo::new("stb")
+        .field("version", &stb.identifier)
+        .field("rows", stb.rows())
+        .field("columns", stb.cols())
+}
+
+pub fn stl(stl: &STL) -> FileInfo {
+    let languages: Vec<String> = stl
+        .language_tables
+        .iter()
+        .map(|table| table.language.to_string())
+        .collect();
+    FileInfo::new("stl")
+        .field("format", stl.format)
+        .field("keys", stl.keys.len())
+        .field(
+            "languages",
+            format!("{} ({})", languages.len(), languages.join(", ")),
+        )
+}
+
+pub fn til(til: &TIL) -> FileInfo {
+    FileInfo::new("til").field("size", format!("{}x{}", til.width, til.height))
+}
+
+pub fn tsi(tsi: &TSI) -> FileInfo {
+    FileInfo::new("tsi")
+        .field("sprite sheets", tsi.sprite_sheets.len())
+        .field(
+            "sprites",
+            tsi.sprite_sheets
+                .iter()
+                .map(|sheet| sheet.sprites.len())
+                .sum::<usize>(),
+        )
+}
+
+pub fn zmd(zmd: &ZMD) -> FileInfo {
+    FileInfo::new("zmd")
+        .field("bones", zmd.bones.len())
+        .field("dummy bones", zmd.dummy_bones.len())
+}
+
+pub fn zmo(zmo: &ZMO) -> FileInfo {
+    // Channel counts by type, in order of first appearance
+    let mut types: Vec<(String, usize)> = Vec::new();
+    for channel in &zmo.channels {
+        let name = format!("{:?}", channel.typ).to_lowercase();
+        match types.iter_mut().find(|(t, _)| *t == name) {
+            Some((_, count)) => *count += 1,
+            None => types.push((name, 1)),
+        }
+    }
+    let types: Vec<String> = types
+        .iter()
+        .map(|(name, count)| format!("{} {}", count, name))
+        .collect();
+
+    let duration = if zmo.fps > 0 {
+        format!("{:.2}s", zmo.frames as f32 / zmo.fps as f32)
+    } else {
+        String::from("-")
+    };
+    FileInfo::new("zmo")
+        .field("version", &zmo.identifier)
+        .field("frames", zmo.frames)
+        .field("fps", zmo.fps)
+        .field("duration", duration)
+        .field(
+            "channels",
+            format!("{} ({})", zmo.channels.len(), types.join(", ")),
+        )
+        .field(
+            "frame events",
+            zmo.frame_events.iter().filter(|&&e| e != 0).count(),
+        )
+}
+
+pub fn zms(zms: &ZMS) -> FileInfo {
+    let flags = [
+        (VertexFormat::Position as i32, "position"),
+        (VertexFormat::Normal as i32, "normal"),
+        (VertexFormat::Color as i32, "color"),
+        (VertexFormat::BoneWeight as i32, "bone weight"),
+        (VertexFormat::BoneIndex as i32, "bone index"),
+        (VertexFormat::Tangent as i32, "tangent"),
+        (VertexFormat::UV1 as i32, "uv1"),
+        (VertexFormat::UV2 as i32, "uv2"),
+        (VertexFormat::UV3 as i32, "uv3"),
+        (VertexFormat::UV4 as i32, "uv4"),
+    ];
+    let format: Vec<&str> = flags
+        .iter()
+        .filter(|(flag, _)| zms.format & flag != 0)
+        .map(|&(_, name)| name)
+        .collect();
+    let b = &zms.bounding_box;
+    FileInfo::new("zms")
+        .field("version", &zms.identifier)
+        .field(
+            "format",
+            format!("{:#x} ({})", zms.format, format.join(", ")),
+        )
+        .field("vertices", zms.vertices.len())
+        .field("faces", zms.indices.len())
+        .field("bones", zms.bones.len())
+        .field("material groups", zms.materials.len())
+        .field("strips", zms.strips.len())
+        .field(
+            "bounds",
+            format!(
+                "({}, {}, {}) to ({}, {}, {})",
+                b.min.x, b.min.y, b.min.z, b.max.x, b.max.y, b.max.z
+            ),
+        )
+}
+
+pub fn zon(zon: &ZON) -> FileInfo {
+    FileInfo::new("zon")
+        .field("name", &zon.name)
+        .field("zone type", format!("{:?}", zon.zone_type))
+        .field("size", format!("{}x{}", zon.width, zon.height))
+        .field("grid", format!("{} x {}", zon.grid_count, zon.grid_size))
+        .field("event points", zon.event_points.len())
+        .field("textures", zon.textures.len())
+        .field("tiles", zon.tiles.len())
+}
+
+pub fn zsc(zsc: &ZSC) -> FileInfo {
+    FileInfo::new("zsc")
+        .field("meshes", zsc.meshes.len())
+        .field("materials", zsc.materials.len())
+        .field("effects", zsc.effects.len())
+        .field("objects", zsc.objects.len())
+        .field(
+            "parts",
+            zsc.objects.iter().map(|o| o.parts.len()).sum::<usize>(),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zmo::{Channel, ChannelType};
+
+    #[test]
+    fn test_file_info() {
+        let mut mesh = ZMS::new();
+        mesh.format = VertexFormat::Position as i32 | VertexFormat::UV1 as i32;
+        mesh.bones = vec![0, 1];
+        let info = zms(&mesh);
+        assert_eq!(info.get("format"), Some("0x82 (position, uv1)"));
+        assert_eq!(info.get("bones"), Some("2"));
+
+        let mut motion = ZMO::new();
+        motion.fps = 30;
+        motion.frames = 45;
+        motion.channels = vec![
+            Channel::from(ChannelType::Position),
+            Channel::from(ChannelType::Rotation),
+            Channel::from(ChannelType::Rotation),
+        ];
+        let info = zmo(&motion);
+        assert_eq!(info.get("duration"), Some("1.50s"));
+        assert_eq!(info.get("channels"), Some("3 (1 position, 2 rotation)"));
+
+        let text = zmd(&ZMD::new()).to_string();
+        assert_eq!(text, "type         ZMD\nbones        0\ndummy bones  0\n");
+    }
+}
@@ -0,0 +1,1350 @@
+//! glTF export of ROSE models
+//!
+//! Models are written as a `.gltf` file with a `.bin` buffer of the same name.
+//! ROSE is Z-up with part positions in centimeters while glTF is Y-up in
+//! meters, so positions are converted from `(x, y, z)` to `(x, z, -y)` and
+//! part positions scaled down by 100. Meshes are already in meters.
+//!
+//! Materials reference their textures by client path, e.g.
+//! `3DDATA\AVATAR\BODY\TEXTURE.DDS`. The textures are resolved in the client
+//! directory, decoded and written as PNG to a `textures` directory next to the
+//! glTF file, and the materials use their relative URIs instead. A single
+//! mesh gets the material of the first ZSC part using it.
+//!
+//! The terrain of a map is a mesh per map block with a single texture
+//! stretched over the whole terrain. Its water is a flat quad per plane with
+//! a translucent blue material.
+//!
+//! Skeletons are a node per bone and dummy bone, with a mesh of lines
+//! between the joints to see them in viewers.
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use failure::{bail, Error};
+use log::warn;
+use serde::Serialize;
+
+use roselib::files::zms::{Vertex, VertexFormat};
+use roselib::files::zsc::SceneMaterial;
+use roselib::files::{ZMD, ZMS, ZSC};
+use roselib::io::RoseFile;
+use roselib::utils::{Quaternion, Vector2, Vector3};
+
+use crate::axes::Axes;
+use crate::image_format::{read_image, ImageFormat};
+use crate::lightmap::{self, PartLightmap};
+use crate::terrain::{Terrain, BLOCK_CELLS, BLOCK_SIZE};
+use crate::vfs::{client_path_key, resolve_client_path};
+use crate::water::{WaterKind, WaterPlane};
+use crate::zmd_viz;
+use crate::zms_tangents;
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_SHORT: u32 = 5123;
+const LINES: u32 = 1;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Centimeters per meter, the scale of part positions
+const PART_SCALE: f32 = 100.0;
+
+/// Base color of water surfaces
+const WATER_COLOR: [f32; 4] = [0.1, 0.3, 0.5, 0.6];
+
+/// Directory of the converted textures, relative to the glTF file
+pub const TEXTURE_DIR: &str = "textures";
+
+/// Textures of the exported materials, converted once each
+pub struct Textures {
+    client_dir: PathBuf,
+    out_dir: PathBuf,
+    /// URIs of the converted textures by lowercase client path, `None` for
+    /// textures missing from the client
+    uris: HashMap<String, Option<String>>,
+    file_names: HashSet<String>,
+}
+
+impl Textures {
+    /// Textures of `client_dir` converted to the `textures` directory of
+    /// `out_dir`
+    pub fn new(client_dir: &Path, out_dir: &Path) -> Textures {
+        Textures {
+            client_dir: client_dir.to_path_buf(),
+            out_dir: out_dir.to_path_buf(),
+            uris: HashMap::new(),
+            file_names: HashSet::new(),
+        }
+    }
+
+    /// URI of a texture relative to the glTF file, converting it on first
+    /// use. Textures missing from the client are left out with a warning, so
+    /// their materials are untextured.
+    pub fn uri(&mut self, texture: &str) -> Result<Option<String>, Error> {
+        let key = client_path_key(texture);
+        if let Some(uri) = self.uris.get(&key) {
+            return Ok(uri.clone());
+        }
+
+        let uri = match resolve_client_path(&self.client_dir, texture) {
+            Some(path) => Some(self.convert(&path)?),
+            None => {
+                warn!(
+                    "Texture not found in {}: {}",
+                    self.client_dir.display(),
+                    texture
+                );
+                None
+            }
+        };
+        self.uris.insert(key, uri.clone());
+        Ok(uri)
+    }
+
+    /// Paths of the converted textures
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .uris
+            .values()
+            .flatten()
+            .map(|uri| self.out_dir.join(uri))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    fn convert(&mut self, path: &Path) -> Result<String, Error> {
+        // Textures of different directories may share a file name
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut file_name = format!("{}.png", stem);
+        let mut n = 1;
+        while !self.file_names.insert(file_name.to_lowercase()) {
+            file_name = format!("{}_{}.png", stem, n);
+            n += 1;
+        }
+
+        let texture_dir = self.out_dir.join(TEXTURE_DIR);
+        fs::create_dir_all(&texture_dir)?;
+        ImageFormat::Png.save(&read_image(path)?, &texture_dir.join(&file_name))?;
+        Ok(format!("{}/{}", TEXTURE_DIR, file_name))
+    }
+}
+
+#[derive(Serialize)]
+struct Asset {
+    version: &'static str,
+    generator: String,
+}
+
+#[derive(Serialize)]
+struct Scene {
+    nodes: Vec<usize>,
+}
+
+/// Node of the scene hierarchy
+#[derive(Debug, Default, Serialize)]
+pub struct Node {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mesh: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translation: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<[f32; 4]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<[f32; 16]>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: usize,
+    component_type: u32,
+    count: usize,
+    #[serde(rename = "type")]
+    accessor_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max: Option<[f32; 3]>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    byte_offset: usize,
+    byte_length: usize,
+    target: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: String,
+    byte_length: usize,
+}
+
+#[derive(Serialize)]
+struct Primitive {
+    attributes: BTreeMap<&'static str, usize>,
+    indices: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    material: Option<usize>,
+    /// Triangles if `None`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct Mesh {
+    name: String,
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Serialize)]
+struct TextureInfo {
+    index: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PbrMetallicRoughness {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base_color_texture: Option<TextureInfo>,
+    base_color_factor: [f32; 4],
+    metallic_factor: f32,
+    roughness_factor: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Material {
+    name: String,
+    pbr_metallic_roughness: PbrMetallicRoughness,
+    alpha_mode: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alpha_cutoff: Option<f32>,
+    double_sided: bool,
+}
+
+#[derive(Serialize)]
+struct Texture {
+    source: usize,
+}
+
+#[derive(Serialize)]
+struct Image {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Root {
+    asset: Asset,
+    scene: usize,
+    scenes: Vec<Scene>,
+    nodes: Vec<Node>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    meshes: Vec<Mesh>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    materials: Vec<Material>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    textures: Vec<Texture>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<Image>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    accessors: Vec<Accessor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    buffer_views: Vec<BufferView>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    buffers: Vec<Buffer>,
+}
+
+/// glTF document being built, with the data of its binary buffer
+pub struct Document {
+    root: Root,
+    buffer: Vec<u8>,
+}
+
+impl Default for Document {
+    fn default() -> Document {
+        Document::new()
+    }
+}
+
+impl Document {
+    pub fn new() -> Document {
+        Document {
+            root: Root {
+                asset: Asset {
+                    version: "2.0",
+                    generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+                },
+                scene: 0,
+                scenes: vec![Scene { nodes: Vec::new() }],
+                nodes: Vec::new(),
+                meshes: Vec::new(),
+                materials: Vec::new(),
+                textures: Vec::new(),
+                images: Vec::new(),
+                accessors: Vec::new(),
+                buffer_views: Vec::new(),
+                buffers: Vec::new(),
+            },
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Add a node as a child of `parent`, or to the scene
+    pub fn add_node(&mut self, node: Node, parent: Option<usize>) -> usize {
+        let idx = self.root.nodes.len();
+        self.root.nodes.push(node);
+        match parent {
+            Some(parent) => self.root.nodes[parent].children.push(idx),
+            None => self.root.scenes[0].nodes.push(idx),
+        }
+        idx
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.root.nodes
+    }
+
+    pub fn mesh_count(&self) -> usize {
+        self.root.meshes.len()
+    }
+
+    /// Add a mesh with its positions, normals, tangents, vertex colors and
+    /// first UV channel
+    pub fn add_mesh(&mut self, name: &str, zms: &ZMS, material: Option<usize>) -> usize {
+        let mut attributes = BTreeMap::new();
+        let positions: Vec<[f32; 3]> = zms.vertices.iter().map(|v| position(&v.position)).collect();
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for p in &positions {
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+        let data: Vec<f32> = positions.iter().flatten().copied().collect();
+        let position_accessor =
+            self.add_accessor(&floats(&data), ARRAY_BUFFER, FLOAT, positions.len(), "VEC3");
+        if !positions.is_empty() {
+            self.root.accessors[position_accessor].min = Some(min);
+            self.root.accessors[position_accessor].max = Some(max);
+        }
+        attributes.insert("POSITION", position_accessor);
+
+        if zms.normals_enabled() {
+            let data: Vec<f32> = zms
+                .vertices
+                .iter()
+                .flat_map(|v| position(&v.normal))
+                .collect();
+            let accessor = self.add_accessor(
+                &floats(&data),
+                ARRAY_BUFFER,
+                FLOAT,
+                zms.vertices.len(),
+                "VEC3",
+            );
+            attributes.insert("NORMAL", accessor);
+        }
+        if let Some(tangents) = zms_tangents::tangents(zms) {
+            let data: Vec<f32> = tangents
+                .iter()
+                .flat_map(|t| {
+                    let [x, y, z] = position(&Vector3 {
+                        x: t.x,
+                        y: t.y,
+                        z: t.z,
+                    });
+                    vec![x, y, z, t.w]
+                })
+                .collect();
+            let accessor = self.add_accessor(
+                &floats(&data),
+                ARRAY_BUFFER,
+                FLOAT,
+                zms.vertices.len(),
+                "VEC4",
+            );
+            attributes.insert("TANGENT", accessor);
+        }
+        if zms.colors_enabled() {
+            let data: Vec<f32> = zms
+                .vertices
+                .iter()
+                .flat_map(|v| vec![v.color.r, v.color.g, v.color.b, v.color.a])
+                .collect();
+            let accessor = self.add_accessor(
+                &floats(&data),
+                ARRAY_BUFFER,
+                FLOAT,
+                zms.vertices.len(),
+                "VEC4",
+            );
+            attributes.insert("COLOR_0", accessor);
+        }
+        if zms.uv1_enabled() {
+            let data: Vec<f32> = zms
+                .vertices
+                .iter()
+                .flat_map(|v| vec![v.uv1.x, v.uv1.y])
+                .collect();
+            let accessor = self.add_accessor(
+                &floats(&data),
+                ARRAY_BUFFER,
+                FLOAT,
+                zms.vertices.len(),
+                "VEC2",
+            );
+            attributes.insert("TEXCOORD_0", accessor);
+        }
+
+        let mut indices = Vec::with_capacity(zms.indices.len() * 6);
+        for triangle in &zms.indices {
+            for index in &[triangle.x, triangle.y, triangle.z] {
+                indices.extend_from_slice(&(*index as u16).to_le_bytes());
+            }
+        }
+        let indices = self.add_accessor(
+            &indices,
+            ELEMENT_ARRAY_BUFFER,
+            UNSIGNED_SHORT,
+            zms.indices.len() * 3,
+            "SCALAR",
+        );
+
+        self.root.meshes.push(Mesh {
+            name: name.to_string(),
+            primitives: vec![Primitive {
+                attributes,
+                indices,
+                material,
+                mode: None,
+            }],
+        });
+        self.root.meshes.len() - 1
+    }
+
+    /// Add a mesh of lines between pairs of points
+    pub fn add_lines(&mut self, name: &str, points: &[[f32; 3]], lines: &[[u16; 2]]) -> usize {
+        let data: Vec<f32> = points.iter().flatten().copied().collect();
+        let positions =
+            self.add_accessor(&floats(&data), ARRAY_BUFFER, FLOAT, points.len(), "VEC3");
+        if !points.is_empty() {
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for p in points {
+                for i in 0..3 {
+                    min[i] = min[i].min(p[i]);
+                    max[i] = max[i].max(p[i]);
+                }
+            }
+            self.root.accessors[positions].min = Some(min);
+            self.root.accessors[positions].max = Some(max);
+        }
+        let indices: Vec<u8> = lines
+            .iter()
+            .flatten()
+            .flat_map(|i| i.to_le_bytes())
+            .collect();
+        let indices = self.add_accessor(
+            &indices,
+            ELEMENT_ARRAY_BUFFER,
+            UNSIGNED_SHORT,
+            lines.len() * 2,
+            "SCALAR",
+        );
+
+        let mut attributes = BTreeMap::new();
+        attributes.insert("POSITION", positions);
+        self.root.meshes.push(Mesh {
+            name: name.to_string(),
+            primitives: vec![Primitive {
+                attributes,
+                indices,
+                material: None,
+                mode: Some(LINES),
+            }],
+        });
+        self.root.meshes.len() - 1
+    }
+
+    /// Add a ZSC material with the URI of its converted texture
+    pub fn add_material(
+        &mut self,
+        name: &str,
+        material: &SceneMaterial,
+        texture_uri: Option<String>,
+    ) -> usize {
+        let base_color_texture = texture_uri.map(|uri| {
+            self.root.images.push(Image { uri });
+            self.root.textures.push(Texture {
+                source: self.root.images.len() - 1,
+            });
+            TextureInfo {
+                index: self.root.textures.len() - 1,
+            }
+        });
+
+        let (alpha_mode, alpha_cutoff) = if material.alpha_test_enabled {
+            ("MASK", Some(material.alpha_ref as f32 / 255.0))
+        } else if material.alpha_enabled {
+            ("BLEND", None)
+        } else {
+            ("OPAQUE", None)
+        };
+
+        self.root.materials.push(Material {
+            name: name.to_string(),
+            pbr_metallic_roughness: PbrMetallicRoughness {
+                base_color_texture,
+                base_color_factor: [1.0, 1.0, 1.0, material.alpha],
+                metallic_factor: 0.0,
+                roughness_factor: 1.0,
+            },
+            alpha_mode,
+            alpha_cutoff,
+            double_sided: material.two_sided,
+        });
+        self.root.materials.len() - 1
+    }
+
+    /// Convert the scene to other axes by parenting it to a root node with
+    /// the conversion matrix, `name` being the name of that node
+    pub fn convert_axes(&mut self, name: &str, axes: &Axes) {
+        if axes.is_default() {
+            return;
+        }
+        let children = std::mem::take(&mut self.root.scenes[0].nodes);
+        self.add_node(
+            Node {
+                name: name.to_string(),
+                matrix: Some(axes.matrix()),
+                children,
+                ..Default::default()
+            },
+            None,
+        );
+    }
+
+    /// Contents of the `.gltf` file, referencing the buffer as `<name>.bin`,
+    /// and of the buffer
+    pub fn to_files(mut self, name: &str, pretty: bool) -> Result<(String, Vec<u8>), Error> {
+        if !self.buffer.is_empty() {
+            self.root.buffers.push(Buffer {
+                uri: format!("{}.bin", name),
+                byte_length: self.buffer.len(),
+            });
+        }
+        let json = if pretty {
+            serde_json::to_string_pretty(&self.root)?
+        } else {
+            serde_json::to_string(&self.root)?
+        };
+        Ok((json, self.buffer))
+    }
+
+    fn add_accessor(
+        &mut self,
+        data: &[u8],
+        target: u32,
+        component_type: u32,
+        count: usize,
+        accessor_type: &'static str,
+    ) -> usize {
+        // Views start at multiples of 4 bytes
+        self.buffer.resize(self.buffer.len().div_ceil(4) * 4, 0);
+        self.root.buffer_views.push(BufferView {
+            buffer: 0,
+            byte_offset: self.buffer.len(),
+            byte_length: data.len(),
+            target,
+        });
+        self.buffer.extend_from_slice(data);
+        self.root.accessors.push(Accessor {
+            buffer_view: self.root.buffer_views.len() - 1,
+            component_type,
+            count,
+            accessor_type,
+            min: None,
+            max: None,
+        });
+        self.root.accessors.len() - 1
+    }
+}
+
+fn floats(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Y-up position or direction of a Z-up vector
+pub fn position(v: &Vector3<f32>) -> [f32; 3] {
+    // Adding zero turns -0.0 into 0.0
+    [v.x, v.z, -v.y + 0.0]
+}
+
+/// Y-up rotation of a Z-up rotation
+pub fn rotation(q: &Quaternion) -> [f32; 4] {
+    [q.x, q.z, -q.y + 0.0, q.w]
+}
+
+/// Name of a file without its directory and extension
+fn file_stem(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    Path::new(&path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// glTF of an object of a ZSC, with a node per part under a root node named
+/// `name`. Parts are parented as in the ZSC, where the parent is the 1-based
+/// index of the parent part. Meshes missing from the client are left out
+/// with a warning.
+pub fn zsc_object(
+    zsc: &ZSC,
+    object_idx: usize,
+    name: &str,
+    client_dir: &Path,
+    textures: &mut Textures,
+) -> Result<Document, Error> {
+    zsc_object_lit(zsc, object_idx, name, client_dir, textures, &HashMap::new())
+}
+
+/// glTF of an object of a ZSC like [`zsc_object`], with the lightmaps of its
+/// parts by index baked into the vertex colors of their meshes
+pub fn zsc_object_lit(
+    zsc: &ZSC,
+    object_idx: usize,
+    name: &str,
+    client_dir: &Path,
+    textures: &mut Textures,
+    lightmaps: &HashMap<usize, PartLightmap>,
+) -> Result<Document, Error> {
+    let object = match zsc.objects.get(object_idx) {
+        Some(object) => object,
+        None => bail!(
+            "Object {} not found, the ZSC has {} objects",
+            object_idx,
+            zsc.objects.len()
+        ),
+    };
+
+    let mut doc = Document::new();
+    let root = doc.add_node(
+        Node {
+            name: name.to_string(),
+            ..Default::default()
+        },
+        None,
+    );
+
+    // Meshes and materials shared by several parts are added once
+    let mut materials: HashMap<usize, Option<usize>> = HashMap::new();
+    let mut meshes: HashMap<(usize, usize, Option<usize>), Option<usize>> = HashMap::new();
+
+    let mut part_nodes = Vec::new();
+    for (part_idx, part) in object.parts.iter().enumerate() {
+        let mesh_id = part.mesh_id as usize;
+        let material_id = part.material_id as usize;
+        let mesh_path = zsc.meshes.get(mesh_id);
+
+        if let Entry::Vacant(entry) = materials.entry(material_id) {
+            let material = match zsc.materials.get(material_id) {
+                Some(material) => {
+                    let texture = material.path.to_string_lossy();
+                    let uri = textures.uri(&texture)?;
+                    Some(doc.add_material(&file_stem(&material.path), material, uri))
+                }
+                None => None,
+            };
+            entry.insert(material);
+        }
+
+        // Lit parts have a mesh of their own
+        let lightmap = lightmaps.get(&part_idx);
+        let key = (mesh_id, material_id, lightmap.map(|_| part_idx));
+        if let Entry::Vacant(entry) = meshes.entry(key) {
+            let mesh = match mesh_path
+                .map(|p| (p, resolve_client_path(client_dir, &p.to_string_lossy())))
+            {
+                Some((_, Some(path))) => {
+                    let mut zms = ZMS::from_path(&path)?;
+                    if let Some(lightmap) = lightmap {
+                        if !lightmap::bake(&mut zms, lightmap) {
+                            warn!("Mesh has no lightmap UVs: {}", path.display());
+                        }
+                    }
+                    Some(doc.add_mesh(&file_stem(&path), &zms, materials[&material_id]))
+                }
+                Some((path, None)) => {
+                    warn!(
+                        "Mesh not found in {}: {}",
+                        client_dir.display(),
+                        path.display()
+                    );
+                    None
+                }
+                None => {
+                    warn!(
+                        "Mesh {} not found, the ZSC has {} meshes",
+                        mesh_id,
+                        zsc.meshes.len()
+                    );
+                    None
+                }
+            };
+            entry.insert(mesh);
+        }
+
+        let scale = if part.scale == Vector3::default() {
+            None
+        } else {
+            Some([part.scale.x, part.scale.z, part.scale.y])
+        };
+        let translation = position(&part.position);
+        let node = Node {
+            name: mesh_path.map(|p| file_stem(p)).unwrap_or_default(),
+            mesh: meshes[&key],
+            translation: Some([
+                translation[0] / PART_SCALE,
+                translation[1] / PART_SCALE,
+                translation[2] / PART_SCALE,
+            ]),
+            rotation: Some(rotation(&part.rotation)),
+            scale,
+            ..Default::default()
+        };
+        part_nodes.push(doc.add_node(node, None));
+    }
+
+    // Parents may come after their children, so parts are linked once they
+    // all have a node
+    doc.root.scenes[0].nodes.retain(|&n| n == root);
+    let part_count = object.parts.len();
+    let parents: Vec<Option<usize>> = object
+        .parts
+        .iter()
+        .enumerate()
+        .map(|(idx, part)| match part.parent as usize {
+            0 => None,
+            parent if parent <= part_count && parent - 1 != idx => Some(parent - 1),
+            parent => {
+                warn!(
+                    "Part {} of object {} has an invalid parent: {}",
+                    idx, object_idx, parent
+                );
+                None
+            }
+        })
+        .collect();
+    for (idx, &node) in part_nodes.iter().enumerate() {
+        // Parts in a cycle of parents are attached to the root instead
+        let mut ancestor = parents[idx];
+        let mut in_cycle = false;
+        for _ in 0..part_count {
+            match ancestor {
+                Some(a) if a == idx => {
+                    in_cycle = true;
+                    break;
+                }
+                Some(a) => ancestor = parents[a],
+                None => break,
+            }
+        }
+        let parent = match parents[idx] {
+            Some(parent) if !in_cycle => part_nodes[parent],
+            _ => root,
+        };
+        doc.root.nodes[parent].children.push(node);
+    }
+
+    Ok(doc)
+}
+
+/// Material of the first part of a ZSC using the mesh at the client path
+/// `mesh`, compared case-insensitively
+pub fn mesh_material<'a>(zscs: &'a [ZSC], mesh: &str) -> Option<&'a SceneMaterial> {
+    let key = client_path_key(mesh);
+    zscs.iter().find_map(|zsc| {
+        let mesh_id = zsc
+            .meshes
+            .iter()
+            .position(|p| client_path_key(&p.to_string_lossy()) == key)?;
+        zsc.objects
+            .iter()
+            .flat_map(|o| o.parts.iter())
+            .find(|part| part.mesh_id as usize == mesh_id)
+            .and_then(|part| zsc.materials.get(part.material_id as usize))
+    })
+}
+
+/// glTF of a single mesh under a root node named `name`, with the material
+/// and converted texture of `material` if any
+pub fn zms_model(
+    name: &str,
+    zms: &ZMS,
+    material: Option<&SceneMaterial>,
+    textures: &mut Textures,
+) -> Result<Document, Error> {
+    let mut doc = Document::new();
+    let material = match material {
+        Some(material) => {
+            let uri = textures.uri(&material.path.to_string_lossy())?;
+            Some(doc.add_material(&file_stem(&material.path), material, uri))
+        }
+        None => None,
+    };
+    let mesh = doc.add_mesh(name, zms, material);
+    doc.add_node(
+        Node {
+            name: name.to_string(),
+            mesh: Some(mesh),
+            ..Default::default()
+        },
+        None,
+    );
+    Ok(doc)
+}
+
+/// glTF of the terrain of a map, with a node per map block under a root node
+/// named `name` at the world position of the terrain's north west corner
+pub fn terrain(name: &str, terrain: &Terrain, texture_uri: Option<String>) -> Document {
+    let mut doc = Document::new();
+    let (x, y) = terrain.origin();
+    let origin = Vector3 {
+        x: x / PART_SCALE,
+        y: y / PART_SCALE,
+        z: 0.0,
+    };
+    let root = doc.add_node(
+        Node {
+            name: name.to_string(),
+            translation: Some(position(&origin)),
+            ..Default::default()
+        },
+        None,
+    );
+    let material = SceneMaterial {
+        alpha: 1.0,
+        ..Default::default()
+    };
+    let material = doc.add_material(name, &material, texture_uri);
+
+    for row in (0..terrain.rows() - 1).step_by(BLOCK_CELLS) {
+        for col in (0..terrain.cols() - 1).step_by(BLOCK_CELLS) {
+            let block_name = format!(
+                "{}_{}",
+                terrain.min_block[0] as usize + col / BLOCK_CELLS,
+                terrain.min_block[1] as usize + row / BLOCK_CELLS
+            );
+            let zms = terrain_block(terrain, row, col);
+            let mesh = doc.add_mesh(&block_name, &zms, Some(material));
+            let node = Node {
+                name: block_name,
+                mesh: Some(mesh),
+                ..Default::default()
+            };
+            doc.add_node(node, Some(root));
+        }
+    }
+    doc
+}
+
+/// Mesh of the block with its first height at `(row0, col0)`, in meters from
+/// the terrain's north west corner
+fn terrain_block(terrain: &Terrain, row0: usize, col0: usize) -> ZMS {
+    let cell = BLOCK_SIZE / BLOCK_CELLS as f32 / PART_SCALE;
+    let (rows, cols) = (terrain.rows(), terrain.cols());
+    let height =
+        |row: usize, col: usize| terrain.heights[row.min(rows - 1)][col.min(cols - 1)] / PART_SCALE;
+
+    let mut zms = ZMS::new();
+    zms.format =
+        VertexFormat::Position as i32 | VertexFormat::Normal as i32 | VertexFormat::UV1 as i32;
+    for row in row0..=row0 + BLOCK_CELLS {
+        for col in col0..=col0 + BLOCK_CELLS {
+            // Slopes east and north, across the edges of the block
+            let dx = (height(row, col + 1) - height(row, col.saturating_sub(1)))
+                / ((col + 1).min(cols - 1) - col.saturating_sub(1)) as f32
+                / cell;
+            let dy = (height(row.saturating_sub(1), col) - height(row + 1, col))
+                / ((row + 1).min(rows - 1) - row.saturating_sub(1)) as f32
+                / cell;
+            let length = (dx * dx + dy * dy + 1.0).sqrt();
+            let mut vertex = Vertex::new();
+            vertex.position = Vector3 {
+                x: col as f32 * cell,
+                y: -(row as f32) * cell,
+                z: height(row, col),
+            };
+            vertex.normal = Vector3 {
+                x: -dx / length,
+                y: -dy / length,
+                z: 1.0 / length,
+            };
+            vertex.uv1 = Vector2 {
+                x: col as f32 / (cols - 1) as f32,
+                y: row as f32 / (rows - 1) as f32,
+            };
+            zms.vertices.push(vertex);
+        }
+    }
+
+    // Two triangles per cell, counterclockwise from above
+    let stride = BLOCK_CELLS + 1;
+    for row in 0..BLOCK_CELLS {
+        for col in 0..BLOCK_CELLS {
+            let i = (row * stride + col) as i16;
+            let (right, below) = (i + 1, i + stride as i16);
+            zms.indices.push(Vector3 {
+                x: i,
+                y: below,
+                z: right,
+            });
+            zms.indices.push(Vector3 {
+                x: right,
+                y: below,
+                z: below + 1,
+            });
+        }
+    }
+    zms
+}
+
+/// glTF of the water planes of a map, with a node per plane at its world
+/// position under a root node named `name`
+pub fn water(name: &str, planes: &[WaterPlane]) -> Document {
+    let mut doc = Document::new();
+    let root = doc.add_node(
+        Node {
+            name: name.to_string(),
+            ..Default::default()
+        },
+        None,
+    );
+    let material = SceneMaterial {
+        alpha: WATER_COLOR[3],
+        alpha_enabled: true,
+        two_sided: true,
+        ..Default::default()
+    };
+    let material = doc.add_material("Water", &material, None);
+    doc.root.materials[material]
+        .pbr_metallic_roughness
+        .base_color_factor = WATER_COLOR;
+
+    let mut counts = HashMap::new();
+    for plane in planes {
+        let kind = match plane.kind {
+            WaterKind::Ocean => "Ocean",
+            WaterKind::Water => "Water",
+        };
+        let count = counts.entry((kind, plane.block)).or_insert(0);
+        let plane_name = format!("{}_{}_{}_{}", kind, plane.block[0], plane.block[1], count);
+        *count += 1;
+
+        // A quad from the north west corner, counterclockwise from above
+        let (width, length) = (
+            (plane.max[0] - plane.min[0]) / PART_SCALE,
+            (plane.max[1] - plane.min[1]) / PART_SCALE,
+        );
+        let mut zms = ZMS::new();
+        zms.format = VertexFormat::Position as i32 | VertexFormat::Normal as i32;
+        for &(x, y) in &[(0.0, 0.0), (width, 0.0), (0.0, -length), (width, -length)] {
+            let mut vertex = Vertex::new();
+            vertex.position = Vector3 { x, y, z: 0.0 };
+            vertex.normal = Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            };
+            zms.vertices.push(vertex);
+        }
+        zms.indices.push(Vector3 { x: 0, y: 2, z: 1 });
+        zms.indices.push(Vector3 { x: 1, y: 2, z: 3 });
+
+        let mesh = doc.add_mesh(&plane_name, &zms, Some(material));
+        let corner = Vector3 {
+            x: plane.min[0] / PART_SCALE,
+            y: plane.max[1] / PART_SCALE,
+            z: plane.height / PART_SCALE,
+        };
+        let node = Node {
+            name: plane_name,
+            mesh: Some(mesh),
+            translation: Some(position(&corner)),
+            ..Default::default()
+        };
+        doc.add_node(node, Some(root));
+    }
+    doc
+}
+
+/// glTF of a skeleton under a root node named `name`: a node per bone and
+/// dummy bone, placed relative to its parent, and a mesh of lines from every
+/// joint to its parent's
+pub fn skeleton(name: &str, zmd: &ZMD) -> Document {
+    let mut doc = Document::new();
+    let root = doc.add_node(
+        Node {
+            name: name.to_string(),
+            ..Default::default()
+        },
+        None,
+    );
+
+    // Parents may come after their children, so they're linked afterwards
+    let joints = zmd_viz::joints(zmd);
+    let first = doc.root.nodes.len();
+    for bone in zmd.bones.iter().chain(&zmd.dummy_bones) {
+        doc.root.nodes.push(Node {
+            name: bone.name.clone(),
+            translation: Some(zmd_viz::bone_position(bone)),
+            rotation: Some(rotation(&zmd_viz::bone_rotation(bone))),
+            ..Default::default()
+        });
+    }
+    for (idx, joint) in joints.iter().enumerate() {
+        let parent = joint.parent.map_or(root, |p| first + p);
+        doc.root.nodes[parent].children.push(first + idx);
+    }
+
+    let points: Vec<[f32; 3]> = joints.iter().map(zmd_viz::joint_position).collect();
+    let lines: Vec<[u16; 2]> = joints
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, joint)| Some([joint.parent? as u16, idx as u16]))
+        .collect();
+    let mesh = doc.add_lines("Bones", &points, &lines);
+    doc.add_node(
+        Node {
+            name: format!("{}_lines", name),
+            mesh: Some(mesh),
+            ..Default::default()
+        },
+        Some(root),
+    );
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::path::PathBuf;
+
+    use image::{Rgba, RgbaImage};
+
+    use crate::dds::{self, DxtFormat};
+
+    #[test]
+    fn test_textures() {
+        let dir = env::temp_dir().join("rose-conv-test-gltf");
+        let client_dir = dir.join("client");
+        let out_dir = dir.join("out");
+        fs::create_dir_all(client_dir.join("3DDATA/avatar")).unwrap();
+        fs::create_dir_all(client_dir.join("3DDATA/NPC")).unwrap();
+
+        let img = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        let data = dds::encode(&img, DxtFormat::Dxt1, false);
+        fs::write(client_dir.join("3DDATA/avatar/Body.dds"), &data).unwrap();
+        fs::write(client_dir.join("3DDATA/NPC/BODY.DDS"), &data).unwrap();
+
+        let mut textures = Textures::new(&client_dir, &out_dir);
+        let uri = textures.uri("3DDATA\\AVATAR\\BODY.DDS").unwrap();
+        assert_eq!(uri.as_deref(), Some("textures/Body.png"));
+        assert_eq!(textures.uri("3ddata/avatar/body.dds").unwrap(), uri);
+        let uri = textures.uri("3DDATA\\NPC\\BODY.DDS").unwrap();
+        assert_eq!(uri.as_deref(), Some("textures/BODY_1.png"));
+        assert_eq!(textures.uri("3DDATA\\NPC\\MISSING.DDS").unwrap(), None);
+
+        assert_eq!(textures.paths().len(), 2);
+        let png = read_image(&out_dir.join("textures/Body.png")).unwrap();
+        assert_eq!(png, img);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_zsc_object() {
+        use roselib::files::zms::{Vertex, VertexFormat};
+        use roselib::files::zsc::{SceneObject, SceneObjectPart};
+
+        let dir = env::temp_dir().join("rose-conv-test-gltf-zsc");
+        let client_dir = dir.join("client");
+        fs::create_dir_all(client_dir.join("3DDATA/DECO")).unwrap();
+
+        let mut zms = ZMS::new();
+        zms.format = VertexFormat::Position as i32 | VertexFormat::UV1 as i32;
+        for (x, y) in &[(0.0, 0.0), (1.0, 0.0), (0.0, 2.0)] {
+            let mut vertex = Vertex::new();
+            vertex.position = Vector3 {
+                x: *x,
+                y: *y,
+                z: 0.5,
+            };
+            zms.vertices.push(vertex);
+        }
+        zms.indices.push(Vector3 { x: 0, y: 1, z: 2 });
+        zms.write_to_path(&client_dir.join("3DDATA/DECO/TREE.ZMS"))
+            .unwrap();
+
+        let mut zsc = ZSC::new();
+        zsc.meshes.push(PathBuf::from("3DDATA\\DECO\\TREE.ZMS"));
+        zsc.meshes.push(PathBuf::from("3DDATA\\DECO\\MISSING.ZMS"));
+        zsc.materials.push(SceneMaterial {
+            path: PathBuf::from("3DDATA\\DECO\\TREE.DDS"),
+            alpha_test_enabled: true,
+            alpha_ref: 51,
+            ..Default::default()
+        });
+        let mut object = SceneObject::default();
+        object.parts.push(SceneObjectPart {
+            position: Vector3 {
+                x: 100.0,
+                y: 200.0,
+                z: 300.0,
+            },
+            parent: 2,
+            ..Default::default()
+        });
+        object.parts.push(SceneObjectPart {
+            mesh_id: 1,
+            ..Default::default()
+        });
+        zsc.objects.push(object);
+
+        let mut textures = Textures::new(&client_dir, &dir);
+        let doc = zsc_object(&zsc, 0, "DECO_0", &client_dir, &mut textures).unwrap();
+        assert!(zsc_object(&zsc, 1, "DECO_1", &client_dir, &mut textures).is_err());
+
+        // The second part is the parent of the first and has no mesh
+        let nodes = doc.nodes();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[0].children, vec![2]);
+        assert_eq!(nodes[2].children, vec![1]);
+        assert_eq!(nodes[1].name, "TREE");
+        assert_eq!(nodes[1].mesh, Some(0));
+        assert_eq!(nodes[1].translation, Some([1.0, 3.0, -2.0]));
+        assert_eq!(nodes[2].mesh, None);
+        assert_eq!(doc.mesh_count(), 1);
+
+        let (json, buffer) = doc.to_files("DECO_0", false).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["buffers"][0]["uri"], "DECO_0.bin");
+        assert_eq!(json["buffers"][0]["byteLength"], buffer.len());
+        // 3 positions, 3 UVs and 3 indices padded to 4 bytes
+        assert_eq!(buffer.len(), 36 + 24 + 6);
+        assert_eq!(json["accessors"][0]["max"][1], 0.5);
+        assert_eq!(json["accessors"][0]["min"][2], -2.0);
+        let material = &json["materials"][0];
+        assert_eq!(material["alphaMode"], "MASK");
+        assert_eq!(material["alphaCutoff"], 0.2);
+        // The texture is missing from the client
+        assert!(material["pbrMetallicRoughness"]["baseColorTexture"].is_null());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_zms_model() {
+        use roselib::files::zms::{Vertex, VertexFormat};
+        use roselib::files::zsc::{SceneObject, SceneObjectPart};
+
+        let dir = env::temp_dir().join("rose-conv-test-gltf-zms");
+        let client_dir = dir.join("client");
+        fs::create_dir_all(client_dir.join("3DDATA/NPC")).unwrap();
+        let img = RgbaImage::from_pixel(4, 4, Rgba([0, 255, 0, 255]));
+        fs::write(
+            client_dir.join("3DDATA/NPC/WOLF.DDS"),
+            dds::encode(&img, DxtFormat::Dxt1, false),
+        )
+        .unwrap();
+
+        let mut zsc = ZSC::new();
+        zsc.meshes.push(PathBuf::from("3DDATA\\NPC\\HEAD.ZMS"));
+        zsc.meshes.push(PathBuf::from("3DDATA\\NPC\\WOLF.ZMS"));
+        zsc.materials.push(SceneMaterial {
+            path: PathBuf::from("3DDATA\\NPC\\HEAD.DDS"),
+            ..Default::default()
+        });
+        zsc.materials.push(SceneMaterial {
+            path: PathBuf::from("3DDATA\\NPC\\WOLF.DDS"),
+            alpha: 1.0,
+            ..Default::default()
+        });
+        let mut object = SceneObject::default();
+        object.parts.push(SceneObjectPart {
+            mesh_id: 1,
+            material_id: 1,
+            ..Default::default()
+        });
+        zsc.objects.push(object);
+
+        let zscs = [zsc];
+        assert!(mesh_material(&zscs, "3DDATA/NPC/HEAD.ZMS").is_none());
+        let material = mesh_material(&zscs, "3ddata/npc/wolf.zms").unwrap();
+        assert_eq!(material.path, PathBuf::from("3DDATA\\NPC\\WOLF.DDS"));
+
+        let mut zms = ZMS::new();
+        zms.format = VertexFormat::Position as i32;
+        zms.vertices = vec![Vertex::new(); 3];
+        zms.indices.push(Vector3 { x: 0, y: 1, z: 2 });
+
+        let mut textures = Textures::new(&client_dir, &dir.join("out"));
+        let doc = zms_model("WOLF", &zms, Some(material), &mut textures).unwrap();
+        assert_eq!(doc.nodes().len(), 1);
+        assert_eq!(doc.nodes()[0].mesh, Some(0));
+        let (json, _) = doc.to_files("WOLF", false).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["materials"][0]["name"], "WOLF");
+        assert_eq!(json["images"][0]["uri"], "textures/WOLF.png");
+        assert_eq!(textures.paths(), [dir.join("out/textures/WOLF.png")]);
+
+        let doc = zms_model("WOLF", &zms, None, &mut textures).unwrap();
+        assert_eq!(doc.nodes()[0].mesh, Some(0));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_terrain() {
+        use roselib::files::HIM;
+
+        let mut blocks = BTreeMap::new();
+        for (block, z) in &[((31, 32), 0.0), ((32, 32), 1000.0)] {
+            let mut him = HIM::new();
+            him.width = 65;
+            him.length = 65;
+            him.heights = vec![*z; 65 * 65];
+            blocks.insert(*block, him);
+        }
+        let terrain = Terrain::from_blocks(&blocks);
+        let doc = super::terrain("JPT01", &terrain, Some(String::from("textures/JPT01.png")));
+
+        let nodes = doc.nodes();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[0].translation, Some([4960.0, 0.0, -5280.0]));
+        assert_eq!(nodes[0].children, vec![1, 2]);
+        assert_eq!(nodes[2].name, "32_32");
+        assert_eq!(doc.mesh_count(), 2);
+
+        let (json, _) = doc.to_files("JPT01", false).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let positions = &json["accessors"][5];
+        assert_eq!(positions["count"], 65 * 65);
+        // The second block starts 160 meters east and is 10 meters high
+        assert_eq!(positions["min"][0], 160.0);
+        assert_eq!(positions["max"][1], 10.0);
+        assert_eq!(positions["max"][2], 160.0);
+        assert_eq!(json["images"][0]["uri"], "textures/JPT01.png");
+
+        // The first block slopes up towards the second on its east edge
+        let zms = terrain_block(&terrain, 0, 0);
+        assert_eq!(zms.indices.len(), 64 * 64 * 2);
+        assert_eq!(zms.vertices[0].normal.z, 1.0);
+        assert!(zms.vertices[64].normal.x < 0.0);
+        assert_eq!(zms.vertices[65 * 65 - 1].uv1.x, 0.5);
+    }
+
+    #[test]
+    fn test_water() {
+        let planes = vec![
+            WaterPlane {
+                kind: WaterKind::Ocean,
+                block: [32, 33],
+                min: [519_000.0, 518_000.0],
+                max: [521_000.0, 520_000.0],
+                height: 500.0,
+            },
+            WaterPlane {
+                kind: WaterKind::Ocean,
+                block: [32, 33],
+                min: [0.0, 0.0],
+                max: [100.0, 100.0],
+                height: 0.0,
+            },
+        ];
+        let doc = water("JPT01_water", &planes);
+        let nodes = doc.nodes();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[0].children, vec![1, 2]);
+        assert_eq!(nodes[1].name, "Ocean_32_33_0");
+        assert_eq!(nodes[2].name, "Ocean_32_33_1");
+        assert_eq!(nodes[1].translation, Some([5190.0, 5.0, -5200.0]));
+
+        let (json, _) = doc.to_files("JPT01_water", false).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["materials"][0]["alphaMode"], "BLEND");
+        assert_eq!(json["materials"][0]["doubleSided"], true);
+        // 20 meters east and south of the corner
+        assert_eq!(json["accessors"][0]["max"][0], 20.0);
+        assert_eq!(json["accessors"][0]["max"][2], 20.0);
+    }
+
+    #[test]
+    fn test_skeleton() {
+        use roselib::files::zmd::Bone;
+
+        let mut zmd = ZMD::new();
+        // The spine before the pelvis it hangs from
+        zmd.bones.push(Bone {
+            parent: 1,
+            name: String::from("b1_spine"),
+            position: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 50.0,
+            },
+            ..Default::default()
+        });
+        zmd.bones.push(Bone {
+            parent: 1,
+            name: String::from("b1_pelvis"),
+            position: Vector3 {
+                x: 0.0,
+                y: 100.0,
+                z: 0.0,
+            },
+            ..Default::default()
+        });
+        zmd.dummy_bones.push(Bone {
+            parent: 0,
+            name: String::from("p_00"),
+            ..Default::default()
+        });
+
+        let doc = skeleton("MALE", &zmd);
+        let nodes = doc.nodes();
+        assert_eq!(nodes.len(), 5);
+        assert_eq!(nodes[0].children, vec![2, 4]);
+        assert_eq!(nodes[2].name, "b1_pelvis");
+        assert_eq!(nodes[2].children, vec![1]);
+        assert_eq!(nodes[1].children, vec![3]);
+        assert_eq!(nodes[1].translation, Some([0.0, 0.5, 0.0]));
+        assert_eq!(nodes[2].translation, Some([0.0, 0.0, -1.0]));
+        assert_eq!(nodes[3].rotation, Some([0.0, 0.0, 0.0, 1.0]));
+        assert_eq!(nodes[4].mesh, Some(0));
+
+        let (json, buffer) = doc.to_files("MALE", false).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["meshes"][0]["primitives"][0]["mode"], 1);
+        // 3 joints and 2 lines
+        assert_eq!(json["accessors"][1]["count"], 4);
+        assert_eq!(buffer.len(), 36 + 8);
+        assert_eq!(json["accessors"][0]["max"][1], 0.5);
+    }
+}
@@ -0,0 +1,241 @@
+//! Godot scenes of maps
+//!
+//! A map is written as a text scene (`.tscn`) for Godot 4 instancing glTF
+//! scenes: the terrain and the models of the placed objects. Resources are
+//! referenced by paths relative to the scene. Godot is Y-up in meters like
+//! glTF, so instances are converted like the parts of glTF models.
+use std::fmt::Write;
+
+use roselib::utils::Vector3;
+
+use crate::gltf;
+use crate::ifo_instances::Instance;
+
+/// Godot text scene being built
+pub struct GodotScene {
+    name: String,
+    /// Paths of the instanced scenes
+    resources: Vec<String>,
+    nodes: Vec<SceneNode>,
+}
+
+struct SceneNode {
+    name: String,
+    /// Path of the parent from the root, `.` for the root
+    parent: String,
+    resource: Option<usize>,
+    transform: Option<[f32; 12]>,
+}
+
+impl GodotScene {
+    /// Scene with a `Node3D` root named `name`
+    pub fn new(name: &str) -> GodotScene {
+        GodotScene {
+            name: name.to_string(),
+            resources: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Index of the scene at `path`, added on first use
+    pub fn resource(&mut self, path: &str) -> usize {
+        match self.resources.iter().position(|p| p == path) {
+            Some(idx) => idx,
+            None => {
+                self.resources.push(path.to_string());
+                self.resources.len() - 1
+            }
+        }
+    }
+
+    /// Add a `Node3D` grouping nodes under the root
+    pub fn add_group(&mut self, name: &str) {
+        self.nodes.push(SceneNode {
+            name: name.to_string(),
+            parent: String::from("."),
+            resource: None,
+            transform: None,
+        });
+    }
+
+    /// Add an instance of a scene under `parent`, `.` for the root
+    pub fn add_instance(
+        &mut self,
+        name: &str,
+        parent: &str,
+        resource: usize,
+        transform: Option<[f32; 12]>,
+    ) {
+        self.nodes.push(SceneNode {
+            name: name.to_string(),
+            parent: parent.to_string(),
+            resource: Some(resource),
+            transform,
+        });
+    }
+
+    pub fn to_tscn(&self) -> String {
+        let mut tscn = String::new();
+        let _ = writeln!(
+            tscn,
+            "[gd_scene load_steps={} format=3]\n",
+            self.resources.len() + 1
+        );
+        for (i, path) in self.resources.iter().enumerate() {
+            let _ = writeln!(
+                tscn,
+                "[ext_resource type=\"PackedScene\" path=\"{}\" id=\"{}\"]",
+                escape(path),
+                i + 1
+            );
+        }
+        if !self.resources.is_empty() {
+            tscn.push('\n');
+        }
+
+        let _ = writeln!(
+            tscn,
+            "[node name=\"{}\" type=\"Node3D\"]",
+            escape(&self.name)
+        );
+        for node in &self.nodes {
+            let _ = write!(
+                tscn,
+                "\n[node name=\"{}\" parent=\"{}\"",
+                escape(&node.name),
+                escape(&node.parent)
+            );
+            match node.resource {
+                Some(idx) => {
+                    let _ = writeln!(tscn, " instance=ExtResource(\"{}\")]", idx + 1);
+                }
+                None => {
+                    let _ = writeln!(tscn, " type=\"Node3D\"]");
+                }
+            }
+            if let Some(transform) = node.transform {
+                let values: Vec<String> = transform.iter().map(|v| v.to_string()).collect();
+                let _ = writeln!(tscn, "transform = Transform3D({})", values.join(", "));
+            }
+        }
+        tscn
+    }
+}
+
+/// Transform of an instance in Godot's text format: the rows of the basis,
+/// then the origin in meters
+pub fn instance_transform(instance: &Instance) -> [f32; 12] {
+    let [x, y, z, w] = gltf::rotation(&instance.rotation);
+    let scale = &instance.scale;
+    let scale = [scale.x, scale.z, scale.y];
+    let basis = [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - z * w),
+            2.0 * (x * z + y * w),
+        ],
+        [
+            2.0 * (x * y + z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - x * w),
+        ],
+        [
+            2.0 * (x * z - y * w),
+            2.0 * (y * z + x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ];
+    let origin = gltf::position(&Vector3 {
+        x: instance.position.x / 100.0,
+        y: instance.position.y / 100.0,
+        z: instance.position.z / 100.0,
+    });
+
+    let mut transform = [0.0; 12];
+    for row in 0..3 {
+        for col in 0..3 {
+            // Adding zero turns -0.0 into 0.0
+            transform[row * 3 + col] = basis[row][col] * scale[col] + 0.0;
+        }
+    }
+    transform[9..].copy_from_slice(&origin);
+    transform
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::utils::Quaternion;
+
+    use crate::ifo_instances::InstanceKind;
+
+    #[test]
+    fn test_godot_scene() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let instance = Instance {
+            kind: InstanceKind::Decoration,
+            name: String::from("tree"),
+            object_id: 1,
+            block: [31, 32],
+            position: Vector3 {
+                x: 520_100.0,
+                y: 520_200.0,
+                z: 50.0,
+            },
+            // A quarter turn counterclockwise from above, from east to north
+            rotation: Quaternion {
+                x: 0.0,
+                y: 0.0,
+                z: half,
+                w: half,
+            },
+            scale: Vector3 {
+                x: 1.0,
+                y: 1.0,
+                z: 2.0,
+            },
+            parts: None,
+        };
+        let transform = instance_transform(&instance);
+        // East turns to north, -Z in Godot, and the height doubles
+        let rounded: Vec<f32> = transform.iter().map(|v| v.round()).collect();
+        assert_eq!(
+            rounded,
+            vec![0.0, 0.0, 1.0, 0.0, 2.0, 0.0, -1.0, 0.0, 0.0, 5201.0, 1.0, -5202.0]
+        );
+
+        let mut scene = GodotScene::new("JPT01");
+        let terrain = scene.resource("JPT01_terrain.gltf");
+        let tree = scene.resource("deco_1.gltf");
+        assert_eq!(scene.resource("JPT01_terrain.gltf"), terrain);
+        scene.add_instance("Terrain", ".", terrain, None);
+        scene.add_group("Decorations");
+        scene.add_instance("Decoration_31_32_0", "Decorations", tree, Some([0.0; 12]));
+
+        let tscn = scene.to_tscn();
+        let lines: Vec<&str> = tscn.lines().collect();
+        assert_eq!(lines[0], "[gd_scene load_steps=3 format=3]");
+        assert_eq!(
+            lines[3],
+            "[ext_resource type=\"PackedScene\" path=\"deco_1.gltf\" id=\"2\"]"
+        );
+        assert_eq!(lines[5], "[node name=\"JPT01\" type=\"Node3D\"]");
+        assert_eq!(
+            lines[7],
+            "[node name=\"Terrain\" parent=\".\" instance=ExtResource(\"1\")]"
+        );
+        assert_eq!(
+            lines[9],
+            "[node name=\"Decorations\" parent=\".\" type=\"Node3D\"]"
+        );
+        assert_eq!(
+            lines[12],
+            "transform = Transform3D(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0)"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Search of STL strings and STB cells
+use std::fmt;
+
+use failure::Error;
+use serde::Serialize;
+
+use roselib::files::{STB, STL};
+
+use crate::regex::Regex;
+use crate::stl_csv;
+
+/// Pattern searched for, a regular expression or a fixed string
+pub enum Pattern {
+    Regex(Regex),
+    Fixed { text: String, ignore_case: bool },
+}
+
+impl Pattern {
+    pub fn new(pattern: &str, fixed: bool, ignore_case: bool) -> Result<Pattern, Error> {
+        if !fixed {
+            return Ok(Pattern::Regex(Regex::new(pattern, ignore_case)?));
+        }
+
+        let text = if ignore_case {
+            pattern.to_lowercase()
+        } else {
+            pattern.to_string()
+        };
+        Ok(Pattern::Fixed { text, ignore_case })
+    }
+
+    pub fn is_match(&self, s: &str) -> bool {
+        match self {
+            Pattern::Regex(regex) => regex.is_match(s),
+            Pattern::Fixed {
+                text,
+                ignore_case: true,
+            } => s.to_lowercase().contains(text.as_str()),
+            Pattern::Fixed { text, .. } => s.contains(text.as_str()),
+        }
+    }
+}
+
+/// String matching a pattern
+#[derive(Debug, PartialEq, Serialize)]
+pub struct GrepMatch {
+    pub file: String,
+    /// Where the string is in the file, e.g. `key 1 (ITEM_1) en Text`
+    pub location: String,
+    pub value: String,
+}
+
+impl fmt::Display for GrepMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.file, self.location, self.value)
+    }
+}
+
+/// Cells of an STB matching the pattern, columns are named by their header
+/// if the STB has one
+pub fn grep_stb(file: &str, stb: &STB, pattern: &Pattern) -> Vec<GrepMatch> {
+    let mut matches = Vec::new();
+    for (row, values) in stb.data.iter().enumerate() {
+        for (col, value) in values.iter().enumerate() {
+            if !pattern.is_match(value) {
+                continue;
+            }
+
+            let location = match stb.header(col) {
+                Some(header) if !header.is_empty() => format!("row {} ({})", row, header),
+                _ => format!("row {} col {}", row, col),
+            };
+            matches.push(GrepMatch {
+                file: file.to_string(),
+                location,
+                value: value.clone(),
+            });
+        }
+    }
+    matches
+}
+
+/// Key names and strings of an STL matching the pattern
+pub fn grep_stl(file: &str, stl: &STL, pattern: &Pattern) -> Vec<GrepMatch> {
+    let mut matches = Vec::new();
+    for key in &stl.keys {
+        if pattern.is_match(&key.name) {
+            matches.push(GrepMatch {
+                file: file.to_string(),
+                location: format!("key {} name", key.id),
+                value: key.name.clone(),
+            });
+        }
+    }
+
+    let columns = stl_csv::columns(&stl.format);
+    for table in &stl.language_tables {
+        for (row, values) in table.rows.iter().enumerate() {
+            for (column, value) in columns.iter().zip(stl_csv::row_values(values)) {
+                if !pattern.is_match(value) {
+                    continue;
+                }
+
+                let key = match stl.keys.get(row) {
+                    Some(key) => format!("key {} ({})", key.id, key.name),
+                    None => format!("row {}", row),
+                };
+                matches.push(GrepMatch {
+                    file: file.to_string(),
+                    location: format!("{} {} {}", key, table.language.code(), column),
+                    value: value.to_string(),
+                });
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::stl::*;
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_grep() {
+        let mut stl = STL::new();
+        stl.keys.push(StringTableKey {
+            id: 7,
+            name: String::from("ITEM_SWORD"),
+        });
+        stl.language_tables.push(StringTableLanguageTable {
+            language: StringTableLanguage::English,
+            rows: vec![stl_csv::row_from_values(&stl.format, &["Iron Sword"])],
+        });
+
+        let pattern = Pattern::new("sword$", false, true).unwrap();
+        let matches = grep_stl("LIST_ITEM_S.STL", &stl, &pattern);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(
+            matches[1].to_string(),
+            "LIST_ITEM_S.STL: key 7 (ITEM_SWORD) en Text: Iron Sword"
+        );
+
+        let mut stb = STB::new();
+        stb.headers = vec![String::from("Name"), String::new()];
+        stb.data = vec![vec![String::from("Sword (1)"), String::from("sword")]];
+        let pattern = Pattern::new("d (1", true, false).unwrap();
+        let matches = grep_stb("LIST_WEAPON.STB", &stb, &pattern);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].location, "row 0 (Name)");
+        let pattern = Pattern::new("SWORD", true, true).unwrap();
+        assert_eq!(grep_stb("", &stb, &pattern)[1].location, "row 0 col 1");
+    }
+}
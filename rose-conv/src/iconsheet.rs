@@ -0,0 +1,589 @@
+//! Slicing iconsheets into icons and packing icons into iconsheets
+//!
+//! Icons are cut from a grid of cells, 40x40 for item icons, or, with the
+//! sprite information (TSI) of the UI, from the rectangles of the sheet's
+//! sprites. Packed sheets
+//! are 1024x1024 textures with a grid of 25x25 icons.
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use failure::{bail, Error};
+use image::{imageops, Rgba, RgbaImage};
+
+use roselib::files::tsi::{Sprite, SpriteSheet};
+use roselib::files::TSI;
+use roselib::utils::Vector2;
+
+/// Width and height of ROSE item icons
+pub const ICON_SIZE: u32 = 40;
+
+/// Width and height of packed iconsheets
+pub const SHEET_SIZE: u32 = 1024;
+
+/// Color of transparent pixels in sheets without an alpha channel
+pub const COLOR_KEY: [u8; 3] = [255, 0, 255];
+
+/// Length of sprite names in a TSI, including the NUL terminator
+const SPRITE_NAME_SIZE: usize = 32;
+
+/// Rectangle of an icon in an iconsheet
+#[derive(Clone, Debug, PartialEq)]
+pub struct IconRect {
+    /// File name of the icon, without extension
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Layout of the cells of an iconsheet
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub icon_width: u32,
+    pub icon_height: u32,
+    /// Number of columns, as many as fit in the sheet if not set
+    pub cols: Option<u32>,
+    /// Number of rows, as many as fit in the sheet if not set
+    pub rows: Option<u32>,
+    /// Pixels between two cells
+    pub padding: u32,
+}
+
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid {
+            icon_width: ICON_SIZE,
+            icon_height: ICON_SIZE,
+            cols: None,
+            rows: None,
+            padding: 0,
+        }
+    }
+}
+
+impl Grid {
+    /// Parse an icon size, `<size>` for square icons or `<width>x<height>`
+    pub fn parse_size(s: &str) -> Result<(u32, u32), Error> {
+        let mut parts = s.trim().splitn(2, ['x', 'X']);
+        let width: u32 = parts.next().unwrap_or_default().parse()?;
+        let height: u32 = match parts.next() {
+            Some(height) => height.parse()?,
+            None => width,
+        };
+        if width == 0 || height == 0 {
+            bail!("Icon size must not be empty: {}", s);
+        }
+        Ok((width, height))
+    }
+
+    /// Icons of the grid, named `<sheet>_<index>` row by row
+    pub fn icons(&self, sheet_name: &str, width: u32, height: u32) -> Vec<IconRect> {
+        let fit = |size: u32, icon_size: u32| (size + self.padding) / (icon_size + self.padding);
+        let cols = self.cols.unwrap_or_else(|| fit(width, self.icon_width));
+        let rows = self.rows.unwrap_or_else(|| fit(height, self.icon_height));
+
+        let mut icons = Vec::new();
+        for row in 0..rows {
+            for col in 0..cols {
+                icons.push(IconRect {
+                    name: format!("{}_{}", sheet_name, icons.len()),
+                    x: col * (self.icon_width + self.padding),
+                    y: row * (self.icon_height + self.padding),
+                    width: self.icon_width,
+                    height: self.icon_height,
+                });
+            }
+        }
+        icons
+    }
+}
+
+/// Sheet of a TSI with the same file name as an iconsheet, ignoring case
+/// and extension
+pub fn find_sheet<'a>(tsi: &'a TSI, path: &Path) -> Option<&'a SpriteSheet> {
+    let stem = |path: &Path| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+    };
+    let sheet_stem = stem(path)?;
+
+    tsi.sprite_sheets.iter().find(|sheet| {
+        // Paths are stored with backslashes
+        let path = sheet.path.to_string_lossy().replace('\\', "/");
+        stem(Path::new(&path)).as_ref() == Some(&sheet_stem)
+    })
+}
+
+/// Icons of the sprites of a TSI sheet, named by the sprite name or
+/// `<sheet>_<index>` for sprites without a name. Names used more than once get
+/// the index appended.
+pub fn tsi_icons(sheet_name: &str, sheet: &SpriteSheet) -> Vec<IconRect> {
+    let mut names = HashSet::new();
+    let mut icons = Vec::new();
+    for (idx, sprite) in sheet.sprites.iter().enumerate() {
+        // Names are fixed-size fields padded with NUL characters
+        let name = sprite.name.split('\0').next().unwrap_or_default();
+        let name = sanitize_name(name.trim());
+        let name = if name.is_empty() {
+            format!("{}_{}", sheet_name, idx)
+        } else if !names.insert(name.clone()) {
+            format!("{}_{}", name, idx)
+        } else {
+            name
+        };
+
+        icons.push(IconRect {
+            name,
+            x: sprite.start_point.x,
+            y: sprite.start_point.y,
+            width: sprite.end_point.x.saturating_sub(sprite.start_point.x),
+            height: sprite.end_point.y.saturating_sub(sprite.start_point.y),
+        });
+    }
+    icons
+}
+
+/// Copy an icon out of an iconsheet
+pub fn crop(sheet: &RgbaImage, icon: &IconRect) -> Result<RgbaImage, Error> {
+    if icon.width == 0
+        || icon.height == 0
+        || icon.x + icon.width > sheet.width()
+        || icon.y + icon.height > sheet.height()
+    {
+        bail!(
+            "Icon {} ({}x{} at {},{}) is outside of the {}x{} iconsheet",
+            icon.name,
+            icon.width,
+            icon.height,
+            icon.x,
+            icon.y,
+            sheet.width(),
+            sheet.height()
+        );
+    }
+    Ok(imageops::crop_imm(sheet, icon.x, icon.y, icon.width, icon.height).to_image())
+}
+
+/// Iconsheet packed from icons
+pub struct PackedSheet {
+    pub image: RgbaImage,
+    pub icons: Vec<IconRect>,
+}
+
+/// Pack 40x40 icons into as many sheets as needed, in order, row by row
+pub fn pack(icons: &[(String, RgbaImage)]) -> Result<Vec<PackedSheet>, Error> {
+    let per_row = SHEET_SIZE / ICON_SIZE;
+    let per_sheet = (per_row * per_row) as usize;
+
+    let mut sheets = Vec::new();
+    for chunk in icons.chunks(per_sheet) {
+        let mut sheet = PackedSheet {
+            image: RgbaImage::new(SHEET_SIZE, SHEET_SIZE),
+            icons: Vec::new(),
+        };
+        for (idx, (name, icon)) in chunk.iter().enumerate() {
+            if icon.dimensions() != (ICON_SIZE, ICON_SIZE) {
+                bail!(
+                    "Icon {} is {}x{}, expected {}x{}",
+                    name,
+                    icon.width(),
+                    icon.height(),
+                    ICON_SIZE,
+                    ICON_SIZE
+                );
+            }
+
+            let rect = IconRect {
+                name: name.clone(),
+                x: (idx as u32 % per_row) * ICON_SIZE,
+                y: (idx as u32 / per_row) * ICON_SIZE,
+                width: ICON_SIZE,
+                height: ICON_SIZE,
+            };
+            imageops::replace(&mut sheet.image, icon, rect.x, rect.y);
+            sheet.icons.push(rect);
+        }
+        sheets.push(sheet);
+    }
+    Ok(sheets)
+}
+
+/// Pack images of any size into square sheets of `size` pixels, with
+/// `padding` pixels between them. Images are placed from the tallest on
+/// shelves, rows as tall as their first image, filling the sheets in order.
+pub fn pack_sprites(
+    sprites: &[(String, RgbaImage)],
+    size: u32,
+    padding: u32,
+) -> Result<Vec<PackedSheet>, Error> {
+    struct Shelf {
+        y: u32,
+        height: u32,
+        width: u32,
+    }
+
+    let mut order: Vec<usize> = (0..sprites.len()).collect();
+    order.sort_by_key(|&idx| {
+        let (width, height) = sprites[idx].1.dimensions();
+        (std::cmp::Reverse(height), std::cmp::Reverse(width))
+    });
+
+    let mut sheets: Vec<(PackedSheet, Vec<Shelf>)> = Vec::new();
+    for idx in order {
+        let (name, sprite) = &sprites[idx];
+        let (width, height) = sprite.dimensions();
+        if width > size || height > size {
+            bail!(
+                "Sprite {} is {}x{}, larger than the {}x{} sheet",
+                name,
+                width,
+                height,
+                size,
+                size
+            );
+        }
+
+        // First shelf with room for the sprite, or a new shelf below the last
+        let mut place = None;
+        for (sheet_idx, (_, shelves)) in sheets.iter_mut().enumerate() {
+            if let Some(shelf) = shelves
+                .iter_mut()
+                .find(|shelf| shelf.height >= height && shelf.width + width <= size)
+            {
+                place = Some((sheet_idx, shelf.width, shelf.y));
+                shelf.width += width + padding;
+                break;
+            }
+            let y = shelves
+                .last()
+                .map_or(0, |shelf| shelf.y + shelf.height + padding);
+            if y + height <= size {
+                shelves.push(Shelf {
+                    y,
+                    height,
+                    width: width + padding,
+                });
+                place = Some((sheet_idx, 0, y));
+                break;
+            }
+        }
+        let (sheet_idx, x, y) = match place {
+            Some(place) => place,
+            None => {
+                let sheet = PackedSheet {
+                    image: RgbaImage::new(size, size),
+                    icons: Vec::new(),
+                };
+                let shelf = Shelf {
+                    y: 0,
+                    height,
+                    width: width + padding,
+                };
+                sheets.push((sheet, vec![shelf]));
+                (sheets.len() - 1, 0, 0)
+            }
+        };
+
+        let sheet = &mut sheets[sheet_idx].0;
+        imageops::replace(&mut sheet.image, sprite, x, y);
+        sheet.icons.push(IconRect {
+            name: name.clone(),
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    Ok(sheets.into_iter().map(|(sheet, _)| sheet).collect())
+}
+
+/// Replace the sprites of the TSI sheet with the same file name as
+/// `sheet_path` by the icons, adding the sheet if the TSI doesn't have it yet.
+/// New sheets are stored next to the TSI's other sheets.
+pub fn update_tsi(tsi: &mut TSI, sheet_path: &Path, icons: &[IconRect]) {
+    let stem = sheet_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase());
+    let existing = tsi.sprite_sheets.iter().position(|sheet| {
+        let path = sheet.path.to_string_lossy().replace('\\', "/");
+        Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            == stem
+    });
+
+    match existing {
+        Some(idx) => tsi.sprite_sheets[idx].sprites = tsi_sheet(PathBuf::new(), icons).sprites,
+        None => {
+            let file_name = sheet_path.file_name().unwrap_or_default();
+            let path = match tsi.sprite_sheets.first() {
+                Some(sheet) => {
+                    let path = sheet.path.to_string_lossy().replace('\\', "/");
+                    Path::new(&path).with_file_name(file_name)
+                }
+                None => PathBuf::from(file_name),
+            };
+            tsi.sprite_sheets.push(tsi_sheet(path, icons));
+        }
+    }
+}
+
+/// TSI sheet with a sprite per icon, names are truncated to the length of
+/// the TSI field
+pub fn tsi_sheet(path: PathBuf, icons: &[IconRect]) -> SpriteSheet {
+    let mut sheet = SpriteSheet::new();
+    sheet.path = path;
+    sheet.sprites = icons
+        .iter()
+        .map(|icon| {
+            let mut sprite = Sprite::new();
+            sprite.name = truncate_name(&icon.name, SPRITE_NAME_SIZE - 1);
+            sprite.start_point = Vector2 {
+                x: icon.x,
+                y: icon.y,
+            };
+            sprite.end_point = Vector2 {
+                x: icon.x + icon.width,
+                y: icon.y + icon.height,
+            };
+            sprite
+        })
+        .collect();
+    sheet
+}
+
+/// Compare file names with their numbers by value, so `ITEM_2` sorts before
+/// `ITEM_10`
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    fn parts(s: &str) -> Vec<(String, u64)> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut digits = String::new();
+        for c in s.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+            } else {
+                if !digits.is_empty() {
+                    parts.push((text.clone(), digits.parse().unwrap_or(u64::MAX)));
+                    text.clear();
+                    digits.clear();
+                }
+                text.push(c);
+            }
+        }
+        parts.push((text, digits.parse().unwrap_or(0)));
+        parts
+    }
+    parts(&a.to_lowercase()).cmp(&parts(&b.to_lowercase()))
+}
+
+/// Truncate a name to a number of bytes on a character boundary
+fn truncate_name(name: &str, len: usize) -> String {
+    let mut end = name.len().min(len);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    name[..end].to_string()
+}
+
+/// Parse a color as hexadecimal `rrggbb`, with or without a leading `#`
+pub fn parse_color(s: &str) -> Result<[u8; 3], Error> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("Invalid color, expected rrggbb: {}", s);
+    }
+    let mut color = [0; 3];
+    for (i, channel) in color.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)?;
+    }
+    Ok(color)
+}
+
+/// Make the pixels of the key color transparent
+pub fn remove_color_key(img: &mut RgbaImage, key: [u8; 3]) {
+    for pixel in img.pixels_mut() {
+        if pixel.0[0..3] == key {
+            *pixel = Rgba([0, 0, 0, 0]);
+        }
+    }
+}
+
+/// Replace transparency by the key color: pixels that are more than half
+/// transparent get the key color and the others become opaque
+pub fn apply_color_key(img: &mut RgbaImage, key: [u8; 3]) {
+    for pixel in img.pixels_mut() {
+        *pixel = if pixel[3] < 128 {
+            Rgba([key[0], key[1], key[2], 255])
+        } else {
+            Rgba([pixel[0], pixel[1], pixel[2], 255])
+        };
+    }
+}
+
+/// Replace the characters of a sprite name that aren't safe in file names
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_tsi_icons() {
+        let icons = Grid::default().icons("ITEM1", 120, 90);
+        assert_eq!(icons.len(), 6);
+        assert_eq!(icons[4].x, 40);
+
+        let grid = Grid {
+            icon_width: 32,
+            icon_height: 32,
+            padding: 2,
+            ..Default::default()
+        };
+        let icons = grid.icons("SKILL", 100, 66);
+        assert_eq!(icons.len(), 6);
+        assert_eq!((icons[4].x, icons[4].y), (34, 34));
+        let grid = Grid {
+            cols: Some(1),
+            rows: Some(2),
+            ..grid
+        };
+        assert_eq!(grid.icons("SKILL", 100, 66).len(), 2);
+        assert_eq!(Grid::parse_size("48").unwrap(), (48, 48));
+        assert_eq!(Grid::parse_size("32x16").unwrap(), (32, 16));
+        assert!(Grid::parse_size("0").is_err());
+
+        let mut tsi = TSI::new();
+        let mut sheet = SpriteSheet::new();
+        sheet.path = PathBuf::from("3DDATA\\Control\\Res\\UI01.DDS");
+        for (name, start, end) in &[
+            ("BTN OK\0\0", (0, 0), (2, 3)),
+            ("", (2, 1), (4, 2)),
+            ("BTN OK", (0, 0), (1, 1)),
+        ] {
+            let mut sprite = Sprite::new();
+            sprite.name = name.to_string();
+            sprite.start_point = Vector2 {
+                x: start.0,
+                y: start.1,
+            };
+            sprite.end_point = Vector2 { x: end.0, y: end.1 };
+            sheet.sprites.push(sprite);
+        }
+        tsi.sprite_sheets.push(sheet);
+
+        let sheet = find_sheet(&tsi, Path::new("res/ui01.png")).unwrap();
+        assert!(find_sheet(&tsi, Path::new("UI02.DDS")).is_none());
+
+        let icons = tsi_icons("ui01", sheet);
+        let names: Vec<&str> = icons.iter().map(|icon| icon.name.as_str()).collect();
+        assert_eq!(names, vec!["BTN_OK", "ui01_1", "BTN_OK_2"]);
+
+        let mut img = RgbaImage::new(4, 4);
+        img.put_pixel(2, 1, Rgba([255, 0, 0, 255]));
+        let icon = crop(&img, &icons[1]).unwrap();
+        assert_eq!(icon.dimensions(), (2, 1));
+        assert_eq!(icon.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+
+        let outside = IconRect {
+            x: 3,
+            ..icons[1].clone()
+        };
+        assert!(crop(&img, &outside).is_err());
+    }
+
+    #[test]
+    fn test_pack() {
+        let mut names = vec!["ITEM_10", "ITEM_2", "item_1"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, vec!["item_1", "ITEM_2", "ITEM_10"]);
+
+        let mut icon = RgbaImage::new(ICON_SIZE, ICON_SIZE);
+        icon.put_pixel(1, 2, Rgba([0, 255, 0, 255]));
+        let icons: Vec<(String, RgbaImage)> = (0..700)
+            .map(|idx| (format!("ICON_{}", idx), icon.clone()))
+            .collect();
+
+        let sheets = pack(&icons).unwrap();
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(sheets[1].icons.len(), 75);
+        let rect = &sheets[0].icons[26];
+        assert_eq!((rect.x, rect.y), (40, 40));
+        assert_eq!(sheets[0].image.get_pixel(41, 42), &Rgba([0, 255, 0, 255]));
+        assert!(pack(&[(String::from("BIG"), RgbaImage::new(64, 64))]).is_err());
+
+        let mut tsi = TSI::new();
+        let mut sheet = SpriteSheet::new();
+        sheet.path = PathBuf::from("3DDATA\\Control\\Res\\ITEM1.DDS");
+        tsi.sprite_sheets.push(sheet);
+        update_tsi(&mut tsi, Path::new("out/ITEM1.png"), &sheets[0].icons);
+        update_tsi(&mut tsi, Path::new("out/ITEM2.png"), &sheets[1].icons);
+        assert_eq!(tsi.sprite_sheets.len(), 2);
+        assert_eq!(tsi.sprite_sheets[0].sprites.len(), 625);
+        assert_eq!(
+            tsi.sprite_sheets[1].path,
+            PathBuf::from("3DDATA/Control/Res/ITEM2.png")
+        );
+        assert_eq!(tsi.sprite_sheets[1].sprites[0].end_point.x, 40);
+    }
+
+    #[test]
+    fn test_color_key() {
+        assert_eq!(parse_color("#FF00ff").unwrap(), COLOR_KEY);
+        assert_eq!(parse_color("00ff00").unwrap(), [0, 255, 0]);
+        assert!(parse_color("f0f").is_err());
+
+        let mut img = RgbaImage::new(3, 1);
+        img.put_pixel(0, 0, Rgba([255, 0, 255, 255]));
+        img.put_pixel(1, 0, Rgba([10, 20, 30, 255]));
+        remove_color_key(&mut img, COLOR_KEY);
+        assert_eq!(img.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+        assert_eq!(img.get_pixel(1, 0), &Rgba([10, 20, 30, 255]));
+
+        img.put_pixel(2, 0, Rgba([10, 20, 30, 200]));
+        apply_color_key(&mut img, COLOR_KEY);
+        assert_eq!(img.get_pixel(0, 0), &Rgba([255, 0, 255, 255]));
+        assert_eq!(img.get_pixel(2, 0), &Rgba([10, 20, 30, 255]));
+    }
+
+    #[test]
+    fn test_pack_sprites() {
+        let sprites: Vec<(String, RgbaImage)> = [(60, 20), (30, 40), (30, 10), (50, 30)]
+            .iter()
+            .enumerate()
+            .map(|(idx, &(w, h))| (format!("SPRITE_{}", idx), RgbaImage::new(w, h)))
+            .collect();
+
+        let sheets = pack_sprites(&sprites, 64, 2).unwrap();
+        let rects: Vec<(&str, u32, u32)> = sheets
+            .iter()
+            .flat_map(|sheet| &sheet.icons)
+            .map(|icon| (icon.name.as_str(), icon.x, icon.y))
+            .collect();
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(
+            rects,
+            vec![
+                ("SPRITE_1", 0, 0),
+                ("SPRITE_0", 0, 42),
+                ("SPRITE_2", 32, 0),
+                ("SPRITE_3", 0, 0),
+            ]
+        );
+        assert!(pack_sprites(&sprites, 32, 0).is_err());
+    }
+}
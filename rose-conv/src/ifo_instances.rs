@@ -0,0 +1,398 @@
+//! Objects placed on a map with their models
+//!
+//! The decorations and buildings of an IFO only reference an object of the
+//! zone's decoration or construction ZSC by index. The instances pair each of
+//! them with the meshes and textures of its object and its position in the
+//! world.
+//!
+//! The ZSCs of a zone are listed in `LIST_ZONE.STB` with the path of its ZON
+//! file. IFO positions are in centimeters relative to a world origin 5200
+//! meters from the map's origin on both horizontal axes.
+//!
+//! As an Unreal Engine data table, positions and rotations are mirrored to
+//! Unreal's left-handed axes: X east, Y south and Z up.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::Path;
+
+use failure::Error;
+use serde::Serialize;
+
+use roselib::files::ifo::ObjectData;
+use roselib::files::{IFO, STB, ZSC};
+use roselib::utils::{Quaternion, Vector3};
+
+use crate::csv_dialect::CsvDialect;
+use crate::vfs::client_path_key;
+
+/// Columns of `LIST_ZONE.STB`, counting the name column
+const ZONE_FILE_COLUMN: usize = 2;
+const DECORATIONS_COLUMN: usize = 12;
+const BUILDINGS_COLUMN: usize = 13;
+
+/// Offset of IFO positions on the horizontal axes, in centimeters
+pub const WORLD_OFFSET: f32 = 520_000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstanceKind {
+    Decoration,
+    Building,
+}
+
+/// Object of a ZSC placed on a map
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Instance {
+    pub kind: InstanceKind,
+    pub name: String,
+    /// Index of the object in the ZSC
+    pub object_id: i32,
+    /// Map block of the IFO
+    pub block: [i32; 2],
+    /// World position in centimeters
+    pub position: Vector3<f32>,
+    pub rotation: Quaternion,
+    pub scale: Vector3<f32>,
+    /// Parts of the object, `None` if the object isn't in the ZSC
+    pub parts: Option<Vec<InstancePart>>,
+}
+
+/// Model of a part of an instance, as client paths with forward slashes
+#[derive(Debug, PartialEq, Serialize)]
+pub struct InstancePart {
+    pub mesh: Option<String>,
+    pub texture: Option<String>,
+}
+
+/// Client paths of the decoration and construction ZSCs of the zone whose ZON
+/// file is `zon_path`, from `LIST_ZONE.STB`
+pub fn zone_scenes(list_zone: &STB, zon_path: &str) -> Option<(String, String)> {
+    let key = client_path_key(zon_path);
+    list_zone.data.iter().find_map(|row| {
+        let zone_file = row.get(ZONE_FILE_COLUMN)?;
+        if zone_file.is_empty() || client_path_key(zone_file) != key {
+            return None;
+        }
+        let column = |c: usize| row.get(c).map(|p| p.trim().to_string()).unwrap_or_default();
+        Some((column(DECORATIONS_COLUMN), column(BUILDINGS_COLUMN)))
+    })
+}
+
+/// Decorations and buildings of an IFO with the models of their objects in
+/// the zone's ZSCs
+pub fn instances(ifo: &IFO, decorations: &ZSC, buildings: &ZSC) -> Vec<Instance> {
+    let block = [ifo.map_pos.x, ifo.map_pos.y];
+    let decorations = ifo
+        .objects
+        .iter()
+        .map(|o| (InstanceKind::Decoration, o, decorations));
+    let buildings = ifo
+        .buildings
+        .iter()
+        .map(|o| (InstanceKind::Building, o, buildings));
+
+    decorations
+        .chain(buildings)
+        .map(|(kind, object, zsc)| Instance {
+            kind,
+            name: object.name.clone(),
+            object_id: object.object_id,
+            block,
+            position: Vector3 {
+                x: object.position.x + WORLD_OFFSET,
+                y: object.position.y + WORLD_OFFSET,
+                z: object.position.z,
+            },
+            rotation: object.rotation,
+            scale: object.scale,
+            parts: object_parts(zsc, object),
+        })
+        .collect()
+}
+
+/// Unique names of instances, `<kind>_<block x>_<block y>_<index>` with the
+/// index of the instance among those of its kind in its block
+pub fn instance_names(instances: &[Instance]) -> Vec<String> {
+    let mut counts = HashMap::new();
+    instances
+        .iter()
+        .map(|instance| {
+            let kind = match instance.kind {
+                InstanceKind::Decoration => "Decoration",
+                InstanceKind::Building => "Building",
+            };
+            let count = counts.entry((kind, instance.block)).or_insert(0);
+            let name = format!(
+                "{}_{}_{}_{}",
+                kind, instance.block[0], instance.block[1], count
+            );
+            *count += 1;
+            name
+        })
+        .collect()
+}
+
+/// Write instances as CSV for an Unreal Engine data table, a row for every
+/// instance named by [`instance_names`] with the properties in Unreal's text
+/// format
+pub fn to_unreal_csv(instances: &[Instance], dialect: &CsvDialect) -> Result<String, Error> {
+    let mut writer = dialect.writer_builder().from_writer(Vec::new());
+    writer.write_record([
+        "Name",
+        "Kind",
+        "ObjectName",
+        "ObjectId",
+        "Meshes",
+        "Textures",
+        "Location",
+        "Rotation",
+        "Scale",
+    ])?;
+
+    for (instance, name) in instances.iter().zip(instance_names(instances)) {
+        let kind = match instance.kind {
+            InstanceKind::Decoration => "Decoration",
+            InstanceKind::Building => "Building",
+        };
+        let parts = instance.parts.as_deref().unwrap_or_default();
+        let array = |paths: Vec<&Option<String>>| {
+            let paths: Vec<String> = paths
+                .into_iter()
+                .map(|p| format!("\"{}\"", p.as_deref().unwrap_or_default()))
+                .collect();
+            format!("({})", paths.join(","))
+        };
+        let [pitch, yaw, roll] = unreal_rotator(&instance.rotation);
+        let (position, scale) = (&instance.position, &instance.scale);
+        writer.write_record(&[
+            name,
+            kind.to_string(),
+            instance.name.clone(),
+            instance.object_id.to_string(),
+            array(parts.iter().map(|p| &p.mesh).collect()),
+            array(parts.iter().map(|p| &p.texture).collect()),
+            format!("(X={},Y={},Z={})", position.x, -position.y, position.z),
+            format!("(Pitch={},Yaw={},Roll={})", pitch, yaw, roll),
+            format!("(X={},Y={},Z={})", scale.x, scale.y, scale.z),
+        ])?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Pitch, yaw and roll in degrees of a rotation mirrored to Unreal's axes,
+/// like Unreal's conversion of quaternions to rotators
+fn unreal_rotator(rotation: &Quaternion) -> [f32; 3] {
+    // Mirroring Y flips the rotations around X and Z
+    let (x, y, z, w) = (-rotation.x, rotation.y, -rotation.z, rotation.w);
+    let singularity = z * x - w * y;
+    let yaw = (2.0 * (w * z + x * y))
+        .atan2(1.0 - 2.0 * (y * y + z * z))
+        .to_degrees();
+    let normalize = |angle: f32| (angle + 180.0).rem_euclid(360.0) - 180.0;
+    let [pitch, yaw, roll] = if singularity < -0.499_999_5 {
+        [-90.0, yaw, normalize(-yaw - 2.0 * x.atan2(w).to_degrees())]
+    } else if singularity > 0.499_999_5 {
+        [90.0, yaw, normalize(yaw - 2.0 * x.atan2(w).to_degrees())]
+    } else {
+        let pitch = (2.0 * singularity).asin().to_degrees();
+        let roll = (-2.0 * (w * x + y * z))
+            .atan2(1.0 - 2.0 * (x * x + y * y))
+            .to_degrees();
+        [pitch, yaw, roll]
+    };
+    // Without negative zeros
+    [pitch + 0.0, yaw + 0.0, roll + 0.0]
+}
+
+fn object_parts(zsc: &ZSC, object: &ObjectData) -> Option<Vec<InstancePart>> {
+    let scene_object = zsc.objects.get(usize::try_from(object.object_id).ok()?)?;
+    Some(
+        scene_object
+            .parts
+            .iter()
+            .map(|part| InstancePart {
+                mesh: zsc
+                    .meshes
+                    .get(part.mesh_id as usize)
+                    .map(|p| client_path(p)),
+                texture: zsc
+                    .materials
+                    .get(part.material_id as usize)
+                    .map(|m| client_path(&m.path)),
+            })
+            .collect(),
+    )
+}
+
+fn client_path(path: &Path) -> String {
+    path.to_string_lossy().trim().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use roselib::files::zsc::{SceneMaterial, SceneObject, SceneObjectPart};
+    use roselib::io::RoseFile;
+
+    fn zsc(mesh: &str, texture: &str) -> ZSC {
+        let mut zsc = ZSC::new();
+        zsc.meshes.push(PathBuf::from(mesh));
+        zsc.materials.push(SceneMaterial {
+            path: PathBuf::from(texture),
+            ..Default::default()
+        });
+        zsc.objects.push(SceneObject::default());
+        let mut object = SceneObject::default();
+        object.parts.push(SceneObjectPart::default());
+        object.parts.push(SceneObjectPart {
+            material_id: 1,
+            ..Default::default()
+        });
+        zsc.objects.push(object);
+        zsc
+    }
+
+    #[test]
+    fn test_zone_scenes() {
+        let mut list_zone = STB::new();
+        let mut row = vec![String::new(); 14];
+        row[ZONE_FILE_COLUMN] = String::from("3DDATA\\MAPS\\JUNON\\JPT01\\JPT01.ZON");
+        row[DECORATIONS_COLUMN] = String::from("3DDATA\\JUNON\\LIST_DECO_JPT.ZSC");
+        row[BUILDINGS_COLUMN] = String::from("3DDATA\\JUNON\\LIST_CNST_JPT.ZSC");
+        list_zone.data.push(vec![String::new(); 14]);
+        list_zone.data.push(row);
+
+        assert_eq!(
+            zone_scenes(&list_zone, "3ddata/maps/junon/jpt01/jpt01.zon"),
+            Some((
+                String::from("3DDATA\\JUNON\\LIST_DECO_JPT.ZSC"),
+                String::from("3DDATA\\JUNON\\LIST_CNST_JPT.ZSC")
+            ))
+        );
+        assert_eq!(
+            zone_scenes(&list_zone, "3DDATA/MAPS/JUNON/JPT02/JPT02.ZON"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_instances() {
+        let decorations = zsc("3DDATA\\DECO\\TREE.ZMS", "3DDATA\\DECO\\TREE.DDS");
+        let buildings = zsc("3DDATA\\CNST\\HOUSE.ZMS", "3DDATA\\CNST\\HOUSE.DDS");
+
+        let mut ifo = IFO::new();
+        ifo.map_pos.x = 31;
+        ifo.map_pos.y = 32;
+        ifo.objects.push(ObjectData {
+            name: String::from("tree"),
+            object_id: 1,
+            position: Vector3 {
+                x: -100.0,
+                y: 200.0,
+                z: 50.0,
+            },
+            ..Default::default()
+        });
+        ifo.buildings.push(ObjectData {
+            object_id: 1,
+            ..Default::default()
+        });
+        ifo.buildings.push(ObjectData {
+            object_id: 7,
+            ..Default::default()
+        });
+
+        let instances = instances(&ifo, &decorations, &buildings);
+        assert_eq!(instances.len(), 3);
+
+        let tree = &instances[0];
+        assert_eq!(tree.kind, InstanceKind::Decoration);
+        assert_eq!(tree.name, "tree");
+        assert_eq!(tree.block, [31, 32]);
+        assert_eq!(
+            tree.position,
+            Vector3 {
+                x: 519_900.0,
+                y: 520_200.0,
+                z: 50.0,
+            }
+        );
+        assert_eq!(
+            tree.parts,
+            Some(vec![
+                InstancePart {
+                    mesh: Some(String::from("3DDATA/DECO/TREE.ZMS")),
+                    texture: Some(String::from("3DDATA/DECO/TREE.DDS")),
+                },
+                InstancePart {
+                    mesh: Some(String::from("3DDATA/DECO/TREE.ZMS")),
+                    texture: None,
+                },
+            ])
+        );
+
+        assert_eq!(instances[1].kind, InstanceKind::Building);
+        let parts = instances[1].parts.as_ref().unwrap();
+        assert_eq!(parts[0].mesh.as_deref(), Some("3DDATA/CNST/HOUSE.ZMS"));
+        assert_eq!(instances[2].parts, None);
+    }
+
+    #[test]
+    fn test_to_unreal_csv() {
+        let decorations = zsc("3DDATA\\DECO\\TREE.ZMS", "3DDATA\\DECO\\TREE.DDS");
+        let mut ifo = IFO::new();
+        ifo.map_pos.x = 31;
+        ifo.map_pos.y = 32;
+        // Unturned, then turned a quarter counterclockwise from east to north
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        for &(z, w) in &[(0.0, 1.0), (half, half)] {
+            ifo.objects.push(ObjectData {
+                name: String::from("tree"),
+                object_id: 1,
+                position: Vector3 {
+                    x: 100.0,
+                    y: 200.0,
+                    z: 50.0,
+                },
+                rotation: Quaternion {
+                    x: 0.0,
+                    y: 0.0,
+                    z,
+                    w,
+                },
+                scale: Vector3 {
+                    x: 1.0,
+                    y: 1.0,
+                    z: 2.0,
+                },
+                ..Default::default()
+            });
+        }
+        ifo.buildings.push(ObjectData {
+            object_id: 7,
+            ..Default::default()
+        });
+
+        let instances = instances(&ifo, &decorations, &ZSC::new());
+        let csv = to_unreal_csv(&instances, &CsvDialect::default()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Name,Kind,ObjectName,ObjectId,Meshes,Textures,Location,Rotation,Scale"
+        );
+        assert_eq!(
+            lines[1],
+            "Decoration_31_32_0,Decoration,tree,1,\
+             \"(\"\"3DDATA/DECO/TREE.ZMS\"\",\"\"3DDATA/DECO/TREE.ZMS\"\")\",\
+             \"(\"\"3DDATA/DECO/TREE.DDS\"\",\"\"\"\")\",\
+             \"(X=520100,Y=-520200,Z=50)\",\"(Pitch=0,Yaw=0,Roll=0)\",\"(X=1,Y=1,Z=2)\""
+        );
+        // Clockwise from Unreal's X to Y
+        assert!(lines[2].starts_with("Decoration_31_32_1,"));
+        assert!(lines[2].contains("Yaw=-90"));
+        assert!(lines[3].starts_with("Building_31_32_0,Building,,7,(),(),"));
+    }
+}
@@ -0,0 +1,138 @@
+//! Formats of images read and written by the iconsheet and texture commands
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use failure::{bail, Error};
+use image::RgbaImage;
+
+use crate::{dds, tga, webp};
+
+/// Names of the formats, for the command line
+pub const IMAGE_FORMAT_NAMES: [&str; 4] = ["png", "webp", "tga", "bmp"];
+
+/// Format of an exported image
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    /// Lossless WebP
+    Webp,
+    /// 32-bit TGA with alpha
+    Tga,
+    /// 32-bit BMP with alpha
+    Bmp,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Result<ImageFormat, Error> {
+        match name.trim().to_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "webp" => Ok(ImageFormat::Webp),
+            "tga" => Ok(ImageFormat::Tga),
+            "bmp" => Ok(ImageFormat::Bmp),
+            _ => bail!("Unknown image format: {}", name),
+        }
+    }
+
+    /// File extension of the format, which is also its name
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Tga => "tga",
+            ImageFormat::Bmp => "bmp",
+        }
+    }
+
+    /// Write an image in this format, whatever the extension of the path
+    pub fn save(&self, img: &RgbaImage, path: &Path) -> Result<(), Error> {
+        let format = match self {
+            ImageFormat::Webp => return Ok(fs::write(path, webp::encode(img)?)?),
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Tga => image::ImageFormat::Tga,
+            ImageFormat::Bmp => image::ImageFormat::Bmp,
+        };
+        img.save_with_format(path, format)?;
+        Ok(())
+    }
+}
+
+/// Read an image as RGBA, DDS textures and TGA images with our own decoders
+/// since the image crate only reads DXT textures without their 1-bit alpha
+/// and not every kind of TGA
+pub fn read_image(path: &Path) -> Result<RgbaImage, Error> {
+    let data = fs::read(path)?;
+    if dds::is_dds(&data) {
+        return dds::decode(&data);
+    }
+    // TGA images can't be recognized by their content
+    if is_tga(path) {
+        return tga::decode(&data);
+    }
+    Ok(image::load_from_memory(&data)?.into_rgba8())
+}
+
+/// Width and height of an image without decoding it
+pub fn read_image_dimensions(path: &Path) -> Result<(u32, u32), Error> {
+    let mut header = Vec::new();
+    File::open(path)?.take(128).read_to_end(&mut header)?;
+    if dds::is_dds(&header) {
+        return dds::dimensions(&header);
+    }
+    if is_tga(path) {
+        return tga::dimensions(&header);
+    }
+    Ok(image::image_dimensions(path)?)
+}
+
+fn is_tga(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("tga"))
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    use image::Rgba;
+
+    #[test]
+    fn test_save() {
+        let mut img = RgbaImage::new(2, 2);
+        img.put_pixel(1, 0, Rgba([255, 0, 255, 128]));
+
+        for name in &IMAGE_FORMAT_NAMES {
+            let format = ImageFormat::from_name(name).unwrap();
+            assert_eq!(format.extension(), *name);
+
+            let path = env::temp_dir().join(format!("rose-conv-test-image.{}", name));
+            format.save(&img, &path).unwrap();
+            let data = fs::read(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            // The image crate can't read lossless WebP
+            let format = match format {
+                ImageFormat::Png => image::ImageFormat::Png,
+                ImageFormat::Tga => image::ImageFormat::Tga,
+                ImageFormat::Bmp => image::ImageFormat::Bmp,
+                ImageFormat::Webp => {
+                    assert_eq!(&data[8..16], b"WEBPVP8L");
+                    continue;
+                }
+            };
+            let saved = image::load_from_memory_with_format(&data, format).unwrap();
+            assert_eq!(saved.into_rgba8(), img);
+        }
+        assert!(ImageFormat::from_name("jpg").is_err());
+    }
+}
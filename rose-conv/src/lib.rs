@@ -1,103 +1,123 @@
+pub mod aip_check;
+pub mod axes;
+pub mod bitmap_font;
+pub mod bounds;
+pub mod cache;
+pub mod client;
+pub mod client_diff;
+pub mod client_manifest;
+pub mod codegen;
+pub mod collision;
+pub mod config;
+pub mod csv_dialect;
+pub mod dds;
+pub mod doctor;
+pub mod encoding_report;
+pub mod file_info;
+pub mod gltf;
+pub mod godot;
+pub mod grep;
+pub mod iconsheet;
+pub mod ifo_instances;
+pub mod image_format;
+pub mod lightmap;
+pub mod manifest;
+pub mod map_spawns;
+pub mod minimap;
+pub mod naming;
+pub mod navmesh;
+pub mod normal_map;
+pub mod patch;
+pub mod qsd_check;
+pub mod references;
+pub mod regex;
+pub mod report;
+pub mod schema;
+pub mod script;
+pub mod splatmap;
+pub mod stats;
+pub mod stb_csv;
+pub mod stb_diff;
+pub mod stb_join;
+pub mod stb_json;
+pub mod stb_merge;
+pub mod stb_schema;
+pub mod stb_sqlite;
+pub mod stl_csv;
+pub mod stl_diff;
+pub mod stl_duplicates;
+pub mod stl_po;
+pub mod stl_report;
+pub mod terrain;
+pub mod tga;
+pub mod tile_pyramid;
+pub mod tsi_atlas;
+pub mod validate;
+pub mod vfs;
+pub mod warp_graph;
+pub mod water;
+pub mod webp;
+pub mod world_atlas;
+pub mod xlsx;
+pub mod zip;
+pub mod zmd_viz;
+pub mod zmo_events;
+pub mod zmo_resample;
+pub mod zmo_root_motion;
+pub mod zms_merge;
+pub mod zms_normals;
+pub mod zms_obj;
+pub mod zms_optimize;
+pub mod zms_simplify;
+pub mod zms_skin;
+pub mod zms_tangents;
+pub mod zms_weld;
+pub mod zone_manifest;
+pub mod zsc_bundle;
+pub mod zsc_materials;
+
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
-use std::str::FromStr;
 
-use failure::{bail, Error};
+use failure::Error;
 
 use roselib::files::stl::*;
 use roselib::files::*;
 use roselib::io::RoseFile;
 
-pub trait ToCsv {
-    fn to_csv(&self) -> Result<String, Error>;
-}
+use crate::csv_dialect::CsvDialect;
+use crate::stb_csv::StbCsv;
 
-impl ToCsv for STB {
+pub trait ToCsv {
     fn to_csv(&self) -> Result<String, Error> {
-        let mut writer = csv::Writer::from_writer(Vec::new());
+        self.to_csv_with(&CsvDialect::default())
+    }
 
-        writer.write_record(&self.headers)?;
-        for row in &self.data {
-            writer.write_record(row)?;
-        }
+    fn to_csv_with(&self, dialect: &CsvDialect) -> Result<String, Error>;
+}
 
-        let data = String::from_utf8(writer.into_inner()?)?;
-        return Ok(data);
+impl ToCsv for STB {
+    fn to_csv_with(&self, dialect: &CsvDialect) -> Result<String, Error> {
+        stb_csv::write(self, &self.headers, false, dialect)
     }
 }
 
 impl ToCsv for STL {
-    fn to_csv(&self) -> Result<String, Error> {
-        let mut writer = csv::Writer::from_writer(Vec::new());
-
-        let mut headers = Vec::new();
-        let mut headers2 = Vec::new();
-
-        headers.push(self.format.to_string());
-        headers2.push("Row ID");
-
-        // Second and third column contain our keys
-        headers.push(String::new());
-        headers.push(String::new());
-        headers2.push("Key ID");
-        headers2.push("Key Name");
-
-        for table in &self.language_tables {
-            headers.push(table.language.to_string());
-            headers2.push("Text");
-
-            if self.format == StringTableType::Item || self.format == StringTableType::Quest {
-                headers.push(String::new());
-                headers2.push("Description");
-            }
-
-            if self.format == StringTableType::Quest {
-                headers.push(String::new());
-                headers.push(String::new());
-                headers2.push("Start Message");
-                headers2.push("End Message");
-            }
-        }
-
-        writer.write_record(&headers)?;
-        writer.write_record(&headers2)?;
-
-        for row_idx in 0..self.row_count() {
-            let mut row = Vec::new();
-            row.push(row_idx.to_string());
-
-            let key = &self.keys[row_idx];
-            row.push(key.id.to_string());
-            row.push(key.name.clone());
-
-            for table in &self.language_tables {
-                let table_row = &table.rows[row_idx];
-                match table_row {
-                    StringTableRow::NormalRow(data) => row.push(data.text.clone()),
-                    StringTableRow::ItemRow(data) => {
-                        row.push(data.text.clone());
-                        row.push(data.description.clone());
-                    }
-                    StringTableRow::QuestRow(data) => {
-                        row.push(data.text.clone());
-                        row.push(data.description.clone());
-                        row.push(data.start_message.clone());
-                        row.push(data.end_message.clone());
-                    }
-                }
-            }
-
-            writer.write_record(&row)?;
-        }
-
-        let data = String::from_utf8(writer.into_inner()?)?;
-        return Ok(data);
+    fn to_csv_with(&self, dialect: &CsvDialect) -> Result<String, Error> {
+        stl_csv::write(self, &[], dialect)
     }
 }
 
 pub trait FromCsv {
     fn from_csv(s: &str) -> Result<Self, Error>
+    where
+        Self: std::marker::Sized,
+    {
+        Self::from_csv_with(s, &CsvDialect::default())
+    }
+
+    fn from_csv_with(s: &str, dialect: &CsvDialect) -> Result<Self, Error>
     where
         Self: std::marker::Sized;
 
@@ -115,126 +135,29 @@ pub trait FromCsv {
 }
 
 impl FromCsv for STB {
-    fn from_csv(s: &str) -> Result<Self, Error>
+    fn from_csv_with(s: &str, dialect: &CsvDialect) -> Result<Self, Error>
     where
         Self: std::marker::Sized,
     {
-        let mut stb = STB::new();
-
-        let mut reader = csv::Reader::from_reader(s.as_bytes());
-        for header in reader.headers()? {
-            stb.headers.push(header.to_string())
-        }
+        let csv = StbCsv::parse(s, dialect)?;
 
-        for record in reader.records() {
-            let mut row = Vec::new();
-            for field in record?.iter() {
-                row.push(field.to_string());
-            }
-            stb.data.push(row);
-        }
+        let mut stb = STB::new();
+        stb.headers = csv.headers.unwrap_or(csv.names);
+        stb.root_row = csv.root_row.unwrap_or_default();
+        stb.row_height = csv.row_height;
+        stb.column_widths = csv.column_widths;
+        stb.data = csv.rows;
 
         return Ok(stb);
     }
 }
 
 impl FromCsv for STL {
-    fn from_csv(s: &str) -> Result<Self, Error>
+    fn from_csv_with(s: &str, dialect: &CsvDialect) -> Result<Self, Error>
     where
         Self: std::marker::Sized,
     {
-        let mut stl = STL::new();
-        let mut reader = csv::Reader::from_reader(s.as_bytes());
-        stl.format = StringTableType::from_str(reader.headers()?.get(0).unwrap_or_default())?;
-
-        let headers: Vec<&str> = reader.headers()?.iter().collect();
-
-        // Get the number of columns after key columns
-        let data_col_count = headers.len() - 3;
-        if data_col_count <= 0 {
-            bail!(
-                "Invalid number of headers in the CSV file, expected at least 4, found {}",
-                headers.len()
-            );
-        }
-
-        let language_count = match stl.format {
-            StringTableType::Normal => data_col_count,
-            StringTableType::Item => data_col_count / 2,
-            StringTableType::Quest => data_col_count / 4,
-        };
-
-        for language_idx in 0..language_count {
-            stl.language_tables.push(StringTableLanguageTable {
-                language: StringTableLanguage::from(language_idx as u32),
-                rows: Vec::new(),
-            });
-        }
-
-        // Skip the second header line
-        let records = reader.records().skip(1);
-
-        let mut record_idx = 0;
-        for record in records {
-            record_idx += 1;
-
-            let record = record?;
-            let fields: Vec<&str> = record.iter().collect();
-            if fields.len() < 3 {
-                bail!(
-                    "STL row #{}, expected at least 4 columns, found {}.",
-                    record_idx,
-                    fields.len()
-                );
-            }
-
-            // Ignore first column, it's only written for readability of the csv
-
-            // Read key values from 2nd and 3rd column
-            stl.keys.push(StringTableKey {
-                id: str::parse(fields[1])?,
-                name: fields[2].into(),
-            });
-
-            // Read tables from remaining columns depending on STL format
-            match &stl.format {
-                StringTableType::Normal => {
-                    // Every column after 2 is a row from a different normal table
-                    for idx in 3..fields.len() {
-                        let row = StringTableRow::NormalRow(NormalRowData {
-                            text: fields[idx].into(),
-                        });
-                        let language_idx = idx - 3;
-                        stl.language_tables[language_idx].rows.push(row);
-                    }
-                }
-                StringTableType::Item => {
-                    // Every 2 columns after 2 is a row from a different item table
-                    for idx in (3..fields.len()).step_by(2) {
-                        let row = StringTableRow::ItemRow(ItemRowData {
-                            text: fields[idx].into(),
-                            description: fields[idx + 1].into(),
-                        });
-                        let language_idx = (idx - 3) / 2;
-                        stl.language_tables[language_idx].rows.push(row);
-                    }
-                }
-                StringTableType::Quest => {
-                    // Every 2 columns after 2 is a row from a different item table
-                    for idx in (3..fields.len()).step_by(4) {
-                        let row = StringTableRow::QuestRow(QuestRowData {
-                            text: fields[idx].into(),
-                            description: fields[idx + 1].into(),
-                            start_message: fields[idx + 2].into(),
-                            end_message: fields[idx + 3].into(),
-                        });
-                        let language_idx = (idx - 3) / 4;
-                        stl.language_tables[language_idx].rows.push(row);
-                    }
-                }
-            }
-        }
-        Ok(stl)
+        stl_csv::read(s, dialect)
     }
 }
 
@@ -245,6 +168,13 @@ pub trait ToJson {
     {
         Ok(serde_json::to_string_pretty(self)?)
     }
+
+    fn to_json_compact(&self) -> Result<String, Error>
+    where
+        Self: serde::ser::Serialize,
+    {
+        Ok(serde_json::to_string(self)?)
+    }
 }
 
 impl<F> ToJson for F where F: RoseFile {}
@@ -272,6 +202,84 @@ pub trait FromJson {
 
 impl<F> FromJson for F where F: RoseFile {}
 
+pub trait ToYaml {
+    fn to_yaml(&self) -> Result<String, Error>
+    where
+        Self: serde::ser::Serialize,
+    {
+        Ok(serde_yaml::to_string(self)?)
+    }
+}
+
+impl<F> ToYaml for F where F: RoseFile {}
+
+pub trait FromYaml {
+    fn from_yaml(s: &str) -> Result<Self, Error>
+    where
+        Self: std::marker::Sized + serde::de::DeserializeOwned,
+    {
+        Ok(serde_yaml::from_str(s)?)
+    }
+
+    fn from_yaml_path(p: &Path) -> Result<Self, Error>
+    where
+        Self: std::marker::Sized + serde::de::DeserializeOwned,
+    {
+        let mut s = String::new();
+
+        let mut f = File::open(p)?;
+        f.read_to_string(&mut s)?;
+
+        Self::from_yaml(&s)
+    }
+}
+
+impl<F> FromYaml for F where F: RoseFile {}
+
+pub trait ToMsgpack {
+    fn to_msgpack(&self) -> Result<Vec<u8>, Error>
+    where
+        Self: serde::ser::Serialize,
+    {
+        Ok(rmp_serde::to_vec_named(self)?)
+    }
+}
+
+impl<F> ToMsgpack for F where F: RoseFile {}
+
+pub trait FromMsgpack {
+    fn from_msgpack(data: &[u8]) -> Result<Self, Error>
+    where
+        Self: std::marker::Sized + serde::de::DeserializeOwned,
+    {
+        Ok(rmp_serde::from_slice(data)?)
+    }
+}
+
+impl<F> FromMsgpack for F where F: RoseFile {}
+
+pub trait ToCbor {
+    fn to_cbor(&self) -> Result<Vec<u8>, Error>
+    where
+        Self: std::marker::Sized + serde::ser::Serialize,
+    {
+        Ok(serde_cbor::to_vec(self)?)
+    }
+}
+
+impl<F> ToCbor for F where F: RoseFile {}
+
+pub trait FromCbor {
+    fn from_cbor(data: &[u8]) -> Result<Self, Error>
+    where
+        Self: std::marker::Sized + serde::de::DeserializeOwned,
+    {
+        Ok(serde_cbor::from_slice(data)?)
+    }
+}
+
+impl<F> FromCbor for F where F: RoseFile {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -296,6 +304,25 @@ mod tests {
         }};
     }
 
+    macro_rules! test_binary {
+        ($filetype: ident, $path: expr) => {{
+            let orig_file = $filetype::from_path(&$path).unwrap();
+            let msgpack = orig_file.to_msgpack().unwrap();
+            assert_eq!(orig_file, $filetype::from_msgpack(&msgpack).unwrap());
+            let cbor = orig_file.to_cbor().unwrap();
+            assert_eq!(orig_file, $filetype::from_cbor(&cbor).unwrap());
+        }};
+    }
+
+    macro_rules! test_yaml {
+        ($filetype: ident, $path: expr) => {{
+            let orig_file = $filetype::from_path(&$path).unwrap();
+            let yaml_string = orig_file.to_yaml().unwrap();
+            let new_file = $filetype::from_yaml(&yaml_string).unwrap();
+            assert_eq!(orig_file, new_file);
+        }};
+    }
+
     #[test]
     fn test_csv() {
         let mut root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -323,4 +350,30 @@ mod tests {
         test_json!(ZSC, root.join("list_weapon.zsc"));
         test_json!(ZSC, root.join("part_npc.zsc"));
     }
+
+    #[test]
+    fn test_yaml() {
+        let mut root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        root.pop();
+        root.push("rose-lib");
+        root.push("tests");
+        root.push("data");
+
+        test_yaml!(IDX, root.join("data.idx"));
+        test_yaml!(LIT, root.join("OBJECTLIGHTMAPDATA.LIT"));
+        test_yaml!(ZSC, root.join("list_weapon.zsc"));
+    }
+
+    #[test]
+    fn test_binary() {
+        let mut root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        root.pop();
+        root.push("rose-lib");
+        root.push("tests");
+        root.push("data");
+
+        test_binary!(IDX, root.join("data.idx"));
+        test_binary!(LIT, root.join("OBJECTLIGHTMAPDATA.LIT"));
+        test_binary!(ZSC, root.join("list_weapon.zsc"));
+    }
 }
@@ -0,0 +1,198 @@
+//! Lightmaps of LIT files
+//!
+//! A LIT lists the parts of the objects of a map block with baked lighting.
+//! The lightmaps of the parts are packed into DDS pages next to the LIT: a
+//! page is split into a square grid of `parts_per_width` cells on each side
+//! and a part's lightmap is the cell at `part_position`, counted row by row
+//! from the top left. The second UV channel of the part's mesh is scaled into
+//! its cell.
+//!
+//! Engines that won't use the lightmaps get them baked into vertex colors.
+//! ROSE modulates textures with twice the lightmap, so the colors are
+//! doubled, up to white.
+use std::convert::TryFrom;
+
+use image::{imageops, RgbaImage};
+use serde::Serialize;
+
+use roselib::files::lit::LightmapPart;
+use roselib::files::zms::VertexFormat;
+use roselib::files::{LIT, ZMS};
+use roselib::utils::Color4;
+
+/// Lightmap of a part of an object, on its page
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LightmapEntry {
+    /// Id of the object, as in the LIT
+    pub object: i32,
+    /// Id of the part in its object
+    pub part: i32,
+    pub name: String,
+    /// File name of the DDS page, next to the LIT
+    pub page: String,
+    /// Rectangle on the page in UV coordinates: left, top, right, bottom
+    pub uv: [f32; 4],
+    /// Rectangle on the page in pixels: x, y, width, height, `None` if the
+    /// page wasn't read
+    pub rect: Option<[u32; 4]>,
+    /// Cropped lightmap, relative to the mapping
+    pub image: Option<String>,
+}
+
+/// Lightmaps of the parts of a LIT, without the parts missing from the grid
+/// of their page
+pub fn lightmap_entries(lit: &LIT) -> Vec<LightmapEntry> {
+    lit.objects
+        .iter()
+        .flat_map(|object| object.parts.iter().map(move |part| (object.id, part)))
+        .filter_map(|(object, part)| {
+            let page = usize::try_from(part.lightmap_index)
+                .ok()
+                .and_then(|idx| lit.filenames.get(idx))
+                .unwrap_or(&part.filename);
+            Some(LightmapEntry {
+                object,
+                part: part.id,
+                name: part.name.clone(),
+                page: page.clone(),
+                uv: uv_rect(part)?,
+                rect: None,
+                image: None,
+            })
+        })
+        .collect()
+}
+
+/// Cell of a part on its page in UV coordinates, `None` if it's outside the
+/// grid
+pub fn uv_rect(part: &LightmapPart) -> Option<[f32; 4]> {
+    let cells = part.parts_per_width;
+    if cells <= 0 || part.part_position < 0 || part.part_position >= cells * cells {
+        return None;
+    }
+    let size = 1.0 / cells as f32;
+    let left = (part.part_position % cells) as f32 * size;
+    let top = (part.part_position / cells) as f32 * size;
+    Some([left, top, left + size, top + size])
+}
+
+/// Pixels of a UV rectangle on a page of `width` by `height` pixels
+pub fn pixel_rect(uv: &[f32; 4], width: u32, height: u32) -> [u32; 4] {
+    let x = (uv[0] * width as f32).round() as u32;
+    let y = (uv[1] * height as f32).round() as u32;
+    let right = ((uv[2] * width as f32).round() as u32).min(width);
+    let bottom = ((uv[3] * height as f32).round() as u32).min(height);
+    [x, y, right.saturating_sub(x), bottom.saturating_sub(y)]
+}
+
+/// Crop the lightmap of an entry from its page, recording the rectangle
+pub fn crop(entry: &mut LightmapEntry, page: &RgbaImage) -> RgbaImage {
+    let [x, y, width, height] = pixel_rect(&entry.uv, page.width(), page.height());
+    entry.rect = Some([x, y, width, height]);
+    imageops::crop_imm(page, x, y, width, height).to_image()
+}
+
+/// Lightmap of a part: its page and its cell on it in UV coordinates
+pub struct PartLightmap<'a> {
+    pub page: &'a RgbaImage,
+    pub uv: [f32; 4],
+}
+
+/// Bake a lightmap into the vertex colors of a mesh, sampled at its second
+/// UV channel. Returns `false` if the mesh has no second UV channel.
+pub fn bake(zms: &mut ZMS, lightmap: &PartLightmap) -> bool {
+    if !zms.uv2_enabled() {
+        return false;
+    }
+    let (page_width, page_height) = lightmap.page.dimensions();
+    let [x0, y0, width, height] = pixel_rect(&lightmap.uv, page_width, page_height);
+    if width == 0 || height == 0 {
+        return false;
+    }
+    for vertex in &mut zms.vertices {
+        // Nearest pixel of the cell, without bleeding into the next one
+        let x = ((vertex.uv2.x.clamp(0.0, 1.0) * width as f32) as u32).min(width - 1);
+        let y = ((vertex.uv2.y.clamp(0.0, 1.0) * height as f32) as u32).min(height - 1);
+        let pixel = lightmap.page.get_pixel(x0 + x, y0 + y);
+        let channel = |c: u8| (c as f32 * 2.0 / 255.0).min(1.0);
+        vertex.color = Color4 {
+            r: channel(pixel[0]),
+            g: channel(pixel[1]),
+            b: channel(pixel[2]),
+            a: 1.0,
+        };
+    }
+    zms.format |= VertexFormat::Color as i32;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::Rgba;
+    use roselib::files::lit::LightmapObject;
+    use roselib::io::RoseFile;
+
+    fn part(id: i32, lightmap_index: i32, part_position: i32) -> LightmapPart {
+        LightmapPart {
+            name: format!("Part{}", id),
+            id,
+            filename: String::from("Object_0.dds"),
+            lightmap_index,
+            pixels_per_part: 64,
+            parts_per_width: 4,
+            part_position,
+        }
+    }
+
+    #[test]
+    fn test_lightmap_entries() {
+        let mut lit = LIT::new();
+        lit.filenames.push(String::from("Object_0.dds"));
+        lit.filenames.push(String::from("Object_1.dds"));
+        lit.objects.push(LightmapObject {
+            id: 3,
+            parts: vec![part(0, 1, 6), part(1, 0, 16), part(2, 5, 0)],
+        });
+
+        let mut entries = lightmap_entries(&lit);
+        // The second part is past the last cell
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].object, 3);
+        assert_eq!(entries[0].page, "Object_1.dds");
+        assert_eq!(entries[0].uv, [0.5, 0.25, 0.75, 0.5]);
+        // Pages missing from the list fall back to the part's file name
+        assert_eq!(entries[1].page, "Object_0.dds");
+
+        // A page smaller than the grid of the LIT
+        let mut page = RgbaImage::new(128, 128);
+        page.put_pixel(64, 32, Rgba([255, 0, 0, 255]));
+        let img = crop(&mut entries[0], &page);
+        assert_eq!(entries[0].rect, Some([64, 32, 32, 32]));
+        assert_eq!(img.dimensions(), (32, 32));
+        assert_eq!(img.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+
+        page.put_pixel(95, 32, Rgba([64, 128, 200, 255]));
+        page.put_pixel(96, 32, Rgba([255, 255, 255, 255]));
+        let lightmap = PartLightmap {
+            page: &page,
+            uv: entries[0].uv,
+        };
+        let mut zms = ZMS::new();
+        zms.format = VertexFormat::Position as i32;
+        zms.vertices.push(Default::default());
+        zms.vertices.push(Default::default());
+        assert!(!bake(&mut zms, &lightmap));
+
+        zms.format |= VertexFormat::UV2 as i32;
+        zms.vertices[1].uv2.x = 1.0;
+        assert!(bake(&mut zms, &lightmap));
+        assert!(zms.colors_enabled());
+        let color = &zms.vertices[0].color;
+        assert_eq!([color.r, color.g, color.b, color.a], [1.0, 0.0, 0.0, 1.0]);
+        // The right edge of the cell is its last pixel, doubled
+        let color = &zms.vertices[1].color;
+        assert_eq!([color.r, color.g, color.b], [128.0 / 255.0, 1.0, 1.0]);
+    }
+}
@@ -0,0 +1,120 @@
+//! Manifest of a conversion run, mapping each input to its outputs
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use failure::Error;
+use serde::Serialize;
+
+use crate::cache::file_hash;
+use crate::report::{ConversionStatus, Report};
+
+/// Default name of the manifest file in the output directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ManifestOutput {
+    pub path: PathBuf,
+    pub checksum: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ManifestEntry {
+    #[serde(rename = "type")]
+    pub file_type: String,
+    /// Checksum of the input file, not set for directory inputs (e.g. maps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    pub outputs: Vec<ManifestOutput>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Manifest {
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Build a manifest from the successful conversions in a report. All
+    /// inputs and outputs must exist to compute their checksums.
+    pub fn from_report(report: &Report) -> Result<Manifest, Error> {
+        let mut manifest = Manifest::default();
+
+        for result in &report.results {
+            if result.status != ConversionStatus::Ok {
+                continue;
+            }
+
+            let checksum = if result.input.is_file() {
+                Some(file_hash(&result.input)?)
+            } else {
+                None
+            };
+
+            let mut outputs = Vec::new();
+            for path in &result.outputs {
+                outputs.push(ManifestOutput {
+                    path: path.clone(),
+                    checksum: file_hash(path)?,
+                });
+            }
+
+            manifest.files.insert(
+                result.input.to_string_lossy().into_owned(),
+                ManifestEntry {
+                    file_type: result.file_type.clone().unwrap_or_default(),
+                    checksum,
+                    outputs,
+                },
+            );
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn write_to_path(&self, path: &Path) -> Result<(), Error> {
+        let f = File::create(path)?;
+        serde_json::to_writer_pretty(f, self)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use failure::format_err;
+
+    #[test]
+    fn test_manifest() {
+        let dir = std::env::temp_dir().join("rose-conv-test-manifest");
+        fs::create_dir_all(&dir).unwrap();
+
+        let input = dir.join("input.stb");
+        let output = dir.join("input.csv");
+        fs::write(&input, "data").unwrap();
+        fs::write(&output, "").unwrap();
+
+        let mut report = Report::new();
+        report.success(&input, "stb", vec![output.clone()]);
+        report.failure(&dir.join("failed.stb"), &format_err!("Invalid file"));
+
+        let manifest = Manifest::from_report(&report).unwrap();
+        assert_eq!(manifest.files.len(), 1);
+
+        let entry = &manifest.files[input.to_str().unwrap()];
+        assert_eq!(entry.file_type, "stb");
+        assert_eq!(
+            entry.checksum.as_deref(),
+            Some("3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7")
+        );
+        assert_eq!(entry.outputs[0].path, output);
+        assert_eq!(
+            entry.outputs[0].checksum,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
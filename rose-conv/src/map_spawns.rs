@@ -0,0 +1,244 @@
+//! Monster spawns and NPCs placed by the IFO files of zones
+//!
+//! Positions are world positions in centimeters, see
+//! [`WORLD_OFFSET`](crate::ifo_instances::WORLD_OFFSET). As CSV, every monster
+//! of a spawn point and every NPC is a row.
+use failure::Error;
+use serde::Serialize;
+
+use roselib::files::ifo::{self, ObjectData};
+use roselib::files::IFO;
+use roselib::utils::{Quaternion, Vector3};
+
+use crate::csv_dialect::CsvDialect;
+use crate::ifo_instances::WORLD_OFFSET;
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Spawns {
+    pub monsters: Vec<MonsterSpawn>,
+    pub npcs: Vec<NpcPlacement>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct MonsterSpawn {
+    pub zone: String,
+    /// Map block of the IFO
+    pub block: [i32; 2],
+    pub name: String,
+    pub position: Vector3<f32>,
+    /// Monsters spawned regularly
+    pub basic: Vec<SpawnMonster>,
+    /// Monsters spawned once the tactical variable is reached
+    pub tactical: Vec<SpawnMonster>,
+    pub interval: u32,
+    pub limit: u32,
+    pub range: u32,
+    pub tactical_variable: u32,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SpawnMonster {
+    pub name: String,
+    pub monster: u32,
+    pub count: u32,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NpcPlacement {
+    pub zone: String,
+    pub block: [i32; 2],
+    pub name: String,
+    /// Row of the NPC in `LIST_NPC.STB`
+    pub npc: i32,
+    pub ai: i32,
+    /// Conversation file of the NPC
+    pub file: String,
+    pub position: Vector3<f32>,
+    pub rotation: Quaternion,
+}
+
+impl Spawns {
+    pub fn new() -> Spawns {
+        Spawns::default()
+    }
+
+    /// Add the monster spawns and NPCs of an IFO of `zone`
+    pub fn add(&mut self, zone: &str, ifo: &IFO) {
+        let block = [ifo.map_pos.x, ifo.map_pos.y];
+        for spawn in &ifo.monster_spawns {
+            let monsters = |points: &[ifo::MonsterSpawnPoint]| {
+                points
+                    .iter()
+                    .map(|p| SpawnMonster {
+                        name: p.name.clone(),
+                        monster: p.monster,
+                        count: p.count,
+                    })
+                    .collect()
+            };
+            self.monsters.push(MonsterSpawn {
+                zone: zone.to_string(),
+                block,
+                name: spawn.name.clone(),
+                position: world_position(&spawn.data),
+                basic: monsters(&spawn.basic_spawns),
+                tactical: monsters(&spawn.tactical_spawns),
+                interval: spawn.interval,
+                limit: spawn.limit,
+                range: spawn.range,
+                tactical_variable: spawn.tactical_variable,
+            });
+        }
+        for npc in &ifo.npcs {
+            self.npcs.push(NpcPlacement {
+                zone: zone.to_string(),
+                block,
+                name: npc.data.name.clone(),
+                npc: npc.data.object_id,
+                ai: npc.ai,
+                file: npc.file.clone(),
+                position: world_position(&npc.data),
+                rotation: npc.data.rotation,
+            });
+        }
+    }
+
+    /// Write the spawns as CSV with a row for every monster of a spawn point
+    /// and every NPC
+    pub fn to_csv(&self, dialect: &CsvDialect) -> Result<String, Error> {
+        let mut writer = dialect.writer_builder().from_writer(Vec::new());
+        writer.write_record([
+            "Type", "Zone", "Block X", "Block Y", "Name", "ID", "Count", "Tactical", "Interval",
+            "Limit", "Range", "X", "Y", "Z",
+        ])?;
+
+        for spawn in &self.monsters {
+            let tactical = spawn.tactical.iter().map(|m| (m, true));
+            for (monster, is_tactical) in spawn.basic.iter().map(|m| (m, false)).chain(tactical) {
+                writer.write_record(&[
+                    String::from("monster"),
+                    spawn.zone.clone(),
+                    spawn.block[0].to_string(),
+                    spawn.block[1].to_string(),
+                    spawn.name.clone(),
+                    monster.monster.to_string(),
+                    monster.count.to_string(),
+                    is_tactical.to_string(),
+                    spawn.interval.to_string(),
+                    spawn.limit.to_string(),
+                    spawn.range.to_string(),
+                    spawn.position.x.to_string(),
+                    spawn.position.y.to_string(),
+                    spawn.position.z.to_string(),
+                ])?;
+            }
+        }
+
+        for npc in &self.npcs {
+            writer.write_record(&[
+                String::from("npc"),
+                npc.zone.clone(),
+                npc.block[0].to_string(),
+                npc.block[1].to_string(),
+                npc.name.clone(),
+                npc.npc.to_string(),
+                String::from("1"),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                npc.position.x.to_string(),
+                npc.position.y.to_string(),
+                npc.position.z.to_string(),
+            ])?;
+        }
+
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+fn world_position(data: &ObjectData) -> Vector3<f32> {
+    Vector3 {
+        x: data.position.x + WORLD_OFFSET,
+        y: data.position.y + WORLD_OFFSET,
+        z: data.position.z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::ifo::{MonsterSpawnPoint, NpcData};
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_spawns() {
+        let mut ifo = IFO::new();
+        ifo.map_pos.x = 31;
+        ifo.map_pos.y = 32;
+        ifo.monster_spawns.push(ifo::MonsterSpawn {
+            data: ObjectData {
+                position: Vector3 {
+                    x: 100.0,
+                    y: -100.0,
+                    z: 0.0,
+                },
+                ..Default::default()
+            },
+            name: String::from("Jellies"),
+            basic_spawns: vec![MonsterSpawnPoint {
+                name: String::from("Jelly Bean"),
+                monster: 1,
+                count: 3,
+            }],
+            tactical_spawns: vec![MonsterSpawnPoint {
+                name: String::from("Big Jelly Bean"),
+                monster: 2,
+                count: 1,
+            }],
+            interval: 30,
+            limit: 10,
+            range: 500,
+            tactical_variable: 20,
+        });
+        ifo.npcs.push(NpcData {
+            data: ObjectData {
+                name: String::from("Judith"),
+                object_id: 1001,
+                ..Default::default()
+            },
+            ai: 1001,
+            file: String::from("3DDATA\\EVENT\\JUDITH.CON"),
+        });
+
+        let mut spawns = Spawns::new();
+        spawns.add("JPT01", &ifo);
+        assert_eq!(spawns.monsters.len(), 1);
+        let spawn = &spawns.monsters[0];
+        assert_eq!(spawn.zone, "JPT01");
+        assert_eq!(spawn.block, [31, 32]);
+        assert_eq!(spawn.position.x, 520_100.0);
+        assert_eq!(spawn.position.y, 519_900.0);
+        assert_eq!(spawn.basic[0].monster, 1);
+        assert_eq!(spawn.tactical[0].count, 1);
+        assert_eq!(spawns.npcs[0].npc, 1001);
+        assert_eq!(spawns.npcs[0].file, "3DDATA\\EVENT\\JUDITH.CON");
+
+        let csv = spawns.to_csv(&CsvDialect::default()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[1],
+            "monster,JPT01,31,32,Jellies,1,3,false,30,10,500,520100,519900,0"
+        );
+        assert_eq!(
+            lines[2],
+            "monster,JPT01,31,32,Jellies,2,1,true,30,10,500,520100,519900,0"
+        );
+        assert_eq!(
+            lines[3],
+            "npc,JPT01,31,32,Judith,1001,1,,,,,520000,520000,0"
+        );
+    }
+}
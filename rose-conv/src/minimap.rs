@@ -0,0 +1,260 @@
+//! Top-down images of zones
+//!
+//! Every map block is 16x16 tiles. A tile of the TIL references a tile of the
+//! ZON, which has two texture layers: the second layer is drawn over the first
+//! with its alpha if the tile blends, rotated or flipped by the tile's
+//! rotation. Textures are resized to the size of a tile in the image, so a
+//! small tile size averages them.
+//!
+//! Hillshading darkens the slopes facing away from a light in the north-west
+//! (the top left of the image) and brightens the ones facing it, using the
+//! 65x65 heights of the block's HIM. Flat terrain keeps its color.
+use std::convert::TryFrom;
+
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+
+use roselib::files::zon::{ZoneTile, ZoneTileRotation};
+use roselib::files::{HIM, TIL};
+
+/// Tiles of a map block on each axis
+pub const BLOCK_TILES: u32 = 16;
+
+/// Distance between the heights of a HIM in centimeters
+const GRID_SIZE: f32 = 250.0;
+
+/// Color of tiles whose textures are missing
+const MISSING_COLOR: Rgba<u8> = Rgba([128, 128, 128, 255]);
+
+pub struct Minimap<'a> {
+    tiles: &'a [ZoneTile],
+    /// Textures of the ZON resized to the tile size, `None` if missing
+    textures: Vec<Option<RgbaImage>>,
+    tile_size: u32,
+}
+
+impl<'a> Minimap<'a> {
+    /// Minimap of the tiles and textures of a ZON, with `tile_size` pixels per
+    /// tile
+    pub fn new(
+        tiles: &'a [ZoneTile],
+        textures: Vec<Option<RgbaImage>>,
+        tile_size: u32,
+    ) -> Minimap<'a> {
+        let textures = textures
+            .into_iter()
+            .map(|texture| texture.map(|t| resize_texture(&t, tile_size)))
+            .collect();
+        Minimap {
+            tiles,
+            textures,
+            tile_size,
+        }
+    }
+
+    /// Pixels of a map block on each axis
+    pub fn block_size(&self) -> u32 {
+        self.tile_size * BLOCK_TILES
+    }
+
+    /// Draw the tiles of a block with its top left corner at `(x, y)`
+    pub fn draw_block(&self, img: &mut RgbaImage, x: u32, y: u32, til: &TIL) {
+        for (row, tiles) in til.tiles.iter().enumerate() {
+            for (col, tile) in tiles.iter().enumerate() {
+                let tile_img = self.tile(tile.tile_id);
+                imageops::replace(
+                    img,
+                    &tile_img,
+                    x + col as u32 * self.tile_size,
+                    y + row as u32 * self.tile_size,
+                );
+            }
+        }
+    }
+
+    /// Image of a tile of the ZON
+    fn tile(&self, tile_id: i32) -> RgbaImage {
+        let size = self.tile_size;
+        let tile = match self.tiles.get(tile_id as usize) {
+            Some(tile) if tile_id >= 0 => tile,
+            _ => return RgbaImage::from_pixel(size, size, MISSING_COLOR),
+        };
+        let layer = |layer: i32, offset: i32| {
+            usize::try_from(layer + offset)
+                .ok()
+                .and_then(|i| self.textures.get(i))
+                .and_then(|t| t.as_ref())
+        };
+
+        let mut img = match layer(tile.layer1, tile.offset1) {
+            Some(texture) => texture.clone(),
+            None => RgbaImage::from_pixel(size, size, MISSING_COLOR),
+        };
+        if tile.blend {
+            if let Some(texture) = layer(tile.layer2, tile.offset2) {
+                let top = rotate_layer(texture, &tile.rotation);
+                for (bottom, top) in img.pixels_mut().zip(top.pixels()) {
+                    let alpha = top[3] as u32;
+                    for c in 0..3 {
+                        bottom[c] = ((top[c] as u32 * alpha + bottom[c] as u32 * (255 - alpha))
+                            / 255) as u8;
+                    }
+                }
+            }
+        }
+        for pixel in img.pixels_mut() {
+            pixel[3] = 255;
+        }
+        img
+    }
+}
+
+/// Shade the block with its top left corner at `(x, y)` by the slopes of its
+/// heightmap
+pub fn hillshade_block(img: &mut RgbaImage, x: u32, y: u32, block_size: u32, him: &HIM) {
+    let max = (him.width.min(him.length) - 1).max(0) as usize;
+    let height = |row: usize, col: usize| him.height(row.min(max), col.min(max));
+
+    // Light from the top left at 45 degrees
+    let light = [-1.0f32, -1.0, 1.0];
+    let light_length = (light[0] * light[0] + light[1] * light[1] + light[2] * light[2]).sqrt();
+    let flat = light[2] / light_length;
+
+    for py in 0..block_size {
+        for px in 0..block_size {
+            let row = ((py as f32 + 0.5) * max as f32 / block_size as f32) as usize;
+            let col = ((px as f32 + 0.5) * max as f32 / block_size as f32) as usize;
+            // Central differences, one-sided at the edges of the block
+            let (left, right) = (col.saturating_sub(1), (col + 1).min(max));
+            let (top, bottom) = (row.saturating_sub(1), (row + 1).min(max));
+            let dx = (height(row, right) - height(row, left))
+                / (GRID_SIZE * (right - left).max(1) as f32);
+            let dy = (height(bottom, col) - height(top, col))
+                / (GRID_SIZE * (bottom - top).max(1) as f32);
+
+            // Surface normal (-dx, -dy, 1)
+            let length = (dx * dx + dy * dy + 1.0).sqrt();
+            let shade = (-dx * light[0] - dy * light[1] + light[2]) / (length * light_length);
+            let factor = (0.5 + 0.5 * shade / flat).clamp(0.3, 1.5);
+
+            let pixel = img.get_pixel_mut(x + px, y + py);
+            for c in 0..3 {
+                pixel[c] = (pixel[c] as f32 * factor).round().min(255.0) as u8;
+            }
+        }
+    }
+}
+
+/// Resize a tile texture to `size` pixels square
+pub fn resize_texture(img: &RgbaImage, size: u32) -> RgbaImage {
+    if img.width() > size && img.height() > size {
+        // Averages the pixels, better than filtering for large reductions
+        imageops::thumbnail(img, size, size)
+    } else {
+        imageops::resize(img, size, size, FilterType::Triangle)
+    }
+}
+
+/// Rotate or flip the second layer of a tile by the tile's rotation
+pub fn rotate_layer(img: &RgbaImage, rotation: &ZoneTileRotation) -> RgbaImage {
+    match rotation {
+        ZoneTileRotation::FlipHorizontal => imageops::flip_horizontal(img),
+        ZoneTileRotation::FlipVertical => imageops::flip_vertical(img),
+        ZoneTileRotation::Flip => imageops::rotate180(img),
+        ZoneTileRotation::Clockwise90 => imageops::rotate90(img),
+        ZoneTileRotation::CounterClockwise90 => imageops::rotate270(img),
+        ZoneTileRotation::Unknown | ZoneTileRotation::None => img.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::til::Tile;
+    use roselib::io::RoseFile;
+
+    fn til(tile_ids: &[i32]) -> TIL {
+        let mut til = TIL::new();
+        til.width = BLOCK_TILES as i32;
+        til.height = BLOCK_TILES as i32;
+        for row in 0..BLOCK_TILES as usize {
+            let mut tiles = Vec::new();
+            for col in 0..BLOCK_TILES as usize {
+                tiles.push(Tile {
+                    tile_id: tile_ids[(row * BLOCK_TILES as usize + col) % tile_ids.len()],
+                    ..Default::default()
+                });
+            }
+            til.tiles.push(tiles);
+        }
+        til
+    }
+
+    #[test]
+    fn test_draw_block() {
+        let red = RgbaImage::from_pixel(8, 8, Rgba([255, 0, 0, 255]));
+        // Left half opaque blue, right half transparent
+        let mut blue = RgbaImage::new(8, 8);
+        for (x, _, pixel) in blue.enumerate_pixels_mut() {
+            *pixel = Rgba([0, 0, 255, if x < 4 { 255 } else { 0 }]);
+        }
+
+        let tiles = vec![
+            ZoneTile {
+                layer1: 0,
+                offset1: 0,
+                ..Default::default()
+            },
+            ZoneTile {
+                layer1: 0,
+                offset1: 0,
+                layer2: 0,
+                offset2: 1,
+                blend: true,
+                rotation: ZoneTileRotation::FlipHorizontal,
+                ..Default::default()
+            },
+            ZoneTile {
+                layer1: 5,
+                offset1: 0,
+                ..Default::default()
+            },
+        ];
+        let minimap = Minimap::new(&tiles, vec![Some(red), Some(blue)], 2);
+        assert_eq!(minimap.block_size(), 32);
+
+        let mut img = RgbaImage::new(32, 32);
+        minimap.draw_block(&mut img, 0, 0, &til(&[0, 1, 2, -1]));
+        assert_eq!(img.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        // Flipped, so the opaque half of the second layer is on the right
+        assert_eq!(img.get_pixel(2, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(img.get_pixel(3, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(img.get_pixel(4, 0), &MISSING_COLOR);
+        assert_eq!(img.get_pixel(6, 0), &MISSING_COLOR);
+    }
+
+    #[test]
+    fn test_hillshade_block() {
+        let mut him = HIM::new();
+        him.width = 65;
+        him.length = 65;
+        // Rising towards the bottom right, so facing the light
+        for row in 0..65 {
+            for col in 0..65 {
+                him.heights.push((row + col) as f32 * 100.0);
+            }
+        }
+        let mut img = RgbaImage::from_pixel(16, 16, Rgba([100, 100, 100, 255]));
+        hillshade_block(&mut img, 0, 0, 16, &him);
+        assert!(img.get_pixel(8, 8)[0] > 100);
+
+        let mut flat = HIM::new();
+        flat.width = 65;
+        flat.length = 65;
+        flat.heights = vec![500.0; 65 * 65];
+        let mut img = RgbaImage::from_pixel(16, 16, Rgba([100, 100, 100, 255]));
+        hillshade_block(&mut img, 0, 0, 16, &flat);
+        assert_eq!(img.get_pixel(8, 8), &Rgba([100, 100, 100, 255]));
+    }
+}
@@ -0,0 +1,158 @@
+//! Output file name templates, e.g. `{stem}.{type}.{ext}`
+//!
+//! Placeholders:
+//! - `{name}`: input file name
+//! - `{stem}`: input file name without its extension
+//! - `{src_ext}`: input file extension
+//! - `{ext}`: output file extension
+//! - `{type}`: ROSE file type
+//! - `{dir}`: input directory relative to the directory being converted
+//! - `{parent}`: name of the input's parent directory
+//!
+//! Placeholders can be lower or upper cased with a modifier, e.g. `{stem:lower}`
+use std::path::{Path, PathBuf};
+
+use failure::{bail, Error};
+
+/// Template used when no other format is given
+pub const DEFAULT_NAME_FORMAT: &str = "{stem}.{ext}";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    Placeholder(String, Option<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameFormat {
+    parts: Vec<Part>,
+}
+
+impl NameFormat {
+    pub fn new(template: &str) -> Result<NameFormat, Error> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => bail!("Unclosed placeholder in name format: {}", template),
+            };
+
+            let placeholder = &rest[start + 1..end];
+            let mut split = placeholder.splitn(2, ':');
+            let key = split.next().unwrap_or_default();
+            let modifier = split.next();
+
+            match key {
+                "name" | "stem" | "src_ext" | "ext" | "type" | "dir" | "parent" => {}
+                _ => bail!("Unknown placeholder in name format: {{{}}}", key),
+            }
+
+            match modifier {
+                None | Some("lower") | Some("upper") => {}
+                Some(m) => bail!("Unknown modifier in name format: {}", m),
+            }
+
+            parts.push(Part::Placeholder(
+                key.to_string(),
+                modifier.map(|m| m.to_string()),
+            ));
+            rest = &rest[end + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        if parts.is_empty() {
+            bail!("Name format is empty");
+        }
+
+        Ok(NameFormat { parts })
+    }
+
+    /// Output path, relative to the output directory, for `input` found in
+    /// `dir` (relative to the converted directory)
+    pub fn format(&self, input: &Path, dir: &Path, rose_type: &str, ext: &str) -> PathBuf {
+        let to_str =
+            |s: Option<&std::ffi::OsStr>| s.unwrap_or_default().to_string_lossy().into_owned();
+
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => name.push_str(s),
+                Part::Placeholder(key, modifier) => {
+                    let value = match key.as_str() {
+                        "name" => to_str(input.file_name()),
+                        "stem" => to_str(input.file_stem()),
+                        "src_ext" => to_str(input.extension()),
+                        "ext" => ext.to_string(),
+                        "type" => rose_type.to_string(),
+                        "dir" => dir.to_string_lossy().into_owned(),
+                        "parent" => to_str(input.parent().and_then(|p| p.file_name())),
+                        _ => String::new(),
+                    };
+
+                    match modifier.as_deref() {
+                        Some("lower") => name.push_str(&value.to_lowercase()),
+                        Some("upper") => name.push_str(&value.to_uppercase()),
+                        _ => name.push_str(&value),
+                    }
+                }
+            }
+        }
+
+        // Drop empty components, e.g. from an empty `{dir}`
+        name.split('/')
+            .filter(|c| !c.is_empty())
+            .collect::<PathBuf>()
+    }
+}
+
+impl Default for NameFormat {
+    fn default() -> NameFormat {
+        NameFormat::new(DEFAULT_NAME_FORMAT).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_format() {
+        let input = Path::new("3DDATA/STB/LIST_ZONE.STB");
+
+        let format = NameFormat::default();
+        assert_eq!(
+            format.format(input, Path::new(""), "stb", "csv"),
+            PathBuf::from("LIST_ZONE.csv")
+        );
+
+        let format = NameFormat::new("{dir}/{stem:lower}.{type}.{ext}").unwrap();
+        assert_eq!(
+            format.format(input, Path::new("STB"), "stb", "csv"),
+            PathBuf::from("STB/list_zone.stb.csv")
+        );
+        assert_eq!(
+            format.format(input, Path::new(""), "stb", "csv"),
+            PathBuf::from("list_zone.stb.csv")
+        );
+
+        let format = NameFormat::new("{parent:lower}_{name}").unwrap();
+        assert_eq!(
+            format.format(input, Path::new(""), "stb", "csv"),
+            PathBuf::from("stb_LIST_ZONE.STB")
+        );
+
+        assert!(NameFormat::new("{stem").is_err());
+        assert!(NameFormat::new("{foo}").is_err());
+        assert!(NameFormat::new("{stem:title}").is_err());
+        assert!(NameFormat::new("").is_err());
+    }
+}
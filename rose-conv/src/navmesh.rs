@@ -0,0 +1,425 @@
+//! Navigation meshes of maps
+//!
+//! The walkable area of a map is rasterized on the cells of its terrain, 2.5
+//! meters on each side. A cell is walkable unless it's steeper than the
+//! maximum slope or under an object that collides. Objects collide if a part
+//! of their ZSC object has a collision shape, unless the shape is only used
+//! for the height of characters standing on the object (bridges, stairs).
+//! The footprint of an object is its bounding box, in centimeters like the
+//! positions of its parts, placed and projected on the ground.
+//!
+//! The navigation mesh is the walkable cells as two triangles each in OBJ
+//! format, Y-up in meters at world positions like glTF exports, for tools
+//! such as Recast to build polygon meshes from.
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use roselib::files::zsc::SceneObject;
+use roselib::utils::Vector3;
+
+use crate::collision::CollisionShape;
+use crate::gltf;
+use crate::ifo_instances::Instance;
+use crate::terrain::{Terrain, BLOCK_CELLS, BLOCK_SIZE};
+
+/// Flag of collision shapes used only for the height of characters
+const COLLISION_HEIGHT_ONLY: u16 = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NavCell {
+    Walkable,
+    /// Steeper than the maximum slope
+    Steep,
+    /// Under an object
+    Blocked,
+}
+
+impl NavCell {
+    fn symbol(self) -> char {
+        match self {
+            NavCell::Walkable => '.',
+            NavCell::Steep => '^',
+            NavCell::Blocked => '#',
+        }
+    }
+}
+
+/// Walkable cells of a map
+pub struct Navmesh {
+    /// Heights of the corners of the cells in centimeters
+    heights: Vec<Vec<f32>>,
+    /// World position of the north west corner in centimeters
+    origin: (f32, f32),
+    pub cells: Vec<Vec<NavCell>>,
+}
+
+/// Cells of a navigation mesh, as JSON
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NavGrid {
+    /// Size of the cells in centimeters
+    pub cell_size: f32,
+    /// World position of the north west corner in centimeters
+    pub origin: [f32; 2],
+    pub rows: usize,
+    pub cols: usize,
+    pub walkable: usize,
+    pub steep: usize,
+    pub blocked: usize,
+    /// A string per row from north to south, a character per cell from
+    /// west to east: `.` walkable, `^` steep and `#` under an object
+    pub cells: Vec<String>,
+}
+
+impl Navmesh {
+    /// Cells of the terrain, the ones steeper than `max_slope` degrees along
+    /// an edge or a diagonal not walkable
+    pub fn new(terrain: &Terrain, max_slope: f32) -> Navmesh {
+        let cell = cell_size();
+        let heights = &terrain.heights;
+        let cells = (0..terrain.rows() - 1)
+            .map(|row| {
+                (0..terrain.cols() - 1)
+                    .map(|col| {
+                        let nw = heights[row][col];
+                        let ne = heights[row][col + 1];
+                        let sw = heights[row + 1][col];
+                        let se = heights[row + 1][col + 1];
+                        let edge = [nw - ne, sw - se, nw - sw, ne - se]
+                            .iter()
+                            .fold(0.0f32, |m, d| m.max(d.abs()));
+                        let diagonal = (nw - se).abs().max((ne - sw).abs());
+                        let rise = (edge / cell).max(diagonal / (cell * 2f32.sqrt()));
+                        if rise.atan().to_degrees() > max_slope {
+                            NavCell::Steep
+                        } else {
+                            NavCell::Walkable
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Navmesh {
+            heights: terrain.heights.clone(),
+            origin: terrain.origin(),
+            cells,
+        }
+    }
+
+    /// Mark the cells with their center in a convex footprint, in world
+    /// centimeters, as blocked
+    pub fn block(&mut self, footprint: &[[f32; 2]]) {
+        if footprint.len() < 3 {
+            return;
+        }
+        let cell = cell_size();
+        let (x0, y0) = self.origin;
+        let xs = footprint.iter().map(|p| p[0]);
+        let ys = footprint.iter().map(|p| p[1]);
+        let (min_x, max_x) = (
+            xs.clone().fold(f32::MAX, f32::min),
+            xs.fold(f32::MIN, f32::max),
+        );
+        let (min_y, max_y) = (
+            ys.clone().fold(f32::MAX, f32::min),
+            ys.fold(f32::MIN, f32::max),
+        );
+
+        // Rows run south, from the largest Y
+        let rows = self.cells.len() as f32;
+        let cols = self.cells.first().map_or(0, |r| r.len()) as f32;
+        let col_range = ((min_x - x0) / cell).floor().max(0.0) as usize
+            ..((max_x - x0) / cell).ceil().clamp(0.0, cols) as usize;
+        let row_range = ((y0 - max_y) / cell).floor().max(0.0) as usize
+            ..((y0 - min_y) / cell).ceil().clamp(0.0, rows) as usize;
+        for row in row_range {
+            for col in col_range.clone() {
+                let center = [
+                    x0 + (col as f32 + 0.5) * cell,
+                    y0 - (row as f32 + 0.5) * cell,
+                ];
+                if contains(footprint, center) {
+                    self.cells[row][col] = NavCell::Blocked;
+                }
+            }
+        }
+    }
+
+    pub fn grid(&self) -> NavGrid {
+        let count = |kind: NavCell| self.cells.iter().flatten().filter(|&&c| c == kind).count();
+        NavGrid {
+            cell_size: cell_size(),
+            origin: [self.origin.0, self.origin.1],
+            rows: self.cells.len(),
+            cols: self.cells.first().map_or(0, |r| r.len()),
+            walkable: count(NavCell::Walkable),
+            steep: count(NavCell::Steep),
+            blocked: count(NavCell::Blocked),
+            cells: self
+                .cells
+                .iter()
+                .map(|row| row.iter().map(|c| c.symbol()).collect())
+                .collect(),
+        }
+    }
+
+    /// OBJ of the walkable cells, sharing the vertices of neighboring cells
+    pub fn to_obj(&self, name: &str) -> String {
+        let cell = cell_size();
+        let cols = self.cells.first().map_or(0, |r| r.len());
+        let mut indices = vec![vec![0usize; cols + 1]; self.cells.len() + 1];
+        let mut obj = String::new();
+        let _ = writeln!(obj, "# Navigation mesh of {}\no {}", name, name);
+
+        let mut vertices = 0;
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (col, &kind) in cells.iter().enumerate() {
+                if kind != NavCell::Walkable {
+                    continue;
+                }
+                for (r, c) in [
+                    (row, col),
+                    (row, col + 1),
+                    (row + 1, col),
+                    (row + 1, col + 1),
+                ] {
+                    if indices[r][c] != 0 {
+                        continue;
+                    }
+                    vertices += 1;
+                    indices[r][c] = vertices;
+                    let [x, y, z] = gltf::position(&Vector3 {
+                        x: (self.origin.0 + c as f32 * cell) / 100.0,
+                        y: (self.origin.1 - r as f32 * cell) / 100.0,
+                        z: self.heights[r][c] / 100.0,
+                    });
+                    let _ = writeln!(obj, "v {} {} {}", x, y, z);
+                }
+            }
+        }
+
+        // Counterclockwise from above
+        for (row, cells) in self.cells.iter().enumerate() {
+            for (col, &kind) in cells.iter().enumerate() {
+                if kind != NavCell::Walkable {
+                    continue;
+                }
+                let nw = indices[row][col];
+                let ne = indices[row][col + 1];
+                let sw = indices[row + 1][col];
+                let se = indices[row + 1][col + 1];
+                let _ = writeln!(obj, "f {} {} {}\nf {} {} {}", nw, sw, ne, ne, sw, se);
+            }
+        }
+        obj
+    }
+}
+
+/// Footprint of an instance on the ground in world centimeters, the convex
+/// hull of its placed bounding box, or `None` if it doesn't collide
+pub fn footprint(instance: &Instance, object: &SceneObject) -> Option<Vec<[f32; 2]>> {
+    let collides = object.parts.iter().any(|part| {
+        CollisionShape::from_part(part).is_some() && part.collision & COLLISION_HEIGHT_ONLY == 0
+    });
+    if !collides {
+        return None;
+    }
+
+    let (min, max) = (&object.bounding_box.min, &object.bounding_box.max);
+    let q = &instance.rotation;
+    let s = &instance.scale;
+    let mut points = Vec::with_capacity(8);
+    for &x in &[min.x, max.x] {
+        for &y in &[min.y, max.y] {
+            for &z in &[min.z, max.z] {
+                let v = [x * s.x, y * s.y, z * s.z];
+                // Rotate by the quaternion: v + 2w(u x v) + 2u x (u x v)
+                let u = [q.x, q.y, q.z];
+                let t = cross(u, v).map(|c| 2.0 * c);
+                let r = cross(u, t);
+                points.push([
+                    instance.position.x + v[0] + q.w * t[0] + r[0],
+                    instance.position.y + v[1] + q.w * t[1] + r[1],
+                ]);
+            }
+        }
+    }
+    Some(convex_hull(points))
+}
+
+fn cell_size() -> f32 {
+    BLOCK_SIZE / BLOCK_CELLS as f32
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Convex hull of points counterclockwise, by Andrew's monotone chain
+fn convex_hull(mut points: Vec<[f32; 2]>) -> Vec<[f32; 2]> {
+    points.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let turn = |o: [f32; 2], a: [f32; 2], b: [f32; 2]| {
+        (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+    };
+    let mut hull: Vec<[f32; 2]> = Vec::new();
+    for pass in 0..2 {
+        let start = hull.len();
+        let iter: Box<dyn Iterator<Item = &[f32; 2]>> = if pass == 0 {
+            Box::new(points.iter())
+        } else {
+            Box::new(points.iter().rev())
+        };
+        for &p in iter {
+            while hull.len() >= start + 2
+                && turn(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // The last point starts the other half
+        hull.pop();
+    }
+    hull
+}
+
+/// Whether a point is inside or on the edge of a counterclockwise convex
+/// polygon
+fn contains(polygon: &[[f32; 2]], p: [f32; 2]) -> bool {
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .all(|(a, b)| (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0]) >= 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    use roselib::files::zsc::SceneObjectPart;
+    use roselib::files::HIM;
+    use roselib::io::RoseFile;
+    use roselib::utils::{BoundingBox, Quaternion};
+
+    use crate::ifo_instances::InstanceKind;
+
+    fn terrain() -> Terrain {
+        let mut him = HIM::new();
+        him.width = 65;
+        him.length = 65;
+        // Flat, with a cliff along the inner index
+        for _ in 0..65 {
+            for i in 0..65 {
+                him.heights.push(if i < 40 { 0.0 } else { 1000.0 });
+            }
+        }
+        let mut blocks = BTreeMap::new();
+        blocks.insert((32, 32), him);
+        Terrain::from_blocks(&blocks)
+    }
+
+    #[test]
+    fn test_slopes() {
+        let navmesh = Navmesh::new(&terrain(), 45.0);
+        assert_eq!(navmesh.cells.len(), 64);
+        assert_eq!(navmesh.cells[0][0], NavCell::Walkable);
+        assert_eq!(navmesh.cells[39][10], NavCell::Steep);
+        assert_eq!(navmesh.cells[40][10], NavCell::Walkable);
+        let grid = navmesh.grid();
+        assert_eq!((grid.walkable, grid.steep, grid.blocked), (63 * 64, 64, 0));
+        assert_eq!(grid.origin, [512_000.0, 528_000.0]);
+        assert_eq!(&grid.cells[39][..3], "^^^");
+
+        // Everything is walkable at 90 degrees
+        let navmesh = Navmesh::new(&terrain(), 90.0);
+        assert_eq!(navmesh.grid().steep, 0);
+    }
+
+    #[test]
+    fn test_objects() {
+        let mut object = SceneObject {
+            bounding_box: BoundingBox {
+                min: Vector3 {
+                    x: -500.0,
+                    y: -250.0,
+                    z: 0.0,
+                },
+                max: Vector3 {
+                    x: 500.0,
+                    y: 250.0,
+                    z: 300.0,
+                },
+            },
+            ..Default::default()
+        };
+        object.parts.push(SceneObjectPart {
+            collision: COLLISION_HEIGHT_ONLY | 2,
+            ..Default::default()
+        });
+        // Turned a quarter, so 5 meters wide and 10 meters long north to south
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let instance = Instance {
+            kind: InstanceKind::Building,
+            name: String::new(),
+            object_id: 0,
+            block: [32, 32],
+            position: Vector3 {
+                x: 512_000.0 + 1000.0,
+                y: 528_000.0 - 1000.0,
+                z: 0.0,
+            },
+            rotation: Quaternion {
+                x: 0.0,
+                y: 0.0,
+                z: half,
+                w: half,
+            },
+            scale: Vector3 {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+            parts: None,
+        };
+        assert_eq!(footprint(&instance, &object), None);
+
+        object.parts.push(SceneObjectPart {
+            collision: 2,
+            ..Default::default()
+        });
+        let footprint = footprint(&instance, &object).unwrap();
+        assert_eq!(footprint.len(), 4);
+
+        let mut navmesh = Navmesh::new(&terrain(), 45.0);
+        navmesh.block(&footprint);
+        let grid = navmesh.grid();
+        // Cells 2 to 6 south of the corner, 3 and 4 east of it
+        assert_eq!(grid.blocked, 8);
+        assert_eq!(&grid.cells[2][..6], "...##.");
+        assert_eq!(&grid.cells[5][..6], "...##.");
+        assert_eq!(&grid.cells[6][..6], "......");
+    }
+
+    #[test]
+    fn test_obj() {
+        let navmesh = Navmesh::new(&terrain(), 45.0);
+        let obj = navmesh.to_obj("JPT01");
+        let vertices = obj.lines().filter(|l| l.starts_with("v ")).count();
+        let faces = obj.lines().filter(|l| l.starts_with("f ")).count();
+        // The steep row splits the vertices in two
+        assert_eq!(vertices, 65 * 40 + 65 * 25);
+        assert_eq!(faces, 63 * 64 * 2);
+        assert_eq!(obj.lines().nth(2), Some("v 5120 0 -5280"));
+        assert_eq!(obj.lines().nth(1), Some("o JPT01"));
+    }
+}
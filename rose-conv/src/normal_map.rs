@@ -0,0 +1,66 @@
+//! Tangent-space normal maps of heightmaps
+//!
+//! The slopes are estimated with a Sobel filter, `strength` scales them to
+//! exaggerate or flatten the terrain. Normals are encoded like OpenGL normal
+//! maps: red points right, green points up in the image (against the rows)
+//! and blue points out of the terrain. Heights past the edges repeat the
+//! edge heights.
+use image::{Rgb, RgbImage};
+
+/// Normal map of `heights`, indexed by row and column, with `spacing` between
+/// the heights in the same unit as the heights
+pub fn normal_map(heights: &[Vec<f32>], spacing: f32, strength: f32) -> RgbImage {
+    let rows = heights.len();
+    let cols = heights.first().map_or(0, |r| r.len());
+    let height = |row: isize, col: isize| {
+        let row = row.clamp(0, rows as isize - 1) as usize;
+        let col = col.clamp(0, cols as isize - 1) as usize;
+        heights[row][col]
+    };
+
+    let mut img = RgbImage::new(cols as u32, rows as u32);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let (r, c) = (y as isize, x as isize);
+        let gx = (height(r - 1, c + 1) + 2.0 * height(r, c + 1) + height(r + 1, c + 1))
+            - (height(r - 1, c - 1) + 2.0 * height(r, c - 1) + height(r + 1, c - 1));
+        let gy = (height(r + 1, c - 1) + 2.0 * height(r + 1, c) + height(r + 1, c + 1))
+            - (height(r - 1, c - 1) + 2.0 * height(r - 1, c) + height(r - 1, c + 1));
+        // Slopes along the columns and down the rows
+        let dx = gx / (8.0 * spacing) * strength;
+        let dy = gy / (8.0 * spacing) * strength;
+
+        // Normal (-dx, -dy, 1) with y flipped to point up the image
+        let length = (dx * dx + dy * dy + 1.0).sqrt();
+        let encode = |n: f32| ((n / length * 0.5 + 0.5) * 255.0).round() as u8;
+        *pixel = Rgb([encode(-dx), encode(dy), encode(1.0)]);
+    }
+    img
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normal_map() {
+        let flat = vec![vec![100.0; 4]; 3];
+        let img = normal_map(&flat, 250.0, 1.0);
+        assert_eq!(img.dimensions(), (4, 3));
+        assert_eq!(img.get_pixel(1, 1), &Rgb([128, 128, 255]));
+
+        // Rising to the right by 250 per column, a slope of 45 degrees
+        let ramp: Vec<Vec<f32>> = (0..3)
+            .map(|_| (0..4).map(|c| c as f32 * 250.0).collect())
+            .collect();
+        let img = normal_map(&ramp, 250.0, 1.0);
+        assert_eq!(img.get_pixel(1, 1), &Rgb([37, 128, 218]));
+        // Flattened
+        let img = normal_map(&ramp, 250.0, 0.0);
+        assert_eq!(img.get_pixel(1, 1), &Rgb([128, 128, 255]));
+
+        // Rising down the rows, so the normals lean up the image
+        let ramp: Vec<Vec<f32>> = (0..3).map(|r| vec![r as f32 * 250.0; 4]).collect();
+        let img = normal_map(&ramp, 250.0, 1.0);
+        assert_eq!(img.get_pixel(1, 1), &Rgb([128, 218, 218]));
+    }
+}
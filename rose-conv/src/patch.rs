@@ -0,0 +1,236 @@
+//! Semantic patches for STB and STL files
+//!
+//! A patch is a JSON list of edits applied in order, so mods can ship only the
+//! rows they change instead of whole tables:
+//!
+//! ```json
+//! [
+//!   { "op": "set", "row": 12, "column": 7, "value": "40" },
+//!   { "op": "insert", "row": 980, "values": ["NPC_980", "1", "..."] },
+//!   { "op": "delete", "row": 981 }
+//! ]
+//! ```
+//!
+//! STB rows and columns are the same as in the serialized CSV. STL rows are
+//! edited in their CSV layout without the row number column, i.e. column 0 is
+//! the key ID, column 1 the key name and the remaining columns the text of
+//! each language.
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use failure::{bail, Error};
+use serde::{Deserialize, Serialize};
+
+use roselib::files::{STB, STL};
+
+use crate::{FromCsv, ToCsv};
+
+/// Number of header rows in the STL CSV layout
+const STL_HEADER_ROWS: usize = 2;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    /// Set the value of a cell
+    Set {
+        row: usize,
+        column: usize,
+        value: String,
+    },
+    /// Insert a row before `row`, or append it if `row` is the row count
+    Insert {
+        row: usize,
+        values: Vec<String>,
+    },
+    Delete {
+        row: usize,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Patch {
+    pub ops: Vec<PatchOp>,
+}
+
+impl FromStr for Patch {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Patch, Error> {
+        Ok(Patch {
+            ops: serde_json::from_str(s)?,
+        })
+    }
+}
+
+impl Patch {
+    pub fn from_path(path: &Path) -> Result<Patch, Error> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Parse a CSV file of cell edits with the columns `row`, `column` and
+    /// `value` as a patch of `set` ops
+    pub fn from_csv_edits(s: &str) -> Result<Patch, Error> {
+        let mut reader = csv::Reader::from_reader(s.as_bytes());
+
+        let mut patch = Patch::default();
+        for record in reader.deserialize() {
+            let (row, column, value) = record?;
+            patch.ops.push(PatchOp::Set { row, column, value });
+        }
+        Ok(patch)
+    }
+
+    /// Apply the edits to the rows of a table with `col_count` columns
+    pub fn apply(&self, rows: &mut Vec<Vec<String>>, col_count: usize) -> Result<(), Error> {
+        for (op_idx, op) in self.ops.iter().enumerate() {
+            match op {
+                PatchOp::Set { row, column, value } => {
+                    let cells = match rows.get_mut(*row) {
+                        Some(cells) => cells,
+                        None => bail!("Patch op {}: row {} does not exist", op_idx, row),
+                    };
+                    match cells.get_mut(*column) {
+                        Some(cell) => *cell = value.clone(),
+                        None => bail!("Patch op {}: column {} does not exist", op_idx, column),
+                    }
+                }
+                PatchOp::Insert { row, values } => {
+                    if *row > rows.len() {
+                        bail!(
+                            "Patch op {}: can't insert row {} after the last row",
+                            op_idx,
+                            row
+                        );
+                    }
+                    if values.len() != col_count {
+                        bail!(
+                            "Patch op {}: expected {} values, found {}",
+                            op_idx,
+                            col_count,
+                            values.len()
+                        );
+                    }
+                    rows.insert(*row, values.clone());
+                }
+                PatchOp::Delete { row } => {
+                    if *row >= rows.len() {
+                        bail!("Patch op {}: row {} does not exist", op_idx, row);
+                    }
+                    rows.remove(*row);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply_stb(&self, stb: &mut STB) -> Result<(), Error> {
+        let col_count = stb.headers.len();
+        self.apply(&mut stb.data, col_count)
+    }
+
+    pub fn apply_stl(&self, stl: &STL) -> Result<STL, Error> {
+        let csv = stl.to_csv()?;
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(csv.as_bytes());
+
+        let mut records = Vec::new();
+        for record in reader.records() {
+            records.push(record?.iter().map(String::from).collect::<Vec<String>>());
+        }
+
+        // Strip the header rows and the row number column
+        let mut rows: Vec<Vec<String>> = records
+            .split_off(STL_HEADER_ROWS)
+            .into_iter()
+            .map(|row| row.into_iter().skip(1).collect())
+            .collect();
+        let col_count = records[0].len() - 1;
+        self.apply(&mut rows, col_count)?;
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for record in &records {
+            writer.write_record(record)?;
+        }
+        for (row_idx, row) in rows.into_iter().enumerate() {
+            let mut record = vec![row_idx.to_string()];
+            record.extend(row);
+            writer.write_record(&record)?;
+        }
+
+        STL::from_csv(&String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::stl::*;
+    use roselib::io::RoseFile;
+
+    fn rows(data: &[&[&str]]) -> Vec<Vec<String>> {
+        data.iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_patch() {
+        let patch: Patch = r#"[
+            { "op": "set", "row": 0, "column": 1, "value": "5" },
+            { "op": "insert", "row": 1, "values": ["x", "9"] },
+            { "op": "delete", "row": 2 }
+        ]"#
+        .parse()
+        .unwrap();
+
+        let mut data = rows(&[&["a", "1"], &["b", "2"], &["c", "3"]]);
+        patch.apply(&mut data, 2).unwrap();
+        assert_eq!(data, rows(&[&["a", "5"], &["x", "9"], &["c", "3"]]));
+
+        let mut data = rows(&[&["a", "1"]]);
+        assert!(patch.apply(&mut data, 2).is_err());
+
+        let patch = Patch::from_csv_edits("row,column,value\n0,1,5\n1,0,\"x, y\"\n").unwrap();
+        let mut data = rows(&[&["a", "1"], &["b", "2"]]);
+        patch.apply(&mut data, 2).unwrap();
+        assert_eq!(data, rows(&[&["a", "5"], &["x, y", "2"]]));
+        assert!("[{ \"op\": \"move\", \"row\": 1 }]"
+            .parse::<Patch>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_patch_stl() {
+        let mut stl = STL::new();
+        stl.keys.push(StringTableKey {
+            id: 1,
+            name: "KEY_1".into(),
+        });
+        stl.language_tables.push(StringTableLanguageTable {
+            language: StringTableLanguage::from(0),
+            rows: vec![StringTableRow::NormalRow(NormalRowData {
+                text: "Hello".into(),
+            })],
+        });
+
+        let patch: Patch = r#"[
+            { "op": "set", "row": 0, "column": 2, "value": "Hi" },
+            { "op": "insert", "row": 1, "values": ["2", "KEY_2", "Bye"] }
+        ]"#
+        .parse()
+        .unwrap();
+
+        let stl = patch.apply_stl(&stl).unwrap();
+        assert_eq!(stl.keys[1].name, "KEY_2");
+        assert_eq!(
+            stl.language_tables[0].rows,
+            vec![
+                StringTableRow::NormalRow(NormalRowData { text: "Hi".into() }),
+                StringTableRow::NormalRow(NormalRowData { text: "Bye".into() }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,385 @@
+//! Checks of edited quests against the layouts of their commands and the
+//! tables of a client
+//!
+//! As for AI patterns, see [`aip_check`](crate::aip_check), the layouts of
+//! conditions and rewards are built into roselib, see
+//! [`QuestCondition::operand_size`] and [`QuestReward::operand_size`], and
+//! commands that don't match them are read as `Raw`.
+//!
+//! The quests, items, NPCs and skills referenced by conditions and rewards are
+//! also checked against the rows of their STB, and the triggers run by rewards
+//! against the triggers of the quest and the QSD files of a client.
+//!
+//! Items are their type times 1000 plus their row in the STB of the type, e.g.
+//! 8001 is the first row of `LIST_WEAPON.STB`.
+use std::collections::{HashMap, HashSet};
+
+use roselib::files::qsd::{QuestCondition, QuestReward};
+use roselib::files::{QSD, STB};
+
+use crate::client::Client;
+
+/// Path of the quest list in the client
+pub const LIST_QUEST_PATH: &str = "3DDATA/STB/LIST_QUEST.STB";
+
+/// Path of the NPC list in the client
+pub const LIST_NPC_PATH: &str = "3DDATA/STB/LIST_NPC.STB";
+
+/// Path of the skill list in the client
+pub const LIST_SKILL_PATH: &str = "3DDATA/STB/LIST_SKILL.STB";
+
+/// STBs of the item types, by type
+pub const ITEM_TABLES: [(u32, &str); 14] = [
+    (1, "3DDATA/STB/LIST_FACEITEM.STB"),
+    (2, "3DDATA/STB/LIST_CAP.STB"),
+    (3, "3DDATA/STB/LIST_BODY.STB"),
+    (4, "3DDATA/STB/LIST_ARMS.STB"),
+    (5, "3DDATA/STB/LIST_FOOT.STB"),
+    (6, "3DDATA/STB/LIST_BACK.STB"),
+    (7, "3DDATA/STB/LIST_JEWEL.STB"),
+    (8, "3DDATA/STB/LIST_WEAPON.STB"),
+    (9, "3DDATA/STB/LIST_SUBWPN.STB"),
+    (10, "3DDATA/STB/LIST_USEITEM.STB"),
+    (11, "3DDATA/STB/LIST_JEMITEM.STB"),
+    (12, "3DDATA/STB/LIST_NATURAL.STB"),
+    (13, "3DDATA/STB/LIST_QUESTITEM.STB"),
+    (14, "3DDATA/STB/LIST_PAT.STB"),
+];
+
+/// Target of the calculated rewards that give an item
+const CALCULATED_ITEM: u8 = 2;
+
+/// Conditions and rewards of a quest with an unknown opcode or operands of
+/// another size than their layout
+pub fn check_layouts(qsd: &QSD) -> Vec<String> {
+    check_commands(
+        qsd,
+        |condition| match condition {
+            QuestCondition::Raw(command) => QuestCondition::decode(command)
+                .err()
+                .map(|e| e.to_string())
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        },
+        |reward| match reward {
+            QuestReward::Raw(command) => QuestReward::decode(command)
+                .err()
+                .map(|e| e.to_string())
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        },
+    )
+}
+
+/// Names of the triggers of every QSD of a client, `None` if it has none
+pub fn quest_triggers(client: &Client) -> Option<HashSet<String>> {
+    let triggers: HashSet<String> = client
+        .files_with_extension("qsd")
+        .filter_map(|file| client.read_file::<QSD>(&file.path).ok())
+        .flat_map(|qsd| {
+            qsd.triggers()
+                .map(|t| t.name.clone())
+                .collect::<Vec<String>>()
+        })
+        .collect();
+    if triggers.is_empty() {
+        None
+    } else {
+        Some(triggers)
+    }
+}
+
+/// Number of rows of the STBs referenced by quests and names of the quest
+/// triggers, for the ones in the client
+#[derive(Debug, Default, PartialEq)]
+pub struct QuestTables {
+    pub quests: Option<usize>,
+    pub npcs: Option<usize>,
+    pub skills: Option<usize>,
+    /// Rows of the STB of each item type
+    pub items: HashMap<u32, usize>,
+    /// Triggers of every QSD of the client, if it has any
+    pub triggers: Option<HashSet<String>>,
+}
+
+impl QuestTables {
+    pub fn from_client(client: &Client) -> QuestTables {
+        let rows = |path: &str| client.read_file::<STB>(path).ok().map(|stb| stb.rows());
+        QuestTables {
+            quests: rows(LIST_QUEST_PATH),
+            npcs: rows(LIST_NPC_PATH),
+            skills: rows(LIST_SKILL_PATH),
+            items: ITEM_TABLES
+                .iter()
+                .filter_map(|&(item_type, path)| Some((item_type, rows(path)?)))
+                .collect(),
+            triggers: quest_triggers(client),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.quests.is_none()
+            && self.npcs.is_none()
+            && self.skills.is_none()
+            && self.items.is_empty()
+            && self.triggers.is_none()
+    }
+
+    /// Quests, items, NPCs and skills referenced by a quest that aren't rows
+    /// of their STB and triggers that are neither in the quest nor the client,
+    /// skipping references to STBs missing from the client
+    pub fn check(&self, qsd: &QSD) -> Vec<String> {
+        let quest = |quest: i64| check_row("quest", quest, self.quests, LIST_QUEST_PATH);
+        let npc = |npc: i64| check_row("NPC", npc, self.npcs, LIST_NPC_PATH);
+        let skill = |skill: i64| check_row("skill", skill, self.skills, LIST_SKILL_PATH);
+        let item = |item: i64| check_item(item, &self.items);
+        let own_triggers: HashSet<&str> = qsd.triggers().map(|t| t.name.as_str()).collect();
+        let trigger = |trigger: &String| match &self.triggers {
+            Some(triggers)
+                if !triggers.contains(trigger) && !own_triggers.contains(trigger.as_str()) =>
+            {
+                Some(format!("trigger {} isn't in the QSD files", trigger))
+            }
+            _ => None,
+        };
+
+        check_commands(
+            qsd,
+            |condition| match condition {
+                QuestCondition::SelectQuest { quest: q } => {
+                    quest(i64::from(*q)).into_iter().collect()
+                }
+                QuestCondition::Items { items } => items
+                    .iter()
+                    .filter_map(|i| item(i64::from(i.item)))
+                    .collect(),
+                QuestCondition::Skill { start, end, .. } => skill(i64::from(*start))
+                    .into_iter()
+                    .chain(skill(i64::from(*end)))
+                    .collect(),
+                QuestCondition::ClanSkill { start, end, .. } => skill(i64::from(*start))
+                    .into_iter()
+                    .chain(skill(i64::from(*end)))
+                    .collect(),
+                QuestCondition::SelectNpc { npc: n } => npc(i64::from(*n)).into_iter().collect(),
+                QuestCondition::CompareNpcVariables {
+                    npc: n, other_npc, ..
+                } => npc(i64::from(*n))
+                    .into_iter()
+                    .chain(npc(i64::from(*other_npc)))
+                    .collect(),
+                _ => Vec::new(),
+            },
+            |reward| match reward {
+                QuestReward::Quest { quest: q, .. } => quest(i64::from(*q)).into_iter().collect(),
+                QuestReward::Item { item: i, .. } => item(i64::from(*i)).into_iter().collect(),
+                QuestReward::Calculated {
+                    target, item: i, ..
+                } if *target == CALCULATED_ITEM => item(i64::from(*i)).into_iter().collect(),
+                QuestReward::SpawnMonster { npc: n, .. } => {
+                    npc(i64::from(*n)).into_iter().collect()
+                }
+                QuestReward::Skill { skill: s, .. } => skill(i64::from(*s)).into_iter().collect(),
+                QuestReward::ClanSkill { skill: s, .. } => {
+                    skill(i64::from(*s)).into_iter().collect()
+                }
+                QuestReward::Trigger { trigger: t }
+                | QuestReward::DelayedTrigger { trigger: t, .. }
+                | QuestReward::ZoneTeamTrigger { trigger: t, .. } => {
+                    trigger(t).into_iter().collect()
+                }
+                _ => Vec::new(),
+            },
+        )
+    }
+}
+
+/// Problem of a reference to an item that isn't a row of the STB of its type,
+/// skipping types whose STB is missing from the client
+pub fn check_item(item: i64, items: &HashMap<u32, usize>) -> Option<String> {
+    let item_type = item.div_euclid(1000);
+    match ITEM_TABLES
+        .iter()
+        .find(|&&(t, _)| i64::from(t) == item_type)
+    {
+        Some(&(t, path)) => check_row("item", item % 1000, items.get(&t).copied(), path)
+            .map(|problem| format!("{} ({})", problem, item)),
+        None => Some(format!("item {} has the unknown type {}", item, item_type)),
+    }
+}
+
+/// Problem of a reference to a row of an STB with `rows` rows, `None` if the
+/// STB is missing from the client
+pub fn check_row(name: &str, row: i64, rows: Option<usize>, path: &str) -> Option<String> {
+    let rows = rows?;
+    if row <= 0 || row as usize >= rows {
+        Some(format!(
+            "{} {} isn't a row of {}, which has {} rows",
+            name,
+            row,
+            path.rsplit('/').next().unwrap_or(path),
+            rows
+        ))
+    } else {
+        None
+    }
+}
+
+/// Problems of every condition and reward of a quest, prefixed with their
+/// group, trigger and position
+fn check_commands<C, R>(qsd: &QSD, check_condition: C, check_reward: R) -> Vec<String>
+where
+    C: Fn(&QuestCondition) -> Vec<String>,
+    R: Fn(&QuestReward) -> Vec<String>,
+{
+    let mut problems = Vec::new();
+    for group in &qsd.groups {
+        for trigger in &group.triggers {
+            let commands = trigger
+                .conditions
+                .iter()
+                .map(|c| ("condition", check_condition(c)))
+                .enumerate()
+                .chain(
+                    trigger
+                        .rewards
+                        .iter()
+                        .map(|r| ("reward", check_reward(r)))
+                        .enumerate(),
+                );
+            for (idx, (kind, command_problems)) in commands {
+                for problem in command_problems {
+                    problems.push(format!(
+                        "{} / {} / {} {}: {}",
+                        group.name, trigger.name, kind, idx, problem
+                    ));
+                }
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::qsd::{QuestCommand, QuestGroup, QuestItem, QuestTrigger};
+    use roselib::io::RoseFile;
+
+    fn qsd(conditions: Vec<QuestCondition>, rewards: Vec<QuestReward>) -> QSD {
+        let mut qsd = QSD::new();
+        qsd.groups.push(QuestGroup {
+            name: String::from("Helping Judy"),
+            triggers: vec![QuestTrigger {
+                name: String::from("judy-01"),
+                conditions,
+                rewards,
+                ..Default::default()
+            }],
+        });
+        qsd
+    }
+
+    #[test]
+    fn test_check_layouts() {
+        assert!(check_layouts(&qsd(
+            vec![QuestCondition::SelectNpc { npc: 1 }],
+            vec![QuestReward::ResetStats]
+        ))
+        .is_empty());
+        // Known opcodes with padding that isn't zero are kept raw but valid
+        let command = |opcode: u32, data: Vec<u8>| QuestCommand { opcode, data };
+        let conditions = vec![
+            QuestCondition::from_command(&command(10, vec![10, 20, 0xCC, 0xCC])),
+            QuestCondition::from_command(&command(13, vec![0; 8])),
+            QuestCondition::from_command(&command(1, vec![2, 0, 0, 0, 0, 0, 0, 0])),
+        ];
+        let rewards = vec![QuestReward::from_command(&command(13, vec![0; 4]))];
+        assert_eq!(
+            check_layouts(&qsd(conditions, rewards)),
+            [
+                "Helping Judy / judy-01 / condition 1: condition 0x0000000d has 8 bytes of operands instead of 4",
+                "Helping Judy / judy-01 / condition 2: failed to fill whole buffer",
+                "Helping Judy / judy-01 / reward 0: unknown reward opcode 0x0000000d"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_tables() {
+        let tables = QuestTables {
+            quests: Some(10),
+            npcs: Some(100),
+            skills: Some(50),
+            items: vec![(8, 100)].into_iter().collect(),
+            triggers: Some(vec![String::from("judy-02")].into_iter().collect()),
+        };
+        assert!(!tables.is_empty());
+        assert!(QuestTables::default().is_empty());
+
+        let item = |item: u32| QuestItem {
+            item,
+            slot: 0,
+            count: 1,
+            op: 0,
+        };
+        let conditions = vec![
+            QuestCondition::SelectQuest { quest: 12 },
+            QuestCondition::Items {
+                items: vec![item(8050), item(8150)],
+            },
+            QuestCondition::SelectNpc { npc: 5000 },
+            QuestCondition::Skill {
+                start: 1,
+                end: 60,
+                has: 1,
+            },
+        ];
+        let rewards = vec![
+            QuestReward::Quest {
+                quest: 3,
+                action: 1,
+            },
+            QuestReward::Item {
+                item: 99001,
+                op: 1,
+                count: 1,
+                party: 0,
+            },
+            QuestReward::Calculated {
+                target: 2,
+                equation: 0,
+                value: 1,
+                item: 8200,
+                party: 0,
+                item_option: 0,
+            },
+            QuestReward::Trigger {
+                trigger: String::from("judy-02"),
+            },
+            QuestReward::Trigger {
+                trigger: String::from("judy-01"),
+            },
+            QuestReward::DelayedTrigger {
+                who: 2,
+                seconds: 10,
+                trigger: String::from("judy-03"),
+            },
+        ];
+        assert_eq!(
+            tables.check(&qsd(conditions, rewards)),
+            [
+                "Helping Judy / judy-01 / condition 0: quest 12 isn't a row of LIST_QUEST.STB, which has 10 rows",
+                "Helping Judy / judy-01 / condition 1: item 150 isn't a row of LIST_WEAPON.STB, which has 100 rows (8150)",
+                "Helping Judy / judy-01 / condition 2: NPC 5000 isn't a row of LIST_NPC.STB, which has 100 rows",
+                "Helping Judy / judy-01 / condition 3: skill 60 isn't a row of LIST_SKILL.STB, which has 50 rows",
+                "Helping Judy / judy-01 / reward 1: item 99001 has the unknown type 99",
+                "Helping Judy / judy-01 / reward 2: item 200 isn't a row of LIST_WEAPON.STB, which has 100 rows (8200)",
+                "Helping Judy / judy-01 / reward 5: trigger judy-03 isn't in the QSD files"
+            ]
+        );
+    }
+}
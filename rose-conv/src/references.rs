@@ -0,0 +1,500 @@
+//! References between the files of a client
+//!
+//! The files a file references are read from the formats that reference
+//! others by client path: the meshes, textures, effects and animations of a
+//! ZSC, the lightmaps of a LIT, the sheets of a TSI, the tile textures and map
+//! blocks of a ZON, the ZSCs of the zone and the NPCs, sounds and effects of
+//! an IFO and the cells of an STB that are paths. Other formats that aren't
+//! leaves, such as CHR, EFT, PTL or XML, are scanned for strings that look
+//! like client paths.
+//!
+//! Files are used if they are reachable from the roots, the files the client
+//! loads by a path in its executable: every file of a type that isn't
+//! referenced by other files, such as STB, STL, CHR and TSI files.
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::path::Path;
+
+use failure::Error;
+use serde::Serialize;
+
+use roselib::files::{IFO, LIT, STB, TSI, ZON, ZSC};
+
+use crate::client::{Client, ClientFile};
+use crate::ifo_instances::zone_scenes;
+use crate::validate::LIST_ZONE_PATH;
+use crate::vfs::client_path_key;
+use crate::zsc_bundle::dependencies;
+
+/// Types of files that are only loaded through references from other files
+const REFERENCED_TYPES: [&str; 14] = [
+    "zsc", "zms", "zmo", "zmd", "dds", "tga", "zon", "ifo", "him", "til", "lit", "eft", "ptl",
+    "wav",
+];
+
+/// Types of files that don't reference other files, or whose references
+/// aren't known, which are not scanned
+const LEAF_TYPES: [&str; 22] = [
+    "zms", "zmo", "zmd", "dds", "tga", "bmp", "jpg", "png", "him", "til", "stl", "wav", "ogg",
+    "mp3", "bik", "avi", "ttf", "fnt", "exe", "dll", "vfs", "idx",
+];
+
+/// Extensions of the client paths found in scanned files and STB cells
+const PATH_TYPES: [&str; 25] = [
+    "zsc", "zms", "zmo", "zmd", "dds", "tga", "bmp", "png", "jpg", "zon", "ifo", "him", "til",
+    "lit", "eft", "ptl", "chr", "stb", "stl", "tsi", "wav", "ogg", "mp3", "xml", "lua",
+];
+
+/// References of every file of a client, by the client path of the file
+#[derive(Debug, Default)]
+pub struct ReferenceGraph {
+    /// Paths referenced by every file, as written in the file with forward
+    /// slashes, without duplicates
+    pub references: BTreeMap<String, Vec<String>>,
+    /// Files that couldn't be read, with the error
+    pub unreadable: Vec<(String, String)>,
+}
+
+impl ReferenceGraph {
+    pub fn new(client: &Client) -> ReferenceGraph {
+        let context = Context::new(client);
+        let mut graph = ReferenceGraph::default();
+        for file in client.files() {
+            if LEAF_TYPES.contains(&file.extension().as_str()) {
+                continue;
+            }
+            match context.file_references(file) {
+                Ok(references) if !references.is_empty() => {
+                    graph.references.insert(file.path.clone(), references);
+                }
+                Ok(_) => {}
+                Err(e) => graph.unreadable.push((file.path.clone(), e.to_string())),
+            }
+        }
+        graph
+    }
+
+    /// Paths referenced by a file
+    pub fn references_of(&self, path: &str) -> &[String] {
+        self.references
+            .get(path)
+            .map(|r| r.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Files of a type only loaded through references that no root
+    /// references, directly or through other files
+    pub fn unused<'a>(&self, client: &'a Client) -> Vec<&'a ClientFile> {
+        let mut used: HashSet<String> = HashSet::new();
+        let mut stack: Vec<&ClientFile> = client.files().filter(|f| is_root(f)).collect();
+        while let Some(file) = stack.pop() {
+            if !used.insert(client_path_key(&file.path)) {
+                continue;
+            }
+            for path in self.references_of(&file.path) {
+                if let Some(referenced) = client.get(path) {
+                    stack.push(referenced);
+                }
+            }
+        }
+
+        client
+            .files()
+            .filter(|f| !is_root(f) && !used.contains(&client_path_key(&f.path)))
+            .collect()
+    }
+
+    /// Referenced paths missing from the client, by referencing file
+    pub fn missing(&self, client: &Client) -> BTreeMap<String, Vec<String>> {
+        self.references
+            .iter()
+            .filter_map(|(file, references)| {
+                let missing: Vec<String> = references
+                    .iter()
+                    .filter(|path| !client.contains(path))
+                    .cloned()
+                    .collect();
+                if missing.is_empty() {
+                    None
+                } else {
+                    Some((file.clone(), missing))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Files a file of a client depends on, directly or through other files
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct DependencyGraph {
+    /// Client path of the file the graph is rooted at
+    pub root: String,
+    /// Files of the graph, the root first, in the order they're reached
+    pub files: Vec<Dependency>,
+    pub references: Vec<Reference>,
+    /// Files whose references couldn't be read, with the error
+    pub unreadable: Vec<(String, String)>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Dependency {
+    /// Client path, in the case of the client if it has the file
+    pub path: String,
+    /// Whether the client doesn't have the file
+    pub missing: bool,
+}
+
+/// Reference of a file of the graph to another, by client path
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Reference {
+    pub from: String,
+    pub to: String,
+}
+
+impl DependencyGraph {
+    /// Graph of the files reachable from `root`, reading only those files
+    pub fn new(client: &Client, root: &ClientFile) -> DependencyGraph {
+        let context = Context::new(client);
+        let mut graph = DependencyGraph {
+            root: root.path.clone(),
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+        seen.insert(client_path_key(&root.path));
+        graph.files.push(Dependency {
+            path: root.path.clone(),
+            missing: false,
+        });
+
+        let mut queue = VecDeque::new();
+        queue.push_back(root);
+        while let Some(file) = queue.pop_front() {
+            if LEAF_TYPES.contains(&file.extension().as_str()) {
+                continue;
+            }
+            let references = match context.file_references(file) {
+                Ok(references) => references,
+                Err(e) => {
+                    graph.unreadable.push((file.path.clone(), e.to_string()));
+                    continue;
+                }
+            };
+            for path in references {
+                let referenced = client.get(&path);
+                let path = referenced.map_or(path, |f| f.path.clone());
+                if seen.insert(client_path_key(&path)) {
+                    graph.files.push(Dependency {
+                        path: path.clone(),
+                        missing: referenced.is_none(),
+                    });
+                    if let Some(referenced) = referenced {
+                        queue.push_back(referenced);
+                    }
+                }
+                graph.references.push(Reference {
+                    from: file.path.clone(),
+                    to: path,
+                });
+            }
+        }
+        graph
+    }
+
+    /// Graphviz DOT of the graph, with the missing files dashed
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph deps {\n");
+        for file in &self.files {
+            let style = if file.missing { " [style=dashed]" } else { "" };
+            let _ = writeln!(dot, "    \"{}\"{};", escape(&file.path), style);
+        }
+        for reference in &self.references {
+            let _ = writeln!(
+                dot,
+                "    \"{}\" -> \"{}\";",
+                escape(&reference.from),
+                escape(&reference.to)
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// File of a client by client path, or the ZON of a zone by its name, e.g.
+/// `JPT01` for `3DDATA/MAPS/JUNON/JPT01/JPT01.ZON`
+pub fn resolve_root<'a>(client: &'a Client, name: &str) -> Option<&'a ClientFile> {
+    client.get(name).or_else(|| {
+        client.files_with_extension("zon").find(|f| {
+            let file_name = f.path.rsplit('/').next().unwrap_or_default();
+            file_name.len() > 4 && file_name[..file_name.len() - 4].eq_ignore_ascii_case(name)
+        })
+    })
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn is_root(file: &ClientFile) -> bool {
+    !REFERENCED_TYPES.contains(&file.extension().as_str())
+}
+
+/// Files of a client that the references of other files depend on
+struct Context<'a> {
+    client: &'a Client,
+    list_zone: Option<STB>,
+    /// ZON files by the key of their directory
+    zones: HashMap<String, &'a ClientFile>,
+}
+
+impl<'a> Context<'a> {
+    fn new(client: &'a Client) -> Context<'a> {
+        let zones = client
+            .files_with_extension("zon")
+            .map(|f| (client_path_key(directory(&f.path)), f))
+            .collect();
+        Context {
+            client,
+            list_zone: client.read_file(LIST_ZONE_PATH).ok(),
+            zones,
+        }
+    }
+
+    /// Paths referenced by a file of the client
+    fn file_references(&self, file: &ClientFile) -> Result<Vec<String>, Error> {
+        let client = self.client;
+        let paths: Vec<String> = match file.extension().as_str() {
+            "zsc" => {
+                let zsc: ZSC = client.read_file(&file.path)?;
+                dependencies(&zsc).into_iter().map(|d| d.path).collect()
+            }
+            "lit" => {
+                // Relative to the directory of the LIT
+                let lit: LIT = client.read_file(&file.path)?;
+                let dir = directory(&file.path);
+                lit.filenames
+                    .iter()
+                    .map(|name| format!("{}/{}", dir, name))
+                    .collect()
+            }
+            "tsi" => {
+                let tsi: TSI = client.read_file(&file.path)?;
+                tsi.sprite_sheets
+                    .iter()
+                    .map(|sheet| sheet.path.to_string_lossy().into_owned())
+                    .collect()
+            }
+            "zon" => {
+                let zon: ZON = client.read_file(&file.path)?;
+                let mut paths = zon.textures.clone();
+                paths.extend(zone_blocks(client, file).map(|f| f.path.clone()));
+                paths
+            }
+            "ifo" => {
+                let ifo: IFO = client.read_file(&file.path)?;
+                let mut paths = Vec::new();
+                // The objects of the ZSCs of the zone
+                if let Some((decorations, buildings)) = self.zone_scenes(&file.path) {
+                    if !ifo.objects.is_empty() {
+                        paths.push(decorations);
+                    }
+                    if !ifo.buildings.is_empty() {
+                        paths.push(buildings);
+                    }
+                }
+                let npcs = ifo.npcs.iter().map(|n| &n.file);
+                let sounds = ifo.sounds.iter().map(|s| &s.file);
+                let effects = ifo.effects.iter().map(|e| &e.file);
+                paths.extend(npcs.chain(sounds).chain(effects).cloned());
+                paths
+            }
+            "stb" => {
+                let stb: STB = client.read_file(&file.path)?;
+                stb.data
+                    .iter()
+                    .flatten()
+                    .filter(|cell| is_client_path(cell.trim()))
+                    .map(|cell| cell.trim().to_string())
+                    .collect()
+            }
+            _ => path_strings(&client.read(&file.path)?),
+        };
+
+        let mut seen = HashSet::new();
+        Ok(paths
+            .into_iter()
+            .map(|p| p.trim().replace('\\', "/"))
+            .filter(|p| !p.is_empty() && seen.insert(client_path_key(p)))
+            .collect())
+    }
+
+    /// Decoration and construction ZSCs of the zone of a map block file, in
+    /// the directory of the zone's ZON
+    fn zone_scenes(&self, path: &str) -> Option<(String, String)> {
+        let zon = self.zones.get(&client_path_key(directory(path)))?;
+        zone_scenes(self.list_zone.as_ref()?, &zon.path)
+    }
+}
+
+/// Whether a string looks like a client path, a path in a directory with the
+/// extension of a client file
+pub fn is_client_path(s: &str) -> bool {
+    if !s.contains(['/', '\\']) || s.contains(char::is_whitespace) {
+        return false;
+    }
+    Path::new(s)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| PATH_TYPES.contains(&e.as_str()))
+}
+
+/// Client paths in the printable strings of a file
+pub fn path_strings(data: &[u8]) -> Vec<String> {
+    let is_separator = |b: &u8| {
+        !(0x21..0x7f).contains(b) || matches!(b, b'"' | b'\'' | b'<' | b'>' | b'=' | b',' | b';')
+    };
+    data.split(is_separator)
+        .filter(|word| word.len() > 4)
+        .filter_map(|word| std::str::from_utf8(word).ok())
+        .filter(|word| is_client_path(word))
+        .map(String::from)
+        .collect()
+}
+
+/// Map blocks of a zone, the `<x>_<y>` files in the directory of its ZON and
+/// the files in their `<x>_<y>` directories, such as lightmaps
+fn zone_blocks<'a>(
+    client: &'a Client,
+    zon: &ClientFile,
+) -> impl Iterator<Item = &'a ClientFile> + 'a {
+    let dir = client_path_key(directory(&zon.path));
+    client.files().filter(move |f| {
+        let key = client_path_key(&f.path);
+        let rest = match key.strip_prefix(&dir).and_then(|r| r.strip_prefix('/')) {
+            Some(rest) if !dir.is_empty() => rest,
+            _ => return false,
+        };
+        let block = rest.split(['/', '.']).next().unwrap_or_default();
+        match block.split_once('_') {
+            Some((x, y)) => x.parse::<u32>().is_ok() && y.parse::<u32>().is_ok(),
+            None => false,
+        }
+    })
+}
+
+/// Directory of a client path, empty for files at the root
+fn directory(path: &str) -> &str {
+    path.rfind('/').map(|i| &path[..i]).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::PathBuf;
+
+    use roselib::files::zsc::SceneMaterial;
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_path_strings() {
+        assert!(is_client_path("3DDATA\\NPC\\ANIMAL\\PIG.ZMD"));
+        assert!(!is_client_path("PIG.ZMD"));
+        assert!(!is_client_path("3DDATA/NPC/README"));
+        let data = b"\x02\x003DDATA\\NPC\\PIG.ZMD\x00\x013DDATA\\MOTION\\PIG_RUN.ZMO\x00<x file=\"3DDATA/FX/A.EFT\"/>";
+        assert_eq!(
+            path_strings(data),
+            [
+                "3DDATA\\NPC\\PIG.ZMD",
+                "3DDATA\\MOTION\\PIG_RUN.ZMO",
+                "3DDATA/FX/A.EFT"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_graph() {
+        let dir = std::env::temp_dir().join("rose-conv-test-references");
+        fs::create_dir_all(dir.join("3DDATA/DECO")).unwrap();
+        fs::create_dir_all(dir.join("3DDATA/NPC")).unwrap();
+        fs::write(dir.join("3DDATA/DECO/TREE.ZMS"), "").unwrap();
+        fs::write(dir.join("3DDATA/DECO/TREE.DDS"), "").unwrap();
+        fs::write(dir.join("3DDATA/DECO/OLD.DDS"), "").unwrap();
+        fs::write(dir.join("3DDATA/NPC/PIG.ZMD"), "").unwrap();
+        fs::write(dir.join("3DDATA/NPC/OLD.ZMD"), "").unwrap();
+        fs::write(
+            dir.join("3DDATA/NPC/LIST_NPC.CHR"),
+            b"\x01\x003DDATA\\NPC\\PIG.ZMD\x00",
+        )
+        .unwrap();
+
+        // A ZSC referenced by a root
+        let mut zsc = ZSC::new();
+        zsc.meshes.push(PathBuf::from("3DDATA\\DECO\\TREE.ZMS"));
+        zsc.meshes.push(PathBuf::from("3DDATA\\DECO\\GONE.ZMS"));
+        zsc.materials.push(SceneMaterial {
+            path: PathBuf::from("3DDATA\\DECO\\TREE.DDS"),
+            ..Default::default()
+        });
+        zsc.write_to_path(&dir.join("3DDATA/DECO/DECO.ZSC"))
+            .unwrap();
+        fs::write(
+            dir.join("scenes.xml"),
+            "<scene path=\"3DDATA/DECO/DECO.ZSC\"/>",
+        )
+        .unwrap();
+
+        let client = Client::open(&dir).unwrap();
+        let graph = ReferenceGraph::new(&client);
+        assert_eq!(
+            graph.references_of("3DDATA/DECO/DECO.ZSC"),
+            [
+                "3DDATA/DECO/TREE.ZMS",
+                "3DDATA/DECO/GONE.ZMS",
+                "3DDATA/DECO/TREE.DDS"
+            ]
+        );
+        let unused: Vec<&str> = graph
+            .unused(&client)
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect();
+        assert_eq!(unused, ["3DDATA/DECO/OLD.DDS", "3DDATA/NPC/OLD.ZMD"]);
+
+        let missing = graph.missing(&client);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing["3DDATA/DECO/DECO.ZSC"], ["3DDATA/DECO/GONE.ZMS"]);
+
+        let root = resolve_root(&client, "SCENES.XML").unwrap();
+        let deps = DependencyGraph::new(&client, root);
+        let files: Vec<(&str, bool)> = deps
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.missing))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("scenes.xml", false),
+                ("3DDATA/DECO/DECO.ZSC", false),
+                ("3DDATA/DECO/TREE.ZMS", false),
+                ("3DDATA/DECO/GONE.ZMS", true),
+                ("3DDATA/DECO/TREE.DDS", false)
+            ]
+        );
+        assert_eq!(deps.references.len(), 4);
+        let dot = deps.to_dot();
+        assert!(dot.contains("    \"3DDATA/DECO/GONE.ZMS\" [style=dashed];\n"));
+        assert!(dot.contains("    \"scenes.xml\" -> \"3DDATA/DECO/DECO.ZSC\";\n"));
+
+        fs::create_dir_all(dir.join("3DDATA/MAPS/JUNON/JPT01")).unwrap();
+        fs::write(dir.join("3DDATA/MAPS/JUNON/JPT01/JPT01.ZON"), "").unwrap();
+        let client = Client::open(&dir).unwrap();
+        let zon = resolve_root(&client, "jpt01").unwrap();
+        assert_eq!(zon.path, "3DDATA/MAPS/JUNON/JPT01/JPT01.ZON");
+        assert!(resolve_root(&client, "JPT02").is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
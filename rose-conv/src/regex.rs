@@ -0,0 +1,482 @@
+//! Small regular expression engine for searching strings
+//!
+//! Supports literals, `.`, classes like `[a-z]` and `[^0-9]`, `\d`, `\w` and
+//! `\s`, the anchors `^` and `$`, groups with alternation like `(a|b)` and the
+//! quantifiers `*`, `+`, `?` and `{n,m}`. Patterns are compiled to a program
+//! run by a Pike VM, so matching time is linear in the length of the text.
+use failure::{bail, Error};
+
+/// Maximum number of instructions of a compiled pattern, large repetitions
+/// like `(a{100}){100}` are rejected
+const MAX_PROGRAM_SIZE: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl Class {
+    fn new(ranges: &[(char, char)], negated: bool) -> Class {
+        Class {
+            ranges: ranges.to_vec(),
+            negated,
+        }
+    }
+
+    fn contains(&self, c: char, ignore_case: bool) -> bool {
+        let in_ranges = |c: char| self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+        let found = in_ranges(c)
+            || (ignore_case && (in_ranges(fold_case(c)) || c.to_uppercase().any(in_ranges)));
+        found != self.negated
+    }
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+/// Compiled regular expression
+#[derive(Clone, Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+    ignore_case: bool,
+}
+
+impl Regex {
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Regex, Error> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let node = parser.parse_alternation()?;
+        if parser.pos < parser.chars.len() {
+            bail!("Unmatched `)` at position {}", parser.pos);
+        }
+
+        let mut program = Vec::new();
+        compile(&node, &mut program, ignore_case)?;
+        program.push(Inst::Match);
+        Ok(Regex {
+            program,
+            ignore_case,
+        })
+    }
+
+    /// Whether the pattern matches anywhere in the text
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+
+        for pos in 0..=chars.len() {
+            // Start a new thread at every position for an unanchored search
+            current.add(&self.program, 0, pos, chars.len());
+
+            for idx in 0..current.list.len() {
+                let pc = current.list[idx];
+                let c = chars.get(pos).cloned();
+                let matched = match (&self.program[pc], c) {
+                    (Inst::Match, _) => return true,
+                    (Inst::Char(expected), Some(c)) => {
+                        *expected == c || (self.ignore_case && *expected == fold_case(c))
+                    }
+                    (Inst::Any, Some(_)) => true,
+                    (Inst::Class(class), Some(c)) => class.contains(c, self.ignore_case),
+                    _ => false,
+                };
+                if matched {
+                    next.add(&self.program, pc + 1, pos + 1, chars.len());
+                }
+            }
+
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        false
+    }
+}
+
+/// Instructions waiting to run at a position
+struct Threads {
+    list: Vec<usize>,
+    /// Position every instruction was last added at, including jumps
+    added: Vec<Option<usize>>,
+}
+
+impl Threads {
+    fn new(size: usize) -> Threads {
+        Threads {
+            list: Vec::with_capacity(size),
+            added: vec![None; size],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    /// Add an instruction, following jumps and checking anchors
+    fn add(&mut self, program: &[Inst], pc: usize, pos: usize, len: usize) {
+        if self.added[pc] == Some(pos) {
+            return;
+        }
+        self.added[pc] = Some(pos);
+
+        match program[pc] {
+            Inst::Jump(target) => self.add(program, target, pos, len),
+            Inst::Split(a, b) => {
+                self.add(program, a, pos, len);
+                self.add(program, b, pos, len);
+            }
+            Inst::Start if pos == 0 => self.add(program, pc + 1, pos, len),
+            Inst::End if pos == len => self.add(program, pc + 1, pos, len),
+            Inst::Start | Inst::End => {}
+            _ => self.list.push(pc),
+        }
+    }
+}
+
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>, ignore_case: bool) -> Result<(), Error> {
+    if program.len() > MAX_PROGRAM_SIZE {
+        bail!("Pattern is too large");
+    }
+
+    match node {
+        Node::Empty => {}
+        Node::Char(c) if ignore_case => program.push(Inst::Char(fold_case(*c))),
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, program, ignore_case)?;
+            }
+        }
+        Node::Alternate(nodes) => {
+            // Every alternative but the last splits off to the next one and
+            // jumps to the end when it matches
+            let mut jumps = Vec::new();
+            for (idx, node) in nodes.iter().enumerate() {
+                if idx + 1 < nodes.len() {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(node, program, ignore_case)?;
+                    jumps.push(program.len());
+                    program.push(Inst::Jump(0));
+                    program[split] = Inst::Split(split + 1, program.len());
+                } else {
+                    compile(node, program, ignore_case)?;
+                }
+            }
+            for jump in jumps {
+                program[jump] = Inst::Jump(program.len());
+            }
+        }
+        Node::Repeat { node, min, max } => {
+            for _ in 0..*min {
+                compile(node, program, ignore_case)?;
+            }
+            match max {
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(node, program, ignore_case)?;
+                    program.push(Inst::Jump(split));
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                Some(max) => {
+                    for _ in *min..*max {
+                        let split = program.len();
+                        program.push(Inst::Split(split + 1, 0));
+                        compile(node, program, ignore_case)?;
+                        program[split] = Inst::Split(split + 1, program.len());
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, Error> {
+        let mut nodes = vec![self.parse_concat()?];
+        while self.eat('|') {
+            nodes.push(self.parse_concat()?);
+        }
+        Ok(if nodes.len() == 1 {
+            nodes.remove(0)
+        } else {
+            Node::Alternate(nodes)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, Error> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            nodes.push(self.parse_repeat()?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.remove(0),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn parse_repeat(&mut self) -> Result<Node, Error> {
+        let mut node = self.parse_atom()?;
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.parse_counts()? {
+                    Some(counts) => counts,
+                    None => break,
+                },
+                _ => break,
+            };
+            // Skip the quantifier or the closing brace of the counts
+            self.pos += 1;
+            // Lazy quantifiers match the same strings
+            self.eat('?');
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+            };
+        }
+        Ok(node)
+    }
+
+    /// Counts of a `{n}`, `{n,}` or `{n,m}` repetition, stopping before the
+    /// closing brace. A brace that doesn't start a repetition is a literal.
+    fn parse_counts(&mut self) -> Result<Option<(u32, Option<u32>)>, Error> {
+        let end = match self.chars[self.pos..].iter().position(|&c| c == '}') {
+            Some(end) => self.pos + end,
+            None => return Ok(None),
+        };
+        let counts: String = self.chars[self.pos + 1..end].iter().collect();
+        let parse = |s: &str| s.trim().parse::<u32>().ok();
+        let (min, max) = match counts.find(',') {
+            None => match parse(&counts) {
+                Some(n) => (n, Some(n)),
+                None => return Ok(None),
+            },
+            Some(idx) => match (parse(&counts[..idx]), &counts[idx + 1..]) {
+                (Some(min), max) if max.trim().is_empty() => (min, None),
+                (Some(min), max) => match parse(max) {
+                    Some(max) => (min, Some(max)),
+                    None => return Ok(None),
+                },
+                (None, _) => return Ok(None),
+            },
+        };
+        if max.is_some_and(|max| max < min) {
+            bail!("Invalid repetition {{{}}}", counts);
+        }
+        self.pos = end;
+        Ok(Some((min, max)))
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, Error> {
+        let c = match self.next() {
+            Some(c) => c,
+            None => bail!("Unexpected end of pattern"),
+        };
+        Ok(match c {
+            '(' => {
+                // Non-capturing groups are the same as groups
+                if self.peek() == Some('?') {
+                    self.pos += 1;
+                    if !self.eat(':') {
+                        bail!("Unsupported group syntax at position {}", self.pos);
+                    }
+                }
+                let node = self.parse_alternation()?;
+                if !self.eat(')') {
+                    bail!("Unclosed group");
+                }
+                node
+            }
+            '[' => Node::Class(self.parse_class()?),
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '\\' => match self.parse_escape()? {
+                Escape::Char(c) => Node::Char(c),
+                Escape::Class(class) => Node::Class(class),
+            },
+            '*' | '+' | '?' => bail!("Nothing to repeat at position {}", self.pos - 1),
+            c => Node::Char(c),
+        })
+    }
+
+    fn parse_escape(&mut self) -> Result<Escape, Error> {
+        let c = match self.next() {
+            Some(c) => c,
+            None => bail!("Trailing backslash"),
+        };
+        Ok(match c {
+            'd' => Escape::Class(Class::new(DIGIT, false)),
+            'D' => Escape::Class(Class::new(DIGIT, true)),
+            'w' => Escape::Class(Class::new(WORD, false)),
+            'W' => Escape::Class(Class::new(WORD, true)),
+            's' => Escape::Class(Class::new(SPACE, false)),
+            'S' => Escape::Class(Class::new(SPACE, true)),
+            'n' => Escape::Char('\n'),
+            'r' => Escape::Char('\r'),
+            't' => Escape::Char('\t'),
+            c if c.is_alphanumeric() => bail!("Unsupported escape \\{}", c),
+            c => Escape::Char(c),
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Class, Error> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => bail!("Unclosed character class"),
+            };
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = match c {
+                '\\' => match self.parse_escape()? {
+                    Escape::Char(c) => c,
+                    Escape::Class(Class {
+                        ranges: class_ranges,
+                        negated: false,
+                    }) => {
+                        ranges.extend(class_ranges);
+                        continue;
+                    }
+                    Escape::Class(_) => bail!("Negated classes aren't supported in classes"),
+                },
+                c => c,
+            };
+
+            let is_range =
+                self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']');
+            if !is_range {
+                ranges.push((lo, lo));
+                continue;
+            }
+            self.pos += 1;
+            let hi = match self.next() {
+                Some('\\') => match self.parse_escape()? {
+                    Escape::Char(c) => c,
+                    Escape::Class(_) => bail!("Invalid class range"),
+                },
+                Some(c) => c,
+                None => bail!("Unclosed character class"),
+            };
+            if hi < lo {
+                bail!("Invalid class range {}-{}", lo, hi);
+            }
+            ranges.push((lo, hi));
+        }
+        Ok(Class { ranges, negated })
+    }
+}
+
+enum Escape {
+    Char(char),
+    Class(Class),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regex() {
+        let is_match =
+            |pattern: &str, text: &str| Regex::new(pattern, false).unwrap().is_match(text);
+
+        assert!(is_match("Sword", "Iron Sword"));
+        assert!(!is_match("^Sword", "Iron Sword"));
+        assert!(is_match("^Iron S.ord$", "Iron Sword"));
+        assert!(is_match("(Axe|Sword)s?$", "Swords"));
+        assert!(is_match("[A-Z][a-z]+ \\d{2,3}", "Level 120"));
+        assert!(!is_match("[A-Z][a-z]+ \\d{2,3}$", "Level 1200"));
+        assert!(is_match("[^a-z ]", "abc D"));
+        assert!(is_match("(a|b)*c", "ababac"));
+        assert!(is_match("몬스터", "몬스터 사냥"));
+        assert!(is_match("a{,2}", "a{,2}"));
+        assert!(Regex::new("sword", true).unwrap().is_match("Iron SWORD"));
+        assert!(Regex::new("[a-c]x", true).unwrap().is_match("BX"));
+
+        assert!(Regex::new("(a", false).is_err());
+        assert!(Regex::new("a)", false).is_err());
+        assert!(Regex::new("*a", false).is_err());
+        assert!(Regex::new("[a", false).is_err());
+    }
+}
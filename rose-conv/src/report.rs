@@ -0,0 +1,100 @@
+//! Machine-readable summary of a conversion run
+use std::path::{Path, PathBuf};
+
+use failure::Error;
+use serde::Serialize;
+
+/// Outcome of converting a single input
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversionStatus {
+    Ok,
+    Failed,
+}
+
+/// Result of converting a single input
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ConversionResult {
+    pub input: PathBuf,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<String>,
+    pub status: ConversionStatus,
+    pub outputs: Vec<PathBuf>,
+    pub error: Option<String>,
+}
+
+/// Results of every conversion attempted during a run
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Report {
+    pub results: Vec<ConversionResult>,
+}
+
+impl Report {
+    pub fn new() -> Report {
+        Self::default()
+    }
+
+    /// Record a successful conversion of `input` as `file_type` into `outputs`
+    pub fn success(&mut self, input: &Path, file_type: &str, outputs: Vec<PathBuf>) {
+        self.results.push(ConversionResult {
+            input: input.to_path_buf(),
+            file_type: Some(file_type.to_string()),
+            status: ConversionStatus::Ok,
+            outputs,
+            error: None,
+        });
+    }
+
+    /// Record a failed conversion of `input`
+    pub fn failure(&mut self, input: &Path, error: &Error) {
+        self.results.push(ConversionResult {
+            input: input.to_path_buf(),
+            file_type: None,
+            status: ConversionStatus::Failed,
+            outputs: Vec::new(),
+            error: Some(error.to_string()),
+        });
+    }
+
+    /// Inputs that failed to convert
+    pub fn failures(&self) -> impl Iterator<Item = &ConversionResult> {
+        self.results
+            .iter()
+            .filter(|r| r.status == ConversionStatus::Failed)
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.failures().next().is_some()
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use failure::format_err;
+
+    #[test]
+    fn test_report() {
+        let mut report = Report::new();
+        assert!(!report.has_failures());
+
+        report.success(Path::new("a.stb"), "stb", vec![PathBuf::from("out/a.csv")]);
+        assert!(!report.has_failures());
+
+        report.failure(Path::new("b.stb"), &format_err!("Invalid file"));
+        assert!(report.has_failures());
+        assert_eq!(report.failures().count(), 1);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["results"][0]["status"], "ok");
+        assert_eq!(json["results"][0]["type"], "stb");
+        assert_eq!(json["results"][0]["outputs"][0], "out/a.csv");
+        assert_eq!(json["results"][1]["status"], "failed");
+        assert_eq!(json["results"][1]["error"], "Invalid file");
+    }
+}
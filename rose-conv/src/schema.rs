@@ -0,0 +1,50 @@
+//! JSON Schemas describing the serialized structure of ROSE files
+use failure::{bail, Error};
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+
+use roselib::files::*;
+
+/// File types that have a JSON Schema
+pub const SCHEMA_TYPES: [&str; 13] = [
+    "aip", "him", "idx", "ifo", "lit", "qsd", "til", "tsi", "zmd", "zmo", "zms", "zon", "zsc",
+];
+
+/// JSON Schema of the serialized representation of `rose_type`
+pub fn schema(rose_type: &str) -> Result<RootSchema, Error> {
+    let schema = match rose_type {
+        "aip" => schema_for!(AIP),
+        "him" => schema_for!(HIM),
+        "idx" => schema_for!(IDX),
+        "ifo" => schema_for!(IFO),
+        "lit" => schema_for!(LIT),
+        "qsd" => schema_for!(QSD),
+        "til" => schema_for!(TIL),
+        "tsi" => schema_for!(TSI),
+        "zmd" => schema_for!(ZMD),
+        "zmo" => schema_for!(ZMO),
+        "zms" => schema_for!(ZMS),
+        "zon" => schema_for!(ZON),
+        "zsc" => schema_for!(ZSC),
+        _ => bail!("No schema for file type: {}", rose_type),
+    };
+    Ok(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema() {
+        for rose_type in SCHEMA_TYPES.iter() {
+            let json = serde_json::to_value(schema(rose_type).unwrap()).unwrap();
+            assert_eq!(json["type"], "object");
+        }
+
+        let json = serde_json::to_value(schema("idx").unwrap()).unwrap();
+        assert!(json["properties"]["file_systems"].is_object());
+
+        assert!(schema("stb").is_err());
+    }
+}
@@ -0,0 +1,1654 @@
+//! Readable scripts of quests and AI patterns
+//!
+//! Scripts are a text form of QSD and AIP files with one statement per line,
+//! indented by their level:
+//!
+//! ```text
+//! quest "QUEST_JUNON" version 1
+//!
+//! group "Helping Judy"
+//!   trigger "judy-01" check_next
+//!     when select_npc(1001)
+//!       and level >= 10, job == 111
+//!       and has_item(1021)
+//!     then take_item(1021, 1)
+//!       and reward exp 500 to party
+//!       and trigger("judy-02")
+//!       and raw 0x01000012 [01 00 00 00]
+//! ```
+//!
+//! AI patterns start with `ai "<title>" idle <seconds> damage <percent>`,
+//! followed by triggers and their events. The conditions of a trigger or event
+//! follow `when` and its rewards or actions `then`, joined by `and` on one line
+//! or on the lines after it, e.g. `when level >= 10 and has_item(1021) then
+//! reward exp 500`. Lines starting with `#` are comments.
+//!
+//! `op` fields are written as the operators of the server: `==`, `!=`, `>`,
+//! `>=`, `<` and `<=` compare a value, `=`, `+=` and `-=` change it. The
+//! common commands of quests have their own forms:
+//!
+//! - `quest_var(<kind>, <index>)`, `var(<kind>, <index>)`, the abilities
+//!   `level`, `exp`, `money`, `job`, ... or `ability(<ability>)` and
+//!   `item(<item>)` or `item(<item>, <slot>)` with an operator and a value,
+//!   e.g. `level >= 10`. The records of one command are separated by commas.
+//! - `has_item(<item>)`, `select_quest(<quest>)` and `select_npc(<npc>)`
+//! - `add_quest(<quest>)`, `remove_quest`, `change_quest`,
+//!   `change_quest_reset` and `select_quest`, `give_item(<item>, <count>)`,
+//!   `take_item(<item>, <count>)` and `trigger("<name>")`
+//! - `reward exp <value>`, `reward money <value>` and
+//!   `reward item <item> x <value>`, with `option <option>` and
+//!   `equation <equation>` of `REWARD_CALC`
+//!
+//! Rewards of the party end with `to party`. Other commands are written with
+//! the snake case name of their variant in roselib and their fields, numbers,
+//! quoted strings, `[...]` lists and `(<field>: <value>, ...)` records, in the
+//! order of their layout, e.g. `random(min: 1, max: 50)`. The field a command
+//! compares or changes with its `op` follows its operator instead, e.g.
+//! `health < 50` or `npc_variable(variable: 2) == 1`. `Raw` commands are
+//! written as `raw` with their opcode and operand bytes, so every file is
+//! written back as is.
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use failure::{bail, format_err, Error};
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+use roselib::files::aip::{AiAction, AiCommand, AiCondition, AiEvent, AiTrigger};
+use roselib::files::qsd::{
+    QuestAbility, QuestCommand, QuestCondition, QuestGroup, QuestItem, QuestReward, QuestTrigger,
+    QuestVariable,
+};
+use roselib::files::{AIP, QSD};
+use roselib::io::RoseFile;
+
+/// Operators of `op` fields by their code in the server, the longer ones
+/// first to read them before their prefixes
+const OPERATORS: &[(&str, u8)] = &[
+    ("==", 0),
+    ("!=", 10),
+    (">=", 2),
+    ("<=", 4),
+    ("+=", 6),
+    ("-=", 7),
+    (">", 1),
+    ("<", 3),
+    ("=", 5),
+];
+
+/// Names of the abilities of the player in quests
+const ABILITIES: &[(&str, i32)] = &[
+    ("job", 4),
+    ("str", 10),
+    ("dex", 11),
+    ("int", 12),
+    ("con", 13),
+    ("charm", 14),
+    ("sense", 15),
+    ("hp", 16),
+    ("mp", 17),
+    ("exp", 30),
+    ("level", 31),
+    ("stat_points", 32),
+    ("skill_points", 37),
+    ("money", 40),
+];
+
+/// Names of the actions of quest rewards
+const QUEST_ACTIONS: &[(&str, u8)] = &[
+    ("remove_quest", 0),
+    ("add_quest", 1),
+    ("change_quest", 2),
+    ("change_quest_reset", 3),
+    ("select_quest", 4),
+];
+
+/// Variants with an `op` field, with the field it compares or changes, which
+/// is written after the operator. Variants comparing their fields with each
+/// other keep `op` with their fields.
+type Operands = &'static [(&'static str, Option<&'static str>)];
+
+const QUEST_CONDITION_OPERANDS: Operands = &[
+    ("QuestTime", Some("time")),
+    ("ObjectVariable", Some("value")),
+    ("CompareNpcVariables", None),
+    ("ClanPosition", Some("value")),
+    ("ClanContribution", Some("value")),
+    ("ClanLevel", Some("value")),
+    ("ClanPoints", Some("value")),
+    ("ClanMoney", Some("money")),
+    ("ClanMemberCount", Some("value")),
+];
+
+const QUEST_REWARD_OPERANDS: Operands = &[
+    ("ObjectVariable", Some("value")),
+    ("ClanMoney", Some("money")),
+    ("ClanPoints", Some("value")),
+    ("ClanContribution", Some("value")),
+];
+
+const AI_CONDITION_OPERANDS: Operands = &[
+    ("DistanceToTarget", Some("distance")),
+    ("AbilityDifference", Some("difference")),
+    ("Health", Some("percent")),
+    ("CompareAbility", None),
+    ("TargetAbility", Some("value")),
+    ("NpcVariable", Some("value")),
+    ("WorldVariable", Some("value")),
+    ("EconomyVariable", Some("value")),
+    ("DistanceToOwner", Some("distance")),
+    ("SelfAbility", Some("value")),
+    ("NearbyCharacterCount", Some("count")),
+    ("MonsterVariable", Some("value")),
+];
+
+const AI_ACTION_OPERANDS: Operands = &[
+    ("SetNpcVariable", Some("value")),
+    ("SetWorldVariable", Some("value")),
+    ("SetEconomyVariable", Some("value")),
+    ("SetMonsterVariable", Some("value")),
+];
+
+/// Code of `>=`, the operator of `has_item(<item>)`
+const HAS_ITEM_OP: u8 = 2;
+
+/// List of the commands of a trigger or event continued by `and`
+#[derive(Clone, Copy)]
+enum List {
+    When,
+    Then,
+}
+
+/// Script of a quest
+pub fn qsd_to_script(qsd: &QSD) -> String {
+    let mut script = String::new();
+    writeln!(script, "quest {} version {}", quote(&qsd.name), qsd.version).unwrap();
+    for group in &qsd.groups {
+        writeln!(script, "\ngroup {}", quote(&group.name)).unwrap();
+        for trigger in &group.triggers {
+            let check_next = if trigger.check_next {
+                " check_next"
+            } else {
+                ""
+            };
+            writeln!(script, "  trigger {}{}", quote(&trigger.name), check_next).unwrap();
+            let conditions = trigger.conditions.iter().map(quest_condition_to_script);
+            write_commands(&mut script, "when", conditions);
+            let rewards = trigger.rewards.iter().map(quest_reward_to_script);
+            write_commands(&mut script, "then", rewards);
+        }
+    }
+    script
+}
+
+/// Quest of a script
+pub fn qsd_from_script(script: &str) -> Result<QSD, Error> {
+    let mut qsd: Option<QSD> = None;
+    let mut list = None;
+    for (idx, line) in statements(script) {
+        let parse = |qsd: &mut Option<QSD>, list: &mut Option<List>| -> Result<(), Error> {
+            let (keyword, rest) = split_keyword(line);
+            if keyword == "quest" {
+                if qsd.is_some() {
+                    bail!("Second quest header");
+                }
+                let (name, rest) = parse_string(rest)?;
+                let mut new_qsd = QSD::new();
+                new_qsd.name = name;
+                new_qsd.version = parse_value(rest, "version")?;
+                *qsd = Some(new_qsd);
+                return Ok(());
+            }
+
+            let qsd = qsd
+                .as_mut()
+                .ok_or_else(|| format_err!("Expected a quest header first"))?;
+            match keyword {
+                "group" => {
+                    let (name, rest) = parse_string(rest)?;
+                    expect_end(rest)?;
+                    qsd.groups.push(QuestGroup {
+                        name,
+                        triggers: Vec::new(),
+                    });
+                    *list = None;
+                }
+                "trigger" => {
+                    let (name, rest) = parse_string(rest)?;
+                    let check_next = match rest.trim() {
+                        "" => false,
+                        "check_next" => true,
+                        s => bail!("Unexpected `{}`", s),
+                    };
+                    let group = qsd
+                        .groups
+                        .last_mut()
+                        .ok_or_else(|| format_err!("Trigger outside of a group"))?;
+                    group.triggers.push(QuestTrigger {
+                        name,
+                        check_next,
+                        ..Default::default()
+                    });
+                    *list = None;
+                }
+                "when" | "then" | "and" => {
+                    let trigger = qsd
+                        .groups
+                        .last_mut()
+                        .and_then(|g| g.triggers.last_mut())
+                        .ok_or_else(|| format_err!("Command outside of a trigger"))?;
+                    for (list, command) in split_commands(keyword, rest, list)? {
+                        match list {
+                            List::When => trigger.conditions.push(parse_quest_condition(command)?),
+                            List::Then => trigger.rewards.push(parse_quest_reward(command)?),
+                        }
+                    }
+                }
+                _ => bail!("Unknown statement `{}`", keyword),
+            }
+            Ok(())
+        };
+        parse(&mut qsd, &mut list).map_err(|e| format_err!("Line {}: {}", idx + 1, e))?;
+    }
+    qsd.ok_or_else(|| format_err!("Script has no quest header"))
+}
+
+/// Script of an AI pattern
+pub fn aip_to_script(aip: &AIP) -> String {
+    let mut script = String::new();
+    writeln!(
+        script,
+        "ai {} idle {} damage {}",
+        quote(&aip.title),
+        aip.idle_interval,
+        aip.damage_chance
+    )
+    .unwrap();
+    for trigger in &aip.triggers {
+        writeln!(script, "\ntrigger {}", quote(&trigger.name)).unwrap();
+        for event in &trigger.events {
+            writeln!(script, "  event {}", quote(&event.name)).unwrap();
+            let conditions = event.conditions.iter().map(|c| match c {
+                AiCondition::Raw(c) => raw_to_script(c.opcode, &c.data),
+                c => command_to_script(c, AI_CONDITION_OPERANDS),
+            });
+            write_commands(&mut script, "when", conditions);
+            let actions = event.actions.iter().map(|a| match a {
+                AiAction::Raw(a) => raw_to_script(a.opcode, &a.data),
+                a => command_to_script(a, AI_ACTION_OPERANDS),
+            });
+            write_commands(&mut script, "then", actions);
+        }
+    }
+    script
+}
+
+/// AI pattern of a script
+pub fn aip_from_script(script: &str) -> Result<AIP, Error> {
+    let mut aip: Option<AIP> = None;
+    let mut list = None;
+    for (idx, line) in statements(script) {
+        let parse = |aip: &mut Option<AIP>, list: &mut Option<List>| -> Result<(), Error> {
+            let (keyword, rest) = split_keyword(line);
+            if keyword == "ai" {
+                if aip.is_some() {
+                    bail!("Second AI header");
+                }
+                let (title, rest) = parse_string(rest)?;
+                let (idle, damage) = match rest.find("damage") {
+                    Some(pos) => rest.split_at(pos),
+                    None => bail!("Expected `damage <percent>`"),
+                };
+                let mut new_aip = AIP::new();
+                new_aip.title = title;
+                new_aip.idle_interval = parse_value(idle, "idle")?;
+                new_aip.damage_chance = parse_value(damage, "damage")?;
+                *aip = Some(new_aip);
+                return Ok(());
+            }
+
+            let aip = aip
+                .as_mut()
+                .ok_or_else(|| format_err!("Expected an AI header first"))?;
+            match keyword {
+                "trigger" => {
+                    let (name, rest) = parse_string(rest)?;
+                    expect_end(rest)?;
+                    aip.triggers.push(AiTrigger {
+                        name,
+                        events: Vec::new(),
+                    });
+                    *list = None;
+                }
+                "event" => {
+                    let (name, rest) = parse_string(rest)?;
+                    expect_end(rest)?;
+                    let trigger = aip
+                        .triggers
+                        .last_mut()
+                        .ok_or_else(|| format_err!("Event outside of a trigger"))?;
+                    trigger.events.push(AiEvent {
+                        name,
+                        ..Default::default()
+                    });
+                    *list = None;
+                }
+                "when" | "then" | "and" => {
+                    let event = aip
+                        .triggers
+                        .last_mut()
+                        .and_then(|t| t.events.last_mut())
+                        .ok_or_else(|| format_err!("Command outside of an event"))?;
+                    for (list, command) in split_commands(keyword, rest, list)? {
+                        match list {
+                            List::When => {
+                                let condition = parse_command(
+                                    "condition",
+                                    command,
+                                    AI_CONDITION_OPERANDS,
+                                    |opcode, data| {
+                                        AiCondition::from_command(&AiCommand { opcode, data })
+                                    },
+                                )?;
+                                event.conditions.push(condition);
+                            }
+                            List::Then => {
+                                let action = parse_command(
+                                    "action",
+                                    command,
+                                    AI_ACTION_OPERANDS,
+                                    |opcode, data| {
+                                        AiAction::from_command(&AiCommand { opcode, data })
+                                    },
+                                )?;
+                                event.actions.push(action);
+                            }
+                        }
+                    }
+                }
+                _ => bail!("Unknown statement `{}`", keyword),
+            }
+            Ok(())
+        };
+        parse(&mut aip, &mut list).map_err(|e| format_err!("Line {}: {}", idx + 1, e))?;
+    }
+    aip.ok_or_else(|| format_err!("Script has no AI header"))
+}
+
+/// Commands of a list, the first after `keyword` and the others after `and`
+/// on the lines after it
+fn write_commands(script: &mut String, keyword: &str, commands: impl Iterator<Item = String>) {
+    for (idx, command) in commands.enumerate() {
+        if idx == 0 {
+            writeln!(script, "    {} {}", keyword, command).unwrap();
+        } else {
+            writeln!(script, "      and {}", command).unwrap();
+        }
+    }
+}
+
+/// Commands of a `when`, `then` or `and` statement with their list, split at
+/// the words `when`, `then` and `and` outside of strings and brackets. `and`
+/// continues the list of the command before it, also on the lines before.
+fn split_commands<'a>(
+    keyword: &'a str,
+    rest: &'a str,
+    list: &mut Option<List>,
+) -> Result<Vec<(List, &'a str)>, Error> {
+    let mut parts = Vec::new();
+    let (mut keyword, mut start) = (keyword, 0);
+    for (pos, _) in top_level(rest) {
+        if !rest[..pos].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let word = ["when", "then", "and"].iter().find(|word| {
+            rest[pos..]
+                .strip_prefix(**word)
+                .is_some_and(|after| after.is_empty() || after.starts_with(char::is_whitespace))
+        });
+        if let Some(word) = word {
+            parts.push((keyword, &rest[start..pos]));
+            keyword = word;
+            start = pos + word.len();
+        }
+    }
+    parts.push((keyword, &rest[start..]));
+
+    let mut commands = Vec::new();
+    for (keyword, command) in parts {
+        match keyword {
+            "when" => *list = Some(List::When),
+            "then" => *list = Some(List::Then),
+            _ => {}
+        }
+        let list = list.ok_or_else(|| format_err!("Expected `when` or `then` before `and`"))?;
+        match command.trim() {
+            "" => bail!("Expected a command after `{}`", keyword),
+            command => commands.push((list, command)),
+        }
+    }
+    Ok(commands)
+}
+
+/// Condition of a quest by its own form or the name of its variant
+fn quest_condition_to_script(condition: &QuestCondition) -> String {
+    let script = match condition {
+        QuestCondition::SelectQuest { quest } => Some(format!("select_quest({})", quest)),
+        QuestCondition::QuestVariables { variables } => {
+            records_to_script(variables, |v| variable_to_script("quest_var", v))
+        }
+        QuestCondition::Variables { variables } => {
+            records_to_script(variables, |v| variable_to_script("var", v))
+        }
+        QuestCondition::Abilities { abilities } => records_to_script(abilities, ability_to_script),
+        QuestCondition::Items { items } => records_to_script(items, item_to_script),
+        QuestCondition::SelectNpc { npc } => Some(format!("select_npc({})", npc)),
+        QuestCondition::Raw(c) => Some(raw_to_script(c.opcode, &c.data)),
+        _ => None,
+    };
+    script.unwrap_or_else(|| command_to_script(condition, QUEST_CONDITION_OPERANDS))
+}
+
+/// Reward of a quest by its own form or the name of its variant
+fn quest_reward_to_script(reward: &QuestReward) -> String {
+    let script = match reward {
+        QuestReward::Quest { quest, action } => QUEST_ACTIONS
+            .iter()
+            .find(|&&(_, code)| code == *action)
+            .map(|(name, _)| format!("{}({})", name, quest)),
+        QuestReward::Item {
+            item,
+            op,
+            count,
+            party,
+        } => match op {
+            0 => Some("take_item"),
+            1 => Some("give_item"),
+            _ => None,
+        }
+        .and_then(|name| to_party(format!("{}({}, {})", name, item, count), *party)),
+        QuestReward::QuestVariables { variables } => {
+            records_to_script(variables, |v| variable_to_script("quest_var", v))
+        }
+        QuestReward::Abilities { party, abilities } => {
+            records_to_script(abilities, ability_to_script).and_then(|s| to_party(s, *party))
+        }
+        QuestReward::Variables { variables } => {
+            records_to_script(variables, |v| variable_to_script("var", v))
+        }
+        QuestReward::Calculated {
+            target,
+            equation,
+            value,
+            item,
+            party,
+            item_option,
+        } => {
+            let reward = match (target, item, item_option) {
+                (0, 0, 0) => Some(format!("reward exp {}", value)),
+                (1, 0, 0) => Some(format!("reward money {}", value)),
+                (2, _, 0) => Some(format!("reward item {} x {}", item, value)),
+                (2, _, _) => Some(format!(
+                    "reward item {} x {} option {}",
+                    item, value, item_option
+                )),
+                _ => None,
+            };
+            reward
+                .map(|reward| match equation {
+                    0 => reward,
+                    _ => format!("{} equation {}", reward, equation),
+                })
+                .and_then(|reward| to_party(reward, *party))
+        }
+        QuestReward::Trigger { trigger } => Some(format!("trigger({})", quote(trigger))),
+        QuestReward::Raw(r) => Some(raw_to_script(r.opcode, &r.data)),
+        _ => None,
+    };
+    script.unwrap_or_else(|| command_to_script(reward, QUEST_REWARD_OPERANDS))
+}
+
+/// Records separated by commas, `None` if there are none or one of them has
+/// no form
+fn records_to_script<R>(records: &[R], record: impl Fn(&R) -> Option<String>) -> Option<String> {
+    if records.is_empty() {
+        return None;
+    }
+    let records: Option<Vec<String>> = records.iter().map(record).collect();
+    records.map(|records| records.join(", "))
+}
+
+/// `quest_var(<kind>, <index>) <op> <value>` or the same with `var`
+fn variable_to_script(name: &str, variable: &QuestVariable) -> Option<String> {
+    Some(format!(
+        "{}({}, {}) {} {}",
+        name,
+        variable.kind,
+        variable.index,
+        operator_name(variable.op)?,
+        variable.value
+    ))
+}
+
+/// `<ability> <op> <value>`, with `ability(<ability>)` for the abilities
+/// without a name
+fn ability_to_script(ability: &QuestAbility) -> Option<String> {
+    let operator = operator_name(ability.op)?;
+    Some(
+        match ABILITIES.iter().find(|&&(_, code)| code == ability.ability) {
+            Some((name, _)) => format!("{} {} {}", name, operator, ability.value),
+            None => format!(
+                "ability({}) {} {}",
+                ability.ability, operator, ability.value
+            ),
+        },
+    )
+}
+
+/// `has_item(<item>)` for an item anywhere in the inventory, otherwise
+/// `item(<item>) <op> <count>` or `item(<item>, <slot>) <op> <count>`
+fn item_to_script(item: &QuestItem) -> Option<String> {
+    if item.slot == 0 && item.count == 1 && item.op == HAS_ITEM_OP {
+        return Some(format!("has_item({})", item.item));
+    }
+    let operator = operator_name(item.op)?;
+    Some(match item.slot {
+        0 => format!("item({}) {} {}", item.item, operator, item.count),
+        slot => format!("item({}, {}) {} {}", item.item, slot, operator, item.count),
+    })
+}
+
+/// Reward ending with `to party` for the party, `None` for other values of
+/// `party`
+fn to_party(reward: String, party: u8) -> Option<String> {
+    match party {
+        0 => Some(reward),
+        1 => Some(reward + " to party"),
+        _ => None,
+    }
+}
+
+fn operator_name(code: u8) -> Option<&'static str> {
+    OPERATORS
+        .iter()
+        .find(|&&(_, c)| c == code)
+        .map(|&(name, _)| name)
+}
+
+/// Code of the operator at the start of `s` and the rest of `s`
+fn strip_operator(s: &str) -> Option<(u8, &str)> {
+    OPERATORS
+        .iter()
+        .find_map(|&(name, code)| s.strip_prefix(name).map(|rest| (code, rest)))
+}
+
+/// Records of the list commands of quests, e.g. the abilities of
+/// `level >= 10, job == 111`
+enum Records {
+    QuestVariables(Vec<QuestVariable>),
+    Variables(Vec<QuestVariable>),
+    Abilities(Vec<QuestAbility>),
+    Items(Vec<QuestItem>),
+}
+
+impl Records {
+    /// Append the records of `other` if they are of the same type
+    fn append(&mut self, other: Records) -> bool {
+        match (self, other) {
+            (Records::QuestVariables(records), Records::QuestVariables(other))
+            | (Records::Variables(records), Records::Variables(other)) => records.extend(other),
+            (Records::Abilities(records), Records::Abilities(other)) => records.extend(other),
+            (Records::Items(records), Records::Items(other)) => records.extend(other),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Records of a list command separated by commas, `None` if `s` isn't one
+fn parse_records(s: &str) -> Result<Option<Records>, Error> {
+    let mut records: Option<Records> = None;
+    for part in split_list(s) {
+        let record = match parse_record(part)? {
+            Some(record) => record,
+            None if records.is_none() => return Ok(None),
+            None => bail!("Expected a record, found `{}`", part.trim()),
+        };
+        match &mut records {
+            Some(records) => {
+                if !records.append(record) {
+                    bail!(
+                        "`{}` isn't the same type as the records before it",
+                        part.trim()
+                    );
+                }
+            }
+            None => records = Some(record),
+        }
+    }
+    Ok(records)
+}
+
+fn parse_record(s: &str) -> Result<Option<Records>, Error> {
+    let s = s.trim();
+    if let Some(("has_item", args)) = call_args(s) {
+        let item = QuestItem {
+            item: parse_int(single_arg("has_item", &args)?)?,
+            slot: 0,
+            count: 1,
+            op: HAS_ITEM_OP,
+        };
+        return Ok(Some(Records::Items(vec![item])));
+    }
+
+    let (operand, op, value) = match split_operator(s) {
+        Some(operator) => operator,
+        None => return Ok(None),
+    };
+    let operand = operand.trim();
+    let value = value.trim();
+    let (name, args) = call_args(operand).unwrap_or((operand, Vec::new()));
+    let record = match (name, args.as_slice()) {
+        ("quest_var", [kind, index]) | ("var", [kind, index]) => {
+            let variable = QuestVariable {
+                index: parse_int(index)?,
+                kind: parse_int(kind)?,
+                value: parse_int(value)?,
+                op,
+            };
+            if name == "var" {
+                Records::Variables(vec![variable])
+            } else {
+                Records::QuestVariables(vec![variable])
+            }
+        }
+        ("quest_var", _) | ("var", _) => bail!("Expected `{}(<kind>, <index>)`", name),
+        ("item", [item]) | ("item", [item, _]) => Records::Items(vec![QuestItem {
+            item: parse_int(item)?,
+            slot: args.get(1).map_or(Ok(0), |slot| parse_int(slot))?,
+            count: parse_int(value)?,
+            op,
+        }]),
+        ("item", _) => bail!("Expected `item(<item>)` or `item(<item>, <slot>)`"),
+        ("ability", [ability]) => Records::Abilities(vec![QuestAbility {
+            ability: parse_int(ability)?,
+            value: parse_int(value)?,
+            op,
+        }]),
+        ("ability", _) => bail!("Expected `ability(<ability>)`"),
+        (name, []) => match ABILITIES.iter().find(|&&(n, _)| n == name) {
+            Some(&(_, ability)) => Records::Abilities(vec![QuestAbility {
+                ability,
+                value: parse_int(value)?,
+                op,
+            }]),
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(record))
+}
+
+/// Condition of a quest by its own form or the name of its variant
+fn parse_quest_condition(s: &str) -> Result<QuestCondition, Error> {
+    if let Some(records) = parse_records(s)? {
+        return Ok(match records {
+            Records::QuestVariables(variables) => QuestCondition::QuestVariables { variables },
+            Records::Variables(variables) => QuestCondition::Variables { variables },
+            Records::Abilities(abilities) => QuestCondition::Abilities { abilities },
+            Records::Items(items) => QuestCondition::Items { items },
+        });
+    }
+    match call_args(s) {
+        Some(("select_quest", args)) => Ok(QuestCondition::SelectQuest {
+            quest: parse_int(single_arg("select_quest", &args)?)?,
+        }),
+        Some(("select_npc", args)) => Ok(QuestCondition::SelectNpc {
+            npc: parse_int(single_arg("select_npc", &args)?)?,
+        }),
+        _ => parse_command("condition", s, QUEST_CONDITION_OPERANDS, |opcode, data| {
+            QuestCondition::from_command(&QuestCommand { opcode, data })
+        }),
+    }
+}
+
+/// Reward of a quest by its own form or the name of its variant, for the
+/// party if it ends with `to party`
+fn parse_quest_reward(s: &str) -> Result<QuestReward, Error> {
+    let (s, to_party) = match s.strip_suffix("to party") {
+        Some(rest) if rest.ends_with(char::is_whitespace) => (rest.trim_end(), true),
+        _ => (s, false),
+    };
+
+    let mut reward = if let Some(rest) = s.strip_prefix("reward ") {
+        parse_calculated_reward(rest)?
+    } else if let Some(records) = parse_records(s)? {
+        match records {
+            Records::QuestVariables(variables) => QuestReward::QuestVariables { variables },
+            Records::Variables(variables) => QuestReward::Variables { variables },
+            Records::Abilities(abilities) => QuestReward::Abilities {
+                party: 0,
+                abilities,
+            },
+            Records::Items(_) => bail!("Unknown reward `{}`", s),
+        }
+    } else {
+        match call_args(s) {
+            Some((name, args)) if QUEST_ACTIONS.iter().any(|&(n, _)| n == name) => {
+                QuestReward::Quest {
+                    quest: parse_int(single_arg(name, &args)?)?,
+                    action: QUEST_ACTIONS
+                        .iter()
+                        .find(|&&(n, _)| n == name)
+                        .map_or(0, |&(_, action)| action),
+                }
+            }
+            Some((name @ "take_item", args)) | Some((name @ "give_item", args)) => {
+                match args.as_slice() {
+                    [item, count] => QuestReward::Item {
+                        item: parse_int(item)?,
+                        op: if name == "give_item" { 1 } else { 0 },
+                        count: parse_int(count)?,
+                        party: 0,
+                    },
+                    _ => bail!("Expected `{}(<item>, <count>)`", name),
+                }
+            }
+            Some(("trigger", args)) => {
+                let (trigger, rest) = parse_string(single_arg("trigger", &args)?)?;
+                expect_end(rest)?;
+                QuestReward::Trigger { trigger }
+            }
+            _ => parse_command("reward", s, QUEST_REWARD_OPERANDS, |opcode, data| {
+                QuestReward::from_command(&QuestCommand { opcode, data })
+            })?,
+        }
+    };
+
+    if to_party {
+        match &mut reward {
+            QuestReward::Item { party, .. }
+            | QuestReward::Abilities { party, .. }
+            | QuestReward::Calculated { party, .. } => *party = 1,
+            _ => bail!("Unexpected `to party`"),
+        }
+    }
+    Ok(reward)
+}
+
+/// `reward exp <value>`, `reward money <value>` or
+/// `reward item <item> x <value>`, followed by `option <option>` for items
+/// and `equation <equation>`
+fn parse_calculated_reward(s: &str) -> Result<QuestReward, Error> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let (target, item, value, rest) = match words.as_slice() {
+        ["exp", value, rest @ ..] => (0, 0, value, rest),
+        ["money", value, rest @ ..] => (1, 0, value, rest),
+        ["item", item, "x", value, rest @ ..] => (2, parse_int(item)?, value, rest),
+        _ => bail!("Expected `reward exp|money <value>` or `reward item <item> x <value>`"),
+    };
+    let mut equation = 0;
+    let mut item_option = 0;
+    for pair in rest.chunks(2) {
+        match pair {
+            ["equation", value] => equation = parse_int(value)?,
+            ["option", value] if target == 2 => item_option = parse_int(value)?,
+            _ => bail!("Unexpected `{}`", pair.join(" ")),
+        }
+    }
+    Ok(QuestReward::Calculated {
+        target,
+        equation,
+        value: parse_int(value)?,
+        item,
+        party: 0,
+        item_option,
+    })
+}
+
+/// Name and arguments of `<name>(<argument>, ...)`, `None` if `s` isn't a
+/// call or its arguments are fields
+fn call_args(s: &str) -> Option<(&str, Vec<&str>)> {
+    let s = s.trim();
+    let pos = s.find('(')?;
+    let name = s[..pos].trim();
+    let args = s[pos + 1..].strip_suffix(')')?;
+    if name.is_empty()
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        || top_level(args).iter().any(|&(_, c)| c == ':' || c == ')')
+    {
+        return None;
+    }
+    let args = match args.trim() {
+        "" => Vec::new(),
+        _ => split_list(args).into_iter().map(str::trim).collect(),
+    };
+    Some((name, args))
+}
+
+fn single_arg<'a>(name: &str, args: &[&'a str]) -> Result<&'a str, Error> {
+    match args {
+        [arg] => Ok(arg),
+        _ => bail!("Expected one argument of `{}`", name),
+    }
+}
+
+/// Characters of `s` and their positions, skipping quoted strings and the
+/// contents of brackets
+fn top_level(s: &str) -> Vec<(usize, char)> {
+    let mut chars = Vec::new();
+    let mut depth = 0usize;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (pos, c) in s.char_indices() {
+        if quoted {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => quoted = true,
+            '(' | '[' => depth += 1,
+            ')' | ']' if depth > 0 => depth -= 1,
+            c if depth == 0 => chars.push((pos, c)),
+            _ => {}
+        }
+    }
+    chars
+}
+
+/// Parts of `s` between the commas outside of strings and brackets
+fn split_list(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (pos, c) in top_level(s) {
+        if c == ',' {
+            parts.push(&s[start..pos]);
+            start = pos + 1;
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Operand, operator code and value of `<operand> <op> <value>`, with the
+/// first operator outside of strings and brackets
+fn split_operator(s: &str) -> Option<(&str, u8, &str)> {
+    top_level(s).into_iter().find_map(|(pos, _)| {
+        let (code, value) = strip_operator(&s[pos..])?;
+        Some((&s[..pos], code, value))
+    })
+}
+
+/// Lines of a script with their index, without blank lines and comments
+fn statements(script: &str) -> impl Iterator<Item = (usize, &str)> {
+    script
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+fn split_keyword(line: &str) -> (&str, &str) {
+    match line.find(char::is_whitespace) {
+        Some(pos) => (&line[..pos], &line[pos..]),
+        None => (line, ""),
+    }
+}
+
+fn expect_end(rest: &str) -> Result<(), Error> {
+    match rest.trim() {
+        "" => Ok(()),
+        s => bail!("Unexpected `{}`", s),
+    }
+}
+
+/// Value of a `<key> <value>` pair
+fn parse_value<T: TryFrom<i64>>(s: &str, key: &str) -> Result<T, Error> {
+    let mut words = s.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some(k), Some(value), None) if k == key => parse_int(value),
+        _ => bail!("Expected `{} <value>`", key),
+    }
+}
+
+fn parse_int<T: TryFrom<i64>>(s: &str) -> Result<T, Error> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(&hex.replace('_', ""), 16),
+        None => digits.replace('_', "").parse::<i64>(),
+    }
+    .map_err(|_| format_err!("Invalid number `{}`", s))?;
+    let value = if negative { -value } else { value };
+    T::try_from(value).map_err(|_| format_err!("Number out of range `{}`", s))
+}
+
+/// Quoted string, escaping quotes, backslashes and line breaks
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quoted string at the start of `s` and the rest of `s`
+fn parse_string(s: &str) -> Result<(String, &str), Error> {
+    let s = s.trim_start();
+    if !s.starts_with('"') {
+        bail!("Expected a quoted name");
+    }
+
+    let mut string = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '"' => return Ok((string, &s[pos + 1..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => string.push('\n'),
+                Some((_, 'r')) => string.push('\r'),
+                Some((_, c @ '"')) | Some((_, c @ '\\')) => string.push(c),
+                _ => bail!("Invalid escape in string"),
+            },
+            c => string.push(c),
+        }
+    }
+    bail!("Unterminated string")
+}
+
+/// Command by the name of its variant and its fields, e.g.
+/// `random(min: 1, max: 50)`, or only its name if it has no fields. The field
+/// compared or changed by the `op` of the variants in `operands` follows its
+/// operator, e.g. `quest_time >= 300`.
+fn command_to_script<T: Serialize>(command: &T, operands: Operands) -> String {
+    // Commands are plain enums, which always serialize
+    let (variant, fields) = match serde_yaml::to_value(command).unwrap() {
+        Value::String(variant) => return snake_case(&variant),
+        Value::Mapping(variant) => variant.into_iter().next().unwrap(),
+        value => return value_to_script(&value),
+    };
+    let variant = variant.as_str().unwrap_or_default().to_string();
+    let name = snake_case(&variant);
+    let mut fields = match fields {
+        Value::Mapping(fields) => fields,
+        fields => return format!("{}{}", name, value_to_script(&fields)),
+    };
+
+    let op = Value::String(String::from("op"));
+    let operator = fields
+        .get(&op)
+        .and_then(Value::as_u64)
+        .and_then(|code| u8::try_from(code).ok())
+        .and_then(operator_name);
+    let operand = operands
+        .iter()
+        .find(|&&(v, _)| v == variant)
+        .map(|&(_, operand)| operand);
+    match (operand, operator) {
+        (Some(Some(operand)), Some(operator)) => {
+            fields.remove(&op);
+            let value = fields
+                .remove(&Value::String(operand.to_string()))
+                .unwrap_or(Value::Null);
+            let name = if fields.is_empty() {
+                name
+            } else {
+                format!("{}{}", name, fields_to_script(&fields, None))
+            };
+            format!("{} {} {}", name, operator, value_to_script(&value))
+        }
+        (Some(None), Some(operator)) => {
+            format!("{}{}", name, fields_to_script(&fields, Some(operator)))
+        }
+        _ => format!("{}{}", name, fields_to_script(&fields, None)),
+    }
+}
+
+/// Opcode and operand bytes of a command without a layout
+fn raw_to_script(opcode: u32, data: &[u8]) -> String {
+    let bytes: Vec<String> = data.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("raw {:#010x} [{}]", opcode, bytes.join(" "))
+}
+
+/// Numbers, quoted strings, `[...]` lists and `(<field>: <value>, ...)`
+/// records
+fn value_to_script(value: &Value) -> String {
+    match value {
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => quote(s),
+        Value::Bool(b) => b.to_string(),
+        Value::Sequence(values) => {
+            let values: Vec<String> = values.iter().map(value_to_script).collect();
+            format!("[{}]", values.join(", "))
+        }
+        Value::Mapping(fields) => fields_to_script(fields, None),
+        Value::Null => String::from("()"),
+    }
+}
+
+/// `(<field>: <value>, ...)` record, with `operator` as the value of its `op`
+/// field if given
+fn fields_to_script(fields: &Mapping, operator: Option<&str>) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(k, v)| {
+            let field = k.as_str().unwrap_or_default();
+            match operator {
+                Some(operator) if field == "op" => format!("{}: {}", field, operator),
+                _ => format!("{}: {}", field, value_to_script(v)),
+            }
+        })
+        .collect();
+    format!("({})", fields.join(", "))
+}
+
+/// Raw command, passed to `raw` with its opcode and operands, or command read
+/// into its variant by name, with the field of its operator after it for the
+/// variants in `operands`
+fn parse_command<T, F>(kind: &str, s: &str, operands: Operands, raw: F) -> Result<T, Error>
+where
+    T: Serialize + DeserializeOwned,
+    F: Fn(u32, Vec<u8>) -> T,
+{
+    let s = s.trim();
+    if let Some(rest) = s.strip_prefix("raw ") {
+        let (opcode, bytes) = match rest.find('[') {
+            Some(pos) => rest.split_at(pos),
+            None => bail!("Expected `raw <opcode> [<bytes>]`"),
+        };
+        let bytes = bytes
+            .strip_prefix('[')
+            .and_then(|b| b.strip_suffix(']'))
+            .ok_or_else(|| format_err!("Expected `raw <opcode> [<bytes>]`"))?;
+        let data = bytes
+            .split_whitespace()
+            .map(|b| u8::from_str_radix(b, 16).map_err(|_| format_err!("Invalid byte `{}`", b)))
+            .collect::<Result<Vec<u8>, Error>>()?;
+        return Ok(raw(parse_int(opcode.trim())?, data));
+    }
+
+    let (command, operator) = match split_operator(s) {
+        Some((command, op, value)) => (command.trim(), Some((op, value))),
+        None => (s, None),
+    };
+    let (name, fields) = match command.find('(') {
+        Some(pos) => (command[..pos].trim(), Some(&command[pos + 1..])),
+        None => (command, None),
+    };
+    if name == "raw" {
+        bail!("Expected `raw <opcode> [<bytes>]`");
+    }
+    let variant = camel_case(name);
+    if snake_case(&variant) != name || !variant_names::<T>().contains(&variant.as_str()) {
+        bail!("Unknown {} `{}`", kind, name);
+    }
+    let mut fields = match fields {
+        Some(fields) => {
+            let (fields, rest) = parse_fields(fields)?;
+            expect_end(rest)?;
+            Some(fields)
+        }
+        None => None,
+    };
+
+    if let Some((op, value)) = operator {
+        let operand = operands
+            .iter()
+            .find(|&&(v, _)| v == variant)
+            .and_then(|&(_, operand)| operand)
+            .ok_or_else(|| format_err!("`{}` has no operator", name))?;
+        let fields = fields.get_or_insert_with(Mapping::new);
+        for field in &[operand, "op"] {
+            if fields.contains_key(&Value::String(field.to_string())) {
+                bail!("Duplicate field `{}`", field);
+            }
+        }
+        let value: i64 = parse_int(value.trim())?;
+        fields.insert(
+            Value::String(operand.to_string()),
+            Value::Number(value.into()),
+        );
+        fields.insert(Value::String(String::from("op")), Value::Number(op.into()));
+    }
+
+    let value = match fields {
+        Some(fields) => {
+            let mut variant_value = Mapping::new();
+            variant_value.insert(Value::String(variant), Value::Mapping(fields));
+            Value::Mapping(variant_value)
+        }
+        None => Value::String(variant),
+    };
+    let command: T = serde_yaml::from_value(value.clone())
+        .map_err(|e| format_err!("Invalid `{}`: {}", name, e))?;
+    // Unknown fields are ignored when deserializing, so they are the fields
+    // that aren't serialized back
+    if let Some(field) = unknown_field(&value, &serde_yaml::to_value(&command)?) {
+        bail!("Unknown field `{}` of `{}`", field, name);
+    }
+    Ok(command)
+}
+
+/// Value at the start of `s`, a number, a quoted string, a `[...]` list or a
+/// `(<field>: <value>, ...)` record, and the rest of `s`
+fn parse_field_value(s: &str) -> Result<(Value, &str), Error> {
+    let s = s.trim_start();
+    if s.starts_with('"') {
+        let (string, rest) = parse_string(s)?;
+        return Ok((Value::String(string), rest));
+    }
+    if let Some(rest) = s.strip_prefix('(') {
+        let (fields, rest) = parse_fields(rest)?;
+        return Ok((Value::Mapping(fields), rest));
+    }
+    if let Some(rest) = s.strip_prefix('[') {
+        let mut values = Vec::new();
+        let mut rest = rest.trim_start();
+        if let Some(rest) = rest.strip_prefix(']') {
+            return Ok((Value::Sequence(values), rest));
+        }
+        loop {
+            let (value, after) = parse_field_value(rest)?;
+            values.push(value);
+            let after = after.trim_start();
+            if let Some(after) = after.strip_prefix(',') {
+                rest = after;
+            } else if let Some(after) = after.strip_prefix(']') {
+                return Ok((Value::Sequence(values), after));
+            } else {
+                bail!("Expected `,` or `]`");
+            }
+        }
+    }
+
+    let end = s
+        .find(|c: char| c == ',' || c == ')' || c == ']' || c.is_whitespace())
+        .unwrap_or(s.len());
+    let value: i64 = parse_int(&s[..end])?;
+    Ok((Value::Number(value.into()), &s[end..]))
+}
+
+/// `<field>: <value>` pairs up to a closing `)`, and the rest of `s` after it
+fn parse_fields(s: &str) -> Result<(Mapping, &str), Error> {
+    let mut fields = Mapping::new();
+    let mut rest = s.trim_start();
+    if let Some(rest) = rest.strip_prefix(')') {
+        return Ok((fields, rest));
+    }
+    loop {
+        let (field, value) = match rest.find(':') {
+            Some(pos) => (rest[..pos].trim(), &rest[pos + 1..]),
+            None => bail!("Expected `<field>: <value>`, found `{}`", rest.trim()),
+        };
+        // `op` fields are written as their operator
+        let (value, after) = match strip_operator(value.trim_start()) {
+            Some((code, after)) if field == "op" => (Value::Number(code.into()), after),
+            _ => parse_field_value(value)?,
+        };
+        if fields
+            .insert(Value::String(field.to_string()), value)
+            .is_some()
+        {
+            bail!("Duplicate field `{}`", field);
+        }
+        let after = after.trim_start();
+        if let Some(after) = after.strip_prefix(',') {
+            rest = after.trim_start();
+        } else if let Some(after) = after.strip_prefix(')') {
+            return Ok((fields, after));
+        } else {
+            bail!("Expected `,` or `)`");
+        }
+    }
+}
+
+/// First field of a record in `input` that isn't in `output`, searching the
+/// records of its fields too
+fn unknown_field(input: &Value, output: &Value) -> Option<String> {
+    match (input, output) {
+        (Value::Mapping(input), Value::Mapping(output)) => {
+            input
+                .iter()
+                .find_map(|(field, value)| match output.get(field) {
+                    Some(output) => unknown_field(value, output),
+                    None => Some(field.as_str().unwrap_or_default().to_string()),
+                })
+        }
+        (Value::Sequence(input), Value::Sequence(output)) => input
+            .iter()
+            .zip(output)
+            .find_map(|(input, output)| unknown_field(input, output)),
+        _ => None,
+    }
+}
+
+/// Names of the variants of an enum, read from its `Deserialize` impl
+fn variant_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    struct Variants<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de, 'a> Deserializer<'de> for Variants<'a> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not an enum"))
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            variants: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = variants;
+            Err(de::Error::custom("read the variants"))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct identifier ignored_any
+        }
+    }
+
+    let mut variants: &'static [&'static str] = &[];
+    let _ = T::deserialize(Variants(&mut variants));
+    variants
+}
+
+/// `select_npc` of `SelectNpc`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (idx, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && idx > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// `SelectNpc` of `select_npc`
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qsd() -> QSD {
+        let mut qsd = QSD::new();
+        qsd.version = 1;
+        qsd.name = String::from("QUEST_JUNON");
+        qsd.groups.push(QuestGroup {
+            name: String::from("Helping \"Judy\""),
+            triggers: vec![
+                QuestTrigger {
+                    name: String::from("judy-01"),
+                    check_next: true,
+                    conditions: vec![
+                        QuestCondition::SelectNpc { npc: 1001 },
+                        QuestCondition::Abilities {
+                            abilities: vec![
+                                QuestAbility {
+                                    ability: 31,
+                                    value: 10,
+                                    op: 2,
+                                },
+                                QuestAbility {
+                                    ability: 4,
+                                    value: 111,
+                                    op: 0,
+                                },
+                            ],
+                        },
+                        QuestCondition::Items {
+                            items: vec![QuestItem {
+                                item: 8001,
+                                slot: 0,
+                                count: 1,
+                                op: 2,
+                            }],
+                        },
+                        QuestCondition::Items {
+                            items: vec![QuestItem {
+                                item: 8002,
+                                slot: 3,
+                                count: 1,
+                                op: 1,
+                            }],
+                        },
+                        QuestCondition::QuestVariables {
+                            variables: vec![QuestVariable {
+                                index: 2,
+                                kind: 0,
+                                value: 1,
+                                op: 10,
+                            }],
+                        },
+                        QuestCondition::QuestTime { time: 300, op: 3 },
+                        QuestCondition::Random { min: 1, max: 50 },
+                        QuestCondition::CompareNpcVariables {
+                            npc: 1001,
+                            variable: 2,
+                            other_npc: 1002,
+                            other_variable: 3,
+                            op: 4,
+                        },
+                    ],
+                    rewards: vec![
+                        QuestReward::Item {
+                            item: 8001,
+                            op: 0,
+                            count: -1,
+                            party: 0,
+                        },
+                        QuestReward::Calculated {
+                            target: 0,
+                            equation: 0,
+                            value: 500,
+                            item: 0,
+                            party: 1,
+                            item_option: 0,
+                        },
+                        QuestReward::Calculated {
+                            target: 2,
+                            equation: 3,
+                            value: 2,
+                            item: 10001,
+                            party: 0,
+                            item_option: 5,
+                        },
+                        QuestReward::Abilities {
+                            party: 0,
+                            abilities: vec![QuestAbility {
+                                ability: 50,
+                                value: 1,
+                                op: 6,
+                            }],
+                        },
+                        QuestReward::Variables {
+                            variables: vec![QuestVariable {
+                                index: 4,
+                                kind: 1024,
+                                value: 0,
+                                op: 5,
+                            }],
+                        },
+                        QuestReward::Quest {
+                            quest: 12,
+                            action: 1,
+                        },
+                        QuestReward::ClanMoney { money: 1000, op: 7 },
+                        QuestReward::Trigger {
+                            trigger: String::from("judy-02"),
+                        },
+                        QuestReward::Raw(QuestCommand {
+                            opcode: 0x0100_0000,
+                            data: vec![3, 0, 0, 0, 1, 0xCC, 0xCC, 0xCC],
+                        }),
+                    ],
+                },
+                QuestTrigger {
+                    name: String::from("judy-02"),
+                    ..Default::default()
+                },
+            ],
+        });
+        qsd
+    }
+
+    #[test]
+    fn test_qsd_script() {
+        let script = qsd_to_script(&qsd());
+        assert_eq!(
+            script,
+            r#"quest "QUEST_JUNON" version 1
+
+group "Helping \"Judy\""
+  trigger "judy-01" check_next
+    when select_npc(1001)
+      and level >= 10, job == 111
+      and has_item(8001)
+      and item(8002, 3) > 1
+      and quest_var(0, 2) != 1
+      and quest_time < 300
+      and random(min: 1, max: 50)
+      and compare_npc_variables(npc: 1001, variable: 2, other_npc: 1002, other_variable: 3, op: <=)
+    then take_item(8001, -1)
+      and reward exp 500 to party
+      and reward item 10001 x 2 option 5 equation 3
+      and ability(50) += 1
+      and var(1024, 4) = 0
+      and add_quest(12)
+      and clan_money -= 1000
+      and trigger("judy-02")
+      and raw 0x01000000 [03 00 00 00 01 cc cc cc]
+  trigger "judy-02"
+"#
+        );
+        assert_eq!(qsd_from_script(&script).unwrap(), qsd());
+    }
+
+    #[test]
+    fn test_qsd_from_script() {
+        let qsd = qsd_from_script(
+            "# Judy\nquest \"Q\" version 0x2\ngroup \"G\"\ntrigger \"T\"\n\
+             then item(party: 0, item: 8001, op: 0, count: -1)\n",
+        )
+        .unwrap();
+        assert_eq!(qsd.version, 2);
+        assert_eq!(
+            qsd.groups[0].triggers[0].rewards,
+            [QuestReward::Item {
+                item: 8001,
+                op: 0,
+                count: -1,
+                party: 0,
+            }]
+        );
+
+        let qsd = qsd_from_script(
+            "quest \"Q\" version 1\ngroup \"G\"\ntrigger \"T\"\n\
+             when level >= 10 and has_item(1021) then reward exp 500\n\
+             and trigger(\"T\")\n",
+        )
+        .unwrap();
+        let trigger = &qsd.groups[0].triggers[0];
+        assert_eq!(
+            trigger.conditions,
+            [
+                QuestCondition::Abilities {
+                    abilities: vec![QuestAbility {
+                        ability: 31,
+                        value: 10,
+                        op: 2,
+                    }],
+                },
+                QuestCondition::Items {
+                    items: vec![QuestItem {
+                        item: 1021,
+                        slot: 0,
+                        count: 1,
+                        op: 2,
+                    }],
+                },
+            ]
+        );
+        assert_eq!(
+            trigger.rewards,
+            [
+                QuestReward::Calculated {
+                    target: 0,
+                    equation: 0,
+                    value: 500,
+                    item: 0,
+                    party: 0,
+                    item_option: 0,
+                },
+                QuestReward::Trigger {
+                    trigger: String::from("T"),
+                },
+            ]
+        );
+
+        let error = |script: &str| qsd_from_script(script).unwrap_err().to_string();
+        assert_eq!(
+            error("quest \"Q\" version 1\ntrigger \"T\""),
+            "Line 2: Trigger outside of a group"
+        );
+        let command_error = |command: &str| {
+            error(&format!(
+                "quest \"Q\" version 1\ngroup \"G\"\ntrigger \"T\"\n{}",
+                command
+            ))
+        };
+        assert_eq!(
+            command_error("when select_npc(npc: 1, op: 2)"),
+            "Line 4: Unknown field `op` of `select_npc`"
+        );
+        assert_eq!(
+            command_error("when items(items: [(item: 1, slot: 0, count: 1, op: 0, where: 0)])"),
+            "Line 4: Unknown field `where` of `items`"
+        );
+        assert_eq!(
+            command_error("then select_npc(npc: 1)"),
+            "Line 4: Unknown reward `select_npc`"
+        );
+        assert_eq!(
+            command_error("when SelectNpc(npc: 1)"),
+            "Line 4: Unknown condition `SelectNpc`"
+        );
+        assert_eq!(
+            command_error("when random(min: 300, max: 1)"),
+            "Line 4: Invalid `random`: invalid value: integer `300`, expected u8"
+        );
+        assert_eq!(
+            command_error("when random(min: 1)"),
+            "Line 4: Invalid `random`: missing field `max`"
+        );
+        assert_eq!(
+            command_error("when random(min: 1, max: 2"),
+            "Line 4: Expected `,` or `)`"
+        );
+        assert_eq!(
+            command_error("and has_item(1)"),
+            "Line 4: Expected `when` or `then` before `and`"
+        );
+        assert_eq!(
+            command_error("when quest_time >= 1 and"),
+            "Line 4: Expected a command after `and`"
+        );
+        assert_eq!(
+            command_error("when level >= 10, has_item(1)"),
+            "Line 4: `has_item(1)` isn't the same type as the records before it"
+        );
+        assert_eq!(
+            command_error("when select_npc(npc: 1) >= 2"),
+            "Line 4: `select_npc` has no operator"
+        );
+        assert_eq!(
+            command_error("when select_np(1)"),
+            "Line 4: Unknown condition `select_np`"
+        );
+        assert_eq!(
+            command_error("then give_item(1)"),
+            "Line 4: Expected `give_item(<item>, <count>)`"
+        );
+        assert_eq!(
+            command_error("then trigger(\"T\") to party"),
+            "Line 4: Unexpected `to party`"
+        );
+        assert_eq!(
+            command_error("then reward exp 500 option 1"),
+            "Line 4: Unexpected `option 1`"
+        );
+    }
+
+    #[test]
+    fn test_aip_script() {
+        let mut aip = AIP::new();
+        aip.title = String::from("Wolf");
+        aip.idle_interval = 4;
+        aip.damage_chance = 30;
+        aip.triggers.push(AiTrigger {
+            name: String::from("On Idle"),
+            events: vec![AiEvent {
+                name: String::from("Wander"),
+                conditions: vec![
+                    AiCondition::Random { percent: 50 },
+                    AiCondition::Health { percent: 50, op: 3 },
+                    AiCondition::NpcVariable {
+                        variable: 2,
+                        value: 1,
+                        op: 0,
+                    },
+                    AiCondition::CompareAbility { ability: 1, op: 2 },
+                ],
+                actions: vec![
+                    AiAction::Stop,
+                    AiAction::SetNpcVariable {
+                        variable: 2,
+                        value: 1,
+                        op: 6,
+                    },
+                    AiAction::DropItem {
+                        items: [10001, 10002, 0, 0, 0],
+                        to_owner: 1,
+                    },
+                    AiAction::QuestTrigger {
+                        trigger: String::from("wolf-01"),
+                    },
+                    AiAction::Raw(AiCommand {
+                        opcode: 0x0B00_0016,
+                        data: Vec::new(),
+                    }),
+                ],
+            }],
+        });
+
+        let script = aip_to_script(&aip);
+        assert_eq!(
+            script,
+            r#"ai "Wolf" idle 4 damage 30
+
+trigger "On Idle"
+  event "Wander"
+    when random(percent: 50)
+      and health < 50
+      and npc_variable(variable: 2) == 1
+      and compare_ability(ability: 1, op: >=)
+    then stop
+      and set_npc_variable(variable: 2) += 1
+      and drop_item(items: [10001, 10002, 0, 0, 0], to_owner: 1)
+      and quest_trigger(trigger: "wolf-01")
+      and raw 0x0b000016 []
+"#
+        );
+        assert_eq!(aip_from_script(&script).unwrap(), aip);
+    }
+}
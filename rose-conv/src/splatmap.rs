@@ -0,0 +1,260 @@
+//! Splatmaps of the tile textures of maps
+//!
+//! A splatmap has the weight of every ZON texture at every pixel, packed four
+//! textures to an RGBA image, for terrain shaders that blend textures by
+//! weight. The first layer of a tile weighs what its second layer doesn't
+//! cover: if the tile blends, the second layer weighs the alpha of its
+//! texture, rotated or flipped like the tile. Without the texture, both
+//! layers of a blending tile weigh half.
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use image::{GrayImage, Luma, RgbaImage};
+use serde::Serialize;
+
+use roselib::files::zon::ZoneTile;
+use roselib::files::TIL;
+
+use crate::minimap::{self, BLOCK_TILES};
+
+const CHANNELS: [&str; 4] = ["r", "g", "b", "a"];
+
+/// Texture weighed by a channel of a splatmap image
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SplatChannel {
+    /// Index of the splatmap image
+    pub image: usize,
+    pub channel: &'static str,
+    /// Index of the texture in the ZON
+    pub texture_id: usize,
+    pub texture: String,
+}
+
+pub struct Splatmap<'a> {
+    tiles: &'a [ZoneTile],
+    /// Textures of the ZON resized to the tile size, `None` if missing
+    textures: Vec<Option<RgbaImage>>,
+    tile_size: u32,
+    width: u32,
+    height: u32,
+    /// Weights of the ZON textures used by the tiles, by texture index
+    weights: BTreeMap<usize, GrayImage>,
+}
+
+impl<'a> Splatmap<'a> {
+    /// Empty splatmap of `width` by `height` pixels for the tiles and textures
+    /// of a ZON, with `tile_size` pixels per tile
+    pub fn new(
+        tiles: &'a [ZoneTile],
+        textures: Vec<Option<RgbaImage>>,
+        tile_size: u32,
+        width: u32,
+        height: u32,
+    ) -> Splatmap<'a> {
+        let textures = textures
+            .into_iter()
+            .map(|texture| texture.map(|t| minimap::resize_texture(&t, tile_size)))
+            .collect();
+        Splatmap {
+            tiles,
+            textures,
+            tile_size,
+            width,
+            height,
+            weights: BTreeMap::new(),
+        }
+    }
+
+    /// Pixels of a map block on each axis
+    pub fn block_size(&self) -> u32 {
+        self.tile_size * BLOCK_TILES
+    }
+
+    /// Add the weights of the tiles of a block with its top left corner at
+    /// `(x, y)`
+    pub fn draw_block(&mut self, x: u32, y: u32, til: &TIL) {
+        for (row, tiles) in til.tiles.iter().enumerate() {
+            for (col, tile) in tiles.iter().enumerate() {
+                let tile = match usize::try_from(tile.tile_id)
+                    .ok()
+                    .and_then(|id| self.tiles.get(id))
+                {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                self.draw_tile(
+                    x + col as u32 * self.tile_size,
+                    y + row as u32 * self.tile_size,
+                    tile,
+                );
+            }
+        }
+    }
+
+    fn draw_tile(&mut self, x: u32, y: u32, tile: &ZoneTile) {
+        let layer1 = match usize::try_from(tile.layer1 + tile.offset1) {
+            Ok(layer) => layer,
+            Err(_) => return,
+        };
+        let layer2 = usize::try_from(tile.layer2 + tile.offset2)
+            .ok()
+            .filter(|_| tile.blend);
+        let mask = layer2
+            .and_then(|layer| self.textures.get(layer))
+            .and_then(|t| t.as_ref())
+            .map(|texture| minimap::rotate_layer(texture, &tile.rotation));
+
+        for py in 0..self.tile_size {
+            for px in 0..self.tile_size {
+                let (ix, iy) = (x + px, y + py);
+                if ix >= self.width || iy >= self.height {
+                    continue;
+                }
+                let top = match (layer2, &mask) {
+                    (None, _) => 0,
+                    (Some(_), Some(mask)) => mask.get_pixel(px, py)[3],
+                    (Some(_), None) => 128,
+                };
+                self.add_weight(layer1, ix, iy, 255 - top);
+                if let Some(layer2) = layer2 {
+                    self.add_weight(layer2, ix, iy, top);
+                }
+            }
+        }
+    }
+
+    fn add_weight(&mut self, texture: usize, x: u32, y: u32, weight: u8) {
+        let (width, height) = (self.width, self.height);
+        let weights = self
+            .weights
+            .entry(texture)
+            .or_insert_with(|| GrayImage::new(width, height));
+        let pixel = weights.get_pixel_mut(x, y);
+        *pixel = Luma([pixel[0].saturating_add(weight)]);
+    }
+
+    /// The textures weighed by the channels of the images, with their client
+    /// paths from the ZON's `textures`
+    pub fn channels(&self, textures: &[String]) -> Vec<SplatChannel> {
+        self.weights
+            .keys()
+            .enumerate()
+            .map(|(i, &texture_id)| SplatChannel {
+                image: i / 4,
+                channel: CHANNELS[i % 4],
+                texture_id,
+                texture: textures
+                    .get(texture_id)
+                    .map(|t| t.trim().replace('\\', "/"))
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Weights of a texture of the ZON, `None` if no tile uses it
+    pub fn weights(&self, texture_id: usize) -> Option<&GrayImage> {
+        self.weights.get(&texture_id)
+    }
+
+    /// Splatmap images with the weights of four textures each, in the order
+    /// of the channels
+    pub fn to_images(&self) -> Vec<RgbaImage> {
+        let weights: Vec<&GrayImage> = self.weights.values().collect();
+        weights
+            .chunks(4)
+            .map(|chunk| {
+                let mut img = RgbaImage::new(self.width, self.height);
+                for (x, y, pixel) in img.enumerate_pixels_mut() {
+                    for (c, weights) in chunk.iter().enumerate() {
+                        pixel[c] = weights.get_pixel(x, y)[0];
+                    }
+                }
+                img
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::Rgba;
+    use roselib::files::til::Tile;
+    use roselib::files::zon::ZoneTileRotation;
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_splatmap() {
+        // Second layer covering the left half of the tile
+        let mut mask = RgbaImage::new(2, 2);
+        mask.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+        mask.put_pixel(0, 1, Rgba([0, 0, 0, 255]));
+
+        let mut tiles = Vec::new();
+        for layer1 in 0..5 {
+            tiles.push(ZoneTile {
+                layer1,
+                offset1: 0,
+                ..Default::default()
+            });
+        }
+        tiles.push(ZoneTile {
+            layer1: 0,
+            offset1: 0,
+            layer2: 4,
+            offset2: 1,
+            blend: true,
+            rotation: ZoneTileRotation::FlipHorizontal,
+            ..Default::default()
+        });
+        tiles.push(ZoneTile {
+            layer1: 1,
+            offset1: 0,
+            layer2: 3,
+            offset2: 0,
+            blend: true,
+            ..Default::default()
+        });
+
+        let mut textures = vec![None; 5];
+        textures.push(Some(mask));
+        let mut splatmap = Splatmap::new(&tiles, textures, 2, 32, 32);
+        assert_eq!(splatmap.block_size(), 32);
+
+        let mut til = TIL::new();
+        for row in 0..16 {
+            til.tiles.push(
+                (0..16)
+                    .map(|col| Tile {
+                        tile_id: if row == 0 { col.min(6) } else { -1 },
+                        ..Default::default()
+                    })
+                    .collect(),
+            );
+        }
+        splatmap.draw_block(0, 0, &til);
+
+        let names: Vec<String> = (0..6).map(|i| format!("T{}.DDS", i)).collect();
+        let channels = splatmap.channels(&names);
+        let textures: Vec<usize> = channels.iter().map(|c| c.texture_id).collect();
+        assert_eq!(textures, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!((channels[5].image, channels[5].channel), (1, "g"));
+        assert_eq!(channels[5].texture, "T5.DDS");
+
+        assert_eq!(splatmap.weights(1).unwrap().get_pixel(12, 0)[0], 127);
+        assert!(splatmap.weights(6).is_none());
+
+        let images = splatmap.to_images();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].get_pixel(0, 0), &Rgba([255, 0, 0, 0]));
+        assert_eq!(images[1].get_pixel(8, 0), &Rgba([255, 0, 0, 0]));
+        // Flipped, so the second layer covers the right half
+        assert_eq!(images[0].get_pixel(10, 0), &Rgba([255, 0, 0, 0]));
+        assert_eq!(images[1].get_pixel(11, 1), &Rgba([0, 255, 0, 0]));
+        // Missing second layer texture
+        assert_eq!(images[0].get_pixel(12, 0), &Rgba([0, 127, 0, 128]));
+        // Tiles without a ZON tile have no weights
+        assert_eq!(images[0].get_pixel(0, 2), &Rgba([0, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,291 @@
+//! Statistics of client directories and file systems
+//!
+//! Files are counted by type and by zone, the directory of a zone under
+//! `3DDATA/MAPS` such as `junon/jpt01`, with the largest files and the
+//! vertices of the meshes and frames of the motions in them.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Cursor;
+
+use failure::Error;
+use serde::Serialize;
+
+use roselib::files::{ZMO, ZMS};
+use roselib::io::{RoseFile, RoseReader};
+
+use crate::vfs;
+
+/// Number and size of files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct FileCount {
+    pub files: usize,
+    pub size: u64,
+}
+
+impl FileCount {
+    fn add(&mut self, size: u64) {
+        self.files += 1;
+        self.size += size;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FileSize {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct MeshStats {
+    pub meshes: usize,
+    pub vertices: u64,
+    pub faces: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct MotionStats {
+    pub motions: usize,
+    pub frames: u64,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ClientStats {
+    pub total: FileCount,
+    /// Files by lowercase extension
+    pub types: BTreeMap<String, FileCount>,
+    /// Files by zone directory
+    pub zones: BTreeMap<String, FileCount>,
+    /// Largest files, largest first
+    pub largest: Vec<FileSize>,
+    pub meshes: MeshStats,
+    pub motions: MotionStats,
+    /// Meshes and motions that couldn't be read
+    pub unreadable: usize,
+    #[serde(skip)]
+    largest_count: usize,
+}
+
+impl ClientStats {
+    /// Statistics keeping the `largest_count` largest files
+    pub fn new(largest_count: usize) -> ClientStats {
+        ClientStats {
+            largest_count,
+            ..Default::default()
+        }
+    }
+
+    /// Count a file by its client path, relative to the client directory
+    pub fn add(&mut self, path: &str, size: u64) {
+        let key = vfs::client_path_key(path);
+        self.total.add(size);
+        self.types.entry(extension(&key)).or_default().add(size);
+        if let Some(zone) = zone(&key) {
+            self.zones.entry(zone).or_default().add(size);
+        }
+
+        if self.largest_count > 0 {
+            let at = self.largest.partition_point(|f| f.size >= size);
+            if at < self.largest_count {
+                self.largest.insert(
+                    at,
+                    FileSize {
+                        path: path.replace('\\', "/"),
+                        size,
+                    },
+                );
+                self.largest.truncate(self.largest_count);
+            }
+        }
+    }
+
+    /// Whether the contents of a file are counted, meshes and motions
+    pub fn reads_contents(path: &str) -> bool {
+        matches!(
+            extension(&vfs::client_path_key(path)).as_str(),
+            "zms" | "zmo"
+        )
+    }
+
+    /// Count the vertices of a mesh or the frames of a motion
+    pub fn add_contents(&mut self, path: &str, data: &[u8]) -> Result<(), Error> {
+        let mut reader = RoseReader::new(Cursor::new(data));
+        let res = match extension(&vfs::client_path_key(path)).as_str() {
+            "zms" => {
+                let mut zms = ZMS::new();
+                zms.read(&mut reader).map(|_| {
+                    self.meshes.meshes += 1;
+                    self.meshes.vertices += zms.vertices.len() as u64;
+                    self.meshes.faces += zms.indices.len() as u64;
+                })
+            }
+            "zmo" => {
+                let mut zmo = ZMO::new();
+                zmo.read(&mut reader).map(|_| {
+                    self.motions.motions += 1;
+                    self.motions.frames += zmo.frames as u64;
+                })
+            }
+            _ => Ok(()),
+        };
+        if res.is_err() {
+            self.unreadable += 1;
+        }
+        res
+    }
+}
+
+impl fmt::Display for ClientStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} files, {}",
+            self.total.files,
+            format_size(self.total.size)
+        )?;
+
+        writeln!(f, "\nTypes:")?;
+        let mut types: Vec<_> = self.types.iter().collect();
+        types.sort_by(|a, b| b.1.size.cmp(&a.1.size).then(a.0.cmp(b.0)));
+        for (extension, count) in types {
+            let name = if extension.is_empty() {
+                "(none)"
+            } else {
+                extension
+            };
+            writeln!(
+                f,
+                "  {:8} {:>8} files {:>12}",
+                name,
+                count.files,
+                format_size(count.size)
+            )?;
+        }
+
+        if !self.largest.is_empty() {
+            writeln!(f, "\nLargest files:")?;
+            for file in &self.largest {
+                writeln!(f, "  {:>12}  {}", format_size(file.size), file.path)?;
+            }
+        }
+
+        writeln!(
+            f,
+            "\nMeshes: {} ({} vertices, {} faces)",
+            self.meshes.meshes, self.meshes.vertices, self.meshes.faces
+        )?;
+        writeln!(
+            f,
+            "Motions: {} ({} frames)",
+            self.motions.motions, self.motions.frames
+        )?;
+        if self.unreadable > 0 {
+            writeln!(f, "Unreadable: {}", self.unreadable)?;
+        }
+
+        if !self.zones.is_empty() {
+            writeln!(f, "\nZones:")?;
+            for (zone, count) in &self.zones {
+                writeln!(
+                    f,
+                    "  {:20} {:>8} files {:>12}",
+                    zone,
+                    count.files,
+                    format_size(count.size)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Size in bytes with a binary unit, e.g. `1.5 MiB`
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Lowercase extension of a path key, empty without one
+fn extension(key: &str) -> String {
+    let name = key.rsplit('/').next().unwrap_or_default();
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => name[dot + 1..].to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Zone directory of a file in one, `<planet>/<zone>` under `3ddata/maps`
+fn zone(key: &str) -> Option<String> {
+    let components: Vec<&str> = key.split('/').filter(|c| !c.is_empty()).collect();
+    let maps = components
+        .windows(2)
+        .position(|w| w == ["3ddata", "maps"])?;
+    match &components[maps + 2..] {
+        [planet, zone, _, ..] => Some(format!("{}/{}", planet, zone)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_stats() {
+        let mut stats = ClientStats::new(2);
+        stats.add("3DDATA\\MAPS\\JUNON\\JPT01\\30_30.HIM", 100);
+        stats.add("3ddata/maps/junon/jpt01/30_30.ifo", 50);
+        stats.add("3DDATA/MAPS/JUNON/JPT01.ZON", 10);
+        stats.add("3DDATA/STB/LIST_ZONE.STB", 300);
+        stats.add("README", 1);
+
+        assert_eq!(
+            stats.total,
+            FileCount {
+                files: 5,
+                size: 461
+            }
+        );
+        assert_eq!(stats.types["him"].size, 100);
+        assert_eq!(stats.types[""].files, 1);
+        assert_eq!(stats.zones.len(), 1);
+        assert_eq!(
+            stats.zones["junon/jpt01"],
+            FileCount {
+                files: 2,
+                size: 150
+            }
+        );
+        let largest: Vec<&str> = stats.largest.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            largest,
+            [
+                "3DDATA/STB/LIST_ZONE.STB",
+                "3DDATA/MAPS/JUNON/JPT01/30_30.HIM"
+            ]
+        );
+
+        let mut zmo = ZMO::new();
+        zmo.identifier = String::from("ZMO0002");
+        zmo.frames = 12;
+        let mut data = Cursor::new(Vec::new());
+        zmo.write(&mut data).unwrap();
+        let data = data.into_inner();
+        assert!(ClientStats::reads_contents("3DDATA/MOTION/A.ZMO"));
+        stats.add_contents("3DDATA/MOTION/A.ZMO", &data).unwrap();
+        assert_eq!(stats.motions.frames, 12);
+        assert!(stats.add_contents("3DDATA/A.ZMS", b"bad").is_err());
+        assert_eq!(stats.unreadable, 1);
+
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+    }
+}
@@ -0,0 +1,227 @@
+//! CSV layout of STB files
+//!
+//! The first CSV row names the columns, either with the STB's headers or with
+//! the names from an STB schema. Marker rows follow it so that the STB's own
+//! headers, with `--headers`, and root row and display layout, whenever they
+//! aren't empty, survive a round trip:
+//!
+//! ```csv
+//! name,level
+//! #headers,NAME,LEVEL
+//! #root,ROOT
+//! #layout,20,50,100,25
+//! a,1
+//! ```
+//!
+//! The layout row holds the row height followed by the width of the root
+//! column and of every other column.
+use failure::{bail, format_err, Error};
+
+use roselib::files::STB;
+
+use crate::csv_dialect::CsvDialect;
+
+/// First cell of the row containing the STB's column headers
+pub const HEADERS_MARKER: &str = "#headers";
+
+/// First cell of the row containing the STB's root row
+pub const ROOT_ROW_MARKER: &str = "#root";
+
+/// First cell of the row containing the STB's row height and column widths
+pub const LAYOUT_MARKER: &str = "#layout";
+
+/// Contents of an STB CSV file
+#[derive(Debug, Default, PartialEq)]
+pub struct StbCsv {
+    /// Column names from the first row
+    pub names: Vec<String>,
+    /// STB column headers, if the file has a headers row
+    pub headers: Option<Vec<String>>,
+    /// STB root row, if the file has a root row
+    pub root_row: Option<String>,
+    /// STB row height, 0 if the file has no layout row
+    pub row_height: u32,
+    /// STB root column width followed by the column widths, empty if the
+    /// file has no layout row
+    pub column_widths: Vec<u16>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl StbCsv {
+    /// Parse an STB CSV file, consuming the marker rows after the first row
+    pub fn parse(s: &str, dialect: &CsvDialect) -> Result<StbCsv, Error> {
+        let mut reader = dialect
+            .reader_builder()
+            .flexible(true)
+            .from_reader(s.as_bytes());
+
+        let mut csv = StbCsv {
+            names: reader.headers()?.iter().map(String::from).collect(),
+            ..Default::default()
+        };
+        let mut has_layout = false;
+
+        for (row_idx, record) in reader.records().enumerate() {
+            let record = record?;
+            let mut fields = record.iter().map(String::from);
+
+            if csv.rows.is_empty() {
+                match record.get(0) {
+                    Some(HEADERS_MARKER) if csv.headers.is_none() => {
+                        csv.headers = Some(fields.skip(1).collect());
+                        continue;
+                    }
+                    Some(ROOT_ROW_MARKER) if csv.root_row.is_none() => {
+                        csv.root_row = Some(fields.nth(1).unwrap_or_default());
+                        continue;
+                    }
+                    Some(LAYOUT_MARKER) if !has_layout => {
+                        let mut values = record.iter().skip(1);
+                        csv.row_height = parse_layout(values.next().unwrap_or_default())?;
+                        csv.column_widths = values.map(parse_layout).collect::<Result<_, _>>()?;
+                        if csv.column_widths.len() != csv.names.len() + 1 {
+                            bail!(
+                                "Invalid number of STB column widths, expected {}, found {}",
+                                csv.names.len() + 1,
+                                csv.column_widths.len()
+                            );
+                        }
+                        has_layout = true;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            if record.len() != csv.names.len() {
+                bail!(
+                    "Invalid number of columns in row {}, expected {}, found {}",
+                    row_idx,
+                    csv.names.len(),
+                    record.len()
+                );
+            }
+            csv.rows.push(fields.collect());
+        }
+
+        if let Some(headers) = &csv.headers {
+            if headers.len() != csv.names.len() {
+                bail!(
+                    "Invalid number of STB headers, expected {}, found {}",
+                    csv.names.len(),
+                    headers.len()
+                );
+            }
+        }
+
+        Ok(csv)
+    }
+}
+
+fn parse_layout<T: std::str::FromStr>(s: &str) -> Result<T, Error> {
+    s.trim()
+        .parse()
+        .map_err(|_| format_err!("Invalid STB row height or column width: {}", s))
+}
+
+/// Write an STB as CSV with `names` as the first row. With `headers` the
+/// STB's headers are written as a marker row, and so is its root row unless
+/// it's empty. Its layout is written as a marker row unless it's all 0.
+pub fn write(
+    stb: &STB,
+    names: &[String],
+    headers: bool,
+    dialect: &CsvDialect,
+) -> Result<String, Error> {
+    let mut writer = dialect
+        .writer_builder()
+        .flexible(true)
+        .from_writer(Vec::new());
+
+    writer.write_record(names)?;
+    if headers {
+        let mut headers_row = vec![HEADERS_MARKER];
+        headers_row.extend(stb.headers.iter().map(String::as_str));
+        writer.write_record(&headers_row)?;
+    }
+    if headers || !stb.root_row.is_empty() {
+        writer.write_record([ROOT_ROW_MARKER, stb.root_row.as_str()])?;
+    }
+    if stb.row_height != 0 || stb.column_widths.iter().any(|&w| w != 0) {
+        // Missing widths are written as 0, as in the binary file
+        let mut layout_row = vec![LAYOUT_MARKER.to_string(), stb.row_height.to_string()];
+        layout_row.extend(
+            (0..stb.headers.len() + 1)
+                .map(|idx| stb.column_widths.get(idx).cloned().unwrap_or(0).to_string()),
+        );
+        writer.write_record(&layout_row)?;
+    }
+
+    for row in &stb.data {
+        writer.write_record(row)?;
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_stb_csv_headers() {
+        let mut stb = STB::new();
+        stb.headers = vec!["NAME".into(), "LEVEL".into()];
+        stb.root_row = "ROOT".into();
+        stb.data = vec![vec!["a".into(), "1".into()]];
+
+        let names = vec![String::from("name"), String::from("level")];
+        let s = write(&stb, &names, true, &CsvDialect::default()).unwrap();
+        assert_eq!(s, "name,level\n#headers,NAME,LEVEL\n#root,ROOT\na,1\n");
+
+        let csv = StbCsv::parse(&s, &CsvDialect::default()).unwrap();
+        assert_eq!(csv.names, names);
+        assert_eq!(csv.headers, Some(stb.headers.clone()));
+        assert_eq!(csv.root_row.as_deref(), Some("ROOT"));
+        assert_eq!(csv.rows, stb.data);
+
+        // The root row isn't dropped without the headers
+        let s = write(&stb, &names, false, &CsvDialect::default()).unwrap();
+        assert_eq!(s, "name,level\n#root,ROOT\na,1\n");
+        let csv = StbCsv::parse(&s, &CsvDialect::default()).unwrap();
+        assert_eq!(csv.headers, None);
+        assert_eq!(csv.root_row.as_deref(), Some("ROOT"));
+
+        assert_eq!(csv.row_height, 0);
+        assert!(csv.column_widths.is_empty());
+
+        stb.row_height = 20;
+        stb.column_widths = vec![50, 100];
+        let s = write(&stb, &names, false, &CsvDialect::default()).unwrap();
+        assert_eq!(s, "name,level\n#root,ROOT\n#layout,20,50,100,0\na,1\n");
+        let csv = StbCsv::parse(&s, &CsvDialect::default()).unwrap();
+        assert_eq!(csv.row_height, 20);
+        assert_eq!(csv.column_widths, [50, 100, 0]);
+        assert_eq!(csv.rows, stb.data);
+
+        stb.row_height = 0;
+        stb.column_widths.clear();
+        stb.root_row.clear();
+        assert_eq!(
+            write(&stb, &names, false, &CsvDialect::default()).unwrap(),
+            "name,level\na,1\n"
+        );
+
+        let csv = StbCsv::parse("name,level\na,1\n", &CsvDialect::default()).unwrap();
+        assert_eq!(csv.headers, None);
+        assert_eq!(csv.root_row, None);
+        assert_eq!(csv.rows, stb.data);
+
+        assert!(StbCsv::parse("name,level\na\n", &CsvDialect::default()).is_err());
+        assert!(StbCsv::parse("name,level\n#layout,20,1\n", &CsvDialect::default()).is_err());
+        assert!(StbCsv::parse("name,level\n#layout,x,1,2,3\n", &CsvDialect::default()).is_err());
+        assert!(StbCsv::parse("name,level\n#headers,NAME\n", &CsvDialect::default()).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Cell-by-cell comparison of STB files
+//!
+//! Rows and columns are compared by index since STB rows often don't have
+//! unique names. Cells missing from the shorter row are compared as empty.
+use std::fmt;
+
+use serde::Serialize;
+
+use roselib::files::STB;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HeaderChange {
+    pub column: usize,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RowChange {
+    pub row: usize,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CellChange {
+    pub row: usize,
+    pub column: usize,
+    pub old: String,
+    pub new: String,
+}
+
+/// Differences between two STB files
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct StbDiff {
+    pub headers: Vec<HeaderChange>,
+    pub added_rows: Vec<RowChange>,
+    pub removed_rows: Vec<RowChange>,
+    pub changed_cells: Vec<CellChange>,
+}
+
+impl StbDiff {
+    pub fn new(old: &STB, new: &STB) -> StbDiff {
+        let mut diff = StbDiff::default();
+
+        for column in 0..old.headers.len().max(new.headers.len()) {
+            let old_header = cell(&old.headers, column);
+            let new_header = cell(&new.headers, column);
+            if old_header != new_header {
+                diff.headers.push(HeaderChange {
+                    column,
+                    old: old_header.to_string(),
+                    new: new_header.to_string(),
+                });
+            }
+        }
+
+        for (row, (old_row, new_row)) in old.data.iter().zip(&new.data).enumerate() {
+            for column in 0..old_row.len().max(new_row.len()) {
+                let old_value = cell(old_row, column);
+                let new_value = cell(new_row, column);
+                if old_value != new_value {
+                    diff.changed_cells.push(CellChange {
+                        row,
+                        column,
+                        old: old_value.to_string(),
+                        new: new_value.to_string(),
+                    });
+                }
+            }
+        }
+
+        let common_rows = old.data.len().min(new.data.len());
+        for (row, values) in new.data.iter().enumerate().skip(common_rows) {
+            diff.added_rows.push(RowChange {
+                row,
+                values: values.clone(),
+            });
+        }
+        for (row, values) in old.data.iter().enumerate().skip(common_rows) {
+            diff.removed_rows.push(RowChange {
+                row,
+                values: values.clone(),
+            });
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+            && self.added_rows.is_empty()
+            && self.removed_rows.is_empty()
+            && self.changed_cells.is_empty()
+    }
+}
+
+fn cell(row: &[String], column: usize) -> &str {
+    row.get(column).map(String::as_str).unwrap_or_default()
+}
+
+impl fmt::Display for StbDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.headers {
+            writeln!(
+                f,
+                "~ header {}: {:?} -> {:?}",
+                change.column, change.old, change.new
+            )?;
+        }
+        for change in &self.changed_cells {
+            writeln!(
+                f,
+                "~ row {} column {}: {:?} -> {:?}",
+                change.row, change.column, change.old, change.new
+            )?;
+        }
+        for change in &self.removed_rows {
+            writeln!(f, "- row {}: {}", change.row, change.values.join(", "))?;
+        }
+        for change in &self.added_rows {
+            writeln!(f, "+ row {}: {}", change.row, change.values.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::io::RoseFile;
+
+    fn stb(headers: &[&str], data: &[&[&str]]) -> STB {
+        let mut stb = STB::new();
+        stb.headers = headers.iter().map(|s| s.to_string()).collect();
+        stb.data = data
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect();
+        stb
+    }
+
+    #[test]
+    fn test_stb_diff() {
+        let old = stb(&["A", "B"], &[&["a", "1"], &["b", "2"], &["c", "3"]]);
+        let new = stb(&["A", "C"], &[&["a", "1"], &["b", "5"]]);
+
+        let diff = StbDiff::new(&old, &new);
+        assert_eq!(
+            diff.headers,
+            vec![HeaderChange {
+                column: 1,
+                old: "B".into(),
+                new: "C".into()
+            }]
+        );
+        assert_eq!(
+            diff.changed_cells,
+            vec![CellChange {
+                row: 1,
+                column: 1,
+                old: "2".into(),
+                new: "5".into()
+            }]
+        );
+        assert_eq!(diff.removed_rows[0].row, 2);
+        assert!(diff.added_rows.is_empty());
+        assert_eq!(
+            diff.to_string(),
+            "~ header 1: \"B\" -> \"C\"\n~ row 1 column 1: \"2\" -> \"5\"\n- row 2: c, 3\n"
+        );
+
+        assert!(StbDiff::new(&old, &old).is_empty());
+    }
+}
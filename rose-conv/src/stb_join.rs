@@ -0,0 +1,415 @@
+//! Denormalized views of STB and STL files joined together
+//!
+//! A join spec names a base table and the tables joined to it, e.g. to replace
+//! the string IDs of an item list with the item names:
+//!
+//! ```toml
+//! table = "3DDATA/STB/LIST_WEAPON.STB"
+//! columns = ["name", "list_weapon_s.text", "price"]
+//!
+//! [[join]]
+//! column = "string_id"
+//! table = "3DDATA/STB/LIST_WEAPON_S.STL"
+//! on = "name"
+//! language = 1
+//! ```
+//!
+//! Columns are referenced by name or index. STB columns are named by the STB
+//! schema, or the headers if there is no schema. STL files are joined as a
+//! table of one language with the columns `id`, `name`, `text` and, depending
+//! on the STL format, `description`, `start_message` and `end_message`.
+//!
+//! Each join matches the value of `column` in the view so far, which includes
+//! the columns of earlier joins, against the `on` column of the joined table,
+//! or its row index if `on` isn't set. The joined columns are named
+//! `<prefix>.<column>` with the prefix defaulting to the lowercase file stem.
+//! Rows without a match get empty values.
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use failure::{bail, Error};
+use serde::Deserialize;
+
+use roselib::files::stl::{StringTableRow, StringTableType};
+use roselib::files::{STB, STL};
+use roselib::io::RoseFile;
+
+use crate::stb_json::TypedStb;
+use crate::stb_schema::{ColumnType, StbColumn, StbSchema};
+
+/// A column referenced by its name or index
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ColumnRef {
+    Index(usize),
+    Name(String),
+}
+
+impl fmt::Display for ColumnRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColumnRef::Index(idx) => write!(f, "{}", idx),
+            ColumnRef::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Join {
+    /// Column of the view with the values to match
+    pub column: ColumnRef,
+    /// STB or STL file to join, relative to the spec
+    pub table: PathBuf,
+    /// Column of the joined table to match, the row index if not set
+    pub on: Option<ColumnRef>,
+    /// Prefix of the joined column names
+    pub prefix: Option<String>,
+    /// Columns of the joined table to include, all if empty
+    #[serde(default)]
+    pub columns: Vec<ColumnRef>,
+    /// Index of the STL language to join
+    #[serde(default)]
+    pub language: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct JoinSpec {
+    /// STB or STL file the rows come from, relative to the spec
+    pub table: PathBuf,
+    /// Columns of the view to output, all if empty
+    #[serde(default)]
+    pub columns: Vec<ColumnRef>,
+    #[serde(default, rename = "join")]
+    pub joins: Vec<Join>,
+}
+
+impl FromStr for JoinSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<JoinSpec, Error> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+impl JoinSpec {
+    pub fn from_path(path: &Path) -> Result<JoinSpec, Error> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Load the tables relative to `base_dir` and join them, naming the STB
+    /// columns by the schemas in `schema_dir`
+    pub fn run(&self, base_dir: &Path, schema_dir: &Path) -> Result<Table, Error> {
+        let mut view = Table::from_path(&base_dir.join(&self.table), schema_dir, 0)?;
+        for join in &self.joins {
+            let path = base_dir.join(&join.table);
+            let other = Table::from_path(&path, schema_dir, join.language)?;
+            let prefix = match &join.prefix {
+                Some(prefix) => prefix.clone(),
+                None => path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_lowercase(),
+            };
+            view.join(&other, join, &prefix)?;
+        }
+
+        if !self.columns.is_empty() {
+            view = view.select(&self.columns)?;
+        }
+        Ok(view)
+    }
+}
+
+/// Rows with named and typed columns
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Table {
+    pub names: Vec<String>,
+    pub types: Vec<ColumnType>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Load an STB, or the given language of an STL
+    pub fn from_path(path: &Path, schema_dir: &Path, language: usize) -> Result<Table, Error> {
+        if !path.is_file() {
+            bail!("File does not exist: {}", path.display());
+        }
+
+        let extension = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        match extension.as_str() {
+            "stb" => {
+                let schema = StbSchema::find(schema_dir, path)?.unwrap_or_default();
+                Ok(Table::from_stb(&STB::from_path(path)?, &schema))
+            }
+            "stl" => Table::from_stl(&STL::from_path(path)?, language),
+            _ => bail!("Only STB and STL files can be joined: {}", path.display()),
+        }
+    }
+
+    pub fn from_stb(stb: &STB, schema: &StbSchema) -> Table {
+        let names = schema.unique_column_names(stb);
+        let types = (0..names.len())
+            .map(|idx| match schema.columns.get(idx) {
+                Some(column) => column.column_type,
+                None => ColumnType::infer(
+                    stb.data
+                        .iter()
+                        .filter_map(|row| row.get(idx))
+                        .map(String::as_str),
+                ),
+            })
+            .collect();
+
+        let rows = stb
+            .data
+            .iter()
+            .map(|row| {
+                let mut row = row.clone();
+                row.resize(names.len(), String::new());
+                row
+            })
+            .collect();
+
+        Table { names, types, rows }
+    }
+
+    pub fn from_stl(stl: &STL, language: usize) -> Result<Table, Error> {
+        let table = match stl.language_tables.get(language) {
+            Some(table) => table,
+            None => bail!(
+                "STL has {} languages, can't join language {}",
+                stl.language_tables.len(),
+                language
+            ),
+        };
+
+        let mut names = vec!["id", "name", "text"];
+        let mut rows = Vec::new();
+        for (key, row) in stl.keys.iter().zip(&table.rows) {
+            let mut values = vec![key.id.to_string(), key.name.clone()];
+            match row {
+                StringTableRow::NormalRow(data) => values.push(data.text.clone()),
+                StringTableRow::ItemRow(data) => {
+                    values.push(data.text.clone());
+                    values.push(data.description.clone());
+                }
+                StringTableRow::QuestRow(data) => {
+                    values.push(data.text.clone());
+                    values.push(data.description.clone());
+                    values.push(data.start_message.clone());
+                    values.push(data.end_message.clone());
+                }
+            }
+            rows.push(values);
+        }
+        match stl.format {
+            StringTableType::Normal => {}
+            StringTableType::Item => names.push("description"),
+            StringTableType::Quest => {
+                names.extend(&["description", "start_message", "end_message"])
+            }
+        }
+
+        let mut types = vec![ColumnType::String; names.len()];
+        types[0] = ColumnType::Int;
+        Ok(Table {
+            names: names.into_iter().map(String::from).collect(),
+            types,
+            rows,
+        })
+    }
+
+    /// Index of a column, names are matched case-insensitively if there's no
+    /// exact match
+    pub fn column(&self, column: &ColumnRef) -> Result<usize, Error> {
+        let idx = match column {
+            ColumnRef::Index(idx) if *idx < self.names.len() => Some(*idx),
+            ColumnRef::Index(_) => None,
+            ColumnRef::Name(name) => self
+                .names
+                .iter()
+                .position(|n| n == name)
+                .or_else(|| self.names.iter().position(|n| n.eq_ignore_ascii_case(name))),
+        };
+
+        match idx {
+            Some(idx) => Ok(idx),
+            None => bail!(
+                "Column {} does not exist, expected one of: {}",
+                column,
+                self.names.join(", ")
+            ),
+        }
+    }
+
+    /// Append the columns of `other` to the rows whose `join.column` matches
+    pub fn join(&mut self, other: &Table, join: &Join, prefix: &str) -> Result<(), Error> {
+        let column = self.column(&join.column)?;
+
+        // The first row with a key wins
+        let mut keys = HashMap::new();
+        if let Some(on) = &join.on {
+            let on = other.column(on)?;
+            for (row_idx, row) in other.rows.iter().enumerate().rev() {
+                keys.insert(row[on].trim(), row_idx);
+            }
+        }
+
+        let columns = if join.columns.is_empty() {
+            (0..other.names.len()).collect()
+        } else {
+            join.columns
+                .iter()
+                .map(|c| other.column(c))
+                .collect::<Result<Vec<usize>, Error>>()?
+        };
+
+        for &idx in &columns {
+            self.names.push(format!("{}.{}", prefix, other.names[idx]));
+            self.types.push(other.types[idx]);
+        }
+
+        for row in &mut self.rows {
+            let value = row[column].trim();
+            let matched = match &join.on {
+                Some(_) => keys.get(value).copied(),
+                None => value.parse::<usize>().ok(),
+            };
+            let other_row = matched.and_then(|idx| other.rows.get(idx));
+            for &idx in &columns {
+                row.push(other_row.map(|r| r[idx].clone()).unwrap_or_default());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Table with only the given columns
+    pub fn select(&self, columns: &[ColumnRef]) -> Result<Table, Error> {
+        let columns = columns
+            .iter()
+            .map(|c| self.column(c))
+            .collect::<Result<Vec<usize>, Error>>()?;
+
+        Ok(Table {
+            names: columns.iter().map(|&idx| self.names[idx].clone()).collect(),
+            types: columns.iter().map(|&idx| self.types[idx]).collect(),
+            rows: self
+                .rows
+                .iter()
+                .map(|row| columns.iter().map(|&idx| row[idx].clone()).collect())
+                .collect(),
+        })
+    }
+
+    /// STB with the column names as headers, e.g. to write it as CSV
+    pub fn to_stb(&self) -> STB {
+        let mut stb = STB::new();
+        stb.headers = self.names.clone();
+        stb.data = self.rows.clone();
+        stb
+    }
+
+    /// Rows with values converted to the column types, e.g. to write them as
+    /// JSON
+    pub fn to_typed(&self) -> Result<TypedStb, Error> {
+        let schema = StbSchema {
+            columns: self
+                .names
+                .iter()
+                .zip(&self.types)
+                .map(|(name, column_type)| StbColumn {
+                    name: name.clone(),
+                    column_type: *column_type,
+                    header: None,
+                })
+                .collect(),
+        };
+        TypedStb::new(&self.to_stb(), &schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::stl::*;
+
+    #[test]
+    fn test_join() {
+        let mut items = STB::new();
+        items.headers = vec!["NAME".into(), "STRING_ID".into(), "ZONE".into()];
+        items.data = vec![
+            vec!["sword".into(), "ITEM_2".into(), "1".into()],
+            vec!["bow".into(), "ITEM_9".into(), "0".into()],
+        ];
+
+        let mut zones = STB::new();
+        zones.headers = vec!["ZONE_NAME".into()];
+        zones.data = vec![vec!["Canyon".into()], vec!["Junon".into()]];
+
+        let mut strings = STL::new();
+        strings.format = StringTableType::Item;
+        strings.keys = vec![StringTableKey {
+            id: 2,
+            name: "ITEM_2".into(),
+        }];
+        strings.language_tables.push(StringTableLanguageTable {
+            language: StringTableLanguage::from(0),
+            rows: vec![StringTableRow::ItemRow(ItemRowData {
+                text: "Sword".into(),
+                description: "Sharp".into(),
+            })],
+        });
+
+        let spec: JoinSpec = r#"
+            table = "LIST_ITEM.STB"
+            columns = ["NAME", "zone.ZONE_NAME", "s.text", "s.id"]
+
+            [[join]]
+            column = "string_id"
+            table = "LIST_ITEM_S.STL"
+            on = "name"
+            prefix = "s"
+
+            [[join]]
+            column = 2
+            table = "LIST_ZONE.STB"
+            prefix = "zone"
+        "#
+        .parse()
+        .unwrap();
+
+        let mut view = Table::from_stb(&items, &StbSchema::default());
+        view.join(&Table::from_stl(&strings, 0).unwrap(), &spec.joins[0], "s")
+            .unwrap();
+        view.join(
+            &Table::from_stb(&zones, &StbSchema::default()),
+            &spec.joins[1],
+            "zone",
+        )
+        .unwrap();
+        let view = view.select(&spec.columns).unwrap();
+
+        assert_eq!(view.names, vec!["NAME", "zone.ZONE_NAME", "s.text", "s.id"]);
+        assert_eq!(
+            view.rows,
+            vec![
+                vec!["sword", "Junon", "Sword", "2"],
+                vec!["bow", "Canyon", "", ""]
+            ]
+        );
+        assert_eq!(view.to_typed().unwrap().rows[1][3], serde_json::Value::Null);
+        assert!(view.select(&[ColumnRef::Name("missing".into())]).is_err());
+    }
+}
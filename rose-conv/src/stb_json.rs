@@ -0,0 +1,160 @@
+//! Typed JSON representation of STB files
+//!
+//! Every row is written as an object keyed by the column names with the values
+//! converted to the column's type, so consumers don't have to parse every
+//! cell. Column types come from the STB schema or are inferred from the data.
+//!
+//! ```json
+//! [
+//!   { "name": "a", "level": 1, "rate": 0.5 }
+//! ]
+//! ```
+use failure::{bail, Error};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde_json::{Number, Value};
+
+use roselib::files::STB;
+
+use crate::stb_schema::{ColumnType, StbSchema};
+
+/// STB rows with typed values
+#[derive(Debug, PartialEq)]
+pub struct TypedStb {
+    pub names: Vec<String>,
+    pub types: Vec<ColumnType>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl TypedStb {
+    /// Convert the rows of `stb`, using the names and types of the columns
+    /// in `schema` and inferring the types of the other columns
+    pub fn new(stb: &STB, schema: &StbSchema) -> Result<TypedStb, Error> {
+        // Object keys have to be unique
+        let names = schema.unique_column_names(stb);
+
+        let types: Vec<ColumnType> = (0..names.len())
+            .map(|idx| match schema.columns.get(idx) {
+                Some(column) => column.column_type,
+                None => ColumnType::infer(
+                    stb.data
+                        .iter()
+                        .filter_map(|row| row.get(idx))
+                        .map(String::as_str),
+                ),
+            })
+            .collect();
+
+        let mut rows = Vec::new();
+        for (row_idx, row) in stb.data.iter().enumerate() {
+            let mut values = Vec::new();
+            for (idx, column_type) in types.iter().enumerate() {
+                let value = row.get(idx).map(String::as_str).unwrap_or_default();
+                match typed_value(*column_type, value) {
+                    Some(value) => values.push(value),
+                    None => bail!(
+                        "Invalid value in row {} column {}, expected {:?}: {}",
+                        row_idx,
+                        names[idx],
+                        column_type,
+                        value
+                    ),
+                }
+            }
+            rows.push(values);
+        }
+
+        Ok(TypedStb { names, types, rows })
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_json_compact(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+/// Convert `value` to `column_type`, empty numbers are null
+fn typed_value(column_type: ColumnType, value: &str) -> Option<Value> {
+    if !column_type.is_valid(value) {
+        return None;
+    }
+
+    let value = match column_type {
+        ColumnType::String => Value::String(value.to_string()),
+        _ if value.is_empty() => Value::Null,
+        ColumnType::Int => Value::Number(value.parse::<i64>().ok()?.into()),
+        ColumnType::Float => Value::Number(Number::from_f64(value.parse().ok()?)?),
+    };
+    Some(value)
+}
+
+/// Row of a `TypedStb`, serialized as an object in column order
+struct TypedRow<'a> {
+    names: &'a [String],
+    values: &'a [Value],
+}
+
+impl<'a> Serialize for TypedRow<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.names.len()))?;
+        for (name, value) in self.names.iter().zip(self.values) {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for TypedStb {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.rows.len()))?;
+        for values in &self.rows {
+            seq.serialize_element(&TypedRow {
+                names: &self.names,
+                values,
+            })?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_typed_stb() {
+        let mut stb = STB::new();
+        stb.headers = vec!["NAME".into(), "LEVEL".into(), "RATE".into(), "".into()];
+        stb.data = vec![
+            vec!["b".into(), "1".into(), "0.5".into(), "x".into()],
+            vec!["a".into(), "".into(), "2".into(), "".into()],
+        ];
+
+        let typed = TypedStb::new(&stb, &StbSchema::default()).unwrap();
+        assert_eq!(
+            typed.types,
+            vec![
+                ColumnType::String,
+                ColumnType::Int,
+                ColumnType::Float,
+                ColumnType::String
+            ]
+        );
+        assert_eq!(
+            typed.to_json_compact().unwrap(),
+            r#"[{"NAME":"b","LEVEL":1,"RATE":0.5,"column_3":"x"},{"NAME":"a","LEVEL":null,"RATE":2.0,"column_3":""}]"#
+        );
+
+        let schema: StbSchema =
+            "[[columns]]\nname = \"name\"\n[[columns]]\nname = \"level\"\ntype = \"string\"\n"
+                .parse()
+                .unwrap();
+        let typed = TypedStb::new(&stb, &schema).unwrap();
+        assert_eq!(typed.names[..2], ["name", "level"]);
+        assert_eq!(typed.rows[0][1], Value::from("1"));
+    }
+}
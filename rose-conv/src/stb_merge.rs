@@ -0,0 +1,244 @@
+//! Three-way merge of STB files
+//!
+//! Changes made on one side relative to the base are applied to the result.
+//! When both sides change the same header, row or cell differently the change
+//! from `ours` is kept and a conflict is recorded. Like `diff`, rows and columns
+//! are matched by index.
+use std::fmt;
+
+use serde::Serialize;
+
+use roselib::files::STB;
+use roselib::io::RoseFile;
+
+/// Conflicting change to the root row (no row or column), a header (no row),
+/// a whole row (no column) or a cell
+#[derive(Debug, PartialEq, Serialize)]
+pub struct MergeConflict {
+    pub row: Option<usize>,
+    pub column: Option<usize>,
+    /// Values of each side, `None` if the row doesn't exist
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct StbMerge {
+    pub stb: STB,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Merge a single value, returning `None` on conflict
+fn merge_value<T: PartialEq>(base: T, ours: T, theirs: T) -> Option<T> {
+    if ours == theirs || theirs == base {
+        Some(ours)
+    } else if ours == base {
+        Some(theirs)
+    } else {
+        None
+    }
+}
+
+impl StbMerge {
+    pub fn new(base: &STB, ours: &STB, theirs: &STB) -> StbMerge {
+        let mut stb = STB::new();
+        let mut conflicts = Vec::new();
+
+        stb.identifier = ours.identifier.clone();
+        stb.row_height = ours.row_height;
+        stb.column_widths = ours.column_widths.clone();
+        stb.root_row = match merge_value(&base.root_row, &ours.root_row, &theirs.root_row) {
+            Some(root_row) => root_row.clone(),
+            None => {
+                conflicts.push(MergeConflict {
+                    row: None,
+                    column: None,
+                    base: Some(base.root_row.clone()),
+                    ours: Some(ours.root_row.clone()),
+                    theirs: Some(theirs.root_row.clone()),
+                });
+                ours.root_row.clone()
+            }
+        };
+
+        let col_count = base
+            .headers
+            .len()
+            .max(ours.headers.len())
+            .max(theirs.headers.len());
+        for column in 0..col_count {
+            let values = (
+                base.headers.get(column),
+                ours.headers.get(column),
+                theirs.headers.get(column),
+            );
+            let header = match merge_value(values.0, values.1, values.2) {
+                Some(header) => header,
+                None => {
+                    conflicts.push(MergeConflict {
+                        row: None,
+                        column: Some(column),
+                        base: values.0.cloned(),
+                        ours: values.1.cloned(),
+                        theirs: values.2.cloned(),
+                    });
+                    values.1
+                }
+            };
+            if let Some(header) = header {
+                stb.headers.push(header.clone());
+            }
+        }
+
+        let row_count = base.data.len().max(ours.data.len()).max(theirs.data.len());
+        for row_idx in 0..row_count {
+            let base_row = base.data.get(row_idx);
+            let ours_row = ours.data.get(row_idx);
+            let theirs_row = theirs.data.get(row_idx);
+
+            if let Some(row) = merge_value(base_row, ours_row, theirs_row) {
+                if let Some(row) = row {
+                    stb.data.push(row.clone());
+                }
+                continue;
+            }
+
+            match (ours_row, theirs_row) {
+                (Some(ours_row), Some(theirs_row)) => {
+                    let empty = Vec::new();
+                    let base_row = base_row.unwrap_or(&empty);
+                    stb.data.push(merge_row(
+                        row_idx,
+                        base_row,
+                        ours_row,
+                        theirs_row,
+                        &mut conflicts,
+                    ));
+                }
+                _ => {
+                    // Deleted on one side and changed on the other
+                    let join = |row: Option<&Vec<String>>| row.map(|r| r.join(", "));
+                    conflicts.push(MergeConflict {
+                        row: Some(row_idx),
+                        column: None,
+                        base: join(base_row),
+                        ours: join(ours_row),
+                        theirs: join(theirs_row),
+                    });
+                    if let Some(row) = ours_row {
+                        stb.data.push(row.clone());
+                    }
+                }
+            }
+        }
+
+        StbMerge { stb, conflicts }
+    }
+}
+
+/// Merge a row that was changed on both sides cell by cell
+fn merge_row(
+    row_idx: usize,
+    base: &[String],
+    ours: &[String],
+    theirs: &[String],
+    conflicts: &mut Vec<MergeConflict>,
+) -> Vec<String> {
+    let col_count = base.len().max(ours.len()).max(theirs.len());
+
+    let mut row = Vec::new();
+    for column in 0..col_count {
+        let values = (base.get(column), ours.get(column), theirs.get(column));
+        let value = match merge_value(values.0, values.1, values.2) {
+            Some(value) => value,
+            None => {
+                conflicts.push(MergeConflict {
+                    row: Some(row_idx),
+                    column: Some(column),
+                    base: values.0.cloned(),
+                    ours: values.1.cloned(),
+                    theirs: values.2.cloned(),
+                });
+                values.1
+            }
+        };
+        row.push(value.cloned().unwrap_or_default());
+    }
+    row
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let location = match (self.row, self.column) {
+            (None, Some(column)) => format!("header {}", column),
+            (Some(row), None) => format!("row {}", row),
+            (Some(row), Some(column)) => format!("row {} column {}", row, column),
+            (None, None) => String::from("root row"),
+        };
+        let value = |value: &Option<String>| match value {
+            Some(value) => value.clone(),
+            None => String::from("(deleted)"),
+        };
+
+        writeln!(f, "<<<<<<< ours ({})", location)?;
+        writeln!(f, "{}", value(&self.ours))?;
+        writeln!(f, "||||||| base")?;
+        writeln!(f, "{}", value(&self.base))?;
+        writeln!(f, "=======")?;
+        writeln!(f, "{}", value(&self.theirs))?;
+        writeln!(f, ">>>>>>> theirs")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stb(data: &[&[&str]]) -> STB {
+        let mut stb = STB::new();
+        stb.headers = vec!["A".into(), "B".into()];
+        stb.data = data
+            .iter()
+            .map(|row| row.iter().map(|s| s.to_string()).collect())
+            .collect();
+        stb
+    }
+
+    #[test]
+    fn test_stb_merge() {
+        let base = stb(&[&["a", "1"], &["b", "2"], &["c", "3"]]);
+        let ours = stb(&[&["a", "5"], &["b", "2"], &["c", "4"]]);
+        let theirs = stb(&[&["a", "1"], &["b", "6"], &["x", "3"], &["d", "4"]]);
+
+        let merge = StbMerge::new(&base, &ours, &theirs);
+        assert_eq!(
+            merge.stb.data,
+            stb(&[&["a", "5"], &["b", "6"], &["x", "4"], &["d", "4"]]).data
+        );
+        assert!(merge.conflicts.is_empty());
+
+        let theirs = stb(&[&["a", "7"], &["b", "2"]]);
+        let merge = StbMerge::new(&base, &ours, &theirs);
+        assert_eq!(merge.stb.data, ours.data);
+        assert_eq!(
+            merge.conflicts,
+            vec![
+                MergeConflict {
+                    row: Some(0),
+                    column: Some(1),
+                    base: Some("1".into()),
+                    ours: Some("5".into()),
+                    theirs: Some("7".into()),
+                },
+                MergeConflict {
+                    row: Some(2),
+                    column: None,
+                    base: Some("c, 3".into()),
+                    ours: Some("c, 4".into()),
+                    theirs: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! Column definitions for STB files
+//!
+//! A schema names and types the columns of an STB so they can be edited by
+//! name instead of by index. Schemas are looked up by the STB's file name,
+//! e.g. `schemas/list_zone.toml` for `LIST_ZONE.STB`.
+//!
+//! ```toml
+//! [[columns]]
+//! name = "name"
+//!
+//! [[columns]]
+//! name = "zon_file"
+//!
+//! [[columns]]
+//! name = "is_underground"
+//! type = "int"
+//! # Header written to the STB, defaults to the name
+//! header = "UNDERGROUND"
+//! ```
+//!
+//! The first column is the STB's root column (the row names). Columns past the
+//! end of the schema keep the STB's header, or are named `column_<index>` if it
+//! is empty, and are mapped back by position.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use failure::{bail, Error};
+use serde::Deserialize;
+
+use roselib::files::STB;
+use roselib::io::RoseFile;
+
+use crate::csv_dialect::CsvDialect;
+use crate::stb_csv::{self, StbCsv};
+
+/// Type of the values in an STB column
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    #[default]
+    String,
+    Int,
+    Float,
+}
+
+impl ColumnType {
+    /// Whether `value` is valid for this type, empty values are always valid
+    pub fn is_valid(self, value: &str) -> bool {
+        if value.is_empty() {
+            return true;
+        }
+
+        match self {
+            ColumnType::String => true,
+            ColumnType::Int => value.parse::<i64>().is_ok(),
+            ColumnType::Float => value.parse::<f64>().is_ok_and(f64::is_finite),
+        }
+    }
+
+    /// Narrowest type that is valid for all `values`, columns without any
+    /// values are strings
+    pub fn infer<'a, I>(values: I) -> ColumnType
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut values = values.into_iter().filter(|v| !v.is_empty()).peekable();
+        if values.peek().is_none() {
+            return ColumnType::String;
+        }
+
+        let mut column_type = ColumnType::Int;
+        for value in values {
+            if column_type == ColumnType::Int && !column_type.is_valid(value) {
+                column_type = ColumnType::Float;
+            }
+            if column_type == ColumnType::Float && !column_type.is_valid(value) {
+                return ColumnType::String;
+            }
+        }
+        column_type
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StbColumn {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub column_type: ColumnType,
+    /// Header written to the STB file, defaults to the name
+    pub header: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StbSchema {
+    pub columns: Vec<StbColumn>,
+}
+
+impl FromStr for StbSchema {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<StbSchema, Error> {
+        let schema: StbSchema = toml::from_str(s)?;
+
+        let mut names = HashMap::new();
+        for (idx, column) in schema.columns.iter().enumerate() {
+            if let Some(other) = names.insert(column.name.as_str(), idx) {
+                bail!(
+                    "Duplicate column name in STB schema: {} (columns {} and {})",
+                    column.name,
+                    other,
+                    idx
+                );
+            }
+        }
+
+        Ok(schema)
+    }
+}
+
+impl StbSchema {
+    pub fn from_path(path: &Path) -> Result<StbSchema, Error> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Path of the schema for an STB, or a file serialized from an STB, in
+    /// `schema_dir`. The name is the lowercase file name up to the first `.`
+    /// so `LIST_ZONE.STB` and `list_zone.stb.csv` share a schema.
+    pub fn path_for(schema_dir: &Path, input: &Path) -> PathBuf {
+        let file_name = input
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        let name = file_name.split('.').next().unwrap_or_default();
+        schema_dir.join(name).with_extension("toml")
+    }
+
+    /// Load the schema for `input` from `schema_dir` if one exists
+    pub fn find(schema_dir: &Path, input: &Path) -> Result<Option<StbSchema>, Error> {
+        let path = StbSchema::path_for(schema_dir, input);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        match StbSchema::from_path(&path) {
+            Ok(schema) => Ok(Some(schema)),
+            Err(e) => bail!("Invalid STB schema {}: {}", path.display(), e),
+        }
+    }
+
+    /// Name of the column at `idx`
+    pub fn column_name(&self, idx: usize) -> String {
+        match self.columns.get(idx) {
+            Some(column) => column.name.clone(),
+            None => format!("column_{}", idx),
+        }
+    }
+
+    fn is_column(&self, name: &str) -> bool {
+        self.columns.iter().any(|c| c.name == name)
+    }
+
+    /// Type of the column at `idx`
+    pub fn column_type(&self, idx: usize) -> ColumnType {
+        self.columns
+            .get(idx)
+            .map(|c| c.column_type)
+            .unwrap_or_default()
+    }
+
+    /// Names of the columns of `stb`. Columns past the end of the schema are
+    /// named by their header unless it's empty or clashes with the schema.
+    pub fn column_names(&self, stb: &STB) -> Vec<String> {
+        stb.headers
+            .iter()
+            .enumerate()
+            .map(|(idx, header)| {
+                if idx < self.columns.len() || header.is_empty() || self.is_column(header) {
+                    self.column_name(idx)
+                } else {
+                    header.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Column names made unique by naming the duplicates by their index, for
+    /// formats where names are keys
+    pub fn unique_column_names(&self, stb: &STB) -> Vec<String> {
+        let mut names = Vec::new();
+        for (idx, name) in self.column_names(stb).into_iter().enumerate() {
+            if names.contains(&name) {
+                names.push(format!("column_{}", idx));
+            } else {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// Serialize an STB to CSV using the column names as the first row. With
+    /// `headers` the STB's headers and root row are written too.
+    pub fn to_csv(&self, stb: &STB, headers: bool, dialect: &CsvDialect) -> Result<String, Error> {
+        stb_csv::write(stb, &self.column_names(stb), headers, dialect)
+    }
+
+    /// Deserialize an STB from CSV. Columns in the schema are mapped by name
+    /// and can be in any order, other columns are mapped by position. A
+    /// headers row in the CSV overrides the headers from the schema.
+    pub fn from_csv(&self, s: &str, dialect: &CsvDialect) -> Result<STB, Error> {
+        let csv = StbCsv::parse(s, dialect)?;
+        let names = &csv.names;
+        let col_count = names.len();
+
+        let mut stb = STB::new();
+        stb.headers = vec![String::new(); col_count];
+
+        // Index of the STB column for each CSV column
+        let mut indices = Vec::new();
+        let mut next_extra_idx = self.columns.len();
+        for name in names {
+            let idx = match self.columns.iter().position(|c| &c.name == name) {
+                Some(idx) => {
+                    let column = &self.columns[idx];
+                    if idx < col_count {
+                        stb.headers[idx] = column.header.clone().unwrap_or_else(|| name.clone());
+                    }
+                    idx
+                }
+                None => {
+                    let idx = next_extra_idx;
+                    next_extra_idx += 1;
+                    if idx < col_count && *name != format!("column_{}", idx) {
+                        stb.headers[idx] = name.clone();
+                    }
+                    idx
+                }
+            };
+
+            if idx >= col_count {
+                bail!(
+                    "Missing STB columns, expected column {} but only found {} columns",
+                    self.column_name(idx),
+                    col_count
+                );
+            }
+            if indices.contains(&idx) {
+                bail!("Duplicate STB column: {}", name);
+            }
+            indices.push(idx);
+        }
+
+        // Headers in the CSV are in the same order as the columns
+        if let Some(headers) = csv.headers {
+            for (header, &idx) in headers.into_iter().zip(&indices) {
+                stb.headers[idx] = header;
+            }
+        }
+        stb.root_row = csv.root_row.unwrap_or_default();
+        stb.row_height = csv.row_height;
+        stb.column_widths = csv.column_widths.clone();
+
+        for (row_idx, record) in csv.rows.iter().enumerate() {
+            let mut row = vec![String::new(); col_count];
+            for (field, &idx) in record.iter().zip(&indices) {
+                if !self.column_type(idx).is_valid(field) {
+                    bail!(
+                        "Invalid value in row {} column {}, expected {:?}: {}",
+                        row_idx,
+                        self.column_name(idx),
+                        self.column_type(idx),
+                        field
+                    );
+                }
+                row[idx] = field.clone();
+            }
+            stb.data.push(row);
+        }
+
+        Ok(stb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> StbSchema {
+        r#"
+        [[columns]]
+        name = "name"
+
+        [[columns]]
+        name = "level"
+        type = "int"
+        header = "LEVEL"
+        "#
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_stb_schema() {
+        let schema = schema();
+        let dialect = CsvDialect::default();
+
+        let mut stb = STB::new();
+        stb.headers = vec!["name".into(), "LEVEL".into(), "".into()];
+        stb.data = vec![vec!["a".into(), "1".into(), "x".into()]];
+
+        let csv = schema.to_csv(&stb, false, &dialect).unwrap();
+        assert_eq!(csv, "name,level,column_2\na,1,x\n");
+
+        let new_stb = schema.from_csv(&csv, &dialect).unwrap();
+        assert_eq!(stb, new_stb);
+
+        // Schema columns are mapped by name
+        let new_stb = schema
+            .from_csv("level,column_2,name\n1,x,a\n", &dialect)
+            .unwrap();
+        assert_eq!(stb, new_stb);
+
+        // Headers of columns past the schema are kept
+        stb.headers[2] = "EXTRA".into();
+        let csv = schema.to_csv(&stb, false, &dialect).unwrap();
+        assert_eq!(csv, "name,level,EXTRA\na,1,x\n");
+        assert_eq!(stb, schema.from_csv(&csv, &dialect).unwrap());
+
+        // Headers that differ from the schema are kept with a headers row
+        stb.headers[1] = "LVL".into();
+        stb.root_row = "ROOT".into();
+        let csv = schema.to_csv(&stb, true, &dialect).unwrap();
+        assert_eq!(
+            csv,
+            "name,level,EXTRA\n#headers,name,LVL,EXTRA\n#root,ROOT\na,1,x\n"
+        );
+        assert_eq!(stb, schema.from_csv(&csv, &dialect).unwrap());
+
+        assert!(schema
+            .from_csv("name,level,column_2\na,b,x\n", &dialect)
+            .is_err());
+        assert!(schema.from_csv("name,foo\na,1\n", &dialect).is_err());
+        assert!("[[columns]]\nname = \"a\"\n[[columns]]\nname = \"a\"\n"
+            .parse::<StbSchema>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_column_type_infer() {
+        assert_eq!(ColumnType::infer(vec!["1", "", "-2"]), ColumnType::Int);
+        assert_eq!(ColumnType::infer(vec!["1", "2.5"]), ColumnType::Float);
+        assert_eq!(ColumnType::infer(vec!["1", "NaN"]), ColumnType::String);
+        assert_eq!(ColumnType::infer(vec!["1.5", "a"]), ColumnType::String);
+        assert_eq!(ColumnType::infer(vec!["", ""]), ColumnType::String);
+    }
+
+    #[test]
+    fn test_stb_schema_path() {
+        let dir = Path::new("schemas");
+        assert_eq!(
+            StbSchema::path_for(dir, Path::new("3DDATA/STB/LIST_ZONE.STB")),
+            dir.join("list_zone.toml")
+        );
+        assert_eq!(
+            StbSchema::path_for(dir, Path::new("out/list_zone.stb.csv")),
+            dir.join("list_zone.toml")
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! SQLite databases of STB files
+//!
+//! Every STB is stored in its own table with the columns named by the STB
+//! schema, or the headers if there is no schema, and typed like the typed JSON
+//! export. The `row_id` column is the index of the row in the STB. Everything
+//! else needed to write the STB back, e.g. its headers and original path, is
+//! stored in the `stb_files` table.
+//!
+//! Numbers are written back to the STB in their shortest form, e.g. `1.50`
+//! becomes `1.5`.
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use failure::{bail, Error};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, NO_PARAMS};
+use serde_json::Value;
+
+use roselib::files::STB;
+use roselib::io::RoseFile;
+
+use crate::stb_json::TypedStb;
+use crate::stb_schema::{ColumnType, StbSchema};
+
+/// Table with the metadata of every STB in the database
+pub const METADATA_TABLE: &str = "stb_files";
+
+/// Column with the index of each row in the STB
+pub const ROW_ID_COLUMN: &str = "row_id";
+
+/// Quote an SQL identifier
+pub fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// SQL type of an STB column
+pub fn sql_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::String => "TEXT",
+        ColumnType::Int => "INTEGER",
+        ColumnType::Float => "REAL",
+    }
+}
+
+/// Table name of an STB, its lowercase file stem with any characters other
+/// than letters, digits and `_` replaced by `_`
+pub fn table_name(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Metadata of an STB in the database
+#[derive(Debug, PartialEq)]
+pub struct StbTable {
+    pub table_name: String,
+    /// Path of the STB relative to the exported directory or VFS
+    pub path: PathBuf,
+}
+
+pub struct StbDatabase {
+    pub conn: Connection,
+    tables: HashSet<String>,
+}
+
+impl StbDatabase {
+    /// Create a new database at `path`, which must not exist yet
+    pub fn create(path: &Path) -> Result<StbDatabase, Error> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(&format!(
+            "CREATE TABLE {} (
+                table_name TEXT PRIMARY KEY NOT NULL,
+                path TEXT NOT NULL,
+                identifier TEXT NOT NULL,
+                headers TEXT NOT NULL,
+                root_row TEXT NOT NULL,
+                row_height INTEGER NOT NULL,
+                column_widths TEXT NOT NULL
+            );",
+            METADATA_TABLE
+        ))?;
+
+        Ok(StbDatabase {
+            conn,
+            tables: HashSet::new(),
+        })
+    }
+
+    /// Open an existing database
+    pub fn open(path: &Path) -> Result<StbDatabase, Error> {
+        if !path.is_file() {
+            bail!("Database does not exist: {}", path.display());
+        }
+
+        let conn = Connection::open(path)?;
+        let mut db = StbDatabase {
+            conn,
+            tables: HashSet::new(),
+        };
+        for table in db.tables()? {
+            db.tables.insert(table.table_name);
+        }
+        Ok(db)
+    }
+
+    /// Every STB in the database
+    pub fn tables(&self) -> Result<Vec<StbTable>, Error> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT table_name, path FROM {} ORDER BY table_name",
+            METADATA_TABLE
+        ))?;
+        let rows = stmt.query_map(NO_PARAMS, |row| {
+            Ok(StbTable {
+                table_name: row.get(0)?,
+                path: PathBuf::from(row.get::<_, String>(1)?),
+            })
+        })?;
+
+        let mut tables = Vec::new();
+        for table in rows {
+            tables.push(table?);
+        }
+        Ok(tables)
+    }
+
+    /// Read the STB in `table`, checking that the table has a column for
+    /// every header and that the values match the column types
+    pub fn read(&self, table: &str) -> Result<STB, Error> {
+        let mut stb = STB::new();
+        let (headers, column_widths) = self.conn.query_row(
+            &format!(
+                "SELECT identifier, headers, root_row, row_height, column_widths FROM {} WHERE table_name = ?",
+                METADATA_TABLE
+            ),
+            &[table],
+            |row| {
+                stb.identifier = row.get(0)?;
+                stb.root_row = row.get(2)?;
+                stb.row_height = row.get::<_, i64>(3)? as u32;
+                Ok((row.get::<_, String>(1)?, row.get::<_, String>(4)?))
+            },
+        )?;
+        stb.headers = serde_json::from_str(&headers)?;
+        stb.column_widths = serde_json::from_str(&column_widths)?;
+
+        // Declared type of every column except the row ID
+        let mut column_types = Vec::new();
+        {
+            let mut stmt = self
+                .conn
+                .prepare(&format!("PRAGMA table_info({})", quote(table)))?;
+            let rows = stmt.query_map(NO_PARAMS, |row| {
+                Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?;
+            for column in rows {
+                let (name, sql_type) = column?;
+                if name != ROW_ID_COLUMN {
+                    column_types.push((name, sql_type.to_uppercase()));
+                }
+            }
+        }
+
+        if column_types.len() != stb.headers.len() {
+            bail!(
+                "Table {} has {} columns but the STB has {} headers",
+                table,
+                column_types.len(),
+                stb.headers.len()
+            );
+        }
+
+        let columns: Vec<String> = column_types.iter().map(|(name, _)| quote(name)).collect();
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, {} FROM {} ORDER BY {}",
+            quote(ROW_ID_COLUMN),
+            columns.join(", "),
+            quote(table),
+            quote(ROW_ID_COLUMN)
+        ))?;
+        let mut rows = stmt.query(NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
+            let row_id: i64 = row.get(0)?;
+
+            let mut values = Vec::new();
+            for (idx, (name, sql_type)) in column_types.iter().enumerate() {
+                let value: SqlValue = row.get(idx + 1)?;
+                let value = match (sql_type.as_str(), value) {
+                    (_, SqlValue::Null) => String::new(),
+                    ("INTEGER", SqlValue::Integer(i)) => i.to_string(),
+                    ("REAL", SqlValue::Integer(i)) => i.to_string(),
+                    ("REAL", SqlValue::Real(f)) => f.to_string(),
+                    ("INTEGER", _) | ("REAL", _) => bail!(
+                        "Invalid value in table {} row {} column {}, expected {}",
+                        table,
+                        row_id,
+                        name,
+                        sql_type
+                    ),
+                    (_, SqlValue::Integer(i)) => i.to_string(),
+                    (_, SqlValue::Real(f)) => f.to_string(),
+                    (_, SqlValue::Text(s)) => s,
+                    (_, SqlValue::Blob(_)) => bail!(
+                        "Invalid value in table {} row {} column {}, blobs are not supported",
+                        table,
+                        row_id,
+                        name
+                    ),
+                };
+                values.push(value);
+            }
+            stb.data.push(values);
+        }
+
+        Ok(stb)
+    }
+
+    /// Add an STB to a new table, returning the name of the table. The table
+    /// is named after the file and suffixed with a number if that name is
+    /// already taken.
+    pub fn add(&mut self, path: &Path, stb: &STB, schema: &StbSchema) -> Result<String, Error> {
+        let typed = TypedStb::new(stb, schema)?;
+
+        let base_name = table_name(path);
+        let mut table = base_name.clone();
+        let mut suffix = 1;
+        while self.tables.contains(&table) || table == METADATA_TABLE {
+            suffix += 1;
+            table = format!("{}_{}", base_name, suffix);
+        }
+
+        // Column names are case-insensitive in SQL
+        let mut used = HashSet::new();
+        used.insert(ROW_ID_COLUMN.to_string());
+        let mut columns = vec![format!("{} INTEGER PRIMARY KEY", quote(ROW_ID_COLUMN))];
+        for (idx, (name, column_type)) in typed.names.iter().zip(&typed.types).enumerate() {
+            let name = if used.insert(name.to_lowercase()) {
+                name.clone()
+            } else {
+                used.insert(format!("column_{}", idx));
+                format!("column_{}", idx)
+            };
+            columns.push(format!("{} {}", quote(&name), sql_type(*column_type)));
+        }
+
+        let tx = self.conn.transaction()?;
+        tx.execute_batch(&format!(
+            "CREATE TABLE {} ({});",
+            quote(&table),
+            columns.join(", ")
+        ))?;
+
+        {
+            let placeholders = vec!["?"; columns.len()].join(", ");
+            let mut insert = tx.prepare(&format!(
+                "INSERT INTO {} VALUES ({})",
+                quote(&table),
+                placeholders
+            ))?;
+            for (row_idx, row) in typed.rows.iter().enumerate() {
+                let mut values = vec![SqlValue::Integer(row_idx as i64)];
+                values.extend(row.iter().map(sql_value));
+                insert.execute(&values)?;
+            }
+        }
+
+        tx.execute(
+            &format!(
+                "INSERT INTO {} VALUES (?, ?, ?, ?, ?, ?, ?)",
+                METADATA_TABLE
+            ),
+            &[
+                SqlValue::Text(table.clone()),
+                SqlValue::Text(path.to_string_lossy().into_owned()),
+                SqlValue::Text(stb.identifier.clone()),
+                SqlValue::Text(serde_json::to_string(&stb.headers)?),
+                SqlValue::Text(stb.root_row.clone()),
+                SqlValue::Integer(i64::from(stb.row_height)),
+                SqlValue::Text(serde_json::to_string(&stb.column_widths)?),
+            ],
+        )?;
+        tx.commit()?;
+
+        self.tables.insert(table.clone());
+        Ok(table)
+    }
+
+    /// Number of rows in `table`
+    pub fn row_count(&self, table: &str) -> Result<i64, Error> {
+        Ok(self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM {}", quote(table)),
+            NO_PARAMS,
+            |row| row.get(0),
+        )?)
+    }
+}
+
+fn sql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        value => SqlValue::Text(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stb_database() {
+        let path = std::env::temp_dir().join("rose-conv-test-stb.sqlite");
+        let _ = std::fs::remove_file(&path);
+
+        let mut stb = STB::new();
+        stb.headers = vec!["NAME".into(), "LEVEL".into(), "name".into()];
+        stb.data = vec![
+            vec!["a".into(), "1".into(), "x".into()],
+            vec!["b".into(), "".into(), "y".into()],
+        ];
+
+        let mut db = StbDatabase::create(&path).unwrap();
+        let schema = StbSchema::default();
+        assert_eq!(
+            db.add(Path::new("STB/LIST_ZONE.STB"), &stb, &schema)
+                .unwrap(),
+            "list_zone"
+        );
+        assert_eq!(
+            db.add(Path::new("other/list-zone.stb"), &stb, &schema)
+                .unwrap(),
+            "list_zone_2"
+        );
+        assert_eq!(db.row_count("list_zone").unwrap(), 2);
+        assert_eq!(db.read("list_zone").unwrap(), stb);
+
+        let level: Option<i64> = db
+            .conn
+            .query_row(
+                "SELECT LEVEL FROM list_zone WHERE column_2 = 'x'",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(level, Some(1));
+
+        let db = StbDatabase::open(&path).unwrap();
+        assert_eq!(
+            db.tables().unwrap()[1].path,
+            Path::new("other/list-zone.stb")
+        );
+
+        db.conn
+            .execute("UPDATE list_zone SET LEVEL = 'x'", NO_PARAMS)
+            .unwrap();
+        assert!(db.read("list_zone").is_err());
+        db.conn
+            .execute("ALTER TABLE list_zone ADD COLUMN extra TEXT", NO_PARAMS)
+            .unwrap();
+        assert!(db.read("list_zone").is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
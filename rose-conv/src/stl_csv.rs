@@ -0,0 +1,530 @@
+//! CSV layout of STL files
+//!
+//! Every key is a row with a group of columns per language, side by side. The
+//! first row names the STL format and the language of each group, the second
+//! row names the columns:
+//!
+//! ```csv
+//! ITST01,,,English,,Japanese,
+//! Row ID,Key ID,Key Name,Text,Description,Text,Description
+//! 0,1,ITEM_1,Sword,A sharp sword,Katana,
+//! ```
+//!
+//! Languages are read back by name so a CSV can contain any subset of the
+//! languages. The STL's languages are positional, languages missing from the
+//! CSV are written as empty strings.
+//!
+//! A single language can be exported in a compact layout without the format
+//! and language row, which is patched back into an existing STL by key ID:
+//!
+//! ```csv
+//! Key ID,Key Name,Text,Description
+//! 1,ITEM_1,Sword,A sharp sword
+//! ```
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use failure::{bail, format_err, Error};
+
+use roselib::files::stl::*;
+use roselib::files::STL;
+use roselib::io::RoseFile;
+
+use crate::csv_dialect::CsvDialect;
+
+/// Names of the columns of a language, depending on the STL format
+pub fn columns(format: &StringTableType) -> &'static [&'static str] {
+    match format {
+        StringTableType::Normal => &["Text"],
+        StringTableType::Item => &["Text", "Description"],
+        StringTableType::Quest => &["Text", "Description", "Start Message", "End Message"],
+    }
+}
+
+/// Values of a row, in the order of `columns`
+pub fn row_values(row: &StringTableRow) -> Vec<&str> {
+    match row {
+        StringTableRow::NormalRow(data) => vec![&data.text],
+        StringTableRow::ItemRow(data) => vec![&data.text, &data.description],
+        StringTableRow::QuestRow(data) => vec![
+            &data.text,
+            &data.description,
+            &data.start_message,
+            &data.end_message,
+        ],
+    }
+}
+
+/// Create a row of the given format from values in the order of `columns`,
+/// missing values are empty
+pub fn row_from_values(format: &StringTableType, values: &[&str]) -> StringTableRow {
+    let value = |idx: usize| values.get(idx).cloned().unwrap_or_default().to_string();
+    match format {
+        StringTableType::Normal => StringTableRow::NormalRow(NormalRowData { text: value(0) }),
+        StringTableType::Item => StringTableRow::ItemRow(ItemRowData {
+            text: value(0),
+            description: value(1),
+        }),
+        StringTableType::Quest => StringTableRow::QuestRow(QuestRowData {
+            text: value(0),
+            description: value(1),
+            start_message: value(2),
+            end_message: value(3),
+        }),
+    }
+}
+
+/// Language tables of an STL in the order of `languages`, every table if
+/// `languages` is empty
+pub fn select_languages<'a>(
+    stl: &'a STL,
+    languages: &[StringTableLanguage],
+) -> Result<Vec<&'a StringTableLanguageTable>, Error> {
+    if languages.is_empty() {
+        return Ok(stl.language_tables.iter().collect());
+    }
+
+    let mut tables = Vec::new();
+    for language in languages {
+        match stl.language_tables.iter().find(|t| t.language == *language) {
+            Some(table) => tables.push(table),
+            None => bail!("STL has no {} strings", language),
+        }
+    }
+    Ok(tables)
+}
+
+/// Language table of an STL, languages up to `language` are added with empty
+/// rows if the STL doesn't have them
+pub fn language_table_mut(
+    stl: &mut STL,
+    language: StringTableLanguage,
+) -> Result<&mut StringTableLanguageTable, Error> {
+    if language == StringTableLanguage::Unknown {
+        bail!("Unknown STL language");
+    }
+
+    let language_idx = language as usize;
+    while stl.language_tables.len() <= language_idx {
+        let rows = stl
+            .keys
+            .iter()
+            .map(|_| row_from_values(&stl.format, &[]))
+            .collect();
+        stl.language_tables.push(StringTableLanguageTable {
+            language: StringTableLanguage::from(stl.language_tables.len() as u32),
+            rows,
+        });
+    }
+    Ok(&mut stl.language_tables[language_idx])
+}
+
+/// Write an STL as CSV with a column group per language. Only the given
+/// languages are written, every language if `languages` is empty.
+pub fn write(
+    stl: &STL,
+    languages: &[StringTableLanguage],
+    dialect: &CsvDialect,
+) -> Result<String, Error> {
+    let tables = select_languages(stl, languages)?;
+    let columns = columns(&stl.format);
+
+    let mut writer = dialect.writer_builder().from_writer(Vec::new());
+
+    // Second and third column contain our keys
+    let mut headers = vec![stl.format.to_string(), String::new(), String::new()];
+    let mut headers2 = vec!["Row ID", "Key ID", "Key Name"];
+    for table in &tables {
+        headers.push(table.language.to_string());
+        headers.extend(columns.iter().skip(1).map(|_| String::new()));
+        headers2.extend(columns);
+    }
+
+    writer.write_record(&headers)?;
+    writer.write_record(&headers2)?;
+
+    for (row_idx, key) in stl.keys.iter().enumerate() {
+        let mut row = vec![row_idx.to_string(), key.id.to_string(), key.name.clone()];
+        for table in &tables {
+            match table.rows.get(row_idx) {
+                Some(table_row) => row.extend(row_values(table_row).into_iter().map(String::from)),
+                None => bail!("STL {} strings have no row {}", table.language, row_idx),
+            }
+        }
+        writer.write_record(&row)?;
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Write a single language of an STL as compact CSV
+pub fn write_language(
+    stl: &STL,
+    language: StringTableLanguage,
+    dialect: &CsvDialect,
+) -> Result<String, Error> {
+    let table = select_languages(stl, &[language])?[0];
+
+    let mut writer = dialect.writer_builder().from_writer(Vec::new());
+
+    let mut headers = vec!["Key ID", "Key Name"];
+    headers.extend(columns(&stl.format));
+    writer.write_record(&headers)?;
+
+    for (key, row) in stl.keys.iter().zip(&table.rows) {
+        let mut record = vec![key.id.to_string(), key.name.clone()];
+        record.extend(row_values(row).into_iter().map(String::from));
+        writer.write_record(&record)?;
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Patch a single language of an STL from compact CSV, returning the number
+/// of patched rows. Rows are matched by key ID, keys that aren't in the CSV
+/// are left as they are.
+pub fn patch_language(
+    stl: &mut STL,
+    language: StringTableLanguage,
+    s: &str,
+    dialect: &CsvDialect,
+) -> Result<usize, Error> {
+    let mut reader = dialect.reader_builder().from_reader(s.as_bytes());
+    let col_count = 2 + columns(&stl.format).len();
+    if reader.headers()?.len() != col_count {
+        bail!(
+            "Invalid number of columns for a {} STL, expected {}, found {}",
+            stl.format,
+            col_count,
+            reader.headers()?.len()
+        );
+    }
+
+    // The first row with a key wins, like the client does
+    let mut key_rows = HashMap::new();
+    for (row_idx, key) in stl.keys.iter().enumerate() {
+        key_rows.entry(key.id).or_insert(row_idx);
+    }
+
+    let format = stl.format;
+    let table = language_table_mut(stl, language)?;
+
+    let mut count = 0;
+    for (record_idx, record) in reader.records().enumerate() {
+        let record = record?;
+        let fields: Vec<&str> = record.iter().collect();
+
+        let id: u32 = fields[0].trim().parse().map_err(|_| {
+            format_err!("STL row #{}, invalid key ID: {}", record_idx + 1, fields[0])
+        })?;
+        let row_idx = match key_rows.get(&id) {
+            Some(row_idx) => *row_idx,
+            None => bail!("STL row #{}, unknown key ID: {}", record_idx + 1, id),
+        };
+
+        table.rows[row_idx] = row_from_values(&format, &fields[2..]);
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Merge the non-empty strings of a CSV into an STL, returning the number of
+/// changed strings. Both layouts are accepted, the compact layout needs the
+/// `language` of its strings. Rows are matched by key ID, keys that aren't in
+/// the CSV and empty strings are left as they are.
+pub fn merge(
+    stl: &mut STL,
+    s: &str,
+    language: Option<StringTableLanguage>,
+    dialect: &CsvDialect,
+) -> Result<usize, Error> {
+    // The first row with a key wins, like the client does
+    let mut key_rows = HashMap::new();
+    for (row_idx, key) in stl.keys.iter().enumerate() {
+        key_rows.entry(key.id).or_insert(row_idx);
+    }
+
+    let mut reader = dialect.reader_builder().from_reader(s.as_bytes());
+    let first_header = reader.headers()?.get(0).unwrap_or_default().to_string();
+
+    let mut count = 0;
+    if StringTableType::from_str(&first_header).is_ok() {
+        let csv = read(s, dialect)?;
+        if csv.format != stl.format {
+            bail!("Can't merge a {} CSV into a {} STL", csv.format, stl.format);
+        }
+
+        for table in &csv.language_tables {
+            for (key, row) in csv.keys.iter().zip(&table.rows) {
+                let row_idx = match key_rows.get(&key.id) {
+                    Some(row_idx) => *row_idx,
+                    None => bail!("Unknown key ID: {}", key.id),
+                };
+                count += merge_row(stl, table.language, row_idx, &row_values(row))?;
+            }
+        }
+        return Ok(count);
+    }
+
+    let language = match language {
+        Some(language) => language,
+        None => bail!("The language of a single-language CSV has to be given"),
+    };
+    let col_count = 2 + columns(&stl.format).len();
+    if reader.headers()?.len() != col_count {
+        bail!(
+            "Invalid number of columns for a {} STL, expected {}, found {}",
+            stl.format,
+            col_count,
+            reader.headers()?.len()
+        );
+    }
+
+    for (record_idx, record) in reader.records().enumerate() {
+        let record = record?;
+        let fields: Vec<&str> = record.iter().collect();
+
+        let id: u32 = fields[0].trim().parse().map_err(|_| {
+            format_err!("STL row #{}, invalid key ID: {}", record_idx + 1, fields[0])
+        })?;
+        let row_idx = match key_rows.get(&id) {
+            Some(row_idx) => *row_idx,
+            None => bail!("STL row #{}, unknown key ID: {}", record_idx + 1, id),
+        };
+        count += merge_row(stl, language, row_idx, &fields[2..])?;
+    }
+    Ok(count)
+}
+
+/// Set the non-empty values of a row, returning the number of changed values
+fn merge_row(
+    stl: &mut STL,
+    language: StringTableLanguage,
+    row_idx: usize,
+    values: &[&str],
+) -> Result<usize, Error> {
+    if values.iter().all(|value| value.is_empty()) {
+        return Ok(0);
+    }
+
+    let format = stl.format;
+    let row = &mut language_table_mut(stl, language)?.rows[row_idx];
+    let mut new_values: Vec<String> = row_values(row).into_iter().map(String::from).collect();
+
+    let mut count = 0;
+    for (new_value, value) in new_values.iter_mut().zip(values) {
+        if !value.is_empty() && new_value != value {
+            *new_value = value.to_string();
+            count += 1;
+        }
+    }
+
+    let new_values: Vec<&str> = new_values.iter().map(String::as_str).collect();
+    *row = row_from_values(&format, &new_values);
+    Ok(count)
+}
+
+/// Read an STL from CSV, see the module documentation for the layout
+pub fn read(s: &str, dialect: &CsvDialect) -> Result<STL, Error> {
+    let mut stl = STL::new();
+    let mut reader = dialect.reader_builder().from_reader(s.as_bytes());
+    let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+    stl.format =
+        StringTableType::from_str(headers.first().map(String::as_str).unwrap_or_default())?;
+
+    // Get the number of columns after key columns
+    let group_size = columns(&stl.format).len();
+    let data_col_count = headers.len().saturating_sub(3);
+    if data_col_count == 0 || data_col_count / group_size * group_size != data_col_count {
+        bail!(
+            "Invalid number of headers in the CSV file, expected 3 key columns and {} columns per language, found {}",
+            group_size,
+            headers.len()
+        );
+    }
+
+    // Language of each column group, groups without a known language name
+    // follow the previous group
+    let mut group_languages = Vec::new();
+    let mut seen = HashMap::new();
+    for (group_idx, header) in headers[3..].iter().step_by(group_size).enumerate() {
+        let language_idx = match StringTableLanguage::from_str(header) {
+            Ok(language) if language != StringTableLanguage::Unknown => language as usize,
+            _ => group_languages.last().map(|idx| idx + 1).unwrap_or(0),
+        };
+        if let Some(other) = seen.insert(language_idx, group_idx) {
+            bail!(
+                "Language {} is in column groups {} and {}",
+                StringTableLanguage::from(language_idx as u32),
+                other + 1,
+                group_idx + 1
+            );
+        }
+        group_languages.push(language_idx);
+    }
+
+    let language_count = group_languages.iter().max().map(|idx| idx + 1).unwrap_or(0);
+    for language_idx in 0..language_count {
+        stl.language_tables.push(StringTableLanguageTable {
+            language: StringTableLanguage::from(language_idx as u32),
+            rows: Vec::new(),
+        });
+    }
+
+    // Skip the second header line
+    for (record_idx, record) in reader.records().skip(1).enumerate() {
+        let record = record?;
+        let fields: Vec<&str> = record.iter().collect();
+
+        // Ignore first column, it's only written for readability of the csv
+
+        // Read key values from 2nd and 3rd column
+        stl.keys.push(StringTableKey {
+            id: fields[1].trim().parse().map_err(|_| {
+                format_err!("STL row #{}, invalid key ID: {}", record_idx + 1, fields[1])
+            })?,
+            name: fields[2].into(),
+        });
+
+        for (table_idx, table) in stl.language_tables.iter_mut().enumerate() {
+            let row = match group_languages.iter().position(|idx| *idx == table_idx) {
+                Some(group_idx) => {
+                    let start = 3 + group_idx * group_size;
+                    row_from_values(&stl.format, &fields[start..start + group_size])
+                }
+                None => row_from_values(&stl.format, &[]),
+            };
+            table.rows.push(row);
+        }
+    }
+
+    Ok(stl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stl_csv_languages() {
+        let mut stl = STL::new();
+        stl.format = StringTableType::Item;
+        stl.keys.push(StringTableKey {
+            id: 1,
+            name: "ITEM_1".into(),
+        });
+        for (idx, text) in ["Geom", "Sword", "Katana"].iter().enumerate() {
+            stl.language_tables.push(StringTableLanguageTable {
+                language: StringTableLanguage::from(idx as u32),
+                rows: vec![row_from_values(&stl.format, &[text, "desc"])],
+            });
+        }
+
+        let dialect = CsvDialect::default();
+        let languages = [StringTableLanguage::Japanese, StringTableLanguage::English];
+        let s = write(&stl, &languages, &dialect).unwrap();
+        assert_eq!(
+            s,
+            "ITST01,,,Japanese,,English,\n\
+             Row ID,Key ID,Key Name,Text,Description,Text,Description\n\
+             0,1,ITEM_1,Katana,desc,Sword,desc\n"
+        );
+
+        // Korean isn't in the CSV and is left empty
+        let subset = read(&s, &dialect).unwrap();
+        assert_eq!(subset.language_count(), 3);
+        assert_eq!(row_values(&subset.language_tables[0].rows[0]), vec!["", ""]);
+        assert_eq!(subset.language_tables[1], stl.language_tables[1]);
+        assert_eq!(subset.language_tables[2], stl.language_tables[2]);
+
+        assert_eq!(
+            read(&write(&stl, &[], &dialect).unwrap(), &dialect).unwrap(),
+            stl
+        );
+        assert!(write(&stl, &[StringTableLanguage::ChineseSimplified], &dialect).is_err());
+    }
+
+    #[test]
+    fn test_stl_csv_language() {
+        let mut stl = STL::new();
+        stl.format = StringTableType::Normal;
+        for (id, name) in [(1, "A"), (2, "B")].iter() {
+            stl.keys.push(StringTableKey {
+                id: *id,
+                name: name.to_string(),
+            });
+        }
+        stl.language_tables.push(StringTableLanguageTable {
+            language: StringTableLanguage::Korean,
+            rows: vec![
+                row_from_values(&stl.format, &["a"]),
+                row_from_values(&stl.format, &["b"]),
+            ],
+        });
+
+        let dialect = CsvDialect::default();
+        let s = write_language(&stl, StringTableLanguage::Korean, &dialect).unwrap();
+        assert_eq!(s, "Key ID,Key Name,Text\n1,A,a\n2,B,b\n");
+
+        // Only the rows in the CSV are patched, missing languages are added
+        let english = StringTableLanguage::English;
+        let count = patch_language(
+            &mut stl,
+            english,
+            "Key ID,Key Name,Text\n2,B,bee\n",
+            &dialect,
+        )
+        .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(stl.language_count(), 2);
+        assert_eq!(row_values(&stl.language_tables[1].rows[0]), vec![""]);
+        assert_eq!(row_values(&stl.language_tables[1].rows[1]), vec!["bee"]);
+        assert_eq!(row_values(&stl.language_tables[0].rows[1]), vec!["b"]);
+
+        assert!(
+            patch_language(&mut stl, english, "Key ID,Key Name,Text\n3,C,c\n", &dialect).is_err()
+        );
+        assert!(patch_language(&mut stl, english, "Key ID,Text\n1,a\n", &dialect).is_err());
+    }
+
+    #[test]
+    fn test_stl_csv_merge() {
+        let mut stl = STL::new();
+        stl.format = StringTableType::Item;
+        for id in 1..3 {
+            stl.keys.push(StringTableKey {
+                id,
+                name: format!("ITEM_{}", id),
+            });
+        }
+        for language in 0..2 {
+            stl.language_tables.push(StringTableLanguageTable {
+                language: StringTableLanguage::from(language),
+                rows: vec![
+                    row_from_values(&stl.format, &["a", "b"]),
+                    row_from_values(&stl.format, &["c", "d"]),
+                ],
+            });
+        }
+
+        // Only non-empty strings of the keys in the CSV are merged
+        let dialect = CsvDialect::default();
+        let csv = "ITST01,,,English,\n\
+                   Row ID,Key ID,Key Name,Text,Description\n\
+                   0,2,ITEM_2,,dd\n";
+        assert_eq!(merge(&mut stl, csv, None, &dialect).unwrap(), 1);
+        assert_eq!(row_values(&stl.language_tables[1].rows[1]), vec!["c", "dd"]);
+        assert_eq!(row_values(&stl.language_tables[0].rows[1]), vec!["c", "d"]);
+        assert_eq!(row_values(&stl.language_tables[1].rows[0]), vec!["a", "b"]);
+
+        let csv = "Key ID,Key Name,Text,Description\n1,ITEM_1,aa,\n";
+        assert!(merge(&mut stl, csv, None, &dialect).is_err());
+        let korean = Some(StringTableLanguage::Korean);
+        assert_eq!(merge(&mut stl, csv, korean, &dialect).unwrap(), 1);
+        assert_eq!(row_values(&stl.language_tables[0].rows[0]), vec!["aa", "b"]);
+
+        let csv = "Key ID,Key Name,Text,Description\n3,ITEM_3,e,f\n";
+        assert!(merge(&mut stl, csv, korean, &dialect).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! Key-by-key comparison of STL files
+//!
+//! Keys are matched by ID, the first row wins if an ID is used more than
+//! once. Strings are compared for the languages both files have.
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use roselib::files::stl::*;
+use roselib::files::STL;
+
+use crate::stl_csv;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct KeyChange {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct KeyNameChange {
+    pub id: u32,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StringChange {
+    pub id: u32,
+    pub name: String,
+    pub language: StringTableLanguage,
+    /// Column of the string, e.g. `Description`
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// Differences between two STL files
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct StlDiff {
+    /// Old and new format, if it changed
+    pub format: Option<(StringTableType, StringTableType)>,
+    pub added_languages: Vec<StringTableLanguage>,
+    pub removed_languages: Vec<StringTableLanguage>,
+    pub added_keys: Vec<KeyChange>,
+    pub removed_keys: Vec<KeyChange>,
+    pub renamed_keys: Vec<KeyNameChange>,
+    pub changed_strings: Vec<StringChange>,
+}
+
+impl StlDiff {
+    pub fn new(old: &STL, new: &STL) -> StlDiff {
+        let mut diff = StlDiff::default();
+
+        if old.format != new.format {
+            diff.format = Some((old.format, new.format));
+        }
+
+        let languages = |stl: &STL| -> Vec<StringTableLanguage> {
+            stl.language_tables.iter().map(|t| t.language).collect()
+        };
+        let (old_languages, new_languages) = (languages(old), languages(new));
+        diff.added_languages = new_languages
+            .iter()
+            .filter(|l| !old_languages.contains(l))
+            .cloned()
+            .collect();
+        diff.removed_languages = old_languages
+            .iter()
+            .filter(|l| !new_languages.contains(l))
+            .cloned()
+            .collect();
+
+        let old_rows = key_rows(old);
+        let new_rows = key_rows(new);
+
+        for (row_idx, key) in old.keys.iter().enumerate() {
+            if old_rows[&key.id] != row_idx {
+                continue;
+            }
+
+            let new_row_idx = match new_rows.get(&key.id) {
+                Some(new_row_idx) => *new_row_idx,
+                None => {
+                    diff.removed_keys.push(KeyChange {
+                        id: key.id,
+                        name: key.name.clone(),
+                    });
+                    continue;
+                }
+            };
+
+            let new_key = &new.keys[new_row_idx];
+            if key.name != new_key.name {
+                diff.renamed_keys.push(KeyNameChange {
+                    id: key.id,
+                    old: key.name.clone(),
+                    new: new_key.name.clone(),
+                });
+            }
+
+            // Fields of both formats are compared by their position
+            let columns = stl_csv::columns(&new.format);
+            for old_table in &old.language_tables {
+                let new_table = match new
+                    .language_tables
+                    .iter()
+                    .find(|t| t.language == old_table.language)
+                {
+                    Some(new_table) => new_table,
+                    None => continue,
+                };
+
+                let old_values = old_table
+                    .rows
+                    .get(row_idx)
+                    .map(stl_csv::row_values)
+                    .unwrap_or_default();
+                let new_values = new_table
+                    .rows
+                    .get(new_row_idx)
+                    .map(stl_csv::row_values)
+                    .unwrap_or_default();
+
+                for (field_idx, field) in columns.iter().enumerate() {
+                    let old_value = old_values.get(field_idx).cloned().unwrap_or_default();
+                    let new_value = new_values.get(field_idx).cloned().unwrap_or_default();
+                    if old_value != new_value {
+                        diff.changed_strings.push(StringChange {
+                            id: key.id,
+                            name: new_key.name.clone(),
+                            language: old_table.language,
+                            field,
+                            old: old_value.to_string(),
+                            new: new_value.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        for (row_idx, key) in new.keys.iter().enumerate() {
+            if new_rows[&key.id] == row_idx && !old_rows.contains_key(&key.id) {
+                diff.added_keys.push(KeyChange {
+                    id: key.id,
+                    name: key.name.clone(),
+                });
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.format.is_none()
+            && self.added_languages.is_empty()
+            && self.removed_languages.is_empty()
+            && self.added_keys.is_empty()
+            && self.removed_keys.is_empty()
+            && self.renamed_keys.is_empty()
+            && self.changed_strings.is_empty()
+    }
+}
+
+/// Row of every key ID, the first row wins
+fn key_rows(stl: &STL) -> HashMap<u32, usize> {
+    let mut rows = HashMap::new();
+    for (row_idx, key) in stl.keys.iter().enumerate() {
+        rows.entry(key.id).or_insert(row_idx);
+    }
+    rows
+}
+
+impl fmt::Display for StlDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((old, new)) = &self.format {
+            writeln!(f, "~ format: {} -> {}", old, new)?;
+        }
+        for language in &self.removed_languages {
+            writeln!(f, "- language {}", language)?;
+        }
+        for language in &self.added_languages {
+            writeln!(f, "+ language {}", language)?;
+        }
+        for change in &self.renamed_keys {
+            writeln!(
+                f,
+                "~ key {} name: {:?} -> {:?}",
+                change.id, change.old, change.new
+            )?;
+        }
+        for change in &self.changed_strings {
+            writeln!(
+                f,
+                "~ key {} ({}) {} {}: {:?} -> {:?}",
+                change.id,
+                change.name,
+                change.language.code(),
+                change.field,
+                change.old,
+                change.new
+            )?;
+        }
+        for change in &self.removed_keys {
+            writeln!(f, "- key {} ({})", change.id, change.name)?;
+        }
+        for change in &self.added_keys {
+            writeln!(f, "+ key {} ({})", change.id, change.name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::io::RoseFile;
+
+    fn stl(keys: &[(u32, &str)], languages: &[&[&str]]) -> STL {
+        let mut stl = STL::new();
+        stl.keys = keys
+            .iter()
+            .map(|(id, name)| StringTableKey {
+                id: *id,
+                name: name.to_string(),
+            })
+            .collect();
+        for (idx, texts) in languages.iter().enumerate() {
+            stl.language_tables.push(StringTableLanguageTable {
+                language: StringTableLanguage::from(idx as u32),
+                rows: texts
+                    .iter()
+                    .map(|text| stl_csv::row_from_values(&stl.format, &[text]))
+                    .collect(),
+            });
+        }
+        stl
+    }
+
+    #[test]
+    fn test_stl_diff() {
+        let old = stl(&[(1, "A"), (2, "B"), (3, "C")], &[&["a", "b", "c"]]);
+        let new = stl(
+            &[(2, "B2"), (1, "A"), (4, "D")],
+            &[&["bb", "a", "d"], &["", "", ""]],
+        );
+
+        let diff = StlDiff::new(&old, &new);
+        assert_eq!(diff.added_languages, vec![StringTableLanguage::English]);
+        assert_eq!(
+            diff.to_string(),
+            "+ language English\n\
+             ~ key 2 name: \"B\" -> \"B2\"\n\
+             ~ key 2 (B2) ko Text: \"b\" -> \"bb\"\n\
+             - key 3 (C)\n\
+             + key 4 (D)\n"
+        );
+
+        assert!(StlDiff::new(&old, &old).is_empty());
+    }
+}
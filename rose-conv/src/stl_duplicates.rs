@@ -0,0 +1,252 @@
+//! Duplicate strings across STL files
+//!
+//! Strings of a language are grouped when they are identical or, unless only
+//! exact duplicates are wanted, near-identical: equal after ignoring case,
+//! punctuation and runs of whitespace, e.g. `Iron Sword` and `iron sword.`.
+//! Empty strings are ignored.
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use roselib::files::stl::StringTableLanguage;
+use roselib::files::STL;
+
+use crate::stl_csv;
+
+/// Place of a string in an STL file
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Occurrence {
+    pub file: String,
+    pub key_id: u32,
+    pub key_name: String,
+    /// Column of the string, e.g. `Description`
+    pub field: &'static str,
+    pub text: String,
+}
+
+/// Strings of a language that are duplicates of each other
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DuplicateGroup {
+    pub language: StringTableLanguage,
+    /// Whether every string of the group is identical
+    pub exact: bool,
+    pub occurrences: Vec<Occurrence>,
+}
+
+impl fmt::Display for DuplicateGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = &self.occurrences[0].text;
+        writeln!(
+            f,
+            "{} {:?} ({})",
+            self.language.code(),
+            text,
+            self.occurrences.len()
+        )?;
+        for occurrence in &self.occurrences {
+            write!(
+                f,
+                "    {} key {} ({}) {}",
+                occurrence.file, occurrence.key_id, occurrence.key_name, occurrence.field
+            )?;
+            if occurrence.text != *text {
+                write!(f, ": {:?}", occurrence.text)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of strings and words of a language, counting duplicates once for
+/// the unique counts
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct LanguageSummary {
+    pub language: StringTableLanguage,
+    pub strings: usize,
+    pub unique_strings: usize,
+    pub words: usize,
+    pub unique_words: usize,
+}
+
+impl fmt::Display for LanguageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} strings, {} unique ({} words, {} unique)",
+            self.language, self.strings, self.unique_strings, self.words, self.unique_words
+        )
+    }
+}
+
+/// Duplicate strings of a set of STL files
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct DuplicateReport {
+    pub summaries: Vec<LanguageSummary>,
+    pub groups: Vec<DuplicateGroup>,
+}
+
+impl DuplicateReport {
+    /// Group the strings of the `languages` of every file, all languages if
+    /// `languages` is empty
+    pub fn new(files: &[(String, STL)], languages: &[StringTableLanguage], exact: bool) -> Self {
+        // Occurrences by language and then by comparison key, in file order
+        let mut strings: Vec<(StringTableLanguage, Vec<Vec<Occurrence>>)> = Vec::new();
+        let mut group_idx: HashMap<(StringTableLanguage, String), usize> = HashMap::new();
+
+        for (file, stl) in files {
+            let columns = stl_csv::columns(&stl.format);
+            for table in &stl.language_tables {
+                if !languages.is_empty() && !languages.contains(&table.language) {
+                    continue;
+                }
+
+                let idx = match strings.iter().position(|(l, _)| *l == table.language) {
+                    Some(idx) => idx,
+                    None => {
+                        strings.push((table.language, Vec::new()));
+                        strings.len() - 1
+                    }
+                };
+                let groups = &mut strings[idx].1;
+
+                for (row, key) in stl.keys.iter().enumerate() {
+                    let values = match table.rows.get(row) {
+                        Some(values) => stl_csv::row_values(values),
+                        None => continue,
+                    };
+                    for (field, text) in columns.iter().zip(values) {
+                        let compared = if exact {
+                            text.to_string()
+                        } else {
+                            comparison_key(text)
+                        };
+                        if compared.is_empty() {
+                            continue;
+                        }
+
+                        let occurrence = Occurrence {
+                            file: file.clone(),
+                            key_id: key.id,
+                            key_name: key.name.clone(),
+                            field,
+                            text: text.to_string(),
+                        };
+                        let group =
+                            *group_idx
+                                .entry((table.language, compared))
+                                .or_insert_with(|| {
+                                    groups.push(Vec::new());
+                                    groups.len() - 1
+                                });
+                        groups[group].push(occurrence);
+                    }
+                }
+            }
+        }
+
+        let mut report = DuplicateReport::default();
+        for (language, groups) in strings {
+            let mut summary = LanguageSummary {
+                language,
+                ..Default::default()
+            };
+            for occurrences in groups {
+                let words = occurrences[0].text.split_whitespace().count();
+                summary.strings += occurrences.len();
+                summary.unique_strings += 1;
+                summary.words += words * occurrences.len();
+                summary.unique_words += words;
+
+                if occurrences.len() > 1 {
+                    report.groups.push(DuplicateGroup {
+                        language,
+                        exact: occurrences.iter().all(|o| o.text == occurrences[0].text),
+                        occurrences,
+                    });
+                }
+            }
+            report.summaries.push(summary);
+        }
+        report
+    }
+}
+
+impl fmt::Display for DuplicateReport {
+    /// Summaries of every language followed by the groups
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for summary in &self.summaries {
+            write!(f, "{}", summary)?;
+        }
+        for group in &self.groups {
+            write!(f, "{}", group)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lowercase words of a string without punctuation, separated by a space
+fn comparison_key(text: &str) -> String {
+    let stripped: String = text
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    stripped
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::stl::*;
+    use roselib::io::RoseFile;
+
+    fn stl(texts: &[&str]) -> STL {
+        let mut stl = STL::new();
+        stl.format = StringTableType::Item;
+        stl.language_tables.push(StringTableLanguageTable {
+            language: StringTableLanguage::English,
+            rows: Vec::new(),
+        });
+        for (id, text) in texts.iter().enumerate() {
+            stl.keys.push(StringTableKey {
+                id: id as u32,
+                name: format!("ITEM_{}", id),
+            });
+            stl.language_tables[0]
+                .rows
+                .push(stl_csv::row_from_values(&stl.format, &[text, ""]));
+        }
+        stl
+    }
+
+    #[test]
+    fn test_stl_duplicates() {
+        let files = vec![
+            (String::from("A.STL"), stl(&["Iron Sword", "Shield"])),
+            (String::from("B.STL"), stl(&["iron  sword!", "Iron Sword"])),
+        ];
+
+        let report = DuplicateReport::new(&files, &[], false);
+        assert_eq!(report.groups.len(), 1);
+        assert!(!report.groups[0].exact);
+        assert_eq!(
+            report.to_string(),
+            "English: 4 strings, 2 unique (7 words, 3 unique)\n\
+             en \"Iron Sword\" (3)\n    \
+             A.STL key 0 (ITEM_0) Text\n    \
+             B.STL key 0 (ITEM_0) Text: \"iron  sword!\"\n    \
+             B.STL key 1 (ITEM_1) Text\n"
+        );
+
+        let report = DuplicateReport::new(&files, &[], true);
+        assert_eq!(report.groups[0].occurrences.len(), 2);
+        assert!(report.groups[0].exact);
+        assert_eq!(report.summaries[0].unique_strings, 3);
+    }
+}
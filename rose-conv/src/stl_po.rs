@@ -0,0 +1,444 @@
+//! Gettext PO files of STL translations
+//!
+//! Every non-empty string of the source language becomes a message with the
+//! string of the target language as its translation. The message context is
+//! the key ID, followed by the field for the other fields of item and quest
+//! STLs, and the key name is added as a comment for translators:
+//!
+//! ```po
+//! #. ITEM_1
+//! msgctxt "1.description"
+//! msgid "A sharp sword"
+//! msgstr "Ein scharfes Schwert"
+//! ```
+//!
+//! Fuzzy and untranslated messages are ignored when patching an STL.
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+
+use failure::{bail, Error};
+
+use roselib::files::stl::*;
+use roselib::files::STL;
+
+use crate::stl_csv;
+
+/// Field names used in message contexts, in the order of the STL columns
+const FIELDS: [&str; 4] = ["text", "description", "start_message", "end_message"];
+
+/// Message of a PO file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PoEntry {
+    /// Comments for translators (`#.`)
+    pub comments: Vec<String>,
+    pub fuzzy: bool,
+    pub context: Option<String>,
+    pub id: String,
+    pub translation: String,
+}
+
+/// PO file, the header entry is stored as a list of fields
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PoFile {
+    pub headers: Vec<(String, String)>,
+    pub entries: Vec<PoEntry>,
+}
+
+impl PoFile {
+    /// Value of a header field, e.g. `Language`
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Parse a PO file. Obsolete messages are skipped and only the first
+    /// form of plural messages is kept.
+    pub fn parse(s: &str) -> Result<PoFile, Error> {
+        let mut po = PoFile::default();
+
+        let mut entry = PoEntry::default();
+        // Keyword the current string continues, with the entry having started
+        let mut keyword: Option<&str> = None;
+        let mut obsolete = false;
+
+        let mut finish = |entry: &mut PoEntry, keyword: &mut Option<&str>, obsolete: bool| {
+            let entry = std::mem::take(entry);
+            if keyword.take().is_none() || obsolete {
+                return;
+            }
+            if entry.id.is_empty() && entry.context.is_none() {
+                po.headers = entry
+                    .translation
+                    .lines()
+                    .filter_map(|line| {
+                        let mut parts = line.splitn(2, ':');
+                        let key = parts.next()?.trim();
+                        let value = parts.next()?.trim();
+                        Some((key.to_string(), value.to_string()))
+                    })
+                    .collect();
+            } else {
+                po.entries.push(entry);
+            }
+        };
+
+        for (line_idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            let line_number = line_idx + 1;
+
+            if line.is_empty() {
+                finish(&mut entry, &mut keyword, obsolete);
+                obsolete = false;
+                continue;
+            }
+
+            if line.starts_with('#') {
+                // Comments start a new entry
+                if keyword.is_some() && !line.starts_with("#~") {
+                    finish(&mut entry, &mut keyword, obsolete);
+                    obsolete = false;
+                }
+                if line.starts_with("#~") {
+                    obsolete = true;
+                } else if let Some(comment) = line.strip_prefix("#.") {
+                    entry.comments.push(comment.trim().to_string());
+                } else if let Some(flags) = line.strip_prefix("#,") {
+                    entry.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+                }
+                continue;
+            }
+
+            if line.starts_with('"') {
+                let value = unquote(line, line_number)?;
+                match keyword {
+                    Some("msgctxt") => entry
+                        .context
+                        .get_or_insert_with(String::new)
+                        .push_str(&value),
+                    Some("msgid") => entry.id.push_str(&value),
+                    Some("msgstr") => entry.translation.push_str(&value),
+                    Some(_) => {}
+                    None => bail!("Line {}: string without a keyword", line_number),
+                }
+                continue;
+            }
+
+            let mut parts = line.splitn(2, char::is_whitespace);
+            let name = parts.next().unwrap_or_default();
+            let value = unquote(parts.next().unwrap_or_default().trim(), line_number)?;
+
+            // msgctxt and msgid after a translation start a new entry
+            if (name == "msgctxt" || name == "msgid")
+                && (keyword == Some("msgstr") || keyword == Some("msgstr_other"))
+            {
+                finish(&mut entry, &mut keyword, obsolete);
+                obsolete = false;
+            }
+
+            keyword = Some(match name {
+                "msgctxt" => {
+                    entry.context = Some(value);
+                    "msgctxt"
+                }
+                "msgid" => {
+                    entry.id = value;
+                    "msgid"
+                }
+                "msgid_plural" => "msgid_plural",
+                "msgstr" | "msgstr[0]" => {
+                    entry.translation = value;
+                    "msgstr"
+                }
+                _ if name.starts_with("msgstr[") => "msgstr_other",
+                _ => bail!("Line {}: unknown keyword {}", line_number, name),
+            });
+        }
+        finish(&mut entry, &mut keyword, obsolete);
+
+        Ok(po)
+    }
+}
+
+impl fmt::Display for PoFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut header = String::new();
+        for (key, value) in &self.headers {
+            let _ = writeln!(header, "{}: {}", key, value);
+        }
+        writeln!(f, "msgid \"\"")?;
+        write_string(f, "msgstr", &header)?;
+
+        for entry in &self.entries {
+            writeln!(f)?;
+            for comment in &entry.comments {
+                writeln!(f, "#. {}", comment)?;
+            }
+            if entry.fuzzy {
+                writeln!(f, "#, fuzzy")?;
+            }
+            if let Some(context) = &entry.context {
+                write_string(f, "msgctxt", context)?;
+            }
+            write_string(f, "msgid", &entry.id)?;
+            write_string(f, "msgstr", &entry.translation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a keyword and its string, multi-line strings are split after every
+/// line break
+fn write_string(f: &mut fmt::Formatter<'_>, keyword: &str, s: &str) -> fmt::Result {
+    let lines: Vec<&str> = s.split_inclusive('\n').collect();
+    if lines.len() <= 1 {
+        return writeln!(f, "{} \"{}\"", keyword, escape(s));
+    }
+
+    writeln!(f, "{} \"\"", keyword)?;
+    for line in lines {
+        writeln!(f, "\"{}\"", escape(line))?;
+    }
+    Ok(())
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Parse a quoted PO string
+fn unquote(s: &str, line_number: usize) -> Result<String, Error> {
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        bail!("Line {}: expected a quoted string: {}", line_number, s);
+    }
+
+    let mut value = String::new();
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('r') => value.push('\r'),
+            Some('t') => value.push('\t'),
+            Some(c @ '\\') | Some(c @ '"') => value.push(c),
+            Some(c) => bail!("Line {}: unknown escape sequence \\{}", line_number, c),
+            None => bail!("Line {}: unterminated escape sequence", line_number),
+        }
+    }
+    Ok(value)
+}
+
+/// Gettext code of a language, e.g. `ja` for Japanese
+pub fn po_language(language: StringTableLanguage) -> &'static str {
+    match language {
+        StringTableLanguage::Korean => "ko",
+        StringTableLanguage::English => "en",
+        StringTableLanguage::Japanese => "ja",
+        StringTableLanguage::ChineseTraditional => "zh_TW",
+        StringTableLanguage::ChineseSimplified => "zh_CN",
+        StringTableLanguage::Unknown => "und",
+    }
+}
+
+/// Language of a gettext code, e.g. `ja` or `zh-Hant`, or any language
+/// accepted by `StringTableLanguage`
+pub fn parse_po_language(s: &str) -> Result<StringTableLanguage, Error> {
+    let code = s.trim().replace('-', "_").to_lowercase();
+    Ok(match code.as_str() {
+        "ja" | "ja_jp" => StringTableLanguage::Japanese,
+        "ko_kr" => StringTableLanguage::Korean,
+        "zh_tw" | "zh_hant" | "zh_hk" => StringTableLanguage::ChineseTraditional,
+        "zh_cn" | "zh_hans" | "zh" => StringTableLanguage::ChineseSimplified,
+        _ if code.starts_with("en_") => StringTableLanguage::English,
+        _ => s.parse()?,
+    })
+}
+
+fn context(key: &StringTableKey, field_idx: usize) -> String {
+    if field_idx == 0 {
+        key.id.to_string()
+    } else {
+        format!("{}.{}", key.id, FIELDS[field_idx])
+    }
+}
+
+/// Create a PO file translating the `source` strings of an STL to `target`
+pub fn from_stl(
+    stl: &STL,
+    source: StringTableLanguage,
+    target: StringTableLanguage,
+) -> Result<PoFile, Error> {
+    let tables = stl_csv::select_languages(stl, &[source, target])?;
+
+    let mut po = PoFile {
+        headers: vec![
+            ("Language".into(), po_language(target).into()),
+            ("MIME-Version".into(), "1.0".into()),
+            ("Content-Type".into(), "text/plain; charset=UTF-8".into()),
+            ("Content-Transfer-Encoding".into(), "8bit".into()),
+            ("X-Source-Language".into(), po_language(source).into()),
+            ("X-Generator".into(), "rose-conv".into()),
+        ],
+        entries: Vec::new(),
+    };
+
+    // Messages have to be unique, the first row of a key wins
+    let mut seen = HashMap::new();
+    for (row_idx, key) in stl.keys.iter().enumerate() {
+        if seen.insert(key.id, row_idx).is_some() {
+            continue;
+        }
+
+        let (source_row, target_row) =
+            match (tables[0].rows.get(row_idx), tables[1].rows.get(row_idx)) {
+                (Some(source_row), Some(target_row)) => (source_row, target_row),
+                _ => bail!("STL has no row {} for every language", row_idx),
+            };
+        let source_values = stl_csv::row_values(source_row);
+        let target_values = stl_csv::row_values(target_row);
+
+        for (field_idx, (id, translation)) in source_values.iter().zip(&target_values).enumerate() {
+            if id.is_empty() {
+                continue;
+            }
+            po.entries.push(PoEntry {
+                comments: if key.name.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![key.name.clone()]
+                },
+                fuzzy: false,
+                context: Some(context(key, field_idx)),
+                id: id.to_string(),
+                translation: translation.to_string(),
+            });
+        }
+    }
+
+    Ok(po)
+}
+
+/// Patch the translations of a PO file into the `language` strings of an
+/// STL, returning the number of patched strings
+pub fn patch_stl(
+    stl: &mut STL,
+    po: &PoFile,
+    language: StringTableLanguage,
+) -> Result<usize, Error> {
+    let mut contexts = HashMap::new();
+    let field_count = stl_csv::columns(&stl.format).len();
+    for (row_idx, key) in stl.keys.iter().enumerate() {
+        for field_idx in 0..field_count {
+            contexts
+                .entry(context(key, field_idx))
+                .or_insert((row_idx, field_idx));
+        }
+    }
+
+    let format = stl.format;
+    let table = stl_csv::language_table_mut(stl, language)?;
+
+    let mut count = 0;
+    for entry in &po.entries {
+        if entry.fuzzy || entry.translation.is_empty() {
+            continue;
+        }
+
+        let context = entry.context.as_deref().unwrap_or_default();
+        let (row_idx, field_idx) = match contexts.get(context) {
+            Some(position) => *position,
+            None => bail!(
+                "Unknown message context \"{}\" of \"{}\"",
+                context,
+                entry.id
+            ),
+        };
+
+        let row = &mut table.rows[row_idx];
+        let mut values = stl_csv::row_values(row);
+        values[field_idx] = &entry.translation;
+        *row = stl_csv::row_from_values(&format, &values);
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_stl_po() {
+        let mut stl = STL::new();
+        stl.format = StringTableType::Item;
+        stl.keys.push(StringTableKey {
+            id: 7,
+            name: "ITEM_7".into(),
+        });
+        let languages = [
+            (StringTableLanguage::Korean, ["", ""]),
+            (
+                StringTableLanguage::English,
+                ["Sword", "A \"sharp\"\nsword"],
+            ),
+            (StringTableLanguage::Japanese, ["Katana", ""]),
+        ];
+        for (language, values) in languages.iter() {
+            stl.language_tables.push(StringTableLanguageTable {
+                language: *language,
+                rows: vec![stl_csv::row_from_values(&stl.format, values)],
+            });
+        }
+
+        let po = from_stl(
+            &stl,
+            StringTableLanguage::English,
+            StringTableLanguage::Japanese,
+        )
+        .unwrap();
+        let s = po.to_string();
+        assert!(s.contains("\"Language: ja\\n\"\n"));
+        assert!(s.contains(
+            "#. ITEM_7\nmsgctxt \"7.description\"\nmsgid \"\"\n\"A \\\"sharp\\\"\\n\"\n\"sword\"\nmsgstr \"\"\n"
+        ));
+
+        let mut parsed = PoFile::parse(&s).unwrap();
+        assert_eq!(parsed, po);
+        assert_eq!(
+            parse_po_language(parsed.header("language").unwrap()).unwrap(),
+            StringTableLanguage::Japanese
+        );
+
+        // Untranslated and fuzzy messages are skipped
+        parsed.entries[1].translation = "Eine Klinge".into();
+        parsed.entries[0].fuzzy = true;
+        let count = patch_stl(&mut stl, &parsed, StringTableLanguage::Japanese).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            stl_csv::row_values(&stl.language_tables[2].rows[0]),
+            vec!["Katana", "Eine Klinge"]
+        );
+
+        parsed.entries[1].context = Some("8".into());
+        assert!(patch_stl(&mut stl, &parsed, StringTableLanguage::Japanese).is_err());
+    }
+}
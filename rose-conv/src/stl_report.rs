@@ -0,0 +1,230 @@
+//! Missing-translation report of STL files
+//!
+//! A string of a target language is missing when it's empty while the source
+//! language has a string, and untranslated when it's identical to the source
+//! string. Strings that are empty in the source language aren't checked.
+use std::fmt;
+
+use failure::{bail, Error};
+use serde::Serialize;
+
+use roselib::files::stl::StringTableLanguage;
+use roselib::files::STL;
+
+use crate::stl_csv;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    Missing,
+    Untranslated,
+}
+
+/// String of a target language that needs to be translated
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Issue {
+    pub language: StringTableLanguage,
+    pub row: usize,
+    pub key_id: u32,
+    pub key_name: String,
+    /// Column of the string, e.g. `Description`
+    pub field: &'static str,
+    pub kind: IssueKind,
+    /// String of the source language
+    pub source: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            IssueKind::Missing => "missing",
+            IssueKind::Untranslated => "untranslated",
+        };
+        writeln!(
+            f,
+            "{} row {} ({} {}) {}: {} {:?}",
+            self.language.code(),
+            self.row,
+            self.key_id,
+            self.key_name,
+            self.field,
+            kind,
+            self.source
+        )
+    }
+}
+
+/// Number of issues of a target language
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LanguageCount {
+    pub language: StringTableLanguage,
+    pub missing: usize,
+    pub untranslated: usize,
+}
+
+/// Missing and untranslated strings of an STL
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StlReport {
+    pub source: StringTableLanguage,
+    pub counts: Vec<LanguageCount>,
+    pub issues: Vec<Issue>,
+}
+
+impl StlReport {
+    /// Check the `targets` strings of an STL against the `source` strings,
+    /// every other language if `targets` is empty
+    pub fn new(
+        stl: &STL,
+        source: StringTableLanguage,
+        targets: &[StringTableLanguage],
+    ) -> Result<StlReport, Error> {
+        let source_table = stl_csv::select_languages(stl, &[source])?[0];
+        let target_tables = if targets.is_empty() {
+            stl.language_tables
+                .iter()
+                .filter(|table| table.language != source)
+                .collect()
+        } else {
+            stl_csv::select_languages(stl, targets)?
+        };
+        let columns = stl_csv::columns(&stl.format);
+
+        let mut report = StlReport {
+            source,
+            counts: Vec::new(),
+            issues: Vec::new(),
+        };
+        for table in target_tables {
+            let mut count = LanguageCount {
+                language: table.language,
+                missing: 0,
+                untranslated: 0,
+            };
+
+            for (row, key) in stl.keys.iter().enumerate() {
+                let (source_row, target_row) =
+                    match (source_table.rows.get(row), table.rows.get(row)) {
+                        (Some(source_row), Some(target_row)) => (source_row, target_row),
+                        _ => bail!("STL has no row {} for every language", row),
+                    };
+
+                let source_values = stl_csv::row_values(source_row);
+                let target_values = stl_csv::row_values(target_row);
+                for (column_idx, (source_value, target_value)) in
+                    source_values.iter().zip(&target_values).enumerate()
+                {
+                    let kind = if source_value.is_empty() {
+                        continue;
+                    } else if target_value.is_empty() {
+                        count.missing += 1;
+                        IssueKind::Missing
+                    } else if source_value == target_value {
+                        count.untranslated += 1;
+                        IssueKind::Untranslated
+                    } else {
+                        continue;
+                    };
+
+                    report.issues.push(Issue {
+                        language: table.language,
+                        row,
+                        key_id: key.id,
+                        key_name: key.name.clone(),
+                        field: columns[column_idx],
+                        kind,
+                        source: source_value.to_string(),
+                    });
+                }
+            }
+            report.counts.push(count);
+        }
+        Ok(report)
+    }
+
+    pub fn missing(&self) -> usize {
+        self.counts.iter().map(|count| count.missing).sum()
+    }
+
+    pub fn untranslated(&self) -> usize {
+        self.counts.iter().map(|count| count.untranslated).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for StlReport {
+    /// Counts of every language followed by the issues
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for count in &self.counts {
+            writeln!(
+                f,
+                "{}: {} missing, {} untranslated",
+                count.language, count.missing, count.untranslated
+            )?;
+        }
+        for issue in &self.issues {
+            write!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::stl::*;
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_stl_report() {
+        let mut stl = STL::new();
+        stl.format = StringTableType::Item;
+        for id in 0..2 {
+            stl.keys.push(StringTableKey {
+                id,
+                name: format!("ITEM_{}", id),
+            });
+        }
+        let languages = [
+            (StringTableLanguage::Korean, [["Geom", ""], ["", ""]]),
+            (
+                StringTableLanguage::English,
+                [["Sword", "Sharp"], ["", "Round"]],
+            ),
+            (
+                StringTableLanguage::Japanese,
+                [["Katana", "Sharp"], ["Tate", ""]],
+            ),
+        ];
+        for (language, rows) in languages.iter() {
+            stl.language_tables.push(StringTableLanguageTable {
+                language: *language,
+                rows: rows
+                    .iter()
+                    .map(|values| stl_csv::row_from_values(&stl.format, values))
+                    .collect(),
+            });
+        }
+
+        let report = StlReport::new(&stl, StringTableLanguage::English, &[]).unwrap();
+        assert_eq!(report.counts.len(), 2);
+        assert_eq!(report.missing(), 3);
+        assert_eq!(report.untranslated(), 1);
+        assert_eq!(
+            report.issues[3].to_string(),
+            "jp row 1 (1 ITEM_1) Description: missing \"Round\"\n"
+        );
+
+        let japanese = [StringTableLanguage::Japanese];
+        let report = StlReport::new(&stl, StringTableLanguage::English, &japanese).unwrap();
+        assert_eq!(
+            report.to_string(),
+            "Japanese: 1 missing, 1 untranslated\n\
+             jp row 0 (0 ITEM_0) Description: untranslated \"Sharp\"\n\
+             jp row 1 (1 ITEM_1) Description: missing \"Round\"\n"
+        );
+    }
+}
@@ -0,0 +1,403 @@
+//! Terrain of maps for game engines
+//!
+//! A map block is 160 meters on each side with a HIM of 65x65 heights, the
+//! last row and column of a block repeating the first ones of the next block.
+//! The terrain of a map stitches its blocks into one grid of heights with the
+//! repeated edges merged, 64 cells per block. The top left corner of block
+//! `(x, y)` is at `(x * 160, (65 - y) * 160)` meters in the world, rows run
+//! south and columns east.
+use std::collections::BTreeMap;
+
+use image::{ImageBuffer, Luma};
+use serde::Serialize;
+
+use roselib::files::HIM;
+
+/// Size of a map block in centimeters
+pub const BLOCK_SIZE: f32 = 16_000.0;
+
+/// Cells of a map block on each axis
+pub const BLOCK_CELLS: usize = 64;
+
+/// Resolutions of Unity terrain heightmaps
+const UNITY_RESOLUTIONS: [usize; 8] = [33, 65, 129, 257, 513, 1025, 2049, 4097];
+
+/// Landscape sizes recommended by Unreal Engine, in vertices on each axis
+const UNREAL_SIZES: [usize; 7] = [127, 253, 505, 1009, 2017, 4033, 8129];
+
+/// Heights of the stitched blocks of a map
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Terrain {
+    /// Heights in centimeters by row and column, the lowest height where a
+    /// block is missing
+    pub heights: Vec<Vec<f32>>,
+    pub min_height: f32,
+    pub max_height: f32,
+    /// Block coordinates of the first and last blocks
+    pub min_block: [u32; 2],
+    pub max_block: [u32; 2],
+}
+
+impl Terrain {
+    /// Stitch the HIMs of a map by block coordinates
+    pub fn from_blocks(blocks: &BTreeMap<(u32, u32), HIM>) -> Terrain {
+        let xs = blocks.keys().map(|b| b.0);
+        let ys = blocks.keys().map(|b| b.1);
+        let min_block = [xs.clone().min().unwrap_or(0), ys.clone().min().unwrap_or(0)];
+        let max_block = [xs.max().unwrap_or(0), ys.max().unwrap_or(0)];
+
+        let cols = (max_block[0] - min_block[0] + 1) as usize * BLOCK_CELLS + 1;
+        let rows = (max_block[1] - min_block[1] + 1) as usize * BLOCK_CELLS + 1;
+        let mut heights = vec![vec![f32::NAN; cols]; rows];
+        let (mut min_height, mut max_height) = (f32::INFINITY, f32::NEG_INFINITY);
+        for (&(x, y), him) in blocks {
+            let row0 = (y - min_block[1]) as usize * BLOCK_CELLS;
+            let col0 = (x - min_block[0]) as usize * BLOCK_CELLS;
+            let size = (him.width.min(him.length).max(1) as usize).min(BLOCK_CELLS + 1);
+            for row in 0..size {
+                for col in 0..size {
+                    let height = him.height(row, col);
+                    min_height = min_height.min(height);
+                    max_height = max_height.max(height);
+                    heights[row0 + row][col0 + col] = height;
+                }
+            }
+        }
+        if min_height > max_height {
+            min_height = 0.0;
+            max_height = 0.0;
+        }
+        for height in heights.iter_mut().flatten().filter(|h| h.is_nan()) {
+            *height = min_height;
+        }
+
+        Terrain {
+            heights,
+            min_height,
+            max_height,
+            min_block,
+            max_block,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.heights.first().map_or(0, |r| r.len())
+    }
+
+    /// Size of the terrain in centimeters, east and south
+    pub fn size(&self) -> (f32, f32) {
+        let cell = BLOCK_SIZE / BLOCK_CELLS as f32;
+        (
+            (self.cols() - 1) as f32 * cell,
+            (self.rows() - 1) as f32 * cell,
+        )
+    }
+
+    /// World position of the top left corner in centimeters
+    pub fn origin(&self) -> (f32, f32) {
+        (
+            self.min_block[0] as f32 * BLOCK_SIZE,
+            (65.0 - self.min_block[1] as f32) * BLOCK_SIZE,
+        )
+    }
+
+    /// Heights resampled bilinearly to `rows` by `cols`, keeping the corners
+    pub fn resample(&self, rows: usize, cols: usize) -> Vec<Vec<f32>> {
+        let scale = |i: usize, n: usize, m: usize| {
+            if n > 1 {
+                i as f32 * (m - 1) as f32 / (n - 1) as f32
+            } else {
+                0.0
+            }
+        };
+        (0..rows)
+            .map(|row| {
+                let r = scale(row, rows, self.rows());
+                let (r0, fr) = (r.floor() as usize, r.fract());
+                let r1 = (r0 + 1).min(self.rows() - 1);
+                (0..cols)
+                    .map(|col| {
+                        let c = scale(col, cols, self.cols());
+                        let (c0, fc) = (c.floor() as usize, c.fract());
+                        let c1 = (c0 + 1).min(self.cols() - 1);
+                        let top = self.heights[r0][c0] * (1.0 - fc) + self.heights[r0][c1] * fc;
+                        let bottom = self.heights[r1][c0] * (1.0 - fc) + self.heights[r1][c1] * fc;
+                        top * (1.0 - fr) + bottom * fr
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Height scaled to 16 bits from the lowest to the highest height
+    pub fn to_u16(&self, height: f32) -> u16 {
+        let range = (self.max_height - self.min_height).max(f32::EPSILON);
+        (65535.0 * (height - self.min_height) / range)
+            .round()
+            .clamp(0.0, 65535.0) as u16
+    }
+}
+
+/// Texture of a terrain weighed by a channel of a splatmap
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TerrainLayer {
+    /// Client path of the ZON texture
+    pub source: String,
+    /// Converted texture, `None` if it's missing from the client
+    pub texture: Option<String>,
+    pub splatmap: String,
+    pub channel: &'static str,
+    /// Size of the texture on the terrain in meters, a map tile
+    pub tile_size: f32,
+}
+
+/// Settings of a Unity terrain, in meters with Y up and Z north
+#[derive(Debug, PartialEq, Serialize)]
+pub struct UnityTerrain {
+    /// RAW heightmap with `heightmap_resolution` heights on each axis
+    pub heightmap: String,
+    pub heightmap_resolution: usize,
+    /// Byte order of the RAW heightmap, as named by Unity's importer
+    pub byte_order: &'static str,
+    /// Width, height and length of the terrain
+    pub size: [f32; 3],
+    /// World position of the south west corner at the lowest height
+    pub position: [f32; 3],
+    pub layers: Vec<TerrainLayer>,
+    /// glTF of the water planes, `None` without water
+    pub water: Option<String>,
+}
+
+impl UnityTerrain {
+    pub fn new(
+        terrain: &Terrain,
+        heightmap: String,
+        heightmap_resolution: usize,
+        layers: Vec<TerrainLayer>,
+    ) -> UnityTerrain {
+        let (width, length) = terrain.size();
+        let (x, y) = terrain.origin();
+        UnityTerrain {
+            heightmap,
+            heightmap_resolution,
+            byte_order: "Windows",
+            size: [
+                width / 100.0,
+                (terrain.max_height - terrain.min_height) / 100.0,
+                length / 100.0,
+            ],
+            position: [x / 100.0, terrain.min_height / 100.0, (y - length) / 100.0],
+            layers,
+            water: None,
+        }
+    }
+}
+
+/// Texture of a landscape painted by a weight map
+#[derive(Debug, PartialEq, Serialize)]
+pub struct UnrealLayer {
+    pub name: String,
+    /// Client path of the ZON texture
+    pub source: String,
+    /// Converted texture, `None` if it's missing from the client
+    pub texture: Option<String>,
+    pub weightmap: String,
+}
+
+/// Import settings of an Unreal Engine landscape, in centimeters with X east,
+/// Y south and Z up
+#[derive(Debug, PartialEq, Serialize)]
+pub struct UnrealLandscape {
+    /// 16-bit PNG heightmap of `size` vertices, columns first
+    pub heightmap: String,
+    pub size: [usize; 2],
+    /// Location of the north west corner, with the middle height at Z
+    pub location: [f32; 3],
+    pub scale: [f32; 3],
+    pub layers: Vec<UnrealLayer>,
+    /// Data table of the placed objects, `None` without the zone's ZSCs
+    pub placements: Option<String>,
+    /// glTF of the water planes, `None` without water
+    pub water: Option<String>,
+}
+
+impl UnrealLandscape {
+    pub fn new(
+        terrain: &Terrain,
+        heightmap: String,
+        size: [usize; 2],
+        layers: Vec<UnrealLayer>,
+        placements: Option<String>,
+    ) -> UnrealLandscape {
+        let (width, length) = terrain.size();
+        let (x, y) = terrain.origin();
+        // Unreal heights are 1/128 of the Z scale from the middle height
+        let range = (terrain.max_height - terrain.min_height).max(1.0);
+        let z_scale = range * 128.0 / 65535.0;
+        UnrealLandscape {
+            heightmap,
+            size,
+            location: [x, -y, terrain.min_height + 32768.0 * z_scale / 128.0],
+            scale: [
+                width / (size[0].max(2) - 1) as f32,
+                length / (size[1].max(2) - 1) as f32,
+                z_scale,
+            ],
+            layers,
+            placements,
+            water: None,
+        }
+    }
+}
+
+/// Smallest resolution of a Unity terrain heightmap with at least `samples`
+/// heights on each axis, at most 4097
+pub fn unity_resolution(samples: usize) -> usize {
+    UNITY_RESOLUTIONS
+        .iter()
+        .copied()
+        .find(|&r| r >= samples)
+        .unwrap_or(UNITY_RESOLUTIONS[UNITY_RESOLUTIONS.len() - 1])
+}
+
+/// Smallest landscape size recommended by Unreal Engine with at least
+/// `samples` vertices, at most 8129
+pub fn unreal_size(samples: usize) -> usize {
+    UNREAL_SIZES
+        .iter()
+        .copied()
+        .find(|&s| s >= samples)
+        .unwrap_or(UNREAL_SIZES[UNREAL_SIZES.len() - 1])
+}
+
+/// 16-bit heightmap of the terrain resampled to `cols` by `rows` for Unreal
+/// Engine landscapes
+pub fn unreal_heightmap(
+    terrain: &Terrain,
+    cols: usize,
+    rows: usize,
+) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+    let heights = terrain.resample(rows, cols);
+    ImageBuffer::from_fn(cols as u32, rows as u32, |x, y| {
+        Luma([terrain.to_u16(heights[y as usize][x as usize])])
+    })
+}
+
+/// Unity RAW heightmap of the terrain at `resolution`: 16-bit little-endian
+/// heights, the southern row first like the rows of Unity terrains
+pub fn unity_raw(terrain: &Terrain, resolution: usize) -> Vec<u8> {
+    let heights = terrain.resample(resolution, resolution);
+    let mut raw = Vec::with_capacity(resolution * resolution * 2);
+    for row in heights.iter().rev() {
+        for &height in row {
+            raw.extend_from_slice(&terrain.to_u16(height).to_le_bytes());
+        }
+    }
+    raw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::io::RoseFile;
+
+    fn him(height: impl Fn(usize, usize) -> f32) -> HIM {
+        let mut him = HIM::new();
+        him.width = 65;
+        him.length = 65;
+        for y in 0..65 {
+            for x in 0..65 {
+                him.heights.push(height(x, y));
+            }
+        }
+        him
+    }
+
+    #[test]
+    fn test_terrain() {
+        let mut blocks = BTreeMap::new();
+        blocks.insert((31, 32), him(|_, _| 100.0));
+        // Rising down the rows, like the heightmaps of `map`
+        blocks.insert((32, 32), him(|x, _| 100.0 + x as f32 * 10.0));
+        blocks.insert((32, 33), him(|_, _| 500.0));
+        let terrain = Terrain::from_blocks(&blocks);
+
+        assert_eq!((terrain.rows(), terrain.cols()), (129, 129));
+        assert_eq!((terrain.min_block, terrain.max_block), ([31, 32], [32, 33]));
+        assert_eq!((terrain.min_height, terrain.max_height), (100.0, 740.0));
+        assert_eq!(terrain.size(), (32000.0, 32000.0));
+        assert_eq!(terrain.origin(), (496_000.0, 528_000.0));
+        assert_eq!(terrain.heights[0][64], 100.0);
+        assert_eq!(terrain.heights[10][64], 200.0);
+        // The missing block is at the lowest height
+        assert_eq!(terrain.heights[100][10], 100.0);
+        assert_eq!(terrain.heights[100][100], 500.0);
+
+        let resampled = terrain.resample(65, 65);
+        assert_eq!(resampled[0][0], 100.0);
+        assert_eq!(resampled[5][48], 200.0);
+        assert_eq!(resampled[64][64], 500.0);
+        assert_eq!(terrain.to_u16(740.0), 65535);
+    }
+
+    #[test]
+    fn test_unity_raw() {
+        assert_eq!(unity_resolution(1), 33);
+        assert_eq!(unity_resolution(129), 129);
+        assert_eq!(unity_resolution(130), 257);
+        assert_eq!(unity_resolution(10_000), 4097);
+
+        let mut blocks = BTreeMap::new();
+        blocks.insert((32, 32), him(|_, _| 0.0));
+        blocks.insert((32, 33), him(|_, _| 100.0));
+        let terrain = Terrain::from_blocks(&blocks);
+        let raw = unity_raw(&terrain, 129);
+        assert_eq!(raw.len(), 129 * 129 * 2);
+        // The southern block first
+        assert_eq!(&raw[..2], &[0xFF, 0xFF]);
+        assert_eq!(&raw[raw.len() - 2..], &[0, 0]);
+
+        let unity = UnityTerrain::new(&terrain, String::from("JPT01.raw"), 129, Vec::new());
+        assert_eq!(unity.size, [160.0, 1.0, 320.0]);
+        assert_eq!(unity.position, [5120.0, 0.0, 4960.0]);
+    }
+
+    #[test]
+    fn test_unreal_heightmap() {
+        assert_eq!(unreal_size(65), 127);
+        assert_eq!(unreal_size(129), 253);
+        assert_eq!(unreal_size(20_000), 8129);
+
+        let mut blocks = BTreeMap::new();
+        blocks.insert((32, 32), him(|_, _| 0.0));
+        blocks.insert((33, 32), him(|_, _| 100.0));
+        let terrain = Terrain::from_blocks(&blocks);
+        let img = unreal_heightmap(&terrain, 253, 127);
+        assert_eq!(img.dimensions(), (253, 127));
+        // The eastern block on the right
+        assert_eq!(img.get_pixel(0, 0)[0], 0);
+        assert_eq!(img.get_pixel(252, 126)[0], 65535);
+
+        let landscape = UnrealLandscape::new(
+            &terrain,
+            String::from("JPT01.png"),
+            [253, 127],
+            Vec::new(),
+            None,
+        );
+        assert_eq!(
+            landscape.scale,
+            [32000.0 / 252.0, 16000.0 / 126.0, 12800.0 / 65535.0]
+        );
+        assert_eq!(landscape.location[..2], [512_000.0, -528_000.0]);
+        // The lowest and highest heights
+        let height = |v: f32| landscape.location[2] + (v - 32768.0) * landscape.scale[2] / 128.0;
+        assert!(height(0.0).abs() < 0.01);
+        assert!((height(65535.0) - 100.0).abs() < 0.01);
+    }
+}
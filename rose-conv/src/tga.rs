@@ -0,0 +1,242 @@
+//! Truevision TGA images
+//!
+//! Decodes the TGA images of the client's UI and effects: color-mapped,
+//! true-color and grayscale images, raw or run-length encoded, with 15, 16, 24
+//! or 32-bit pixels and any origin. The image crate doesn't read 16-bit
+//! (A1R5G5B5) images and ignores right-to-left origins.
+//!
+//! 32-bit images whose header declares no alpha bits often leave the alpha
+//! byte as 0, so they are only read as transparent if some pixel has alpha.
+use byteorder::{ByteOrder, LittleEndian};
+use failure::{bail, Error};
+use image::{Rgba, RgbaImage};
+
+const HEADER_SIZE: usize = 18;
+
+const COLOR_MAPPED: u8 = 1;
+const TRUE_COLOR: u8 = 2;
+const GRAYSCALE: u8 = 3;
+const RLE: u8 = 8;
+
+const ALPHA_BITS_MASK: u8 = 0xF;
+const RIGHT_TO_LEFT: u8 = 0x10;
+const TOP_TO_BOTTOM: u8 = 0x20;
+
+/// Width and height of a TGA image
+pub fn dimensions(data: &[u8]) -> Result<(u32, u32), Error> {
+    if data.len() < HEADER_SIZE {
+        bail!("Invalid TGA image: header is {} bytes", data.len());
+    }
+    let width = LittleEndian::read_u16(&data[12..14]) as u32;
+    let height = LittleEndian::read_u16(&data[14..16]) as u32;
+    Ok((width, height))
+}
+
+/// Decode a TGA image
+pub fn decode(data: &[u8]) -> Result<RgbaImage, Error> {
+    let (width, height) = dimensions(data)?;
+    let id_length = data[0] as usize;
+    let map_type = data[1];
+    let image_type = data[2];
+    let map_first = LittleEndian::read_u16(&data[3..5]) as usize;
+    let map_length = LittleEndian::read_u16(&data[5..7]) as usize;
+    let map_depth = data[7];
+    let depth = data[16];
+    let descriptor = data[17];
+    let alpha_bits = descriptor & ALPHA_BITS_MASK;
+
+    let kind = image_type & !RLE;
+    match (kind, depth) {
+        (COLOR_MAPPED, 8) | (COLOR_MAPPED, 16) => {}
+        (TRUE_COLOR, 15) | (TRUE_COLOR, 16) | (TRUE_COLOR, 24) | (TRUE_COLOR, 32) => {}
+        (GRAYSCALE, 8) | (GRAYSCALE, 16) => {}
+        _ => bail!(
+            "Unsupported TGA image of type {} with {}-bit pixels",
+            image_type,
+            depth
+        ),
+    }
+
+    let mut offset = HEADER_SIZE + id_length;
+    let mut palette = Vec::new();
+    if map_type == 1 {
+        let entry_size = (map_depth as usize).div_ceil(8);
+        let map_size = map_length * entry_size;
+        if data.len() < offset + map_size {
+            bail!("Invalid TGA image: color map is truncated");
+        }
+        for entry in data[offset..offset + map_size].chunks(entry_size) {
+            palette.push(color(entry, map_depth, alpha_bits)?);
+        }
+        offset += map_size;
+    } else if kind == COLOR_MAPPED {
+        bail!("Invalid TGA image: color-mapped image without a color map");
+    }
+
+    let pixel_size = (depth as usize).div_ceil(8);
+    let pixel_count = width as usize * height as usize;
+    let pixels = if image_type & RLE != 0 {
+        decode_rle(&data[offset..], pixel_size, pixel_count)?
+    } else {
+        let size = pixel_count * pixel_size;
+        if data.len() < offset + size {
+            bail!("Invalid TGA image: expected {} bytes of pixels", size);
+        }
+        data[offset..offset + size].to_vec()
+    };
+
+    let mut colors = Vec::with_capacity(pixel_count);
+    for pixel in pixels.chunks(pixel_size) {
+        colors.push(match kind {
+            COLOR_MAPPED => {
+                let index = if pixel_size == 1 {
+                    pixel[0] as usize
+                } else {
+                    LittleEndian::read_u16(pixel) as usize
+                };
+                match index.checked_sub(map_first).and_then(|i| palette.get(i)) {
+                    Some(color) => *color,
+                    None => bail!("Invalid TGA image: color index {} is out of range", index),
+                }
+            }
+            GRAYSCALE => {
+                let alpha = if pixel_size == 2 { pixel[1] } else { 255 };
+                [pixel[0], pixel[0], pixel[0], alpha]
+            }
+            _ => color(pixel, depth, alpha_bits)?,
+        });
+    }
+
+    if depth == 32 && alpha_bits == 0 && colors.iter().all(|c| c[3] == 0) {
+        for color in &mut colors {
+            color[3] = 255;
+        }
+    }
+
+    let mut img = RgbaImage::new(width, height);
+    for (i, color) in colors.into_iter().enumerate() {
+        let mut x = i as u32 % width;
+        let mut y = i as u32 / width;
+        if descriptor & RIGHT_TO_LEFT != 0 {
+            x = width - 1 - x;
+        }
+        if descriptor & TOP_TO_BOTTOM == 0 {
+            y = height - 1 - y;
+        }
+        img.put_pixel(x, y, Rgba(color));
+    }
+    Ok(img)
+}
+
+/// Pixels of run-length encoded data: packets of repeated or raw pixels
+fn decode_rle(data: &[u8], pixel_size: usize, pixel_count: usize) -> Result<Vec<u8>, Error> {
+    let size = pixel_count * pixel_size;
+    let mut pixels = Vec::with_capacity(size);
+    let mut offset = 0;
+    while pixels.len() < size {
+        let packet = match data.get(offset) {
+            Some(packet) => *packet,
+            None => bail!("Invalid TGA image: run-length encoded pixels are truncated"),
+        };
+        offset += 1;
+        let count = (packet & 0x7F) as usize + 1;
+        let length = if packet & 0x80 != 0 {
+            pixel_size
+        } else {
+            count * pixel_size
+        };
+        if data.len() < offset + length {
+            bail!("Invalid TGA image: run-length encoded pixels are truncated");
+        }
+        if packet & 0x80 != 0 {
+            for _ in 0..count {
+                pixels.extend_from_slice(&data[offset..offset + length]);
+            }
+        } else {
+            pixels.extend_from_slice(&data[offset..offset + length]);
+        }
+        offset += length;
+    }
+    // Packets may cross the end of the image
+    pixels.truncate(size);
+    Ok(pixels)
+}
+
+/// RGBA color of a BGR(A) pixel or color map entry. 16-bit pixels have 5 bits
+/// per channel and an alpha bit if the image has alpha.
+fn color(pixel: &[u8], depth: u8, alpha_bits: u8) -> Result<[u8; 4], Error> {
+    Ok(match depth {
+        15 | 16 => {
+            let value = LittleEndian::read_u16(pixel);
+            let channel = |shift: u16| {
+                let v = ((value >> shift) & 0x1F) as u8;
+                (v << 3) | (v >> 2)
+            };
+            let alpha = if depth == 16 && alpha_bits > 0 && value & 0x8000 == 0 {
+                0
+            } else {
+                255
+            };
+            [channel(10), channel(5), channel(0), alpha]
+        }
+        24 => [pixel[2], pixel[1], pixel[0], 255],
+        32 => [pixel[2], pixel[1], pixel[0], pixel[3]],
+        _ => bail!("Unsupported TGA color depth: {}", depth),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(image_type: u8, width: u16, height: u16, depth: u8, descriptor: u8) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_SIZE];
+        data[2] = image_type;
+        LittleEndian::write_u16(&mut data[12..14], width);
+        LittleEndian::write_u16(&mut data[14..16], height);
+        data[16] = depth;
+        data[17] = descriptor;
+        data
+    }
+
+    #[test]
+    fn test_decode() {
+        // 32-bit with alpha, bottom-left origin
+        let mut data = header(TRUE_COLOR, 2, 2, 32, 8);
+        data.extend_from_slice(&[0, 0, 255, 255, 0, 255, 0, 128]);
+        data.extend_from_slice(&[255, 0, 0, 0, 1, 2, 3, 4]);
+        let img = decode(&data).unwrap();
+        assert_eq!(img.get_pixel(0, 1), &Rgba([255, 0, 0, 255]));
+        assert_eq!(img.get_pixel(1, 1), &Rgba([0, 255, 0, 128]));
+        assert_eq!(img.get_pixel(0, 0), &Rgba([0, 0, 255, 0]));
+        assert_eq!(img.get_pixel(1, 0), &Rgba([3, 2, 1, 4]));
+
+        // 32-bit without alpha bits and an unused alpha byte, top-right origin
+        let mut data = header(TRUE_COLOR, 2, 1, 32, TOP_TO_BOTTOM | RIGHT_TO_LEFT);
+        data.extend_from_slice(&[0, 0, 255, 0, 255, 0, 0, 0]);
+        let img = decode(&data).unwrap();
+        assert_eq!(img.get_pixel(0, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(img.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+
+        // Run-length encoded A1R5G5B5
+        let mut data = header(TRUE_COLOR | RLE, 3, 1, 16, 1 | TOP_TO_BOTTOM);
+        data.extend_from_slice(&[0x81, 0x1F, 0x80, 0x00, 0xE0, 0x7F]);
+        let img = decode(&data).unwrap();
+        assert_eq!(img.get_pixel(0, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(img.get_pixel(1, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(img.get_pixel(2, 0), &Rgba([255, 255, 0, 0]));
+
+        // Color-mapped with a 24-bit color map
+        let mut data = header(COLOR_MAPPED, 2, 1, 8, TOP_TO_BOTTOM);
+        data[1] = 1;
+        LittleEndian::write_u16(&mut data[5..7], 2);
+        data[7] = 24;
+        data.extend_from_slice(&[0, 0, 0, 255, 255, 255, 1, 0]);
+        let img = decode(&data).unwrap();
+        assert_eq!(img.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+        assert_eq!(img.get_pixel(1, 0), &Rgba([0, 0, 0, 255]));
+
+        assert!(decode(&header(TRUE_COLOR, 2, 2, 32, 8)).is_err());
+        assert!(decode(&header(TRUE_COLOR, 1, 1, 12, 0)).is_err());
+    }
+}
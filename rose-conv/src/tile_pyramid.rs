@@ -0,0 +1,154 @@
+//! Tile pyramids of images for web maps
+//!
+//! Tiles are named `{z}/{x}/{y}` like the tiles of a slippy map, with the
+//! image in the top left corner of the square of the highest zoom level. Each
+//! lower zoom level halves the size, down to a single tile at zoom level 0.
+//! This is the layout of Leaflet's `CRS.Simple`, where the pixels of the image
+//! at the highest zoom level are the map units.
+use failure::Error;
+use image::imageops;
+use image::{Rgba, RgbaImage};
+use serde::Serialize;
+
+/// Dimensions and zoom levels of a tile pyramid
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TilePyramid {
+    /// Size of the image at the highest zoom level
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: u32,
+    pub min_zoom: u32,
+    pub max_zoom: u32,
+}
+
+impl TilePyramid {
+    /// Pyramid of an image of `width` by `height` pixels with the lowest zoom
+    /// level that shows the image at full size
+    pub fn new(width: u32, height: u32, tile_size: u32) -> TilePyramid {
+        let mut max_zoom = 0;
+        while (tile_size << max_zoom) < width.max(height) {
+            max_zoom += 1;
+        }
+        TilePyramid {
+            width,
+            height,
+            tile_size,
+            min_zoom: 0,
+            max_zoom,
+        }
+    }
+
+    /// Cut `img` into the tiles of every zoom level, calling `write` with the
+    /// zoom level, column, row and image of each tile. Fully transparent
+    /// tiles are skipped.
+    pub fn write_tiles<F>(&self, img: &RgbaImage, mut write: F) -> Result<(), Error>
+    where
+        F: FnMut(u32, u32, u32, &RgbaImage) -> Result<(), Error>,
+    {
+        let size = self.tile_size << self.max_zoom;
+        let mut level = RgbaImage::new(size, size);
+        imageops::replace(&mut level, img, 0, 0);
+
+        for z in (self.min_zoom..=self.max_zoom).rev() {
+            let tiles = level.width() / self.tile_size;
+            for y in 0..tiles {
+                for x in 0..tiles {
+                    let tile = imageops::crop_imm(
+                        &level,
+                        x * self.tile_size,
+                        y * self.tile_size,
+                        self.tile_size,
+                        self.tile_size,
+                    )
+                    .to_image();
+                    if tile.pixels().any(|p| p[3] != 0) {
+                        write(z, x, y, &tile)?;
+                    }
+                }
+            }
+            if z > self.min_zoom {
+                level = half_size(&level);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Halve an image by averaging every 2x2 square of pixels, weighted by alpha
+/// so that transparent pixels don't darken their neighbours
+fn half_size(img: &RgbaImage) -> RgbaImage {
+    let mut half = RgbaImage::new(img.width() / 2, img.height() / 2);
+    for (x, y, pixel) in half.enumerate_pixels_mut() {
+        let mut color = [0u32; 3];
+        let mut alpha = 0u32;
+        for (dx, dy) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let p = img.get_pixel(x * 2 + dx, y * 2 + dy);
+            for c in 0..3 {
+                color[c] += p[c] as u32 * p[3] as u32;
+            }
+            alpha += p[3] as u32;
+        }
+        // Fully transparent squares stay black
+        let weight = alpha.max(1);
+        *pixel = Rgba([
+            (color[0] / weight) as u8,
+            (color[1] / weight) as u8,
+            (color[2] / weight) as u8,
+            (alpha / 4) as u8,
+        ]);
+    }
+    half
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zoom_levels() {
+        assert_eq!(TilePyramid::new(256, 100, 256).max_zoom, 0);
+        assert_eq!(TilePyramid::new(257, 100, 256).max_zoom, 1);
+        assert_eq!(TilePyramid::new(640, 1024, 256).max_zoom, 2);
+    }
+
+    #[test]
+    fn test_write_tiles() {
+        let img = RgbaImage::from_pixel(12, 6, Rgba([200, 100, 0, 255]));
+        let pyramid = TilePyramid::new(img.width(), img.height(), 4);
+        assert_eq!(pyramid.max_zoom, 2);
+
+        let mut tiles = Vec::new();
+        pyramid
+            .write_tiles(&img, |z, x, y, tile| {
+                tiles.push((z, x, y, *tile.get_pixel(0, 1), *tile.get_pixel(3, 3)));
+                Ok(())
+            })
+            .unwrap();
+
+        let opaque = Rgba([200, 100, 0, 255]);
+        let coordinates: Vec<(u32, u32, u32)> = tiles.iter().map(|t| (t.0, t.1, t.2)).collect();
+        assert_eq!(
+            coordinates,
+            vec![
+                (2, 0, 0),
+                (2, 1, 0),
+                (2, 2, 0),
+                (2, 0, 1),
+                (2, 1, 1),
+                (2, 2, 1),
+                (1, 0, 0),
+                (1, 1, 0),
+                (0, 0, 0),
+            ]
+        );
+        assert_eq!(tiles[0].3, opaque);
+        // Below the bottom of the image
+        assert_eq!(tiles[3].4, Rgba([0, 0, 0, 0]));
+        assert_eq!(tiles[7].3, opaque);
+        assert_eq!(tiles[7].4, Rgba([0, 0, 0, 0]));
+        // The bottom row of the image averaged with the transparent row below
+        // it, without darkening
+        assert_eq!(tiles[8].3, Rgba([200, 100, 0, 127]));
+        assert_eq!(tiles[8].4, Rgba([0, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,235 @@
+//! Sprite atlas descriptors of TSI sheets
+//!
+//! Sheets are described as TexturePacker JSON (hash) atlases, read by most
+//! web and game engines, or as CSS sprite maps. Sheets are referenced by
+//! their file name with the extension of the exported images, as the DDS
+//! textures themselves can't be used by those tools.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use failure::{bail, Error};
+use serde::Serialize;
+
+use roselib::files::tsi::SpriteSheet;
+
+use crate::iconsheet::{self, IconRect};
+
+/// Names of the atlas formats, for the command line
+pub const ATLAS_FORMAT_NAMES: [&str; 2] = ["texturepacker", "css"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AtlasFormat {
+    /// TexturePacker JSON (hash), one file per sheet
+    TexturePacker,
+    /// CSS classes with the sheet as background, one file per TSI
+    Css,
+}
+
+impl AtlasFormat {
+    pub fn from_name(name: &str) -> Result<AtlasFormat, Error> {
+        match name.trim().to_lowercase().as_str() {
+            "texturepacker" => Ok(AtlasFormat::TexturePacker),
+            "css" => Ok(AtlasFormat::Css),
+            _ => bail!("Unknown atlas format: {}", name),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AtlasFormat::TexturePacker => "json",
+            AtlasFormat::Css => "css",
+        }
+    }
+}
+
+/// Sprites of a sheet, named as when cutting the sheet into icons
+pub struct AtlasSheet {
+    /// File name of the sheet without extension, e.g. `UI01`
+    pub name: String,
+    /// File name of the sheet image, e.g. `UI01.png`
+    pub image: String,
+    pub sprites: Vec<IconRect>,
+}
+
+impl AtlasSheet {
+    pub fn new(sheet: &SpriteSheet, extension: &str) -> AtlasSheet {
+        // Paths are stored with backslashes
+        let path = sheet.path.to_string_lossy().replace('\\', "/");
+        let name = Path::new(&path)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        AtlasSheet {
+            image: format!("{}.{}", name, extension),
+            sprites: iconsheet::tsi_icons(&name, sheet),
+            name,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Serialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Frame {
+    frame: Rect,
+    rotated: bool,
+    trimmed: bool,
+    sprite_source_size: Rect,
+    source_size: Size,
+}
+
+#[derive(Serialize)]
+struct Meta {
+    app: &'static str,
+    version: &'static str,
+    image: String,
+    format: &'static str,
+    scale: &'static str,
+}
+
+#[derive(Serialize)]
+struct TexturePackerAtlas {
+    frames: BTreeMap<String, Frame>,
+    meta: Meta,
+}
+
+/// TexturePacker JSON (hash) atlas of a sheet
+pub fn texturepacker_json(sheet: &AtlasSheet, pretty: bool) -> Result<String, Error> {
+    let frames = sheet
+        .sprites
+        .iter()
+        .map(|sprite| {
+            let frame = Frame {
+                frame: Rect {
+                    x: sprite.x,
+                    y: sprite.y,
+                    w: sprite.width,
+                    h: sprite.height,
+                },
+                rotated: false,
+                trimmed: false,
+                sprite_source_size: Rect {
+                    x: 0,
+                    y: 0,
+                    w: sprite.width,
+                    h: sprite.height,
+                },
+                source_size: Size {
+                    w: sprite.width,
+                    h: sprite.height,
+                },
+            };
+            (sprite.name.clone(), frame)
+        })
+        .collect();
+
+    let atlas = TexturePackerAtlas {
+        frames,
+        meta: Meta {
+            app: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            image: sheet.image.clone(),
+            format: "RGBA8888",
+            scale: "1",
+        },
+    };
+    Ok(if pretty {
+        serde_json::to_string_pretty(&atlas)?
+    } else {
+        serde_json::to_string(&atlas)?
+    })
+}
+
+/// CSS sprite map of sheets, a `.<sheet>-<sprite>` class per sprite
+pub fn css(sheets: &[AtlasSheet]) -> String {
+    let mut css = String::new();
+    for sheet in sheets {
+        for sprite in &sheet.sprites {
+            let _ = writeln!(
+                css,
+                ".{} {{ background: url(\"{}\") {} {}; width: {}px; height: {}px; }}",
+                css_class(&format!("{}-{}", sheet.name, sprite.name)),
+                sheet.image,
+                css_offset(sprite.x),
+                css_offset(sprite.y),
+                sprite.width,
+                sprite.height
+            );
+        }
+    }
+    css
+}
+
+/// Background position of a sprite at an offset in the sheet
+fn css_offset(offset: u32) -> String {
+    if offset == 0 {
+        String::from("0")
+    } else {
+        format!("-{}px", offset)
+    }
+}
+
+/// Class name with the characters that would need escaping replaced
+fn css_class(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use roselib::files::tsi::Sprite;
+    use roselib::utils::Vector2;
+
+    #[test]
+    fn test_atlas() {
+        let mut sheet = SpriteSheet::new();
+        sheet.path = PathBuf::from("3DDATA\\Control\\Res\\UI01.DDS");
+        let mut sprite = Sprite::new();
+        sprite.name = String::from("BTN.OK\0\0");
+        sprite.start_point = Vector2 { x: 10, y: 20 };
+        sprite.end_point = Vector2 { x: 50, y: 36 };
+        sheet.sprites.push(sprite);
+
+        let sheet = AtlasSheet::new(&sheet, "png");
+        assert_eq!(sheet.image, "UI01.png");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&texturepacker_json(&sheet, false).unwrap()).unwrap();
+        assert_eq!(json["meta"]["image"], "UI01.png");
+        let frame = &json["frames"]["BTN.OK"];
+        assert_eq!(frame["frame"]["x"], 10);
+        assert_eq!(frame["frame"]["w"], 40);
+        assert_eq!(frame["sourceSize"]["h"], 16);
+
+        assert_eq!(
+            css(&[sheet]),
+            ".UI01-BTN-OK { background: url(\"UI01.png\") -10px -20px; width: 40px; height: 16px; }\n"
+        );
+    }
+}
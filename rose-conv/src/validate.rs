@@ -0,0 +1,421 @@
+//! Cross-reference checks of a client
+//!
+//! ROSE files reference each other by client path, STL key or index, and a
+//! broken reference usually only shows as a crash of the client. The checks
+//! are:
+//!
+//! * the string IDs of an STB, in its last column, are keys of the STL next
+//!   to it with the same name and an `_S` suffix, e.g. `LIST_WEAPON_S.STL`
+//! * the meshes and textures of a ZSC exist, and its parts reference meshes
+//!   and materials in its tables
+//! * the decorations and buildings of an IFO reference objects of the ZSCs
+//!   listed for its zone in `LIST_ZONE.STB`
+//! * the tiles of a TIL reference tiles of the ZON in its directory
+//! * the channels of a ZMO animate bones of the ZMD in its directory, for
+//!   directories with a single skeleton since motions aren't linked to their
+//!   skeleton otherwise
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::Serialize;
+
+use roselib::files::{IFO, STB, STL, TIL, ZMD, ZMO, ZON, ZSC};
+
+use crate::client::{Client, ClientFile};
+use crate::ifo_instances::zone_scenes;
+use crate::vfs::client_path_key;
+use crate::zsc_bundle::{dependencies, DependencyType};
+
+/// Path of the zone list in the client
+pub const LIST_ZONE_PATH: &str = "3DDATA/STB/LIST_ZONE.STB";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    StringId,
+    SceneReference,
+    ObjectId,
+    TileId,
+    BoneIndex,
+    /// A file that couldn't be read
+    Unreadable,
+}
+
+/// Broken reference of a file
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Issue {
+    pub file: String,
+    pub check: Check,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+/// Run every check on the files of a client
+pub fn validate(client: &Client) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut add = |file: &str, check: Check, messages: Vec<String>| {
+        issues.extend(messages.into_iter().map(|message| Issue {
+            file: file.to_string(),
+            check,
+            message,
+        }));
+    };
+
+    // STB string IDs
+    for file in client.files_with_extension("stb") {
+        let stl_path = format!("{}_S.STL", &file.path[..file.path.len() - 4]);
+        let stl_path = match client.get(&stl_path) {
+            Some(stl) => stl.path.clone(),
+            None => continue,
+        };
+        match (
+            client.read_file::<STB>(&file.path),
+            client.read_file::<STL>(&stl_path),
+        ) {
+            (Ok(stb), Ok(stl)) => add(&file.path, Check::StringId, string_ids(&stb, &stl)),
+            (Err(e), _) => add(&file.path, Check::Unreadable, vec![e.to_string()]),
+            (_, Err(e)) => add(&stl_path, Check::Unreadable, vec![e.to_string()]),
+        }
+    }
+
+    // ZSC references
+    let mut scenes: HashMap<String, Option<ZSC>> = HashMap::new();
+    for file in client.files_with_extension("zsc") {
+        match client.read_file::<ZSC>(&file.path) {
+            Ok(zsc) => {
+                let messages = scene_references(&zsc, |p| client.contains(p));
+                add(&file.path, Check::SceneReference, messages);
+                scenes.insert(client_path_key(&file.path), Some(zsc));
+            }
+            Err(e) => {
+                add(&file.path, Check::Unreadable, vec![e.to_string()]);
+                scenes.insert(client_path_key(&file.path), None);
+            }
+        }
+    }
+
+    // Map blocks of every zone
+    let list_zone = client.read_file::<STB>(LIST_ZONE_PATH).ok();
+    for zon_file in client.files_with_extension("zon") {
+        let zon = match client.read_file::<ZON>(&zon_file.path) {
+            Ok(zon) => zon,
+            Err(e) => {
+                add(&zon_file.path, Check::Unreadable, vec![e.to_string()]);
+                continue;
+            }
+        };
+        let zone_scenes = list_zone
+            .as_ref()
+            .and_then(|list_zone| zone_scenes(list_zone, &zon_file.path))
+            .map(|(decorations, buildings)| {
+                let scene = |path: &str| {
+                    scenes
+                        .get(&client_path_key(path))
+                        .and_then(|zsc| zsc.as_ref())
+                };
+                (scene(&decorations), scene(&buildings))
+            });
+
+        for block in zone_blocks(client, zon_file) {
+            match block.extension().as_str() {
+                "til" => match client.read_file::<TIL>(&block.path) {
+                    Ok(til) => add(&block.path, Check::TileId, tile_ids(&til, &zon)),
+                    Err(e) => add(&block.path, Check::Unreadable, vec![e.to_string()]),
+                },
+                "ifo" => {
+                    let (decorations, buildings) = match zone_scenes {
+                        Some(scenes) => scenes,
+                        None => continue,
+                    };
+                    match client.read_file::<IFO>(&block.path) {
+                        Ok(ifo) => add(
+                            &block.path,
+                            Check::ObjectId,
+                            object_ids(&ifo, decorations, buildings),
+                        ),
+                        Err(e) => add(&block.path, Check::Unreadable, vec![e.to_string()]),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Motions of the skeletons alone in their directory
+    let mut skeleton_files: HashMap<String, Vec<&ClientFile>> = HashMap::new();
+    for file in client.files_with_extension("zmd") {
+        skeleton_files
+            .entry(client_path_key(directory(&file.path)))
+            .or_default()
+            .push(file);
+    }
+    let mut skeletons: HashMap<String, ZMD> = HashMap::new();
+    for (dir, files) in skeleton_files {
+        if let [file] = files.as_slice() {
+            match client.read_file::<ZMD>(&file.path) {
+                Ok(zmd) => {
+                    skeletons.insert(dir, zmd);
+                }
+                Err(e) => add(&file.path, Check::Unreadable, vec![e.to_string()]),
+            }
+        }
+    }
+    for file in client.files_with_extension("zmo") {
+        let zmd = match skeletons.get(&client_path_key(directory(&file.path))) {
+            Some(zmd) => zmd,
+            None => continue,
+        };
+        match client.read_file::<ZMO>(&file.path) {
+            Ok(zmo) => add(&file.path, Check::BoneIndex, bone_indices(&zmo, zmd)),
+            Err(e) => add(&file.path, Check::Unreadable, vec![e.to_string()]),
+        }
+    }
+
+    issues
+}
+
+/// String IDs of an STB missing from its STL
+pub fn string_ids(stb: &STB, stl: &STL) -> Vec<String> {
+    let keys: HashSet<&str> = stl.keys.iter().map(|k| k.name.as_str()).collect();
+    stb.data
+        .iter()
+        .enumerate()
+        .filter_map(|(row, cells)| {
+            let id = cells.last()?.trim();
+            if id.is_empty() || keys.contains(id) {
+                return None;
+            }
+            Some(format!("row {}: string ID `{}` isn't in the STL", row, id))
+        })
+        .collect()
+}
+
+/// Missing meshes and textures of a ZSC and parts referencing meshes or
+/// materials past its tables
+pub fn scene_references<F>(zsc: &ZSC, exists: F) -> Vec<String>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut messages = Vec::new();
+    for dependency in dependencies(zsc) {
+        let kind = match dependency.file_type {
+            DependencyType::Mesh => "mesh",
+            DependencyType::Texture => "texture",
+            _ => continue,
+        };
+        if !exists(&dependency.path) {
+            messages.push(format!("missing {} {}", kind, dependency.path));
+        }
+    }
+    for (o, object) in zsc.objects.iter().enumerate() {
+        for (p, part) in object.parts.iter().enumerate() {
+            if part.mesh_id as usize >= zsc.meshes.len() {
+                messages.push(format!(
+                    "object {} part {}: mesh {} is past the {} meshes",
+                    o,
+                    p,
+                    part.mesh_id,
+                    zsc.meshes.len()
+                ));
+            }
+            if part.material_id as usize >= zsc.materials.len() {
+                messages.push(format!(
+                    "object {} part {}: material {} is past the {} materials",
+                    o,
+                    p,
+                    part.material_id,
+                    zsc.materials.len()
+                ));
+            }
+        }
+    }
+    messages
+}
+
+/// Decorations and buildings of an IFO referencing objects past their ZSC,
+/// `None` for a ZSC that couldn't be read
+pub fn object_ids(ifo: &IFO, decorations: Option<&ZSC>, buildings: Option<&ZSC>) -> Vec<String> {
+    let kinds = [
+        ("decoration", &ifo.objects, decorations),
+        ("building", &ifo.buildings, buildings),
+    ];
+    let mut messages = Vec::new();
+    for (kind, objects, zsc) in kinds.iter() {
+        let count = match zsc {
+            Some(zsc) => zsc.objects.len(),
+            None => continue,
+        };
+        for (i, object) in objects.iter().enumerate() {
+            if object.object_id < 0 || object.object_id as usize >= count {
+                messages.push(format!(
+                    "{} {}: object {} is past the {} objects of the ZSC",
+                    kind, i, object.object_id, count
+                ));
+            }
+        }
+    }
+    messages
+}
+
+/// Tiles of a TIL referencing tiles past the tiles of the ZON, in a single
+/// message with the first of them since broken TILs are usually broken
+/// throughout
+pub fn tile_ids(til: &TIL, zon: &ZON) -> Vec<String> {
+    let count = zon.tiles.len();
+    let mut broken = til.tiles.iter().enumerate().flat_map(|(y, row)| {
+        row.iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.tile_id < 0 || tile.tile_id as usize >= count)
+            .map(move |(x, tile)| (x, y, tile.tile_id))
+    });
+    let (x, y, tile_id) = match broken.next() {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+    vec![format!(
+        "{} tiles are past the {} tiles of the ZON, the first is tile {} at {},{}",
+        broken.count() + 1,
+        count,
+        tile_id,
+        x,
+        y
+    )]
+}
+
+/// Channels of a ZMO animating bones past the bones of the ZMD
+pub fn bone_indices(zmo: &ZMO, zmd: &ZMD) -> Vec<String> {
+    let bones = zmd.bones.len() + zmd.dummy_bones.len();
+    zmo.channels
+        .iter()
+        .enumerate()
+        .filter(|(_, channel)| channel.index as usize >= bones)
+        .map(|(i, channel)| {
+            format!(
+                "channel {}: bone {} is past the {} bones of the ZMD",
+                i, channel.index, bones
+            )
+        })
+        .collect()
+}
+
+/// Map block files of a zone, the `<x>_<y>` files in the directory of its ZON
+fn zone_blocks<'a>(client: &'a Client, zon: &ClientFile) -> Vec<&'a ClientFile> {
+    let dir = client_path_key(directory(&zon.path));
+    client
+        .files()
+        .filter(|f| client_path_key(directory(&f.path)) == dir)
+        .filter(|f| {
+            let name = f.path.rsplit('/').next().unwrap_or_default();
+            let stem = name.split('.').next().unwrap_or_default();
+            match stem.split_once('_') {
+                Some((x, y)) => x.parse::<u32>().is_ok() && y.parse::<u32>().is_ok(),
+                None => false,
+            }
+        })
+        .collect()
+}
+
+/// Directory of a client path, empty for files at the root
+fn directory(path: &str) -> &str {
+    path.rfind('/').map(|i| &path[..i]).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use roselib::files::ifo::ObjectData;
+    use roselib::files::stl::StringTableKey;
+    use roselib::files::til::Tile;
+    use roselib::files::zmo::{Channel, ChannelType};
+    use roselib::files::zon::ZoneTile;
+    use roselib::files::zsc::{SceneMaterial, SceneObject, SceneObjectPart};
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_checks() {
+        let mut stb = STB::new();
+        stb.data = vec![
+            vec![String::from("1"), String::from("ITEM_1")],
+            vec![String::from("2"), String::from("ITEM_2")],
+            vec![String::from("3"), String::new()],
+        ];
+        let mut stl = STL::new();
+        stl.keys.push(StringTableKey {
+            id: 0,
+            name: String::from("ITEM_1"),
+        });
+        assert_eq!(
+            string_ids(&stb, &stl),
+            ["row 1: string ID `ITEM_2` isn't in the STL"]
+        );
+
+        let mut zsc = ZSC::new();
+        zsc.meshes.push(PathBuf::from("3DDATA\\DECO\\BOX.ZMS"));
+        zsc.materials.push(SceneMaterial {
+            path: PathBuf::from("3DDATA\\DECO\\BOX.DDS"),
+            ..Default::default()
+        });
+        zsc.objects.push(SceneObject {
+            parts: vec![SceneObjectPart {
+                mesh_id: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        assert_eq!(
+            scene_references(&zsc, |p| p.ends_with(".ZMS")),
+            [
+                "missing texture 3DDATA/DECO/BOX.DDS",
+                "object 0 part 0: mesh 1 is past the 1 meshes"
+            ]
+        );
+
+        let mut ifo = IFO::new();
+        ifo.objects.push(ObjectData::default());
+        ifo.buildings.push(ObjectData {
+            object_id: 1,
+            ..Default::default()
+        });
+        assert_eq!(
+            object_ids(&ifo, Some(&zsc), Some(&zsc)),
+            ["building 0: object 1 is past the 1 objects of the ZSC"]
+        );
+        assert!(object_ids(&ifo, None, None).is_empty());
+
+        let mut zon = ZON::new();
+        zon.tiles.push(ZoneTile::default());
+        let mut til = TIL::new();
+        til.tiles = vec![vec![
+            Tile::default(),
+            Tile {
+                tile_id: 3,
+                ..Default::default()
+            },
+        ]];
+        assert_eq!(
+            tile_ids(&til, &zon),
+            ["1 tiles are past the 1 tiles of the ZON, the first is tile 3 at 1,0"]
+        );
+
+        let mut zmo = ZMO::new();
+        let mut channel = Channel::from(ChannelType::Rotation);
+        channel.index = 2;
+        zmo.channels.push(Channel::from(ChannelType::Position));
+        zmo.channels.push(channel);
+        let mut zmd = ZMD::new();
+        zmd.bones.push(Default::default());
+        zmd.dummy_bones.push(Default::default());
+        assert_eq!(
+            bone_indices(&zmo, &zmd),
+            ["channel 1: bone 2 is past the 2 bones of the ZMD"]
+        );
+    }
+}
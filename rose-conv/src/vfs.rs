@@ -0,0 +1,146 @@
+//! Reading files from the virtual file systems of a ROSE client and from
+//! extracted client directories
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use failure::{bail, Error};
+
+use roselib::files::IDX;
+use roselib::io::RoseFile;
+
+/// Read every file in the file systems of the index at `idx_path` whose path
+/// matches `filter`. The `.vfs` files are expected next to the index.
+pub fn read_files<F>(idx_path: &Path, filter: F) -> Result<Vec<(PathBuf, Vec<u8>)>, Error>
+where
+    F: Fn(&Path) -> bool,
+{
+    let idx = IDX::from_path(idx_path)?;
+    let idx_dir = idx_path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut files = Vec::new();
+    for vfs in &idx.file_systems {
+        let vfs_path = idx_dir.join(&vfs.filename);
+        let mut f = match File::open(&vfs_path) {
+            Ok(f) => f,
+            Err(e) => bail!("Unable to open {}: {}", vfs_path.display(), e),
+        };
+
+        for file in &vfs.files {
+            if file.is_deleted || !filter(&file.filepath) {
+                continue;
+            }
+
+            let mut data = vec![0u8; file.size as usize];
+            f.seek(SeekFrom::Start(file.offset as u64))?;
+            f.read_exact(&mut data)?;
+            files.push((file.filepath.clone(), data));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Find a client file by its path, matching every component
+/// case-insensitively since the client paths don't match the case of the
+/// extracted files
+pub fn resolve_client_path(client_dir: &Path, path: &str) -> Option<PathBuf> {
+    let mut resolved = client_dir.to_path_buf();
+    for component in path.split(['\\', '/']).filter(|c| !c.is_empty()) {
+        let exact = resolved.join(component);
+        if exact.exists() {
+            resolved = exact;
+            continue;
+        }
+        resolved = fs::read_dir(&resolved)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .find(|p| {
+                p.file_name()
+                    .is_some_and(|name| name.to_string_lossy().eq_ignore_ascii_case(component))
+            })?;
+    }
+    Some(resolved)
+}
+
+/// Client path with forward slashes in lowercase, to compare paths that
+/// differ by case or separators
+pub fn client_path_key(path: &str) -> String {
+    path.replace('\\', "/").to_lowercase()
+}
+
+/// Client directory of a file in it, the closest directory with a `3DDATA`
+/// directory
+pub fn find_client_dir(path: &Path) -> Option<PathBuf> {
+    path.ancestors().skip(1).find_map(|dir| {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        resolve_client_path(dir, "3DDATA")
+            .filter(|p| p.is_dir())
+            .map(|_| dir.to_path_buf())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use roselib::files::idx::{VfsFileMetadata, VfsMetadata};
+
+    #[test]
+    fn test_read_files() {
+        let dir = std::env::temp_dir().join("rose-conv-test-vfs");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("data.vfs"), "aaabb").unwrap();
+
+        let mut idx = IDX::new();
+        let mut vfs = VfsMetadata::new();
+        vfs.filename = PathBuf::from("data.vfs");
+        for (path, offset, size) in &[("3DDATA/STB/A.STB", 0, 3), ("3DDATA/B.ZON", 3, 2)] {
+            let mut file = VfsFileMetadata::new();
+            file.filepath = PathBuf::from(path);
+            file.offset = *offset;
+            file.size = *size;
+            vfs.files.push(file);
+        }
+        idx.file_systems.push(vfs);
+        idx.write_to_path(&dir.join("data.idx")).unwrap();
+
+        let files = read_files(&dir.join("data.idx"), |p| {
+            p.extension().is_some_and(|e| e.eq_ignore_ascii_case("stb"))
+        })
+        .unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].1, b"aaa");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_client_paths() {
+        let dir = std::env::temp_dir().join("rose-conv-test-client");
+        fs::create_dir_all(dir.join("3Ddata/Junon")).unwrap();
+        fs::write(dir.join("3Ddata/Junon/Deco.zsc"), "").unwrap();
+
+        assert_eq!(
+            resolve_client_path(&dir, "3DDATA\\JUNON\\DECO.ZSC"),
+            Some(dir.join("3Ddata/Junon/Deco.zsc"))
+        );
+        assert_eq!(resolve_client_path(&dir, "3DDATA\\JUNON\\CNST.ZSC"), None);
+        assert_eq!(
+            client_path_key("3DDATA\\Junon\\Deco.ZSC"),
+            "3ddata/junon/deco.zsc"
+        );
+        assert_eq!(
+            find_client_dir(&dir.join("3Ddata/Junon/Deco.zsc")),
+            Some(dir.clone())
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
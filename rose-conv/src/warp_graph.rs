@@ -0,0 +1,227 @@
+//! Graph of the zones connected by warp gates
+//!
+//! The warp gates of an IFO reference a row of `WARP.STB` with the zone they
+//! lead to and the name of the event point of its ZON where players arrive.
+//! Zones are the rows of `LIST_ZONE.STB` with a ZON file.
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use roselib::files::zon::ZoneEventPoint;
+use roselib::files::{IFO, STB};
+use roselib::utils::Vector3;
+
+use crate::ifo_instances::WORLD_OFFSET;
+
+/// Columns of `LIST_ZONE.STB` and `WARP.STB`, counting the name column
+const ZONE_NAME_COLUMN: usize = 1;
+const ZONE_FILE_COLUMN: usize = 2;
+const WARP_ZONE_COLUMN: usize = 1;
+const WARP_EVENT_COLUMN: usize = 2;
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct WarpGraph {
+    pub zones: Vec<Zone>,
+    pub warps: Vec<Warp>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Zone {
+    /// Row of the zone in `LIST_ZONE.STB`
+    pub id: usize,
+    pub name: String,
+    /// Client path of the ZON file
+    pub zon: String,
+}
+
+/// Warp gate of a zone
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Warp {
+    pub from: usize,
+    /// `None` if the row of `WARP.STB` doesn't have a valid zone
+    pub to: Option<usize>,
+    /// Row of the warp in `WARP.STB`
+    pub warp_id: i16,
+    pub name: String,
+    /// World position of the warp gate in centimeters
+    pub position: Vector3<f32>,
+    /// Event point of the destination zone where players arrive
+    pub destination: String,
+    /// Position of the event point in the destination's ZON, `None` if it
+    /// isn't found
+    pub destination_position: Option<Vector3<f32>>,
+}
+
+impl WarpGraph {
+    /// Graph of the zones of `LIST_ZONE.STB` without warps
+    pub fn new(list_zone: &STB) -> WarpGraph {
+        let zones = list_zone
+            .data
+            .iter()
+            .enumerate()
+            .filter_map(|(id, row)| {
+                let zon = row.get(ZONE_FILE_COLUMN)?.trim();
+                if zon.is_empty() {
+                    return None;
+                }
+                let name = match row.get(ZONE_NAME_COLUMN).map(|n| n.trim()) {
+                    Some(name) if !name.is_empty() => name,
+                    _ => row[0].trim(),
+                };
+                Some(Zone {
+                    id,
+                    name: name.to_string(),
+                    zon: zon.to_string(),
+                })
+            })
+            .collect();
+        WarpGraph {
+            zones,
+            warps: Vec::new(),
+        }
+    }
+
+    /// Add the warp gates of an IFO of zone `zone_id`, with their
+    /// destinations in `WARP.STB`
+    pub fn add_warps(&mut self, zone_id: usize, ifo: &IFO, warp_list: &STB) {
+        for gate in &ifo.warps {
+            let row = usize::try_from(gate.warp_id)
+                .ok()
+                .and_then(|id| warp_list.data.get(id));
+            let column = |c: usize| {
+                row.and_then(|r| r.get(c))
+                    .map(|v| v.trim().to_string())
+                    .unwrap_or_default()
+            };
+            self.warps.push(Warp {
+                from: zone_id,
+                to: column(WARP_ZONE_COLUMN).parse().ok(),
+                warp_id: gate.warp_id,
+                name: column(0),
+                position: Vector3 {
+                    x: gate.position.x + WORLD_OFFSET,
+                    y: gate.position.y + WORLD_OFFSET,
+                    z: gate.position.z,
+                },
+                destination: column(WARP_EVENT_COLUMN),
+                destination_position: None,
+            });
+        }
+    }
+
+    /// Set the destination positions of the warps to zone `zone_id` from
+    /// the event points of its ZON
+    pub fn set_event_points(&mut self, zone_id: usize, event_points: &[ZoneEventPoint]) {
+        for warp in self.warps.iter_mut().filter(|w| w.to == Some(zone_id)) {
+            warp.destination_position = event_points
+                .iter()
+                .find(|p| p.name.trim().eq_ignore_ascii_case(&warp.destination))
+                .map(|p| p.position);
+        }
+    }
+
+    /// Graphviz DOT of the graph, with an edge per warp gate
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph warps {\n");
+        for zone in &self.zones {
+            let _ = writeln!(dot, "    {} [label=\"{}\"];", zone.id, escape(&zone.name));
+        }
+        for warp in &self.warps {
+            let to = match warp.to {
+                Some(to) => to.to_string(),
+                None => continue,
+            };
+            let _ = writeln!(
+                dot,
+                "    {} -> {} [label=\"{}\"];",
+                warp.from,
+                to,
+                escape(&warp.name)
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::ifo::ObjectData;
+    use roselib::io::RoseFile;
+
+    fn stb(rows: &[&[&str]]) -> STB {
+        let mut stb = STB::new();
+        for row in rows {
+            stb.data.push(row.iter().map(|c| c.to_string()).collect());
+        }
+        stb
+    }
+
+    #[test]
+    fn test_warp_graph() {
+        let list_zone = stb(&[
+            &["", "", ""],
+            &[
+                "JPT01",
+                "Canyon City of Zant",
+                "3DDATA\\MAPS\\JUNON\\JPT01\\JPT01.ZON",
+            ],
+            &["JPT02", "", "3DDATA\\MAPS\\JUNON\\JPT02\\JPT02.ZON"],
+        ]);
+        let warp_list = stb(&[&["", "", ""], &["Zant \"gate\"", "2", "start"]]);
+
+        let mut graph = WarpGraph::new(&list_zone);
+        assert_eq!(graph.zones.len(), 2);
+        assert_eq!(graph.zones[0].name, "Canyon City of Zant");
+        assert_eq!(graph.zones[1].name, "JPT02");
+
+        let mut ifo = IFO::new();
+        ifo.warps.push(ObjectData {
+            warp_id: 1,
+            ..Default::default()
+        });
+        ifo.warps.push(ObjectData {
+            warp_id: 5,
+            ..Default::default()
+        });
+        graph.add_warps(1, &ifo, &warp_list);
+        graph.set_event_points(
+            2,
+            &[ZoneEventPoint {
+                name: String::from("START"),
+                position: Vector3 {
+                    x: 1.0,
+                    y: 2.0,
+                    z: 3.0,
+                },
+            }],
+        );
+
+        assert_eq!(graph.warps.len(), 2);
+        let warp = &graph.warps[0];
+        assert_eq!((warp.from, warp.to), (1, Some(2)));
+        assert_eq!(warp.position.x, WORLD_OFFSET);
+        assert_eq!(warp.destination, "start");
+        assert_eq!(
+            warp.destination_position,
+            Some(Vector3 {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            })
+        );
+        assert_eq!(graph.warps[1].to, None);
+
+        assert_eq!(
+            graph.to_dot(),
+            "digraph warps {\n    1 [label=\"Canyon City of Zant\"];\n    2 [label=\"JPT02\"];\n    1 -> 2 [label=\"Zant \\\"gate\\\"\"];\n}\n"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Water surfaces of maps
+//!
+//! IFOs place water two ways: ocean patches, rectangles between two corners
+//! at the height of the first, and water grids splitting their map block into
+//! cells, each with water or not at its own height. Both become flat planes
+//! in world centimeters, the cells of a grid merged along rows where the
+//! water is level.
+use serde::Serialize;
+
+use roselib::files::ifo::WaterData;
+use roselib::files::IFO;
+
+use crate::ifo_instances::WORLD_OFFSET;
+use crate::terrain::BLOCK_SIZE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WaterKind {
+    Ocean,
+    Water,
+}
+
+/// Flat rectangle of water
+#[derive(Debug, PartialEq, Serialize)]
+pub struct WaterPlane {
+    pub kind: WaterKind,
+    /// Map block of the IFO
+    pub block: [i32; 2],
+    /// World position of the south west corner in centimeters
+    pub min: [f32; 2],
+    /// World position of the north east corner in centimeters
+    pub max: [f32; 2],
+    /// Height of the surface in centimeters
+    pub height: f32,
+}
+
+/// Water planes of an IFO
+pub fn water_planes(ifo: &IFO) -> Vec<WaterPlane> {
+    let block = [ifo.map_pos.x, ifo.map_pos.y];
+    let mut planes = Vec::new();
+    for patch in ifo.oceans.iter().flat_map(|ocean| &ocean.patches) {
+        let (start, end) = (&patch.start, &patch.end);
+        planes.push(WaterPlane {
+            kind: WaterKind::Ocean,
+            block,
+            min: [
+                start.x.min(end.x) + WORLD_OFFSET,
+                start.y.min(end.y) + WORLD_OFFSET,
+            ],
+            max: [
+                start.x.max(end.x) + WORLD_OFFSET,
+                start.y.max(end.y) + WORLD_OFFSET,
+            ],
+            height: start.z,
+        });
+    }
+    for water in &ifo.waters {
+        grid_planes(water, block, &mut planes);
+    }
+    planes
+}
+
+/// Planes of the runs of level water along the rows of a grid, from the
+/// north west corner of its block
+fn grid_planes(water: &WaterData, block: [i32; 2], planes: &mut Vec<WaterPlane>) {
+    let (cols, rows) = (water.width as usize, water.height as usize);
+    if cols == 0 || rows == 0 || water.has_water.len().min(water.heights.len()) < cols * rows {
+        return;
+    }
+    let cell = [BLOCK_SIZE / cols as f32, BLOCK_SIZE / rows as f32];
+    let x0 = block[0] as f32 * BLOCK_SIZE;
+    let y0 = (65.0 - block[1] as f32) * BLOCK_SIZE;
+
+    for row in 0..rows {
+        let mut col = 0;
+        while col < cols {
+            let i = row * cols + col;
+            if !water.has_water[i] {
+                col += 1;
+                continue;
+            }
+            let height = water.heights[i];
+            let start = col;
+            while col < cols
+                && water.has_water[row * cols + col]
+                && water.heights[row * cols + col] == height
+            {
+                col += 1;
+            }
+            planes.push(WaterPlane {
+                kind: WaterKind::Water,
+                block,
+                min: [x0 + start as f32 * cell[0], y0 - (row + 1) as f32 * cell[1]],
+                max: [x0 + col as f32 * cell[0], y0 - row as f32 * cell[1]],
+                height,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::ifo::{Ocean, OceanPatch};
+    use roselib::io::RoseFile;
+    use roselib::utils::Vector3;
+
+    #[test]
+    fn test_water_planes() {
+        let mut ifo = IFO::new();
+        ifo.map_pos.x = 32;
+        ifo.map_pos.y = 33;
+        ifo.oceans.push(Ocean {
+            size: 1000.0,
+            patches: vec![OceanPatch {
+                start: Vector3 {
+                    x: 1000.0,
+                    y: -2000.0,
+                    z: 500.0,
+                },
+                end: Vector3 {
+                    x: -1000.0,
+                    y: 0.0,
+                    z: 500.0,
+                },
+            }],
+        });
+        // Water in the first row but for the third cell, higher in the last
+        ifo.waters.push(WaterData {
+            width: 4,
+            height: 2,
+            has_water: vec![true, true, false, true, false, false, false, false],
+            heights: vec![100.0, 100.0, 0.0, 200.0, 0.0, 0.0, 0.0, 0.0],
+        });
+
+        let planes = water_planes(&ifo);
+        assert_eq!(planes.len(), 3);
+        assert_eq!(
+            planes[0],
+            WaterPlane {
+                kind: WaterKind::Ocean,
+                block: [32, 33],
+                min: [519_000.0, 518_000.0],
+                max: [521_000.0, 520_000.0],
+                height: 500.0,
+            }
+        );
+        assert_eq!(planes[1].kind, WaterKind::Water);
+        assert_eq!(planes[1].min, [512_000.0, 504_000.0]);
+        assert_eq!(planes[1].max, [520_000.0, 512_000.0]);
+        assert_eq!(planes[1].height, 100.0);
+        assert_eq!(planes[2].min, [524_000.0, 504_000.0]);
+        assert_eq!(planes[2].height, 200.0);
+    }
+}
@@ -0,0 +1,322 @@
+//! Lossless WebP encoding
+//!
+//! The image crate only decodes WebP, so images are encoded here as lossless
+//! (VP8L) WebP. Pixels are stored as literals with a prefix code per channel,
+//! without transforms or backward references, which is compact enough for
+//! icons and keeps them pixel-exact.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use failure::{bail, Error};
+use image::RgbaImage;
+
+const SIGNATURE: u8 = 0x2F;
+const MAX_SIZE: u32 = 1 << 14;
+
+/// Green, red, blue, alpha and distance alphabets without a color cache
+const ALPHABET_SIZES: [usize; 5] = [256 + 24, 256, 256, 256, 40];
+
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// Encode an image as a lossless WebP file
+pub fn encode(img: &RgbaImage) -> Result<Vec<u8>, Error> {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE {
+        bail!(
+            "WebP images must be between 1x1 and {}x{}, not {}x{}",
+            MAX_SIZE,
+            MAX_SIZE,
+            width,
+            height
+        );
+    }
+
+    let mut writer = BitWriter::default();
+    writer.write(SIGNATURE as u32, 8);
+    writer.write(width - 1, 14);
+    writer.write(height - 1, 14);
+    let alpha_used = img.pixels().any(|p| p[3] != 255);
+    writer.write(alpha_used as u32, 1);
+    writer.write(0, 3); // Version
+    writer.write(0, 1); // No transforms
+    writer.write(0, 1); // No color cache
+    writer.write(0, 1); // No meta prefix codes
+
+    // Channels in the order of the alphabets: green, red, blue and alpha
+    let channels = [1, 0, 2, 3];
+    let mut counts: Vec<Vec<u32>> = ALPHABET_SIZES.iter().map(|&n| vec![0; n]).collect();
+    for pixel in img.pixels() {
+        for (alphabet, &channel) in channels.iter().enumerate() {
+            counts[alphabet][pixel[channel] as usize] += 1;
+        }
+    }
+
+    let codes: Vec<PrefixCode> = counts
+        .iter()
+        .map(|counts| PrefixCode::new(counts, MAX_CODE_LENGTH))
+        .collect();
+    for code in &codes {
+        code.write_header(&mut writer);
+    }
+
+    for pixel in img.pixels() {
+        for (code, &channel) in codes.iter().zip(&channels) {
+            code.write_symbol(&mut writer, pixel[channel] as usize);
+        }
+    }
+
+    let data = writer.finish();
+    let padding = data.len() % 2;
+    let mut webp = Vec::with_capacity(data.len() + 21);
+    webp.extend_from_slice(b"RIFF");
+    webp.extend_from_slice(&(12 + data.len() as u32 + padding as u32).to_le_bytes());
+    webp.extend_from_slice(b"WEBPVP8L");
+    webp.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    webp.extend_from_slice(&data);
+    webp.resize(webp.len() + padding, 0);
+    Ok(webp)
+}
+
+/// Bits written least significant bit first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    used: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.used;
+        self.used += count;
+        while self.used >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.used -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.used > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Canonical prefix code of an alphabet
+struct PrefixCode {
+    lengths: Vec<u8>,
+    /// Codes with their bits reversed, as they are read bit by bit
+    codes: Vec<u16>,
+    /// Symbols with a code
+    symbols: Vec<usize>,
+}
+
+impl PrefixCode {
+    fn new(counts: &[u32], max_length: u8) -> PrefixCode {
+        let lengths = code_lengths(counts, max_length);
+        let codes = canonical_codes(&lengths);
+        let symbols = (0..lengths.len()).filter(|&s| lengths[s] > 0).collect();
+        PrefixCode {
+            lengths,
+            codes,
+            symbols,
+        }
+    }
+
+    fn write_symbol(&self, writer: &mut BitWriter, symbol: usize) {
+        // A code of a single symbol takes no bits
+        if self.symbols.len() > 1 {
+            writer.write(self.codes[symbol] as u32, self.lengths[symbol] as u32);
+        }
+    }
+
+    fn write_header(&self, writer: &mut BitWriter) {
+        let symbols = &self.symbols;
+        if symbols.len() <= 2 && symbols.iter().all(|&s| s < 256) {
+            // Simple code of one or two 8-bit symbols
+            let first = symbols.first().copied().unwrap_or(0);
+            writer.write(1, 1);
+            writer.write(symbols.len().saturating_sub(1) as u32, 1);
+            if first < 2 {
+                writer.write(0, 1);
+                writer.write(first as u32, 1);
+            } else {
+                writer.write(1, 1);
+                writer.write(first as u32, 8);
+            }
+            if let Some(&second) = symbols.get(1) {
+                writer.write(second as u32, 8);
+            }
+            return;
+        }
+
+        // Code lengths with runs of zeros, as (symbol, extra bits, value)
+        let mut tokens = Vec::new();
+        let mut idx = 0;
+        while idx < self.lengths.len() {
+            let length = self.lengths[idx];
+            let run = self.lengths[idx..]
+                .iter()
+                .take_while(|&&l| l == length)
+                .count();
+            if length == 0 && run >= 11 {
+                let run = run.min(138);
+                tokens.push((18, 7, run - 11));
+                idx += run;
+            } else if length == 0 && run >= 3 {
+                let run = run.min(10);
+                tokens.push((17, 3, run - 3));
+                idx += run;
+            } else {
+                tokens.push((length as usize, 0, 0));
+                idx += 1;
+            }
+        }
+
+        let mut counts = [0u32; 19];
+        for &(symbol, _, _) in &tokens {
+            counts[symbol] += 1;
+        }
+        let length_code = PrefixCode::new(&counts, MAX_CODE_LENGTH_CODE_LENGTH);
+        let count = CODE_LENGTH_ORDER
+            .iter()
+            .rposition(|&s| length_code.lengths[s] > 0)
+            .map_or(0, |idx| idx + 1)
+            .max(4);
+
+        writer.write(0, 1);
+        writer.write(count as u32 - 4, 4);
+        for &symbol in &CODE_LENGTH_ORDER[..count] {
+            writer.write(length_code.lengths[symbol] as u32, 3);
+        }
+        writer.write(0, 1); // Lengths of the whole alphabet
+        for (symbol, extra_bits, extra) in tokens {
+            length_code.write_symbol(writer, symbol);
+            writer.write(extra as u32, extra_bits);
+        }
+    }
+}
+
+/// Huffman code lengths of the symbols, at most `max_length` bits. Counts are
+/// halved until the code fits.
+fn code_lengths(counts: &[u32], max_length: u8) -> Vec<u8> {
+    let mut counts = counts.to_vec();
+    loop {
+        let mut lengths = vec![0u8; counts.len()];
+        let mut heap: BinaryHeap<Reverse<(u64, usize)>> = BinaryHeap::new();
+        // Nodes are symbols followed by the internal nodes, with their parent
+        let mut parents: Vec<Option<usize>> = vec![None; counts.len()];
+        for (symbol, &count) in counts.iter().enumerate() {
+            if count > 0 {
+                heap.push(Reverse((count as u64, symbol)));
+            }
+        }
+        if heap.len() == 1 {
+            let Reverse((_, symbol)) = heap.pop().unwrap_or_default();
+            lengths[symbol] = 1;
+            return lengths;
+        }
+
+        while heap.len() > 1 {
+            let Reverse((a_count, a)) = heap.pop().unwrap_or_default();
+            let Reverse((b_count, b)) = heap.pop().unwrap_or_default();
+            let node = parents.len();
+            parents.push(None);
+            parents[a] = Some(node);
+            parents[b] = Some(node);
+            heap.push(Reverse((a_count + b_count, node)));
+        }
+
+        let mut fits = true;
+        for symbol in 0..counts.len() {
+            if counts[symbol] == 0 {
+                continue;
+            }
+            let mut depth = 0;
+            let mut node = symbol;
+            while let Some(parent) = parents[node] {
+                depth += 1;
+                node = parent;
+            }
+            fits &= depth <= max_length as usize;
+            lengths[symbol] = depth as u8;
+        }
+        if fits {
+            return lengths;
+        }
+
+        for count in counts.iter_mut().filter(|c| **c > 0) {
+            *count = (*count / 2).max(1);
+        }
+    }
+}
+
+/// Canonical codes of the code lengths, with their bits reversed
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut length_counts = [0u16; 16];
+    for &length in lengths.iter().filter(|&&l| l > 0) {
+        length_counts[length as usize] += 1;
+    }
+
+    let mut next_code = [0u16; 16];
+    let mut code = 0;
+    for length in 1..16 {
+        code = (code + length_counts[length - 1]) << 1;
+        next_code[length] = code;
+    }
+
+    lengths
+        .iter()
+        .map(|&length| {
+            if length == 0 {
+                return 0;
+            }
+            let code = next_code[length as usize];
+            next_code[length as usize] += 1;
+            code.reverse_bits() >> (16 - length)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_lengths() {
+        assert_eq!(code_lengths(&[0, 5, 0], 15), vec![0, 1, 0]);
+        assert_eq!(code_lengths(&[1, 1, 2], 15), vec![2, 2, 1]);
+
+        // Fibonacci counts need a code longer than the limit
+        let mut counts = vec![1, 1];
+        for i in 2..20 {
+            counts.push(counts[i - 1] + counts[i - 2]);
+        }
+        let lengths = code_lengths(&counts, 7);
+        assert!(lengths.iter().all(|&l| l > 0 && l <= 7));
+        let kraft: f64 = lengths.iter().map(|&l| 0.5f64.powi(l as i32)).sum();
+        assert_eq!(kraft, 1.0);
+
+        assert_eq!(canonical_codes(&[2, 2, 1]), vec![0b01, 0b11, 0b0]);
+    }
+
+    #[test]
+    fn test_encode() {
+        let img = RgbaImage::from_fn(3, 2, |x, y| image::Rgba([x as u8, y as u8, 7, 255]));
+        let webp = encode(&img).unwrap();
+        assert_eq!(&webp[0..4], b"RIFF");
+        assert_eq!(&webp[8..16], b"WEBPVP8L");
+        assert_eq!(webp.len() % 2, 0);
+        assert_eq!(webp[20], SIGNATURE);
+        // 14 bits of width - 1 and height - 1
+        assert_eq!(webp[21], 2);
+        assert_eq!(webp[22] >> 6 | (webp[23] & 0xF) << 2, 1);
+        assert!(encode(&RgbaImage::new(MAX_SIZE + 1, 1)).is_err());
+    }
+}
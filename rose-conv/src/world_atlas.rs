@@ -0,0 +1,335 @@
+//! World-scale images of every zone of a client
+//!
+//! `LIST_ZONE.STB` doesn't place zones relative to each other and every zone
+//! has its own grid of map blocks, so the atlas lays the zones out in rows:
+//! a row for each planet of `LIST_ZONE.STB`, with its zones in the order of
+//! the table, wrapped after a number of zones. Each zone keeps the layout of
+//! its blocks, from its first to its last block.
+use image::{Rgba, RgbaImage};
+use serde::Serialize;
+
+use roselib::files::stl::{StringTableLanguage, StringTableRow};
+use roselib::files::{HIM, STB, STL};
+
+use crate::bitmap_font::{self, GLYPH_HEIGHT};
+
+/// Columns of `LIST_ZONE.STB`, counting the name column
+const ZONE_NAME_COLUMN: usize = 1;
+const ZONE_FILE_COLUMN: usize = 2;
+const PLANET_COLUMN: usize = 20;
+const NAME_ID_COLUMN: usize = 27;
+
+/// Zone of `LIST_ZONE.STB` and its place in the atlas
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AtlasZone {
+    /// Row of the zone in `LIST_ZONE.STB`
+    pub id: usize,
+    pub name: String,
+    /// Key of the zone's name in `LIST_ZONE_S.STL`
+    pub name_id: String,
+    /// Client path of the ZON file
+    pub zon: String,
+    pub planet: u32,
+    /// Block coordinates of the zone's first and last blocks
+    pub min_block: [u32; 2],
+    pub max_block: [u32; 2],
+    /// Position and size of the zone in the atlas in pixels
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct WorldAtlas {
+    pub width: u32,
+    pub height: u32,
+    /// Pixels of a map block on each axis
+    pub block_size: u32,
+    pub zones: Vec<AtlasZone>,
+}
+
+impl AtlasZone {
+    /// Set the first and last blocks of the zone from the coordinates of its
+    /// blocks
+    pub fn set_blocks(&mut self, blocks: &[(u32, u32)]) {
+        let xs = blocks.iter().map(|b| b.0);
+        let ys = blocks.iter().map(|b| b.1);
+        self.min_block = [xs.clone().min().unwrap_or(0), ys.clone().min().unwrap_or(0)];
+        self.max_block = [xs.max().unwrap_or(0), ys.max().unwrap_or(0)];
+    }
+
+    /// Position in the atlas of the top left corner of block `(x, y)`
+    pub fn block_origin(&self, x: u32, y: u32, block_size: u32) -> (u32, u32) {
+        (
+            self.x + (x - self.min_block[0]) * block_size,
+            self.y + (y - self.min_block[1]) * block_size,
+        )
+    }
+}
+
+/// Zones of `LIST_ZONE.STB` with a ZON file, without blocks
+pub fn list_zones(list_zone: &STB) -> Vec<AtlasZone> {
+    list_zone
+        .data
+        .iter()
+        .enumerate()
+        .filter_map(|(id, row)| {
+            let column = |c: usize| row.get(c).map(|v| v.trim()).unwrap_or_default();
+            let zon = column(ZONE_FILE_COLUMN);
+            if zon.is_empty() {
+                return None;
+            }
+            let name = match column(ZONE_NAME_COLUMN) {
+                "" => column(0),
+                name => name,
+            };
+            Some(AtlasZone {
+                id,
+                name: name.to_string(),
+                name_id: column(NAME_ID_COLUMN).to_string(),
+                zon: zon.to_string(),
+                planet: column(PLANET_COLUMN).parse().unwrap_or(0),
+                min_block: [0, 0],
+                max_block: [0, 0],
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            })
+        })
+        .collect()
+}
+
+impl WorldAtlas {
+    /// Lay out the zones in rows by planet with at most `columns` zones per
+    /// row, `block_size` pixels per map block and half a block around zones
+    pub fn new(mut zones: Vec<AtlasZone>, block_size: u32, columns: usize) -> WorldAtlas {
+        zones.sort_by_key(|z| z.planet);
+        let margin = block_size / 2;
+
+        let (mut x, mut y) = (margin, margin);
+        let (mut width, mut row_height, mut row_zones) = (0, 0, 0);
+        let mut planet = None;
+        for zone in zones.iter_mut() {
+            zone.width = (zone.max_block[0] - zone.min_block[0] + 1) * block_size;
+            zone.height = (zone.max_block[1] - zone.min_block[1] + 1) * block_size;
+
+            if row_zones > 0 && (row_zones >= columns || planet != Some(zone.planet)) {
+                x = margin;
+                y += row_height + margin;
+                row_height = 0;
+                row_zones = 0;
+            }
+            zone.x = x;
+            zone.y = y;
+            x += zone.width + margin;
+            width = width.max(x);
+            row_height = row_height.max(zone.height);
+            row_zones += 1;
+            planet = Some(zone.planet);
+        }
+
+        WorldAtlas {
+            width,
+            height: if zones.is_empty() {
+                0
+            } else {
+                y + row_height + margin
+            },
+            block_size,
+            zones,
+        }
+    }
+
+    /// Name the zones by the `language` text of their names in
+    /// `LIST_ZONE_S.STL`, keeping the names of zones without one
+    pub fn set_names(&mut self, stl: &STL, language: StringTableLanguage) {
+        let table = match stl.language_tables.iter().find(|t| t.language == language) {
+            Some(table) => table,
+            None => return,
+        };
+        for zone in self.zones.iter_mut() {
+            let text = stl
+                .keys
+                .iter()
+                .zip(&table.rows)
+                .find(|(key, _)| !zone.name_id.is_empty() && key.name == zone.name_id)
+                .map(|(_, row)| match row {
+                    StringTableRow::NormalRow(data) => data.text.trim(),
+                    StringTableRow::ItemRow(data) => data.text.trim(),
+                    StringTableRow::QuestRow(data) => data.text.trim(),
+                });
+            if let Some(text) = text.filter(|t| !t.is_empty()) {
+                zone.name = text.to_string();
+            }
+        }
+    }
+
+    /// Draw the name of every zone over its top left corner, white on a dark
+    /// background
+    pub fn draw_labels(&self, img: &mut RgbaImage) {
+        let scale = (self.block_size / 32).max(1);
+        let padding = scale * 2;
+        for zone in &self.zones {
+            let width = bitmap_font::text_width(&zone.name, scale) + padding * 2;
+            let height = GLYPH_HEIGHT * scale + padding * 2;
+            for y in zone.y..(zone.y + height).min(img.height()) {
+                for x in zone.x..(zone.x + width).min(img.width()) {
+                    let pixel = img.get_pixel_mut(x, y);
+                    for c in 0..3 {
+                        pixel[c] /= 3;
+                    }
+                    pixel[3] = 255;
+                }
+            }
+            bitmap_font::draw_text(
+                img,
+                zone.x + padding,
+                zone.y + padding,
+                &zone.name,
+                scale,
+                Rgba([255, 255, 255, 255]),
+            );
+        }
+    }
+}
+
+/// Draw the heights of a HIM as grays from black at `min_height` to white at
+/// `max_height`, with its top left corner at `(x, y)` and oriented like the
+/// heightmaps of `map`
+pub fn draw_heights(
+    img: &mut RgbaImage,
+    x: u32,
+    y: u32,
+    block_size: u32,
+    him: &HIM,
+    min_height: f32,
+    max_height: f32,
+) {
+    let max = (him.width.min(him.length) - 1).max(0) as usize;
+    let range = (max_height - min_height).max(f32::EPSILON);
+    for py in 0..block_size {
+        for px in 0..block_size {
+            let row = ((py as f32 + 0.5) * max as f32 / block_size as f32) as usize;
+            let col = ((px as f32 + 0.5) * max as f32 / block_size as f32) as usize;
+            let height = him.height(row.min(max), col.min(max));
+            let gray = (255.0 * (height - min_height) / range)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+            img.put_pixel(x + px, y + py, Rgba([gray, gray, gray, 255]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::stl::{NormalRowData, StringTableKey, StringTableLanguageTable};
+    use roselib::io::RoseFile;
+
+    fn list_zone() -> STB {
+        let mut list_zone = STB::new();
+        list_zone.data.push(vec![String::new(); 28]);
+        for (name, zon, planet, name_id) in &[
+            (
+                "JPT01",
+                "3DDATA\\MAPS\\JUNON\\JPT01\\JPT01.ZON",
+                "1",
+                "ZONE_JPT01",
+            ),
+            ("JDT01", "3DDATA\\MAPS\\JUNON\\JDT01\\JDT01.ZON", "1", ""),
+            ("LUNAR", "3DDATA\\MAPS\\LUNA\\LMT01\\LMT01.ZON", "2", ""),
+            ("JG01", "3DDATA\\MAPS\\JUNON\\JG01\\JG01.ZON", "1", ""),
+        ] {
+            let mut row = vec![String::new(); 28];
+            row[0] = name.to_string();
+            row[ZONE_FILE_COLUMN] = zon.to_string();
+            row[PLANET_COLUMN] = planet.to_string();
+            row[NAME_ID_COLUMN] = name_id.to_string();
+            list_zone.data.push(row);
+        }
+        list_zone
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut zones = list_zones(&list_zone());
+        assert_eq!(zones.len(), 4);
+        assert_eq!(zones[0].id, 1);
+        zones[0].set_blocks(&[(31, 32), (33, 31)]);
+        zones[1].set_blocks(&[(30, 30)]);
+        zones[2].set_blocks(&[(32, 32), (32, 33)]);
+        zones[3].set_blocks(&[(31, 31)]);
+        assert_eq!(
+            (zones[0].min_block, zones[0].max_block),
+            ([31, 31], [33, 32])
+        );
+
+        let atlas = WorldAtlas::new(zones, 10, 2);
+        let placed: Vec<(&str, u32, u32, u32, u32)> = atlas
+            .zones
+            .iter()
+            .map(|z| (z.name.as_str(), z.x, z.y, z.width, z.height))
+            .collect();
+        assert_eq!(
+            placed,
+            vec![
+                ("JPT01", 5, 5, 30, 20),
+                ("JDT01", 40, 5, 10, 10),
+                ("JG01", 5, 30, 10, 10),
+                ("LUNAR", 5, 45, 10, 20),
+            ]
+        );
+        assert_eq!((atlas.width, atlas.height), (55, 70));
+        assert_eq!(atlas.zones[0].block_origin(33, 32, 10), (25, 15));
+    }
+
+    #[test]
+    fn test_names_and_labels() {
+        let mut zones = list_zones(&list_zone());
+        zones.truncate(1);
+        let mut atlas = WorldAtlas::new(zones, 64, 1);
+
+        let mut stl = STL::new();
+        stl.keys.push(StringTableKey {
+            id: 0,
+            name: String::from("ZONE_JPT01"),
+        });
+        stl.language_tables.push(StringTableLanguageTable {
+            language: StringTableLanguage::English,
+            rows: vec![StringTableRow::NormalRow(NormalRowData {
+                text: String::from("Canyon City of Zant"),
+            })],
+        });
+        atlas.set_names(&stl, StringTableLanguage::Korean);
+        assert_eq!(atlas.zones[0].name, "JPT01");
+        atlas.set_names(&stl, StringTableLanguage::English);
+        assert_eq!(atlas.zones[0].name, "Canyon City of Zant");
+
+        let mut img = RgbaImage::from_pixel(atlas.width, atlas.height, Rgba([90, 90, 90, 255]));
+        atlas.draw_labels(&mut img);
+        // Background, then the stem of the C
+        assert_eq!(img.get_pixel(33, 33), &Rgba([30, 30, 30, 255]));
+        assert_eq!(img.get_pixel(36, 40), &Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_draw_heights() {
+        let mut him = HIM::new();
+        him.width = 65;
+        him.length = 65;
+        // Rising towards the bottom of the image, like the heightmaps of `map`
+        for _ in 0..65 {
+            for row in 0..65 {
+                him.heights.push(row as f32 * 10.0);
+            }
+        }
+        let mut img = RgbaImage::new(8, 8);
+        draw_heights(&mut img, 0, 0, 8, &him, 0.0, 640.0);
+        assert!(img.get_pixel(0, 0)[0] < 20);
+        assert!(img.get_pixel(0, 7)[0] > 230);
+        assert_eq!(img.get_pixel(0, 3), img.get_pixel(7, 3));
+    }
+}
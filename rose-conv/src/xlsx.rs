@@ -0,0 +1,851 @@
+//! XLSX workbooks of STB and STL files
+//!
+//! Workbooks are written with one worksheet per table. Cells that hold a
+//! number in its shortest form, e.g. `5` or `1.5` but not `05` or `1.50`, are
+//! written as numbers so they can be used in formulas. Everything else is
+//! written as text, so reading the workbook back returns the same values.
+//!
+//! STB files use the same rows as their CSV serialization in a single sheet.
+//! STL files have a sheet per language with the key ID and name in the first
+//! two columns, followed by the text columns of the STL format.
+use std::collections::{HashMap, HashSet};
+
+use failure::{bail, format_err, Error};
+
+use roselib::files::stl::*;
+use roselib::files::STL;
+use roselib::io::RoseFile;
+
+use crate::csv_dialect::CsvDialect;
+use crate::zip::{self, ZipEntry};
+
+/// Maximum length of a sheet name in Excel
+const MAX_SHEET_NAME_LEN: usize = 31;
+
+const MAIN_NS: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+const RELATIONSHIPS_NS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+const OFFICE_DOCUMENT_REL: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument";
+const WORKSHEET_REL: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet";
+const SHARED_STRINGS_REL: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/sharedStrings";
+
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sheet {
+    pub name: String,
+    pub rows: Vec<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Workbook {
+    pub sheets: Vec<Sheet>,
+}
+
+impl Sheet {
+    /// Sheet with the records of a CSV file
+    pub fn from_csv(name: &str, s: &str, dialect: &CsvDialect) -> Result<Sheet, Error> {
+        let mut reader = dialect
+            .reader_builder()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(s.as_bytes());
+
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            rows.push(record?.iter().map(String::from).collect());
+        }
+
+        Ok(Sheet {
+            name: name.to_string(),
+            rows,
+        })
+    }
+
+    /// Write the rows as CSV, e.g. to parse them as an STB
+    pub fn to_csv(&self, dialect: &CsvDialect) -> Result<String, Error> {
+        let mut writer = dialect
+            .writer_builder()
+            .flexible(true)
+            .from_writer(Vec::new());
+        for row in &self.rows {
+            writer.write_record(row)?;
+        }
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+impl Workbook {
+    /// Workbook with a sheet per language of an STL
+    pub fn from_stl(stl: &STL) -> Result<Workbook, Error> {
+        if stl.language_tables.is_empty() {
+            bail!("STL files without languages can't be written to XLSX");
+        }
+
+        let mut headers = vec!["Key ID", "Key Name", "Text"];
+        if stl.format == StringTableType::Item || stl.format == StringTableType::Quest {
+            headers.push("Description");
+        }
+        if stl.format == StringTableType::Quest {
+            headers.push("Start Message");
+            headers.push("End Message");
+        }
+
+        let mut workbook = Workbook::default();
+        for table in &stl.language_tables {
+            let mut rows = vec![headers.iter().map(|s| s.to_string()).collect()];
+            for (key, row) in stl.keys.iter().zip(&table.rows) {
+                let mut values = vec![key.id.to_string(), key.name.clone()];
+                match row {
+                    StringTableRow::NormalRow(data) => values.push(data.text.clone()),
+                    StringTableRow::ItemRow(data) => {
+                        values.push(data.text.clone());
+                        values.push(data.description.clone());
+                    }
+                    StringTableRow::QuestRow(data) => {
+                        values.push(data.text.clone());
+                        values.push(data.description.clone());
+                        values.push(data.start_message.clone());
+                        values.push(data.end_message.clone());
+                    }
+                }
+                rows.push(values);
+            }
+
+            workbook.sheets.push(Sheet {
+                name: table.language.to_string(),
+                rows,
+            });
+        }
+        Ok(workbook)
+    }
+
+    /// Read an STL from a workbook with a sheet per language. The STL format
+    /// is detected from the number of columns and the keys are read from the
+    /// first sheet.
+    pub fn to_stl(&self) -> Result<STL, Error> {
+        let mut stl = STL::new();
+
+        let first = match self.sheets.first() {
+            Some(sheet) => sheet,
+            None => bail!("Workbook has no sheets"),
+        };
+        let col_count = first.rows.first().map(Vec::len).unwrap_or_default();
+        stl.format = match col_count {
+            3 => StringTableType::Normal,
+            4 => StringTableType::Item,
+            6 => StringTableType::Quest,
+            _ => bail!(
+                "Invalid number of columns in sheet {}, expected 3, 4 or 6, found {}",
+                first.name,
+                col_count
+            ),
+        };
+
+        for row in first.rows.iter().skip(1) {
+            stl.keys.push(StringTableKey {
+                id: cell(row, 0).parse().map_err(|_| {
+                    format_err!("Invalid key ID in sheet {}: {}", first.name, cell(row, 0))
+                })?,
+                name: cell(row, 1).to_string(),
+            });
+        }
+
+        for (language_idx, sheet) in self.sheets.iter().enumerate() {
+            if sheet.rows.len() != first.rows.len() {
+                bail!(
+                    "Sheet {} has {} rows, expected {}",
+                    sheet.name,
+                    sheet.rows.len(),
+                    first.rows.len()
+                );
+            }
+
+            let rows = sheet.rows.iter().skip(1).map(|row| match stl.format {
+                StringTableType::Normal => StringTableRow::NormalRow(NormalRowData {
+                    text: cell(row, 2).into(),
+                }),
+                StringTableType::Item => StringTableRow::ItemRow(ItemRowData {
+                    text: cell(row, 2).into(),
+                    description: cell(row, 3).into(),
+                }),
+                StringTableType::Quest => StringTableRow::QuestRow(QuestRowData {
+                    text: cell(row, 2).into(),
+                    description: cell(row, 3).into(),
+                    start_message: cell(row, 4).into(),
+                    end_message: cell(row, 5).into(),
+                }),
+            });
+
+            stl.language_tables.push(StringTableLanguageTable {
+                language: StringTableLanguage::from(language_idx as u32),
+                rows: rows.collect(),
+            });
+        }
+
+        Ok(stl)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        if self.sheets.is_empty() {
+            bail!("Workbooks must have at least one sheet");
+        }
+
+        let mut content_types = format!(
+            "{}<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+             <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+             <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+             <Override PartName=\"/xl/workbook.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml\"/>",
+            XML_DECLARATION
+        );
+        let mut workbook = format!(
+            "{}<workbook xmlns=\"{}\" xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\"><sheets>",
+            XML_DECLARATION, MAIN_NS
+        );
+        let mut workbook_rels = format!(
+            "{}<Relationships xmlns=\"{}\">",
+            XML_DECLARATION, RELATIONSHIPS_NS
+        );
+        let mut entries = Vec::new();
+
+        let mut used_names = HashSet::new();
+        for (idx, sheet) in self.sheets.iter().enumerate() {
+            let id = idx + 1;
+            content_types.push_str(&format!(
+                "<Override PartName=\"/xl/worksheets/sheet{}.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>",
+                id
+            ));
+            workbook.push_str(&format!(
+                "<sheet name=\"{}\" sheetId=\"{}\" r:id=\"rId{}\"/>",
+                escape(&unique_sheet_name(&sheet.name, &mut used_names)),
+                id,
+                id
+            ));
+            workbook_rels.push_str(&format!(
+                "<Relationship Id=\"rId{}\" Type=\"{}\" Target=\"worksheets/sheet{}.xml\"/>",
+                id, WORKSHEET_REL, id
+            ));
+            entries.push(ZipEntry {
+                name: format!("xl/worksheets/sheet{}.xml", id),
+                data: worksheet_xml(sheet).into_bytes(),
+            });
+        }
+
+        content_types.push_str("</Types>");
+        workbook.push_str("</sheets></workbook>");
+        workbook_rels.push_str("</Relationships>");
+        let rels = format!(
+            "{}<Relationships xmlns=\"{}\"><Relationship Id=\"rId1\" Type=\"{}\" Target=\"xl/workbook.xml\"/></Relationships>",
+            XML_DECLARATION, RELATIONSHIPS_NS, OFFICE_DOCUMENT_REL
+        );
+
+        let mut parts = vec![
+            ZipEntry {
+                name: "[Content_Types].xml".into(),
+                data: content_types.into_bytes(),
+            },
+            ZipEntry {
+                name: "_rels/.rels".into(),
+                data: rels.into_bytes(),
+            },
+            ZipEntry {
+                name: "xl/workbook.xml".into(),
+                data: workbook.into_bytes(),
+            },
+            ZipEntry {
+                name: "xl/_rels/workbook.xml.rels".into(),
+                data: workbook_rels.into_bytes(),
+            },
+        ];
+        parts.append(&mut entries);
+        zip::write(&parts)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Workbook, Error> {
+        let parts: HashMap<String, Vec<u8>> = zip::read(data)?
+            .into_iter()
+            .map(|entry| (entry.name, entry.data))
+            .collect();
+        let part = |name: &str| -> Result<String, Error> {
+            match parts.get(name) {
+                Some(data) => Ok(String::from_utf8_lossy(data).into_owned()),
+                None => bail!("Invalid XLSX file, missing {}", name),
+            }
+        };
+
+        let workbook_path = relationships(&part("_rels/.rels")?, "")?
+            .into_iter()
+            .find(|(_, rel_type, _)| rel_type == OFFICE_DOCUMENT_REL)
+            .map(|(_, _, target)| target)
+            .unwrap_or_else(|| String::from("xl/workbook.xml"));
+        let (workbook_dir, workbook_file) = match workbook_path.rfind('/') {
+            Some(idx) => workbook_path.split_at(idx + 1),
+            None => ("", workbook_path.as_str()),
+        };
+        let rels = relationships(
+            &part(&format!("{}_rels/{}.rels", workbook_dir, workbook_file))?,
+            workbook_dir,
+        )?;
+
+        let shared_strings = match rels.iter().find(|(_, t, _)| t == SHARED_STRINGS_REL) {
+            Some((_, _, target)) => read_shared_strings(&part(target)?)?,
+            None => Vec::new(),
+        };
+
+        let mut workbook = Workbook::default();
+        for event in parse_xml(&part(&workbook_path)?)? {
+            if let XmlEvent::Start { name, attrs, .. } = event {
+                if name != "sheet" {
+                    continue;
+                }
+                let sheet_name = attr(&attrs, "name").unwrap_or_default();
+                let rel_id = attr(&attrs, "id").unwrap_or_default();
+                let target = match rels
+                    .iter()
+                    .find(|(id, t, _)| id == rel_id && t == WORKSHEET_REL)
+                {
+                    Some((_, _, target)) => target,
+                    None => bail!("Invalid XLSX file, missing sheet {}", sheet_name),
+                };
+                workbook.sheets.push(Sheet {
+                    name: sheet_name.to_string(),
+                    rows: read_worksheet(&part(target)?, &shared_strings)?,
+                });
+            }
+        }
+
+        Ok(workbook)
+    }
+}
+
+fn cell(row: &[String], column: usize) -> &str {
+    row.get(column).map(String::as_str).unwrap_or_default()
+}
+
+/// Sheet name without the characters Excel doesn't allow, made unique by
+/// appending a number
+fn unique_sheet_name(name: &str, used: &mut HashSet<String>) -> String {
+    let base: String = name
+        .chars()
+        .map(|c| match c {
+            '[' | ']' | ':' | '*' | '?' | '/' | '\\' => '_',
+            c => c,
+        })
+        .take(MAX_SHEET_NAME_LEN)
+        .collect();
+    let base = if base.is_empty() {
+        String::from("Sheet")
+    } else {
+        base
+    };
+
+    let mut name = base.clone();
+    let mut suffix = 1;
+    while !used.insert(name.to_lowercase()) {
+        suffix += 1;
+        let suffix_str = format!(" ({})", suffix);
+        let prefix: String = base
+            .chars()
+            .take(MAX_SHEET_NAME_LEN - suffix_str.len())
+            .collect();
+        name = prefix + &suffix_str;
+    }
+    name
+}
+
+/// Name of a column in a cell reference, e.g. `A` or `AB`
+fn column_name(mut column: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (column % 26) as u8);
+        if column < 26 {
+            break;
+        }
+        column = column / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+/// Column of a cell reference, e.g. 27 for `AB3`
+fn column_index(reference: &str) -> Option<usize> {
+    let mut column = 0;
+    let mut found = false;
+    for c in reference.chars().take_while(char::is_ascii_alphabetic) {
+        column = column * 26 + (c.to_ascii_uppercase() as usize - 'A' as usize + 1);
+        found = true;
+    }
+    if found {
+        Some(column - 1)
+    } else {
+        None
+    }
+}
+
+/// Whether a value is a number in its shortest form
+fn is_number(s: &str) -> bool {
+    s.parse::<f64>()
+        .is_ok_and(|f| f.is_finite() && f.to_string() == s)
+}
+
+fn worksheet_xml(sheet: &Sheet) -> String {
+    let mut xml = format!(
+        "{}<worksheet xmlns=\"{}\"><sheetData>",
+        XML_DECLARATION, MAIN_NS
+    );
+    for (row_idx, row) in sheet.rows.iter().enumerate() {
+        xml.push_str(&format!("<row r=\"{}\">", row_idx + 1));
+        for (column, value) in row.iter().enumerate() {
+            let reference = format!("{}{}", column_name(column), row_idx + 1);
+            if value.is_empty() {
+                continue;
+            } else if is_number(value) {
+                xml.push_str(&format!("<c r=\"{}\"><v>{}</v></c>", reference, value));
+            } else {
+                xml.push_str(&format!(
+                    "<c r=\"{}\" t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+                    reference,
+                    escape(&encode_chars(value))
+                ));
+            }
+        }
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData></worksheet>");
+    xml
+}
+
+/// Read the relationships of a part as (ID, type, target) with the target
+/// relative to the root of the archive
+fn relationships(xml: &str, dir: &str) -> Result<Vec<(String, String, String)>, Error> {
+    let mut rels = Vec::new();
+    for event in parse_xml(xml)? {
+        if let XmlEvent::Start { name, attrs, .. } = event {
+            if name != "Relationship" {
+                continue;
+            }
+            let target = attr(&attrs, "Target").unwrap_or_default();
+            let target = match target.strip_prefix('/') {
+                Some(target) => target.to_string(),
+                None => format!("{}{}", dir, target),
+            };
+            rels.push((
+                attr(&attrs, "Id").unwrap_or_default().to_string(),
+                attr(&attrs, "Type").unwrap_or_default().to_string(),
+                target,
+            ));
+        }
+    }
+    Ok(rels)
+}
+
+fn read_shared_strings(xml: &str) -> Result<Vec<String>, Error> {
+    let mut strings = Vec::new();
+    let mut text = String::new();
+    let mut in_text = false;
+    // Phonetic hints are not part of the text
+    let mut in_phonetic = false;
+
+    for event in parse_xml(xml)? {
+        match event {
+            XmlEvent::Start { name, empty, .. } => match name.as_str() {
+                "si" if empty => strings.push(String::new()),
+                "si" => text.clear(),
+                "t" => in_text = !empty,
+                "rPh" => in_phonetic = !empty,
+                _ => {}
+            },
+            XmlEvent::End(name) => match name.as_str() {
+                "si" => strings.push(decode_chars(&text)),
+                "t" => in_text = false,
+                "rPh" => in_phonetic = false,
+                _ => {}
+            },
+            XmlEvent::Text(s) if in_text && !in_phonetic => text.push_str(&s),
+            XmlEvent::Text(_) => {}
+        }
+    }
+    Ok(strings)
+}
+
+fn read_worksheet(xml: &str, shared_strings: &[String]) -> Result<Vec<Vec<String>>, Error> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row_idx = 0;
+    let mut column = 0;
+    let mut cell_type = String::new();
+    let mut value = String::new();
+    let mut in_value = false;
+    let mut in_phonetic = false;
+
+    for event in parse_xml(xml)? {
+        match event {
+            XmlEvent::Start { name, attrs, empty } => match name.as_str() {
+                "row" => {
+                    row_idx = match attr(&attrs, "r").and_then(|r| r.parse::<usize>().ok()) {
+                        Some(r) if r > 0 => r - 1,
+                        _ => rows.len(),
+                    };
+                    column = 0;
+                    if rows.len() <= row_idx {
+                        rows.resize(row_idx + 1, Vec::new());
+                    }
+                }
+                "c" => {
+                    if let Some(c) = attr(&attrs, "r").and_then(column_index) {
+                        column = c;
+                    }
+                    cell_type = attr(&attrs, "t").unwrap_or_default().to_string();
+                    value.clear();
+                    if empty {
+                        column += 1;
+                    }
+                }
+                "v" | "t" => in_value = !empty,
+                "rPh" => in_phonetic = !empty,
+                _ => {}
+            },
+            XmlEvent::End(name) => match name.as_str() {
+                "c" => {
+                    let value = match cell_type.as_str() {
+                        "s" => {
+                            let idx: usize = value.trim().parse()?;
+                            match shared_strings.get(idx) {
+                                Some(s) => s.clone(),
+                                None => bail!("Invalid shared string index: {}", idx),
+                            }
+                        }
+                        "inlineStr" => decode_chars(&value),
+                        "str" | "b" | "e" => value.clone(),
+                        _ => match value.trim().parse::<f64>() {
+                            Ok(f) if f.is_finite() => f.to_string(),
+                            _ => value.clone(),
+                        },
+                    };
+
+                    if rows.is_empty() {
+                        rows.push(Vec::new());
+                    }
+                    let row = &mut rows[row_idx];
+                    if row.len() <= column {
+                        row.resize(column + 1, String::new());
+                    }
+                    row[column] = value;
+                    column += 1;
+                }
+                "v" | "t" => in_value = false,
+                "rPh" => in_phonetic = false,
+                _ => {}
+            },
+            XmlEvent::Text(s) if in_value && !in_phonetic => value.push_str(&s),
+            XmlEvent::Text(_) => {}
+        }
+    }
+
+    // Trailing empty cells aren't stored
+    let width = rows.iter().map(Vec::len).max().unwrap_or_default();
+    for row in &mut rows {
+        row.resize(width, String::new());
+    }
+    Ok(rows)
+}
+
+/// Encode the characters XML can't contain, and `\r` which XML readers
+/// normalize, like Excel does, e.g. `_x000D_`. Underscores that would be
+/// decoded are encoded as `_x005F_`.
+fn encode_chars(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for (idx, c) in s.char_indices() {
+        let invalid = (c < ' ' && c != '\t' && c != '\n') || c == '\u{FFFE}' || c == '\u{FFFF}';
+        if invalid || (c == '_' && encoded_char(&s[idx..]).is_some()) {
+            out.push_str(&format!("_x{:04X}_", c as u32));
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn decode_chars(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(idx) = rest.find("_x") {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        match encoded_char(rest) {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[7..];
+            }
+            None => {
+                out.push('_');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Character encoded at the start of `s`, e.g. `_x000D_`
+fn encoded_char(s: &str) -> Option<char> {
+    let code = s.get(0..7)?;
+    if !code.starts_with("_x") || !code.ends_with('_') {
+        return None;
+    }
+    u32::from_str_radix(&code[2..6], 16)
+        .ok()
+        .and_then(std::char::from_u32)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(s: &str) -> Result<String, Error> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(idx) = rest.find('&') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+        let end = rest
+            .find(';')
+            .ok_or_else(|| format_err!("Invalid XML entity"))?;
+        let c = match &rest[..end] {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            entity => {
+                let code = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+                code.and_then(std::char::from_u32)
+                    .ok_or_else(|| format_err!("Invalid XML entity: &{};", entity))?
+            }
+        };
+        out.push(c);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[derive(Debug, PartialEq)]
+enum XmlEvent {
+    /// Element names and attributes are without their namespace prefix
+    Start {
+        name: String,
+        attrs: Vec<(String, String)>,
+        empty: bool,
+    },
+    End(String),
+    Text(String),
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn local_name(name: &str) -> String {
+    match name.rfind(':') {
+        Some(idx) => name[idx + 1..].to_string(),
+        None => name.to_string(),
+    }
+}
+
+/// Parse the elements and text of an XML document. Only what's needed to
+/// read workbooks is supported, i.e. no DTDs or custom entities.
+fn parse_xml(xml: &str) -> Result<Vec<XmlEvent>, Error> {
+    let mut events = Vec::new();
+    let mut rest = xml;
+
+    let skip_to = |s: &str, end: &str| -> Result<usize, Error> {
+        s.find(end)
+            .map(|idx| idx + end.len())
+            .ok_or_else(|| format_err!("Invalid XML, missing {}", end))
+    };
+
+    while !rest.is_empty() {
+        let idx = rest.find('<').unwrap_or(rest.len());
+        if idx > 0 {
+            events.push(XmlEvent::Text(unescape(&rest[..idx])?));
+            rest = &rest[idx..];
+            continue;
+        }
+
+        if rest.starts_with("<?") {
+            rest = &rest[skip_to(rest, "?>")?..];
+        } else if rest.starts_with("<!--") {
+            rest = &rest[skip_to(rest, "-->")?..];
+        } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = skip_to(cdata, "]]>")?;
+            events.push(XmlEvent::Text(cdata[..end - 3].to_string()));
+            rest = &cdata[end..];
+        } else if rest.starts_with("<!") {
+            rest = &rest[skip_to(rest, ">")?..];
+        } else if let Some(tag) = rest.strip_prefix("</") {
+            let end = skip_to(tag, ">")?;
+            events.push(XmlEvent::End(local_name(tag[..end - 1].trim())));
+            rest = &tag[end..];
+        } else {
+            let (event, len) = parse_start_tag(rest)?;
+            events.push(event);
+            rest = &rest[len..];
+        }
+    }
+
+    Ok(events)
+}
+
+/// Parse a start tag, returning the event and the length of the tag
+fn parse_start_tag(s: &str) -> Result<(XmlEvent, usize), Error> {
+    let bytes = s.as_bytes();
+    let is_name_end = |b: u8| b.is_ascii_whitespace() || b == b'/' || b == b'>' || b == b'=';
+
+    let mut pos = 1;
+    while pos < bytes.len() && !is_name_end(bytes[pos]) {
+        pos += 1;
+    }
+    let name = local_name(&s[1..pos]);
+
+    let mut attrs = Vec::new();
+    loop {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        match bytes.get(pos) {
+            Some(b'>') => {
+                let event = XmlEvent::Start {
+                    name,
+                    attrs,
+                    empty: false,
+                };
+                return Ok((event, pos + 1));
+            }
+            Some(b'/') if bytes.get(pos + 1) == Some(&b'>') => {
+                let event = XmlEvent::Start {
+                    name,
+                    attrs,
+                    empty: true,
+                };
+                return Ok((event, pos + 2));
+            }
+            Some(_) => {}
+            None => bail!("Invalid XML, unterminated tag: {}", name),
+        }
+
+        let start = pos;
+        while pos < bytes.len() && !is_name_end(bytes[pos]) {
+            pos += 1;
+        }
+        let attr_name = local_name(&s[start..pos]);
+        while pos < bytes.len() && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'=') {
+            pos += 1;
+        }
+
+        let quote = match bytes.get(pos) {
+            Some(&q) if q == b'"' || q == b'\'' => q,
+            _ => bail!("Invalid XML, unquoted attribute in tag: {}", name),
+        };
+        let value_start = pos + 1;
+        let value_len = s[value_start..]
+            .bytes()
+            .position(|b| b == quote)
+            .ok_or_else(|| format_err!("Invalid XML, unterminated attribute in tag: {}", name))?;
+        attrs.push((
+            attr_name,
+            unescape(&s[value_start..value_start + value_len])?,
+        ));
+        pos = value_start + value_len + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workbook() {
+        let sheet = Sheet {
+            name: "LIST/ZONE".into(),
+            rows: vec![
+                vec!["NAME".into(), "LEVEL".into(), "RATE".into()],
+                vec!["<a & b>".into(), "5".into(), "1.50".into()],
+                vec!["x_x0041_\r\n".into(), "".into(), "".into()],
+                vec!["".into(), "".into(), "".into()],
+                vec!["c".into(), "-7".into(), "0.25".into()],
+            ],
+        };
+        let workbook = Workbook {
+            sheets: vec![sheet.clone(), sheet.clone()],
+        };
+
+        let read = Workbook::from_bytes(&workbook.to_bytes().unwrap()).unwrap();
+        assert_eq!(read.sheets[0].name, "LIST_ZONE");
+        assert_eq!(read.sheets[1].name, "LIST_ZONE (2)");
+        assert_eq!(read.sheets[0].rows, sheet.rows);
+
+        let xml = worksheet_xml(&sheet);
+        assert!(xml.contains("<c r=\"B2\"><v>5</v></c>"));
+        assert!(xml.contains("<t xml:space=\"preserve\">1.50</t>"));
+
+        assert_eq!(column_name(27), "AB");
+        assert_eq!(column_index("AB3"), Some(27));
+
+        // Shared strings and cells without references like other writers use
+        let shared = read_shared_strings(
+            "<sst><si><t>a</t></si><si><r><t>b</t></r><r><t>c</t></r><rPh><t>x</t></rPh></si></sst>",
+        )
+        .unwrap();
+        assert_eq!(shared, vec!["a", "bc"]);
+        let rows = read_worksheet(
+            "<x:worksheet><x:sheetData><x:row><x:c t=\"s\"><x:v>1</x:v></x:c><x:c><x:v>2.0</x:v></x:c></x:row>\
+             <x:row r=\"3\"><x:c r=\"B3\" t=\"s\"><x:v>0</x:v></x:c></x:row></x:sheetData></x:worksheet>",
+            &shared,
+        )
+        .unwrap();
+        assert_eq!(rows, vec![vec!["bc", "2"], vec!["", ""], vec!["", "a"]]);
+    }
+
+    #[test]
+    fn test_stl_workbook() {
+        let mut stl = STL::new();
+        stl.format = StringTableType::Item;
+        stl.keys.push(StringTableKey {
+            id: 7,
+            name: "ITEM_7".into(),
+        });
+        for (language, text) in &[(0, "검"), (1, "Sword")] {
+            stl.language_tables.push(StringTableLanguageTable {
+                language: StringTableLanguage::from(*language),
+                rows: vec![StringTableRow::ItemRow(ItemRowData {
+                    text: text.to_string(),
+                    description: "".into(),
+                })],
+            });
+        }
+
+        let workbook = Workbook::from_stl(&stl).unwrap();
+        assert_eq!(workbook.sheets.len(), 2);
+        assert_eq!(workbook.sheets[1].rows[1], vec!["7", "ITEM_7", "Sword", ""]);
+
+        let read = Workbook::from_bytes(&workbook.to_bytes().unwrap()).unwrap();
+        let read = read.to_stl().unwrap();
+        assert_eq!(read.format, stl.format);
+        assert_eq!(read.keys, stl.keys);
+        assert_eq!(read.language_tables, stl.language_tables);
+    }
+}
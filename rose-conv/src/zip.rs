@@ -0,0 +1,200 @@
+//! Minimal ZIP archive reading and writing
+//!
+//! Only what XLSX workbooks need is supported: stored and deflated entries
+//! without encryption or ZIP64 extensions.
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use failure::{bail, format_err, Error};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIGNATURE: u32 = 0x0605_4b50;
+
+/// Size of the end of central directory record without its comment
+const END_OF_CENTRAL_DIR_SIZE: usize = 22;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// Version 2.0, the minimum for deflated entries
+const VERSION: u16 = 20;
+
+/// File names are UTF-8
+const FLAG_UTF8: u16 = 0x0800;
+
+/// 1980-01-01 00:00, the earliest DOS date
+const DOS_DATE: u16 = 0x21;
+
+/// Compression level of deflated entries
+const DEFLATE_LEVEL: u8 = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZipEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Write the entries to a new archive, deflating their data
+pub fn write(entries: &[ZipEntry]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+
+    for entry in entries {
+        let compressed = miniz_oxide::deflate::compress_to_vec(&entry.data, DEFLATE_LEVEL);
+        let crc = crc32fast::hash(&entry.data);
+        let offset = out.len() as u32;
+        let name = entry.name.as_bytes();
+
+        out.write_u32::<LittleEndian>(LOCAL_HEADER_SIGNATURE)?;
+        out.write_u16::<LittleEndian>(VERSION)?;
+        out.write_u16::<LittleEndian>(FLAG_UTF8)?;
+        out.write_u16::<LittleEndian>(METHOD_DEFLATED)?;
+        out.write_u16::<LittleEndian>(0)?;
+        out.write_u16::<LittleEndian>(DOS_DATE)?;
+        out.write_u32::<LittleEndian>(crc)?;
+        out.write_u32::<LittleEndian>(compressed.len() as u32)?;
+        out.write_u32::<LittleEndian>(entry.data.len() as u32)?;
+        out.write_u16::<LittleEndian>(name.len() as u16)?;
+        out.write_u16::<LittleEndian>(0)?;
+        out.write_all(name)?;
+        out.write_all(&compressed)?;
+
+        central.write_u32::<LittleEndian>(CENTRAL_HEADER_SIGNATURE)?;
+        central.write_u16::<LittleEndian>(VERSION)?;
+        central.write_u16::<LittleEndian>(VERSION)?;
+        central.write_u16::<LittleEndian>(FLAG_UTF8)?;
+        central.write_u16::<LittleEndian>(METHOD_DEFLATED)?;
+        central.write_u16::<LittleEndian>(0)?;
+        central.write_u16::<LittleEndian>(DOS_DATE)?;
+        central.write_u32::<LittleEndian>(crc)?;
+        central.write_u32::<LittleEndian>(compressed.len() as u32)?;
+        central.write_u32::<LittleEndian>(entry.data.len() as u32)?;
+        central.write_u16::<LittleEndian>(name.len() as u16)?;
+        // Extra field, comment, disk number, internal and external attributes
+        central.write_u16::<LittleEndian>(0)?;
+        central.write_u16::<LittleEndian>(0)?;
+        central.write_u16::<LittleEndian>(0)?;
+        central.write_u16::<LittleEndian>(0)?;
+        central.write_u32::<LittleEndian>(0)?;
+        central.write_u32::<LittleEndian>(offset)?;
+        central.write_all(name)?;
+    }
+
+    let central_offset = out.len() as u32;
+    out.write_all(&central)?;
+
+    out.write_u32::<LittleEndian>(END_OF_CENTRAL_DIR_SIGNATURE)?;
+    out.write_u16::<LittleEndian>(0)?;
+    out.write_u16::<LittleEndian>(0)?;
+    out.write_u16::<LittleEndian>(entries.len() as u16)?;
+    out.write_u16::<LittleEndian>(entries.len() as u16)?;
+    out.write_u32::<LittleEndian>(central.len() as u32)?;
+    out.write_u32::<LittleEndian>(central_offset)?;
+    out.write_u16::<LittleEndian>(0)?;
+
+    Ok(out)
+}
+
+/// Read every entry of an archive
+pub fn read(data: &[u8]) -> Result<Vec<ZipEntry>, Error> {
+    // The end of central directory record is followed by a comment of up to
+    // 64 KiB
+    if data.len() < END_OF_CENTRAL_DIR_SIZE {
+        bail!("Not a ZIP archive");
+    }
+    let end = (0..=data.len() - END_OF_CENTRAL_DIR_SIZE)
+        .rev()
+        .take(0x10000)
+        .find(|&pos| data[pos..pos + 4] == END_OF_CENTRAL_DIR_SIGNATURE.to_le_bytes())
+        .ok_or_else(|| format_err!("Not a ZIP archive"))?;
+
+    let mut reader = Cursor::new(data);
+    reader.seek(SeekFrom::Start(end as u64 + 10))?;
+    let entry_count = reader.read_u16::<LittleEndian>()?;
+    reader.seek(SeekFrom::Current(4))?;
+    let central_offset = reader.read_u32::<LittleEndian>()?;
+    if entry_count == u16::MAX || central_offset == u32::MAX {
+        bail!("ZIP64 archives are not supported");
+    }
+
+    let mut entries = Vec::new();
+    reader.seek(SeekFrom::Start(u64::from(central_offset)))?;
+    for _ in 0..entry_count {
+        if reader.read_u32::<LittleEndian>()? != CENTRAL_HEADER_SIGNATURE {
+            bail!("Invalid ZIP central directory");
+        }
+        reader.seek(SeekFrom::Current(4))?;
+        let flags = reader.read_u16::<LittleEndian>()?;
+        let method = reader.read_u16::<LittleEndian>()?;
+        reader.seek(SeekFrom::Current(4))?;
+        let crc = reader.read_u32::<LittleEndian>()?;
+        let compressed_size = reader.read_u32::<LittleEndian>()? as usize;
+        reader.seek(SeekFrom::Current(4))?;
+        let name_len = reader.read_u16::<LittleEndian>()? as usize;
+        let extra_len = reader.read_u16::<LittleEndian>()? as i64;
+        let comment_len = reader.read_u16::<LittleEndian>()? as i64;
+        reader.seek(SeekFrom::Current(8))?;
+        let local_offset = reader.read_u32::<LittleEndian>()? as usize;
+
+        let mut name = vec![0u8; name_len];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        reader.seek(SeekFrom::Current(extra_len + comment_len))?;
+
+        if flags & 0x1 != 0 {
+            bail!("Encrypted ZIP entries are not supported: {}", name);
+        }
+
+        // The local header repeats the name and has its own extra field
+        let local = data
+            .get(local_offset..local_offset + 30)
+            .ok_or_else(|| format_err!("Invalid ZIP entry: {}", name))?;
+        if local[0..4] != LOCAL_HEADER_SIGNATURE.to_le_bytes() {
+            bail!("Invalid ZIP entry: {}", name);
+        }
+        let local_name_len = u16::from_le_bytes([local[26], local[27]]) as usize;
+        let local_extra_len = u16::from_le_bytes([local[28], local[29]]) as usize;
+        let start = local_offset + 30 + local_name_len + local_extra_len;
+        let compressed = data
+            .get(start..start + compressed_size)
+            .ok_or_else(|| format_err!("Invalid ZIP entry: {}", name))?;
+
+        let data = match method {
+            METHOD_STORED => compressed.to_vec(),
+            METHOD_DEFLATED => miniz_oxide::inflate::decompress_to_vec(compressed)
+                .map_err(|e| format_err!("Unable to inflate {}: {:?}", name, e))?,
+            _ => bail!("Unsupported ZIP compression method {}: {}", method, name),
+        };
+        if crc32fast::hash(&data) != crc {
+            bail!("Invalid checksum of ZIP entry: {}", name);
+        }
+
+        entries.push(ZipEntry { name, data });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zip() {
+        let entries = vec![
+            ZipEntry {
+                name: "a.txt".into(),
+                data: b"hello hello hello".to_vec(),
+            },
+            ZipEntry {
+                name: "dir/b.xml".into(),
+                data: Vec::new(),
+            },
+        ];
+
+        let data = write(&entries).unwrap();
+        assert_eq!(read(&data).unwrap(), entries);
+        assert!(read(b"not a zip file, not a zip file").is_err());
+    }
+}
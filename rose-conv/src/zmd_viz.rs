@@ -0,0 +1,262 @@
+//! Visualization of ZMD skeletons
+//!
+//! Bones are placed relative to their parent bone, in centimeters, with the
+//! root bone as its own parent. Dummy bones, attachment points for weapons
+//! and effects, hang from a bone the same way. The joints of the skeleton are
+//! found by composing the bone transforms from the root, and every bone is
+//! drawn as a line from its parent's joint to its own.
+//!
+//! The skeleton is written as an OBJ with a vertex per joint and a line per
+//! bone, Y-up in meters like glTF exports.
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use roselib::files::zmd::Bone;
+use roselib::files::ZMD;
+use roselib::utils::{Quaternion, Vector3};
+
+use crate::gltf;
+
+/// Centimeters per meter, the scale of bone positions
+const BONE_SCALE: f32 = 100.0;
+
+/// Joint of a skeleton in model space
+#[derive(Debug, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    /// Index of the parent joint, `None` for the root
+    pub parent: Option<usize>,
+    pub dummy: bool,
+    /// Position in centimeters
+    pub position: Vector3<f32>,
+    pub rotation: Quaternion,
+}
+
+/// Joints of the bones then the dummy bones of a skeleton
+pub fn joints(zmd: &ZMD) -> Vec<Joint> {
+    let bone_parent = |idx: usize, bone: &Bone| {
+        let parent = bone.parent as usize;
+        if bone.parent < 0 || parent == idx || parent >= zmd.bones.len() {
+            None
+        } else {
+            Some(parent)
+        }
+    };
+
+    let mut joints: Vec<Joint> = Vec::new();
+    for (idx, bone) in zmd.bones.iter().enumerate() {
+        // Walk up to the root, parents in a cycle stop after every bone
+        let mut chain = vec![bone];
+        let mut current = bone_parent(idx, bone);
+        while let Some(parent) = current {
+            if chain.len() > zmd.bones.len() {
+                break;
+            }
+            chain.push(&zmd.bones[parent]);
+            current = bone_parent(parent, &zmd.bones[parent]);
+        }
+        let (position, rotation) = compose(chain.iter().rev().copied());
+        joints.push(Joint {
+            name: bone.name.clone(),
+            parent: bone_parent(idx, bone),
+            dummy: false,
+            position,
+            rotation,
+        });
+    }
+    for dummy in &zmd.dummy_bones {
+        let parent = usize::try_from(dummy.parent)
+            .ok()
+            .filter(|&p| p < joints.len());
+        let (position, rotation) = match parent {
+            Some(p) => transform(&joints[p].position, &joints[p].rotation, dummy),
+            None => (dummy.position, bone_rotation(dummy)),
+        };
+        joints.push(Joint {
+            name: dummy.name.clone(),
+            parent,
+            dummy: true,
+            position,
+            rotation,
+        });
+    }
+    joints
+}
+
+/// Model space transform of the last of a chain of bones from the root
+fn compose<'a, I>(chain: I) -> (Vector3<f32>, Quaternion)
+where
+    I: Iterator<Item = &'a Bone>,
+{
+    let mut position = Vector3::default();
+    let mut rotation = IDENTITY;
+    for bone in chain {
+        let (p, r) = transform(&position, &rotation, bone);
+        position = p;
+        rotation = r;
+    }
+    (position, rotation)
+}
+
+const IDENTITY: Quaternion = Quaternion {
+    x: 0.0,
+    y: 0.0,
+    z: 0.0,
+    w: 1.0,
+};
+
+/// Transform of a bone placed relative to a parent transform
+fn transform(
+    position: &Vector3<f32>,
+    rotation: &Quaternion,
+    bone: &Bone,
+) -> (Vector3<f32>, Quaternion) {
+    let offset = rotate(rotation, &bone.position);
+    (
+        Vector3 {
+            x: position.x + offset.x,
+            y: position.y + offset.y,
+            z: position.z + offset.z,
+        },
+        multiply(rotation, &bone_rotation(bone)),
+    )
+}
+
+/// Rotation of a bone relative to its parent, the zero quaternion of dummy
+/// bones without rotations being no rotation
+pub fn bone_rotation(bone: &Bone) -> Quaternion {
+    if bone.rotation == Quaternion::default() {
+        IDENTITY
+    } else {
+        bone.rotation
+    }
+}
+
+fn multiply(a: &Quaternion, b: &Quaternion) -> Quaternion {
+    Quaternion {
+        x: a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+        y: a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+        z: a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        w: a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+    }
+}
+
+fn rotate(q: &Quaternion, v: &Vector3<f32>) -> Vector3<f32> {
+    // v + 2w(u x v) + 2u x (u x v), with u the vector part of q
+    let (x, y, z) = (
+        q.y * v.z - q.z * v.y,
+        q.z * v.x - q.x * v.z,
+        q.x * v.y - q.y * v.x,
+    );
+    Vector3 {
+        x: v.x + 2.0 * (q.w * x + q.y * z - q.z * y),
+        y: v.y + 2.0 * (q.w * y + q.z * x - q.x * z),
+        z: v.z + 2.0 * (q.w * z + q.x * y - q.y * x),
+    }
+}
+
+/// Position of a bone relative to its parent in glTF space, Y-up in meters
+pub fn bone_position(bone: &Bone) -> [f32; 3] {
+    gltf::position(&Vector3 {
+        x: bone.position.x / BONE_SCALE,
+        y: bone.position.y / BONE_SCALE,
+        z: bone.position.z / BONE_SCALE,
+    })
+}
+
+/// Position of a joint in glTF space, Y-up in meters
+pub fn joint_position(joint: &Joint) -> [f32; 3] {
+    gltf::position(&Vector3 {
+        x: joint.position.x / BONE_SCALE,
+        y: joint.position.y / BONE_SCALE,
+        z: joint.position.z / BONE_SCALE,
+    })
+}
+
+/// OBJ of the joints of a skeleton with a line per bone, in groups for the
+/// bones and the dummy bones
+pub fn to_obj(name: &str, joints: &[Joint]) -> String {
+    let mut obj = format!("# Skeleton of {}\n", name);
+    for joint in joints {
+        let [x, y, z] = joint_position(joint);
+        let _ = writeln!(obj, "v {} {} {}", x, y, z);
+    }
+    for (group, dummy) in &[("Bones", false), ("Dummies", true)] {
+        let _ = writeln!(obj, "g {}", group);
+        for (idx, joint) in joints.iter().enumerate() {
+            if joint.dummy != *dummy {
+                continue;
+            }
+            let _ = writeln!(obj, "# {}", joint.name);
+            match joint.parent {
+                Some(parent) => {
+                    let _ = writeln!(obj, "l {} {}", parent + 1, idx + 1);
+                }
+                None => {
+                    let _ = writeln!(obj, "p {}", idx + 1);
+                }
+            }
+        }
+    }
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::io::RoseFile;
+
+    fn bone(name: &str, parent: i32, position: [f32; 3], rotation: Quaternion) -> Bone {
+        Bone {
+            parent,
+            name: String::from(name),
+            position: Vector3 {
+                x: position[0],
+                y: position[1],
+                z: position[2],
+            },
+            rotation,
+        }
+    }
+
+    #[test]
+    fn test_joints() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        // A quarter turn counterclockwise around Z
+        let turn = Quaternion {
+            x: 0.0,
+            y: 0.0,
+            z: half,
+            w: half,
+        };
+        let mut zmd = ZMD::new();
+        zmd.bones
+            .push(bone("b1_pelvis", 0, [0.0, 0.0, 100.0], turn));
+        zmd.bones
+            .push(bone("b1_spine", 0, [100.0, 0.0, 0.0], IDENTITY));
+        zmd.dummy_bones
+            .push(bone("p_00", 1, [0.0, 0.0, 50.0], Quaternion::default()));
+
+        let joints = joints(&zmd);
+        assert_eq!(joints.len(), 3);
+        assert_eq!(joints[0].parent, None);
+        assert_eq!(joints[1].parent, Some(0));
+        // The spine turned with the pelvis
+        let p = &joints[1].position;
+        assert_eq!([p.x.round(), p.y.round(), p.z.round()], [0.0, 100.0, 100.0]);
+        assert!(joints[2].dummy);
+        assert_eq!(joints[2].parent, Some(1));
+        assert_eq!(joints[2].position.z.round(), 150.0);
+
+        let obj = to_obj("MALE", &joints);
+        let lines: Vec<&str> = obj.lines().collect();
+        assert_eq!(lines[0], "# Skeleton of MALE");
+        assert_eq!(lines[1], "v 0 1 0");
+        assert_eq!(lines[4], "g Bones");
+        assert_eq!(lines[6], "p 1");
+        assert_eq!(lines[8], "l 1 2");
+        assert_eq!(lines[9], "g Dummies");
+        assert_eq!(lines[11], "l 2 3");
+    }
+}
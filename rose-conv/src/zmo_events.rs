@@ -0,0 +1,118 @@
+//! Frame events of ZMO motions
+//!
+//! A motion can trigger an event at any of its frames: a sound, an effect or
+//! the hit of an attack, by id. Editing the events moves them between frames
+//! or changes them, keeping one event per frame.
+use std::fmt;
+
+use failure::{bail, format_err, Error};
+use serde::Serialize;
+
+use roselib::files::ZMO;
+
+/// Event triggered at a frame of a motion
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FrameEvent {
+    pub frame: usize,
+    pub event: u16,
+}
+
+impl fmt::Display for FrameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "frame {}: event {}", self.frame, self.event)
+    }
+}
+
+/// Events of a motion, by frame
+pub fn frame_events(zmo: &ZMO) -> Vec<FrameEvent> {
+    zmo.frame_events
+        .iter()
+        .enumerate()
+        .filter(|(_, &event)| event != 0)
+        .map(|(frame, &event)| FrameEvent { frame, event })
+        .collect()
+}
+
+/// Set the event of a frame, 0 to remove it
+pub fn set_event(zmo: &mut ZMO, frame: usize, event: u16) -> Result<(), Error> {
+    check_frame(zmo, frame)?;
+    if zmo.frame_events.len() < zmo.frames as usize {
+        zmo.frame_events.resize(zmo.frames as usize, 0);
+    }
+    zmo.frame_events[frame] = event;
+    // Motions without events have no events block
+    if zmo.frame_events.iter().all(|&e| e == 0) {
+        zmo.frame_events.clear();
+    }
+    Ok(())
+}
+
+/// Move the event of a frame to another, replacing its event
+pub fn move_event(zmo: &mut ZMO, from: usize, to: usize) -> Result<(), Error> {
+    check_frame(zmo, to)?;
+    let event = match zmo.frame_events.get(from) {
+        Some(&event) if event != 0 => event,
+        _ => bail!("No event at frame {}", from),
+    };
+    set_event(zmo, from, 0)?;
+    set_event(zmo, to, event)
+}
+
+fn check_frame(zmo: &ZMO, frame: usize) -> Result<(), Error> {
+    if frame >= zmo.frames as usize {
+        bail!(
+            "Frame {} is past the {} frames of the motion",
+            frame,
+            zmo.frames
+        );
+    }
+    Ok(())
+}
+
+/// Parse an edit of the form `<a>=<b>`
+pub fn parse_edit(s: &str) -> Result<(usize, usize), Error> {
+    let mut parts = s.splitn(2, '=');
+    let a = parts.next().unwrap_or_default().trim();
+    let b = parts
+        .next()
+        .ok_or_else(|| format_err!("Expected <frame>=<value>: {}", s))?
+        .trim();
+    Ok((a.parse()?, b.parse()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_frame_events() {
+        let mut zmo = ZMO::new();
+        zmo.frames = 5;
+        assert!(frame_events(&zmo).is_empty());
+
+        set_event(&mut zmo, 2, 21).unwrap();
+        assert_eq!(zmo.frame_events, vec![0, 0, 21, 0, 0]);
+        assert!(set_event(&mut zmo, 5, 1).is_err());
+        assert!(move_event(&mut zmo, 1, 3).is_err());
+
+        // Move the hit of an attack a frame later
+        move_event(&mut zmo, 2, 3).unwrap();
+        assert_eq!(
+            frame_events(&zmo),
+            vec![FrameEvent {
+                frame: 3,
+                event: 21
+            }]
+        );
+        assert_eq!(frame_events(&zmo)[0].to_string(), "frame 3: event 21");
+
+        set_event(&mut zmo, 3, 0).unwrap();
+        assert!(zmo.frame_events.is_empty());
+
+        assert_eq!(parse_edit("3=21").unwrap(), (3, 21));
+        assert!(parse_edit("3").is_err());
+        assert!(parse_edit("a=1").is_err());
+    }
+}
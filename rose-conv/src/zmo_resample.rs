@@ -0,0 +1,243 @@
+//! Resampling of ZMO motions to another frame rate
+//!
+//! Every channel of a motion has a key per frame, the first at time zero and
+//! the last at `(frames - 1) / fps`. Resampling keeps the first and last keys
+//! and interpolates the frames in between: linearly for positions, scales,
+//! UVs and the like, spherically for rotations. Texture channels hold the
+//! index of a texture and step to the previous key instead. Frame events move
+//! to the nearest new frame.
+use roselib::files::zmo::{Channel, ChannelData};
+use roselib::files::ZMO;
+use roselib::utils::{Quaternion, Vector2, Vector3};
+
+/// Number of frames of a motion of `frames` frames at `fps` once resampled
+/// to `target_fps`, keeping its duration
+pub fn resampled_frames(frames: u32, fps: u32, target_fps: u32) -> u32 {
+    if frames <= 1 || fps == 0 {
+        return frames;
+    }
+    let duration = (frames - 1) as f64 / fps as f64;
+    (duration * target_fps as f64).round() as u32 + 1
+}
+
+/// Copy of a motion resampled to `target_fps`
+pub fn resample(zmo: &ZMO, target_fps: u32) -> ZMO {
+    let frames = resampled_frames(zmo.frames, zmo.fps, target_fps);
+    // Source frame of every new frame
+    let times: Vec<f32> = if frames <= 1 {
+        (0..frames).map(|_| 0.0).collect()
+    } else {
+        let last = (zmo.frames - 1) as f32;
+        (0..frames)
+            .map(|i| i as f32 * last / (frames - 1) as f32)
+            .collect()
+    };
+
+    ZMO {
+        identifier: zmo.identifier.clone(),
+        fps: target_fps,
+        frames,
+        channels: zmo
+            .channels
+            .iter()
+            .map(|channel| Channel {
+                typ: channel.typ,
+                index: channel.index,
+                frames: resample_data(&channel.frames, &times),
+            })
+            .collect(),
+        frame_events: resample_events(&zmo.frame_events, frames),
+        frame_events_tag: zmo.frame_events_tag.clone(),
+    }
+}
+
+/// Events at the nearest of `frames` new frames
+fn resample_events(events: &[u16], frames: u32) -> Vec<u16> {
+    if events.is_empty() {
+        return Vec::new();
+    }
+    let mut resampled = vec![0; frames as usize];
+    let last = (events.len() - 1).max(1) as f32;
+    for (frame, &event) in events.iter().enumerate().filter(|(_, &e)| e != 0) {
+        let new_frame = (frame as f32 * (frames.max(1) - 1) as f32 / last).round() as usize;
+        if let Some(slot) = resampled.get_mut(new_frame) {
+            *slot = event;
+        }
+    }
+    resampled
+}
+
+fn resample_data(data: &ChannelData, times: &[f32]) -> ChannelData {
+    match data {
+        ChannelData::None => ChannelData::None,
+        ChannelData::Position(v) => ChannelData::Position(sample(v, times, lerp3)),
+        ChannelData::Rotation(q) => ChannelData::Rotation(sample(q, times, slerp)),
+        ChannelData::Normal(v) => ChannelData::Normal(sample(v, times, lerp3)),
+        ChannelData::Alpha(f) => ChannelData::Alpha(sample(f, times, lerp)),
+        ChannelData::UV1(v) => ChannelData::UV1(sample(v, times, lerp2)),
+        ChannelData::UV2(v) => ChannelData::UV2(sample(v, times, lerp2)),
+        ChannelData::UV3(v) => ChannelData::UV3(sample(v, times, lerp2)),
+        ChannelData::UV4(v) => ChannelData::UV4(sample(v, times, lerp2)),
+        ChannelData::Texture(f) => ChannelData::Texture(sample(f, times, |a, _, _| *a)),
+        ChannelData::Scale(f) => ChannelData::Scale(sample(f, times, lerp)),
+    }
+}
+
+/// Keys at the given source frames, interpolated between their neighbours
+fn sample<T, F>(keys: &[T], times: &[f32], interpolate: F) -> Vec<T>
+where
+    T: Copy,
+    F: Fn(&T, &T, f32) -> T,
+{
+    if keys.is_empty() {
+        return Vec::new();
+    }
+    let last = keys.len() - 1;
+    times
+        .iter()
+        .map(|&t| {
+            let i = (t.floor() as usize).min(last);
+            let next = (i + 1).min(last);
+            let s = t - i as f32;
+            if s <= 0.0 || i == next {
+                keys[i]
+            } else {
+                interpolate(&keys[i], &keys[next], s)
+            }
+        })
+        .collect()
+}
+
+fn lerp(a: &f32, b: &f32, s: f32) -> f32 {
+    a + (b - a) * s
+}
+
+fn lerp2(a: &Vector2<f32>, b: &Vector2<f32>, s: f32) -> Vector2<f32> {
+    Vector2 {
+        x: lerp(&a.x, &b.x, s),
+        y: lerp(&a.y, &b.y, s),
+    }
+}
+
+fn lerp3(a: &Vector3<f32>, b: &Vector3<f32>, s: f32) -> Vector3<f32> {
+    Vector3 {
+        x: lerp(&a.x, &b.x, s),
+        y: lerp(&a.y, &b.y, s),
+        z: lerp(&a.z, &b.z, s),
+    }
+}
+
+/// Spherical interpolation along the shortest arc
+fn slerp(a: &Quaternion, b: &Quaternion, s: f32) -> Quaternion {
+    let mut dot = a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w;
+    let sign = if dot < 0.0 { -1.0 } else { 1.0 };
+    dot *= sign;
+
+    let (wa, wb) = if dot > 0.9995 {
+        // Nearly the same rotation, linear is precise enough
+        (1.0 - s, s)
+    } else {
+        let theta = dot.acos();
+        let sin = theta.sin();
+        (((1.0 - s) * theta).sin() / sin, (s * theta).sin() / sin)
+    };
+    let wb = wb * sign;
+    let q = Quaternion {
+        x: wa * a.x + wb * b.x,
+        y: wa * a.y + wb * b.y,
+        z: wa * a.z + wb * b.z,
+        w: wa * a.w + wb * b.w,
+    };
+    let len = (q.x * q.x + q.y * q.y + q.z * q.z + q.w * q.w).sqrt();
+    if len == 0.0 {
+        return *a;
+    }
+    Quaternion {
+        x: q.x / len,
+        y: q.y / len,
+        z: q.z / len,
+        w: q.w / len,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zmo::ChannelType;
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_resample() {
+        assert_eq!(resampled_frames(11, 10, 60), 61);
+        assert_eq!(resampled_frames(1, 10, 60), 1);
+        assert_eq!(resampled_frames(4, 30, 10), 2);
+
+        let mut zmo = ZMO::new();
+        zmo.identifier = String::from("ZMO0002");
+        zmo.fps = 10;
+        zmo.frames = 3;
+        let mut position = Channel::from(ChannelType::Position);
+        position.frames = ChannelData::Position(
+            [0.0, 10.0, 30.0]
+                .iter()
+                .map(|&x| Vector3 { x, y: 0.0, z: 1.0 })
+                .collect(),
+        );
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let mut rotation = Channel::from(ChannelType::Rotation);
+        rotation.index = 1;
+        // No turn, then a quarter turn around Z written with the other sign
+        rotation.frames = ChannelData::Rotation(vec![
+            Quaternion {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0,
+            },
+            Quaternion {
+                x: 0.0,
+                y: 0.0,
+                z: -half,
+                w: -half,
+            },
+            Quaternion {
+                x: 0.0,
+                y: 0.0,
+                z: -half,
+                w: -half,
+            },
+        ]);
+        let mut texture = Channel::from(ChannelType::Texture);
+        texture.frames = ChannelData::Texture(vec![0.0, 1.0, 2.0]);
+        zmo.channels = vec![position, rotation, texture];
+        zmo.frame_events = vec![0, 21, 0];
+
+        let mut resampled = resample(&zmo, 20);
+        assert_eq!(resampled.fps, 20);
+        assert_eq!(resampled.frames, 5);
+        assert_eq!(resampled.channels[1].index, 1);
+        assert_eq!(resampled.frame_events, vec![0, 0, 21, 0, 0]);
+
+        let positions = resampled.channels[0].position_frames().unwrap();
+        let x: Vec<f32> = positions.iter().map(|v| v.x).collect();
+        assert_eq!(x, vec![0.0, 5.0, 10.0, 20.0, 30.0]);
+        assert_eq!(positions[1].z, 1.0);
+
+        // An eighth of a turn, along the shortest arc
+        let q = resampled.channels[1].rotation_frames().unwrap()[1];
+        let angle = 2.0 * q.w.abs().acos();
+        assert!((angle - std::f32::consts::FRAC_PI_4).abs() < 1e-5);
+        assert!(q.z * q.w > 0.0);
+
+        let textures = resampled.channels[2].texture_frames().unwrap();
+        assert_eq!(textures, &vec![0.0, 0.0, 1.0, 1.0, 2.0]);
+
+        // Still a valid ZMO
+        let path = std::env::temp_dir().join("rose-conv-test-resample.zmo");
+        resampled.write_to_path(&path).unwrap();
+        let read = ZMO::from_path(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read, resampled);
+    }
+}
@@ -0,0 +1,180 @@
+//! Root motion of ZMO motions
+//!
+//! Motions move characters by translating their root bone, the position
+//! channel of bone 0. Engines driving characters from root motion expect
+//! that translation apart from the animation: the motion curve is the offset
+//! of the root from its position in the first frame, and the motion can be
+//! made in place by pinning the root there.
+use failure::Error;
+use serde::Serialize;
+
+use roselib::files::zmo::{ChannelData, ChannelType};
+use roselib::files::ZMO;
+use roselib::utils::Vector3;
+
+use crate::axes::Axes;
+use crate::csv_dialect::CsvDialect;
+use crate::gltf;
+
+/// Translation of the root bone relative to the first frame, in centimeters
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RootMotion {
+    pub bone: u32,
+    pub fps: u32,
+    pub frames: Vec<Vector3<f32>>,
+}
+
+impl RootMotion {
+    /// Root motion of a bone, `None` if the motion has no position channel
+    /// for it
+    pub fn from_zmo(zmo: &ZMO, bone: u32) -> Option<RootMotion> {
+        let positions = root_positions(zmo, bone)?;
+        let first = positions.first().copied().unwrap_or_default();
+        Some(RootMotion {
+            bone,
+            fps: zmo.fps,
+            frames: positions
+                .iter()
+                .map(|p| Vector3 {
+                    x: p.x - first.x,
+                    y: p.y - first.y,
+                    z: p.z - first.z,
+                })
+                .collect(),
+        })
+    }
+
+    /// Convert the motion curve from the ROSE axes in centimeters to `axes`
+    pub fn convert_axes(&mut self, axes: &Axes) {
+        for offset in self.frames.iter_mut() {
+            let [x, y, z] = axes.position(gltf::position(&Vector3 {
+                x: offset.x / 100.0,
+                y: offset.y / 100.0,
+                z: offset.z / 100.0,
+            }));
+            *offset = Vector3 { x, y, z };
+        }
+    }
+
+    /// Write the motion curve as CSV with a row per frame
+    pub fn to_csv(&self, dialect: &CsvDialect) -> Result<String, Error> {
+        let mut writer = dialect.writer_builder().from_writer(Vec::new());
+        writer.write_record(["Frame", "Time", "X", "Y", "Z"])?;
+        for (frame, offset) in self.frames.iter().enumerate() {
+            let time = if self.fps == 0 {
+                0.0
+            } else {
+                frame as f32 / self.fps as f32
+            };
+            writer.write_record(&[
+                frame.to_string(),
+                time.to_string(),
+                offset.x.to_string(),
+                offset.y.to_string(),
+                offset.z.to_string(),
+            ])?;
+        }
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+fn root_positions(zmo: &ZMO, bone: u32) -> Option<&Vec<Vector3<f32>>> {
+    zmo.channels
+        .iter()
+        .filter(|c| c.typ == ChannelType::Position && c.index == bone)
+        .find_map(|c| match &c.frames {
+            ChannelData::Position(v) => Some(v),
+            _ => None,
+        })
+}
+
+/// Pin the root bone at its position in the first frame, returning `false`
+/// if the motion has no position channel for it
+pub fn make_in_place(zmo: &mut ZMO, bone: u32) -> bool {
+    let channel = zmo
+        .channels
+        .iter_mut()
+        .find(|c| c.typ == ChannelType::Position && c.index == bone);
+    let positions = match channel.and_then(|c| c.position_frames()) {
+        Some(positions) => positions,
+        None => return false,
+    };
+    if let Some(&first) = positions.first() {
+        for position in positions.iter_mut() {
+            *position = first;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zmo::Channel;
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_root_motion() {
+        let mut zmo = ZMO::new();
+        zmo.fps = 10;
+        zmo.frames = 3;
+        let mut rotation = Channel::from(ChannelType::Rotation);
+        rotation.frames = ChannelData::Rotation(vec![Default::default(); 3]);
+        let mut position = Channel::from(ChannelType::Position);
+        position.frames = ChannelData::Position(
+            [0.0, 50.0, 120.0]
+                .iter()
+                .map(|&y| Vector3 {
+                    x: 5.0,
+                    y,
+                    z: 90.0 + y / 10.0,
+                })
+                .collect(),
+        );
+        zmo.channels = vec![rotation, position];
+
+        assert_eq!(RootMotion::from_zmo(&zmo, 1), None);
+        let motion = RootMotion::from_zmo(&zmo, 0).unwrap();
+        assert_eq!(motion.fps, 10);
+        assert_eq!(
+            motion.frames[2],
+            Vector3 {
+                x: 0.0,
+                y: 120.0,
+                z: 12.0
+            }
+        );
+
+        let csv = motion.to_csv(&CsvDialect::default()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "Frame,Time,X,Y,Z");
+        assert_eq!(lines[2], "1,0.1,0,50,5");
+
+        let mut converted = RootMotion::from_zmo(&zmo, 0).unwrap();
+        converted.convert_axes(&Axes::default());
+        assert_eq!(
+            converted.frames[2],
+            Vector3 {
+                x: 0.0,
+                y: 0.12,
+                z: -1.2
+            }
+        );
+
+        assert!(!make_in_place(&mut zmo, 1));
+        assert!(make_in_place(&mut zmo, 0));
+        let positions = zmo.channels[1].position_frames().unwrap();
+        assert_eq!(
+            positions[2],
+            Vector3 {
+                x: 5.0,
+                y: 0.0,
+                z: 90.0
+            }
+        );
+        // The root doesn't move anymore
+        let motion = RootMotion::from_zmo(&zmo, 0).unwrap();
+        assert!(motion.frames.iter().all(|v| *v == Vector3::default()));
+    }
+}
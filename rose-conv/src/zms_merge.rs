@@ -0,0 +1,154 @@
+//! Merging of ZMS meshes
+//!
+//! Objects made of many parts are drawn with a call per part. Merging the
+//! meshes of the parts into one lets engines batch them: the vertices of the
+//! meshes are appended and the indices of every mesh offset past the vertices
+//! before it. Vertex attributes are only kept if every mesh has them.
+//!
+//! Skinned meshes reference the bones of the skeleton through their own bone
+//! table, the tables are merged and the bone indices of the vertices remapped
+//! to the merged table.
+//!
+//! The material groups of a mesh are the face counts of its faces drawn with
+//! each material, in order. The merged mesh can have a group per mesh to
+//! keep a material per source.
+use failure::{bail, Error};
+
+use roselib::files::zms::VertexFormat;
+use roselib::files::ZMS;
+use roselib::io::RoseFile;
+use roselib::utils::Vector3;
+
+use crate::bounds;
+
+/// Merge meshes into one, with a material group per mesh if
+/// `material_groups` is set or the material groups of the meshes otherwise
+pub fn merge(meshes: &[ZMS], material_groups: bool) -> Result<ZMS, Error> {
+    let first = match meshes.first() {
+        Some(first) => first,
+        None => bail!("No meshes to merge"),
+    };
+    let skinned = meshes.iter().filter(|zms| zms.bones_enabled()).count();
+    if skinned != 0 && skinned != meshes.len() {
+        bail!("Skinned meshes can't be merged with static meshes");
+    }
+
+    let mut merged = ZMS::new();
+    merged.identifier = first.identifier.clone();
+    merged.pool = first.pool;
+    merged.format = meshes.iter().fold(!0, |format, zms| format & zms.format);
+
+    for zms in meshes {
+        let offset = merged.vertices.len();
+        if offset + zms.vertices.len() > i16::MAX as usize + 1 {
+            bail!(
+                "Too many vertices for a mesh: {}",
+                offset + zms.vertices.len()
+            );
+        }
+
+        // Index of every bone of the mesh in the merged bone table
+        let bones: Vec<i16> = zms
+            .bones
+            .iter()
+            .map(|bone| match merged.bones.iter().position(|b| b == bone) {
+                Some(idx) => idx as i16,
+                None => {
+                    merged.bones.push(*bone);
+                    merged.bones.len() as i16 - 1
+                }
+            })
+            .collect();
+        let remap = |idx: i16| bones.get(idx as usize).copied().unwrap_or(0);
+
+        for v in &zms.vertices {
+            let mut vertex = v.clone();
+            vertex.bone_indices.x = remap(v.bone_indices.x);
+            vertex.bone_indices.y = remap(v.bone_indices.y);
+            vertex.bone_indices.z = remap(v.bone_indices.z);
+            vertex.bone_indices.w = remap(v.bone_indices.w);
+            merged.vertices.push(vertex);
+        }
+        let offset = offset as i16;
+        merged
+            .indices
+            .extend(zms.indices.iter().map(|triangle| Vector3 {
+                x: triangle.x + offset,
+                y: triangle.y + offset,
+                z: triangle.z + offset,
+            }));
+    }
+
+    if material_groups {
+        for zms in meshes {
+            if zms.indices.len() > i16::MAX as usize {
+                bail!("Too many faces for a material group: {}", zms.indices.len());
+            }
+            merged.materials.push(zms.indices.len() as i16);
+        }
+    } else if meshes.iter().all(|zms| !zms.materials.is_empty()) {
+        merged.materials = meshes
+            .iter()
+            .flat_map(|zms| zms.materials.iter().copied())
+            .collect();
+    }
+
+    merged.bounding_box = first.bounding_box;
+    bounds::recompute_mesh(&mut merged);
+    // Bones are kept only if every mesh is skinned
+    if !merged.bones_enabled() {
+        merged.format &= !(VertexFormat::BoneWeight as i32 | VertexFormat::BoneIndex as i32);
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zms::Vertex;
+
+    fn mesh(format: i32, bones: Vec<i16>, x: f32) -> ZMS {
+        let mut zms = ZMS::new();
+        zms.format = format;
+        zms.bones = bones;
+        for i in 0..3 {
+            let mut vertex = Vertex::new();
+            vertex.position = Vector3 {
+                x: x + i as f32,
+                y: 0.0,
+                z: i as f32,
+            };
+            vertex.bone_indices.x = 1;
+            vertex.bone_weights.x = 1.0;
+            zms.vertices.push(vertex);
+        }
+        zms.indices.push(Vector3 { x: 0, y: 1, z: 2 });
+        zms
+    }
+
+    #[test]
+    fn test_merge() {
+        let skinned = VertexFormat::Position as i32
+            | VertexFormat::BoneWeight as i32
+            | VertexFormat::BoneIndex as i32;
+        let a = mesh(skinned | VertexFormat::UV1 as i32, vec![3, 4], 0.0);
+        let b = mesh(skinned, vec![4, 7], 10.0);
+
+        let merged = merge(&[a, b], true).unwrap();
+        assert_eq!(merged.vertices.len(), 6);
+        assert_eq!(merged.indices[1], Vector3 { x: 3, y: 4, z: 5 });
+        // Only the attributes of both meshes are kept
+        assert_eq!(merged.format, skinned);
+        assert_eq!(merged.bones, vec![3, 4, 7]);
+        assert_eq!(merged.vertices[0].bone_indices.x, 1);
+        assert_eq!(merged.vertices[3].bone_indices.x, 2);
+        assert_eq!(merged.materials, vec![1, 1]);
+        assert_eq!(merged.bounding_box.max.x, 12.0);
+
+        let a = mesh(VertexFormat::Position as i32, Vec::new(), 0.0);
+        let b = mesh(skinned, vec![0], 0.0);
+        assert!(merge(&[a, b], false).is_err());
+        assert!(merge(&[], false).is_err());
+    }
+}
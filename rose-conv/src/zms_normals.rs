@@ -0,0 +1,211 @@
+//! Recomputation of the normals of ZMS meshes
+//!
+//! Many meshes of the client have broken normals that look wrong once
+//! exported. Smooth normals are rebuilt from the faces: the normal of a
+//! corner of a face is the sum of the normals of the faces around its
+//! position, weighted by their area, that are within the smoothing angle of
+//! the face. Positions are matched exactly, so faces split at UV seams are
+//! still smoothed together.
+//!
+//! A vertex shared by faces on both sides of a sharp edge needs a normal per
+//! side, so it is duplicated for every side but the first.
+use std::collections::HashMap;
+
+use failure::{bail, Error};
+
+use roselib::files::zms::VertexFormat;
+use roselib::files::ZMS;
+use roselib::utils::Vector3;
+
+/// Cosine above which two corner normals are the same
+const SAME_NORMAL: f32 = 0.9999;
+
+/// Recompute the normals of a mesh, smoothing faces within `smoothing_angle`
+/// degrees of each other. Returns the number of vertices added along sharp
+/// edges.
+pub fn recompute_normals(zms: &mut ZMS, smoothing_angle: f32) -> Result<usize, Error> {
+    let vertex_count = zms.vertices.len();
+    let faces: Vec<[usize; 3]> = zms
+        .indices
+        .iter()
+        .map(|t| [t.x as usize, t.y as usize, t.z as usize])
+        .collect();
+    if let Some(&i) = faces.iter().flatten().find(|&&i| i >= vertex_count) {
+        bail!("Index {} is past the {} vertices", i, vertex_count);
+    }
+
+    let keys: Vec<[u32; 3]> = zms
+        .vertices
+        .iter()
+        .map(|v| {
+            let p = &v.position;
+            [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]
+        })
+        .collect();
+    // Area weighted normal of every face, and the faces at every position
+    let mut weighted = Vec::with_capacity(faces.len());
+    let mut faces_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (f, face) in faces.iter().enumerate() {
+        let [a, b, c] = face.map(|i| zms.vertices[i].position);
+        weighted.push(cross(&sub(&b, &a), &sub(&c, &a)));
+        for &i in face {
+            let at = faces_at.entry(keys[i]).or_default();
+            if !at.contains(&f) {
+                at.push(f);
+            }
+        }
+    }
+    let unit: Vec<Vector3<f32>> = weighted.iter().map(normalize).collect();
+
+    let cos_limit = smoothing_angle.to_radians().cos();
+    // Normals of every vertex with the index of the vertex holding each one
+    let mut normals: Vec<Vec<(Vector3<f32>, usize)>> = vec![Vec::new(); vertex_count];
+    let mut new_faces = faces.clone();
+    for (f, face) in faces.iter().enumerate() {
+        for (corner, &i) in face.iter().enumerate() {
+            let mut sum = Vector3::default();
+            for &g in &faces_at[&keys[i]] {
+                if g == f || dot(&unit[f], &unit[g]) >= cos_limit {
+                    sum = add(&sum, &weighted[g]);
+                }
+            }
+            // Degenerate faces keep the vertex's normal
+            if dot(&sum, &sum) == 0.0 {
+                continue;
+            }
+            let normal = normalize(&sum);
+
+            let existing = normals[i]
+                .iter()
+                .find(|(n, _)| dot(n, &normal) > SAME_NORMAL)
+                .map(|&(_, index)| index);
+            new_faces[f][corner] = match existing {
+                Some(index) => index,
+                None => {
+                    let index = if normals[i].is_empty() {
+                        i
+                    } else {
+                        let copy = zms.vertices[i].clone();
+                        zms.vertices.push(copy);
+                        zms.vertices.len() - 1
+                    };
+                    zms.vertices[index].normal = normal;
+                    normals[i].push((normal, index));
+                    index
+                }
+            };
+        }
+    }
+
+    if zms.vertices.len() > i16::MAX as usize + 1 {
+        bail!(
+            "Too many vertices after splitting sharp edges: {}",
+            zms.vertices.len()
+        );
+    }
+    for (triangle, face) in zms.indices.iter_mut().zip(&new_faces) {
+        triangle.x = face[0] as i16;
+        triangle.y = face[1] as i16;
+        triangle.z = face[2] as i16;
+    }
+    zms.format |= VertexFormat::Normal as i32;
+    Ok(zms.vertices.len() - vertex_count)
+}
+
+fn add(a: &Vector3<f32>, b: &Vector3<f32>) -> Vector3<f32> {
+    Vector3 {
+        x: a.x + b.x,
+        y: a.y + b.y,
+        z: a.z + b.z,
+    }
+}
+
+fn sub(a: &Vector3<f32>, b: &Vector3<f32>) -> Vector3<f32> {
+    Vector3 {
+        x: a.x - b.x,
+        y: a.y - b.y,
+        z: a.z - b.z,
+    }
+}
+
+fn cross(a: &Vector3<f32>, b: &Vector3<f32>) -> Vector3<f32> {
+    Vector3 {
+        x: a.y * b.z - a.z * b.y,
+        y: a.z * b.x - a.x * b.z,
+        z: a.x * b.y - a.y * b.x,
+    }
+}
+
+fn dot(a: &Vector3<f32>, b: &Vector3<f32>) -> f32 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+fn normalize(v: &Vector3<f32>) -> Vector3<f32> {
+    let len = dot(v, v).sqrt();
+    if len == 0.0 {
+        return *v;
+    }
+    Vector3 {
+        x: v.x / len,
+        y: v.y / len,
+        z: v.z / len,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zms::Vertex;
+    use roselib::io::RoseFile;
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        let mut vertex = Vertex::new();
+        vertex.position = Vector3 { x, y, z };
+        vertex
+    }
+
+    fn triangle(a: i16, b: i16, c: i16) -> Vector3<i16> {
+        Vector3 { x: a, y: b, z: c }
+    }
+
+    #[test]
+    fn test_recompute_normals() {
+        // Two faces folded at a right angle along the shared edge 0-1
+        let mut zms = ZMS::new();
+        zms.format = VertexFormat::Position as i32;
+        zms.vertices = vec![
+            vertex(0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 0.0),
+            vertex(0.0, 1.0, 0.0),
+            vertex(0.0, 0.0, 1.0),
+        ];
+        zms.indices = vec![triangle(0, 1, 2), triangle(1, 0, 3)];
+
+        let mut smooth = ZMS::new();
+        smooth.format = zms.format;
+        smooth.vertices = zms
+            .vertices
+            .iter()
+            .map(|v| vertex(v.position.x, v.position.y, v.position.z))
+            .collect();
+        smooth.indices = vec![triangle(0, 1, 2), triangle(1, 0, 3)];
+        assert_eq!(recompute_normals(&mut smooth, 100.0).unwrap(), 0);
+        assert!(smooth.normals_enabled());
+        let n = &smooth.vertices[0].normal;
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((n.y - half).abs() < 1e-5 && (n.z - half).abs() < 1e-5);
+        assert_eq!(smooth.vertices[2].normal.z, 1.0);
+
+        // The sharp edge gets a vertex per side
+        assert_eq!(recompute_normals(&mut zms, 60.0).unwrap(), 2);
+        assert_eq!(zms.vertices.len(), 6);
+        assert_eq!(zms.vertices[0].normal.z, 1.0);
+        assert_eq!(zms.indices[1], triangle(4, 5, 3));
+        assert_eq!(zms.vertices[4].normal.y, 1.0);
+        assert_eq!(zms.vertices[5].position, zms.vertices[0].position);
+
+        zms.indices.push(triangle(0, 1, 7));
+        assert!(recompute_normals(&mut zms, 60.0).is_err());
+    }
+}
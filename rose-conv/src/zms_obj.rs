@@ -0,0 +1,139 @@
+//! OBJ export of ZMS meshes
+//!
+//! Meshes are written Y-up in meters like glTF exports, with a UV channel
+//! flipped to the bottom-left origin of OBJ. Vertices keep their indices,
+//! every vertex having its own UV and normal.
+//!
+//! The faces of every material group are a group of their own, `<name>_<n>`,
+//! using the material `material_<n>`. OBJ has a single UV channel, the
+//! second one of lightmapped meshes is written as another OBJ with the same
+//! faces.
+use std::fmt::Write;
+
+use roselib::files::zms::Vertex;
+use roselib::files::ZMS;
+use roselib::utils::Vector2;
+
+use crate::gltf;
+
+/// OBJ of a mesh as an object named `name`, with its first UV channel
+pub fn to_obj(name: &str, zms: &ZMS) -> String {
+    to_obj_with_uv(name, zms, 1).unwrap_or_default()
+}
+
+/// OBJ of a mesh as an object named `name`, with its UV channel `channel`
+/// from 1 to 4, `None` if the mesh doesn't have that channel. Without UVs
+/// the first channel has none.
+pub fn to_obj_with_uv(name: &str, zms: &ZMS, channel: usize) -> Option<String> {
+    let uvs: Option<fn(&Vertex) -> &Vector2<f32>> = match channel {
+        1 if zms.uv1_enabled() => Some(|v| &v.uv1),
+        1 => None,
+        2 if zms.uv2_enabled() => Some(|v| &v.uv2),
+        3 if zms.uv3_enabled() => Some(|v| &v.uv3),
+        4 if zms.uv4_enabled() => Some(|v| &v.uv4),
+        _ => return None,
+    };
+
+    let mut obj = format!("# Mesh {}\no {}\n", name, name);
+    for v in &zms.vertices {
+        let [x, y, z] = gltf::position(&v.position);
+        let _ = writeln!(obj, "v {} {} {}", x, y, z);
+    }
+    if let Some(uv) = uvs {
+        for v in &zms.vertices {
+            let uv = uv(v);
+            let _ = writeln!(obj, "vt {} {}", uv.x, 1.0 - uv.y);
+        }
+    }
+    let uvs = uvs.is_some();
+    let normals = zms.normals_enabled();
+    if normals {
+        for v in &zms.vertices {
+            let [x, y, z] = gltf::position(&v.normal);
+            let _ = writeln!(obj, "vn {} {} {}", x, y, z);
+        }
+    }
+
+    let corner = |i: i16| {
+        let i = i as i32 + 1;
+        match (uvs, normals) {
+            (true, true) => format!("{}/{}/{}", i, i, i),
+            (true, false) => format!("{}/{}", i, i),
+            (false, true) => format!("{}//{}", i, i),
+            (false, false) => i.to_string(),
+        }
+    };
+    // Material groups are face counts in face order
+    let mut group_starts = Vec::with_capacity(zms.materials.len());
+    let mut start = 0;
+    for &count in &zms.materials {
+        group_starts.push(start);
+        start += count.max(0) as usize;
+    }
+    for (f, t) in zms.indices.iter().enumerate() {
+        for (group, _) in group_starts.iter().enumerate().filter(|(_, &s)| s == f) {
+            let _ = writeln!(obj, "g {}_{}\nusemtl material_{}", name, group, group);
+        }
+        let _ = writeln!(obj, "f {} {} {}", corner(t.x), corner(t.y), corner(t.z));
+    }
+    Some(obj)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zms::VertexFormat;
+    use roselib::io::RoseFile;
+    use roselib::utils::Vector3;
+
+    #[test]
+    fn test_to_obj() {
+        let mut zms = ZMS::new();
+        zms.format = VertexFormat::Position as i32 | VertexFormat::UV1 as i32;
+        for (x, y) in &[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)] {
+            let mut vertex = Vertex::new();
+            vertex.position = Vector3 {
+                x: *x,
+                y: *y,
+                z: 2.0,
+            };
+            vertex.uv1 = Vector2 { x: *x, y: 0.25 };
+            zms.vertices.push(vertex);
+        }
+        zms.indices.push(Vector3 { x: 0, y: 1, z: 2 });
+
+        let obj = to_obj("BOX_lod", &zms);
+        let lines: Vec<&str> = obj.lines().collect();
+        assert_eq!(lines[1], "o BOX_lod");
+        assert_eq!(lines[4], "v 0 2 -1");
+        assert_eq!(lines[5], "vt 0 0.75");
+        assert_eq!(lines[8], "f 1/1 2/2 3/3");
+
+        zms.format |= VertexFormat::Normal as i32;
+        assert!(to_obj("BOX_lod", &zms).ends_with("f 1/1/1 2/2/2 3/3/3\n"));
+
+        // A group per material group, with the second UV channel
+        zms.format |= VertexFormat::UV2 as i32;
+        for v in zms.vertices.iter_mut() {
+            v.uv2 = Vector2 { x: 0.5, y: 0.5 };
+        }
+        zms.indices.push(Vector3 { x: 2, y: 1, z: 0 });
+        zms.materials = vec![1, 1];
+        let obj = to_obj_with_uv("BOX_lod", &zms, 2).unwrap();
+        let lines: Vec<&str> = obj.lines().collect();
+        assert_eq!(lines[5], "vt 0.5 0.5");
+        assert_eq!(
+            &lines[11..],
+            &[
+                "g BOX_lod_0",
+                "usemtl material_0",
+                "f 1/1/1 2/2/2 3/3/3",
+                "g BOX_lod_1",
+                "usemtl material_1",
+                "f 3/3/3 2/2/2 1/1/1",
+            ]
+        );
+        assert!(to_obj_with_uv("BOX_lod", &zms, 3).is_none());
+    }
+}
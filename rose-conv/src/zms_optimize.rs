@@ -0,0 +1,308 @@
+//! Vertex cache optimization of ZMS meshes
+//!
+//! GPUs keep the last transformed vertices in a small cache, faces reusing
+//! them skip the vertex shader. Faces are reordered with Tom Forsyth's linear
+//! speed vertex cache optimization: the next face is the one whose vertices
+//! score best, favoring vertices in the cache and vertices with few faces
+//! left. Vertices are then reordered in the order the faces first use them so
+//! they're fetched sequentially.
+//!
+//! Faces are only reordered within their material group, the groups keep
+//! their face counts.
+use std::collections::VecDeque;
+
+use roselib::files::ZMS;
+use roselib::utils::Vector3;
+
+/// Size of the simulated vertex cache
+pub const CACHE_SIZE: usize = 32;
+
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_FACE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Reorder the faces and vertices of a mesh for the vertex cache
+pub fn optimize(zms: &mut ZMS) {
+    let vertex_count = zms.vertices.len();
+    let faces: Vec<[usize; 3]> = zms
+        .indices
+        .iter()
+        .map(|t| [t.x as usize, t.y as usize, t.z as usize])
+        .collect();
+    if faces.iter().flatten().any(|&i| i >= vertex_count) {
+        return;
+    }
+
+    // Face ranges of the material groups, the rest of the faces last
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for &count in &zms.materials {
+        let end = (start + count.max(0) as usize).min(faces.len());
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges.push(start..faces.len());
+
+    let mut order = Vec::with_capacity(faces.len());
+    for range in ranges {
+        let offset = range.start;
+        let group: Vec<[usize; 3]> = faces[range].to_vec();
+        order.extend(
+            reorder_faces(&group, vertex_count)
+                .into_iter()
+                .map(|f| f + offset),
+        );
+    }
+
+    // Vertices in the order of their first use, unused ones last
+    let mut remap = vec![None; vertex_count];
+    let mut vertex_order = Vec::with_capacity(vertex_count);
+    for &f in &order {
+        for &i in &faces[f] {
+            if remap[i].is_none() {
+                remap[i] = Some(vertex_order.len());
+                vertex_order.push(i);
+            }
+        }
+    }
+    for (i, index) in remap.iter_mut().enumerate() {
+        if index.is_none() {
+            *index = Some(vertex_order.len());
+            vertex_order.push(i);
+        }
+    }
+    let remap: Vec<usize> = remap.into_iter().flatten().collect();
+
+    zms.vertices = vertex_order
+        .iter()
+        .map(|&i| zms.vertices[i].clone())
+        .collect();
+    zms.indices = order
+        .iter()
+        .map(|&f| Vector3 {
+            x: remap[faces[f][0]] as i16,
+            y: remap[faces[f][1]] as i16,
+            z: remap[faces[f][2]] as i16,
+        })
+        .collect();
+    for i in zms.strips.iter_mut() {
+        if let Some(&index) = remap.get(*i as usize) {
+            *i = index as i16;
+        }
+    }
+}
+
+/// Order of the faces for the vertex cache
+fn reorder_faces(faces: &[[usize; 3]], vertex_count: usize) -> Vec<usize> {
+    let mut vertex_faces = vec![Vec::new(); vertex_count];
+    for (f, face) in faces.iter().enumerate() {
+        for &i in face {
+            vertex_faces[i].push(f);
+        }
+    }
+    let mut remaining: Vec<usize> = vertex_faces.iter().map(Vec::len).collect();
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = (0..vertex_count)
+        .map(|i| vertex_score(None, remaining[i]))
+        .collect();
+    let mut face_scores: Vec<f32> = faces
+        .iter()
+        .map(|face| face.iter().map(|&i| vertex_scores[i]).sum())
+        .collect();
+    let mut emitted = vec![false; faces.len()];
+
+    let mut order = Vec::with_capacity(faces.len());
+    let mut cache: Vec<usize> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut next_unemitted = 0;
+    let mut best = best_face(&face_scores, &emitted, 0..faces.len());
+    while let Some(f) = best {
+        emitted[f] = true;
+        order.push(f);
+
+        for &i in &faces[f] {
+            remaining[i] -= 1;
+            if let Some(pos) = cache.iter().position(|&c| c == i) {
+                cache.remove(pos);
+            }
+        }
+        for &i in faces[f].iter().rev() {
+            cache.insert(0, i);
+        }
+        let evicted: Vec<usize> = if cache.len() > CACHE_SIZE {
+            cache.split_off(CACHE_SIZE)
+        } else {
+            Vec::new()
+        };
+        for &i in &evicted {
+            cache_position[i] = None;
+        }
+        for (pos, &i) in cache.iter().enumerate() {
+            cache_position[i] = Some(pos);
+        }
+
+        // Rescore the vertices whose position changed and their faces
+        let mut candidates = Vec::new();
+        for &i in cache.iter().chain(&evicted) {
+            vertex_scores[i] = vertex_score(cache_position[i], remaining[i]);
+        }
+        for &i in cache.iter().chain(&evicted) {
+            for &g in &vertex_faces[i] {
+                if !emitted[g] {
+                    face_scores[g] = faces[g].iter().map(|&v| vertex_scores[v]).sum();
+                    if cache_position[i].is_some() {
+                        candidates.push(g);
+                    }
+                }
+            }
+        }
+
+        best = best_face(&face_scores, &emitted, candidates.into_iter());
+        if best.is_none() {
+            // Nothing in the cache, start over from the next face
+            while next_unemitted < faces.len() && emitted[next_unemitted] {
+                next_unemitted += 1;
+            }
+            if next_unemitted < faces.len() {
+                best = best_face(&face_scores, &emitted, next_unemitted..faces.len());
+            }
+        }
+    }
+    order
+}
+
+fn best_face<I>(scores: &[f32], emitted: &[bool], faces: I) -> Option<usize>
+where
+    I: Iterator<Item = usize>,
+{
+    let mut best: Option<usize> = None;
+    for f in faces {
+        if emitted[f] {
+            continue;
+        }
+        if best.is_none_or(|b| scores[f] > scores[b]) {
+            best = Some(f);
+        }
+    }
+    best
+}
+
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        // The vertices of the last face were just used, whatever their order
+        Some(pos) if pos < 3 => LAST_FACE_SCORE,
+        Some(pos) => {
+            let scale = 1.0 / (CACHE_SIZE - 3) as f32;
+            (1.0 - (pos - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+    };
+    // Vertices with few faces left are finished first
+    cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/// Average number of vertices transformed per face with a FIFO cache of
+/// `cache_size` vertices, between 0.5 and 3 with lower being better
+pub fn acmr(zms: &ZMS, cache_size: usize) -> f32 {
+    if zms.indices.is_empty() {
+        return 0.0;
+    }
+    let mut cache = VecDeque::new();
+    let mut misses = 0;
+    for t in &zms.indices {
+        for &i in &[t.x, t.y, t.z] {
+            if !cache.contains(&i) {
+                misses += 1;
+                cache.push_back(i);
+                if cache.len() > cache_size {
+                    cache.pop_front();
+                }
+            }
+        }
+    }
+    misses as f32 / zms.indices.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zms::Vertex;
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_optimize() {
+        // Grid of 40 by 40 cells with its faces in a scattered order
+        let n = 40;
+        let mut zms = ZMS::new();
+        for row in 0..=n {
+            for col in 0..=n {
+                let mut vertex = Vertex::new();
+                vertex.position = Vector3 {
+                    x: col as f32,
+                    y: row as f32,
+                    z: 0.0,
+                };
+                zms.vertices.push(vertex);
+            }
+        }
+        let stride = n + 1;
+        let mut faces = Vec::new();
+        for row in 0..n {
+            for col in 0..n {
+                let i = row * stride + col;
+                faces.push([i, i + 1, i + stride]);
+                faces.push([i + 1, i + stride + 1, i + stride]);
+            }
+        }
+        let count = faces.len();
+        for f in 0..count {
+            let [a, b, c] = faces[f * 37 % count];
+            zms.indices.push(Vector3 {
+                x: a as i16,
+                y: b as i16,
+                z: c as i16,
+            });
+        }
+        zms.materials = vec![(count / 2) as i16, (count / 2) as i16];
+        let positions = |zms: &ZMS, t: &Vector3<i16>| {
+            let mut face: Vec<(i32, i32)> = [t.x, t.y, t.z]
+                .iter()
+                .map(|&i| {
+                    let p = &zms.vertices[i as usize].position;
+                    (p.x as i32, p.y as i32)
+                })
+                .collect();
+            // Same face whatever corner it starts at
+            let first = (0..3).min_by_key(|&i| face[i]).unwrap();
+            face.rotate_left(first);
+            face
+        };
+        let mut groups: Vec<Vec<Vec<(i32, i32)>>> = zms
+            .indices
+            .chunks(count / 2)
+            .map(|group| group.iter().map(|t| positions(&zms, t)).collect())
+            .collect();
+
+        let before = acmr(&zms, CACHE_SIZE);
+        optimize(&mut zms);
+        let after = acmr(&zms, CACHE_SIZE);
+        assert!(before > 2.0);
+        assert!(after < 1.0);
+        assert_eq!(zms.indices.len(), count);
+
+        // The faces stay in their group
+        for (group, faces) in groups.iter_mut().zip(zms.indices.chunks(count / 2)) {
+            let mut optimized: Vec<Vec<(i32, i32)>> =
+                faces.iter().map(|t| positions(&zms, t)).collect();
+            group.sort();
+            optimized.sort();
+            assert_eq!(*group, optimized);
+        }
+        // Vertices are in the order of their first use
+        assert_eq!(zms.indices[0], Vector3 { x: 0, y: 1, z: 2 });
+    }
+}
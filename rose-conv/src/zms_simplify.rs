@@ -0,0 +1,385 @@
+//! Simplification of ZMS meshes
+//!
+//! Lower detail meshes for distant objects are made by quadric decimation
+//! (Garland and Heckbert): every vertex accumulates the planes of its faces,
+//! and the edge whose collapse moves its vertex the least from those planes
+//! is collapsed first until few enough faces are left.
+//!
+//! Edges are collapsed onto one of their vertices rather than a new position,
+//! so the remaining vertices keep their UVs, colors and bone weights. ZMS
+//! vertices are split at UV seams, which leaves the edges along seams and the
+//! borders of the mesh with a single face: the vertices of those edges never
+//! move, keeping the seams and outlines intact. Collapses that would flip a
+//! face are skipped.
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use roselib::files::ZMS;
+use roselib::utils::Vector3;
+
+/// Symmetric 4x4 matrix of the squared distance to a set of planes, as its
+/// upper triangle
+type Quadric = [f64; 10];
+
+/// Collapse of the vertex `from` onto the vertex `to`
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    /// Versions of both vertices when the collapse was queued
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Collapse) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Collapse) -> Ordering {
+        // Cheapest first in the max-heap
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Simplify a mesh down to `ratio` of its faces, or as close as its seams
+/// and borders allow. Returns the number of faces removed.
+pub fn simplify(zms: &mut ZMS, ratio: f32) -> usize {
+    let vertex_count = zms.vertices.len();
+    let mut faces: Vec<[usize; 3]> = zms
+        .indices
+        .iter()
+        .map(|t| [t.x as usize, t.y as usize, t.z as usize])
+        .collect();
+    let face_count = faces.len();
+    let target = (face_count as f32 * ratio.clamp(0.0, 1.0)).ceil() as usize;
+    let mut alive: Vec<bool> = faces
+        .iter()
+        .map(|f| f.iter().all(|&i| i < vertex_count))
+        .collect();
+    let positions: Vec<[f64; 3]> = zms
+        .vertices
+        .iter()
+        .map(|v| {
+            let p = &v.position;
+            [p.x as f64, p.y as f64, p.z as f64]
+        })
+        .collect();
+
+    let mut quadrics = vec![[0.0; 10]; vertex_count];
+    let mut vertex_faces = vec![Vec::new(); vertex_count];
+    let mut edge_faces: HashMap<(usize, usize), usize> = HashMap::new();
+    for (f, face) in faces.iter().enumerate() {
+        if !alive[f] {
+            continue;
+        }
+        let q = plane_quadric(&positions, face);
+        for (corner, &i) in face.iter().enumerate() {
+            add(&mut quadrics[i], &q);
+            vertex_faces[i].push(f);
+            *edge_faces
+                .entry(edge(i, face[(corner + 1) % 3]))
+                .or_default() += 1;
+        }
+    }
+    let mut locked = vec![false; vertex_count];
+    for (&(a, b), &count) in &edge_faces {
+        if count == 1 {
+            locked[a] = true;
+            locked[b] = true;
+        }
+    }
+
+    let mut versions = vec![0u32; vertex_count];
+    let mut heap = BinaryHeap::new();
+    let push = |heap: &mut BinaryHeap<Collapse>,
+                quadrics: &[Quadric],
+                versions: &[u32],
+                from: usize,
+                to: usize| {
+        if locked[from] {
+            return;
+        }
+        let mut q = quadrics[from];
+        add(&mut q, &quadrics[to]);
+        heap.push(Collapse {
+            cost: error(&q, &positions[to]),
+            from,
+            to,
+            versions: (versions[from], versions[to]),
+        });
+    };
+    for &(a, b) in edge_faces.keys() {
+        push(&mut heap, &quadrics, &versions, a, b);
+        push(&mut heap, &quadrics, &versions, b, a);
+    }
+
+    let mut remaining = alive.iter().filter(|&&a| a).count();
+    while remaining > target {
+        let collapse = match heap.pop() {
+            Some(collapse) => collapse,
+            None => break,
+        };
+        let (from, to) = (collapse.from, collapse.to);
+        if collapse.versions != (versions[from], versions[to]) {
+            continue;
+        }
+        if flips(&faces, &alive, &vertex_faces[from], &positions, from, to) {
+            continue;
+        }
+
+        for f in std::mem::take(&mut vertex_faces[from]) {
+            if !alive[f] {
+                continue;
+            }
+            if faces[f].contains(&to) {
+                alive[f] = false;
+                remaining -= 1;
+            } else {
+                for i in faces[f].iter_mut() {
+                    if *i == from {
+                        *i = to;
+                    }
+                }
+                vertex_faces[to].push(f);
+            }
+        }
+        let q = quadrics[from];
+        add(&mut quadrics[to], &q);
+        versions[from] += 1;
+        versions[to] += 1;
+
+        let mut neighbors: Vec<usize> = vertex_faces[to]
+            .iter()
+            .filter(|&&f| alive[f])
+            .flat_map(|&f| faces[f])
+            .filter(|&i| i != to)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        for &n in &neighbors {
+            versions[n] += 1;
+        }
+        for &n in &neighbors {
+            push(&mut heap, &quadrics, &versions, to, n);
+            push(&mut heap, &quadrics, &versions, n, to);
+            // Other collapses of the neighbor were invalidated
+            for &f in &vertex_faces[n] {
+                if !alive[f] {
+                    continue;
+                }
+                for &m in &faces[f] {
+                    if m != n && m != to {
+                        push(&mut heap, &quadrics, &versions, n, m);
+                    }
+                }
+            }
+        }
+    }
+
+    compact(zms, &faces, &alive);
+    face_count - zms.indices.len()
+}
+
+/// Whether collapsing `from` onto `to` turns any of the faces of `from` over
+fn flips(
+    faces: &[[usize; 3]],
+    alive: &[bool],
+    from_faces: &[usize],
+    positions: &[[f64; 3]],
+    from: usize,
+    to: usize,
+) -> bool {
+    from_faces
+        .iter()
+        .filter(|&&f| alive[f] && !faces[f].contains(&to))
+        .any(|&f| {
+            let before = normal(positions, &faces[f]);
+            let after = normal(positions, &faces[f].map(|i| if i == from { to } else { i }));
+            dot(&before, &after) <= 0.0
+        })
+}
+
+/// Remove the collapsed faces and the vertices no face uses anymore
+fn compact(zms: &mut ZMS, faces: &[[usize; 3]], alive: &[bool]) {
+    // Material groups are face counts in face order
+    let mut group_of_face = Vec::with_capacity(faces.len());
+    for (group, &count) in zms.materials.iter().enumerate() {
+        group_of_face.extend(std::iter::repeat_n(group, count.max(0) as usize));
+    }
+    let mut group_counts = vec![0i16; zms.materials.len()];
+
+    let mut remap = vec![None; zms.vertices.len()];
+    let mut vertices = Vec::new();
+    let old_vertices = std::mem::take(&mut zms.vertices);
+    zms.indices.clear();
+    for (f, face) in faces.iter().enumerate() {
+        if !alive[f] {
+            continue;
+        }
+        let mut triangle = [0i16; 3];
+        for (corner, &i) in face.iter().enumerate() {
+            let index = *remap[i].get_or_insert_with(|| {
+                vertices.push(old_vertices[i].clone());
+                vertices.len() - 1
+            });
+            triangle[corner] = index as i16;
+        }
+        zms.indices.push(Vector3 {
+            x: triangle[0],
+            y: triangle[1],
+            z: triangle[2],
+        });
+        if let Some(&group) = group_of_face.get(f) {
+            group_counts[group] += 1;
+        }
+    }
+    zms.vertices = vertices;
+    zms.materials = group_counts;
+    // Strips index the old faces
+    zms.strips.clear();
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn plane_quadric(positions: &[[f64; 3]], face: &[usize; 3]) -> Quadric {
+    let n = normal(positions, face);
+    let len = dot(&n, &n).sqrt();
+    if len == 0.0 {
+        return [0.0; 10];
+    }
+    let [a, b, c] = [n[0] / len, n[1] / len, n[2] / len];
+    let d = -dot(&[a, b, c], &positions[face[0]]);
+    [
+        a * a,
+        a * b,
+        a * c,
+        a * d,
+        b * b,
+        b * c,
+        b * d,
+        c * c,
+        c * d,
+        d * d,
+    ]
+}
+
+fn add(q: &mut Quadric, other: &Quadric) {
+    for (a, b) in q.iter_mut().zip(other) {
+        *a += b;
+    }
+}
+
+/// Squared distance of a point to the planes of a quadric
+fn error(q: &Quadric, p: &[f64; 3]) -> f64 {
+    let [x, y, z] = *p;
+    q[0] * x * x
+        + 2.0 * q[1] * x * y
+        + 2.0 * q[2] * x * z
+        + 2.0 * q[3] * x
+        + q[4] * y * y
+        + 2.0 * q[5] * y * z
+        + 2.0 * q[6] * y
+        + q[7] * z * z
+        + 2.0 * q[8] * z
+        + q[9]
+}
+
+fn normal(positions: &[[f64; 3]], face: &[usize; 3]) -> [f64; 3] {
+    let [a, b, c] = face.map(|i| positions[i]);
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ]
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zms::Vertex;
+    use roselib::io::RoseFile;
+
+    /// Flat grid of `n` by `n` cells
+    fn grid(n: usize) -> ZMS {
+        let mut zms = ZMS::new();
+        for row in 0..=n {
+            for col in 0..=n {
+                let mut vertex = Vertex::new();
+                vertex.position = Vector3 {
+                    x: col as f32,
+                    y: row as f32,
+                    z: 0.0,
+                };
+                vertex.bone_weights.x = (row * (n + 1) + col) as f32;
+                zms.vertices.push(vertex);
+            }
+        }
+        let stride = (n + 1) as i16;
+        for row in 0..n as i16 {
+            for col in 0..n as i16 {
+                let i = row * stride + col;
+                zms.indices.push(Vector3 {
+                    x: i,
+                    y: i + 1,
+                    z: i + stride,
+                });
+                zms.indices.push(Vector3 {
+                    x: i + 1,
+                    y: i + stride + 1,
+                    z: i + stride,
+                });
+            }
+        }
+        zms.materials = vec![(n * n) as i16, (n * n) as i16];
+        zms
+    }
+
+    #[test]
+    fn test_simplify() {
+        let mut zms = grid(8);
+        let removed = simplify(&mut zms, 0.25);
+        assert_eq!(removed + zms.indices.len(), 128);
+        // The 32 vertices of the border stay, with one inside the grid
+        assert_eq!(zms.indices.len(), 32);
+        assert_eq!(zms.vertices.len(), 33);
+        assert_eq!(zms.materials.iter().sum::<i16>(), 32);
+        // The remaining vertices are untouched
+        for v in &zms.vertices {
+            let p = &v.position;
+            assert_eq!(v.bone_weights.x, p.y * 9.0 + p.x);
+        }
+        // Every face still faces up
+        let positions: Vec<[f64; 3]> = zms
+            .vertices
+            .iter()
+            .map(|v| [v.position.x as f64, v.position.y as f64, 0.0])
+            .collect();
+        for t in &zms.indices {
+            let face = [t.x as usize, t.y as usize, t.z as usize];
+            assert!(normal(&positions, &face)[2] > 0.0);
+        }
+
+        let mut zms = grid(2);
+        assert_eq!(simplify(&mut zms, 1.0), 0);
+        assert_eq!(zms.indices.len(), 8);
+    }
+}
@@ -0,0 +1,309 @@
+//! Skin weights of ZMS meshes
+//!
+//! Every vertex of a skinned mesh is moved by up to 4 bones of the mesh's
+//! bone table, with weights that should add up to 1. Meshes from modelling
+//! tools often have weights that don't, tiny influences or more bones than an
+//! engine can send in a single draw call. Weights are cleaned up by keeping
+//! the strongest influences of every vertex and normalizing them, and meshes
+//! are split into parts that each reference few enough bones.
+use std::collections::BTreeSet;
+
+use failure::{bail, Error};
+
+use roselib::files::zms::Vertex;
+use roselib::files::ZMS;
+use roselib::io::RoseFile;
+use roselib::utils::Vector3;
+
+use crate::bounds;
+
+/// Weights below this are dropped
+const MIN_WEIGHT: f32 = 0.0001;
+
+/// Keep the `max` strongest influences of every vertex and normalize their
+/// weights. Returns the number of vertices changed.
+pub fn limit_influences(zms: &mut ZMS, max: usize) -> usize {
+    if !zms.bones_enabled() {
+        return 0;
+    }
+    let mut changed = 0;
+    for vertex in zms.vertices.iter_mut() {
+        let w = &vertex.bone_weights;
+        let i = &vertex.bone_indices;
+        let mut influences = [(w.x, i.x), (w.y, i.y), (w.z, i.z), (w.w, i.w)];
+        // Strongest first, keeping the order of equal weights
+        influences.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (slot, influence) in influences.iter_mut().enumerate() {
+            if slot >= max || influence.0 < MIN_WEIGHT {
+                *influence = (0.0, 0);
+            }
+        }
+        let total: f32 = influences.iter().map(|(weight, _)| weight).sum();
+        if total > 0.0 {
+            for influence in influences.iter_mut() {
+                influence.0 /= total;
+            }
+        }
+
+        let [(wx, ix), (wy, iy), (wz, iz), (ww, iw)] = influences;
+        let before = (vertex.bone_weights, vertex.bone_indices);
+        vertex.bone_weights.x = wx;
+        vertex.bone_weights.y = wy;
+        vertex.bone_weights.z = wz;
+        vertex.bone_weights.w = ww;
+        vertex.bone_indices.x = ix;
+        vertex.bone_indices.y = iy;
+        vertex.bone_indices.z = iz;
+        vertex.bone_indices.w = iw;
+        if before != (vertex.bone_weights, vertex.bone_indices) {
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Split a mesh into parts referencing at most `bone_limit` bones each, the
+/// mesh itself if it's within the limit
+pub fn split_bones(zms: ZMS, bone_limit: usize) -> Result<Vec<ZMS>, Error> {
+    if !zms.bones_enabled() || zms.bones.len() <= bone_limit || zms.indices.is_empty() {
+        return Ok(vec![zms]);
+    }
+    let vertex_count = zms.vertices.len();
+    let faces: Vec<[usize; 3]> = zms
+        .indices
+        .iter()
+        .map(|t| [t.x as usize, t.y as usize, t.z as usize])
+        .collect();
+    if let Some(&i) = faces.iter().flatten().find(|&&i| i >= vertex_count) {
+        bail!("Index {} is past the {} vertices", i, vertex_count);
+    }
+
+    // Bones of the bone table used by every face
+    let face_bones: Vec<BTreeSet<i16>> = faces
+        .iter()
+        .map(|face| {
+            face.iter()
+                .flat_map(|&i| vertex_bones(&zms.vertices[i]))
+                .collect()
+        })
+        .collect();
+    if let Some(bones) = face_bones.iter().find(|bones| bones.len() > bone_limit) {
+        bail!(
+            "A face uses {} bones, more than the limit of {}",
+            bones.len(),
+            bone_limit
+        );
+    }
+
+    // Fill every part with the faces that still fit, in order
+    let mut part_of_face = vec![None; faces.len()];
+    let mut part_count = 0;
+    let mut left = faces.len();
+    while left > 0 {
+        let mut bones = BTreeSet::new();
+        for (f, face) in face_bones.iter().enumerate() {
+            if part_of_face[f].is_some() || bones.union(face).count() > bone_limit {
+                continue;
+            }
+            bones.extend(face.iter().copied());
+            part_of_face[f] = Some(part_count);
+            left -= 1;
+        }
+        part_count += 1;
+    }
+
+    // Material groups are face counts in face order
+    let mut group_of_face = Vec::with_capacity(faces.len());
+    for (group, &count) in zms.materials.iter().enumerate() {
+        group_of_face.extend(std::iter::repeat_n(group, count.max(0) as usize));
+    }
+
+    let mut parts = Vec::with_capacity(part_count);
+    for part in 0..part_count {
+        let mut mesh = ZMS::new();
+        mesh.identifier = zms.identifier.clone();
+        mesh.format = zms.format;
+        mesh.bounding_box = zms.bounding_box;
+        mesh.pool = zms.pool;
+        mesh.materials = vec![0; zms.materials.len()];
+
+        let mut bone_remap = vec![None; zms.bones.len()];
+        let mut vertex_remap = vec![None; vertex_count];
+        for (f, face) in faces.iter().enumerate() {
+            if part_of_face[f] != Some(part) {
+                continue;
+            }
+            let mut triangle = [0i16; 3];
+            for (corner, &i) in face.iter().enumerate() {
+                let index = match vertex_remap[i] {
+                    Some(index) => index,
+                    None => {
+                        let vertex = remap_vertex(
+                            &zms.vertices[i],
+                            &zms.bones,
+                            &mut bone_remap,
+                            &mut mesh.bones,
+                        );
+                        mesh.vertices.push(vertex);
+                        vertex_remap[i] = Some(mesh.vertices.len() - 1);
+                        mesh.vertices.len() - 1
+                    }
+                };
+                triangle[corner] = index as i16;
+            }
+            mesh.indices.push(Vector3 {
+                x: triangle[0],
+                y: triangle[1],
+                z: triangle[2],
+            });
+            if let Some(&group) = group_of_face.get(f) {
+                mesh.materials[group] += 1;
+            }
+        }
+        bounds::recompute_mesh(&mut mesh);
+        parts.push(mesh);
+    }
+    Ok(parts)
+}
+
+/// Indices in the bone table of the bones moving a vertex
+fn vertex_bones(vertex: &Vertex) -> impl Iterator<Item = i16> {
+    let w = vertex.bone_weights;
+    let i = vertex.bone_indices;
+    vec![(w.x, i.x), (w.y, i.y), (w.z, i.z), (w.w, i.w)]
+        .into_iter()
+        .filter(|&(weight, _)| weight > 0.0)
+        .map(|(_, index)| index)
+}
+
+/// Copy of a vertex with its bone indices in the bone table of a part
+fn remap_vertex(
+    vertex: &Vertex,
+    bones: &[i16],
+    remap: &mut [Option<i16>],
+    part_bones: &mut Vec<i16>,
+) -> Vertex {
+    let mut copy = vertex.clone();
+    let mut index_of = |index: i16, weight: f32| -> i16 {
+        if weight <= 0.0 {
+            return 0;
+        }
+        let slot = match remap.get_mut(index as usize) {
+            Some(slot) => slot,
+            None => return 0,
+        };
+        *slot.get_or_insert_with(|| {
+            part_bones.push(bones[index as usize]);
+            part_bones.len() as i16 - 1
+        })
+    };
+    copy.bone_indices.x = index_of(vertex.bone_indices.x, vertex.bone_weights.x);
+    copy.bone_indices.y = index_of(vertex.bone_indices.y, vertex.bone_weights.y);
+    copy.bone_indices.z = index_of(vertex.bone_indices.z, vertex.bone_weights.z);
+    copy.bone_indices.w = index_of(vertex.bone_indices.w, vertex.bone_weights.w);
+    copy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zms::VertexFormat;
+    use roselib::utils::Vector4;
+
+    fn vertex(weights: [f32; 4], indices: [i16; 4]) -> Vertex {
+        let mut vertex = Vertex::new();
+        vertex.bone_weights = Vector4 {
+            x: weights[0],
+            y: weights[1],
+            z: weights[2],
+            w: weights[3],
+        };
+        vertex.bone_indices = Vector4 {
+            x: indices[0],
+            y: indices[1],
+            z: indices[2],
+            w: indices[3],
+        };
+        vertex
+    }
+
+    fn skinned() -> ZMS {
+        let mut zms = ZMS::new();
+        zms.format = VertexFormat::Position as i32
+            | VertexFormat::BoneWeight as i32
+            | VertexFormat::BoneIndex as i32;
+        zms
+    }
+
+    #[test]
+    fn test_limit_influences() {
+        let mut zms = skinned();
+        zms.vertices = vec![
+            vertex([0.1, 0.5, 0.3, 0.1], [0, 1, 2, 3]),
+            vertex([1.0, 0.0, 0.0, 0.0], [4, 0, 0, 0]),
+            vertex([0.00001, 0.5, 0.0, 0.0], [5, 6, 0, 0]),
+        ];
+        assert_eq!(limit_influences(&mut zms, 2), 2);
+
+        let v = &zms.vertices[0];
+        assert_eq!(
+            v.bone_indices,
+            Vector4 {
+                x: 1,
+                y: 2,
+                z: 0,
+                w: 0
+            }
+        );
+        assert_eq!(v.bone_weights.x, 0.625);
+        assert_eq!(v.bone_weights.y, 0.375);
+        assert_eq!(v.bone_weights.z, 0.0);
+        // Tiny weights are dropped
+        let v = &zms.vertices[2];
+        assert_eq!(v.bone_indices.x, 6);
+        assert_eq!(v.bone_weights.x, 1.0);
+        assert_eq!(v.bone_weights.y, 0.0);
+    }
+
+    #[test]
+    fn test_split_bones() {
+        let mut zms = skinned();
+        zms.bones = vec![10, 11, 12, 13];
+        // A face on bones 0 and 1, then one on bones 2 and 3
+        for bone in 0..4 {
+            zms.vertices
+                .push(vertex([1.0, 0.0, 0.0, 0.0], [bone, 0, 0, 0]));
+            zms.vertices
+                .push(vertex([0.5, 0.5, 0.0, 0.0], [bone, bone, 0, 0]));
+        }
+        zms.indices = vec![
+            Vector3 { x: 0, y: 1, z: 2 },
+            Vector3 { x: 4, y: 5, z: 6 },
+            Vector3 { x: 2, y: 3, z: 1 },
+        ];
+        zms.materials = vec![1, 2];
+
+        let parts = split_bones(zms, 2).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].bones, vec![10, 11]);
+        assert_eq!(parts[0].indices.len(), 2);
+        assert_eq!(parts[0].vertices.len(), 4);
+        assert_eq!(parts[0].materials, vec![1, 1]);
+        assert_eq!(parts[1].bones, vec![12, 13]);
+        assert_eq!(parts[1].vertices[0].bone_indices.x, 0);
+        assert_eq!(parts[1].vertices[2].bone_indices.x, 1);
+        assert_eq!(parts[1].materials, vec![0, 1]);
+
+        let mut zms = skinned();
+        zms.bones = vec![10, 11, 12];
+        zms.vertices = vec![
+            vertex([1.0, 0.0, 0.0, 0.0], [0, 0, 0, 0]),
+            vertex([1.0, 0.0, 0.0, 0.0], [1, 0, 0, 0]),
+            vertex([1.0, 0.0, 0.0, 0.0], [2, 0, 0, 0]),
+        ];
+        zms.indices = vec![Vector3 { x: 0, y: 1, z: 2 }];
+        assert!(split_bones(zms, 2).is_err());
+        assert_eq!(split_bones(skinned(), 2).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,197 @@
+//! Tangents of ZMS meshes
+//!
+//! Normal maps are in the tangent space of the mesh, the tangent pointing
+//! along U and the bitangent along V. The tangents follow the MikkTSpace
+//! conventions used by Blender and glTF: the tangent of a corner of a face is
+//! found from the UV derivatives of the face, weighted by the angle of the
+//! corner and summed per vertex, then made perpendicular to the normal. The
+//! handedness is stored in W, -1 where the UVs are mirrored, with V pointing
+//! up the texture.
+//!
+//! Vertices are already split at UV seams in ZMS files so a vertex never
+//! sits on both sides of a mirror seam.
+use roselib::files::ZMS;
+use roselib::utils::{Vector3, Vector4};
+
+/// Tangents of the vertices of a mesh with their handedness in W, `None` if
+/// the mesh has no normals or UVs
+pub fn tangents(zms: &ZMS) -> Option<Vec<Vector4<f32>>> {
+    if !zms.normals_enabled() || !zms.uv1_enabled() {
+        return None;
+    }
+    let vertex_count = zms.vertices.len();
+    let mut tangents = vec![Vector3::default(); vertex_count];
+    let mut bitangents = vec![Vector3::default(); vertex_count];
+
+    for triangle in &zms.indices {
+        let face = [
+            triangle.x as usize,
+            triangle.y as usize,
+            triangle.z as usize,
+        ];
+        if face.iter().any(|&i| i >= vertex_count) {
+            continue;
+        }
+        let [v0, v1, v2] = face.map(|i| &zms.vertices[i]);
+        let (e1, e2) = (
+            sub(&v1.position, &v0.position),
+            sub(&v2.position, &v0.position),
+        );
+        // V of ZMS UVs points down the texture
+        let (du1, dv1) = (v1.uv1.x - v0.uv1.x, v0.uv1.y - v1.uv1.y);
+        let (du2, dv2) = (v2.uv1.x - v0.uv1.x, v0.uv1.y - v2.uv1.y);
+        let r = du1 * dv2 - du2 * dv1;
+        if r == 0.0 {
+            continue;
+        }
+        let tangent = normalize(&scale(&sub(&scale(&e1, dv2), &scale(&e2, dv1)), 1.0 / r));
+        let bitangent = normalize(&scale(&sub(&scale(&e2, du1), &scale(&e1, du2)), 1.0 / r));
+
+        for corner in 0..3 {
+            let i = face[corner];
+            let p = &zms.vertices[i].position;
+            let a = sub(&zms.vertices[face[(corner + 1) % 3]].position, p);
+            let b = sub(&zms.vertices[face[(corner + 2) % 3]].position, p);
+            let angle = dot(&normalize(&a), &normalize(&b)).clamp(-1.0, 1.0).acos();
+            tangents[i] = add(&tangents[i], &scale(&tangent, angle));
+            bitangents[i] = add(&bitangents[i], &scale(&bitangent, angle));
+        }
+    }
+
+    Some(
+        zms.vertices
+            .iter()
+            .zip(tangents.iter().zip(&bitangents))
+            .map(|(vertex, (t, b))| {
+                let n = &vertex.normal;
+                let mut t = normalize(&sub(t, &scale(n, dot(n, t))));
+                if dot(&t, &t) == 0.0 {
+                    t = perpendicular(n);
+                }
+                let w = if dot(&cross(n, &t), b) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                Vector4 {
+                    x: t.x,
+                    y: t.y,
+                    z: t.z,
+                    w,
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Any unit vector perpendicular to `n`, for vertices without UV derivatives
+fn perpendicular(n: &Vector3<f32>) -> Vector3<f32> {
+    let axis = if n.x.abs() < 0.9 {
+        Vector3 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    } else {
+        Vector3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        }
+    };
+    normalize(&sub(&axis, &scale(n, dot(n, &axis))))
+}
+
+fn add(a: &Vector3<f32>, b: &Vector3<f32>) -> Vector3<f32> {
+    Vector3 {
+        x: a.x + b.x,
+        y: a.y + b.y,
+        z: a.z + b.z,
+    }
+}
+
+fn sub(a: &Vector3<f32>, b: &Vector3<f32>) -> Vector3<f32> {
+    Vector3 {
+        x: a.x - b.x,
+        y: a.y - b.y,
+        z: a.z - b.z,
+    }
+}
+
+fn scale(v: &Vector3<f32>, s: f32) -> Vector3<f32> {
+    Vector3 {
+        x: v.x * s,
+        y: v.y * s,
+        z: v.z * s,
+    }
+}
+
+fn cross(a: &Vector3<f32>, b: &Vector3<f32>) -> Vector3<f32> {
+    Vector3 {
+        x: a.y * b.z - a.z * b.y,
+        y: a.z * b.x - a.x * b.z,
+        z: a.x * b.y - a.y * b.x,
+    }
+}
+
+fn dot(a: &Vector3<f32>, b: &Vector3<f32>) -> f32 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+fn normalize(v: &Vector3<f32>) -> Vector3<f32> {
+    let len = dot(v, v).sqrt();
+    if len == 0.0 {
+        return *v;
+    }
+    scale(v, 1.0 / len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zms::{Vertex, VertexFormat};
+    use roselib::io::RoseFile;
+    use roselib::utils::Vector2;
+
+    fn vertex(x: f32, y: f32, u: f32, v: f32) -> Vertex {
+        let mut vertex = Vertex::new();
+        vertex.position = Vector3 { x, y, z: 0.0 };
+        vertex.normal = Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        vertex.uv1 = Vector2 { x: u, y: v };
+        vertex
+    }
+
+    #[test]
+    fn test_tangents() {
+        let mut zms = ZMS::new();
+        zms.format = VertexFormat::Position as i32 | VertexFormat::Normal as i32;
+        zms.vertices = vec![
+            vertex(0.0, 0.0, 0.0, 1.0),
+            vertex(1.0, 0.0, 1.0, 1.0),
+            vertex(0.0, 1.0, 0.0, 0.0),
+            // The same face with its UVs mirrored
+            vertex(0.0, 0.0, 1.0, 1.0),
+            vertex(1.0, 0.0, 0.0, 1.0),
+            vertex(0.0, 1.0, 1.0, 0.0),
+        ];
+        zms.indices = vec![Vector3 { x: 0, y: 1, z: 2 }, Vector3 { x: 3, y: 4, z: 5 }];
+        assert_eq!(tangents(&zms), None);
+
+        zms.format |= VertexFormat::UV1 as i32;
+        let tangents = tangents(&zms).unwrap();
+        let expected = |x: f32, w: f32| Vector4 {
+            x,
+            y: 0.0,
+            z: 0.0,
+            w,
+        };
+        assert_eq!(tangents[0], expected(1.0, 1.0));
+        assert_eq!(tangents[2], expected(1.0, 1.0));
+        assert_eq!(tangents[4], expected(-1.0, -1.0));
+    }
+}
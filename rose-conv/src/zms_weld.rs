@@ -0,0 +1,208 @@
+//! Welding of duplicate ZMS vertices
+//!
+//! Older export tools wrote a vertex per corner of every face, or split
+//! vertices whose normals differ by rounding errors, which bloats meshes and
+//! shows seams in their shading. Vertices whose attributes are all within a
+//! tolerance of each other are welded into the first of them, with the
+//! average of their normals, and the faces are reindexed. Faces left with two
+//! corners on the same vertex are removed.
+use std::collections::HashMap;
+
+use roselib::files::zms::Vertex;
+use roselib::files::ZMS;
+use roselib::utils::Vector3;
+
+/// Smallest cell of the grid of positions, for exact welding
+const MIN_CELL: f32 = 1e-6;
+
+/// Weld the vertices of a mesh whose enabled attributes are within
+/// `tolerance` of each other, component by component. Returns the number of
+/// vertices removed.
+pub fn weld(zms: &mut ZMS, tolerance: f32) -> usize {
+    let cell = tolerance.max(MIN_CELL);
+    let cell_of = |p: &Vector3<f32>| {
+        [
+            (p.x / cell).floor() as i64,
+            (p.y / cell).floor() as i64,
+            (p.z / cell).floor() as i64,
+        ]
+    };
+
+    // Index of the welded vertex of every vertex
+    let mut remap = Vec::with_capacity(zms.vertices.len());
+    let mut welded: Vec<usize> = Vec::new();
+    let mut normals: Vec<Vector3<f32>> = Vec::new();
+    let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    for (i, vertex) in zms.vertices.iter().enumerate() {
+        let [x, y, z] = cell_of(&vertex.position);
+        let mut found = None;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let candidates = match grid.get(&[x + dx, y + dy, z + dz]) {
+                        Some(candidates) => candidates,
+                        None => continue,
+                    };
+                    for &w in candidates {
+                        if same_vertex(zms, &zms.vertices[welded[w]], vertex, tolerance) {
+                            found = Some(w);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+        let w = match found {
+            Some(w) => {
+                let n = &mut normals[w];
+                n.x += vertex.normal.x;
+                n.y += vertex.normal.y;
+                n.z += vertex.normal.z;
+                w
+            }
+            None => {
+                welded.push(i);
+                normals.push(vertex.normal);
+                grid.entry([x, y, z]).or_default().push(welded.len() - 1);
+                welded.len() - 1
+            }
+        };
+        remap.push(w);
+    }
+
+    let removed = zms.vertices.len() - welded.len();
+    if removed == 0 {
+        return 0;
+    }
+
+    let normals_enabled = zms.normals_enabled();
+    let mut vertices: Vec<Vertex> = welded.iter().map(|&i| zms.vertices[i].clone()).collect();
+    if normals_enabled {
+        for (vertex, n) in vertices.iter_mut().zip(&normals) {
+            let len = (n.x * n.x + n.y * n.y + n.z * n.z).sqrt();
+            if len > 0.0 {
+                vertex.normal = Vector3 {
+                    x: n.x / len,
+                    y: n.y / len,
+                    z: n.z / len,
+                };
+            }
+        }
+    }
+    let old_count = zms.vertices.len();
+    zms.vertices = vertices;
+
+    let reindex = |i: i16| match remap.get(i as usize) {
+        Some(&w) if (i as usize) < old_count => w as i16,
+        _ => i,
+    };
+    // Material groups are face counts in face order
+    let mut group_of_face = Vec::with_capacity(zms.indices.len());
+    for (group, &count) in zms.materials.iter().enumerate() {
+        group_of_face.extend(std::iter::repeat_n(group, count.max(0) as usize));
+    }
+    let mut group_counts = vec![0i16; zms.materials.len()];
+    let indices = std::mem::take(&mut zms.indices);
+    for (f, t) in indices.iter().enumerate() {
+        let (a, b, c) = (reindex(t.x), reindex(t.y), reindex(t.z));
+        if a == b || b == c || a == c {
+            continue;
+        }
+        zms.indices.push(Vector3 { x: a, y: b, z: c });
+        if let Some(&group) = group_of_face.get(f) {
+            group_counts[group] += 1;
+        }
+    }
+    zms.materials = group_counts;
+    for i in zms.strips.iter_mut() {
+        *i = reindex(*i);
+    }
+    removed
+}
+
+fn same_vertex(zms: &ZMS, a: &Vertex, b: &Vertex, tolerance: f32) -> bool {
+    let close = |x: f32, y: f32| (x - y).abs() <= tolerance;
+    let close3 =
+        |u: &Vector3<f32>, v: &Vector3<f32>| close(u.x, v.x) && close(u.y, v.y) && close(u.z, v.z);
+
+    close3(&a.position, &b.position)
+        && (!zms.normals_enabled() || close3(&a.normal, &b.normal))
+        && (!zms.colors_enabled()
+            || (close(a.color.r, b.color.r)
+                && close(a.color.g, b.color.g)
+                && close(a.color.b, b.color.b)
+                && close(a.color.a, b.color.a)))
+        && (!zms.bones_enabled()
+            || (a.bone_indices == b.bone_indices
+                && close(a.bone_weights.x, b.bone_weights.x)
+                && close(a.bone_weights.y, b.bone_weights.y)
+                && close(a.bone_weights.z, b.bone_weights.z)
+                && close(a.bone_weights.w, b.bone_weights.w)))
+        && (!zms.tangents_enabled() || close3(&a.tangent, &b.tangent))
+        && (!zms.uv1_enabled() || (close(a.uv1.x, b.uv1.x) && close(a.uv1.y, b.uv1.y)))
+        && (!zms.uv2_enabled() || (close(a.uv2.x, b.uv2.x) && close(a.uv2.y, b.uv2.y)))
+        && (!zms.uv3_enabled() || (close(a.uv3.x, b.uv3.x) && close(a.uv3.y, b.uv3.y)))
+        && (!zms.uv4_enabled() || (close(a.uv4.x, b.uv4.x) && close(a.uv4.y, b.uv4.y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use roselib::files::zms::VertexFormat;
+    use roselib::io::RoseFile;
+    use roselib::utils::Vector2;
+
+    fn vertex(x: f32, y: f32, u: f32, nx: f32) -> Vertex {
+        let mut vertex = Vertex::new();
+        vertex.position = Vector3 { x, y, z: 0.0 };
+        vertex.normal = Vector3 {
+            x: nx,
+            y: 0.0,
+            z: 1.0,
+        };
+        vertex.uv1 = Vector2 { x: u, y: 0.0 };
+        vertex
+    }
+
+    #[test]
+    fn test_weld() {
+        // Two triangles with a vertex per corner
+        let mut zms = ZMS::new();
+        zms.format =
+            VertexFormat::Position as i32 | VertexFormat::Normal as i32 | VertexFormat::UV1 as i32;
+        zms.vertices = vec![
+            vertex(0.0, 0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 1.0, 0.0),
+            vertex(0.0, 1.0, 0.0, 0.0),
+            vertex(1.0, 0.00001, 1.0, 0.00005),
+            vertex(1.0, 1.0, 1.0, 0.0),
+            vertex(0.0, 1.0, 0.0, 0.0),
+            // Same position but another UV
+            vertex(0.0, 0.0, 0.5, 0.0),
+        ];
+        zms.indices = vec![
+            Vector3 { x: 0, y: 1, z: 2 },
+            Vector3 { x: 3, y: 4, z: 5 },
+            Vector3 { x: 6, y: 1, z: 2 },
+            Vector3 { x: 0, y: 2, z: 5 },
+        ];
+        zms.materials = vec![2, 2];
+
+        assert_eq!(weld(&mut zms, 0.0001), 2);
+        assert_eq!(zms.vertices.len(), 5);
+        assert_eq!(
+            zms.indices,
+            vec![
+                Vector3 { x: 0, y: 1, z: 2 },
+                Vector3 { x: 1, y: 3, z: 2 },
+                Vector3 { x: 4, y: 1, z: 2 },
+            ]
+        );
+        // The degenerate face was removed from its group
+        assert_eq!(zms.materials, vec![2, 1]);
+        assert!(zms.vertices[1].normal.x > 0.0);
+
+        assert_eq!(weld(&mut zms, 0.0), 0);
+    }
+}
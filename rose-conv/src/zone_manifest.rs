@@ -0,0 +1,294 @@
+//! Manifest of a zone's map files
+//!
+//! Combines the metadata of the ZON with a summary of every map block (which
+//! of its HIM, TIL and IFO files exist and what its IFO places), the extents
+//! of the blocks and the files the zone references: its ZSCs, the tile
+//! textures of the ZON and the textures of the objects placed by the IFOs.
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use roselib::files::zon::ZoneType;
+use roselib::files::{IFO, ZON, ZSC};
+use roselib::utils::{Vector2, Vector3};
+
+use crate::ifo_instances;
+
+#[derive(Debug, Serialize)]
+pub struct ZoneManifest {
+    /// Name of the map directory
+    pub map: String,
+    pub name: String,
+    pub zone_type: ZoneType,
+    pub width: i32,
+    pub height: i32,
+    pub grid_count: i32,
+    pub grid_size: f32,
+    pub start_position: Vector2<i32>,
+    pub is_underground: bool,
+    pub background_music: String,
+    pub sky: String,
+    pub economy: Economy,
+    pub event_points: Vec<EventPoint>,
+    pub tile_count: usize,
+    /// Block coordinates of the first and last blocks, `None` without blocks
+    pub extents: Option<Extents>,
+    pub blocks: Vec<BlockSummary>,
+    /// Client paths of the decoration and construction ZSCs, `None` if they
+    /// aren't known
+    pub scenes: Option<ZoneScenes>,
+    /// Tile textures of the ZON
+    pub textures: Vec<String>,
+    /// Textures of the objects placed by the IFOs, empty if the ZSCs aren't
+    /// known
+    pub object_textures: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Economy {
+    pub tick_rate: i32,
+    pub population_base: i32,
+    pub population_growth_rate: i32,
+    pub metal_consumption: i32,
+    pub stone_consumption: i32,
+    pub wood_consumption: i32,
+    pub leather_consumption: i32,
+    pub cloth_consumption: i32,
+    pub alchemy_consumption: i32,
+    pub chemical_consumption: i32,
+    pub medicine_consumption: i32,
+    pub food_consumption: i32,
+}
+
+/// Named position of the zone, e.g. where players start or arrive by warp
+#[derive(Debug, Serialize)]
+pub struct EventPoint {
+    pub name: String,
+    pub position: Vector3<f32>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Extents {
+    pub min: [u32; 2],
+    pub max: [u32; 2],
+}
+
+#[derive(Debug, Serialize)]
+pub struct ZoneScenes {
+    pub decorations: String,
+    pub buildings: String,
+}
+
+/// Files of a map block and the number of each kind of object of its IFO
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct BlockSummary {
+    pub x: u32,
+    pub y: u32,
+    pub has_heightmap: bool,
+    pub has_tiles: bool,
+    pub has_objects: bool,
+    pub decorations: usize,
+    pub buildings: usize,
+    pub npcs: usize,
+    pub monster_spawns: usize,
+    pub sounds: usize,
+    pub effects: usize,
+    pub animations: usize,
+    pub warps: usize,
+    pub waters: usize,
+    pub collision_objects: usize,
+    pub events: usize,
+}
+
+impl BlockSummary {
+    pub fn new(x: u32, y: u32) -> BlockSummary {
+        BlockSummary {
+            x,
+            y,
+            ..Default::default()
+        }
+    }
+
+    /// Count the objects of the block's IFO
+    pub fn set_objects(&mut self, ifo: &IFO) {
+        self.has_objects = true;
+        self.decorations = ifo.objects.len();
+        self.buildings = ifo.buildings.len();
+        self.npcs = ifo.npcs.len();
+        self.monster_spawns = ifo.monster_spawns.len();
+        self.sounds = ifo.sounds.len();
+        self.effects = ifo.effects.len();
+        self.animations = ifo.animations.len();
+        self.warps = ifo.warps.len();
+        self.waters = ifo.waters.len();
+        self.collision_objects = ifo.collision_objects.len();
+        self.events = ifo.events.len();
+    }
+}
+
+impl ZoneManifest {
+    /// Manifest of the ZON of map `map` without blocks or scenes
+    pub fn new(map: &str, zon: ZON) -> ZoneManifest {
+        ZoneManifest {
+            map: map.to_string(),
+            name: zon.name,
+            zone_type: zon.zone_type,
+            width: zon.width,
+            height: zon.height,
+            grid_count: zon.grid_count,
+            grid_size: zon.grid_size,
+            start_position: zon.start_position,
+            is_underground: zon.is_underground,
+            background_music: zon.background_music,
+            sky: zon.sky,
+            economy: Economy {
+                tick_rate: zon.economy_tick_rate,
+                population_base: zon.population_base,
+                population_growth_rate: zon.population_growth_rate,
+                metal_consumption: zon.metal_consumption,
+                stone_consumption: zon.stone_consumption,
+                wood_consumption: zon.wood_consumption,
+                leather_consumption: zon.leather_consumption,
+                cloth_consumption: zon.cloth_consumption,
+                alchemy_consumption: zon.alchemy_consumption,
+                chemical_consumption: zon.chemical_consumption,
+                medicine_consumption: zon.medicine_consumption,
+                food_consumption: zon.food_consumption,
+            },
+            event_points: zon
+                .event_points
+                .into_iter()
+                .map(|p| EventPoint {
+                    name: p.name,
+                    position: p.position,
+                })
+                .collect(),
+            tile_count: zon.tiles.len(),
+            extents: None,
+            blocks: Vec::new(),
+            scenes: None,
+            textures: zon.textures,
+            object_textures: Vec::new(),
+        }
+    }
+
+    /// Set the blocks of the map, sorted by coordinates, and their extents
+    pub fn set_blocks(&mut self, mut blocks: Vec<BlockSummary>) {
+        blocks.sort_by_key(|b| (b.y, b.x));
+        self.extents = match (
+            blocks.iter().map(|b| b.x).min(),
+            blocks.iter().map(|b| b.y).min(),
+        ) {
+            (Some(min_x), Some(min_y)) => Some(Extents {
+                min: [min_x, min_y],
+                max: [
+                    blocks.iter().map(|b| b.x).max().unwrap_or(min_x),
+                    blocks.iter().map(|b| b.y).max().unwrap_or(min_y),
+                ],
+            }),
+            _ => None,
+        };
+        self.blocks = blocks;
+    }
+
+    /// Set the zone's ZSCs and the textures of the objects its IFOs place
+    pub fn set_scenes(
+        &mut self,
+        scenes: ZoneScenes,
+        ifos: &[IFO],
+        decorations: &ZSC,
+        buildings: &ZSC,
+    ) {
+        let mut textures = BTreeSet::new();
+        for ifo in ifos {
+            for instance in ifo_instances::instances(ifo, decorations, buildings) {
+                let parts = instance.parts.unwrap_or_default();
+                textures.extend(parts.into_iter().filter_map(|p| p.texture));
+            }
+        }
+        textures.remove("");
+        self.scenes = Some(scenes);
+        self.object_textures = textures.into_iter().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use roselib::files::ifo::ObjectData;
+    use roselib::files::zon::ZoneEventPoint;
+    use roselib::files::zsc::{SceneMaterial, SceneObject, SceneObjectPart};
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_manifest() {
+        let mut zon = ZON::new();
+        zon.name = String::from("Canyon City of Zant");
+        zon.textures
+            .push(String::from("3DDATA/TERRAIN/TILES/GRASS.DDS"));
+        zon.event_points.push(ZoneEventPoint {
+            name: String::from("start"),
+            ..Default::default()
+        });
+        zon.population_base = 100;
+
+        let mut manifest = ZoneManifest::new("JPT01", zon);
+        assert_eq!(manifest.economy.population_base, 100);
+        assert_eq!(manifest.event_points[0].name, "start");
+
+        let mut ifo = IFO::new();
+        ifo.objects.push(ObjectData {
+            object_id: 0,
+            ..Default::default()
+        });
+        ifo.objects.push(ObjectData {
+            object_id: 0,
+            ..Default::default()
+        });
+        let mut block = BlockSummary::new(32, 31);
+        block.has_heightmap = true;
+        block.set_objects(&ifo);
+        manifest.set_blocks(vec![
+            block,
+            BlockSummary::new(33, 30),
+            BlockSummary::new(31, 31),
+        ]);
+
+        assert_eq!(
+            manifest.extents,
+            Some(Extents {
+                min: [31, 30],
+                max: [33, 31],
+            })
+        );
+        let coordinates: Vec<(u32, u32)> = manifest.blocks.iter().map(|b| (b.x, b.y)).collect();
+        assert_eq!(coordinates, vec![(33, 30), (31, 31), (32, 31)]);
+        assert_eq!(manifest.blocks[2].decorations, 2);
+        assert!(manifest.blocks[2].has_objects && !manifest.blocks[0].has_objects);
+
+        let mut decorations = ZSC::new();
+        decorations
+            .meshes
+            .push(PathBuf::from("3DDATA\\DECO\\TREE.ZMS"));
+        decorations.materials.push(SceneMaterial {
+            path: PathBuf::from("3DDATA\\DECO\\TREE.DDS"),
+            ..Default::default()
+        });
+        let mut object = SceneObject::default();
+        object.parts.push(SceneObjectPart::default());
+        decorations.objects.push(object);
+        manifest.set_scenes(
+            ZoneScenes {
+                decorations: String::from("3DDATA\\JUNON\\LIST_DECO_JPT.ZSC"),
+                buildings: String::new(),
+            },
+            &[ifo],
+            &decorations,
+            &ZSC::new(),
+        );
+        assert_eq!(manifest.object_textures, vec!["3DDATA/DECO/TREE.DDS"]);
+    }
+}
@@ -0,0 +1,122 @@
+//! Bundles of a ZSC with the files it references
+//!
+//! A bundle is a directory with the ZSC and every mesh, texture, effect and
+//! animation it references, at their client paths so the references still
+//! resolve, and a `bundle.json` manifest listing them. Files missing from
+//! the client are listed as missing instead.
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use roselib::files::ZSC;
+
+use crate::vfs::client_path_key;
+
+/// Name of the manifest in the bundle directory
+pub const BUNDLE_MANIFEST_FILE: &str = "bundle.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyType {
+    Mesh,
+    Texture,
+    Effect,
+    Animation,
+}
+
+/// File referenced by a ZSC
+#[derive(Debug, PartialEq)]
+pub struct Dependency {
+    pub file_type: DependencyType,
+    /// Client path with forward slashes, e.g. `3DDATA/JUNON/DECO/TREE01.ZMS`
+    pub path: String,
+}
+
+/// Files referenced by a ZSC in the order of its tables, without duplicates
+/// or empty paths
+pub fn dependencies(zsc: &ZSC) -> Vec<Dependency> {
+    let meshes = zsc.meshes.iter().map(|p| (DependencyType::Mesh, p));
+    let textures = zsc
+        .materials
+        .iter()
+        .map(|m| (DependencyType::Texture, &m.path));
+    let effects = zsc.effects.iter().map(|p| (DependencyType::Effect, p));
+    let animations = zsc
+        .objects
+        .iter()
+        .flat_map(|object| &object.parts)
+        .map(|part| (DependencyType::Animation, &part.animation_path));
+
+    let mut seen = HashSet::new();
+    let mut dependencies = Vec::new();
+    for (file_type, path) in meshes.chain(textures).chain(effects).chain(animations) {
+        let path = path.to_string_lossy().trim().replace('\\', "/");
+        if path.is_empty() || !seen.insert(client_path_key(&path)) {
+            continue;
+        }
+        dependencies.push(Dependency { file_type, path });
+    }
+    dependencies
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BundleFile {
+    pub path: String,
+    #[serde(rename = "type")]
+    pub file_type: DependencyType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct BundleManifest {
+    /// File name of the ZSC in the bundle
+    pub scene: String,
+    pub files: Vec<BundleFile>,
+    /// Files referenced by the ZSC that aren't in the client
+    pub missing: Vec<BundleFile>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use roselib::files::zsc::{SceneMaterial, SceneObject, SceneObjectPart};
+    use roselib::io::RoseFile;
+
+    #[test]
+    fn test_dependencies() {
+        let mut zsc = ZSC::new();
+        zsc.meshes.push(PathBuf::from("3DDATA\\DECO\\TREE.ZMS"));
+        zsc.meshes.push(PathBuf::from("3DDATA\\DECO\\tree.zms"));
+        zsc.materials.push(SceneMaterial {
+            path: PathBuf::from("3DDATA\\DECO\\TREE.DDS"),
+            ..Default::default()
+        });
+        zsc.effects.push(PathBuf::from("3DDATA\\EFFECT\\LEAF.EFT"));
+        let mut object = SceneObject::default();
+        object.parts.push(SceneObjectPart::default());
+        object.parts.push(SceneObjectPart {
+            animation_path: PathBuf::from("3DDATA\\DECO\\SWAY.ZMO"),
+            ..Default::default()
+        });
+        zsc.objects.push(object);
+
+        let dependencies = dependencies(&zsc);
+        let paths: Vec<(DependencyType, &str)> = dependencies
+            .iter()
+            .map(|d| (d.file_type, d.path.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                (DependencyType::Mesh, "3DDATA/DECO/TREE.ZMS"),
+                (DependencyType::Texture, "3DDATA/DECO/TREE.DDS"),
+                (DependencyType::Effect, "3DDATA/EFFECT/LEAF.EFT"),
+                (DependencyType::Animation, "3DDATA/DECO/SWAY.ZMO"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Resolved materials of the parts of ZSC objects
+//!
+//! ZSC parts reference their material by index into the material table, which
+//! references its texture by client path. The report flattens this into the
+//! material of every part of every object, with the texture found in the
+//! client directory.
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use roselib::files::zsc::{SceneBlendMode, SceneGlowType, SceneMaterial};
+use roselib::files::ZSC;
+
+use crate::vfs::resolve_client_path;
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct MaterialReport {
+    pub objects: Vec<ObjectMaterials>,
+}
+
+/// Materials of the parts of a non-empty object
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ObjectMaterials {
+    pub object: usize,
+    pub parts: Vec<PartMaterial>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PartMaterial {
+    pub part: usize,
+    /// Client path of the mesh, `None` if the mesh index is out of range
+    pub mesh: Option<String>,
+    pub material_id: usize,
+    /// `None` if the material index is out of range
+    #[serde(flatten)]
+    pub material: Option<Material>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Material {
+    /// Client path of the texture with forward slashes
+    pub texture: String,
+    /// Texture in the client directory, `None` if it isn't found
+    pub texture_file: Option<PathBuf>,
+    pub alpha_blend: bool,
+    pub blend_mode: SceneBlendMode,
+    pub alpha_test: bool,
+    pub alpha_ref: u16,
+    /// Opacity of the material, 0 to 1
+    pub alpha: f32,
+    pub two_sided: bool,
+    pub z_write: bool,
+    pub z_test: bool,
+    pub skinned: bool,
+    pub specular: bool,
+    /// `None` if the material doesn't glow
+    pub glow: Option<Glow>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Glow {
+    #[serde(rename = "type")]
+    pub glow_type: SceneGlowType,
+    pub color: [f32; 3],
+}
+
+impl Material {
+    fn new(material: &SceneMaterial, client_dir: &Path) -> Material {
+        let texture = material.path.to_string_lossy().trim().replace('\\', "/");
+        let texture_file = if texture.is_empty() {
+            None
+        } else {
+            resolve_client_path(client_dir, &texture)
+        };
+        let glow = match material.glow_type {
+            SceneGlowType::None | SceneGlowType::NotSet => None,
+            glow_type => Some(Glow {
+                glow_type,
+                color: [
+                    material.glow_color.r,
+                    material.glow_color.g,
+                    material.glow_color.b,
+                ],
+            }),
+        };
+        Material {
+            texture,
+            texture_file,
+            alpha_blend: material.alpha_enabled,
+            blend_mode: material.blend_mode,
+            alpha_test: material.alpha_test_enabled,
+            alpha_ref: material.alpha_ref,
+            alpha: material.alpha,
+            two_sided: material.two_sided,
+            z_write: material.z_write_enabled,
+            z_test: material.z_test_enabled,
+            skinned: material.is_skin,
+            specular: material.specular_enabled,
+            glow,
+        }
+    }
+}
+
+impl MaterialReport {
+    /// Materials of the parts of every non-empty object of a ZSC, with the
+    /// textures resolved in `client_dir`
+    pub fn new(zsc: &ZSC, client_dir: &Path) -> MaterialReport {
+        let materials: Vec<Material> = zsc
+            .materials
+            .iter()
+            .map(|m| Material::new(m, client_dir))
+            .collect();
+
+        let mut report = MaterialReport::default();
+        for (object_id, object) in zsc.objects.iter().enumerate() {
+            if object.parts.is_empty() {
+                continue;
+            }
+            let parts = object
+                .parts
+                .iter()
+                .enumerate()
+                .map(|(part_id, part)| {
+                    let material_id = part.material_id as usize;
+                    PartMaterial {
+                        part: part_id,
+                        mesh: zsc
+                            .meshes
+                            .get(part.mesh_id as usize)
+                            .map(|p| p.to_string_lossy().trim().replace('\\', "/")),
+                        material_id,
+                        material: materials.get(material_id).cloned(),
+                    }
+                })
+                .collect();
+            report.objects.push(ObjectMaterials {
+                object: object_id,
+                parts,
+            });
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use roselib::files::zsc::{SceneObject, SceneObjectPart};
+    use roselib::io::RoseFile;
+    use roselib::utils::Color3;
+
+    #[test]
+    fn test_report() {
+        let client_dir = std::env::temp_dir().join("rose_conv_zsc_materials");
+        let _ = fs::remove_dir_all(&client_dir);
+        fs::create_dir_all(client_dir.join("3DDATA/DECO")).unwrap();
+        fs::write(client_dir.join("3DDATA/DECO/TREE.DDS"), b"").unwrap();
+
+        let mut zsc = ZSC::new();
+        zsc.meshes.push(PathBuf::from("3DDATA\\DECO\\TREE.ZMS"));
+        zsc.materials.push(SceneMaterial {
+            path: PathBuf::from("3DDATA\\DECO\\tree.dds"),
+            alpha_test_enabled: true,
+            alpha_ref: 128,
+            two_sided: true,
+            alpha: 1.0,
+            ..Default::default()
+        });
+        zsc.materials.push(SceneMaterial {
+            path: PathBuf::from("3DDATA\\DECO\\GLOW.DDS"),
+            alpha_enabled: true,
+            blend_mode: SceneBlendMode::Lighten,
+            glow_type: SceneGlowType::Light,
+            glow_color: Color3 {
+                r: 1.0,
+                g: 0.5,
+                b: 0.0,
+            },
+            ..Default::default()
+        });
+        zsc.objects.push(SceneObject::default());
+        let mut object = SceneObject::default();
+        for (mesh_id, material_id) in &[(0, 0), (0, 1), (3, 5)] {
+            object.parts.push(SceneObjectPart {
+                mesh_id: *mesh_id,
+                material_id: *material_id,
+                ..Default::default()
+            });
+        }
+        zsc.objects.push(object);
+
+        let report = MaterialReport::new(&zsc, &client_dir);
+        assert_eq!(report.objects.len(), 1);
+        assert_eq!(report.objects[0].object, 1);
+        let parts = &report.objects[0].parts;
+
+        assert_eq!(parts[0].mesh.as_deref(), Some("3DDATA/DECO/TREE.ZMS"));
+        let material = parts[0].material.as_ref().unwrap();
+        assert_eq!(material.texture, "3DDATA/DECO/tree.dds");
+        assert_eq!(
+            material.texture_file,
+            Some(client_dir.join("3DDATA/DECO/TREE.DDS"))
+        );
+        assert!(material.alpha_test && material.two_sided && !material.alpha_blend);
+        assert_eq!(material.alpha_ref, 128);
+        assert_eq!(material.glow, None);
+
+        let material = parts[1].material.as_ref().unwrap();
+        assert_eq!(material.texture_file, None);
+        assert!(material.alpha_blend);
+        assert_eq!(material.blend_mode, SceneBlendMode::Lighten);
+        assert_eq!(
+            material.glow,
+            Some(Glow {
+                glow_type: SceneGlowType::Light,
+                color: [1.0, 0.5, 0.0],
+            })
+        );
+
+        assert_eq!(parts[2].mesh, None);
+        assert_eq!(parts[2].material_id, 5);
+        assert_eq!(parts[2].material, None);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["objects"][0]["parts"][0]["alpha_ref"], 128);
+        assert_eq!(json["objects"][0]["parts"][1]["glow"]["type"], "Light");
+
+        fs::remove_dir_all(&client_dir).unwrap();
+    }
+}
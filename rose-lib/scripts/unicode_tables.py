@@ -0,0 +1,60 @@
+#!/usr/bin/env python3
+"""Generate the Unicode normalization tables of rose-lib/src/io/normalize/tables.rs
+
+Uses the Unicode database of the Python interpreter:
+
+    python3 scripts/unicode_tables.py > src/io/normalize/tables.rs
+"""
+import unicodedata
+
+# Hangul syllables are decomposed and composed algorithmically
+HANGUL = range(0xAC00, 0xD7A4)
+CHARS = [chr(cp) for cp in range(0x110000) if not 0xD800 <= cp < 0xE000 and cp not in HANGUL]
+
+
+def char(c):
+    return "'\\u{%x}'" % ord(c)
+
+
+def chars(s):
+    return "&[%s]" % ", ".join(char(c) for c in s)
+
+
+def table(name, ty, entries, per_line):
+    print("#[rustfmt::skip]")
+    print("pub(super) static %s: &[%s] = &[" % (name, ty))
+    for i in range(0, len(entries), per_line):
+        print("    " + " ".join(e + "," for e in entries[i:i + per_line]))
+    print("];")
+    print()
+
+
+combining = [(c, unicodedata.combining(c)) for c in CHARS if unicodedata.combining(c)]
+canonical = [(c, unicodedata.normalize("NFD", c)) for c in CHARS]
+canonical = [(c, d) for c, d in canonical if d != c]
+compatibility = [(c, unicodedata.normalize("NFKD", c)) for c in CHARS]
+compatibility = [(c, d) for c, d in compatibility if d != unicodedata.normalize("NFD", c)]
+
+# Primary composites: canonical mappings of two characters that aren't excluded
+# from composition
+composition = []
+for c in CHARS:
+    mapping = unicodedata.decomposition(c)
+    if not mapping or mapping.startswith("<"):
+        continue
+    pair = [chr(int(cp, 16)) for cp in mapping.split()]
+    if len(pair) == 2 and unicodedata.normalize("NFC", c) == c:
+        composition.append((pair[0], pair[1], c))
+composition.sort()
+
+print("//! Unicode %s normalization tables" % unicodedata.unidata_version)
+print("//!")
+print("//! Generated by `scripts/unicode_tables.py`, don't edit by hand.")
+print()
+table("COMBINING_CLASS", "(char, u8)", ["(%s, %d)" % (char(c), ccc) for c, ccc in combining], 6)
+table("CANONICAL_DECOMPOSITION", "(char, &[char])",
+      ["(%s, %s)" % (char(c), chars(d)) for c, d in canonical], 3)
+table("COMPATIBILITY_DECOMPOSITION", "(char, &[char])",
+      ["(%s, %s)" % (char(c), chars(d)) for c, d in compatibility], 3)
+table("COMPOSITION", "((char, char), char)",
+      ["((%s, %s), %s)" % (char(a), char(b), char(c)) for a, b, c in composition], 3)
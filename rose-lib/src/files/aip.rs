@@ -0,0 +1,1583 @@
+//! ROSE Online AI Pattern
+//!
+//! The AI of an NPC is a list of triggers, each run on an event of the NPC
+//! such as being damaged. A trigger runs the actions of the first of its
+//! events whose conditions all hold. Conditions and actions are records with
+//! a type and operands whose layout depends on the type, read into a variant
+//! per type with the layout of the server. Records of an unknown type, with
+//! operands of another size or with padding that isn't zero are kept as raw
+//! bytes so they are written back as they were read.
+use std::convert::TryFrom;
+use std::io::{Cursor, Read};
+
+use failure::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::io::{ReadRoseExt, RoseFile, WriteRoseExt};
+
+/// AI Pattern File
+pub type AIP = AiPattern;
+
+/// Length of the names of triggers and events
+const NAME_LENGTH: u64 = 32;
+
+/// Size of the size and type of a condition or action
+const COMMAND_HEADER_SIZE: u32 = 8;
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct AiPattern {
+    pub title: String,
+    /// Seconds between two runs of the idle trigger
+    pub idle_interval: u32,
+    /// Chance in percent that the damaged trigger runs when the NPC is hit
+    pub damage_chance: u32,
+    /// Triggers in the order of their event: created, idle, attack move,
+    /// damaged, killed a target and dead
+    pub triggers: Vec<AiTrigger>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct AiTrigger {
+    pub name: String,
+    pub events: Vec<AiEvent>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct AiEvent {
+    pub name: String,
+    pub conditions: Vec<AiCondition>,
+    pub actions: Vec<AiAction>,
+}
+
+/// Condition or action as stored in the file
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct AiCommand {
+    /// Type of the condition or action as stored in the file
+    pub opcode: u32,
+    /// Operands, as laid out by the client for the type
+    pub data: Vec<u8>,
+}
+
+/// Condition of an event, checked against the NPC, its target or the
+/// characters around it. `op` fields are comparison operators and `ability`
+/// fields ability types of the server.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum AiCondition {
+    /// Whether the NPC fights (1) or waits (0)
+    FightOrDelay {
+        fight: u8,
+    },
+    /// Damage received (0) or given (1) of at least `damage`
+    Damage {
+        damage: i32,
+        given: u8,
+    },
+    /// At least `count` characters within `distance` whose level minus the
+    /// level of the NPC is in a range, allied or not
+    NearbyCharacters {
+        distance: i32,
+        allied: u8,
+        level_min: i16,
+        level_max: i16,
+        count: u16,
+    },
+    /// Distance of the NPC from its spawn point of at least `distance`
+    DistanceFromSpawn {
+        distance: i32,
+    },
+    DistanceToTarget {
+        distance: i32,
+        op: u8,
+    },
+    /// Ability of the target minus the ability of the NPC
+    AbilityDifference {
+        ability: u8,
+        difference: i32,
+        op: u8,
+    },
+    /// HP of the NPC in percent
+    Health {
+        percent: u32,
+        op: u8,
+    },
+    /// Chance in percent
+    Random {
+        percent: u8,
+    },
+    /// Finds the character within `distance` whose level minus the level of
+    /// the NPC is in a range, for the following conditions and actions
+    FindCharacter {
+        distance: i32,
+        level_min: i16,
+        level_max: i16,
+        allied: u8,
+    },
+    /// The target of the NPC changed
+    TargetChanged,
+    /// Ability of the NPC compared to the one of its target
+    CompareAbility {
+        ability: u8,
+        op: u8,
+    },
+    TargetAbility {
+        ability: u8,
+        value: i32,
+        op: u8,
+    },
+    /// Day (0) or night (1)
+    TimeOfDay {
+        night: u8,
+    },
+    /// Whether the NPC (0) or its target (1) has a status effect of a type
+    Status {
+        target: u8,
+        status: u8,
+        has: u8,
+    },
+    NpcVariable {
+        variable: i16,
+        value: i32,
+        op: u8,
+    },
+    WorldVariable {
+        variable: i16,
+        value: i32,
+        op: u8,
+    },
+    EconomyVariable {
+        variable: i16,
+        value: i32,
+        op: u8,
+    },
+    /// Selects an NPC of `LIST_NPC.STB` for the following conditions and
+    /// actions
+    SelectNpc {
+        npc: i32,
+    },
+    DistanceToOwner {
+        distance: i32,
+        op: u8,
+    },
+    /// Time of the zone, in seconds of its day
+    ZoneTime {
+        start: u32,
+        end: u32,
+    },
+    SelfAbility {
+        ability: u8,
+        value: i32,
+        op: u8,
+    },
+    NoOwner,
+    OwnerHasTarget,
+    /// Time of the world, in seconds of its day
+    WorldTime {
+        start: u32,
+        end: u32,
+    },
+    /// Day of the month, 0 for every day, and time range
+    MonthDay {
+        day: u8,
+        start_hour: u8,
+        start_minute: u8,
+        end_hour: u8,
+        end_minute: u8,
+    },
+    /// Day of the week and time range
+    WeekDay {
+        day: u8,
+        start_hour: u8,
+        start_minute: u8,
+        end_hour: u8,
+        end_minute: u8,
+    },
+    /// Channel of the server in a range
+    Channel {
+        min: u16,
+        max: u16,
+    },
+    /// Number of the characters within `distance` whose level minus the
+    /// level of the NPC is in a range, allied or not
+    NearbyCharacterCount {
+        distance: i32,
+        allied: u8,
+        level_min: i16,
+        level_max: i16,
+        count: u16,
+        op: u8,
+    },
+    MonsterVariable {
+        variable: i16,
+        value: i32,
+        op: u8,
+    },
+    /// Whether the target is the master of its clan
+    TargetIsClanMaster {
+        target: u8,
+    },
+    /// Condition of an unknown type, with operands of another size or
+    /// padding that isn't zero
+    Raw(AiCommand),
+}
+
+/// Action of an event
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum AiAction {
+    Stop,
+    Motion {
+        motion: u8,
+    },
+    /// Says a string of the AI strings
+    Say {
+        message: i32,
+    },
+    /// Moves to a random point within `distance`
+    MoveRandom {
+        distance: i32,
+        speed: u8,
+    },
+    /// Moves to a random point within `distance` of the spawn point
+    MoveRandomFromSpawn {
+        distance: i32,
+        speed: u8,
+    },
+    /// Moves to the character found by a condition
+    MoveToCharacter {
+        speed: u8,
+    },
+    /// Attacks the character within `distance` with the highest or lowest
+    /// ability
+    AttackByAbility {
+        distance: i32,
+        ability: u8,
+        op: u8,
+    },
+    SpecialAttack,
+    /// Moves towards the target until `distance`
+    MoveToTarget {
+        distance: i32,
+        speed: u8,
+    },
+    /// Turns into an NPC of `LIST_NPC.STB`
+    Transform {
+        npc: u16,
+    },
+    /// Summons an NPC of `LIST_NPC.STB`
+    Summon {
+        npc: u16,
+    },
+    /// Calls `count` allies within `distance` to help
+    CallAllies {
+        distance: i32,
+        count: i32,
+    },
+    /// Attacks the nearest character found by a condition
+    AttackNearest,
+    /// Attacks the character found by a condition
+    AttackFound,
+    /// Calls the NPCs of the same team within `distance`
+    CallTeam {
+        distance: i32,
+    },
+    /// Attacks the attacker of the target
+    AttackAttacker,
+    RunAway {
+        distance: i32,
+    },
+    /// Drops one of the items, none for 0, for the owner (1) or not (0)
+    DropItem {
+        items: [i16; 5],
+        to_owner: i32,
+    },
+    /// Spawns `count` NPCs within `distance`
+    CallMonsters {
+        npc: u16,
+        count: u16,
+        distance: i32,
+    },
+    AttackNearestCharacter,
+    /// Spawns an NPC within `distance` of the NPC (0), its target (1) or
+    /// its attacker (2)
+    SpawnMonster {
+        npc: u16,
+        position: u8,
+        distance: i32,
+    },
+    KillSelf,
+    /// Casts a skill of `LIST_SKILL.STB` on the NPC (0) or its target (1)
+    Skill {
+        target: u8,
+        skill: i16,
+        motion: i16,
+    },
+    SetNpcVariable {
+        variable: i16,
+        value: i32,
+        op: u8,
+    },
+    SetWorldVariable {
+        variable: i16,
+        value: i32,
+        op: u8,
+    },
+    SetEconomyVariable {
+        variable: i16,
+        value: i32,
+        op: u8,
+    },
+    /// Says (0), shouts (1) or announces (2) a string of the AI strings
+    Announce {
+        kind: u8,
+        message: i32,
+    },
+    MoveToOwner,
+    /// Runs a trigger of the quests
+    QuestTrigger {
+        trigger: String,
+    },
+    AttackOwnerTarget,
+    /// Turns PvP in a zone on (1) or off (0)
+    SetPvp {
+        zone: i16,
+        enabled: u8,
+    },
+    /// Turns the spawns of a zone on (1) or off (0)
+    SetRespawn {
+        zone: i16,
+        enabled: u8,
+    },
+    /// Gives an item to the owner
+    GiveItem {
+        item: i16,
+        count: i16,
+    },
+    SetMonsterVariable {
+        variable: i16,
+        value: i32,
+        op: u8,
+    },
+    /// Summons an NPC, owned by the NPC (1) or not (0)
+    SummonWithOwner {
+        npc: u16,
+        owner: u8,
+    },
+    SpawnMonsterWithOwner {
+        npc: u16,
+        position: u8,
+        distance: i32,
+        owner: u8,
+    },
+    /// Action of an unknown type, with operands of another size or
+    /// padding that isn't zero
+    Raw(AiCommand),
+}
+
+/// Opcode of the first condition type
+const CONDITION_OPCODE: u32 = 0x0400_0001;
+
+/// Opcode of the first action type
+const ACTION_OPCODE: u32 = 0x0B00_0001;
+
+impl AiCondition {
+    /// Size of the operands of a condition type with a fixed layout
+    pub fn operand_size(opcode: u32) -> Option<usize> {
+        let size = match opcode.checked_sub(CONDITION_OPCODE)? {
+            9 | 21 | 22 => 0,
+            0 | 3 | 7 | 10 | 12 | 13 | 17 | 26 | 29 => 4,
+            1 | 4 | 6 | 18 | 19 | 23 | 24 | 25 => 8,
+            2 | 5 | 8 | 11 | 14 | 15 | 16 | 20 | 28 => 12,
+            27 => 16,
+            _ => return None,
+        };
+        Some(size)
+    }
+
+    /// Condition of a record, raw if it doesn't have the layout of its type
+    pub fn from_command(command: &AiCommand) -> AiCondition {
+        match AiCondition::decode(command) {
+            Ok(condition) if condition.to_command() == *command => condition,
+            _ => AiCondition::Raw(command.clone()),
+        }
+    }
+
+    /// Condition of a record, ignoring the values of its padding
+    pub fn decode(command: &AiCommand) -> Result<AiCondition, Error> {
+        check_size(
+            "condition",
+            command,
+            AiCondition::operand_size(command.opcode),
+        )?;
+
+        let reader = &mut Cursor::new(command.data.as_slice());
+        let condition = match command.opcode.wrapping_sub(CONDITION_OPCODE) {
+            0 => {
+                let fight = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::FightOrDelay { fight }
+            }
+            1 => {
+                let damage = reader.read_i32()?;
+                let given = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::Damage { damage, given }
+            }
+            2 => {
+                let distance = reader.read_i32()?;
+                let allied = reader.read_u8()?;
+                skip(reader, 1)?;
+                let level_min = reader.read_i16()?;
+                let level_max = reader.read_i16()?;
+                let count = reader.read_u16()?;
+                AiCondition::NearbyCharacters {
+                    distance,
+                    allied,
+                    level_min,
+                    level_max,
+                    count,
+                }
+            }
+            3 => {
+                let distance = reader.read_i32()?;
+                AiCondition::DistanceFromSpawn { distance }
+            }
+            4 => {
+                let distance = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::DistanceToTarget { distance, op }
+            }
+            5 => {
+                let ability = reader.read_u8()?;
+                skip(reader, 3)?;
+                let difference = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::AbilityDifference {
+                    ability,
+                    difference,
+                    op,
+                }
+            }
+            6 => {
+                let percent = reader.read_u32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::Health { percent, op }
+            }
+            7 => {
+                let percent = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::Random { percent }
+            }
+            8 => {
+                let distance = reader.read_i32()?;
+                let level_min = reader.read_i16()?;
+                let level_max = reader.read_i16()?;
+                let allied = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::FindCharacter {
+                    distance,
+                    level_min,
+                    level_max,
+                    allied,
+                }
+            }
+            9 => AiCondition::TargetChanged,
+            10 => {
+                let ability = reader.read_u8()?;
+                let op = reader.read_u8()?;
+                skip(reader, 2)?;
+                AiCondition::CompareAbility { ability, op }
+            }
+            11 => {
+                let ability = reader.read_u8()?;
+                skip(reader, 3)?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::TargetAbility { ability, value, op }
+            }
+            12 => {
+                let night = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::TimeOfDay { night }
+            }
+            13 => {
+                let target = reader.read_u8()?;
+                let status = reader.read_u8()?;
+                let has = reader.read_u8()?;
+                skip(reader, 1)?;
+                AiCondition::Status {
+                    target,
+                    status,
+                    has,
+                }
+            }
+            14 => {
+                let variable = reader.read_i16()?;
+                skip(reader, 2)?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::NpcVariable {
+                    variable,
+                    value,
+                    op,
+                }
+            }
+            15 => {
+                let variable = reader.read_i16()?;
+                skip(reader, 2)?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::WorldVariable {
+                    variable,
+                    value,
+                    op,
+                }
+            }
+            16 => {
+                let variable = reader.read_i16()?;
+                skip(reader, 2)?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::EconomyVariable {
+                    variable,
+                    value,
+                    op,
+                }
+            }
+            17 => {
+                let npc = reader.read_i32()?;
+                AiCondition::SelectNpc { npc }
+            }
+            18 => {
+                let distance = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::DistanceToOwner { distance, op }
+            }
+            19 => {
+                let start = reader.read_u32()?;
+                let end = reader.read_u32()?;
+                AiCondition::ZoneTime { start, end }
+            }
+            20 => {
+                let ability = reader.read_u8()?;
+                skip(reader, 3)?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::SelfAbility { ability, value, op }
+            }
+            21 => AiCondition::NoOwner,
+            22 => AiCondition::OwnerHasTarget,
+            23 => {
+                let start = reader.read_u32()?;
+                let end = reader.read_u32()?;
+                AiCondition::WorldTime { start, end }
+            }
+            24 => {
+                let day = reader.read_u8()?;
+                let start_hour = reader.read_u8()?;
+                let start_minute = reader.read_u8()?;
+                let end_hour = reader.read_u8()?;
+                let end_minute = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::MonthDay {
+                    day,
+                    start_hour,
+                    start_minute,
+                    end_hour,
+                    end_minute,
+                }
+            }
+            25 => {
+                let day = reader.read_u8()?;
+                let start_hour = reader.read_u8()?;
+                let start_minute = reader.read_u8()?;
+                let end_hour = reader.read_u8()?;
+                let end_minute = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::WeekDay {
+                    day,
+                    start_hour,
+                    start_minute,
+                    end_hour,
+                    end_minute,
+                }
+            }
+            26 => {
+                let min = reader.read_u16()?;
+                let max = reader.read_u16()?;
+                AiCondition::Channel { min, max }
+            }
+            27 => {
+                let distance = reader.read_i32()?;
+                let allied = reader.read_u8()?;
+                skip(reader, 1)?;
+                let level_min = reader.read_i16()?;
+                let level_max = reader.read_i16()?;
+                let count = reader.read_u16()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::NearbyCharacterCount {
+                    distance,
+                    allied,
+                    level_min,
+                    level_max,
+                    count,
+                    op,
+                }
+            }
+            28 => {
+                let variable = reader.read_i16()?;
+                skip(reader, 2)?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::MonsterVariable {
+                    variable,
+                    value,
+                    op,
+                }
+            }
+            29 => {
+                let target = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiCondition::TargetIsClanMaster { target }
+            }
+            _ => bail!("unknown condition opcode {:#010x}", command.opcode),
+        };
+        check_end("condition", command, reader)?;
+        Ok(condition)
+    }
+
+    /// Record of the condition, with zero padding
+    pub fn to_command(&self) -> AiCommand {
+        if let AiCondition::Raw(command) = self {
+            return command.clone();
+        }
+
+        let mut writer = Cursor::new(Vec::new());
+        // Writing to memory can't fail
+        let number = self.write_operands(&mut writer).unwrap();
+        AiCommand {
+            opcode: CONDITION_OPCODE + number,
+            data: writer.into_inner(),
+        }
+    }
+
+    fn write_operands<W: WriteRoseExt>(&self, writer: &mut W) -> Result<u32, Error> {
+        let number = match self {
+            AiCondition::FightOrDelay { fight } => {
+                writer.write_u8(*fight)?;
+                writer.write_all(&[0; 3])?;
+                0
+            }
+            AiCondition::Damage { damage, given } => {
+                writer.write_i32(*damage)?;
+                writer.write_u8(*given)?;
+                writer.write_all(&[0; 3])?;
+                1
+            }
+            AiCondition::NearbyCharacters {
+                distance,
+                allied,
+                level_min,
+                level_max,
+                count,
+            } => {
+                writer.write_i32(*distance)?;
+                writer.write_u8(*allied)?;
+                writer.write_all(&[0; 1])?;
+                writer.write_i16(*level_min)?;
+                writer.write_i16(*level_max)?;
+                writer.write_u16(*count)?;
+                2
+            }
+            AiCondition::DistanceFromSpawn { distance } => {
+                writer.write_i32(*distance)?;
+                3
+            }
+            AiCondition::DistanceToTarget { distance, op } => {
+                writer.write_i32(*distance)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                4
+            }
+            AiCondition::AbilityDifference {
+                ability,
+                difference,
+                op,
+            } => {
+                writer.write_u8(*ability)?;
+                writer.write_all(&[0; 3])?;
+                writer.write_i32(*difference)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                5
+            }
+            AiCondition::Health { percent, op } => {
+                writer.write_u32(*percent)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                6
+            }
+            AiCondition::Random { percent } => {
+                writer.write_u8(*percent)?;
+                writer.write_all(&[0; 3])?;
+                7
+            }
+            AiCondition::FindCharacter {
+                distance,
+                level_min,
+                level_max,
+                allied,
+            } => {
+                writer.write_i32(*distance)?;
+                writer.write_i16(*level_min)?;
+                writer.write_i16(*level_max)?;
+                writer.write_u8(*allied)?;
+                writer.write_all(&[0; 3])?;
+                8
+            }
+            AiCondition::TargetChanged => 9,
+            AiCondition::CompareAbility { ability, op } => {
+                writer.write_u8(*ability)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 2])?;
+                10
+            }
+            AiCondition::TargetAbility { ability, value, op } => {
+                writer.write_u8(*ability)?;
+                writer.write_all(&[0; 3])?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                11
+            }
+            AiCondition::TimeOfDay { night } => {
+                writer.write_u8(*night)?;
+                writer.write_all(&[0; 3])?;
+                12
+            }
+            AiCondition::Status {
+                target,
+                status,
+                has,
+            } => {
+                writer.write_u8(*target)?;
+                writer.write_u8(*status)?;
+                writer.write_u8(*has)?;
+                writer.write_all(&[0; 1])?;
+                13
+            }
+            AiCondition::NpcVariable {
+                variable,
+                value,
+                op,
+            } => {
+                writer.write_i16(*variable)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                14
+            }
+            AiCondition::WorldVariable {
+                variable,
+                value,
+                op,
+            } => {
+                writer.write_i16(*variable)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                15
+            }
+            AiCondition::EconomyVariable {
+                variable,
+                value,
+                op,
+            } => {
+                writer.write_i16(*variable)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                16
+            }
+            AiCondition::SelectNpc { npc } => {
+                writer.write_i32(*npc)?;
+                17
+            }
+            AiCondition::DistanceToOwner { distance, op } => {
+                writer.write_i32(*distance)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                18
+            }
+            AiCondition::ZoneTime { start, end } => {
+                writer.write_u32(*start)?;
+                writer.write_u32(*end)?;
+                19
+            }
+            AiCondition::SelfAbility { ability, value, op } => {
+                writer.write_u8(*ability)?;
+                writer.write_all(&[0; 3])?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                20
+            }
+            AiCondition::NoOwner => 21,
+            AiCondition::OwnerHasTarget => 22,
+            AiCondition::WorldTime { start, end } => {
+                writer.write_u32(*start)?;
+                writer.write_u32(*end)?;
+                23
+            }
+            AiCondition::MonthDay {
+                day,
+                start_hour,
+                start_minute,
+                end_hour,
+                end_minute,
+            } => {
+                writer.write_u8(*day)?;
+                writer.write_u8(*start_hour)?;
+                writer.write_u8(*start_minute)?;
+                writer.write_u8(*end_hour)?;
+                writer.write_u8(*end_minute)?;
+                writer.write_all(&[0; 3])?;
+                24
+            }
+            AiCondition::WeekDay {
+                day,
+                start_hour,
+                start_minute,
+                end_hour,
+                end_minute,
+            } => {
+                writer.write_u8(*day)?;
+                writer.write_u8(*start_hour)?;
+                writer.write_u8(*start_minute)?;
+                writer.write_u8(*end_hour)?;
+                writer.write_u8(*end_minute)?;
+                writer.write_all(&[0; 3])?;
+                25
+            }
+            AiCondition::Channel { min, max } => {
+                writer.write_u16(*min)?;
+                writer.write_u16(*max)?;
+                26
+            }
+            AiCondition::NearbyCharacterCount {
+                distance,
+                allied,
+                level_min,
+                level_max,
+                count,
+                op,
+            } => {
+                writer.write_i32(*distance)?;
+                writer.write_u8(*allied)?;
+                writer.write_all(&[0; 1])?;
+                writer.write_i16(*level_min)?;
+                writer.write_i16(*level_max)?;
+                writer.write_u16(*count)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                27
+            }
+            AiCondition::MonsterVariable {
+                variable,
+                value,
+                op,
+            } => {
+                writer.write_i16(*variable)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                28
+            }
+            AiCondition::TargetIsClanMaster { target } => {
+                writer.write_u8(*target)?;
+                writer.write_all(&[0; 3])?;
+                29
+            }
+            AiCondition::Raw(_) => unreachable!(),
+        };
+        Ok(number)
+    }
+}
+
+impl AiAction {
+    /// Size of the operands of an action type with a fixed layout
+    pub fn operand_size(opcode: u32) -> Option<usize> {
+        let size = match opcode.checked_sub(ACTION_OPCODE)? {
+            0 | 7 | 12 | 13 | 15 | 19 | 23 | 29 | 31 => 0,
+            1 | 2 | 5 | 9 | 10 | 14 | 16 | 32 | 33 | 34 | 36 => 4,
+            3 | 4 | 6 | 8 | 11 | 18 | 20 | 24 | 28 => 8,
+            25 | 26 | 27 | 35 | 37 => 12,
+            17 => 16,
+            _ => return None,
+        };
+        Some(size)
+    }
+
+    /// Action of a record, raw if it doesn't have the layout of its type
+    pub fn from_command(command: &AiCommand) -> AiAction {
+        match AiAction::decode(command) {
+            Ok(action) if action.to_command() == *command => action,
+            _ => AiAction::Raw(command.clone()),
+        }
+    }
+
+    /// Action of a record, ignoring the values of its padding
+    pub fn decode(command: &AiCommand) -> Result<AiAction, Error> {
+        check_size("action", command, AiAction::operand_size(command.opcode))?;
+
+        let reader = &mut Cursor::new(command.data.as_slice());
+        let action = match command.opcode.wrapping_sub(ACTION_OPCODE) {
+            0 => AiAction::Stop,
+            1 => {
+                let motion = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiAction::Motion { motion }
+            }
+            2 => {
+                let message = reader.read_i32()?;
+                AiAction::Say { message }
+            }
+            3 => {
+                let distance = reader.read_i32()?;
+                let speed = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiAction::MoveRandom { distance, speed }
+            }
+            4 => {
+                let distance = reader.read_i32()?;
+                let speed = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiAction::MoveRandomFromSpawn { distance, speed }
+            }
+            5 => {
+                let speed = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiAction::MoveToCharacter { speed }
+            }
+            6 => {
+                let distance = reader.read_i32()?;
+                let ability = reader.read_u8()?;
+                let op = reader.read_u8()?;
+                skip(reader, 2)?;
+                AiAction::AttackByAbility {
+                    distance,
+                    ability,
+                    op,
+                }
+            }
+            7 => AiAction::SpecialAttack,
+            8 => {
+                let distance = reader.read_i32()?;
+                let speed = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiAction::MoveToTarget { distance, speed }
+            }
+            9 => {
+                let npc = reader.read_u16()?;
+                skip(reader, 2)?;
+                AiAction::Transform { npc }
+            }
+            10 => {
+                let npc = reader.read_u16()?;
+                skip(reader, 2)?;
+                AiAction::Summon { npc }
+            }
+            11 => {
+                let distance = reader.read_i32()?;
+                let count = reader.read_i32()?;
+                AiAction::CallAllies { distance, count }
+            }
+            12 => AiAction::AttackNearest,
+            13 => AiAction::AttackFound,
+            14 => {
+                let distance = reader.read_i32()?;
+                AiAction::CallTeam { distance }
+            }
+            15 => AiAction::AttackAttacker,
+            16 => {
+                let distance = reader.read_i32()?;
+                AiAction::RunAway { distance }
+            }
+            17 => {
+                let items = read_items(reader)?;
+                skip(reader, 2)?;
+                let to_owner = reader.read_i32()?;
+                AiAction::DropItem { items, to_owner }
+            }
+            18 => {
+                let npc = reader.read_u16()?;
+                let count = reader.read_u16()?;
+                let distance = reader.read_i32()?;
+                AiAction::CallMonsters {
+                    npc,
+                    count,
+                    distance,
+                }
+            }
+            19 => AiAction::AttackNearestCharacter,
+            20 => {
+                let npc = reader.read_u16()?;
+                let position = reader.read_u8()?;
+                skip(reader, 1)?;
+                let distance = reader.read_i32()?;
+                AiAction::SpawnMonster {
+                    npc,
+                    position,
+                    distance,
+                }
+            }
+            23 => AiAction::KillSelf,
+            24 => {
+                let target = reader.read_u8()?;
+                skip(reader, 1)?;
+                let skill = reader.read_i16()?;
+                let motion = reader.read_i16()?;
+                skip(reader, 2)?;
+                AiAction::Skill {
+                    target,
+                    skill,
+                    motion,
+                }
+            }
+            25 => {
+                let variable = reader.read_i16()?;
+                skip(reader, 2)?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiAction::SetNpcVariable {
+                    variable,
+                    value,
+                    op,
+                }
+            }
+            26 => {
+                let variable = reader.read_i16()?;
+                skip(reader, 2)?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiAction::SetWorldVariable {
+                    variable,
+                    value,
+                    op,
+                }
+            }
+            27 => {
+                let variable = reader.read_i16()?;
+                skip(reader, 2)?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiAction::SetEconomyVariable {
+                    variable,
+                    value,
+                    op,
+                }
+            }
+            28 => {
+                let kind = reader.read_u8()?;
+                skip(reader, 3)?;
+                let message = reader.read_i32()?;
+                AiAction::Announce { kind, message }
+            }
+            29 => AiAction::MoveToOwner,
+            30 => {
+                let trigger = read_padded_string(reader)?;
+                AiAction::QuestTrigger { trigger }
+            }
+            31 => AiAction::AttackOwnerTarget,
+            32 => {
+                let zone = reader.read_i16()?;
+                let enabled = reader.read_u8()?;
+                skip(reader, 1)?;
+                AiAction::SetPvp { zone, enabled }
+            }
+            33 => {
+                let zone = reader.read_i16()?;
+                let enabled = reader.read_u8()?;
+                skip(reader, 1)?;
+                AiAction::SetRespawn { zone, enabled }
+            }
+            34 => {
+                let item = reader.read_i16()?;
+                let count = reader.read_i16()?;
+                AiAction::GiveItem { item, count }
+            }
+            35 => {
+                let variable = reader.read_i16()?;
+                skip(reader, 2)?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiAction::SetMonsterVariable {
+                    variable,
+                    value,
+                    op,
+                }
+            }
+            36 => {
+                let npc = reader.read_u16()?;
+                let owner = reader.read_u8()?;
+                skip(reader, 1)?;
+                AiAction::SummonWithOwner { npc, owner }
+            }
+            37 => {
+                let npc = reader.read_u16()?;
+                let position = reader.read_u8()?;
+                skip(reader, 1)?;
+                let distance = reader.read_i32()?;
+                let owner = reader.read_u8()?;
+                skip(reader, 3)?;
+                AiAction::SpawnMonsterWithOwner {
+                    npc,
+                    position,
+                    distance,
+                    owner,
+                }
+            }
+            _ => bail!("unknown action opcode {:#010x}", command.opcode),
+        };
+        check_end("action", command, reader)?;
+        Ok(action)
+    }
+
+    /// Record of the action, with zero padding
+    pub fn to_command(&self) -> AiCommand {
+        if let AiAction::Raw(command) = self {
+            return command.clone();
+        }
+
+        let mut writer = Cursor::new(Vec::new());
+        // Writing to memory can't fail
+        let number = self.write_operands(&mut writer).unwrap();
+        AiCommand {
+            opcode: ACTION_OPCODE + number,
+            data: writer.into_inner(),
+        }
+    }
+
+    fn write_operands<W: WriteRoseExt>(&self, writer: &mut W) -> Result<u32, Error> {
+        let number = match self {
+            AiAction::Stop => 0,
+            AiAction::Motion { motion } => {
+                writer.write_u8(*motion)?;
+                writer.write_all(&[0; 3])?;
+                1
+            }
+            AiAction::Say { message } => {
+                writer.write_i32(*message)?;
+                2
+            }
+            AiAction::MoveRandom { distance, speed } => {
+                writer.write_i32(*distance)?;
+                writer.write_u8(*speed)?;
+                writer.write_all(&[0; 3])?;
+                3
+            }
+            AiAction::MoveRandomFromSpawn { distance, speed } => {
+                writer.write_i32(*distance)?;
+                writer.write_u8(*speed)?;
+                writer.write_all(&[0; 3])?;
+                4
+            }
+            AiAction::MoveToCharacter { speed } => {
+                writer.write_u8(*speed)?;
+                writer.write_all(&[0; 3])?;
+                5
+            }
+            AiAction::AttackByAbility {
+                distance,
+                ability,
+                op,
+            } => {
+                writer.write_i32(*distance)?;
+                writer.write_u8(*ability)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 2])?;
+                6
+            }
+            AiAction::SpecialAttack => 7,
+            AiAction::MoveToTarget { distance, speed } => {
+                writer.write_i32(*distance)?;
+                writer.write_u8(*speed)?;
+                writer.write_all(&[0; 3])?;
+                8
+            }
+            AiAction::Transform { npc } => {
+                writer.write_u16(*npc)?;
+                writer.write_all(&[0; 2])?;
+                9
+            }
+            AiAction::Summon { npc } => {
+                writer.write_u16(*npc)?;
+                writer.write_all(&[0; 2])?;
+                10
+            }
+            AiAction::CallAllies { distance, count } => {
+                writer.write_i32(*distance)?;
+                writer.write_i32(*count)?;
+                11
+            }
+            AiAction::AttackNearest => 12,
+            AiAction::AttackFound => 13,
+            AiAction::CallTeam { distance } => {
+                writer.write_i32(*distance)?;
+                14
+            }
+            AiAction::AttackAttacker => 15,
+            AiAction::RunAway { distance } => {
+                writer.write_i32(*distance)?;
+                16
+            }
+            AiAction::DropItem { items, to_owner } => {
+                write_items(writer, items)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*to_owner)?;
+                17
+            }
+            AiAction::CallMonsters {
+                npc,
+                count,
+                distance,
+            } => {
+                writer.write_u16(*npc)?;
+                writer.write_u16(*count)?;
+                writer.write_i32(*distance)?;
+                18
+            }
+            AiAction::AttackNearestCharacter => 19,
+            AiAction::SpawnMonster {
+                npc,
+                position,
+                distance,
+            } => {
+                writer.write_u16(*npc)?;
+                writer.write_u8(*position)?;
+                writer.write_all(&[0; 1])?;
+                writer.write_i32(*distance)?;
+                20
+            }
+            AiAction::KillSelf => 23,
+            AiAction::Skill {
+                target,
+                skill,
+                motion,
+            } => {
+                writer.write_u8(*target)?;
+                writer.write_all(&[0; 1])?;
+                writer.write_i16(*skill)?;
+                writer.write_i16(*motion)?;
+                writer.write_all(&[0; 2])?;
+                24
+            }
+            AiAction::SetNpcVariable {
+                variable,
+                value,
+                op,
+            } => {
+                writer.write_i16(*variable)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                25
+            }
+            AiAction::SetWorldVariable {
+                variable,
+                value,
+                op,
+            } => {
+                writer.write_i16(*variable)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                26
+            }
+            AiAction::SetEconomyVariable {
+                variable,
+                value,
+                op,
+            } => {
+                writer.write_i16(*variable)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                27
+            }
+            AiAction::Announce { kind, message } => {
+                writer.write_u8(*kind)?;
+                writer.write_all(&[0; 3])?;
+                writer.write_i32(*message)?;
+                28
+            }
+            AiAction::MoveToOwner => 29,
+            AiAction::QuestTrigger { trigger } => {
+                write_padded_string(writer, trigger)?;
+                30
+            }
+            AiAction::AttackOwnerTarget => 31,
+            AiAction::SetPvp { zone, enabled } => {
+                writer.write_i16(*zone)?;
+                writer.write_u8(*enabled)?;
+                writer.write_all(&[0; 1])?;
+                32
+            }
+            AiAction::SetRespawn { zone, enabled } => {
+                writer.write_i16(*zone)?;
+                writer.write_u8(*enabled)?;
+                writer.write_all(&[0; 1])?;
+                33
+            }
+            AiAction::GiveItem { item, count } => {
+                writer.write_i16(*item)?;
+                writer.write_i16(*count)?;
+                34
+            }
+            AiAction::SetMonsterVariable {
+                variable,
+                value,
+                op,
+            } => {
+                writer.write_i16(*variable)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                35
+            }
+            AiAction::SummonWithOwner { npc, owner } => {
+                writer.write_u16(*npc)?;
+                writer.write_u8(*owner)?;
+                writer.write_all(&[0; 1])?;
+                36
+            }
+            AiAction::SpawnMonsterWithOwner {
+                npc,
+                position,
+                distance,
+                owner,
+            } => {
+                writer.write_u16(*npc)?;
+                writer.write_u8(*position)?;
+                writer.write_all(&[0; 1])?;
+                writer.write_i32(*distance)?;
+                writer.write_u8(*owner)?;
+                writer.write_all(&[0; 3])?;
+                37
+            }
+            AiAction::Raw(_) => unreachable!(),
+        };
+        Ok(number)
+    }
+}
+
+impl AiPattern {
+    /// Every condition of every event
+    pub fn conditions(&self) -> impl Iterator<Item = &AiCondition> {
+        self.events().flat_map(|e| e.conditions.iter())
+    }
+
+    /// Every action of every event
+    pub fn actions(&self) -> impl Iterator<Item = &AiAction> {
+        self.events().flat_map(|e| e.actions.iter())
+    }
+
+    fn events(&self) -> impl Iterator<Item = &AiEvent> {
+        self.triggers.iter().flat_map(|t| t.events.iter())
+    }
+}
+
+impl RoseFile for AiPattern {
+    fn new() -> AiPattern {
+        Self::default()
+    }
+
+    fn read<R: ReadRoseExt>(&mut self, reader: &mut R) -> Result<(), Error> {
+        let trigger_count = reader.read_u32()?;
+        self.idle_interval = reader.read_u32()?;
+        self.damage_chance = reader.read_u32()?;
+        self.title = reader.read_string_u32()?;
+
+        for _ in 0..trigger_count {
+            let mut trigger = AiTrigger {
+                name: read_name(reader)?,
+                events: Vec::new(),
+            };
+
+            let event_count = reader.read_u32()?;
+            for _ in 0..event_count {
+                let mut event = AiEvent {
+                    name: read_name(reader)?,
+                    ..Default::default()
+                };
+
+                let condition_count = reader.read_u32()?;
+                for _ in 0..condition_count {
+                    let command = read_command(reader)?;
+                    event.conditions.push(AiCondition::from_command(&command));
+                }
+                let action_count = reader.read_u32()?;
+                for _ in 0..action_count {
+                    let command = read_command(reader)?;
+                    event.actions.push(AiAction::from_command(&command));
+                }
+                trigger.events.push(event);
+            }
+            self.triggers.push(trigger);
+        }
+
+        Ok(())
+    }
+
+    fn write<W: WriteRoseExt>(&mut self, writer: &mut W) -> Result<(), Error> {
+        writer.write_u32(u32::try_from(self.triggers.len())?)?;
+        writer.write_u32(self.idle_interval)?;
+        writer.write_u32(self.damage_chance)?;
+        writer.write_string_u32(&self.title)?;
+
+        for trigger in &self.triggers {
+            writer.write_string(&trigger.name, NAME_LENGTH as i32)?;
+            writer.write_u32(u32::try_from(trigger.events.len())?)?;
+            for event in &trigger.events {
+                writer.write_string(&event.name, NAME_LENGTH as i32)?;
+                writer.write_u32(u32::try_from(event.conditions.len())?)?;
+                for condition in &event.conditions {
+                    write_command(writer, &condition.to_command())?;
+                }
+                writer.write_u32(u32::try_from(event.actions.len())?)?;
+                for action in &event.actions {
+                    write_command(writer, &action.to_command())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Name padded with null bytes, which may be followed by leftover bytes
+fn read_name<R: ReadRoseExt>(reader: &mut R) -> Result<String, Error> {
+    let name = reader.read_string(NAME_LENGTH)?;
+    Ok(name.split('\0').next().unwrap_or_default().to_string())
+}
+
+fn read_command<R: ReadRoseExt>(reader: &mut R) -> Result<AiCommand, Error> {
+    let size = reader.read_u32()?;
+    let opcode = reader.read_u32()?;
+    if size < COMMAND_HEADER_SIZE {
+        bail!("Invalid size of AI command {:#x}: {}", opcode, size);
+    }
+
+    // Read through `take` so a corrupt size can't allocate more than the
+    // bytes left in the file
+    let len = u64::from(size - COMMAND_HEADER_SIZE);
+    let mut data = Vec::new();
+    reader.by_ref().take(len).read_to_end(&mut data)?;
+    if data.len() as u64 != len {
+        bail!(
+            "Truncated AI command {:#x}: {} of {} bytes of operands",
+            opcode,
+            data.len(),
+            len
+        );
+    }
+    Ok(AiCommand { opcode, data })
+}
+
+fn write_command<W: WriteRoseExt>(writer: &mut W, command: &AiCommand) -> Result<(), Error> {
+    writer.write_u32(u32::try_from(command.data.len())? + COMMAND_HEADER_SIZE)?;
+    writer.write_u32(command.opcode)?;
+    writer.write_all(&command.data)?;
+    Ok(())
+}
+
+/// Error if the operands of a record with a fixed layout have another size
+fn check_size(kind: &str, command: &AiCommand, size: Option<usize>) -> Result<(), Error> {
+    match size {
+        Some(size) if command.data.len() != size => bail!(
+            "{} {:#010x} has {} bytes of operands instead of {}",
+            kind,
+            command.opcode,
+            command.data.len(),
+            size
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Error if the layout of a record doesn't cover all of its operands
+fn check_end(kind: &str, command: &AiCommand, reader: &mut Cursor<&[u8]>) -> Result<(), Error> {
+    let end = Cursor::position(reader) as usize;
+    if end != command.data.len() {
+        bail!(
+            "{} {:#010x} has {} bytes of operands instead of {}",
+            kind,
+            command.opcode,
+            command.data.len(),
+            end
+        );
+    }
+    Ok(())
+}
+
+fn skip<R: ReadRoseExt>(reader: &mut R, size: usize) -> Result<(), Error> {
+    reader.read_exact(&mut vec![0u8; size])?;
+    Ok(())
+}
+
+fn read_items<R: ReadRoseExt>(reader: &mut R) -> Result<[i16; 5], Error> {
+    let mut items = [0; 5];
+    for item in &mut items {
+        *item = reader.read_i16()?;
+    }
+    Ok(items)
+}
+
+fn write_items<W: WriteRoseExt>(writer: &mut W, items: &[i16; 5]) -> Result<(), Error> {
+    for item in items {
+        writer.write_i16(*item)?;
+    }
+    Ok(())
+}
+
+/// String with a u16 length, padded with null bytes to 4 bytes
+fn read_padded_string<R: ReadRoseExt>(reader: &mut R) -> Result<String, Error> {
+    let string = reader.read_string_u16()?;
+    let end = reader.position()?;
+    skip(reader, ((4 - end % 4) % 4) as usize)?;
+    Ok(string)
+}
+
+fn write_padded_string<W: WriteRoseExt>(writer: &mut W, string: &str) -> Result<(), Error> {
+    writer.write_string_u16(string)?;
+    let end = writer.position()?;
+    writer.write_all(&vec![0u8; ((4 - end % 4) % 4) as usize])?;
+    Ok(())
+}
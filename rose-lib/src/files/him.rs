@@ -11,6 +11,7 @@ pub type HIM = Heightmap;
 
 /// Heightmap
 #[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Heightmap {
     pub width: i32,
     pub length: i32,
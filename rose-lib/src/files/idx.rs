@@ -45,6 +45,7 @@ pub type IDX = VfsIndex;
 /// the file systems. Each file system in the index usually maps to a single
 /// `.vfs` file on disk.
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct VfsIndex {
     pub base_version: i32,
     pub current_version: i32,
@@ -153,6 +154,7 @@ impl RoseFile for VfsIndex {
 ///
 /// Contains the metadata for a single file system.
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct VfsMetadata {
     pub filename: PathBuf,
     pub files: Vec<VfsFileMetadata>,
@@ -169,6 +171,7 @@ impl VfsMetadata {
 ///
 /// Contains the metadata for a single file in the file system
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct VfsFileMetadata {
     pub filepath: PathBuf,
     pub offset: i32,
@@ -12,6 +12,7 @@ use crate::utils::{Quaternion, Vector2, Vector3};
 pub type IFO = MapData;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum MapDataBlockType {
     MapInfo = 0,
     Object = 1,
@@ -54,18 +55,21 @@ impl TryFrom<u32> for MapDataBlockType {
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct OceanPatch {
     pub start: Vector3<f32>,
     pub end: Vector3<f32>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Ocean {
     pub size: f32,
     pub patches: Vec<OceanPatch>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ObjectData {
     pub name: String,
     pub warp_id: i16,
@@ -94,6 +98,7 @@ impl ObjectData {
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct NpcData {
     pub data: ObjectData,
     pub ai: i32,
@@ -101,6 +106,7 @@ pub struct NpcData {
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SoundData {
     pub data: ObjectData,
     pub file: String,
@@ -109,12 +115,14 @@ pub struct SoundData {
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct EffectData {
     pub data: ObjectData,
     pub file: String,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct EventData {
     data: ObjectData,
     function_name: String,
@@ -122,6 +130,7 @@ pub struct EventData {
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct WaterData {
     pub width: u32,
     pub height: u32,
@@ -130,6 +139,7 @@ pub struct WaterData {
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct MonsterSpawnPoint {
     pub name: String,
     pub monster: u32,
@@ -137,6 +147,7 @@ pub struct MonsterSpawnPoint {
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct MonsterSpawn {
     pub data: ObjectData,
     pub name: String,
@@ -149,6 +160,7 @@ pub struct MonsterSpawn {
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct MapData {
     pub map_pos: Vector2<i32>,
     pub zone_pos: Vector2<i32>,
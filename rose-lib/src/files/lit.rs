@@ -12,6 +12,7 @@ use crate::io::{ReadRoseExt, RoseFile, WriteRoseExt};
 pub type LIT = Lightmap;
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Lightmap {
     pub objects: Vec<LightmapObject>,
     pub filenames: Vec<String>,
@@ -85,6 +86,7 @@ impl RoseFile for Lightmap {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct LightmapObject {
     pub id: i32,
     pub parts: Vec<LightmapPart>,
@@ -106,6 +108,7 @@ impl Default for LightmapObject {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct LightmapPart {
     pub name: String,
     pub id: i32,
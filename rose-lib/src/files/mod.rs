@@ -1,8 +1,10 @@
 // pub mod idx;
+pub mod aip;
 pub mod him;
 pub mod idx;
 pub mod ifo;
 pub mod lit;
+pub mod qsd;
 pub mod stb;
 pub mod stl;
 pub mod til;
@@ -13,10 +15,12 @@ pub mod zms;
 pub mod zon;
 pub mod zsc;
 
+pub use self::aip::AIP;
 pub use self::him::HIM;
 pub use self::idx::IDX;
 pub use self::ifo::IFO;
 pub use self::lit::LIT;
+pub use self::qsd::QSD;
 pub use self::stb::STB;
 pub use self::stl::STL;
 pub use self::til::TIL;
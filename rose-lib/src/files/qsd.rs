@@ -0,0 +1,1680 @@
+//! ROSE Online Quest Data
+//!
+//! Quests are groups of triggers. A trigger gives its rewards, such as items,
+//! experience or changes of quest variables, if all of its conditions hold,
+//! and can fall through to the next trigger of its group. Conditions and
+//! rewards are records with a type and operands whose layout depends on the
+//! type, read into a variant per type with the layout of the server. Records
+//! of an unknown type, with operands of another size or with padding that
+//! isn't zero are kept as raw bytes so they are written back as they were
+//! read.
+use std::convert::TryFrom;
+use std::io::{Cursor, Read};
+
+use failure::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::io::{ReadRoseExt, RoseFile, WriteRoseExt};
+
+/// Quest Data File
+pub type QSD = QuestData;
+
+/// Size of the size and type of a condition or reward
+const COMMAND_HEADER_SIZE: u32 = 8;
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct QuestData {
+    pub version: u32,
+    pub name: String,
+    pub groups: Vec<QuestGroup>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct QuestGroup {
+    pub name: String,
+    pub triggers: Vec<QuestTrigger>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct QuestTrigger {
+    pub name: String,
+    /// Whether the next trigger of the group is checked if this one fails
+    pub check_next: bool,
+    pub conditions: Vec<QuestCondition>,
+    pub rewards: Vec<QuestReward>,
+}
+
+/// Condition or reward as stored in the file
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct QuestCommand {
+    /// Type of the condition or reward as stored in the file
+    pub opcode: u32,
+    /// Operands, as laid out by the client for the type
+    pub data: Vec<u8>,
+}
+
+/// Variable of the player or its quest, the variable `index` of the
+/// variables of a `kind`, checked or changed with `op` and `value`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct QuestVariable {
+    pub index: u16,
+    pub kind: u16,
+    pub value: i16,
+    pub op: u8,
+}
+
+/// Ability of the player, checked or changed with `op` and `value`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct QuestAbility {
+    pub ability: i32,
+    pub value: i32,
+    pub op: u8,
+}
+
+/// Item of the player, in the equipment slot `slot` or anywhere in the
+/// inventory, whose count is compared to `count` with `op`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct QuestItem {
+    /// Type times 1000 plus the row in the STB of the type
+    pub item: u32,
+    pub slot: i32,
+    pub count: i32,
+    pub op: u8,
+}
+
+/// Condition of a trigger, checked against the player, its quests or the
+/// selected NPC. `op` fields are comparison operators of the server.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum QuestCondition {
+    /// Selects a quest of `LIST_QUEST.STB` of the player for the following
+    /// conditions
+    SelectQuest {
+        quest: i32,
+    },
+    /// Variables of the selected quest
+    QuestVariables {
+        variables: Vec<QuestVariable>,
+    },
+    /// Variables of the player, such as its episode and job
+    Variables {
+        variables: Vec<QuestVariable>,
+    },
+    /// Abilities of the player
+    Abilities {
+        abilities: Vec<QuestAbility>,
+    },
+    /// Items of the player
+    Items {
+        items: Vec<QuestItem>,
+    },
+    /// Whether the player leads a party, with a level of at least `level`,
+    /// or below it if `reversed`
+    Party {
+        leader: u8,
+        level: i32,
+        reversed: u8,
+    },
+    /// Player within `radius` of a point of a zone
+    Position {
+        zone: i32,
+        x: i32,
+        y: i32,
+        z: i32,
+        radius: i32,
+    },
+    /// Time of the world, in seconds of its day
+    WorldTime {
+        start: u32,
+        end: u32,
+    },
+    /// Remaining time of the selected quest, in seconds
+    QuestTime {
+        time: u32,
+        op: u8,
+    },
+    /// Whether the player has a skill of `LIST_SKILL.STB` in a range
+    Skill {
+        start: i32,
+        end: i32,
+        has: u8,
+    },
+    /// Random percent in a range
+    Random {
+        min: u8,
+        max: u8,
+    },
+    /// Variable of the selected NPC (0) or event object (1)
+    ObjectVariable {
+        who: u8,
+        variable: i16,
+        value: i32,
+        op: u8,
+    },
+    /// Selects an event object of a zone for the following conditions
+    SelectEventObject {
+        zone: i16,
+        x: i32,
+        y: i32,
+        event: i32,
+    },
+    /// Selects an NPC of `LIST_NPC.STB` for the following conditions
+    SelectNpc {
+        npc: i32,
+    },
+    /// Switch of the player, on (1) or off (0)
+    Switch {
+        switch: i16,
+        value: u8,
+    },
+    /// Number of the members of the party in a range
+    PartyMemberCount {
+        min: i16,
+        max: i16,
+    },
+    /// Time of the zone of the selected NPC (0), event object (1) or player
+    /// (2), in seconds of its day
+    ObjectZoneTime {
+        who: u8,
+        start: u32,
+        end: u32,
+    },
+    /// Variable of an NPC of `LIST_NPC.STB` compared to the one of another
+    CompareNpcVariables {
+        npc: i32,
+        variable: i16,
+        other_npc: i32,
+        other_variable: i16,
+        op: u8,
+    },
+    /// Day of the month, 0 for every day, and time range
+    MonthDay {
+        day: u8,
+        start_hour: u8,
+        start_minute: u8,
+        end_hour: u8,
+        end_minute: u8,
+    },
+    /// Day of the week and time range
+    WeekDay {
+        day: u8,
+        start_hour: u8,
+        start_minute: u8,
+        end_hour: u8,
+        end_minute: u8,
+    },
+    /// Team of the player in a range
+    Team {
+        min: i32,
+        max: i32,
+    },
+    /// Player within `distance` of the selected NPC (0) or event object (1)
+    ObjectDistance {
+        who: u8,
+        distance: i32,
+    },
+    /// Channel of the server in a range
+    Channel {
+        min: u16,
+        max: u16,
+    },
+    /// Whether the player is in a clan
+    InClan {
+        in_clan: u8,
+    },
+    /// Position of the player in its clan
+    ClanPosition {
+        value: i16,
+        op: u8,
+    },
+    /// Points the player contributed to its clan
+    ClanContribution {
+        value: i16,
+        op: u8,
+    },
+    ClanLevel {
+        value: i16,
+        op: u8,
+    },
+    ClanPoints {
+        value: i16,
+        op: u8,
+    },
+    ClanMoney {
+        money: i32,
+        op: u8,
+    },
+    ClanMemberCount {
+        value: i16,
+        op: u8,
+    },
+    /// Whether the clan has a skill of `LIST_SKILL.STB` in a range
+    ClanSkill {
+        start: i16,
+        end: i16,
+        has: u8,
+    },
+    /// Condition of an unknown type, with operands of another size or
+    /// padding that isn't zero
+    Raw(QuestCommand),
+}
+
+/// Reward of a trigger. `op` fields are operators of the server, such as
+/// setting, adding or subtracting a value.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum QuestReward {
+    /// Removes (0), adds (1), changes the selected quest to, keeping (2) or
+    /// resetting (3) its variables, or selects (4) a quest of `LIST_QUEST.STB`
+    Quest {
+        quest: i32,
+        action: u8,
+    },
+    /// Takes (0) or gives (1) `count` of an item, to the party if `party`
+    Item {
+        item: u32,
+        op: u8,
+        count: i16,
+        party: u8,
+    },
+    /// Changes variables of the selected quest
+    QuestVariables {
+        variables: Vec<QuestVariable>,
+    },
+    /// Changes abilities of the player, or of its party if `party`
+    Abilities {
+        party: u8,
+        abilities: Vec<QuestAbility>,
+    },
+    /// Changes variables of the player
+    Variables {
+        variables: Vec<QuestVariable>,
+    },
+    /// Gives experience (0), money (1) or an item (2), an amount calculated
+    /// from `value` with an equation of `REWARD_CALC`
+    Calculated {
+        target: u8,
+        equation: u8,
+        value: i32,
+        item: u32,
+        party: u8,
+        item_option: i16,
+    },
+    /// Sets the HP and MP of the player in percent
+    HealthMana {
+        hp: i32,
+        mp: i32,
+        party: u8,
+    },
+    /// Teleports the player to a point of a zone
+    Teleport {
+        zone: i32,
+        x: i32,
+        y: i32,
+        party: u8,
+    },
+    /// Spawns `count` NPCs of `LIST_NPC.STB` within `range` of the selected
+    /// NPC (0), event object (1), player (2) or a point of a zone (3)
+    SpawnMonster {
+        npc: i32,
+        count: i32,
+        who: u8,
+        zone: i32,
+        x: i32,
+        y: i32,
+        range: i32,
+        team: i32,
+    },
+    /// Runs a trigger of the quests
+    Trigger {
+        trigger: String,
+    },
+    ResetStats,
+    /// Changes a variable of the selected NPC (0) or event object (1)
+    ObjectVariable {
+        who: u8,
+        variable: i16,
+        value: i32,
+        op: u8,
+    },
+    /// Says (0), shouts (1) or announces (2) a string of the quest strings
+    NpcMessage {
+        kind: u8,
+        message: i32,
+    },
+    /// Runs a trigger of the quests for the selected NPC (0), event object
+    /// (1) or player (2) after `seconds`
+    DelayedTrigger {
+        who: u8,
+        seconds: i16,
+        trigger: String,
+    },
+    /// Removes (0) or gives (1) a skill of `LIST_SKILL.STB`
+    Skill {
+        op: u8,
+        skill: i32,
+    },
+    /// Turns a switch of the player on (1) or off (0)
+    Switch {
+        switch: i16,
+        value: u8,
+    },
+    /// Turns the 32 switches of a group off
+    ClearSwitchGroup {
+        group: i16,
+    },
+    /// Turns every switch off
+    ClearSwitches,
+    /// Runs a trigger of the quests for the players of a team in a zone
+    ZoneTeamTrigger {
+        zone: i16,
+        team: i16,
+        trigger: String,
+    },
+    /// Sets the team of the player to its own (0), its party's (1) or its
+    /// clan's (2)
+    SetTeam {
+        source: u8,
+    },
+    /// Sets the point the player revives at in its zone
+    RevivePosition {
+        x: i32,
+        y: i32,
+    },
+    /// Turns the spawns of a zone off (0), on (1) or toggles them (2)
+    MonsterSpawns {
+        zone: i16,
+        op: u8,
+    },
+    ClanLevelUp,
+    ClanMoney {
+        money: i32,
+        op: u8,
+    },
+    ClanPoints {
+        value: i16,
+        op: u8,
+    },
+    /// Removes (0) or gives (1) a clan skill of `LIST_SKILL.STB`
+    ClanSkill {
+        skill: i16,
+        op: u8,
+    },
+    ClanContribution {
+        value: i16,
+        op: u8,
+    },
+    /// Teleports the clan members within `range` to a point of a zone
+    TeleportClanMembers {
+        range: i32,
+        zone: i16,
+        x: i32,
+        y: i32,
+    },
+    /// Calls a Lua function of the server
+    LuaFunction {
+        function: String,
+    },
+    ResetSkills,
+    /// Reward of an unknown type, with operands of another size or
+    /// padding that isn't zero
+    Raw(QuestCommand),
+}
+
+/// Opcode of the first condition type
+const CONDITION_OPCODE: u32 = 0;
+
+/// Opcode of the first reward type
+const REWARD_OPCODE: u32 = 0x0100_0000;
+
+impl QuestCondition {
+    /// Size of the operands of a condition type with a fixed layout
+    pub fn operand_size(opcode: u32) -> Option<usize> {
+        let size = match opcode.checked_sub(CONDITION_OPCODE)? {
+            0 | 10 | 13 | 14 | 15 | 22 | 23 | 24 | 25 | 26 | 27 | 29 => 4,
+            7 | 8 | 18 | 19 | 20 | 21 | 28 | 30 => 8,
+            5 | 9 | 11 | 16 => 12,
+            12 => 16,
+            6 | 17 => 20,
+            _ => return None,
+        };
+        Some(size)
+    }
+
+    /// Condition of a record, raw if it doesn't have the layout of its type
+    pub fn from_command(command: &QuestCommand) -> QuestCondition {
+        match QuestCondition::decode(command) {
+            Ok(condition) if condition.to_command() == *command => condition,
+            _ => QuestCondition::Raw(command.clone()),
+        }
+    }
+
+    /// Condition of a record, ignoring the values of its padding
+    pub fn decode(command: &QuestCommand) -> Result<QuestCondition, Error> {
+        check_size(
+            "condition",
+            command,
+            QuestCondition::operand_size(command.opcode),
+        )?;
+
+        let reader = &mut Cursor::new(command.data.as_slice());
+        let condition = match command.opcode.wrapping_sub(CONDITION_OPCODE) {
+            0 => {
+                let quest = reader.read_i32()?;
+                QuestCondition::SelectQuest { quest }
+            }
+            1 => {
+                let variables_count = reader.read_u32()?;
+                let variables = read_records(reader, variables_count)?;
+                QuestCondition::QuestVariables { variables }
+            }
+            2 => {
+                let variables_count = reader.read_u32()?;
+                let variables = read_records(reader, variables_count)?;
+                QuestCondition::Variables { variables }
+            }
+            3 => {
+                let abilities_count = reader.read_u32()?;
+                let abilities = read_records(reader, abilities_count)?;
+                QuestCondition::Abilities { abilities }
+            }
+            4 => {
+                let items_count = reader.read_u32()?;
+                let items = read_records(reader, items_count)?;
+                QuestCondition::Items { items }
+            }
+            5 => {
+                let leader = reader.read_u8()?;
+                skip(reader, 3)?;
+                let level = reader.read_i32()?;
+                let reversed = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestCondition::Party {
+                    leader,
+                    level,
+                    reversed,
+                }
+            }
+            6 => {
+                let zone = reader.read_i32()?;
+                let x = reader.read_i32()?;
+                let y = reader.read_i32()?;
+                let z = reader.read_i32()?;
+                let radius = reader.read_i32()?;
+                QuestCondition::Position {
+                    zone,
+                    x,
+                    y,
+                    z,
+                    radius,
+                }
+            }
+            7 => {
+                let start = reader.read_u32()?;
+                let end = reader.read_u32()?;
+                QuestCondition::WorldTime { start, end }
+            }
+            8 => {
+                let time = reader.read_u32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestCondition::QuestTime { time, op }
+            }
+            9 => {
+                let start = reader.read_i32()?;
+                let end = reader.read_i32()?;
+                let has = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestCondition::Skill { start, end, has }
+            }
+            10 => {
+                let min = reader.read_u8()?;
+                let max = reader.read_u8()?;
+                skip(reader, 2)?;
+                QuestCondition::Random { min, max }
+            }
+            11 => {
+                let who = reader.read_u8()?;
+                skip(reader, 1)?;
+                let variable = reader.read_i16()?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestCondition::ObjectVariable {
+                    who,
+                    variable,
+                    value,
+                    op,
+                }
+            }
+            12 => {
+                let zone = reader.read_i16()?;
+                skip(reader, 2)?;
+                let x = reader.read_i32()?;
+                let y = reader.read_i32()?;
+                let event = reader.read_i32()?;
+                QuestCondition::SelectEventObject { zone, x, y, event }
+            }
+            13 => {
+                let npc = reader.read_i32()?;
+                QuestCondition::SelectNpc { npc }
+            }
+            14 => {
+                let switch = reader.read_i16()?;
+                let value = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestCondition::Switch { switch, value }
+            }
+            15 => {
+                let min = reader.read_i16()?;
+                let max = reader.read_i16()?;
+                QuestCondition::PartyMemberCount { min, max }
+            }
+            16 => {
+                let who = reader.read_u8()?;
+                skip(reader, 3)?;
+                let start = reader.read_u32()?;
+                let end = reader.read_u32()?;
+                QuestCondition::ObjectZoneTime { who, start, end }
+            }
+            17 => {
+                let npc = reader.read_i32()?;
+                let variable = reader.read_i16()?;
+                skip(reader, 2)?;
+                let other_npc = reader.read_i32()?;
+                let other_variable = reader.read_i16()?;
+                skip(reader, 2)?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestCondition::CompareNpcVariables {
+                    npc,
+                    variable,
+                    other_npc,
+                    other_variable,
+                    op,
+                }
+            }
+            18 => {
+                let day = reader.read_u8()?;
+                let start_hour = reader.read_u8()?;
+                let start_minute = reader.read_u8()?;
+                let end_hour = reader.read_u8()?;
+                let end_minute = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestCondition::MonthDay {
+                    day,
+                    start_hour,
+                    start_minute,
+                    end_hour,
+                    end_minute,
+                }
+            }
+            19 => {
+                let day = reader.read_u8()?;
+                let start_hour = reader.read_u8()?;
+                let start_minute = reader.read_u8()?;
+                let end_hour = reader.read_u8()?;
+                let end_minute = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestCondition::WeekDay {
+                    day,
+                    start_hour,
+                    start_minute,
+                    end_hour,
+                    end_minute,
+                }
+            }
+            20 => {
+                let min = reader.read_i32()?;
+                let max = reader.read_i32()?;
+                QuestCondition::Team { min, max }
+            }
+            21 => {
+                let who = reader.read_u8()?;
+                skip(reader, 3)?;
+                let distance = reader.read_i32()?;
+                QuestCondition::ObjectDistance { who, distance }
+            }
+            22 => {
+                let min = reader.read_u16()?;
+                let max = reader.read_u16()?;
+                QuestCondition::Channel { min, max }
+            }
+            23 => {
+                let in_clan = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestCondition::InClan { in_clan }
+            }
+            24 => {
+                let value = reader.read_i16()?;
+                let op = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestCondition::ClanPosition { value, op }
+            }
+            25 => {
+                let value = reader.read_i16()?;
+                let op = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestCondition::ClanContribution { value, op }
+            }
+            26 => {
+                let value = reader.read_i16()?;
+                let op = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestCondition::ClanLevel { value, op }
+            }
+            27 => {
+                let value = reader.read_i16()?;
+                let op = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestCondition::ClanPoints { value, op }
+            }
+            28 => {
+                let money = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestCondition::ClanMoney { money, op }
+            }
+            29 => {
+                let value = reader.read_i16()?;
+                let op = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestCondition::ClanMemberCount { value, op }
+            }
+            30 => {
+                let start = reader.read_i16()?;
+                let end = reader.read_i16()?;
+                let has = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestCondition::ClanSkill { start, end, has }
+            }
+            _ => bail!("unknown condition opcode {:#010x}", command.opcode),
+        };
+        check_end("condition", command, reader)?;
+        Ok(condition)
+    }
+
+    /// Record of the condition, with zero padding
+    pub fn to_command(&self) -> QuestCommand {
+        if let QuestCondition::Raw(command) = self {
+            return command.clone();
+        }
+
+        let mut writer = Cursor::new(Vec::new());
+        // Writing to memory can't fail
+        let number = self.write_operands(&mut writer).unwrap();
+        QuestCommand {
+            opcode: CONDITION_OPCODE + number,
+            data: writer.into_inner(),
+        }
+    }
+
+    fn write_operands<W: WriteRoseExt>(&self, writer: &mut W) -> Result<u32, Error> {
+        let number = match self {
+            QuestCondition::SelectQuest { quest } => {
+                writer.write_i32(*quest)?;
+                0
+            }
+            QuestCondition::QuestVariables { variables } => {
+                writer.write_u32(u32::try_from(variables.len())?)?;
+                write_records(writer, variables)?;
+                1
+            }
+            QuestCondition::Variables { variables } => {
+                writer.write_u32(u32::try_from(variables.len())?)?;
+                write_records(writer, variables)?;
+                2
+            }
+            QuestCondition::Abilities { abilities } => {
+                writer.write_u32(u32::try_from(abilities.len())?)?;
+                write_records(writer, abilities)?;
+                3
+            }
+            QuestCondition::Items { items } => {
+                writer.write_u32(u32::try_from(items.len())?)?;
+                write_records(writer, items)?;
+                4
+            }
+            QuestCondition::Party {
+                leader,
+                level,
+                reversed,
+            } => {
+                writer.write_u8(*leader)?;
+                writer.write_all(&[0; 3])?;
+                writer.write_i32(*level)?;
+                writer.write_u8(*reversed)?;
+                writer.write_all(&[0; 3])?;
+                5
+            }
+            QuestCondition::Position {
+                zone,
+                x,
+                y,
+                z,
+                radius,
+            } => {
+                writer.write_i32(*zone)?;
+                writer.write_i32(*x)?;
+                writer.write_i32(*y)?;
+                writer.write_i32(*z)?;
+                writer.write_i32(*radius)?;
+                6
+            }
+            QuestCondition::WorldTime { start, end } => {
+                writer.write_u32(*start)?;
+                writer.write_u32(*end)?;
+                7
+            }
+            QuestCondition::QuestTime { time, op } => {
+                writer.write_u32(*time)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                8
+            }
+            QuestCondition::Skill { start, end, has } => {
+                writer.write_i32(*start)?;
+                writer.write_i32(*end)?;
+                writer.write_u8(*has)?;
+                writer.write_all(&[0; 3])?;
+                9
+            }
+            QuestCondition::Random { min, max } => {
+                writer.write_u8(*min)?;
+                writer.write_u8(*max)?;
+                writer.write_all(&[0; 2])?;
+                10
+            }
+            QuestCondition::ObjectVariable {
+                who,
+                variable,
+                value,
+                op,
+            } => {
+                writer.write_u8(*who)?;
+                writer.write_all(&[0; 1])?;
+                writer.write_i16(*variable)?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                11
+            }
+            QuestCondition::SelectEventObject { zone, x, y, event } => {
+                writer.write_i16(*zone)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*x)?;
+                writer.write_i32(*y)?;
+                writer.write_i32(*event)?;
+                12
+            }
+            QuestCondition::SelectNpc { npc } => {
+                writer.write_i32(*npc)?;
+                13
+            }
+            QuestCondition::Switch { switch, value } => {
+                writer.write_i16(*switch)?;
+                writer.write_u8(*value)?;
+                writer.write_all(&[0; 1])?;
+                14
+            }
+            QuestCondition::PartyMemberCount { min, max } => {
+                writer.write_i16(*min)?;
+                writer.write_i16(*max)?;
+                15
+            }
+            QuestCondition::ObjectZoneTime { who, start, end } => {
+                writer.write_u8(*who)?;
+                writer.write_all(&[0; 3])?;
+                writer.write_u32(*start)?;
+                writer.write_u32(*end)?;
+                16
+            }
+            QuestCondition::CompareNpcVariables {
+                npc,
+                variable,
+                other_npc,
+                other_variable,
+                op,
+            } => {
+                writer.write_i32(*npc)?;
+                writer.write_i16(*variable)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*other_npc)?;
+                writer.write_i16(*other_variable)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                17
+            }
+            QuestCondition::MonthDay {
+                day,
+                start_hour,
+                start_minute,
+                end_hour,
+                end_minute,
+            } => {
+                writer.write_u8(*day)?;
+                writer.write_u8(*start_hour)?;
+                writer.write_u8(*start_minute)?;
+                writer.write_u8(*end_hour)?;
+                writer.write_u8(*end_minute)?;
+                writer.write_all(&[0; 3])?;
+                18
+            }
+            QuestCondition::WeekDay {
+                day,
+                start_hour,
+                start_minute,
+                end_hour,
+                end_minute,
+            } => {
+                writer.write_u8(*day)?;
+                writer.write_u8(*start_hour)?;
+                writer.write_u8(*start_minute)?;
+                writer.write_u8(*end_hour)?;
+                writer.write_u8(*end_minute)?;
+                writer.write_all(&[0; 3])?;
+                19
+            }
+            QuestCondition::Team { min, max } => {
+                writer.write_i32(*min)?;
+                writer.write_i32(*max)?;
+                20
+            }
+            QuestCondition::ObjectDistance { who, distance } => {
+                writer.write_u8(*who)?;
+                writer.write_all(&[0; 3])?;
+                writer.write_i32(*distance)?;
+                21
+            }
+            QuestCondition::Channel { min, max } => {
+                writer.write_u16(*min)?;
+                writer.write_u16(*max)?;
+                22
+            }
+            QuestCondition::InClan { in_clan } => {
+                writer.write_u8(*in_clan)?;
+                writer.write_all(&[0; 3])?;
+                23
+            }
+            QuestCondition::ClanPosition { value, op } => {
+                writer.write_i16(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 1])?;
+                24
+            }
+            QuestCondition::ClanContribution { value, op } => {
+                writer.write_i16(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 1])?;
+                25
+            }
+            QuestCondition::ClanLevel { value, op } => {
+                writer.write_i16(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 1])?;
+                26
+            }
+            QuestCondition::ClanPoints { value, op } => {
+                writer.write_i16(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 1])?;
+                27
+            }
+            QuestCondition::ClanMoney { money, op } => {
+                writer.write_i32(*money)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                28
+            }
+            QuestCondition::ClanMemberCount { value, op } => {
+                writer.write_i16(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 1])?;
+                29
+            }
+            QuestCondition::ClanSkill { start, end, has } => {
+                writer.write_i16(*start)?;
+                writer.write_i16(*end)?;
+                writer.write_u8(*has)?;
+                writer.write_all(&[0; 3])?;
+                30
+            }
+            QuestCondition::Raw(_) => unreachable!(),
+        };
+        Ok(number)
+    }
+}
+
+impl QuestReward {
+    /// Size of the operands of a reward type with a fixed layout
+    pub fn operand_size(opcode: u32) -> Option<usize> {
+        let size = match opcode.checked_sub(REWARD_OPCODE)? {
+            10 | 17 | 23 | 30 => 0,
+            15 | 16 | 20 | 22 | 25 | 26 | 27 => 4,
+            0 | 12 | 14 | 21 | 24 => 8,
+            1 | 6 | 11 => 12,
+            5 | 7 | 28 => 16,
+            8 => 32,
+            _ => return None,
+        };
+        Some(size)
+    }
+
+    /// Reward of a record, raw if it doesn't have the layout of its type
+    pub fn from_command(command: &QuestCommand) -> QuestReward {
+        match QuestReward::decode(command) {
+            Ok(reward) if reward.to_command() == *command => reward,
+            _ => QuestReward::Raw(command.clone()),
+        }
+    }
+
+    /// Reward of a record, ignoring the values of its padding
+    pub fn decode(command: &QuestCommand) -> Result<QuestReward, Error> {
+        check_size("reward", command, QuestReward::operand_size(command.opcode))?;
+
+        let reader = &mut Cursor::new(command.data.as_slice());
+        let reward = match command.opcode.wrapping_sub(REWARD_OPCODE) {
+            0 => {
+                let quest = reader.read_i32()?;
+                let action = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestReward::Quest { quest, action }
+            }
+            1 => {
+                let item = reader.read_u32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 1)?;
+                let count = reader.read_i16()?;
+                let party = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestReward::Item {
+                    item,
+                    op,
+                    count,
+                    party,
+                }
+            }
+            2 => {
+                let variables_count = reader.read_u32()?;
+                let variables = read_records(reader, variables_count)?;
+                QuestReward::QuestVariables { variables }
+            }
+            3 => {
+                let abilities_count = reader.read_u32()?;
+                let party = reader.read_u8()?;
+                skip(reader, 3)?;
+                let abilities = read_records(reader, abilities_count)?;
+                QuestReward::Abilities { party, abilities }
+            }
+            4 => {
+                let variables_count = reader.read_u32()?;
+                let variables = read_records(reader, variables_count)?;
+                QuestReward::Variables { variables }
+            }
+            5 => {
+                let target = reader.read_u8()?;
+                let equation = reader.read_u8()?;
+                skip(reader, 2)?;
+                let value = reader.read_i32()?;
+                let item = reader.read_u32()?;
+                let party = reader.read_u8()?;
+                skip(reader, 1)?;
+                let item_option = reader.read_i16()?;
+                QuestReward::Calculated {
+                    target,
+                    equation,
+                    value,
+                    item,
+                    party,
+                    item_option,
+                }
+            }
+            6 => {
+                let hp = reader.read_i32()?;
+                let mp = reader.read_i32()?;
+                let party = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestReward::HealthMana { hp, mp, party }
+            }
+            7 => {
+                let zone = reader.read_i32()?;
+                let x = reader.read_i32()?;
+                let y = reader.read_i32()?;
+                let party = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestReward::Teleport { zone, x, y, party }
+            }
+            8 => {
+                let npc = reader.read_i32()?;
+                let count = reader.read_i32()?;
+                let who = reader.read_u8()?;
+                skip(reader, 3)?;
+                let zone = reader.read_i32()?;
+                let x = reader.read_i32()?;
+                let y = reader.read_i32()?;
+                let range = reader.read_i32()?;
+                let team = reader.read_i32()?;
+                QuestReward::SpawnMonster {
+                    npc,
+                    count,
+                    who,
+                    zone,
+                    x,
+                    y,
+                    range,
+                    team,
+                }
+            }
+            9 => {
+                let trigger = read_padded_string(reader)?;
+                QuestReward::Trigger { trigger }
+            }
+            10 => QuestReward::ResetStats,
+            11 => {
+                let who = reader.read_u8()?;
+                skip(reader, 1)?;
+                let variable = reader.read_i16()?;
+                let value = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestReward::ObjectVariable {
+                    who,
+                    variable,
+                    value,
+                    op,
+                }
+            }
+            12 => {
+                let kind = reader.read_u8()?;
+                skip(reader, 3)?;
+                let message = reader.read_i32()?;
+                QuestReward::NpcMessage { kind, message }
+            }
+            13 => {
+                let who = reader.read_u8()?;
+                skip(reader, 1)?;
+                let seconds = reader.read_i16()?;
+                let trigger = read_padded_string(reader)?;
+                QuestReward::DelayedTrigger {
+                    who,
+                    seconds,
+                    trigger,
+                }
+            }
+            14 => {
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                let skill = reader.read_i32()?;
+                QuestReward::Skill { op, skill }
+            }
+            15 => {
+                let switch = reader.read_i16()?;
+                let value = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestReward::Switch { switch, value }
+            }
+            16 => {
+                let group = reader.read_i16()?;
+                skip(reader, 2)?;
+                QuestReward::ClearSwitchGroup { group }
+            }
+            17 => QuestReward::ClearSwitches,
+            19 => {
+                let zone = reader.read_i16()?;
+                let team = reader.read_i16()?;
+                let trigger = read_padded_string(reader)?;
+                QuestReward::ZoneTeamTrigger {
+                    zone,
+                    team,
+                    trigger,
+                }
+            }
+            20 => {
+                let source = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestReward::SetTeam { source }
+            }
+            21 => {
+                let x = reader.read_i32()?;
+                let y = reader.read_i32()?;
+                QuestReward::RevivePosition { x, y }
+            }
+            22 => {
+                let zone = reader.read_i16()?;
+                let op = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestReward::MonsterSpawns { zone, op }
+            }
+            23 => QuestReward::ClanLevelUp,
+            24 => {
+                let money = reader.read_i32()?;
+                let op = reader.read_u8()?;
+                skip(reader, 3)?;
+                QuestReward::ClanMoney { money, op }
+            }
+            25 => {
+                let value = reader.read_i16()?;
+                let op = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestReward::ClanPoints { value, op }
+            }
+            26 => {
+                let skill = reader.read_i16()?;
+                let op = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestReward::ClanSkill { skill, op }
+            }
+            27 => {
+                let value = reader.read_i16()?;
+                let op = reader.read_u8()?;
+                skip(reader, 1)?;
+                QuestReward::ClanContribution { value, op }
+            }
+            28 => {
+                let range = reader.read_i32()?;
+                let zone = reader.read_i16()?;
+                skip(reader, 2)?;
+                let x = reader.read_i32()?;
+                let y = reader.read_i32()?;
+                QuestReward::TeleportClanMembers { range, zone, x, y }
+            }
+            29 => {
+                let function = read_padded_string(reader)?;
+                QuestReward::LuaFunction { function }
+            }
+            30 => QuestReward::ResetSkills,
+            _ => bail!("unknown reward opcode {:#010x}", command.opcode),
+        };
+        check_end("reward", command, reader)?;
+        Ok(reward)
+    }
+
+    /// Record of the reward, with zero padding
+    pub fn to_command(&self) -> QuestCommand {
+        if let QuestReward::Raw(command) = self {
+            return command.clone();
+        }
+
+        let mut writer = Cursor::new(Vec::new());
+        // Writing to memory can't fail
+        let number = self.write_operands(&mut writer).unwrap();
+        QuestCommand {
+            opcode: REWARD_OPCODE + number,
+            data: writer.into_inner(),
+        }
+    }
+
+    fn write_operands<W: WriteRoseExt>(&self, writer: &mut W) -> Result<u32, Error> {
+        let number = match self {
+            QuestReward::Quest { quest, action } => {
+                writer.write_i32(*quest)?;
+                writer.write_u8(*action)?;
+                writer.write_all(&[0; 3])?;
+                0
+            }
+            QuestReward::Item {
+                item,
+                op,
+                count,
+                party,
+            } => {
+                writer.write_u32(*item)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 1])?;
+                writer.write_i16(*count)?;
+                writer.write_u8(*party)?;
+                writer.write_all(&[0; 3])?;
+                1
+            }
+            QuestReward::QuestVariables { variables } => {
+                writer.write_u32(u32::try_from(variables.len())?)?;
+                write_records(writer, variables)?;
+                2
+            }
+            QuestReward::Abilities { party, abilities } => {
+                writer.write_u32(u32::try_from(abilities.len())?)?;
+                writer.write_u8(*party)?;
+                writer.write_all(&[0; 3])?;
+                write_records(writer, abilities)?;
+                3
+            }
+            QuestReward::Variables { variables } => {
+                writer.write_u32(u32::try_from(variables.len())?)?;
+                write_records(writer, variables)?;
+                4
+            }
+            QuestReward::Calculated {
+                target,
+                equation,
+                value,
+                item,
+                party,
+                item_option,
+            } => {
+                writer.write_u8(*target)?;
+                writer.write_u8(*equation)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*value)?;
+                writer.write_u32(*item)?;
+                writer.write_u8(*party)?;
+                writer.write_all(&[0; 1])?;
+                writer.write_i16(*item_option)?;
+                5
+            }
+            QuestReward::HealthMana { hp, mp, party } => {
+                writer.write_i32(*hp)?;
+                writer.write_i32(*mp)?;
+                writer.write_u8(*party)?;
+                writer.write_all(&[0; 3])?;
+                6
+            }
+            QuestReward::Teleport { zone, x, y, party } => {
+                writer.write_i32(*zone)?;
+                writer.write_i32(*x)?;
+                writer.write_i32(*y)?;
+                writer.write_u8(*party)?;
+                writer.write_all(&[0; 3])?;
+                7
+            }
+            QuestReward::SpawnMonster {
+                npc,
+                count,
+                who,
+                zone,
+                x,
+                y,
+                range,
+                team,
+            } => {
+                writer.write_i32(*npc)?;
+                writer.write_i32(*count)?;
+                writer.write_u8(*who)?;
+                writer.write_all(&[0; 3])?;
+                writer.write_i32(*zone)?;
+                writer.write_i32(*x)?;
+                writer.write_i32(*y)?;
+                writer.write_i32(*range)?;
+                writer.write_i32(*team)?;
+                8
+            }
+            QuestReward::Trigger { trigger } => {
+                write_padded_string(writer, trigger)?;
+                9
+            }
+            QuestReward::ResetStats => 10,
+            QuestReward::ObjectVariable {
+                who,
+                variable,
+                value,
+                op,
+            } => {
+                writer.write_u8(*who)?;
+                writer.write_all(&[0; 1])?;
+                writer.write_i16(*variable)?;
+                writer.write_i32(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                11
+            }
+            QuestReward::NpcMessage { kind, message } => {
+                writer.write_u8(*kind)?;
+                writer.write_all(&[0; 3])?;
+                writer.write_i32(*message)?;
+                12
+            }
+            QuestReward::DelayedTrigger {
+                who,
+                seconds,
+                trigger,
+            } => {
+                writer.write_u8(*who)?;
+                writer.write_all(&[0; 1])?;
+                writer.write_i16(*seconds)?;
+                write_padded_string(writer, trigger)?;
+                13
+            }
+            QuestReward::Skill { op, skill } => {
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                writer.write_i32(*skill)?;
+                14
+            }
+            QuestReward::Switch { switch, value } => {
+                writer.write_i16(*switch)?;
+                writer.write_u8(*value)?;
+                writer.write_all(&[0; 1])?;
+                15
+            }
+            QuestReward::ClearSwitchGroup { group } => {
+                writer.write_i16(*group)?;
+                writer.write_all(&[0; 2])?;
+                16
+            }
+            QuestReward::ClearSwitches => 17,
+            QuestReward::ZoneTeamTrigger {
+                zone,
+                team,
+                trigger,
+            } => {
+                writer.write_i16(*zone)?;
+                writer.write_i16(*team)?;
+                write_padded_string(writer, trigger)?;
+                19
+            }
+            QuestReward::SetTeam { source } => {
+                writer.write_u8(*source)?;
+                writer.write_all(&[0; 3])?;
+                20
+            }
+            QuestReward::RevivePosition { x, y } => {
+                writer.write_i32(*x)?;
+                writer.write_i32(*y)?;
+                21
+            }
+            QuestReward::MonsterSpawns { zone, op } => {
+                writer.write_i16(*zone)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 1])?;
+                22
+            }
+            QuestReward::ClanLevelUp => 23,
+            QuestReward::ClanMoney { money, op } => {
+                writer.write_i32(*money)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 3])?;
+                24
+            }
+            QuestReward::ClanPoints { value, op } => {
+                writer.write_i16(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 1])?;
+                25
+            }
+            QuestReward::ClanSkill { skill, op } => {
+                writer.write_i16(*skill)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 1])?;
+                26
+            }
+            QuestReward::ClanContribution { value, op } => {
+                writer.write_i16(*value)?;
+                writer.write_u8(*op)?;
+                writer.write_all(&[0; 1])?;
+                27
+            }
+            QuestReward::TeleportClanMembers { range, zone, x, y } => {
+                writer.write_i32(*range)?;
+                writer.write_i16(*zone)?;
+                writer.write_all(&[0; 2])?;
+                writer.write_i32(*x)?;
+                writer.write_i32(*y)?;
+                28
+            }
+            QuestReward::LuaFunction { function } => {
+                write_padded_string(writer, function)?;
+                29
+            }
+            QuestReward::ResetSkills => 30,
+            QuestReward::Raw(_) => unreachable!(),
+        };
+        Ok(number)
+    }
+}
+
+impl QuestData {
+    /// Every trigger of every group
+    pub fn triggers(&self) -> impl Iterator<Item = &QuestTrigger> {
+        self.groups.iter().flat_map(|g| g.triggers.iter())
+    }
+}
+
+impl RoseFile for QuestData {
+    fn new() -> QuestData {
+        Self::default()
+    }
+
+    fn read<R: ReadRoseExt>(&mut self, reader: &mut R) -> Result<(), Error> {
+        self.version = reader.read_u32()?;
+        let group_count = reader.read_u32()?;
+        self.name = reader.read_string_u16()?;
+
+        for _ in 0..group_count {
+            let trigger_count = reader.read_u32()?;
+            let mut group = QuestGroup {
+                name: reader.read_string_u16()?,
+                triggers: Vec::new(),
+            };
+
+            for _ in 0..trigger_count {
+                let check_next = reader.read_bool()?;
+                let condition_count = reader.read_u32()?;
+                let reward_count = reader.read_u32()?;
+                let mut trigger = QuestTrigger {
+                    name: reader.read_string_u16()?,
+                    check_next,
+                    ..Default::default()
+                };
+
+                for _ in 0..condition_count {
+                    let command = read_command(reader)?;
+                    trigger
+                        .conditions
+                        .push(QuestCondition::from_command(&command));
+                }
+                for _ in 0..reward_count {
+                    let command = read_command(reader)?;
+                    trigger.rewards.push(QuestReward::from_command(&command));
+                }
+                group.triggers.push(trigger);
+            }
+            self.groups.push(group);
+        }
+
+        Ok(())
+    }
+
+    fn write<W: WriteRoseExt>(&mut self, writer: &mut W) -> Result<(), Error> {
+        writer.write_u32(self.version)?;
+        writer.write_u32(u32::try_from(self.groups.len())?)?;
+        writer.write_string_u16(&self.name)?;
+
+        for group in &self.groups {
+            writer.write_u32(u32::try_from(group.triggers.len())?)?;
+            writer.write_string_u16(&group.name)?;
+
+            for trigger in &group.triggers {
+                writer.write_bool(trigger.check_next)?;
+                writer.write_u32(u32::try_from(trigger.conditions.len())?)?;
+                writer.write_u32(u32::try_from(trigger.rewards.len())?)?;
+                writer.write_string_u16(&trigger.name)?;
+
+                for condition in &trigger.conditions {
+                    write_command(writer, &condition.to_command())?;
+                }
+                for reward in &trigger.rewards {
+                    write_command(writer, &reward.to_command())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn read_command<R: ReadRoseExt>(reader: &mut R) -> Result<QuestCommand, Error> {
+    let size = reader.read_u32()?;
+    let opcode = reader.read_u32()?;
+    if size < COMMAND_HEADER_SIZE {
+        bail!("Invalid size of quest command {:#x}: {}", opcode, size);
+    }
+
+    // Read through `take` so a corrupt size can't allocate more than the
+    // bytes left in the file
+    let len = u64::from(size - COMMAND_HEADER_SIZE);
+    let mut data = Vec::new();
+    reader.by_ref().take(len).read_to_end(&mut data)?;
+    if data.len() as u64 != len {
+        bail!(
+            "Truncated quest command {:#x}: {} of {} bytes of operands",
+            opcode,
+            data.len(),
+            len
+        );
+    }
+    Ok(QuestCommand { opcode, data })
+}
+
+fn write_command<W: WriteRoseExt>(writer: &mut W, command: &QuestCommand) -> Result<(), Error> {
+    writer.write_u32(u32::try_from(command.data.len())? + COMMAND_HEADER_SIZE)?;
+    writer.write_u32(command.opcode)?;
+    writer.write_all(&command.data)?;
+    Ok(())
+}
+
+/// Error if the operands of a record with a fixed layout have another size
+fn check_size(kind: &str, command: &QuestCommand, size: Option<usize>) -> Result<(), Error> {
+    match size {
+        Some(size) if command.data.len() != size => bail!(
+            "{} {:#010x} has {} bytes of operands instead of {}",
+            kind,
+            command.opcode,
+            command.data.len(),
+            size
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Error if the layout of a record doesn't cover all of its operands
+fn check_end(kind: &str, command: &QuestCommand, reader: &mut Cursor<&[u8]>) -> Result<(), Error> {
+    let end = Cursor::position(reader) as usize;
+    if end != command.data.len() {
+        bail!(
+            "{} {:#010x} has {} bytes of operands instead of {}",
+            kind,
+            command.opcode,
+            command.data.len(),
+            end
+        );
+    }
+    Ok(())
+}
+
+fn skip<R: ReadRoseExt>(reader: &mut R, size: usize) -> Result<(), Error> {
+    reader.read_exact(&mut vec![0u8; size])?;
+    Ok(())
+}
+
+/// Record of the lists of conditions and rewards, following their count
+trait QuestRecord: Sized {
+    fn read<R: ReadRoseExt>(reader: &mut R) -> Result<Self, Error>;
+    fn write<W: WriteRoseExt>(&self, writer: &mut W) -> Result<(), Error>;
+}
+
+impl QuestRecord for QuestVariable {
+    fn read<R: ReadRoseExt>(reader: &mut R) -> Result<Self, Error> {
+        let index = reader.read_u16()?;
+        let kind = reader.read_u16()?;
+        let value = reader.read_i16()?;
+        let op = reader.read_u8()?;
+        skip(reader, 1)?;
+        Ok(QuestVariable {
+            index,
+            kind,
+            value,
+            op,
+        })
+    }
+
+    fn write<W: WriteRoseExt>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_u16(self.index)?;
+        writer.write_u16(self.kind)?;
+        writer.write_i16(self.value)?;
+        writer.write_u8(self.op)?;
+        writer.write_all(&[0; 1])?;
+        Ok(())
+    }
+}
+
+impl QuestRecord for QuestAbility {
+    fn read<R: ReadRoseExt>(reader: &mut R) -> Result<Self, Error> {
+        let ability = reader.read_i32()?;
+        let value = reader.read_i32()?;
+        let op = reader.read_u8()?;
+        skip(reader, 3)?;
+        Ok(QuestAbility { ability, value, op })
+    }
+
+    fn write<W: WriteRoseExt>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_i32(self.ability)?;
+        writer.write_i32(self.value)?;
+        writer.write_u8(self.op)?;
+        writer.write_all(&[0; 3])?;
+        Ok(())
+    }
+}
+
+impl QuestRecord for QuestItem {
+    fn read<R: ReadRoseExt>(reader: &mut R) -> Result<Self, Error> {
+        let item = reader.read_u32()?;
+        let slot = reader.read_i32()?;
+        let count = reader.read_i32()?;
+        let op = reader.read_u8()?;
+        skip(reader, 3)?;
+        Ok(QuestItem {
+            item,
+            slot,
+            count,
+            op,
+        })
+    }
+
+    fn write<W: WriteRoseExt>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_u32(self.item)?;
+        writer.write_i32(self.slot)?;
+        writer.write_i32(self.count)?;
+        writer.write_u8(self.op)?;
+        writer.write_all(&[0; 3])?;
+        Ok(())
+    }
+}
+
+fn read_records<T: QuestRecord, R: ReadRoseExt>(
+    reader: &mut R,
+    count: u32,
+) -> Result<Vec<T>, Error> {
+    (0..count).map(|_| T::read(reader)).collect()
+}
+
+fn write_records<T: QuestRecord, W: WriteRoseExt>(
+    writer: &mut W,
+    records: &[T],
+) -> Result<(), Error> {
+    for record in records {
+        record.write(writer)?;
+    }
+    Ok(())
+}
+
+/// String with a u16 length, padded with null bytes to 4 bytes
+fn read_padded_string<R: ReadRoseExt>(reader: &mut R) -> Result<String, Error> {
+    let string = reader.read_string_u16()?;
+    let end = reader.position()?;
+    skip(reader, ((4 - end % 4) % 4) as usize)?;
+    Ok(string)
+}
+
+fn write_padded_string<W: WriteRoseExt>(writer: &mut W, string: &str) -> Result<(), Error> {
+    writer.write_string_u16(string)?;
+    let end = writer.position()?;
+    writer.write_all(&vec![0u8; ((4 - end % 4) % 4) as usize])?;
+    Ok(())
+}
@@ -10,10 +10,21 @@ use crate::io::{ReadRoseExt, RoseFile, WriteRoseExt};
 pub type STB = DataTable;
 
 /// Data Table
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct DataTable {
     pub identifier: String,
     pub headers: Vec<String>,
+    /// Name of the header row, not used by the client
+    #[serde(default)]
+    pub root_row: String,
+    /// Display height of the rows, not used by the client
+    #[serde(default)]
+    pub row_height: u32,
+    /// Display width of the root column followed by the other columns, not
+    /// used by the client. Missing widths are 0.
+    #[serde(default)]
+    pub column_widths: Vec<u16>,
     pub data: Vec<Vec<String>>,
 }
 
@@ -22,10 +33,34 @@ impl Default for DataTable {
         DataTable {
             identifier: String::from("STB1"),
             headers: Vec::new(),
+            root_row: String::new(),
+            row_height: 0,
+            column_widths: Vec::new(),
             data: Vec::new(),
         }
     }
 }
+/// Tables are equal if they're written the same, with missing column widths
+/// as 0
+impl PartialEq for DataTable {
+    fn eq(&self, other: &DataTable) -> bool {
+        let widths = |stb: &DataTable| {
+            let len = stb
+                .column_widths
+                .iter()
+                .rposition(|&w| w != 0)
+                .map_or(0, |idx| idx + 1);
+            stb.column_widths[..len].to_vec()
+        };
+        self.identifier == other.identifier
+            && self.headers == other.headers
+            && self.root_row == other.root_row
+            && self.row_height == other.row_height
+            && widths(self) == widths(other)
+            && self.data == other.data
+    }
+}
+
 impl DataTable {
     pub fn rows(&self) -> usize {
         self.data.len()
@@ -52,6 +87,62 @@ impl DataTable {
         }
         None
     }
+
+    /// Write data to a writer, encoding the strings as wide-strings (UTF-16LE)
+    /// if `wide_strings` is set
+    pub fn write_with<W: WriteRoseExt>(
+        &mut self,
+        writer: &mut W,
+        wide_strings: bool,
+    ) -> Result<(), Error> {
+        let write_string = |writer: &mut W, s: &str| {
+            if wide_strings {
+                writer.write_wstring_u16(s)
+            } else {
+                writer.write_string_u16(s)
+            }
+        };
+
+        writer.write_string(&self.identifier, 4)?;
+
+        // Write temporary offset
+        writer.write_u32(0)?;
+
+        writer.write_u32((self.data.len() + 1) as u32)?;
+        writer.write_u32(self.headers.len() as u32)?;
+
+        writer.write_u32(self.row_height)?;
+
+        // Root column width followed by the column widths, missing widths
+        // are written as 0
+        for idx in 0..self.headers.len() + 1 {
+            writer.write_u16(self.column_widths.get(idx).cloned().unwrap_or(0))?;
+        }
+
+        for header in &self.headers {
+            // Column names
+            write_string(writer, header)?;
+        }
+
+        write_string(writer, &self.root_row)?;
+
+        for row in &self.data {
+            write_string(writer, &row[0])?;
+        }
+
+        let offset = writer.seek(SeekFrom::Current(0))?;
+
+        for row in &self.data {
+            for cell in row.iter().skip(1) {
+                write_string(writer, cell)?;
+            }
+        }
+
+        writer.seek(SeekFrom::Start(4))?;
+        writer.write_u32(offset as u32)?;
+
+        Ok(())
+    }
 }
 
 impl RoseFile for DataTable {
@@ -60,17 +151,22 @@ impl RoseFile for DataTable {
     }
 
     fn read<R: ReadRoseExt>(&mut self, reader: &mut R) -> Result<(), Error> {
-        self.identifier = reader.read_string(4)?;
+        // The identifier is never a wide-string
+        let mut identifier = [0u8; 4];
+        reader.read_exact(&mut identifier)?;
+        self.identifier = String::from_utf8_lossy(&identifier)
+            .trim_end_matches('\0')
+            .to_string();
 
         let offset = reader.read_u32()?;
         let row_count = reader.read_u32()?;
         let col_count = reader.read_u32()?;
 
-        let _row_height = reader.read_u32()?;
+        self.row_height = reader.read_u32()?;
 
-        let _root_col_width = reader.read_u16()?;
-        for _ in 0..col_count {
-            let _col_width = reader.read_u16()?;
+        // Root column width followed by the column widths
+        for _ in 0..col_count + 1 {
+            self.column_widths.push(reader.read_u16()?);
         }
 
         let root_col_name = reader.read_string_u16()?;
@@ -80,8 +176,7 @@ impl RoseFile for DataTable {
             self.headers.push(reader.read_string_u16()?);
         }
 
-        // Unknown string
-        let _ = reader.read_string_u16()?;
+        self.root_row = reader.read_string_u16()?;
 
         for _ in 0..row_count - 1 {
             let mut row: Vec<String> = Vec::new();
@@ -101,47 +196,6 @@ impl RoseFile for DataTable {
     }
 
     fn write<W: WriteRoseExt>(&mut self, writer: &mut W) -> Result<(), Error> {
-        writer.write_string(&self.identifier, 4)?;
-
-        // Write temporary offset
-        writer.write_u32(0)?;
-
-        writer.write_u32((self.data.len() + 1) as u32)?;
-        writer.write_u32(self.headers.len() as u32)?;
-
-        // Row height
-        writer.write_u32(0)?;
-
-        // Root column width
-        writer.write_u16(0)?;
-        for _ in 0..self.headers.len() {
-            // Column width
-            writer.write_u16(0)?;
-        }
-
-        for header in &self.headers {
-            // Column names
-            writer.write_string_u16(&header)?;
-        }
-
-        // Unknown string
-        writer.write_string_u16("")?;
-
-        for row in &self.data {
-            writer.write_string_u16(&row[0])?;
-        }
-
-        let offset = writer.seek(SeekFrom::Current(0))?;
-
-        for row in &self.data {
-            for cell in row.iter().skip(1) {
-                writer.write_string_u16(cell)?;
-            }
-        }
-
-        writer.seek(SeekFrom::Start(4))?;
-        writer.write_u32(offset as u32)?;
-
-        Ok(())
+        self.write_with(writer, false)
     }
 }
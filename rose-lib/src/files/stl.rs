@@ -13,7 +13,8 @@ use crate::io::{ReadRoseExt, RoseFile, WriteRoseExt};
 pub type STL = StringTable;
 
 /// String Table Type
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum StringTableType {
     Normal,
     Item,
@@ -51,6 +52,7 @@ impl str::FromStr for StringTableType {
 
 /// String Table Key
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct StringTableKey {
     pub id: u32,
     pub name: String,
@@ -58,12 +60,14 @@ pub struct StringTableKey {
 
 // String Table Normal Row Data
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct NormalRowData {
     pub text: String,
 }
 
 /// String Table Item Row Data
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ItemRowData {
     pub text: String,
     pub description: String,
@@ -71,6 +75,7 @@ pub struct ItemRowData {
 
 /// String Table Quest Row Data
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct QuestRowData {
     pub text: String,
     pub description: String,
@@ -80,6 +85,7 @@ pub struct QuestRowData {
 
 /// String Table Row
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum StringTableRow {
     NormalRow(NormalRowData),
     ItemRow(ItemRowData),
@@ -101,7 +107,8 @@ impl fmt::Display for StringTableRow {
 }
 
 /// String Table Language
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum StringTableLanguage {
     Unknown = 999,
     Korean = 0,
@@ -130,6 +137,43 @@ impl fmt::Display for StringTableLanguage {
     }
 }
 
+impl StringTableLanguage {
+    /// Short code of the language, e.g. `en`
+    pub fn code(&self) -> &'static str {
+        match self {
+            StringTableLanguage::Unknown => "unknown",
+            StringTableLanguage::Korean => "ko",
+            StringTableLanguage::English => "en",
+            StringTableLanguage::Japanese => "jp",
+            StringTableLanguage::ChineseTraditional => "tw",
+            StringTableLanguage::ChineseSimplified => "cn",
+        }
+    }
+}
+
+impl str::FromStr for StringTableLanguage {
+    type Err = failure::Error;
+
+    /// Parse a language from its code, name or index, e.g. `en`, `English` or
+    /// `1`
+    fn from_str(s: &str) -> Result<StringTableLanguage, Self::Err> {
+        let s = s.trim();
+        if let Ok(idx) = s.parse::<u32>() {
+            return Ok(StringTableLanguage::from(idx));
+        }
+
+        for idx in 0..5 {
+            let language = StringTableLanguage::from(idx);
+            if s.eq_ignore_ascii_case(language.code())
+                || s.eq_ignore_ascii_case(&language.to_string())
+            {
+                return Ok(language);
+            }
+        }
+        bail!("Unknown STL language: {}", s)
+    }
+}
+
 impl From<u32> for StringTableLanguage {
     fn from(i: u32) -> StringTableLanguage {
         match i {
@@ -145,6 +189,7 @@ impl From<u32> for StringTableLanguage {
 
 /// String Table Language Table
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct StringTableLanguageTable {
     pub language: StringTableLanguage,
     pub rows: Vec<StringTableRow>,
@@ -152,6 +197,7 @@ pub struct StringTableLanguageTable {
 
 /// String Table
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct StringTable {
     pub format: StringTableType,
     pub keys: Vec<StringTableKey>,
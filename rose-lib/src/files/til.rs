@@ -10,6 +10,7 @@ use crate::io::{ReadRoseExt, RoseFile, WriteRoseExt};
 pub type TIL = Tilemap;
 
 #[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Tilemap {
     pub width: i32,
     pub height: i32,
@@ -55,6 +56,7 @@ impl RoseFile for Tilemap {
 
 /// Tile
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Tile {
     pub brush_id: u8,
     pub tile_idx: u8,
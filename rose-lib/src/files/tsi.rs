@@ -12,6 +12,7 @@ use crate::utils::Vector2;
 pub type TSI = SpriteInformation;
 
 #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SpriteInformation {
     pub sprite_sheets: Vec<SpriteSheet>,
 }
@@ -90,6 +91,7 @@ impl RoseFile for SpriteInformation {
 }
 
 #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SpriteSheet {
     pub path: PathBuf,
     pub color_key: u32,
@@ -103,6 +105,7 @@ impl SpriteSheet {
 }
 
 #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Sprite {
     pub name: String,
     pub start_point: Vector2<u32>,
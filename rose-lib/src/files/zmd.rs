@@ -13,6 +13,7 @@ const ZMD_IDENTIFIER_3: &str = "ZMD0003";
 
 /// Skeleton
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Skeleton {
     pub bones: Vec<Bone>,
     pub dummy_bones: Vec<Bone>,
@@ -82,6 +83,7 @@ impl RoseFile for Skeleton {
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Bone {
     pub parent: i32,
     pub name: String,
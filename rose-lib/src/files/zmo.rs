@@ -1,5 +1,6 @@
 //! ROSE Online Motion
 use std::convert::TryFrom;
+use std::io::SeekFrom;
 
 use failure::Error;
 use serde::{Deserialize, Serialize};
@@ -10,14 +11,46 @@ use crate::utils::{Quaternion, Vector2, Vector3};
 /// Motion File
 pub type ZMO = Motion;
 
+/// Tags of the extension block at the end of motions with frame events
+const EXTENSION_TAG: &[u8; 4] = b"EZMO";
+const EXTENSION_TAG_3: &[u8; 4] = b"3ZMO";
+
 /// Motion
-#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Motion {
     pub identifier: String,
     pub fps: u32,
     pub frames: u32,
 
     pub channels: Vec<Channel>,
+
+    /// Event triggered at every frame, such as a sound or the hit of an
+    /// attack, 0 for none. Empty if the motion has no events.
+    #[serde(default)]
+    pub frame_events: Vec<u16>,
+
+    /// Tag of the extension block with the frame events, `EZMO` or `3ZMO`,
+    /// written back as it was read
+    #[serde(default = "default_frame_events_tag")]
+    pub frame_events_tag: String,
+}
+
+impl Default for Motion {
+    fn default() -> Motion {
+        Motion {
+            identifier: String::new(),
+            fps: 0,
+            frames: 0,
+            channels: Vec::new(),
+            frame_events: Vec::new(),
+            frame_events_tag: default_frame_events_tag(),
+        }
+    }
+}
+
+fn default_frame_events_tag() -> String {
+    String::from_utf8_lossy(EXTENSION_TAG).into_owned()
 }
 
 impl RoseFile for Motion {
@@ -93,6 +126,24 @@ impl RoseFile for Motion {
             }
         }
 
+        // Frame events are in an extension block found from the end
+        let frames_end = reader.position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        if end >= frames_end + 8 {
+            reader.seek(SeekFrom::End(-4))?;
+            let tag = reader.read_u32()?.to_le_bytes();
+            if &tag == EXTENSION_TAG || &tag == EXTENSION_TAG_3 {
+                self.frame_events_tag = String::from_utf8_lossy(&tag).into_owned();
+                reader.seek(SeekFrom::End(-8))?;
+                let offset = reader.read_u32()?;
+                reader.seek(SeekFrom::Start(offset as u64))?;
+                let event_count = reader.read_u16()?;
+                for _ in 0..event_count {
+                    self.frame_events.push(reader.read_u16()?);
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -156,11 +207,26 @@ impl RoseFile for Motion {
             }
         }
 
+        if !self.frame_events.is_empty() {
+            let offset = writer.position()?;
+            writer.write_u16(self.frame_events.len() as u16)?;
+            for event in &self.frame_events {
+                writer.write_u16(*event)?;
+            }
+            writer.write_u32(offset as u32)?;
+            let tag = match self.frame_events_tag.as_bytes() {
+                tag if tag == EXTENSION_TAG_3 => EXTENSION_TAG_3,
+                _ => EXTENSION_TAG,
+            };
+            writer.write_u32(u32::from_le_bytes(*tag))?;
+        }
+
         Ok(())
     }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Channel {
     pub typ: ChannelType,
     pub index: u32,
@@ -273,6 +339,7 @@ impl From<ChannelType> for Channel {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum ChannelData {
     None,
     Position(Vec<Vector3<f32>>),
@@ -294,6 +361,7 @@ impl Default for ChannelData {
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum ChannelType {
     None = 1,
     Position = 1 << 1,
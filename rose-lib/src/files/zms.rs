@@ -10,6 +10,7 @@ pub type ZMS = Mesh;
 
 /// Mesh
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Mesh {
     pub identifier: String,
     pub format: i32,
@@ -390,7 +391,8 @@ impl Default for Mesh {
 }
 
 /// Mesh Vertex
-#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Vertex {
     pub position: Vector3<f32>,
     pub normal: Vector3<f32>,
@@ -412,6 +414,7 @@ impl Vertex {
 
 /// Mesh Vertex Flags
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum VertexFormat {
     Position = 1 << 1,
     Normal = 1 << 2,
@@ -14,6 +14,7 @@ pub type ZON = Zone;
 
 /// Zone
 #[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Zone {
     pub zone_type: ZoneType,
     pub width: i32,
@@ -150,6 +151,7 @@ impl RoseFile for Zone {
 
 /// Zone Type
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum ZoneType {
     Grass = 0,
     Mountain = 1,
@@ -203,6 +205,7 @@ impl TryFrom<i32> for ZoneType {
 
 /// Zone Block Type
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum ZoneBlockType {
     BasicInfo = 0,
     EventPoints = 1,
@@ -230,6 +233,7 @@ impl TryFrom<i32> for ZoneBlockType {
 
 /// Zone Position
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ZonePosition {
     pub position: Vector2<f32>,
     pub is_used: bool,
@@ -243,6 +247,7 @@ impl ZonePosition {
 
 /// Zone Event Position
 #[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ZoneEventPoint {
     pub position: Vector3<f32>,
     pub name: String,
@@ -256,6 +261,7 @@ impl ZoneEventPoint {
 
 /// ZoneTile
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct ZoneTile {
     pub layer1: i32,
     pub layer2: i32,
@@ -287,6 +293,7 @@ impl Default for ZoneTile {
 }
 /// Zone Tile Rotation
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum ZoneTileRotation {
     Unknown = 0,
     None = 1,
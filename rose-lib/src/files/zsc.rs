@@ -14,6 +14,7 @@ pub type ZSC = Scene;
 
 /// Scene
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Scene {
     pub meshes: Vec<PathBuf>,
     pub materials: Vec<SceneMaterial>,
@@ -283,6 +284,7 @@ impl RoseFile for Scene {
 
 /// Scene Material
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SceneMaterial {
     pub path: PathBuf,
     pub is_skin: bool,
@@ -301,6 +303,7 @@ pub struct SceneMaterial {
 
 /// Scene Object
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SceneObject {
     pub bounding_cylinder: BoundingCylinder,
     pub bounding_box: BoundingBox<f32>,
@@ -310,6 +313,7 @@ pub struct SceneObject {
 
 /// Scene Object Part
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SceneObjectPart {
     pub mesh_id: u16,
     pub material_id: u16,
@@ -372,6 +376,7 @@ impl Default for SceneObjectPart {
 
 /// Scene Object Effect
 #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SceneObjectEffect {
     pub effect_id: u16,
     pub effect_type: SceneEffectType,
@@ -382,6 +387,7 @@ pub struct SceneObjectEffect {
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum SceneBlendMode {
     None = 0,
     Custom = 1,
@@ -423,6 +429,7 @@ impl TryFrom<u16> for SceneBlendMode {
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum SceneGlowType {
     None = 0,
     NotSet = 1,
@@ -473,6 +480,7 @@ impl TryFrom<u16> for SceneGlowType {
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum SceneCollisionType {
     None = 0,
     BoundingBox = 3,
@@ -507,6 +515,7 @@ impl TryFrom<u16> for SceneCollisionType {
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum SceneObjectProperty {
     None = 0,
     Position = 1,
@@ -575,6 +584,7 @@ impl TryFrom<u8> for SceneObjectProperty {
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum SceneEffectType {
     Normal = 0,
     DayNight = 1,
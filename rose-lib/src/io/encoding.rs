@@ -0,0 +1,103 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fmt;
+use std::str;
+
+use encoding_rs::{Encoding, EUC_KR, UTF_16BE, UTF_16LE};
+use failure::Error;
+
+// Shared by the readers and writers of a thread, like the wide-strings option
+thread_local! {
+    static STRING_ENCODING: Cell<StringEncoding> = const { Cell::new(StringEncoding::Auto) };
+}
+
+/// Encoding of strings that aren't wide-strings
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StringEncoding {
+    /// Read strings as UTF-8, falling back to EUC-KR for strings that aren't
+    /// valid UTF-8, and write them as UTF-8
+    #[default]
+    Auto,
+    /// Read and write strings in a single encoding, e.g. EUC-KR for the
+    /// original Korean clients
+    Fixed(&'static Encoding),
+}
+
+impl StringEncoding {
+    /// Parse an encoding label, e.g. `euc-kr`, `cp949`, `shift_jis` or
+    /// `utf-8`, or `auto`
+    pub fn from_label(label: &str) -> Result<StringEncoding, Error> {
+        let label = label.trim();
+        if label.eq_ignore_ascii_case("auto") {
+            return Ok(StringEncoding::Auto);
+        }
+
+        // EUC-KR is decoded as its Windows superset CP949 (Unified Hangul Code)
+        if label.eq_ignore_ascii_case("cp949") || label.eq_ignore_ascii_case("uhc") {
+            return Ok(StringEncoding::Fixed(EUC_KR));
+        }
+
+        match Encoding::for_label(label.as_bytes()) {
+            Some(encoding) if encoding == UTF_16LE || encoding == UTF_16BE => {
+                bail!("UTF-16 strings are read and written as wide-strings")
+            }
+            Some(encoding) => Ok(StringEncoding::Fixed(encoding)),
+            None => bail!("Unknown encoding: {}", label),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StringEncoding::Auto => "auto",
+            StringEncoding::Fixed(encoding) => encoding.name(),
+        }
+    }
+
+    /// Decode a string, using replacement characters for invalid bytes
+    pub fn decode(&self, b: &[u8]) -> String {
+        match self {
+            StringEncoding::Auto => match str::from_utf8(b) {
+                Ok(s) => String::from(s),
+                Err(_) => {
+                    let (decoded, _encoding, _valid) = EUC_KR.decode(b);
+                    String::from(decoded)
+                }
+            },
+            StringEncoding::Fixed(encoding) => {
+                let (decoded, _valid) = encoding.decode_without_bom_handling(b);
+                String::from(decoded)
+            }
+        }
+    }
+
+    /// Encode a string, failing if it has characters the encoding can't
+    /// represent
+    pub fn encode<'a>(&self, s: &'a str) -> Result<Cow<'a, [u8]>, Error> {
+        match self {
+            StringEncoding::Auto => Ok(Cow::Borrowed(s.as_bytes())),
+            StringEncoding::Fixed(encoding) => {
+                let (encoded, _encoding, unmappable) = encoding.encode(s);
+                if unmappable {
+                    bail!("Can't encode {:?} as {}", s, encoding.name());
+                }
+                Ok(encoded)
+            }
+        }
+    }
+}
+
+impl fmt::Display for StringEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Set the string encoding of every reader and writer on this thread
+pub fn set_string_encoding(encoding: StringEncoding) {
+    STRING_ENCODING.with(|v| v.set(encoding));
+}
+
+/// String encoding of the readers and writers on this thread
+pub fn string_encoding() -> StringEncoding {
+    STRING_ENCODING.with(|v| v.get())
+}
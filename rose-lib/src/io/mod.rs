@@ -1,11 +1,15 @@
 //! A module for Reading/Writing ROSE data types to/from disk
 
+mod encoding;
 mod file;
+mod normalize;
 mod path;
 mod reader;
 mod writer;
 
+pub use self::encoding::{set_string_encoding, string_encoding, StringEncoding};
 pub use self::file::RoseFile;
+pub use self::normalize::{set_string_normalization, string_normalization, Normalization};
 pub use self::path::PathRoseExt;
 pub use self::reader::{ReadRoseExt, RoseReader};
 pub use self::writer::{RoseWriter, WriteRoseExt};
@@ -0,0 +1,224 @@
+//! Unicode normalization of strings read from ROSE files
+//!
+//! Implements the normalization forms of Unicode Standard Annex #15 with the
+//! tables of `normalize/tables.rs`. Hangul syllables are decomposed and
+//! composed algorithmically.
+use std::cell::Cell;
+use std::fmt;
+
+use failure::Error;
+
+mod tables;
+
+use self::tables::*;
+
+// Shared by the readers of a thread, like the string encoding
+thread_local! {
+    static STRING_NORMALIZATION: Cell<Normalization> = const { Cell::new(Normalization::None) };
+}
+
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// Unicode normalization form of strings
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Normalization {
+    /// Keep strings as they are
+    #[default]
+    None,
+    /// Canonical composition, e.g. `e` followed by a combining acute accent
+    /// becomes `é`
+    Nfc,
+    /// Compatibility composition, which also replaces compatibility
+    /// characters, e.g. full-width `Ａ` becomes `A`
+    Nfkc,
+}
+
+impl Normalization {
+    /// Parse a normalization form, `none`, `nfc` or `nfkc`
+    pub fn from_name(name: &str) -> Result<Normalization, Error> {
+        match name.trim().to_lowercase().as_str() {
+            "none" => Ok(Normalization::None),
+            "nfc" => Ok(Normalization::Nfc),
+            "nfkc" => Ok(Normalization::Nfkc),
+            _ => bail!("Unknown normalization form: {}", name),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Normalization::None => "none",
+            Normalization::Nfc => "nfc",
+            Normalization::Nfkc => "nfkc",
+        }
+    }
+
+    pub fn normalize(&self, s: String) -> String {
+        match self {
+            Normalization::None => s,
+            _ if s.is_ascii() => s,
+            Normalization::Nfc => compose(decompose(&s, false)),
+            Normalization::Nfkc => compose(decompose(&s, true)),
+        }
+    }
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Set the normalization of strings read by every reader on this thread
+pub fn set_string_normalization(normalization: Normalization) {
+    STRING_NORMALIZATION.with(|v| v.set(normalization));
+}
+
+/// Normalization of strings read by the readers on this thread
+pub fn string_normalization() -> Normalization {
+    STRING_NORMALIZATION.with(|v| v.get())
+}
+
+fn combining_class(c: char) -> u8 {
+    COMBINING_CLASS
+        .binary_search_by_key(&c, |&(k, _)| k)
+        .map(|idx| COMBINING_CLASS[idx].1)
+        .unwrap_or(0)
+}
+
+fn lookup(table: &'static [(char, &'static [char])], c: char) -> Option<&'static [char]> {
+    table
+        .binary_search_by_key(&c, |&(k, _)| k)
+        .ok()
+        .map(|idx| table[idx].1)
+}
+
+/// Full decomposition of a string in canonical order
+fn decompose(s: &str, compatibility: bool) -> Vec<char> {
+    let mut chars = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        let s_index = (c as u32).wrapping_sub(S_BASE);
+        if s_index < S_COUNT {
+            let l = L_BASE + s_index / N_COUNT;
+            let v = V_BASE + (s_index % N_COUNT) / T_COUNT;
+            let t = T_BASE + s_index % T_COUNT;
+            chars.extend(
+                [l, v, t]
+                    .iter()
+                    .filter(|&&c| c != T_BASE)
+                    .filter_map(|&c| std::char::from_u32(c)),
+            );
+            continue;
+        }
+
+        let decomposition = if compatibility {
+            lookup(COMPATIBILITY_DECOMPOSITION, c).or_else(|| lookup(CANONICAL_DECOMPOSITION, c))
+        } else {
+            lookup(CANONICAL_DECOMPOSITION, c)
+        };
+        match decomposition {
+            Some(decomposition) => chars.extend_from_slice(decomposition),
+            None => chars.push(c),
+        }
+    }
+
+    // Sort runs of combining marks by their combining class
+    let mut start = 0;
+    while start < chars.len() {
+        if combining_class(chars[start]) == 0 {
+            start += 1;
+            continue;
+        }
+        let mut end = start;
+        while end < chars.len() && combining_class(chars[end]) != 0 {
+            end += 1;
+        }
+        chars[start..end].sort_by_key(|&c| combining_class(c));
+        start = end;
+    }
+    chars
+}
+
+fn compose_pair(a: char, b: char) -> Option<char> {
+    let l_index = (a as u32).wrapping_sub(L_BASE);
+    let v_index = (b as u32).wrapping_sub(V_BASE);
+    if l_index < L_COUNT && v_index < V_COUNT {
+        return std::char::from_u32(S_BASE + (l_index * V_COUNT + v_index) * T_COUNT);
+    }
+
+    let s_index = (a as u32).wrapping_sub(S_BASE);
+    let t_index = (b as u32).wrapping_sub(T_BASE);
+    // Only LV syllables, without a trailing consonant, take one
+    let lv = s_index / T_COUNT * T_COUNT == s_index;
+    if s_index < S_COUNT && lv && t_index > 0 && t_index < T_COUNT {
+        return std::char::from_u32(a as u32 + t_index);
+    }
+
+    COMPOSITION
+        .binary_search_by_key(&(a, b), |&(k, _)| k)
+        .ok()
+        .map(|idx| COMPOSITION[idx].1)
+}
+
+/// Canonical composition of a decomposed string
+fn compose(chars: Vec<char>) -> String {
+    let mut composed: Vec<char> = Vec::with_capacity(chars.len());
+    let mut starter: Option<usize> = None;
+    let mut last_class = 0;
+
+    for c in chars {
+        let class = combining_class(c);
+        if let Some(starter) = starter {
+            // A character is blocked from the starter by a character of the
+            // same or higher combining class, or by another starter
+            let adjacent = starter == composed.len() - 1;
+            if adjacent || (last_class != 0 && last_class < class) {
+                if let Some(pair) = compose_pair(composed[starter], c) {
+                    composed[starter] = pair;
+                    continue;
+                }
+            }
+        }
+
+        if class == 0 {
+            starter = Some(composed.len());
+        }
+        last_class = class;
+        composed.push(c);
+    }
+    composed.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let nfc = |s: &str| Normalization::Nfc.normalize(s.to_string());
+        let nfkc = |s: &str| Normalization::Nfkc.normalize(s.to_string());
+
+        assert_eq!(nfc("e\u{301}"), "\u{e9}");
+        assert_eq!(nfc("\u{1100}\u{1161}\u{11a8}"), "각");
+        assert_eq!(nfc("\u{1e0b}\u{323}"), "\u{1e0d}\u{307}");
+        assert_eq!(nfc("a\u{323}\u{302}"), "\u{1ead}");
+        assert_eq!(nfc("\u{212b}"), "\u{c5}");
+        assert_eq!(nfc("\u{2126}"), "\u{3a9}");
+        assert_eq!(nfc("ｶﾞ"), "ｶﾞ");
+        assert_eq!(nfkc("ｶﾞ"), "ガ");
+        assert_eq!(nfkc("Ａ①ﬁ"), "A1fi");
+        assert_eq!(nfc("몬스터"), "몬스터");
+        assert_eq!(
+            Normalization::None.normalize("e\u{301}".to_string()),
+            "e\u{301}"
+        );
+        assert!(Normalization::from_name("nfd").is_err());
+    }
+}
@@ -1,12 +1,13 @@
 use std::cell::Cell;
 use std::io;
 use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
-use std::str;
 
 use byteorder::{LittleEndian, ReadBytesExt};
-use encoding_rs::{EUC_KR, UTF_16LE};
+use encoding_rs::UTF_16LE;
 use failure::Error;
 
+use crate::io::encoding::{set_string_encoding, string_encoding, StringEncoding};
+use crate::io::normalize::string_normalization;
 use crate::utils::{Color3, Color4, Quaternion, Vector2, Vector3, Vector4};
 
 // Temporary work-around until specialization is supported in Rust
@@ -32,6 +33,12 @@ impl<R: Read> RoseReader<R> {
             v.set(b);
         });
     }
+
+    /// Set the encoding of strings that aren't wide-strings, shared with the
+    /// writers on this thread
+    pub fn set_encoding(&self, encoding: StringEncoding) {
+        set_string_encoding(encoding);
+    }
 }
 
 impl<R: Read> Read for RoseReader<R> {
@@ -136,6 +143,9 @@ pub trait ReadRoseExt: Read + Seek + BufRead {
 
     // Read strings as wide strings (2-bytes)
     fn wide_strings(&self) -> bool;
+
+    // Encoding of strings that aren't wide strings
+    fn encoding(&self) -> StringEncoding;
 }
 
 impl<R> ReadRoseExt for R
@@ -186,7 +196,7 @@ where
         let mut buffer: Vec<u8> = Vec::new();
         self.read_until(0x00, &mut buffer)?;
         let _ = buffer.pop();
-        Ok(decode_string(buffer, self.wide_strings()))
+        Ok(decode_string(buffer, self.wide_strings(), self.encoding()))
     }
 
     fn read_string(&mut self, n: u64) -> Result<String, Error> {
@@ -202,7 +212,7 @@ where
             let _ = buffer.pop();
         }
 
-        Ok(decode_string(buffer, self.wide_strings()))
+        Ok(decode_string(buffer, self.wide_strings(), self.encoding()))
     }
 
     fn read_string_u8(&mut self) -> Result<String, Error> {
@@ -345,22 +355,24 @@ where
     fn wide_strings(&self) -> bool {
         WIDE_STRINGS.with(|b| b.get())
     }
+
+    fn encoding(&self) -> StringEncoding {
+        string_encoding()
+    }
 }
 
-/// Decodes a string by first trying to read as UTF-8, otherwise falls back
-/// to EUC-KR encoding using replacement characters where necessary. If the
-/// wide argument is set then it will only try to decode the string as UTF-16LE
-fn decode_string(b: Vec<u8>, wide: bool) -> String {
-    if wide {
+/// Decodes a string with the given encoding, by default trying to read it as
+/// UTF-8 and falling back to EUC-KR using replacement characters where
+/// necessary. If the wide argument is set then it will only try to decode the
+/// string as UTF-16LE. The string is then normalized if a normalization form is
+/// set.
+fn decode_string(b: Vec<u8>, wide: bool, encoding: StringEncoding) -> String {
+    let decoded = if wide {
         let (decoded, _encoding, _valid) = UTF_16LE.decode(&b);
-        return String::from(decoded.trim_end_matches('\u{fffd}'));
-    }
+        String::from(decoded.trim_end_matches('\u{fffd}'))
+    } else {
+        encoding.decode(&b)
+    };
 
-    match str::from_utf8(&b) {
-        Ok(s) => String::from(s),
-        Err(_) => {
-            let (decoded, _encoding, _valid) = EUC_KR.decode(&b);
-            String::from(decoded)
-        }
-    }
+    string_normalization().normalize(decoded)
 }
@@ -5,12 +5,12 @@ use std::io::{BufWriter, Seek, SeekFrom, Write};
 use byteorder::{LittleEndian, WriteBytesExt};
 use failure::Error;
 
+use crate::io::encoding::{set_string_encoding, string_encoding, StringEncoding};
 use crate::utils::{Color3, Color4, Quaternion, Vector2, Vector3, Vector4};
 
-/// Custom writers that supports some additional configurable options such
-/// as writing strings as wide-strings.
+/// Custom writer for ROSE files. Wide-strings are written with the
+/// `write_wstring_*` methods, other strings with the configured encoding.
 //
-// TODO: Add wide-string writing functionality
 // TODO: Add tests (sample file: ai_s.stb)
 pub struct RoseWriter<W: Write> {
     pub writer: BufWriter<W>,
@@ -22,6 +22,12 @@ impl<W: Write> RoseWriter<W> {
             writer: BufWriter::new(inner),
         }
     }
+
+    /// Set the encoding of strings that aren't wide-strings, shared with the
+    /// readers on this thread
+    pub fn set_encoding(&self, encoding: StringEncoding) {
+        set_string_encoding(encoding);
+    }
 }
 
 impl<W: Write> Write for RoseWriter<W> {
@@ -56,9 +62,9 @@ impl<W: Write + Seek> Seek for RoseWriter<W> {
 /// writer.write_cstring("null terminate me").unwrap();
 /// ```
 ///
-/// NOTE: Strings are encoded as UTF-8. The original ROSE files were encoded as EUC-KR,
-/// as such if reading from an original file the data written back will be written using
-/// a different encoding.
+/// NOTE: Strings are encoded as UTF-8 by default. The original ROSE files were encoded as
+/// EUC-KR, as such if reading from an original file the data written back will be written
+/// using a different encoding unless the encoding is set with `set_string_encoding`.
 ///
 // Note: Clippy recommends passing by value for copy-able small args but
 // we ignore that optimization in favor of API consistency
@@ -93,6 +99,10 @@ pub trait WriteRoseExt: Write + Seek {
     // Write a string with length prefix as u32
     fn write_string_u32(&mut self, string: &str) -> Result<(), Error>;
 
+    // Write a null terminated wide string (UTF-16LE) with its length in bytes,
+    // including the terminator, prefixed as u16
+    fn write_wstring_u16(&mut self, string: &str) -> Result<(), Error>;
+
     /// Write a string with a variable-byte prefixed length to the reader
     ///
     /// If the string is less than 128 characters the the first byte holds the
@@ -116,6 +126,9 @@ pub trait WriteRoseExt: Write + Seek {
     fn write_quaternion_wxyz(&mut self, q: &Quaternion) -> Result<(), Error>;
 
     fn position(&mut self) -> Result<u64, Error>;
+
+    // Encoding of strings that aren't wide strings
+    fn encoding(&self) -> StringEncoding;
 }
 
 impl<W> WriteRoseExt for W
@@ -174,11 +187,12 @@ where
     }
 
     fn write_string(&mut self, string: &str, len: i32) -> Result<(), Error> {
-        let string_len = string.len() as i32;
+        let bytes = self.encoding().encode(string)?;
+        let string_len = bytes.len() as i32;
 
         let n_chars = cmp::min(string_len, len);
         for i in 0..n_chars {
-            WriteRoseExt::write_u8(self, string.as_bytes()[i as usize])?;
+            WriteRoseExt::write_u8(self, bytes[i as usize])?;
         }
 
         if len > string_len {
@@ -191,38 +205,52 @@ where
     }
 
     fn write_cstring(&mut self, string: &str) -> Result<(), Error> {
-        self.write_all(string.as_bytes())?;
+        self.write_all(&self.encoding().encode(string)?)?;
         WriteRoseExt::write_u8(self, 0x00)?;
         Ok(())
     }
 
     fn write_string_u8(&mut self, string: &str) -> Result<(), Error> {
-        WriteRoseExt::write_u8(self, string.len() as u8)?;
-        self.write_all(string.as_bytes())?;
+        let bytes = self.encoding().encode(string)?;
+        WriteRoseExt::write_u8(self, bytes.len() as u8)?;
+        self.write_all(&bytes)?;
         Ok(())
     }
 
     fn write_string_u16(&mut self, string: &str) -> Result<(), Error> {
-        WriteRoseExt::write_u16(self, string.len() as u16)?;
-        self.write_all(string.as_bytes())?;
+        let bytes = self.encoding().encode(string)?;
+        WriteRoseExt::write_u16(self, bytes.len() as u16)?;
+        self.write_all(&bytes)?;
         Ok(())
     }
 
     fn write_string_u32(&mut self, string: &str) -> Result<(), Error> {
-        WriteRoseExt::write_u32(self, string.len() as u32)?;
-        self.write_all(&string.as_bytes())?;
+        let bytes = self.encoding().encode(string)?;
+        WriteRoseExt::write_u32(self, bytes.len() as u32)?;
+        self.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn write_wstring_u16(&mut self, string: &str) -> Result<(), Error> {
+        let mut encoded: Vec<u16> = string.encode_utf16().collect();
+        encoded.push(0);
+        WriteRoseExt::write_u16(self, (encoded.len() * 2) as u16)?;
+        for c in encoded {
+            WriteRoseExt::write_u16(self, c)?;
+        }
         Ok(())
     }
 
     fn write_string_varbyte(&mut self, string: &str) -> Result<(), Error> {
-        let len = string.len();
+        let bytes = self.encoding().encode(string)?;
+        let len = bytes.len();
         if len < 128 {
             WriteRoseExt::write_u8(self, len as u8)?;
         } else {
             WriteRoseExt::write_u8(self, (len as u8) | 0b1000_0000)?;
             WriteRoseExt::write_u8(self, (len >> 7) as u8)?;
         }
-        self.write_all(string.as_bytes())?;
+        self.write_all(&bytes)?;
 
         Ok(())
     }
@@ -309,4 +337,8 @@ where
     fn position(&mut self) -> Result<u64, Error> {
         Ok(self.seek(SeekFrom::Current(0))?)
     }
+
+    fn encoding(&self) -> StringEncoding {
+        string_encoding()
+    }
 }
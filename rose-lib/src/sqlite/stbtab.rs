@@ -366,7 +366,7 @@ mod test {
                     "SELECT row_idx, * FROM list_zone WHERE rowid = ?",
                     params![row_idx as u32],
                     |row| {
-                        assert_eq!(row_idx as u32, row.get_unwrap(0));
+                        assert_eq!(row_idx as u32, row.get_unwrap::<_, u32>(0));
                         for col_idx in 1..row.column_count() {
                             let val = row.get_unwrap::<_, String>(col_idx);
                             assert_eq!(stb.value(row_idx, col_idx - 1).unwrap(), val);
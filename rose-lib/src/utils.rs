@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 pub type Quaternion = Vector4<f32>;
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Color3 {
     pub r: f32,
     pub g: f32,
@@ -24,6 +25,7 @@ impl Color3 {
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Color4 {
     pub r: f32,
     pub g: f32,
@@ -38,6 +40,7 @@ impl Color4 {
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Vector2<T> {
     pub x: T,
     pub y: T,
@@ -62,6 +65,7 @@ impl Vector2<i32> {
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Vector3<T> {
     pub x: T,
     pub y: T,
@@ -85,6 +89,7 @@ impl Vector3<i16> {
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Vector4<T> {
     pub w: T,
     pub x: T,
@@ -115,12 +120,14 @@ impl Vector4<i16> {
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct BoundingBox<T> {
     pub min: Vector3<T>,
     pub max: Vector3<T>,
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct BoundingCylinder {
     pub center: Vector2<i32>,
     pub radius: f32,
@@ -0,0 +1,173 @@
+use std::io::Cursor;
+
+use roselib::files::aip::*;
+use roselib::files::AIP;
+use roselib::io::RoseFile;
+
+fn aip() -> AIP {
+    let mut aip = AIP::new();
+    aip.title = String::from("Wolf");
+    aip.idle_interval = 4;
+    aip.damage_chance = 30;
+    aip.triggers.push(AiTrigger {
+        name: String::from("On Idle"),
+        events: vec![AiEvent {
+            name: String::from("Wander"),
+            conditions: vec![AiCondition::Random { percent: 50 }],
+            actions: vec![
+                AiAction::MoveRandom {
+                    distance: 512,
+                    speed: 100,
+                },
+                AiAction::Stop,
+            ],
+        }],
+    });
+    aip.triggers.push(AiTrigger {
+        name: String::from("On Dead"),
+        events: Vec::new(),
+    });
+    aip
+}
+
+#[test]
+fn write_aip() {
+    let mut orig_aip = aip();
+
+    let mut cursor = Cursor::new(Vec::new());
+    orig_aip.write(&mut cursor).unwrap();
+
+    let data = cursor.get_ref();
+    // Header, title, trigger name and event count, event name and counts,
+    // 12 byte condition and 16 and 8 byte actions, trigger name and count
+    assert_eq!(data.len(), 16 + 4 + 36 + 32 + 8 + 12 + 16 + 8 + 36);
+    // Size of the condition, counting its size and type
+    assert_eq!(&data[92..96], &[12, 0, 0, 0]);
+    assert_eq!(&data[96..104], &[8, 0, 0, 4, 50, 0, 0, 0]);
+    assert_eq!(&data[112..124], &[4, 0, 0, 11, 0, 2, 0, 0, 100, 0, 0, 0]);
+
+    cursor.set_position(0);
+    let mut new_aip = AIP::new();
+    new_aip.read(&mut cursor).unwrap();
+
+    assert_eq!(orig_aip, new_aip);
+    assert_eq!(new_aip.conditions().count(), 1);
+    assert_eq!(new_aip.actions().count(), 2);
+}
+
+#[test]
+fn read_aip_invalid_size() {
+    let mut data = Vec::new();
+    let mut write_u32 = |values: &[u32], padding: usize| {
+        for value in values {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.resize(data.len() + padding, 0);
+    };
+    // One trigger with one event, named with null bytes
+    write_u32(&[1, 0, 0, 0], 32);
+    write_u32(&[1], 32);
+    // A condition smaller than its size and type
+    write_u32(&[1, 4, 0x0400_0001], 0);
+
+    let mut aip = AIP::new();
+    assert!(aip.read(&mut Cursor::new(data)).is_err());
+}
+
+#[test]
+fn read_aip_truncated() {
+    let mut data = Vec::new();
+    let mut write_u32 = |values: &[u32], padding: usize| {
+        for value in values {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.resize(data.len() + padding, 0);
+    };
+    write_u32(&[1, 0, 0, 0], 32);
+    write_u32(&[1], 32);
+    // A condition claiming about 4 GiB of operands in a file that ends
+    write_u32(&[1, u32::MAX, 0x0400_0008, 50], 0);
+
+    let mut aip = AIP::new();
+    assert_eq!(
+        aip.read(&mut Cursor::new(data)).unwrap_err().to_string(),
+        "Truncated AI command 0x4000008: 4 of 4294967287 bytes of operands"
+    );
+}
+
+#[test]
+fn aip_raw_commands() {
+    let command = |opcode: u32, data: Vec<u8>| AiCommand { opcode, data };
+
+    // Unknown type, operands of another size and padding that isn't zero
+    for raw in [
+        command(0x0400_0100, vec![1, 0, 0, 0]),
+        command(0x0400_0008, vec![50, 0, 0, 0, 0, 0, 0, 0]),
+        command(0x0400_0008, vec![50, 0xCC, 0xCC, 0xCC]),
+    ] {
+        assert_eq!(
+            AiCondition::from_command(&raw),
+            AiCondition::Raw(raw.clone())
+        );
+        assert_eq!(AiCondition::Raw(raw.clone()).to_command(), raw);
+    }
+
+    assert_eq!(
+        AiCondition::decode(&command(0x0400_0100, Vec::new()))
+            .unwrap_err()
+            .to_string(),
+        "unknown condition opcode 0x04000100"
+    );
+    assert_eq!(
+        AiCondition::decode(&command(0x0400_0008, vec![0; 8]))
+            .unwrap_err()
+            .to_string(),
+        "condition 0x04000008 has 8 bytes of operands instead of 4"
+    );
+    // Padding is ignored when decoding
+    assert_eq!(
+        AiCondition::decode(&command(0x0400_0008, vec![50, 0xCC, 0xCC, 0xCC])).unwrap(),
+        AiCondition::Random { percent: 50 }
+    );
+    assert_eq!(
+        AiAction::decode(&command(0x0B00_0016, Vec::new()))
+            .unwrap_err()
+            .to_string(),
+        "unknown action opcode 0x0b000016"
+    );
+}
+
+#[test]
+fn aip_command_layouts() {
+    // Every type with a fixed layout reads zero operands of its size
+    for opcode in 0x0400_0000..0x0400_0040 {
+        if let Some(size) = AiCondition::operand_size(opcode) {
+            let command = AiCommand {
+                opcode,
+                data: vec![0; size],
+            };
+            let condition = AiCondition::from_command(&command);
+            assert!(!matches!(condition, AiCondition::Raw(_)), "{:#x}", opcode);
+            assert_eq!(condition.to_command(), command);
+        }
+    }
+    for opcode in 0x0B00_0000..0x0B00_0040 {
+        if let Some(size) = AiAction::operand_size(opcode) {
+            let command = AiCommand {
+                opcode,
+                data: vec![0; size],
+            };
+            let action = AiAction::from_command(&command);
+            assert!(!matches!(action, AiAction::Raw(_)), "{:#x}", opcode);
+            assert_eq!(action.to_command(), command);
+        }
+    }
+
+    let action = AiAction::QuestTrigger {
+        trigger: String::from("wolf-01"),
+    };
+    let command = action.to_command();
+    assert_eq!(command.opcode, 0x0B00_001F);
+    assert_eq!(command.data, b"\x07\x00wolf-01\x00\x00\x00");
+    assert_eq!(AiAction::from_command(&command), action);
+}
@@ -0,0 +1,192 @@
+use std::io::Cursor;
+
+use roselib::files::qsd::*;
+use roselib::files::QSD;
+use roselib::io::RoseFile;
+
+#[test]
+fn write_qsd() {
+    let mut orig_qsd = QSD::new();
+    orig_qsd.version = 1;
+    orig_qsd.name = String::from("QUEST_JUNON");
+    orig_qsd.groups.push(QuestGroup {
+        name: String::from("Helping Judy"),
+        triggers: vec![
+            QuestTrigger {
+                name: String::from("judy-01"),
+                check_next: true,
+                conditions: vec![QuestCondition::SelectQuest { quest: 10 }],
+                rewards: vec![QuestReward::Item {
+                    item: 8001,
+                    op: 1,
+                    count: 1,
+                    party: 0,
+                }],
+            },
+            QuestTrigger {
+                name: String::from("judy-02"),
+                ..Default::default()
+            },
+        ],
+    });
+
+    let mut cursor = Cursor::new(Vec::new());
+    orig_qsd.write(&mut cursor).unwrap();
+
+    let data = cursor.get_ref();
+    // Header and name, group, trigger and its 12 byte condition and 20 byte
+    // reward, second trigger
+    assert_eq!(data.len(), 10 + 11 + 18 + 18 + 12 + 20 + 18);
+    // Size of the condition, counting its size and type
+    assert_eq!(&data[57..61], &[12, 0, 0, 0]);
+    assert_eq!(&data[65..69], &[10, 0, 0, 0]);
+    assert_eq!(&data[73..77], &[1, 0, 0, 1]);
+    assert_eq!(&data[77..89], &[0x41, 0x1F, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0]);
+
+    cursor.set_position(0);
+    let mut new_qsd = QSD::new();
+    new_qsd.read(&mut cursor).unwrap();
+
+    assert_eq!(orig_qsd, new_qsd);
+    assert_eq!(new_qsd.triggers().count(), 2);
+}
+
+#[test]
+fn read_qsd_truncated() {
+    let mut data = Vec::new();
+    // Header, one group with one trigger with one condition, all unnamed
+    data.extend_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0, 0, 0]);
+    data.extend_from_slice(&[1, 0, 0, 0, 0, 0]);
+    data.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    // A condition claiming about 4 GiB of operands in a file that ends
+    for value in &[u32::MAX, 13, 1001] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut qsd = QSD::new();
+    assert_eq!(
+        qsd.read(&mut Cursor::new(data)).unwrap_err().to_string(),
+        "Truncated quest command 0xd: 4 of 4294967287 bytes of operands"
+    );
+}
+
+#[test]
+fn qsd_raw_commands() {
+    let command = |opcode: u32, data: Vec<u8>| QuestCommand { opcode, data };
+
+    // Unknown type, operands of another size, padding that isn't zero and a
+    // count of records that doesn't match the operands
+    for raw in [
+        command(0x64, vec![1, 0, 0, 0]),
+        command(13, vec![1, 0, 0, 0, 0, 0, 0, 0]),
+        command(10, vec![10, 20, 0xCC, 0xCC]),
+        command(1, vec![2, 0, 0, 0, 1, 0, 0, 0, 5, 0, 0, 0]),
+    ] {
+        assert_eq!(
+            QuestCondition::from_command(&raw),
+            QuestCondition::Raw(raw.clone())
+        );
+        assert_eq!(QuestCondition::Raw(raw.clone()).to_command(), raw);
+    }
+
+    assert_eq!(
+        QuestCondition::decode(&command(0x64, Vec::new()))
+            .unwrap_err()
+            .to_string(),
+        "unknown condition opcode 0x00000064"
+    );
+    assert_eq!(
+        QuestCondition::decode(&command(13, vec![0; 8]))
+            .unwrap_err()
+            .to_string(),
+        "condition 0x0000000d has 8 bytes of operands instead of 4"
+    );
+    assert_eq!(
+        QuestCondition::decode(&command(1, vec![0; 12]))
+            .unwrap_err()
+            .to_string(),
+        "condition 0x00000001 has 12 bytes of operands instead of 4"
+    );
+    // Padding is ignored when decoding
+    assert_eq!(
+        QuestCondition::decode(&command(10, vec![10, 20, 0xCC, 0xCC])).unwrap(),
+        QuestCondition::Random { min: 10, max: 20 }
+    );
+    assert_eq!(
+        QuestReward::decode(&command(0x0100_0012, Vec::new()))
+            .unwrap_err()
+            .to_string(),
+        "unknown reward opcode 0x01000012"
+    );
+}
+
+#[test]
+fn qsd_command_layouts() {
+    // Every type with a fixed layout reads zero operands of its size
+    for opcode in 0..0x40 {
+        if let Some(size) = QuestCondition::operand_size(opcode) {
+            let command = QuestCommand {
+                opcode,
+                data: vec![0; size],
+            };
+            let condition = QuestCondition::from_command(&command);
+            assert!(
+                !matches!(condition, QuestCondition::Raw(_)),
+                "{:#x}",
+                opcode
+            );
+            assert_eq!(condition.to_command(), command);
+        }
+    }
+    for opcode in 0x0100_0000..0x0100_0040 {
+        if let Some(size) = QuestReward::operand_size(opcode) {
+            let command = QuestCommand {
+                opcode,
+                data: vec![0; size],
+            };
+            let reward = QuestReward::from_command(&command);
+            assert!(!matches!(reward, QuestReward::Raw(_)), "{:#x}", opcode);
+            assert_eq!(reward.to_command(), command);
+        }
+    }
+
+    let condition = QuestCondition::Items {
+        items: vec![QuestItem {
+            item: 8001,
+            slot: 0,
+            count: 1,
+            op: 1,
+        }],
+    };
+    let command = condition.to_command();
+    assert_eq!(command.opcode, 4);
+    assert_eq!(
+        command.data,
+        [1, 0, 0, 0, 0x41, 0x1F, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]
+    );
+    assert_eq!(QuestCondition::from_command(&command), condition);
+
+    let reward = QuestReward::Abilities {
+        abilities: vec![QuestAbility {
+            ability: 16,
+            value: 500,
+            op: 6,
+        }],
+        party: 1,
+    };
+    let command = reward.to_command();
+    assert_eq!(command.opcode, 0x0100_0003);
+    assert_eq!(
+        command.data,
+        [1, 0, 0, 0, 1, 0, 0, 0, 16, 0, 0, 0, 244, 1, 0, 0, 6, 0, 0, 0]
+    );
+    assert_eq!(QuestReward::from_command(&command), reward);
+
+    let reward = QuestReward::Trigger {
+        trigger: String::from("judy-02"),
+    };
+    let command = reward.to_command();
+    assert_eq!(command.opcode, 0x0100_0009);
+    assert_eq!(command.data, b"\x07\x00judy-02\x00\x00\x00");
+    assert_eq!(QuestReward::from_command(&command), reward);
+}
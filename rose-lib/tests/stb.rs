@@ -3,7 +3,7 @@ use std::io::Cursor;
 use std::path::PathBuf;
 
 use roselib::files::STB;
-use roselib::io::RoseFile;
+use roselib::io::{RoseFile, RoseReader};
 
 #[test]
 fn read_stb() {
@@ -54,3 +54,45 @@ fn write_stb() {
     assert_eq!(orig_stb.data.len(), new_stb.data.len());
     assert_eq!(orig_stb, new_stb);
 }
+
+#[test]
+fn write_wide_stb() {
+    let mut orig_stb = STB::new();
+    orig_stb.identifier = String::from("STB1");
+    orig_stb.headers = vec![String::from("이름"), String::from("AI")];
+    orig_stb.root_row = String::from("root");
+    orig_stb.column_widths = vec![50, 100, 25];
+    orig_stb.data = vec![vec![String::from("몬스터"), String::from("1")]];
+
+    let mut cursor = Cursor::new(Vec::new());
+    orig_stb.write_with(&mut cursor, true).unwrap();
+    cursor.set_position(0);
+
+    let mut reader = RoseReader::new(cursor);
+    reader.set_wide_strings(true);
+    let mut new_stb = STB::new();
+    let res = new_stb.read(&mut reader);
+    reader.set_wide_strings(false);
+
+    res.unwrap();
+    assert_eq!(orig_stb, new_stb);
+}
+
+#[test]
+fn stb_missing_column_widths() {
+    let mut stb = STB::new();
+    stb.headers = vec![String::from("NAME"), String::from("AI")];
+    stb.data = vec![vec![String::from("a"), String::from("1")]];
+
+    // Missing widths are written as 0
+    let mut cursor = Cursor::new(Vec::new());
+    stb.write(&mut cursor).unwrap();
+    cursor.set_position(0);
+    let mut new_stb = STB::new();
+    new_stb.read(&mut cursor).unwrap();
+    assert_eq!(new_stb.column_widths, [0, 0, 0]);
+    assert_eq!(stb, new_stb);
+
+    new_stb.column_widths[1] = 50;
+    assert_ne!(stb, new_stb);
+}
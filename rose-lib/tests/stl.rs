@@ -2,9 +2,12 @@ use std::fs::File;
 use std::io::Cursor;
 use std::path::PathBuf;
 
-use roselib::files::stl::{StringTableLanguage, StringTableLanguageTable, StringTableType};
+use roselib::files::stl::{
+    NormalRowData, StringTableKey, StringTableLanguage, StringTableLanguageTable, StringTableRow,
+    StringTableType,
+};
 use roselib::files::STL;
-use roselib::io::RoseFile;
+use roselib::io::{set_string_encoding, RoseFile, StringEncoding};
 
 #[test]
 fn read_stl() {
@@ -69,3 +72,53 @@ fn write_stl() {
         assert_eq!(orig_stl, new_stl);
     }
 }
+
+#[test]
+fn parse_stl_language() {
+    let languages = ["ko", "EN", "Japanese", "Chinese (Traditional)", "4"];
+    for (idx, s) in languages.iter().enumerate() {
+        let language: StringTableLanguage = s.parse().unwrap();
+        assert_eq!(language, StringTableLanguage::from(idx as u32));
+        assert_eq!(
+            language.code().parse::<StringTableLanguage>().unwrap(),
+            language
+        );
+    }
+    assert!("klingon".parse::<StringTableLanguage>().is_err());
+}
+
+#[test]
+fn write_stl_euc_kr() {
+    let mut orig_stl = STL::new();
+    orig_stl.keys.push(StringTableKey {
+        id: 1,
+        name: String::from("ITEM_1"),
+    });
+    orig_stl.language_tables.push(StringTableLanguageTable {
+        language: StringTableLanguage::Korean,
+        rows: vec![StringTableRow::NormalRow(NormalRowData {
+            text: String::from("몬스터"),
+        })],
+    });
+
+    let euc_kr = StringEncoding::from_label("cp949").unwrap();
+    set_string_encoding(euc_kr);
+
+    let mut cursor = Cursor::new(Vec::new());
+    let mut new_stl = STL::new();
+    let res = orig_stl.write(&mut cursor).and_then(|_| {
+        cursor.set_position(0);
+        new_stl.read(&mut cursor)
+    });
+    set_string_encoding(StringEncoding::Auto);
+
+    res.unwrap();
+    assert_eq!(orig_stl, new_stl);
+
+    let text = cursor.into_inner();
+    assert!(text
+        .windows(6)
+        .any(|b| b == euc_kr.encode("몬스터").unwrap().as_ref()));
+    assert!(euc_kr.encode("\u{1F600}").is_err());
+    assert!(StringEncoding::from_label("utf-16le").is_err());
+}
@@ -60,3 +60,44 @@ fn write_zmo() {
         assert_eq!(orig_zmo, new_zmo);
     }
 }
+
+#[test]
+fn write_zmo_frame_events() {
+    let mut orig_zmo = ZMO::new();
+    orig_zmo.identifier = String::from("ZMO0002");
+    orig_zmo.fps = 30;
+    orig_zmo.frames = 4;
+    orig_zmo.frame_events = vec![0, 0, 21, 0];
+
+    let mut cursor = Cursor::new(Vec::new());
+    orig_zmo.write(&mut cursor).unwrap();
+    let data = cursor.into_inner();
+    // Frame data, then the events, their offset and the tag
+    assert_eq!(data.len(), 20 + 10 + 8);
+    assert_eq!(&data[data.len() - 4..], b"EZMO");
+
+    let mut new_zmo = ZMO::new();
+    new_zmo.read(&mut Cursor::new(data)).unwrap();
+    assert_eq!(orig_zmo, new_zmo);
+}
+
+#[test]
+fn write_zmo_frame_events_tag() {
+    let mut orig_zmo = ZMO::new();
+    orig_zmo.identifier = String::from("ZMO0002");
+    orig_zmo.frame_events = vec![3];
+    orig_zmo.frame_events_tag = String::from("3ZMO");
+
+    let mut cursor = Cursor::new(Vec::new());
+    orig_zmo.write(&mut cursor).unwrap();
+    let data = cursor.into_inner();
+    assert_eq!(&data[data.len() - 4..], b"3ZMO");
+
+    let mut new_zmo = ZMO::new();
+    new_zmo.read(&mut Cursor::new(data.clone())).unwrap();
+    assert_eq!(new_zmo.frame_events_tag, "3ZMO");
+
+    let mut cursor = Cursor::new(Vec::new());
+    new_zmo.write(&mut cursor).unwrap();
+    assert_eq!(cursor.into_inner(), data);
+}